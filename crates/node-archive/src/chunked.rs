@@ -0,0 +1,197 @@
+//! Provides a function for downloading a remote archive into a cache file in several
+//! byte ranges at once, which makes better use of a high-latency link than a single
+//! request does.
+
+use std::cmp;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use reqwest::header::{ByteRangeSpec, Range};
+use reqwest::{Client, StatusCode};
+use failure;
+
+use download::{accepts_byte_ranges, content_length, headers_only};
+use {ClientOptions, HttpError, TruncatedError};
+
+/// The smallest chunk worth a request of its own.
+const MIN_CHUNK_SIZE: u64 = 1024 * 1024;
+
+#[derive(Fail, Debug)]
+#[fail(display = "the server ignored the byte range of a chunk (HTTP status {})", code)]
+struct RangeIgnoredError {
+    code: StatusCode,
+}
+
+/// What the thread downloading a chunk reports.
+enum Event {
+    /// The specified number of bytes were written to the cache file.
+    Progress(u64),
+    /// The chunk was downloaded, or failed to.
+    Done(Result<(), failure::Error>),
+}
+
+/// Splits an archive of `total` bytes into at most `chunks` byte ranges, none of them
+/// smaller than `MIN_CHUNK_SIZE` but the last, as the inclusive offsets of their first
+/// and last bytes.
+fn split(total: u64, chunks: u64) -> Vec<(u64, u64)> {
+    if total == 0 {
+        return vec![];
+    }
+    let chunks = cmp::max(1, cmp::min(chunks, total / MIN_CHUNK_SIZE));
+    let size = (total + chunks - 1) / chunks;
+    (0..chunks)
+        .map(|index| (index * size, cmp::min((index + 1) * size, total)))
+        .filter(|&(first, end)| first < end)
+        .map(|(first, end)| (first, end - 1))
+        .collect()
+}
+
+/// Downloads a byte range of the archive into the same range of the cache file,
+/// reporting each amount written to `progress`. The download stops early, without
+/// failing, once `cancelled` is set (by the failure of another chunk).
+fn fetch_chunk(
+    client: &Client,
+    url: &str,
+    cache_file: &Path,
+    (first, last): (u64, u64),
+    cancelled: &AtomicBool,
+    progress: &Fn(u64),
+) -> Result<(), failure::Error> {
+    let mut response = client.get(url)?
+        .header(Range::Bytes(vec![ByteRangeSpec::FromTo(first, last)]))
+        .send()?;
+    if !response.status().is_success() {
+        Err(HttpError { code: response.status() })?;
+    }
+    if response.status() != StatusCode::PartialContent {
+        Err(RangeIgnoredError { code: response.status() })?;
+    }
+
+    let mut cache = OpenOptions::new().write(true).open(cache_file)?;
+    cache.seek(SeekFrom::Start(first))?;
+    let expected = last - first + 1;
+    let mut written = 0;
+    let mut buffer = vec![0; 64 * 1024];
+    while written < expected {
+        if cancelled.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let read = response.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        let read = cmp::min(read as u64, expected - written) as usize;
+        cache.write_all(&buffer[..read])?;
+        written += read as u64;
+        progress(read as u64);
+    }
+    if written < expected {
+        return Err(TruncatedError { expected, actual: written }.into());
+    }
+    Ok(())
+}
+
+/// Downloads the archive at the specified URL into the cache file in up to `chunks`
+/// byte ranges requested at once, each from a thread of its own, calling `progress`
+/// with the number of bytes downloaded so far and the size of the archive as they
+/// arrive. Returns whether it did: nothing is downloaded if the server doesn't accept
+/// byte ranges for the archive, or the archive is too small to be worth splitting.
+/// If any chunk fails, the others are given up on, and the first failure is produced.
+pub(crate) fn fetch(
+    url: &str,
+    cache_file: &Path,
+    options: &ClientOptions,
+    chunks: u64,
+    progress: &mut FnMut(u64, u64),
+) -> Result<bool, failure::Error> {
+    let head = headers_only(url, options)?;
+    let total = content_length(&head)?;
+    if !accepts_byte_ranges(&head) || total < 2 * MIN_CHUNK_SIZE {
+        return Ok(false);
+    }
+
+    // the file is laid out at its full length, so that each chunk fills in its own part
+    File::create(cache_file)?.set_len(total)?;
+
+    let client = super::client(options)?;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    for range in split(total, chunks) {
+        let client = client.clone();
+        let url = url.to_string();
+        let cache_file = cache_file.to_path_buf();
+        let cancelled = cancelled.clone();
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let result = fetch_chunk(&client, &url, &cache_file, range, &cancelled, &|amount| {
+                let _ = progress_sender.send(Event::Progress(amount));
+            });
+            let _ = sender.send(Event::Done(result));
+        });
+    }
+    // the events end once every thread has dropped its sender
+    drop(sender);
+
+    let mut downloaded = 0;
+    let mut failure = None;
+    progress(downloaded, total);
+    for event in receiver {
+        match event {
+            Event::Progress(amount) => {
+                downloaded += amount;
+                progress(downloaded, total);
+            }
+            Event::Done(Ok(())) => {}
+            Event::Done(Err(error)) => {
+                cancelled.store(true, Ordering::SeqCst);
+                if failure.is_none() {
+                    failure = Some(error);
+                }
+            }
+        }
+    }
+
+    match failure {
+        Some(error) => Err(error),
+        None => Ok(true),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use chunked::{split, MIN_CHUNK_SIZE};
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(0, 4), vec![]);
+        assert_eq!(split(10, 4), vec![(0, 9)]);
+
+        let total = 4 * MIN_CHUNK_SIZE;
+        assert_eq!(
+            split(total, 4),
+            vec![
+                (0, MIN_CHUNK_SIZE - 1),
+                (MIN_CHUNK_SIZE, 2 * MIN_CHUNK_SIZE - 1),
+                (2 * MIN_CHUNK_SIZE, 3 * MIN_CHUNK_SIZE - 1),
+                (3 * MIN_CHUNK_SIZE, total - 1),
+            ]
+        );
+
+        // chunks are never smaller than the minimum, but the last
+        let total = 3 * MIN_CHUNK_SIZE + 7;
+        let ranges = split(total, 8);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].0, 0);
+        assert_eq!(ranges[2].1, total - 1);
+        for window in ranges.windows(2) {
+            assert_eq!(window[0].1 + 1, window[1].0);
+        }
+    }
+}
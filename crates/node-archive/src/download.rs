@@ -0,0 +1,114 @@
+//! Provides functions for downloading a remote archive into a cache file,
+//! resuming an earlier download that was interrupted when the server allows it.
+
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+use reqwest::header::{AcceptRanges, ByteRangeSpec, ContentLength, Range, RangeUnit};
+use reqwest::{Response, StatusCode};
+use failure;
+
+use ClientOptions;
+
+#[derive(Fail, Debug)]
+#[fail(display = "HTTP header '{}' not found", header)]
+struct MissingHeaderError {
+    header: String
+}
+
+/// Determines the length of an HTTP response's content in bytes, using
+/// the HTTP `"Content-Length"` header.
+pub(crate) fn content_length(response: &Response) -> Result<u64, failure::Error> {
+    Ok(match response.headers().get::<ContentLength>() {
+        Some(content_length) => **content_length,
+        None => {
+            return Err(MissingHeaderError { header: String::from("Content-Length") }.into());
+        }
+    })
+}
+
+/// Determines whether the server accepts byte range requests for a resource,
+/// using the HTTP `"Accept-Ranges"` header of a response.
+pub(crate) fn accepts_byte_ranges(response: &Response) -> bool {
+    response.headers().get::<AcceptRanges>()
+        .map(|v| v.iter().any(|unit| *unit == RangeUnit::Bytes))
+        .unwrap_or(false)
+}
+
+/// Fetches just the headers of a URL.
+pub(crate) fn headers_only(url: &str, options: &ClientOptions) -> Result<Response, failure::Error> {
+    let client = super::client(options)?;
+    let response = client.head(url)?.send()?;
+    if !response.status().is_success() {
+        Err(super::HttpError { code: response.status() })?;
+    }
+    Ok(response)
+}
+
+/// An in-progress download of a remote archive into a cache file.
+pub(crate) struct Download {
+    /// The number of bytes at the start of the cache file that were kept from
+    /// an earlier, interrupted download.
+    pub(crate) resumed: u64,
+    /// The response streaming the rest of the archive.
+    pub(crate) response: Response,
+    /// The cache file, positioned to write the rest of the archive.
+    pub(crate) cache: File,
+}
+
+/// Returns the length of a partial download left in the cache file, or zero
+/// if there is nothing worth resuming.
+fn partial_length(cache_file: &Path, total: u64) -> u64 {
+    match cache_file.metadata() {
+        Ok(ref metadata) if metadata.is_file() && metadata.len() < total => metadata.len(),
+        _ => 0
+    }
+}
+
+/// Starts downloading the archive at the specified URL into the cache file. If the
+/// cache file holds the beginning of an earlier download and the server accepts byte
+/// range requests, only the rest of the archive is requested; otherwise, or if the
+/// server ignores the range, the archive is downloaded from scratch.
+pub(crate) fn start(url: &str, cache_file: &Path, options: &ClientOptions, total: u64, ranges: bool) -> Result<Download, failure::Error> {
+    let client = super::client(options)?;
+    let resume_from = if ranges { partial_length(cache_file, total) } else { 0 };
+
+    if resume_from > 0 {
+        let response = client.get(url)?
+            .header(Range::Bytes(vec![ByteRangeSpec::AllFrom(resume_from)]))
+            .send()?;
+
+        if response.status() == StatusCode::PartialContent {
+            let cache = OpenOptions::new().append(true).open(cache_file)?;
+            return Ok(Download { resumed: resume_from, response, cache });
+        }
+    }
+
+    let response = client.get(url)?.send()?;
+
+    if !response.status().is_success() {
+        Err(super::HttpError { code: response.status() })?;
+    }
+
+    Ok(Download { resumed: 0, response, cache: File::create(cache_file)? })
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use download::partial_length;
+    use std::io::Write;
+    use tempfile;
+
+    #[test]
+    fn test_partial_length() {
+        let dir = tempfile::tempdir().expect("Couldn't create temp dir");
+        let cache_file = dir.path().join("archive.tar.gz");
+        assert_eq!(partial_length(&cache_file, 100), 0);
+
+        let mut file = ::std::fs::File::create(&cache_file).expect("Couldn't create cache file");
+        file.write_all(&[0; 40]).expect("Couldn't write cache file");
+        assert_eq!(partial_length(&cache_file, 100), 40);
+        assert_eq!(partial_length(&cache_file, 40), 0);
+    }
+}
@@ -1,76 +1,151 @@
 //! This crate provides types for fetching and unpacking Node distribution
-//! archives, which is a tarball for Unixes and a zipfile for Windows.
+//! archives, which is a tarball (gzip- or xz-compressed) for Unixes and a zipfile
+//! for Windows.
 //!
-//! These docs show the top-level exports of this crate as re-exported of
-//! the `tarball` module (due to limitations of rustdoc); the top-level
-//! exports are re-exported from `tarball` on Unix operating systems and
-//! from `zip` on Windows operating systems.
+//! Both formats are supported on every operating system, since the other tools
+//! Notion fetches (such as Yarn and npm) are distributed as tarballs everywhere.
 
 #![cfg_attr(feature = "universal-docs", feature(doc_cfg))]
 
 #[macro_use]
 extern crate cfg_if;
 
-cfg_if! {
-    if #[cfg(feature = "universal-docs")] {
-        extern crate tar;
-        extern crate flate2;
-
-        #[doc(cfg(unix))]
-        mod tarball;
-
-        extern crate zip as zip_rs;
-        extern crate verbatim;
+#[macro_use]
+extern crate lazy_static;
 
-        #[doc(cfg(windows))]
-        mod zip;
-    } else if #[cfg(unix)] {
-        extern crate tar;
-        extern crate flate2;
+extern crate flate2;
+extern crate tar;
+extern crate xz2;
+extern crate zip as zip_rs;
 
-        mod tarball;
-    } else if #[cfg(windows)] {
-        extern crate zip as zip_rs;
+cfg_if! {
+    if #[cfg(any(feature = "universal-docs", windows))] {
         extern crate verbatim;
-
-        mod zip;
-    } else {
-        compile_error!("Unsupported OS (expected 'unix' or 'windows').");
     }
 }
 
+mod tarball;
+mod unpacked;
+mod zip;
+
 extern crate progress_read;
 extern crate reqwest;
 extern crate tee;
 
+mod chunked;
+mod download;
+
 extern crate failure;
 #[macro_use]
 extern crate failure_derive;
 
+#[cfg(test)]
+extern crate tempfile;
+
 #[derive(Fail, Debug)]
 #[fail(display = "HTTP failure ({})", code)]
 pub(crate) struct HttpError {
     code: ::reqwest::StatusCode,
 }
 
-cfg_if! {
-    if #[cfg(unix)] {
-        pub use tarball::Tarball;
-    } else if #[cfg(windows)] {
-        pub use zip::Zip;
-    } else {
-        compile_error!("Unsupported OS (expected 'unix' or 'windows').");
+/// Thrown when an archive ended before the length the server announced for it, which
+/// means the download was cut short somewhere along the way.
+#[derive(Fail, Debug)]
+#[fail(display = "the download ended after {} of its {} bytes", actual, expected)]
+pub struct TruncatedError {
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Determines whether a failed fetch is likely to succeed if it is tried again:
+/// that is, whether it failed due to a timeout, a dropped connection, or an error
+/// on the server's end rather than a problem with the request itself.
+pub fn is_transient(error: &failure::Error) -> bool {
+    if error.downcast_ref::<TruncatedError>().is_some() {
+        return true;
+    }
+    if let Some(error) = error.downcast_ref::<HttpError>() {
+        return error.code.is_server_error();
+    }
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return !(error.is_serialization() || error.is_redirect() || error.is_client_error());
+    }
+    if let Some(error) = error.downcast_ref::<std::io::Error>() {
+        return match error.kind() {
+            std::io::ErrorKind::TimedOut
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::UnexpectedEof
+            | std::io::ErrorKind::Interrupted => true,
+            _ => false,
+        };
+    }
+    false
+}
+
+/// The settings of the HTTP client that an archive is fetched with.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ClientOptions {
+    /// The proxy to send every request through, if any.
+    pub proxy: Option<String>,
+    /// How long a connection, read, or write may take before the request is given
+    /// up on, if ever.
+    pub timeout: Option<Duration>,
+}
+
+lazy_static! {
+    /// The HTTP clients built so far, one for each set of options, which are shared by
+    /// every fetch so that their connections are kept alive and reused.
+    static ref CLIENTS: Mutex<HashMap<ClientOptions, reqwest::Client>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the shared HTTP client with the specified options, building it the first
+/// time it is asked for.
+pub(crate) fn client(options: &ClientOptions) -> Result<reqwest::Client, failure::Error> {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(client) = clients.get(options) {
+        return Ok(client.clone());
+    }
+
+    let mut builder = reqwest::Client::builder()?;
+    if let Some(ref proxy) = options.proxy {
+        builder.proxy(reqwest::Proxy::all(&proxy[..])?);
+    }
+    if let Some(timeout) = options.timeout {
+        builder.timeout(timeout);
     }
+    let client = builder.build()?;
+    clients.insert(options.clone(), client.clone());
+    Ok(client)
 }
 
+pub use tarball::Tarball;
+pub use zip::Zip;
+
+use std::collections::HashMap;
 use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
 
-pub trait Archive {
+/// A Node distribution archive. Archives are `Send`, so that several of them can be
+/// fetched and unpacked at once from separate threads.
+pub trait Archive: Send {
     fn compressed_size(&self) -> u64;
     fn uncompressed_size(&self) -> Option<u64>;
 
-    /// Unpacks the zip archive to the specified destination folder.
+    /// Unpacks the archive to the specified destination folder, reporting the
+    /// number of uncompressed bytes processed to the `progress` callback, so that
+    /// the reported amounts add up to `uncompressed_size()`. An archive whose
+    /// uncompressed size isn't known ahead of time (such as an xz-compressed
+    /// tarball) reports compressed bytes instead, adding up to `compressed_size()`.
+    ///
+    /// Executable bits and symbolic links are kept on Unix, and each link is replaced
+    /// with a copy of its target on Windows. Unpacking fails if the unpacked tree
+    /// doesn't have them afterwards.
     fn unpack(
         self: Box<Self>,
         dest: &Path,
@@ -78,35 +153,79 @@ pub trait Archive {
     ) -> Result<(), failure::Error>;
 }
 
-cfg_if! {
-    if #[cfg(feature = "universal-docs")] {
-        /// Load a cached Node archive from the specified file.
-        pub fn load(source: File) -> Result<Box<Archive>, failure::Error> {
-            unimplemented!()
-        }
-
-        /// Fetch a remote Node archive from the given URL and cache its results
-        /// at the given file path.
-        pub fn fetch(url: &str, cache_file: &Path) -> Result<Box<Archive>, failure::Error> {
-            unimplemented!()
-        }
-    } else if #[cfg(unix)] {
-        pub fn load(source: File) -> Result<Box<Archive>, failure::Error> {
-            Ok(Box::new(Tarball::load(source)?))
-        }
-
-        pub fn fetch(url: &str, cache_file: &Path) -> Result<Box<Archive>, failure::Error> {
-            Ok(Box::new(Tarball::fetch(url, cache_file)?))
-        }
-    } else if #[cfg(windows)] {
-        pub fn load(source: File) -> Result<Box<Archive>, failure::Error> {
-            Ok(Box::new(Zip::load(source)?))
-        }
-
-        pub fn fetch(url: &str, cache_file: &Path) -> Result<Box<Archive>, failure::Error> {
-            Ok(Box::new(Zip::fetch(url, cache_file)?))
-        }
+/// The bytes that a zip file begins with.
+const ZIP_MAGIC: &'static [u8] = b"PK\x03\x04";
+
+/// Determines whether the specified file is a zip file rather than a tarball.
+fn is_zip(source: &mut File) -> Result<bool, failure::Error> {
+    let mut magic = [0; 4];
+    let read = source.read(&mut magic)?;
+    source.seek(SeekFrom::Start(0))?;
+    Ok(&magic[..read] == ZIP_MAGIC)
+}
+
+/// Load a cached Node archive from the specified file, which may be a tarball or
+/// a zip file.
+pub fn load(mut source: File) -> Result<Box<Archive>, failure::Error> {
+    if is_zip(&mut source)? {
+        Ok(Box::new(Zip::load(source)?))
+    } else {
+        Ok(Box::new(Tarball::load(source)?))
+    }
+}
+
+/// Fetch a remote Node archive from the given URL, with the client configured by the
+/// given options, and cache its results at the given file path. The archive is taken to be a zip
+/// file if the cache file has a `.zip` extension, and a tarball otherwise.
+pub fn fetch(url: &str, cache_file: &Path, options: &ClientOptions) -> Result<Box<Archive>, failure::Error> {
+    if cache_file.extension().map_or(false, |extension| extension == "zip") {
+        Ok(Box::new(Zip::fetch(url, cache_file, options)?))
     } else {
-        compile_error!("Unsupported OS (expected 'unix' or 'windows').");
+        Ok(Box::new(Tarball::fetch(url, cache_file, options)?))
+    }
+}
+
+/// Fetch a remote Node archive from the given URL into the given cache file in up to
+/// `chunks` byte ranges requested at once, with the client configured by the given
+/// options, calling `progress` with the number of bytes downloaded so far and the size
+/// of the archive as they arrive. Unlike `fetch`, the archive is only produced once it
+/// is complete, so it can be checksummed before it is unpacked. Produces `None`,
+/// without downloading anything, if the server doesn't accept byte ranges for the
+/// archive or it is too small to be worth splitting.
+pub fn fetch_chunked(url: &str, cache_file: &Path, options: &ClientOptions, chunks: u64, progress: &mut FnMut(u64, u64)) -> Result<Option<Box<Archive>>, failure::Error> {
+    if !chunked::fetch(url, cache_file, options, chunks, progress)? {
+        return Ok(None);
+    }
+    load(File::open(cache_file)?).map(Some)
+}
+
+/// Stream a remote Node tarball from the given URL, with the client configured by
+/// the given options, without caching it. Every byte downloaded is copied to the
+/// given sink (to checksum the archive as it streams, say). The compression format
+/// is determined by the extension of the given file name. Zip files can't be
+/// streamed, since they are read from their end.
+pub fn stream(url: &str, file_name: &Path, options: &ClientOptions, sink: Box<Write + Send>) -> Result<Box<Archive>, failure::Error> {
+    Ok(Box::new(Tarball::stream(url, file_name, options, sink)?))
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::is_zip;
+    use std::fs::File;
+    use std::path::PathBuf;
+
+    fn fixture_file(fixture_dir: &str, file: &str) -> File {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures");
+        path.push(fixture_dir);
+        path.push(file);
+        File::open(path).expect("Couldn't open test file")
+    }
+
+    #[test]
+    fn test_is_zip() {
+        assert!(is_zip(&mut fixture_file("zips", "test-file.zip")).unwrap());
+        assert!(!is_zip(&mut fixture_file("tarballs", "test-file.tar.gz")).unwrap());
     }
 }
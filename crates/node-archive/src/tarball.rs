@@ -1,25 +1,63 @@
 //! Provides types and functions for fetching and unpacking a Node installation
-//! tarball in Unix operating systems.
+//! tarball, the format Node is distributed in for Unix operating systems.
 
-use std::io::{Read, Seek, SeekFrom};
+use std::cell::Cell;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-use std::fs::File;
+use std::fs::{create_dir_all, File};
 
 use flate2::read::GzDecoder;
-use reqwest::header::{AcceptRanges, ContentLength, Range, RangeUnit, ByteRangeSpec};
-use reqwest::Response;
-use reqwest;
+use reqwest::header::{Range, ByteRangeSpec};
 use tar;
 use tee::TeeReader;
 use progress_read::ProgressRead;
+use xz2::read::XzDecoder;
+#[cfg(windows)]
+use verbatim::PathExt;
 use failure;
 
-use super::Archive;
+use download::{self, accepts_byte_ranges, content_length, headers_only};
+use unpacked::{entry_path, Unpacked};
+use super::{Archive, ClientOptions, TruncatedError};
+
+/// The bytes that an xz file begins with.
+const XZ_MAGIC: &'static [u8] = b"\xFD7zXZ\x00";
+
+/// The compression format of a tarball.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+}
+
+impl Compression {
+    /// Determines the compression format of a tarball from its file name: `.xz`
+    /// files are xz-compressed, and anything else is taken to be gzipped.
+    pub fn for_file(path: &Path) -> Compression {
+        match path.extension() {
+            Some(extension) if extension == "xz" => Compression::Xz,
+            _ => Compression::Gzip,
+        }
+    }
+}
+
+/// Determines the compression format of the specified tarball on disk.
+fn load_compression(file: &mut File) -> Result<Compression, failure::Error> {
+    let mut magic = [0; 6];
+    let read = file.read(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(if &magic[..read] == XZ_MAGIC {
+        Compression::Xz
+    } else {
+        Compression::Gzip
+    })
+}
 
 /// A Node installation tarball.
 pub struct Tarball<S: Read> {
+    compression: Compression,
     compressed_size: u64,
-    uncompressed_size: u64,
+    uncompressed_size: Option<u64>,
     data: S
 }
 
@@ -27,9 +65,15 @@ impl Tarball<File> {
 
     /// Loads a cached Node tarball from the specified file.
     pub fn load(mut source: File) -> Result<Self, failure::Error> {
-        let uncompressed_size = load_uncompressed_size(&mut source)?;
+        let compression = load_compression(&mut source)?;
+        // Only gzip records the uncompressed size in a fixed place.
+        let uncompressed_size = match compression {
+            Compression::Gzip => Some(load_uncompressed_size(&mut source)?),
+            Compression::Xz => None,
+        };
         let compressed_size = source.metadata()?.len();
         Ok(Tarball {
+            compression,
             uncompressed_size,
             compressed_size,
             data: source
@@ -38,68 +82,159 @@ impl Tarball<File> {
 
 }
 
-#[derive(Fail, Debug)]
-#[fail(display = "HTTP header '{}' not found", header)]
-struct MissingHeaderError {
-    header: String
-}
-
-/// Determines the length of an HTTP response's content in bytes, using
-/// the HTTP `"Content-Length"` header.
-fn content_length(response: &Response) -> Result<u64, failure::Error> {
-    Ok(match response.headers().get::<ContentLength>() {
-        Some(content_length) => **content_length,
-        None => {
-            return Err(MissingHeaderError { header: String::from("Content-Length") }.into());
-        }
-    })
-}
 
-impl Tarball<TeeReader<reqwest::Response, File>> {
+impl Tarball<Box<Read + Send>> {
+
+    /// Initiate fetching of a Node tarball from the given URL, with the
+    /// given client options, returning a tarball that can be streamed (and that
+    /// tees its data to a cache file as it streams). If the cache file holds
+    /// the beginning of an interrupted download, only the rest is fetched. The
+    /// compression format is determined by the cache file's extension.
+    pub fn fetch(url: &str, cache_file: &Path, options: &ClientOptions) -> Result<Self, failure::Error> {
+        let head = headers_only(url, options)?;
+        let compressed_size = content_length(&head)?;
+        let compression = Compression::for_file(cache_file);
+
+        // Without byte ranges, neither the uncompressed size can be looked up
+        // ahead of time nor can an interrupted download be resumed.
+        let ranges = accepts_byte_ranges(&head);
+        let uncompressed_size = if ranges && compression == Compression::Gzip {
+            Some(unpack_isize(fetch_isize(url, compressed_size, options)?))
+        } else {
+            None
+        };
+
+        let download = download::start(url, cache_file, options, compressed_size, ranges)?;
+        let rest = TeeReader::new(download.response, download.cache);
+        let data: Box<Read + Send> = if download.resumed > 0 {
+            Box::new(File::open(cache_file)?.take(download.resumed).chain(rest))
+        } else {
+            Box::new(rest)
+        };
 
-    /// Initiate fetching of a Node tarball from the given URL, returning
-    /// a tarball that can be streamed (and that tees its data to a cache
-    /// file as it streams).
-    pub fn fetch(url: &str, cache_file: &Path) -> Result<Self, failure::Error> {
-        let uncompressed_size = fetch_uncompressed_size(url)?;
-        let response = reqwest::get(url)?;
+        Ok(Tarball {
+            compression,
+            uncompressed_size,
+            compressed_size,
+            data
+        })
+    }
 
+    /// Initiate streaming a Node tarball from the given URL, with the client
+    /// configured by the given options, without caching it: its data is only
+    /// copied to the given sink as it streams. The compression format is
+    /// determined by the extension of the given file name.
+    pub fn stream(url: &str, file_name: &Path, options: &ClientOptions, sink: Box<Write + Send>) -> Result<Self, failure::Error> {
+        let head = headers_only(url, options)?;
+        let compressed_size = content_length(&head)?;
+        let compression = Compression::for_file(file_name);
+
+        let uncompressed_size = if accepts_byte_ranges(&head) && compression == Compression::Gzip {
+            Some(unpack_isize(fetch_isize(url, compressed_size, options)?))
+        } else {
+            None
+        };
+
+        let response = super::client(options)?.get(url)?.send()?;
         if !response.status().is_success() {
             Err(super::HttpError { code: response.status() })?;
         }
 
-        let compressed_size = content_length(&response)?;
-        let file = File::create(cache_file)?;
-        let data = TeeReader::new(response, file);
-
         Ok(Tarball {
+            compression,
             uncompressed_size,
             compressed_size,
-            data
+            data: Box::new(TeeReader::new(response, sink))
         })
     }
 
 }
 
-impl<S: Read> Archive for Tarball<S> {
+/// Counts the bytes read through it.
+struct CountingRead<'a, R: Read> {
+    inner: R,
+    count: &'a Cell<u64>,
+}
+
+impl<'a, R: Read> Read for CountingRead<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+impl<S: Read + Send> Archive for Tarball<S> {
     fn compressed_size(&self) -> u64 { self.compressed_size }
-    fn uncompressed_size(&self) -> Option<u64> { Some(self.uncompressed_size) }
+    fn uncompressed_size(&self) -> Option<u64> { self.uncompressed_size }
     fn unpack(self: Box<Self>, dest: &Path, progress: &mut FnMut(&(), usize)) -> Result<(), failure::Error> {
-        let decoded = GzDecoder::new(self.data);
-        let mut tarball = tar::Archive::new(ProgressRead::new(decoded, (), progress));
-        tarball.unpack(dest)?;
+        let Tarball { compression, compressed_size, mut data, .. } = *self;
+        let read = Cell::new(0);
+        {
+            let counted = CountingRead { inner: &mut data, count: &read };
+            match compression {
+                Compression::Gzip => {
+                    let decoded = GzDecoder::new(counted);
+                    unpack_tar(ProgressRead::new(decoded, (), progress), dest)?;
+                }
+                // The uncompressed size of an xz file isn't known ahead of time, so
+                // progress is measured in compressed bytes instead.
+                Compression::Xz => {
+                    let decoded = XzDecoder::new(ProgressRead::new(counted, (), progress));
+                    unpack_tar(decoded, dest)?;
+                }
+            }
+        }
+
+        // A download cut short can still end in what looks like the end of a tarball,
+        // so check that all of it arrived, reading whatever the decoder left (which
+        // also completes the cache file).
+        io::copy(&mut CountingRead { inner: &mut data, count: &read }, &mut io::sink())?;
+        if read.get() < compressed_size {
+            return Err(TruncatedError { expected: compressed_size, actual: read.get() }.into());
+        }
         Ok(())
     }
 }
 
-/// Fetches just the headers of a URL.
-fn headers_only(url: &str) -> Result<Response, failure::Error> {
-    let client = reqwest::Client::new()?;
-    let response = client.head(url)?.send()?;
-    if !response.status().is_success() {
-        Err(super::HttpError { code: response.status() })?;
+/// Unpacks an uncompressed tar stream to the specified destination folder.
+fn unpack_tar<R: Read>(data: R, dest: &Path) -> Result<(), failure::Error> {
+    // Use a verbatim path to avoid the legacy Windows 260 byte path limit.
+    #[cfg(windows)]
+    let dest: &Path = &dest.to_verbatim();
+
+    create_dir_all(dest)?;
+    let mut tarball = tar::Archive::new(data);
+    let mut unpacked = Unpacked::default();
+    for entry in tarball.entries()? {
+        let mut entry = entry?;
+        let path = match entry_path(&entry.path()?) {
+            Some(path) => path,
+            None => continue,
+        };
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() {
+            if let Some(target) = entry.link_name()? {
+                unpacked.symlink(path, target.into_owned());
+            }
+            // Windows gets a copy of the link's target instead, once it is unpacked.
+            if cfg!(windows) {
+                continue;
+            }
+        } else if entry_type.is_file() && entry.header().mode()? & 0o111 != 0 {
+            unpacked.executable(path);
+        }
+        entry.unpack_in(dest)?;
     }
-    Ok(response)
+    unpacked.create_links(dest)?;
+    unpacked.validate(dest)?;
+
+    // The tar format ends with zero-filled padding blocks that `unpack` stops
+    // short of reading. They still count towards the reported progress, so drain
+    // them to make it add up to the archive's full size.
+    let mut rest = tarball.into_inner();
+    io::copy(&mut rest, &mut io::sink())?;
+    Ok(())
 }
 
 // From http://www.gzip.org/zlib/rfc-gzip.html#member-format
@@ -133,8 +268,8 @@ struct UnexpectedContentLengthError {
 /// of a gzip file from a URL. This makes two round-trips to the server but avoids
 /// downloading the entire gzip file. For very small files it's unlikely to be
 /// more efficient than simply downloading the entire file up front.
-fn fetch_isize(url: &str, len: u64) -> Result<[u8; 4], failure::Error> {
-    let client = reqwest::Client::new()?;
+fn fetch_isize(url: &str, len: u64, options: &ClientOptions) -> Result<[u8; 4], failure::Error> {
+    let client = super::client(options)?;
     let mut response = client.get(url)?
         .header(Range::Bytes(
             vec![ByteRangeSpec::FromTo(len - 4, len - 1)]
@@ -166,29 +301,6 @@ fn load_isize(file: &mut File) -> Result<[u8; 4], failure::Error> {
     Ok(buf)
 }
 
-#[derive(Fail, Debug)]
-#[fail(display = "HTTP server does not accept byte range requests")]
-struct ByteRangesNotAcceptedError;
-
-/// Determines the uncompressed size of a gzip file hosted at the specified
-/// URL by fetching just the metadata associated with the file. This makes
-/// two round-trips to the server, so it is only more efficient than simply
-/// downloading the file if the file is large enough that downloading it is
-/// slower than the extra round trips.
-fn fetch_uncompressed_size(url: &str) -> Result<u64, failure::Error> {
-    let response = headers_only(url)?;
-
-    if !response.headers().get::<AcceptRanges>()
-        .map(|v| v.iter().any(|unit| *unit == RangeUnit::Bytes))
-        .unwrap_or(false) {
-        Err(ByteRangesNotAcceptedError)?;
-    }
-
-    let len = content_length(&response)?;
-    let packed = fetch_isize(url, len)?;
-    Ok(unpack_isize(packed))
-}
-
 /// Determines the uncompressed size of the specified gzip file on disk.
 fn load_uncompressed_size(file: &mut File) -> Result<u64, failure::Error> {
     let packed = load_isize(file)?;
@@ -199,9 +311,12 @@ fn load_uncompressed_size(file: &mut File) -> Result<u64, failure::Error> {
 #[cfg(test)]
 pub mod tests {
 
-    use tarball::Tarball;
+    use tarball::{Compression, Tarball};
+    use TruncatedError;
     use std::path::PathBuf;
     use std::fs::File;
+    use tempfile;
+    use Archive;
 
     fn fixture_path(fixture_dir: &str) -> PathBuf {
         let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -217,7 +332,61 @@ pub mod tests {
         let test_file = File::open(test_file_path).expect("Couldn't open test file");
         let tarball = Tarball::load(test_file).expect("Failed to load tarball");
 
-        assert_eq!(tarball.uncompressed_size, 10240);
+        assert_eq!(tarball.uncompressed_size, Some(10240));
         assert_eq!(tarball.compressed_size, 402);
     }
+
+    #[test]
+    fn test_unpack_progress_matches_uncompressed_size() {
+        let mut test_file_path = fixture_path("tarballs");
+        test_file_path.push("test-file.tar.gz");
+        let test_file = File::open(test_file_path).expect("Couldn't open test file");
+        let tarball = Box::new(Tarball::load(test_file).expect("Failed to load tarball"));
+        let expected = tarball.uncompressed_size().unwrap();
+
+        let dest = tempfile::tempdir().expect("Couldn't create temp dir");
+        let mut total = 0;
+        tarball.unpack(dest.path(), &mut |_, read| {
+            total += read as u64;
+        }).expect("Failed to unpack tarball");
+
+        assert_eq!(total, expected);
+    }
+    #[test]
+    fn test_load_xz() {
+        let mut test_file_path = fixture_path("tarballs");
+        test_file_path.push("test-file.tar.xz");
+        let test_file = File::open(test_file_path).expect("Couldn't open test file");
+        let tarball = Box::new(Tarball::load(test_file).expect("Failed to load tarball"));
+
+        assert_eq!(tarball.compression, Compression::Xz);
+        assert_eq!(tarball.uncompressed_size, None);
+        let expected = tarball.compressed_size();
+
+        let dest = tempfile::tempdir().expect("Couldn't create temp dir");
+        let mut total = 0;
+        tarball.unpack(dest.path(), &mut |_, read| {
+            total += read as u64;
+        }).expect("Failed to unpack tarball");
+
+        assert_eq!(total, expected);
+    }
+
+    #[test]
+    fn test_unpack_detects_truncation() {
+        let mut test_file_path = fixture_path("tarballs");
+        test_file_path.push("test-file.tar.gz");
+        let test_file = File::open(test_file_path).expect("Couldn't open test file");
+        let mut tarball = Tarball::load(test_file).expect("Failed to load tarball");
+        // As if the server had announced more than arrived.
+        tarball.compressed_size += 100;
+
+        let dest = tempfile::tempdir().expect("Couldn't create temp dir");
+        let error = Box::new(tarball)
+            .unpack(dest.path(), &mut |_, _| {})
+            .expect_err("Unpacked a truncated tarball");
+        let truncated = error.downcast_ref::<TruncatedError>().expect("Wrong error");
+        assert_eq!(truncated.expected, 502);
+        assert_eq!(truncated.actual, 402);
+    }
 }
@@ -0,0 +1,220 @@
+//! Records the executable files and symbolic links of an archive as it is unpacked,
+//! and checks afterwards that the unpacked tree kept them.
+//!
+//! A Node installation depends on both: `bin/node` has to be executable, and
+//! `bin/npm` is a link to `../lib/node_modules/npm/bin/npm-cli.js`. Unix keeps them as
+//! they are. Creating a symbolic link on Windows takes elevated privileges, so there
+//! each link is replaced with a copy of its target instead, and executable bits (which
+//! Windows doesn't have) aren't checked.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use failure;
+
+/// Thrown when an unpacked file lost its executable bits, or a symbolic link didn't
+/// survive the unpack.
+#[derive(Fail, Debug)]
+#[fail(display = "{} was not unpacked faithfully: {}", path, problem)]
+pub(crate) struct UnpackValidationError {
+    path: String,
+    problem: String,
+}
+
+impl UnpackValidationError {
+    fn new(path: &Path, problem: &str) -> Self {
+        UnpackValidationError {
+            path: path.to_string_lossy().to_string(),
+            problem: problem.to_string(),
+        }
+    }
+}
+
+/// Converts the path of an archive entry, which may use `/` separators, to a relative
+/// path with the platform's own separators (which verbatim paths on Windows require).
+/// Returns `None` for a path that would escape the destination directory.
+pub(crate) fn entry_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// The executable files and symbolic links an archive unpacked, relative to its
+/// destination directory.
+#[derive(Default)]
+pub(crate) struct Unpacked {
+    executables: Vec<PathBuf>,
+    symlinks: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Unpacked {
+    pub(crate) fn executable(&mut self, path: PathBuf) {
+        self.executables.push(path);
+    }
+
+    pub(crate) fn symlink(&mut self, path: PathBuf, target: PathBuf) {
+        self.symlinks.push((path, target));
+    }
+
+    /// Creates the recorded symbolic links. Each is replaced with a copy of its target
+    /// on Windows; since a link can point to another link, the copies are made in as
+    /// many passes as it takes for every target to exist.
+    #[cfg(windows)]
+    pub(crate) fn create_links(&self, dest: &Path) -> Result<(), failure::Error> {
+        let mut pending: Vec<&(PathBuf, PathBuf)> = self.symlinks.iter().collect();
+        while !pending.is_empty() {
+            let before = pending.len();
+            let mut rest = vec![];
+            for link in pending {
+                let &(ref path, ref target) = link;
+                let file = dest.join(path);
+                let source = match file.parent() {
+                    Some(dir) => dir.join(target),
+                    None => continue,
+                };
+                if !source.exists() {
+                    rest.push(link);
+                    continue;
+                }
+                if let Some(dir) = file.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                copy_tree(&source, &file)?;
+            }
+            // Whatever is left points to nothing the archive unpacked; validation
+            // reports it.
+            if rest.len() == before {
+                break;
+            }
+            pending = rest;
+        }
+        Ok(())
+    }
+
+    /// Creates the recorded symbolic links, which Unix keeps as they are.
+    #[cfg(not(windows))]
+    pub(crate) fn create_links(&self, dest: &Path) -> Result<(), failure::Error> {
+        use std::os::unix::fs::symlink;
+
+        for &(ref path, ref target) in &self.symlinks {
+            let file = dest.join(path);
+            if file.symlink_metadata().is_ok() {
+                continue;
+            }
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            symlink(target, &file)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that every executable file is still executable, and that every symbolic
+    /// link exists and points where the archive says (or, on Windows, was copied).
+    pub(crate) fn validate(&self, dest: &Path) -> Result<(), failure::Error> {
+        for path in &self.executables {
+            let file = dest.join(path);
+            if !is_executable(&file)? {
+                return Err(UnpackValidationError::new(&file, "it lost its executable bits").into());
+            }
+        }
+        for &(ref path, ref target) in &self.symlinks {
+            let file = dest.join(path);
+            validate_link(&file, target)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(file: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::metadata(file)?.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(file: &Path) -> io::Result<bool> {
+    Ok(file.is_file())
+}
+
+#[cfg(unix)]
+fn validate_link(file: &Path, target: &Path) -> Result<(), failure::Error> {
+    match fs::read_link(file) {
+        Ok(ref actual) if actual == target => Ok(()),
+        Ok(_) => Err(UnpackValidationError::new(file, "it links to the wrong target").into()),
+        Err(_) => Err(UnpackValidationError::new(file, "its symbolic link is missing").into()),
+    }
+}
+
+#[cfg(not(unix))]
+fn validate_link(file: &Path, _: &Path) -> Result<(), failure::Error> {
+    if !file.exists() {
+        return Err(UnpackValidationError::new(file, "the file it links to is missing").into());
+    }
+    Ok(())
+}
+
+/// Copies a file, or a directory and everything in it.
+#[cfg(windows)]
+fn copy_tree(source: &Path, dest: &Path) -> io::Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(dest)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, dest)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{entry_path, Unpacked};
+    use std::fs::{create_dir_all, File};
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_entry_path() {
+        assert_eq!(
+            entry_path(Path::new("./node-v10.15.3/bin/node")),
+            Some(["node-v10.15.3", "bin", "node"].iter().collect::<PathBuf>())
+        );
+        assert_eq!(entry_path(Path::new("../escape")), None);
+        assert_eq!(entry_path(Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_validate() {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let dest = dir.path();
+        create_dir_all(dest.join("bin")).unwrap();
+        create_dir_all(dest.join("lib")).unwrap();
+        File::create(dest.join("lib").join("npm-cli.js")).unwrap();
+        File::create(dest.join("bin").join("node")).unwrap();
+        set_permissions(dest.join("bin").join("node"), Permissions::from_mode(0o755)).unwrap();
+
+        let mut unpacked = Unpacked::default();
+        unpacked.executable(PathBuf::from("bin/node"));
+        unpacked.symlink(PathBuf::from("bin/npm"), PathBuf::from("../lib/npm-cli.js"));
+        unpacked.create_links(dest).unwrap();
+        assert!(unpacked.validate(dest).is_ok());
+
+        set_permissions(dest.join("bin").join("node"), Permissions::from_mode(0o644)).unwrap();
+        assert!(unpacked.validate(dest).is_err());
+    }
+}
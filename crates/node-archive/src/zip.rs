@@ -1,91 +1,153 @@
 //! Provides types and functions for fetching and unpacking a Node installation
-//! zip file in Windows operating systems.
+//! zip file, the format Node is distributed in for Windows operating systems.
 
-use std::io::{self, Read, Seek, copy};
-use std::path::Path;
+use std::io::{self, Read, Seek, SeekFrom, copy};
+use std::path::{Path, PathBuf};
 use std::fs::{File, create_dir_all};
 
-use reqwest;
 use progress_read::ProgressRead;
 use zip_rs::ZipArchive;
+#[cfg(windows)]
 use verbatim::PathExt;
 
 use failure;
 
-use super::Archive;
+use download::{self, accepts_byte_ranges, content_length, headers_only};
+use unpacked::Unpacked;
+use super::{Archive, ClientOptions, TruncatedError};
 
 pub struct Zip<S: Read + Seek> {
     compressed_size: u64,
+    uncompressed_size: u64,
     data: S
 }
 
 impl Zip<File> {
 
     /// Loads a cached Node zip archive from the specified file.
-    pub fn load(source: File) -> io::Result<Self> {
+    pub fn load(source: File) -> Result<Self, failure::Error> {
         let compressed_size = source.metadata()?.len();
+        let uncompressed_size = load_uncompressed_size(&source)?;
 
         Ok(Zip {
             compressed_size,
+            uncompressed_size,
             data: source
         })
     }
 
-    /// Initiate fetching of a Node zip archive from the given URL, returning
-    /// a `Remote` data source.
-    pub fn fetch(url: &str, cache_file: &Path) -> Result<Self, failure::Error> {
-        let mut response = reqwest::get(url)?;
+    /// Initiate fetching of a Node zip archive from the given URL, with the
+    /// given client options, returning a `Remote` data source. If the cache file
+    /// holds the beginning of an interrupted download, only the rest is fetched.
+    pub fn fetch(url: &str, cache_file: &Path, options: &ClientOptions) -> Result<Self, failure::Error> {
+        let head = headers_only(url, options)?;
+        let total = content_length(&head)?;
+        let ranges = accepts_byte_ranges(&head);
 
-        if !response.status().is_success() {
-            Err(super::HttpError { code: response.status() })?;
+        {
+            let mut download = download::start(url, cache_file, options, total, ranges)?;
+            copy(&mut download.response, &mut download.cache)?;
         }
 
-        {
-            let mut file = File::create(cache_file)?;
-            copy(&mut response, &mut file)?;
+        let actual = cache_file.metadata()?.len();
+        if actual < total {
+            return Err(TruncatedError { expected: total, actual }.into());
         }
 
-        let file = File::create(cache_file)?;
-        let compressed_size = file.metadata()?.len();
+        Zip::load(File::open(cache_file)?)
+    }
 
-        Ok(Zip {
-            compressed_size,
-            data: file
-        })
+}
+
+/// Determines the uncompressed size of a zip archive by summing the sizes
+/// recorded for each entry in its central directory.
+fn load_uncompressed_size(source: &File) -> Result<u64, failure::Error> {
+    let mut reader = source;
+    let mut zip = ZipArchive::new(reader)?;
+    let mut total = 0;
+    for i in 0..zip.len() {
+        total += zip.by_index(i)?.size();
     }
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(total)
+}
 
+/// Converts the name of a zip entry, which always uses `/` separators, to a path.
+#[cfg(windows)]
+fn entry_path(name: &str) -> PathBuf {
+    // Verbatim paths aren't normalized so we have to use correct r"\" separators.
+    PathBuf::from(name.replace('/', r"\"))
 }
 
-impl<S: Read + Seek> Archive for Zip<S> {
+/// Converts the name of a zip entry, which always uses `/` separators, to a path.
+#[cfg(not(windows))]
+fn entry_path(name: &str) -> PathBuf {
+    PathBuf::from(name)
+}
+
+/// The file type bits of a Unix mode, and the type of a symbolic link.
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Restores the permissions an entry was archived with on Unix.
+#[cfg(unix)]
+fn set_mode(file: &File, mode: u32) -> io::Result<()> {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(Permissions::from_mode(mode & 0o777))
+}
+
+/// Windows doesn't have executable bits to restore.
+#[cfg(not(unix))]
+fn set_mode(_: &File, _: u32) -> io::Result<()> {
+    Ok(())
+}
+
+impl<S: Read + Seek + Send> Archive for Zip<S> {
     fn compressed_size(&self) -> u64 { self.compressed_size }
-    fn uncompressed_size(&self) -> Option<u64> { None }
+    fn uncompressed_size(&self) -> Option<u64> { Some(self.uncompressed_size) }
     fn unpack(self: Box<Self>, dest: &Path, progress: &mut FnMut(&(), usize)) -> Result<(), failure::Error> {
         // Use a verbatim path to avoid the legacy Windows 260 byte path limit.
+        #[cfg(windows)]
         let dest: &Path = &dest.to_verbatim();
 
-        let mut zip = ZipArchive::new(ProgressRead::new(self.data, (), progress))?;
+        let mut zip = ZipArchive::new(self.data)?;
+        let mut unpacked = Unpacked::default();
         for i in 0..zip.len() {
             let mut entry = zip.by_index(i)?;
 
             let (is_dir, subpath) = {
                 let name = entry.name();
-
-                // Verbatim paths aren't normalized so we have to use correct r"\" separators.
-                (name.ends_with('/'), Path::new(&name.replace('/', r"\")).to_path_buf())
+                (name.ends_with('/'), entry_path(name))
             };
+            let mode = entry.unix_mode().unwrap_or(0);
 
             if is_dir {
                 create_dir_all(dest.join(subpath))?;
+            } else if mode & S_IFMT == S_IFLNK {
+                // The contents of a symbolic link's entry are its target.
+                let mut target = String::new();
+                entry.read_to_string(&mut target)?;
+                unpacked.symlink(subpath, entry_path(&target));
             } else {
                 let mut file = {
                     if let Some(basedir) = subpath.parent() {
                         create_dir_all(dest.join(basedir))?;
                     }
-                    File::create(dest.join(subpath))?
+                    File::create(dest.join(&subpath))?
                 };
-                copy(&mut entry, &mut file)?;
+                // Report the decompressed bytes so progress is measured in the same
+                // unit as `uncompressed_size()`.
+                copy(&mut ProgressRead::new(&mut entry, (), &mut *progress), &mut file)?;
+
+                if mode & 0o111 != 0 {
+                    set_mode(&file, mode)?;
+                    unpacked.executable(subpath);
+                }
             }
         }
+        unpacked.create_links(dest)?;
+        unpacked.validate(dest)?;
         Ok(())
     }
 
@@ -114,5 +176,6 @@ pub mod tests {
         let zip = Zip::load(test_file).expect("Failed to load zip file");
 
         assert_eq!(zip.compressed_size, 214);
+        assert_eq!(zip.uncompressed_size, 38);
     }
 }
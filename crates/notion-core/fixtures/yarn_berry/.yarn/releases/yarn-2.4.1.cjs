@@ -0,0 +1 @@
+// A stand-in for a vendored release of Yarn.
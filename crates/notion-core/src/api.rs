@@ -0,0 +1,327 @@
+//! A stable facade over Notion's core for programs that embed it, like editor
+//! plugins, which need to ask which Node a directory uses without running `notion`.
+//!
+//! Every function here is a query: none of them installs anything, prints anything,
+//! or records events. The types are plain data, independent of the rest of the
+//! crate, so that its internals can change without breaking the programs that use
+//! this module. Programs that ask again and again, like `notion query --stdio`, can
+//! ask a `Resolver`, which keeps its answers while they stay true.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use catalog::Catalog;
+use notion_fail::Fallible;
+use path;
+use pin_file::PIN_FILE;
+use platform::Stamp;
+use project::{Project, HOOKS_FILE};
+use session::{ActiveVersion, Session, VersionSource};
+
+/// A Node project, as Notion detects it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectInfo {
+    /// The project's root directory.
+    pub root: PathBuf,
+    /// The file the project's toolchain is pinned in (its `package.json`, unless it
+    /// keeps its pins in a pin file).
+    pub toolchain_file: PathBuf,
+    /// The root of the workspace the project belongs to, if any.
+    pub workspace_root: Option<PathBuf>,
+    /// The versions the project pins.
+    pub node: Option<String>,
+    pub yarn: Option<String>,
+    pub npm: Option<String>,
+}
+
+/// Where a selected version comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Pinned in the specified toolchain file.
+    Project(PathBuf),
+    /// Declared as the project's `packageManager`.
+    PackageManager,
+    /// Requested by the project's `.nvmrc` or `.node-version` file, which is named.
+    NodeVersionFile(String),
+    /// Constrained by the `engines` section of the project's manifest.
+    Engines,
+    /// Overridden by `notion use` in the environment of this process.
+    Override,
+    /// The user's default version.
+    Default,
+}
+
+impl Source {
+    fn from_version_source(source: VersionSource) -> Self {
+        match source {
+            VersionSource::Project(file) => Source::Project(file),
+            VersionSource::PackageManager => Source::PackageManager,
+            VersionSource::NodeVersionFile(name) => Source::NodeVersionFile(name.to_string()),
+            VersionSource::Engines => Source::Engines,
+            VersionSource::Override => Source::Override,
+            VersionSource::User => Source::Default,
+        }
+    }
+}
+
+/// The version of a tool selected for a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub version: String,
+    pub source: Source,
+    /// The directory of the version's executables, if it is installed. Running the
+    /// tool through Notion's shims installs it first if not.
+    pub bin_dir: Option<PathBuf>,
+}
+
+impl Selection {
+    fn new<F>(active: ActiveVersion, bin_dir: F) -> Fallible<Self>
+    where
+        F: FnOnce(&str) -> Fallible<PathBuf>,
+    {
+        let version = active.version.to_string();
+        let bin_dir = if active.installed {
+            Some(bin_dir(&version)?)
+        } else {
+            None
+        };
+        Ok(Selection {
+            version,
+            source: Source::from_version_source(active.source),
+            bin_dir,
+        })
+    }
+}
+
+/// The project a directory belongs to and the versions selected for it, as
+/// `Resolver::resolve` finds them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub project: Option<ProjectInfo>,
+    pub node: Option<Selection>,
+    pub yarn: Option<Selection>,
+}
+
+/// The installed versions of the tools, oldest first, and the user's defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Installed {
+    pub node: Vec<String>,
+    pub yarn: Vec<String>,
+    pub default_node: Option<String>,
+    pub default_yarn: Option<String>,
+}
+
+fn project_info(session: &Session) -> Option<ProjectInfo> {
+    session.project().map(|project: &Project| {
+        let manifest = project.manifest();
+        ProjectInfo {
+            root: project.root().to_path_buf(),
+            toolchain_file: project.toolchain_file(),
+            workspace_root: project.workspace_root().map(|root| root.to_path_buf()),
+            node: manifest.node().map(|version| version.to_string()),
+            yarn: manifest.yarn().map(|version| version.to_string()),
+            npm: manifest.npm().map(|version| version.to_string()),
+        }
+    })
+}
+
+/// Detects the Node project that a directory belongs to, if any.
+pub fn project(dir: &Path) -> Fallible<Option<ProjectInfo>> {
+    Ok(project_info(&Session::for_dir(dir)?))
+}
+
+/// Selects the version of Node that running `node` in a directory uses, following
+/// the same rules as the shims. Selecting a version for a range (from an `.nvmrc`
+/// file, say) that no installed version matches consults the version index.
+pub fn node_for_dir(dir: &Path) -> Fallible<Option<Selection>> {
+    match Session::for_dir(dir)?.active_node()? {
+        Some(active) => Selection::new(active, path::node_version_bin_dir).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Selects the version of Yarn that running `yarn` in a directory uses, following
+/// the same rules as the shims.
+pub fn yarn_for_dir(dir: &Path) -> Fallible<Option<Selection>> {
+    match Session::for_dir(dir)?.active_yarn()? {
+        Some(active) => Selection::new(active, path::yarn_version_bin_dir).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The installed versions of Node, oldest first.
+pub fn installed_node_versions() -> Fallible<Vec<String>> {
+    let catalog = Catalog::current()?;
+    Ok(catalog.node.versions.iter().map(|version| version.to_string()).collect())
+}
+
+/// The installed versions of Yarn, oldest first.
+pub fn installed_yarn_versions() -> Fallible<Vec<String>> {
+    let catalog = Catalog::current()?;
+    Ok(catalog.yarn.versions.iter().map(|version| version.to_string()).collect())
+}
+
+/// The user's default version of Node, if any.
+pub fn default_node() -> Fallible<Option<String>> {
+    let catalog = Catalog::current()?;
+    Ok(catalog.node.default.as_ref().map(|version| version.to_string()))
+}
+
+/// The user's default version of Yarn, if any.
+pub fn default_yarn() -> Fallible<Option<String>> {
+    let catalog = Catalog::current()?;
+    Ok(catalog.yarn.default.as_ref().map(|version| version.to_string()))
+}
+
+/// The files in each directory that could make it a project, or select versions for
+/// the directories it contains.
+const SELECTING_FILES: &[&str] = &[
+    "package.json",
+    PIN_FILE,
+    ".nvmrc",
+    ".node-version",
+    ".yarnrc.yml",
+    HOOKS_FILE,
+];
+
+/// The most directories a `Resolver` remembers the resolutions of at once.
+const MAX_RESOLUTIONS: usize = 1024;
+
+/// The files that the resolution for `dir` depends on: the files that select versions
+/// in `dir` and each of its ancestors, the system's and the user's configuration, and
+/// the catalog (which records the installed versions and the defaults).
+fn dependencies(dir: &Path) -> Fallible<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut ancestor = Some(dir);
+    while let Some(current) = ancestor {
+        files.extend(SELECTING_FILES.iter().map(|name| current.join(name)));
+        ancestor = current.parent();
+    }
+    files.extend(path::system_config_file());
+    files.push(path::user_config_file()?);
+    files.push(path::user_catalog_file()?);
+    Ok(files)
+}
+
+/// Answers queries from state kept between them, for long-running programs. Each
+/// resolution is remembered along with the state (size and modification time) of the
+/// files it depends on, and is reused for as long as none of them change; the catalog
+/// is only reloaded once its file changes. Checking the files is much cheaper than
+/// loading the configuration, the project, and the catalog anew.
+pub struct Resolver {
+    resolutions: HashMap<PathBuf, (Vec<Stamp>, Resolution)>,
+    installed: Option<(Stamp, Installed)>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            resolutions: HashMap::new(),
+            installed: None,
+        }
+    }
+
+    /// Detects the project of a directory and selects its versions of Node and Yarn,
+    /// as `project`, `node_for_dir`, and `yarn_for_dir` do.
+    pub fn resolve(&mut self, dir: &Path) -> Fallible<Resolution> {
+        if let Some(&(ref stamps, ref resolution)) = self.resolutions.get(dir) {
+            if stamps.iter().all(Stamp::is_current) {
+                return Ok(resolution.clone());
+            }
+        }
+
+        // the files are stamped before they are read, so that a change made while they
+        // are read makes the resolution stale
+        let stamps: Option<Vec<Stamp>> = dependencies(dir)?
+            .iter()
+            .map(|file| Stamp::of(file))
+            .collect();
+        let session = Session::for_dir(dir)?;
+        let node = match session.active_node()? {
+            Some(active) => Some(Selection::new(active, path::node_version_bin_dir)?),
+            None => None,
+        };
+        let yarn = match session.active_yarn()? {
+            Some(active) => Some(Selection::new(active, path::yarn_version_bin_dir)?),
+            None => None,
+        };
+        let resolution = Resolution {
+            project: project_info(&session),
+            node,
+            yarn,
+        };
+
+        match stamps {
+            Some(stamps) => {
+                if self.resolutions.len() >= MAX_RESOLUTIONS {
+                    self.resolutions.clear();
+                }
+                self.resolutions
+                    .insert(dir.to_path_buf(), (stamps, resolution.clone()));
+            }
+            // paths that aren't valid Unicode can't be stamped
+            None => {
+                self.resolutions.remove(dir);
+            }
+        }
+        Ok(resolution)
+    }
+
+    /// Lists the installed versions of Node and Yarn and the user's defaults, as
+    /// `installed_node_versions` and the like do.
+    pub fn installed(&mut self) -> Fallible<Installed> {
+        if let Some((ref stamp, ref installed)) = self.installed {
+            if stamp.is_current() {
+                return Ok(installed.clone());
+            }
+        }
+
+        let stamp = Stamp::of(&path::user_catalog_file()?);
+        let catalog = Catalog::current()?;
+        let installed = Installed {
+            node: catalog.node.versions.iter().map(|version| version.to_string()).collect(),
+            yarn: catalog.yarn.versions.iter().map(|version| version.to_string()).collect(),
+            default_node: catalog.node.default.as_ref().map(|version| version.to_string()),
+            default_yarn: catalog.yarn.default.as_ref().map(|version| version.to_string()),
+        };
+        self.installed = stamp.map(|stamp| (stamp, installed.clone()));
+        Ok(installed)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{dependencies, project};
+    use path;
+    use std::path::PathBuf;
+
+    fn fixture_path(fixture_dir: &str) -> PathBuf {
+        let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        cargo_manifest_dir.push("fixtures");
+        cargo_manifest_dir.push(fixture_dir);
+        cargo_manifest_dir
+    }
+
+    #[test]
+    fn test_project() {
+        let root = fixture_path("basic");
+        let info = project(&root).unwrap().unwrap();
+        assert_eq!(info.root, root);
+        assert_eq!(info.toolchain_file, root.join("package.json"));
+        assert_eq!(info.workspace_root, None);
+        assert_eq!(info.node, Some("6.11.1".to_string()));
+        assert_eq!(info.yarn, Some("1.2.0".to_string()));
+        assert_eq!(info.npm, None);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let dir = fixture_path("basic");
+        let files = dependencies(&dir).unwrap();
+        assert!(files.contains(&dir.join("package.json")));
+        assert!(files.contains(&dir.join(".nvmrc")));
+        assert!(files.contains(&dir.parent().unwrap().join("package.json")));
+        assert!(files.contains(&path::user_catalog_file().unwrap()));
+    }
+}
@@ -0,0 +1,254 @@
+//! Provides eviction of downloaded archives from the Notion cache, so that the cache
+//! stays within the limits of the `cache` configuration settings.
+
+use std::fs::{self, remove_file};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use config::{CacheConfig, Config};
+use distro::stamp::{self, is_stamp_file};
+use fs::is_lock_file;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use style::display_warning;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// An archive in the cache.
+struct CachedArchive {
+    path: PathBuf,
+    size: u64,
+    /// When the archive was last used. Access times aren't always kept up to date by
+    /// the filesystem, so this is only an approximation of recency.
+    last_used: SystemTime,
+}
+
+/// The cache directories of every tool.
+fn cache_dirs() -> Fallible<Vec<PathBuf>> {
+    Ok(vec![
+        path::node_cache_dir()?,
+        path::yarn_cache_dir()?,
+        path::npm_cache_dir()?,
+        path::pnpm_cache_dir()?,
+    ])
+}
+
+/// Lists the archives in the cache directories of every tool. The cached index of
+/// Node versions isn't an archive, so it is never evicted.
+fn cached_archives() -> Fallible<Vec<CachedArchive>> {
+    let mut archives = Vec::new();
+    for dir in cache_dirs()? {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir).unknown()? {
+            let entry = entry.unknown()?;
+            let metadata = entry.metadata().unknown()?;
+            let is_index = entry.file_name().to_string_lossy().starts_with("index.json");
+            let path = entry.path();
+            if !metadata.is_file() || is_index || is_lock_file(&path) || is_stamp_file(&path) {
+                continue;
+            }
+            let last_used = metadata
+                .accessed()
+                .or_else(|_| metadata.modified())
+                .unwrap_or(SystemTime::now());
+            archives.push(CachedArchive {
+                path,
+                size: metadata.len(),
+                last_used,
+            });
+        }
+    }
+    Ok(archives)
+}
+
+/// Lists the stamps left behind in the cache directories by archives that are gone.
+pub(crate) fn orphaned_stamps() -> Fallible<Vec<PathBuf>> {
+    let mut orphans = Vec::new();
+    for dir in cache_dirs()? {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir).unknown()? {
+            let path = entry.unknown()?.path();
+            if is_stamp_file(&path) && !path.with_extension("").is_file() {
+                orphans.push(path);
+            }
+        }
+    }
+    Ok(orphans)
+}
+
+/// Selects the archives to evict in order to satisfy the limits: first those that
+/// haven't been used within the maximum age, then the least recently used ones until
+/// the rest fit within the maximum size.
+fn select_evictions(
+    mut archives: Vec<CachedArchive>,
+    config: &CacheConfig,
+    now: SystemTime,
+) -> Vec<CachedArchive> {
+    if config.keep_archives {
+        return vec![];
+    }
+
+    archives.sort_by(|a, b| a.last_used.cmp(&b.last_used));
+
+    let mut total: u64 = archives.iter().map(|archive| archive.size).sum();
+    let oldest = config
+        .max_age
+        .map(|days| now - Duration::from_secs(days * SECONDS_PER_DAY));
+
+    let mut evicted = Vec::new();
+    for archive in archives {
+        let too_old = oldest.map_or(false, |oldest| archive.last_used < oldest);
+        let too_big = config.max_size.map_or(false, |max_size| total > max_size);
+        if !too_old && !too_big {
+            break;
+        }
+        total -= archive.size;
+        evicted.push(archive);
+    }
+    evicted
+}
+
+/// Whether the archives of an installed version stay in the cache: they do unless
+/// `cache.keep` is false, and `cache.keep-archives` keeps them regardless.
+fn keeps_installed(config: Option<&CacheConfig>) -> bool {
+    config.map_or(true, |config| config.keep || config.keep_archives)
+}
+
+/// Removes the cached archives of a version that has just been installed, unless the
+/// `cache` settings keep them (which they do by default). The version is installed by
+/// then, so an archive that can't be removed only produces a warning.
+pub(crate) fn discard_installed(config: &Config, archives: Vec<PathBuf>) {
+    if keeps_installed(config.cache.as_ref()) {
+        return;
+    }
+    for archive in archives {
+        if let Err(error) = remove_file(&archive) {
+            display_warning(&format!(
+                "could not remove {} from the cache: {}",
+                archive.display(),
+                error
+            ));
+            continue;
+        }
+        stamp::remove(&archive);
+    }
+}
+
+/// Evicts archives from the cache until it is within the limits configured by the
+/// `cache` settings, if any.
+pub(crate) fn enforce_limits(config: &Config) -> Fallible<()> {
+    let cache_config = match config.cache {
+        Some(ref cache_config) => cache_config,
+        None => {
+            return Ok(());
+        }
+    };
+
+    for archive in select_evictions(cached_archives()?, cache_config, SystemTime::now()) {
+        remove_file(&archive.path).unknown()?;
+        stamp::remove(&archive.path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    fn archive(name: &str, size: u64, days_ago: u64, now: SystemTime) -> CachedArchive {
+        CachedArchive {
+            path: PathBuf::from(name),
+            size,
+            last_used: now - Duration::from_secs(days_ago * SECONDS_PER_DAY),
+        }
+    }
+
+    fn names(archives: Vec<CachedArchive>) -> Vec<String> {
+        archives
+            .into_iter()
+            .map(|archive| archive.path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn archives(now: SystemTime) -> Vec<CachedArchive> {
+        vec![
+            archive("recent", 30, 1, now),
+            archive("oldest", 10, 60, now),
+            archive("old", 20, 10, now),
+        ]
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_over_max_size() {
+        let now = SystemTime::now();
+        let config = CacheConfig {
+            max_size: Some(35),
+            max_age: None,
+            keep_archives: false,
+            keep: true,
+            stream: false,
+        };
+        assert_eq!(
+            names(select_evictions(archives(now), &config, now)),
+            vec!["oldest".to_string(), "old".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_evicts_archives_over_max_age() {
+        let now = SystemTime::now();
+        let config = CacheConfig {
+            max_size: None,
+            max_age: Some(30),
+            keep_archives: false,
+            keep: true,
+            stream: false,
+        };
+        assert_eq!(
+            names(select_evictions(archives(now), &config, now)),
+            vec!["oldest".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_keep_archives_disables_eviction() {
+        let now = SystemTime::now();
+        let config = CacheConfig {
+            max_size: Some(0),
+            max_age: Some(0),
+            keep_archives: true,
+            keep: true,
+            stream: false,
+        };
+        assert!(select_evictions(archives(now), &config, now).is_empty());
+    }
+
+    #[test]
+    fn test_discard_installed() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let archive = dir.path().join("node-v10.0.0-linux-x64.tar.gz");
+        let discard = |src: &str| {
+            File::create(&archive).expect("Could not create archive");
+            let config: Config = src.parse().expect("Could not parse config");
+            discard_installed(&config, vec![archive.clone()]);
+            archive.is_file()
+        };
+
+        // kept by default
+        assert!(discard(""));
+        assert!(!discard("[cache]\nkeep = false\n"));
+        // keep-archives wins over keep
+        assert!(discard("[cache]\nkeep = false\nkeep-archives = true\n"));
+
+        // an archive that is already gone is only warned about
+        let config: Config = "[cache]\nkeep = false\n".parse().unwrap();
+        discard_installed(&config, vec![dir.path().join("missing.tar.gz")]);
+    }
+}
@@ -0,0 +1,118 @@
+//! Upgrades catalog files written by older versions of Notion.
+//!
+//! A catalog file records the version of its layout in its `schema-version` field,
+//! which files written before the field existed lack (they are version 0). When the
+//! layout changes, bump `CURRENT_VERSION` and add the migration from the previous
+//! version to `MIGRATIONS`: catalogs are upgraded one version at a time, on the raw
+//! TOML, before they are deserialized, so that no migration needs to know any layout
+//! but the two it converts between.
+
+use toml::value::{Table, Value};
+
+use notion_fail::{Fallible, NotionFail};
+
+/// The name of the field that records the version of a catalog's layout.
+pub(crate) const VERSION_FIELD: &'static str = "schema-version";
+
+/// The version of the layout this version of Notion writes.
+pub(crate) const CURRENT_VERSION: u32 = 1;
+
+/// The migrations between layouts: the migration at index `n` upgrades a catalog of
+/// version `n` to version `n + 1`.
+const MIGRATIONS: &[fn(&mut Table) -> Fallible<()>] = &[to_v1];
+
+/// Version 1 only adds the `schema-version` field, which `migrate` stamps on every
+/// catalog it upgrades.
+fn to_v1(_: &mut Table) -> Fallible<()> {
+    Ok(())
+}
+
+/// Thrown when the catalog was written by a newer version of Notion, whose layout
+/// this one can't read without losing what it doesn't know about.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The catalog has schema version {}, newer than this Notion supports ({})", found, supported)]
+#[notion_fail(
+    code = "ConfigurationError",
+    suggestion = "Upgrade Notion to use it with this catalog."
+)]
+pub(crate) struct CatalogVersionError {
+    pub(crate) found: i64,
+    pub(crate) supported: u32,
+}
+
+/// Thrown when the `schema-version` field of the catalog isn't a version number.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid schema version in the catalog file: {}", value)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct InvalidCatalogVersionError {
+    pub(crate) value: String,
+}
+
+/// Upgrades a catalog to the current layout, returning whether it needed upgrading.
+pub(crate) fn migrate(catalog: &mut Table) -> Fallible<bool> {
+    let version = match catalog.get(VERSION_FIELD) {
+        None => 0,
+        Some(&Value::Integer(n)) if n >= 0 => n,
+        Some(value) => throw!(InvalidCatalogVersionError {
+            value: value.to_string(),
+        }),
+    };
+    if version > CURRENT_VERSION as i64 {
+        throw!(CatalogVersionError {
+            found: version,
+            supported: CURRENT_VERSION,
+        });
+    }
+    if version == CURRENT_VERSION as i64 {
+        return Ok(false);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(catalog)?;
+    }
+    catalog.insert(
+        VERSION_FIELD.to_string(),
+        Value::Integer(CURRENT_VERSION as i64),
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{migrate, CURRENT_VERSION, MIGRATIONS, VERSION_FIELD};
+    use toml;
+    use toml::value::{Table, Value};
+
+    fn table(src: &str) -> Table {
+        toml::from_str(src).unwrap()
+    }
+
+    #[test]
+    fn test_migrations() {
+        assert_eq!(MIGRATIONS.len(), CURRENT_VERSION as usize);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut unversioned = table("[node]\ndefault = \"8.9.4\"\nversions = [\"8.9.4\"]\n");
+        assert!(migrate(&mut unversioned).unwrap());
+        assert_eq!(
+            unversioned.get(VERSION_FIELD),
+            Some(&Value::Integer(CURRENT_VERSION as i64))
+        );
+        assert_eq!(
+            unversioned.get("node"),
+            table("[node]\ndefault = \"8.9.4\"\nversions = [\"8.9.4\"]\n").get("node")
+        );
+
+        // A current catalog is left alone.
+        assert!(!migrate(&mut unversioned).unwrap());
+
+        let mut future = table("schema-version = 1000\n");
+        assert!(migrate(&mut future).is_err());
+
+        let mut invalid = table("schema-version = \"one\"\n");
+        assert!(migrate(&mut invalid).is_err());
+    }
+}
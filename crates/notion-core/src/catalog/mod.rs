@@ -1,43 +1,85 @@
 //! Provides types for working with Notion's local _catalog_, the local repository
 //! of available tool versions.
 
-use std::collections::{BTreeSet, HashSet};
-use std::fs::{remove_dir_all, File};
-use std::io::{self, Write};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs::{read_dir, remove_file, File};
+use std::io;
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::str::FromStr;
 use std::string::ToString;
 use std::time::{Duration, SystemTime};
 
+use crossbeam;
+use indicatif::ProgressBar;
 use lazycell::LazyCell;
+use log;
 use readext::ReadExt;
 use reqwest;
-use reqwest::header::{CacheControl, CacheDirective, Expires, HttpDate};
+use reqwest::header::{CacheControl, CacheDirective, ETag, Expires, Headers, HttpDate};
+use reqwest::StatusCode;
 use serde_json;
-use tempfile::NamedTempFile;
 use toml;
 
-use config::{Config, ToolConfig};
-use distro::node::NodeDistro;
+use cache;
+use config::{Config, LifecycleConfig, ToolConfig};
+use dedupe;
+use distro::checksum;
+use distro::error::{BrokenNodeError, ChecksumNotFoundError};
+use distro::node::{self, NodeDistro};
+use distro::npm::NpmDistro;
+use distro::pnpm::PnpmDistro;
+use distro::source;
+use distro::stamp;
 use distro::yarn::YarnDistro;
 use distro::{Distro, Fetched};
-use fs::{ensure_containing_dir_exists, read_file_opt, touch};
+use env;
+use event;
+use fs::{lock, read_file_opt, remove_dir_all, rename, touch, write_atomic};
+use http;
 use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use package::Package;
 use path::{self, user_catalog_file};
-use semver::{Version, VersionReq};
-use style::progress_spinner;
-use version::VersionSpec;
-
+use profile::{self, Phase};
+use plan::{Change, Plan};
+use semver::Version;
+use plugin::ResolvePlugin;
+use style::{display_warning, progress_spinner, ProgressDisplay};
+use trace::Trace;
+use version::{Channel, VersionSpec};
+
+mod migrate;
+mod yarn_index;
+
+pub use self::yarn_index::YarnRelease;
+pub mod references;
 pub(crate) mod serial;
 
 // ISSUE (#86): Move public repository URLs to config file
-/// URL of the index of available Node versions on the public Node server.
-const PUBLIC_NODE_VERSION_INDEX: &'static str = "https://nodejs.org/dist/index.json";
-/// URL of the index of available Yarn versions on the public git repository.
-const PUBLIC_YARN_VERSION_INDEX: &'static str =
-    "https://github.com/notion-cli/yarn-releases/raw/master/index.json";
+/// Name of the index of available Node versions, relative to the root of the Node server.
+const NODE_VERSION_INDEX_FILE: &'static str = "index.json";
+/// How long in seconds after it expires a cached public Node index is still used, while
+/// it is refreshed in the background.
+const STALE_INDEX_GRACE: u64 = 24 * 60 * 60;
+
+/// How long in seconds a background refresh of the public Node index is given before
+/// another one may be started.
+const INDEX_REFRESH_INTERVAL: u64 = 10 * 60;
+
 /// URL of the latest Yarn version on the public yarnpkg.com
 const PUBLIC_YARN_LATEST_VERSION: &'static str = "https://yarnpkg.com/latest-version";
+/// URL of the package metadata for Yarn 1 on the public npm registry, whose dist-tags
+/// the public index doesn't record.
+const PUBLIC_YARN_REGISTRY_INDEX: &'static str = "https://registry.npmjs.org/yarn";
+/// URL of the package metadata for Yarn 2 and later ("berry") on the public npm
+/// registry, which publishes them under a package of their own.
+const PUBLIC_YARN_BERRY_INDEX: &'static str = "https://registry.npmjs.org/@yarnpkg%2fcli-dist";
+/// URL of the package metadata for npm on the public npm registry.
+const PUBLIC_NPM_VERSION_INDEX: &'static str = "https://registry.npmjs.org/npm";
+/// URL of the package metadata for pnpm on the public npm registry.
+const PUBLIC_PNPM_VERSION_INDEX: &'static str = "https://registry.npmjs.org/pnpm";
 
 /// Lazily loaded tool catalog.
 pub struct LazyCatalog {
@@ -54,40 +96,198 @@ impl LazyCatalog {
 
     /// Forces the loading of the catalog and returns an immutable reference to it.
     pub fn get(&self) -> Fallible<&Catalog> {
-        self.catalog.try_borrow_with(|| Catalog::current())
+        self.catalog.try_borrow_with(|| profile::time(Phase::CatalogLoad, Catalog::current))
     }
 
     /// Forces the loading of the catalog and returns a mutable reference to it.
     pub fn get_mut(&mut self) -> Fallible<&mut Catalog> {
-        self.catalog.try_borrow_mut_with(|| Catalog::current())
+        self.catalog.try_borrow_mut_with(|| profile::time(Phase::CatalogLoad, Catalog::current))
     }
 }
 
 pub struct Collection<D: Distro> {
-    /// The currently activated Node version, if any.
+    /// The user's default version, if any: the fallback used outside of projects that
+    /// select a version of their own. Project pins live in the projects themselves, never
+    /// in the catalog.
     pub default: Option<Version>,
 
     // A sorted collection of the available versions in the catalog.
     pub versions: BTreeSet<Version>,
 
-    pub phantom: PhantomData<D>,
+    /// The versions that only the read-only system install provides (see the `path`
+    /// module). They are among `versions`, but never saved to the user's catalog, and
+    /// can't be uninstalled.
+    pub system: BTreeSet<Version>,
+
+    /// The prerelease channel each version that came from one was fetched from. These
+    /// versions are only selected when their channel is asked for explicitly.
+    pub channels: BTreeMap<Version, Channel>,
+
+    /// The version of npm bundled with each version, as the Node index recorded when
+    /// the version was fetched. Only Node bundles npm.
+    pub bundled_npm: BTreeMap<Version, Version>,
+
+    /// Where each version that is a custom build (see `Catalog::fetch_node_url`) came
+    /// from. Only Node has custom builds.
+    pub custom: BTreeMap<Version, CustomBuild>,
+
+    // Marks the distro type without inheriting its thread-safety, so that a collection
+    // can be shared with the threads fetching distros concurrently.
+    pub phantom: PhantomData<fn() -> D>,
+}
+
+/// The provenance of a custom build of Node, recorded under the label the user gave it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomBuild {
+    pub label: String,
+    /// The URL of the build's archive.
+    pub url: String,
+    /// The SHA-256 checksum of the build's archive, as it was verified against, or as
+    /// it was computed when it wasn't given.
+    pub checksum: Option<String>,
+}
+
+/// What is known about a release of a tool, from the index of its releases and the
+/// catalog, for deciding what to pin (see `Catalog::node_release_info`).
+#[derive(Debug, Clone)]
+pub struct ReleaseInfo {
+    pub version: Version,
+    /// The date of the release (`YYYY-MM-DD`), if the index records it.
+    pub date: Option<String>,
+    /// The name of the LTS release line the release belongs to, if any.
+    pub lts: Option<String>,
+    /// Whether the index marks the release as a security release.
+    pub security: bool,
+    /// The version of npm bundled with the release. Only Node bundles npm.
+    pub npm: Option<Version>,
+    /// The files the release is published as, as the Node index names them (e.g.
+    /// `linux-x64` or `win-x64-zip`), sorted. Only the Node index records them.
+    pub files: Vec<String>,
+    /// The archives of the release in the cache, with their sizes in bytes.
+    pub cached: Vec<(PathBuf, u64)>,
+    pub installed: bool,
+    /// Whether the release is the user's default version.
+    pub default: bool,
+}
+
+/// A request to fetch a version of a tool, e.g. `node@8`.
+#[derive(Debug, Clone)]
+pub enum ToolSpec {
+    Node(VersionSpec),
+    Yarn(VersionSpec),
+    Npm(VersionSpec),
+    Pnpm(VersionSpec),
+}
+
+impl fmt::Display for ToolSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ToolSpec::Node(ref version) => write!(f, "node@{}", version),
+            ToolSpec::Yarn(ref version) => write!(f, "yarn@{}", version),
+            ToolSpec::Npm(ref version) => write!(f, "npm@{}", version),
+            ToolSpec::Pnpm(ref version) => write!(f, "pnpm@{}", version),
+        }
+    }
+}
+
+/// A distro that has been resolved for a `ToolSpec` but not yet fetched.
+enum PendingFetch {
+    Node(NodeDistro),
+    Yarn(YarnDistro),
+    Npm(NpmDistro),
+    Pnpm(PnpmDistro),
+}
+
+impl PendingFetch {
+    /// Identifies the tool and version being fetched, to avoid fetching it twice.
+    fn key(&self) -> (&'static str, Version) {
+        match *self {
+            PendingFetch::Node(ref distro) => ("node", distro.version().clone()),
+            PendingFetch::Yarn(ref distro) => ("yarn", distro.version().clone()),
+            PendingFetch::Npm(ref distro) => ("npm", distro.version().clone()),
+            PendingFetch::Pnpm(ref distro) => ("pnpm", distro.version().clone()),
+        }
+    }
+
+    fn progress_bar(&self) -> ProgressBar {
+        match *self {
+            PendingFetch::Node(ref distro) => distro.progress_bar(),
+            PendingFetch::Yarn(ref distro) => distro.progress_bar(),
+            PendingFetch::Npm(ref distro) => distro.progress_bar(),
+            PendingFetch::Pnpm(ref distro) => distro.progress_bar(),
+        }
+    }
+
+    fn fetch(
+        self,
+        catalog: &Catalog,
+        bar: ProgressBar,
+        hooks: Option<&LifecycleConfig>,
+    ) -> Fallible<Fetched> {
+        match self {
+            PendingFetch::Node(distro) => distro.fetch_with_hooks("node", &catalog.node, bar, hooks),
+            PendingFetch::Yarn(distro) => distro.fetch_with_hooks("yarn", &catalog.yarn, bar, hooks),
+            PendingFetch::Npm(distro) => distro.fetch_with_hooks("npm", &catalog.npm, bar, hooks),
+            PendingFetch::Pnpm(distro) => distro.fetch_with_hooks("pnpm", &catalog.pnpm, bar, hooks),
+        }
+    }
+}
+
+/// Resolves the distro to fetch for the specified semantic versioning requirements,
+/// or the local version that satisfies them if there is nothing to fetch.
+fn resolve_pending<D: Distro>(
+    collection: &Collection<D>,
+    matching: &VersionSpec,
+    config: &Config,
+) -> Fallible<Result<D, Version>>
+where
+    Collection<D>: Resolve<D>,
+{
+    if env::offline() {
+        if let Some(version) = collection.resolve_local(matching) {
+            return Ok(Err(version));
+        }
+    }
+
+    let distro = collection.resolve_remote(matching, config, &Trace::disabled())?;
+    if collection.contains(distro.version()) {
+        return Ok(Err(distro.version().clone()));
+    }
+    Ok(Ok(distro))
 }
 
 pub type NodeCollection = Collection<NodeDistro>;
 pub type YarnCollection = Collection<YarnDistro>;
+pub type NpmCollection = Collection<NpmDistro>;
+pub type PnpmCollection = Collection<PnpmDistro>;
 
 /// The catalog of tool versions available locally.
 pub struct Catalog {
     pub node: NodeCollection,
     pub yarn: YarnCollection,
+    pub npm: NpmCollection,
+    pub pnpm: PnpmCollection,
+    /// The packages installed as global tools, by name.
+    pub packages: BTreeMap<String, Package>,
 }
 
 impl Catalog {
-    /// Returns the current tool catalog.
-    fn current() -> Fallible<Catalog> {
+    /// Returns the current tool catalog. A catalog written by an older version of
+    /// Notion is upgraded to the current layout, and saved that way.
+    pub(crate) fn current() -> Fallible<Catalog> {
         let path = user_catalog_file()?;
         let src = touch(&path)?.read_into_string().unknown()?;
-        src.parse()
+        let (mut catalog, migrated) = Catalog::parse(&src)?;
+        if migrated {
+            catalog.save()?;
+        }
+        if let Some(system_dir) = path::system_versions_dir() {
+            add_system_versions(&mut catalog.node, &system_dir.join("node"))?;
+            add_system_versions(&mut catalog.yarn, &system_dir.join("yarn"))?;
+            add_system_versions(&mut catalog.npm, &system_dir.join("npm"))?;
+            add_system_versions(&mut catalog.pnpm, &system_dir.join("pnpm"))?;
+        }
+        Ok(catalog)
     }
 
     /// Returns a pretty-printed TOML representation of the contents of the catalog.
@@ -95,16 +295,57 @@ impl Catalog {
         toml::to_string_pretty(&self.to_serial()).unwrap()
     }
 
-    /// Saves the contents of the catalog to the user's catalog file.
-    pub fn save(&self) -> Fallible<()> {
+    /// Saves the contents of the catalog to the user's catalog file. The file is
+    /// replaced atomically, so it is never left partially written.
+    ///
+    /// Another Notion process may have changed the file since this catalog was loaded,
+    /// so the file is locked while it is rewritten, and the versions and packages it
+    /// lists that are still installed are kept.
+    pub fn save(&mut self) -> Fallible<()> {
         let path = user_catalog_file()?;
-        let mut file = File::create(&path).unknown()?;
-        file.write_all(self.to_string().as_bytes()).unknown()?;
+        let _lock = lock(&path, "updating the catalog")?;
+        if let Some(src) = read_file_opt(&path).unknown()? {
+            if let Ok(on_disk) = src.parse::<Catalog>() {
+                self.merge_installed(on_disk)?;
+            }
+        }
+        write_atomic(&path, &self.to_string())
+    }
+
+    /// Adds the versions and packages of another catalog that are still installed but
+    /// missing from this one.
+    fn merge_installed(&mut self, other: Catalog) -> Fallible<()> {
+        merge_versions(&mut self.node, other.node.versions, path::user_node_version_dir)?;
+        for (version, channel) in other.node.channels {
+            if self.node.contains(&version) {
+                self.node.channels.entry(version).or_insert(channel);
+            }
+        }
+        for (version, npm) in other.node.bundled_npm {
+            if self.node.contains(&version) {
+                self.node.bundled_npm.entry(version).or_insert(npm);
+            }
+        }
+        for (version, build) in other.node.custom {
+            if self.node.contains(&version) {
+                self.node.custom.entry(version).or_insert(build);
+            }
+        }
+        merge_versions(&mut self.yarn, other.yarn.versions, path::user_yarn_version_dir)?;
+        merge_versions(&mut self.npm, other.npm.versions, path::user_npm_version_dir)?;
+        merge_versions(&mut self.pnpm, other.pnpm.versions, path::user_pnpm_version_dir)?;
+        for (name, package) in other.packages {
+            if !self.packages.contains_key(&name) && path::package_dir(&name)?.is_dir() {
+                self.packages.insert(name, package);
+            }
+        }
         Ok(())
     }
 
-    /// Sets the Node version in the user toolchain to one matching the specified semantic versioning requirements.
-    pub fn set_user_node(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<()> {
+    /// Sets the user's default Node version, which is used outside of projects that
+    /// select a version of their own, to one matching the specified semantic versioning
+    /// requirements. This never changes a project's pins.
+    pub fn set_default_node(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<()> {
         let fetched = self.fetch_node(matching, config)?;
         let version = Some(fetched.into_version());
 
@@ -118,27 +359,411 @@ impl Catalog {
 
     /// Fetches a Node version matching the specified semantic versioning requirements.
     pub fn fetch_node(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<Fetched> {
-        let distro = self.node.resolve_remote(matching, config.node.as_ref())?;
-        let fetched = distro.fetch(&self.node).unknown()?;
+        if env::offline() {
+            if let Some(version) = self.node.resolve_local(matching) {
+                return Ok(Fetched::Already(version));
+            }
+        }
+
+        let distro = self.node.resolve_remote(matching, config, &Trace::disabled())?;
+        let fetched = match distro.fetch("node", &self.node, config) {
+            // The broken archive has been removed from the cache, so the second attempt
+            // downloads it afresh.
+            Err(ref error) if error.downcast_ref::<BrokenNodeError>().is_some() => {
+                display_warning(&format!("{}; trying again", error));
+                let distro = self.node.resolve_remote(matching, config, &Trace::disabled())?;
+                distro.fetch("node", &self.node, config).unknown()?
+            }
+            result => result.unknown()?,
+        };
+
+        if let &Fetched::Now(ref version) = &fetched {
+            self.node.versions.insert(version.clone());
+            if let VersionSpec::Channel(channel) = *matching {
+                self.node.channels.insert(version.clone(), channel);
+            }
+            self.record_bundled_npm(version);
+            self.save()?;
+            cache::discard_installed(config, cached_archives("node", version)?);
+            cache::enforce_limits(config)?;
+            dedupe::after_fetch(config)?;
+        }
+
+        Ok(fetched)
+    }
+
+    /// Sets the user's default Node version like `set_default_node`, except that a
+    /// release without a prebuilt binary for this platform is built from source (see
+    /// `fetch_node_or_build`).
+    pub fn set_default_node_from_source(
+        &mut self,
+        matching: &VersionSpec,
+        config: &Config,
+    ) -> Fallible<()> {
+        let version = Some(self.fetch_node_or_build(matching, config)?.into_version());
+
+        if self.node.default != version {
+            self.node.default = version;
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetches a Node version matching the specified semantic versioning requirements
+    /// like `fetch_node`, except that when Node publishes no prebuilt binary of the
+    /// matching release for this platform (an unusual architecture, or one the release
+    /// predates builds for), the release is built from its source instead. Building
+    /// takes a long while, so it is never done unless asked for.
+    pub fn fetch_node_or_build(
+        &mut self,
+        matching: &VersionSpec,
+        config: &Config,
+    ) -> Fallible<Fetched> {
+        let error = match self.fetch_node(matching, config) {
+            Err(error) => error,
+            fetched => return fetched,
+        };
+        let no_binary = error.downcast_ref::<ChecksumNotFoundError>().is_some()
+            || error.downcast_ref::<NoMuslNodeBuildError>().is_some();
+        if !no_binary {
+            return Err(error);
+        }
+
+        // Every release's source is published, whatever platforms it was built for.
+        let index: Index = resolve_node_versions(config)?.into_index()?;
+        let version = match select_node_version(&index.entries, matching) {
+            Some(version) => version,
+            None => return Err(error),
+        };
+        display_warning(&format!(
+            "Node v{} has no prebuilt binary for {}, so it is built from source, which takes a while",
+            version,
+            path::node_platform()
+        ));
+
+        let fetched = source::fetch(version, &self.node, config)?;
+
+        if let &Fetched::Now(ref version) = &fetched {
+            self.node.versions.insert(version.clone());
+            self.record_bundled_npm(version);
+            self.save()?;
+            cache::discard_installed(config, cached_archives("node", version)?);
+            cache::enforce_limits(config)?;
+            dedupe::after_fetch(config)?;
+        }
+
+        Ok(fetched)
+    }
+
+    /// Fetches Node from an archive provided locally (e.g. one that has been through a
+    /// security review) rather than downloading it. The archive must be named as Node
+    /// publishes it for this platform, which identifies the version it contains, and
+    /// that version must match the specified semantic versioning requirements. The
+    /// archive is added to the cache.
+    pub fn fetch_node_file(
+        &mut self,
+        matching: &VersionSpec,
+        file: &Path,
+        config: &Config,
+    ) -> Fallible<Fetched> {
+        let version = match local_archive_version(file) {
+            Some(version) => version,
+            None => throw!(LocalArchiveNameError {
+                file: file.to_string_lossy().to_string(),
+                expected: path::node_archive_file("<version>"),
+            }),
+        };
+        let matches = match *matching {
+            VersionSpec::Latest => true,
+            VersionSpec::Semver(ref matching) => matching.matches(&version),
+            VersionSpec::Lts(_) | VersionSpec::Channel(_) | VersionSpec::Tag(_) => false,
+        };
+        if !matches {
+            throw!(LocalArchiveVersionError {
+                file: file.to_string_lossy().to_string(),
+                version: version.to_string(),
+                matching: matching.clone(),
+            });
+        }
+
+        if self.node.contains(&version) {
+            return Ok(Fetched::Already(version));
+        }
+
+        let distro = NodeDistro::local(version, file)?;
+        let fetched = distro.fetch("node", &self.node, config).unknown()?;
+
+        if let &Fetched::Now(ref version) = &fetched {
+            self.node.versions.insert(version.clone());
+            self.record_bundled_npm(version);
+            self.save()?;
+            cache::discard_installed(config, cached_archives("node", version)?);
+            cache::enforce_limits(config)?;
+            dedupe::after_fetch(config)?;
+        }
+
+        Ok(fetched)
+    }
+
+    /// Sets the user's default Node version to a custom build fetched from a URL (see
+    /// `fetch_node_url`), producing its version.
+    pub fn set_default_node_url(
+        &mut self,
+        url: &str,
+        label: &str,
+        checksum: Option<&str>,
+        config: &Config,
+    ) -> Fallible<Version> {
+        let version = self.fetch_node_url(url, label, checksum, config)?.into_version();
+
+        if self.node.default.as_ref() != Some(&version) {
+            self.node.default = Some(version.clone());
+            self.save()?;
+        }
+
+        Ok(version)
+    }
+
+    /// Fetches a custom build of Node (e.g. one with patches under test) from the
+    /// archive at a URL, recording the URL and the archive's checksum under the specified
+    /// label. The archive must be named as Node publishes it for this platform, which
+    /// identifies the version it contains. Custom builds are installed alongside Node's
+    /// own releases, so a version that is already installed from elsewhere must be
+    /// uninstalled first. Projects pin a custom build by its version, like any other.
+    pub fn fetch_node_url(
+        &mut self,
+        url: &str,
+        label: &str,
+        checksum: Option<&str>,
+        config: &Config,
+    ) -> Fallible<Fetched> {
+        let version = match local_archive_version(Path::new(url.rsplit('/').next().unwrap_or(url))) {
+            Some(version) => version,
+            None => throw!(LocalArchiveNameError {
+                file: url.to_string(),
+                expected: path::node_archive_file("<version>"),
+            }),
+        };
+
+        if self.node.contains(&version) {
+            let relabel = match self.node.custom.get(&version) {
+                Some(build) if build.url == url => build.label != label,
+                _ => throw!(CustomBuildConflictError {
+                    version: version.to_string(),
+                }),
+            };
+            if relabel {
+                if let Some(build) = self.node.custom.get_mut(&version) {
+                    build.label = label.to_string();
+                }
+                self.save()?;
+            }
+            return Ok(Fetched::Already(version));
+        }
+
+        let distro = NodeDistro::custom(version, url, checksum.map(ToString::to_string), config)?;
+        let fetched = distro.fetch("node", &self.node, config).unknown()?;
 
         if let &Fetched::Now(ref version) = &fetched {
+            let checksum = match checksum {
+                Some(checksum) => Some(checksum.to_string()),
+                None => {
+                    let archive_file = url.rsplit('/').next().unwrap_or(url);
+                    checksum::sha256_file(&path::node_custom_cache_dir()?.join(archive_file)).ok()
+                }
+            };
             self.node.versions.insert(version.clone());
+            self.node.custom.insert(
+                version.clone(),
+                CustomBuild {
+                    label: label.to_string(),
+                    url: url.to_string(),
+                    checksum,
+                },
+            );
             self.save()?;
+            cache::discard_installed(config, cached_archives("node", version)?);
+            cache::enforce_limits(config)?;
+            dedupe::after_fetch(config)?;
         }
 
         Ok(fetched)
     }
 
-    /// Resolves a Node version matching the specified semantic versioning requirements.
-    pub fn resolve_node(&self, matching: &VersionSpec, config: &Config) -> Fallible<Version> {
-        let distro = self.node.resolve_remote(&matching, config.node.as_ref())?;
+    /// Resolves a Node version matching the specified semantic versioning requirements,
+    /// recording how it was resolved in `trace`.
+    pub fn resolve_node(
+        &self,
+        matching: &VersionSpec,
+        config: &Config,
+        trace: &Trace,
+    ) -> Fallible<Version> {
+        let distro = self.node.resolve_remote(&matching, config, trace)?;
         Ok(distro.version().clone())
     }
 
-    /// Uninstalls a specific Node version from the local catalog.
+    /// Downloads the public Node index afresh (or revalidates the cached one), whether
+    /// or not the cached one has expired. The index listed by a `node.ls-remote` plugin,
+    /// or that of a mirror on the filesystem, is never cached, so it isn't refreshed.
+    pub fn refresh_node_index(&self, config: &Config) -> Fallible<()> {
+        if config.node.as_ref().and_then(|node| node.ls_remote.as_ref()).is_some() {
+            return Ok(());
+        }
+        let index_url = format!("{}{}", node::server_root(config), NODE_VERSION_INDEX_FILE);
+        if http::file_url_path(&index_url).is_none() {
+            fetch_node_index(&index_url, config)?;
+        }
+        Ok(())
+    }
+
+    /// Lists the Node versions available from the public Node server (or the configured
+    /// mirror), newest first.
+    pub fn remote_node_versions(&self, config: &Config) -> Fallible<Vec<(Version, VersionData)>> {
+        let index: Index = resolve_node_versions(config)?.into_index()?;
+        let mut entries = index.entries;
+        entries.sort_by(|&(ref a, _), &(ref b, _)| b.cmp(a));
+        Ok(entries)
+    }
+
+    /// Lists the newest release of each line of Node worth installing, newest line first:
+    /// the current line and every LTS line. The index is read from the cache if it is
+    /// fresh enough.
+    pub fn recent_node_releases(&self, config: &Config) -> Fallible<Vec<(Version, VersionData)>> {
+        Ok(recent_releases(self.remote_node_versions(config)?))
+    }
+
+    /// Describes the newest release of Node matching the specified requirements, from
+    /// the index (which is read from the cache if it is fresh enough) and the catalog.
+    pub fn node_release_info(&self, matching: &VersionSpec, config: &Config) -> Fallible<ReleaseInfo> {
+        let entries = self.remote_node_versions(config)?;
+        let version = match select_node_version(&entries, matching) {
+            Some(version) => version,
+            None => {
+                let closest = match *matching {
+                    VersionSpec::Semver(ref req) => closest_version(
+                        &req.to_string(),
+                        entries.iter().map(|&(ref version, _)| version),
+                    ),
+                    _ => None,
+                };
+                throw!(NoNodeVersionFoundError {
+                    matching: matching.clone(),
+                    closest,
+                });
+            }
+        };
+        let data = entries
+            .into_iter()
+            .find(|&(ref entry, _)| *entry == version)
+            .map(|(_, data)| data)
+            .unwrap();
+
+        let mut files: Vec<String> = data.files.into_iter().collect();
+        files.sort();
+        let npm = data.npm
+            .or_else(|| self.node.bundled_npm.get(&version).cloned());
+        Ok(ReleaseInfo {
+            date: data.date,
+            lts: data.lts,
+            security: data.security,
+            npm,
+            files,
+            cached: archive_sizes(cached_archives("node", &version)?),
+            installed: self.node.contains(&version),
+            default: self.node.default.as_ref() == Some(&version),
+            version,
+        })
+    }
+
+    /// Describes the newest release of Yarn matching the specified requirements, from
+    /// the index (which is read from the cache if it is fresh enough) and the catalog.
+    pub fn yarn_release_info(&self, matching: &VersionSpec, config: &Config) -> Fallible<ReleaseInfo> {
+        let releases = self.remote_yarn_releases(config)?;
+        let release = match *matching {
+            VersionSpec::Latest => releases.into_iter().max_by(|a, b| a.version.cmp(&b.version)),
+            VersionSpec::Semver(ref req) => {
+                let closest = closest_version(
+                    &req.to_string(),
+                    releases.iter().map(|release| &release.version),
+                );
+                match releases
+                    .into_iter()
+                    .filter(|release| req.matches(&release.version))
+                    .max_by(|a, b| a.version.cmp(&b.version))
+                {
+                    Some(release) => Some(release),
+                    None => throw!(NoYarnVersionFoundError {
+                        matching: matching.clone(),
+                        closest,
+                    }),
+                }
+            }
+            VersionSpec::Lts(_) | VersionSpec::Channel(_) | VersionSpec::Tag(_) => None,
+        };
+        let release = match release {
+            Some(release) => release,
+            None => throw!(NoYarnVersionFoundError {
+                matching: matching.clone(),
+                closest: None,
+            }),
+        };
+
+        let version = release.version;
+        Ok(ReleaseInfo {
+            date: release.date,
+            lts: None,
+            security: false,
+            npm: None,
+            files: vec![],
+            cached: archive_sizes(cached_archives("yarn", &version)?),
+            installed: self.yarn.contains(&version),
+            default: self.yarn.default.as_ref() == Some(&version),
+            version,
+        })
+    }
+
+    /// Lists the Yarn versions available from the public Yarn index, newest first.
+    pub fn remote_yarn_versions(&self, config: &Config) -> Fallible<Vec<Version>> {
+        Ok(self.remote_yarn_releases(config)?
+            .into_iter()
+            .map(|release| release.version)
+            .collect())
+    }
+
+    /// Lists the Yarn releases in the public Yarn index, newest first. The index is read
+    /// from the cache if it is fresh enough.
+    pub fn remote_yarn_releases(&self, config: &Config) -> Fallible<Vec<YarnRelease>> {
+        yarn_index::releases(config, false)
+    }
+
+    /// Downloads the public Yarn index afresh, whether or not the cached one is still
+    /// fresh.
+    pub fn refresh_yarn_index(&self, config: &Config) -> Fallible<()> {
+        yarn_index::releases(config, true).map(|_| ())
+    }
+
+    /// Returns the directory where the specified Node version is installed.
+    pub fn node_install_path(&self, version: &Version) -> Fallible<PathBuf> {
+        path::node_version_dir(&version.to_string())
+    }
+
+    /// Returns the directory containing the executables of the specified Node
+    /// version, using the platform's layout of a Node installation.
+    pub fn node_bin_path(&self, version: &Version) -> Fallible<PathBuf> {
+        path::node_version_bin_dir(&version.to_string())
+    }
+
+    /// Uninstalls a specific Node version from the local catalog, deleting its
+    /// installation directory and its cached archive. The installation is first moved
+    /// aside, so that if the catalog can't be updated the version stays installed, and
+    /// the catalog never lists a version whose files are gone.
     pub fn uninstall_node(&mut self, version: &Version) -> Fallible<()> {
+        if self.node.system.contains(version) {
+            throw!(SystemVersionError::new("node", version));
+        }
         if self.node.contains(version) {
-            let home = path::node_version_dir(&version.to_string())?;
+            let home = path::user_node_version_dir(&version.to_string())?;
 
             if !home.is_dir() {
                 Err(io::Error::new(
@@ -147,20 +772,92 @@ impl Catalog {
                 )).unknown()?;
             }
 
-            remove_dir_all(home).unknown()?;
+            let trash = home.with_extension("uninstalling");
+            rename(&home, &trash).unknown()?;
 
             self.node.versions.remove(version);
+            let channel = self.node.channels.remove(version);
+            let bundled_npm = self.node.bundled_npm.remove(version);
+            let custom = self.node.custom.remove(version);
+            if self.node.default.as_ref() == Some(version) {
+                self.node.default = None;
+            }
+            if let Err(err) = self.save() {
+                self.node.versions.insert(version.clone());
+                if let Some(channel) = channel {
+                    self.node.channels.insert(version.clone(), channel);
+                }
+                if let Some(npm) = bundled_npm {
+                    self.node.bundled_npm.insert(version.clone(), npm);
+                }
+                if let Some(build) = custom {
+                    self.node.custom.insert(version.clone(), build);
+                }
+                rename(&trash, &home).unknown()?;
+                return Err(err);
+            }
 
-            self.save()?;
+            remove_dir_all(trash).unknown()?;
+
+            for archive in cached_node_archives(version)? {
+                remove_file(&archive).unknown()?;
+                stamp::remove(&archive);
+            }
         }
 
         Ok(())
     }
 
+    /// Returns whether an installed Node version is missing its executable, as happens
+    /// when its directory is damaged after it was fetched. The system's versions are
+    /// never repaired, so they never count as broken.
+    pub fn node_is_broken(&self, version: &Version) -> Fallible<bool> {
+        if !self.node.contains(version) || self.node.system.contains(version) {
+            return Ok(false);
+        }
+        Ok(!env::node_executable(&version.to_string())?.is_file())
+    }
+
+    /// Forgets a broken install of a Node version (see `node_is_broken`), removing what
+    /// is left of it and its cached archives, so that the next fetch starts afresh.
+    pub fn forget_broken_node(&mut self, version: &Version) -> Fallible<()> {
+        let home = path::user_node_version_dir(&version.to_string())?;
+        if home.exists() {
+            remove_dir_all(&home).unknown()?;
+        }
+        for archive in cached_node_archives(version)? {
+            remove_file(&archive).unknown()?;
+            stamp::remove(&archive);
+        }
+        self.node.versions.remove(version);
+        self.save()
+    }
+
+    /// Plans the changes of `uninstall_node`, without making them.
+    pub fn plan_uninstall_node(&self, version: &Version) -> Fallible<Plan> {
+        if self.node.system.contains(version) {
+            throw!(SystemVersionError::new("node", version));
+        }
+        let mut plan = Plan::new();
+        if self.node.contains(version) {
+            plan.push(Change::Delete(path::user_node_version_dir(&version.to_string())?));
+            for archive in cached_node_archives(version)? {
+                plan.push(Change::Delete(archive));
+            }
+            plan.push(Change::Update {
+                file: user_catalog_file()?,
+                what: format!("forget node v{}", version),
+            });
+        }
+        Ok(plan)
+    }
+
     // ISSUE (#87) Abstract Catalog's activate, install and uninstall methods
     // And potentially share code between node and yarn
-    /// Sets the Yarn version in the user toolchain to one matching the specified semantic versioning requirements.
-    pub fn set_user_yarn(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<()> {
+    /// Sets the user's default Yarn version, which is used outside of projects that
+    /// select a version of their own, to one matching the specified semantic versioning
+    /// requirements. This never changes a project's pins.
+    pub fn set_default_yarn(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<()> {
         let fetched = self.fetch_yarn(matching, config)?;
         let version = Some(fetched.into_version());
 
@@ -174,12 +871,20 @@ impl Catalog {
 
     /// Fetches a Yarn version matching the specified semantic versioning requirements.
     pub fn fetch_yarn(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<Fetched> {
-        let distro = self.yarn.resolve_remote(&matching, config.yarn.as_ref())?;
-        let fetched = distro.fetch(&self.yarn).unknown()?;
+        if env::offline() {
+            if let Some(version) = self.yarn.resolve_local(matching) {
+                return Ok(Fetched::Already(version));
+            }
+        }
+
+        let distro = self.yarn.resolve_remote(&matching, config, &Trace::disabled())?;
+        let fetched = distro.fetch("yarn", &self.yarn, config).unknown()?;
 
         if let &Fetched::Now(ref version) = &fetched {
             self.yarn.versions.insert(version.clone());
             self.save()?;
+            cache::discard_installed(config, cached_archives("yarn", version)?);
+            cache::enforce_limits(config)?;
         }
 
         Ok(fetched)
@@ -187,14 +892,17 @@ impl Catalog {
 
     /// Resolves a Yarn version matching the specified semantic versioning requirements.
     pub fn resolve_yarn(&self, matching: &VersionSpec, config: &Config) -> Fallible<Version> {
-        let distro = self.yarn.resolve_remote(&matching, config.yarn.as_ref())?;
+        let distro = self.yarn.resolve_remote(&matching, config, &Trace::disabled())?;
         Ok(distro.version().clone())
     }
 
     /// Uninstalls a specific Yarn version from the local catalog.
     pub fn uninstall_yarn(&mut self, version: &Version) -> Fallible<()> {
+        if self.yarn.system.contains(version) {
+            throw!(SystemVersionError::new("yarn", version));
+        }
         if self.yarn.contains(version) {
-            let home = path::yarn_version_dir(&version.to_string())?;
+            let home = path::user_yarn_version_dir(&version.to_string())?;
 
             if !home.is_dir() {
                 Err(io::Error::new(
@@ -212,22 +920,348 @@ impl Catalog {
 
         Ok(())
     }
+
+    /// Plans the changes of `uninstall_yarn`, without making them.
+    pub fn plan_uninstall_yarn(&self, version: &Version) -> Fallible<Plan> {
+        if self.yarn.system.contains(version) {
+            throw!(SystemVersionError::new("yarn", version));
+        }
+        let mut plan = Plan::new();
+        if self.yarn.contains(version) {
+            plan.push(Change::Delete(path::user_yarn_version_dir(&version.to_string())?));
+            plan.push(Change::Update {
+                file: user_catalog_file()?,
+                what: format!("forget yarn v{}", version),
+            });
+        }
+        Ok(plan)
+    }
+
+    /// Fetches a version of each of the specified tools, downloading and unpacking them
+    /// concurrently. Specs that resolve to the same version are only fetched once. The
+    /// results are produced in the same order as the specs.
+    pub fn fetch_many(&mut self, specs: &[ToolSpec], config: &Config) -> Fallible<Vec<Fetched>> {
+        // Resolve every spec up front on this thread, so that only the downloads run
+        // concurrently.
+        let mut results: Vec<Result<usize, Version>> = Vec::new();
+        let mut pending: Vec<PendingFetch> = Vec::new();
+        // The prerelease channel each pending Node fetch was resolved from, if any.
+        let mut channels: Vec<Option<Channel>> = Vec::new();
+        for spec in specs {
+            let resolved = match *spec {
+                ToolSpec::Node(ref matching) => {
+                    resolve_pending(&self.node, matching, config)?.map(PendingFetch::Node)
+                }
+                ToolSpec::Yarn(ref matching) => {
+                    resolve_pending(&self.yarn, matching, config)?.map(PendingFetch::Yarn)
+                }
+                ToolSpec::Npm(ref matching) => {
+                    resolve_pending(&self.npm, matching, config)?.map(PendingFetch::Npm)
+                }
+                ToolSpec::Pnpm(ref matching) => {
+                    resolve_pending(&self.pnpm, matching, config)?.map(PendingFetch::Pnpm)
+                }
+            };
+            results.push(match resolved {
+                Ok(distro) => {
+                    let key = distro.key();
+                    match pending.iter().position(|other| other.key() == key) {
+                        Some(index) => Ok(index),
+                        None => {
+                            pending.push(distro);
+                            channels.push(match *spec {
+                                ToolSpec::Node(VersionSpec::Channel(channel)) => Some(channel),
+                                _ => None,
+                            });
+                            Ok(pending.len() - 1)
+                        }
+                    }
+                }
+                Err(version) => Err(version),
+            });
+        }
+
+        let keys: Vec<(&'static str, Version)> = pending.iter().map(|distro| distro.key()).collect();
+        let fetched: Vec<Fallible<Fetched>> = {
+            let catalog: &Catalog = self;
+            let hooks = config.lifecycle.as_ref();
+            let mut display = ProgressDisplay::new();
+            let bars: Vec<ProgressBar> = pending
+                .iter()
+                .map(|distro| display.add(distro.progress_bar()))
+                .collect();
+
+            crossbeam::scope(|scope| {
+                let handles: Vec<_> = pending
+                    .into_iter()
+                    .zip(bars.into_iter())
+                    .map(|(distro, bar)| {
+                        scope.spawn(move || {
+                            let result = distro.fetch(catalog, bar.clone(), hooks);
+                            // The display waits for every bar to finish, including the
+                            // bars of fetches that failed or turned out to be unnecessary.
+                            bar.finish_and_clear();
+                            result
+                        })
+                    })
+                    .collect();
+                display.join_and_clear();
+                handles.into_iter().map(|handle| handle.join()).collect()
+            })
+        };
+
+        let mut done: Vec<Fetched> = Vec::new();
+        let mut installed_archives: Vec<PathBuf> = Vec::new();
+        for (index, (result, &(tool, _))) in fetched.into_iter().zip(keys.iter()).enumerate() {
+            let fetched = result?;
+            if let Fetched::Now(ref version) = fetched {
+                installed_archives.extend(cached_archives(tool, version)?);
+                if let Some(channel) = channels[index] {
+                    self.node.channels.insert(version.clone(), channel);
+                }
+                if tool == "node" {
+                    self.record_bundled_npm(version);
+                }
+                match tool {
+                    "node" => self.node.versions.insert(version.clone()),
+                    "yarn" => self.yarn.versions.insert(version.clone()),
+                    "npm" => self.npm.versions.insert(version.clone()),
+                    "pnpm" => self.pnpm.versions.insert(version.clone()),
+                    _ => unreachable!(),
+                };
+            }
+            done.push(fetched);
+        }
+        if done.iter().any(|fetched| match *fetched {
+            Fetched::Now(_) => true,
+            Fetched::Already(_) => false,
+        }) {
+            self.save()?;
+            cache::discard_installed(config, installed_archives);
+            cache::enforce_limits(config)?;
+            dedupe::after_fetch(config)?;
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|result| match result {
+                Ok(index) => match done[index] {
+                    Fetched::Now(ref version) => Fetched::Now(version.clone()),
+                    Fetched::Already(ref version) => Fetched::Already(version.clone()),
+                },
+                Err(version) => Fetched::Already(version),
+            })
+            .collect())
+    }
+
+    /// Fetches an npm version matching the specified semantic versioning requirements.
+    pub fn fetch_npm(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<Fetched> {
+        if env::offline() {
+            if let Some(version) = self.npm.resolve_local(matching) {
+                return Ok(Fetched::Already(version));
+            }
+        }
+
+        let distro = self.npm.resolve_remote(&matching, config, &Trace::disabled())?;
+        let fetched = distro.fetch("npm", &self.npm, config).unknown()?;
+
+        if let &Fetched::Now(ref version) = &fetched {
+            self.npm.versions.insert(version.clone());
+            self.save()?;
+            cache::discard_installed(config, cached_archives("npm", version)?);
+            cache::enforce_limits(config)?;
+        }
+
+        Ok(fetched)
+    }
+
+    /// Fetches a pnpm version matching the specified semantic versioning requirements.
+    pub fn fetch_pnpm(&mut self, matching: &VersionSpec, config: &Config) -> Fallible<Fetched> {
+        if env::offline() {
+            if let Some(version) = self.pnpm.resolve_local(matching) {
+                return Ok(Fetched::Already(version));
+            }
+        }
+
+        let distro = self.pnpm.resolve_remote(&matching, config, &Trace::disabled())?;
+        let fetched = distro.fetch("pnpm", &self.pnpm, config).unknown()?;
+
+        if let &Fetched::Now(ref version) = &fetched {
+            self.pnpm.versions.insert(version.clone());
+            self.save()?;
+            cache::discard_installed(config, cached_archives("pnpm", version)?);
+            cache::enforce_limits(config)?;
+        }
+
+        Ok(fetched)
+    }
 }
 
 /// Thrown when there is no Node version matching a requested semver specifier.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "No Node version found for {}", matching)]
-#[notion_fail(code = "NoVersionMatch")]
+#[notion_fail(code = "NoVersionMatch", suggest_with = "suggest", error_code = "VERSION_NOT_FOUND")]
 struct NoNodeVersionFoundError {
     matching: VersionSpec,
+    /// The available version closest to the one requested, if one is close.
+    closest: Option<Version>,
+}
+
+impl NoNodeVersionFoundError {
+    fn suggest(&self) -> Option<String> {
+        Some(match self.closest {
+            Some(ref closest) => format!("did you mean node@{}?", closest),
+            None => "run `notion ls-remote` to see the available versions".to_string(),
+        })
+    }
+}
+
+/// Thrown when none of the unofficial builds of Node for musl-based systems matches a
+/// requested semver specifier.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "No Node version found for {} with a build for {}\nThe official Node distributions don't support musl-based systems like Alpine Linux, so Notion uses the unofficial builds, which don't cover every version.",
+    matching, platform
+)]
+#[notion_fail(code = "NoVersionMatch", error_code = "VERSION_NOT_FOUND")]
+struct NoMuslNodeBuildError {
+    matching: VersionSpec,
+    platform: String,
 }
 
 /// Thrown when there is no Yarn version matching a requested semver specifier.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "No Yarn version found for {}", matching)]
-#[notion_fail(code = "NoVersionMatch")]
+#[notion_fail(code = "NoVersionMatch", suggest_with = "suggest", error_code = "VERSION_NOT_FOUND")]
 struct NoYarnVersionFoundError {
-    matching: VersionReq,
+    matching: VersionSpec,
+    /// The available version closest to the one requested, if one is close.
+    closest: Option<Version>,
+}
+
+impl NoYarnVersionFoundError {
+    fn suggest(&self) -> Option<String> {
+        Some(match self.closest {
+            Some(ref closest) => format!("did you mean yarn@{}?", closest),
+            None => "run `notion ls-remote yarn` to see the available versions".to_string(),
+        })
+    }
+}
+
+/// Finds the newest of the available versions that shares the major and minor version
+/// of the one requested, or failing that its major version, to suggest in its place.
+/// The requested version is read from the digits of the requirement, so that a typo
+/// like `10.15.9` suggests `10.15.3`.
+fn closest_version<'a, I>(matching: &str, available: I) -> Option<Version>
+where
+    I: IntoIterator<Item = &'a Version>,
+{
+    let mut numbers = matching
+        .trim_left_matches(|c: char| !c.is_ascii_digit())
+        .split('.')
+        .map(|number| number.parse::<u64>());
+    let major = numbers.next()?.ok()?;
+    let minor = numbers.next().and_then(|minor| minor.ok());
+
+    let available: Vec<&Version> = available.into_iter().filter(|v| v.pre.is_empty()).collect();
+    let same_minor = available
+        .iter()
+        .filter(|v| v.major == major && Some(v.minor) == minor)
+        .max();
+    let same_major = available.iter().filter(|v| v.major == major).max();
+    same_minor.or(same_major).map(|v| (*v).clone())
+}
+
+/// Thrown when there is no npm version matching a requested semver specifier.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No npm version found for {}", matching)]
+#[notion_fail(code = "NoVersionMatch", error_code = "VERSION_NOT_FOUND")]
+struct NoNpmVersionFoundError {
+    matching: VersionSpec,
+}
+
+/// Thrown when there is no pnpm version matching a requested semver specifier.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No pnpm version found for {}", matching)]
+#[notion_fail(code = "NoVersionMatch", error_code = "VERSION_NOT_FOUND")]
+struct NoPnpmVersionFoundError {
+    matching: VersionSpec,
+}
+
+/// Thrown when an LTS alias is used for a tool other than Node.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "LTS aliases are only supported for Node, not {}", tool)]
+#[notion_fail(code = "NoVersionMatch")]
+struct LtsNotSupportedError {
+    tool: String,
+}
+
+/// Thrown when a local Node archive isn't named as Node publishes its archives for
+/// this platform.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not a Node archive for this platform\nexpected a file named {}", file, expected)]
+#[notion_fail(code = "InvalidArguments")]
+struct LocalArchiveNameError {
+    file: String,
+    expected: String,
+}
+
+/// Thrown when fetching a custom build of a Node version that is already installed
+/// from elsewhere.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Node v{} is already installed from elsewhere\nuninstall it first to install a custom build of it", version)]
+#[notion_fail(code = "InvalidArguments")]
+struct CustomBuildConflictError {
+    version: String,
+}
+
+/// Thrown when a local Node archive contains a version other than the one requested.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} contains Node {}, which doesn't match {}", file, version, matching)]
+#[notion_fail(code = "NoVersionMatch")]
+struct LocalArchiveVersionError {
+    file: String,
+    version: String,
+    matching: VersionSpec,
+}
+
+/// Thrown when uninstalling a version that only the read-only system install provides.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "{} v{} is provided by the system install, which is read-only",
+    tool, version
+)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct SystemVersionError {
+    tool: String,
+    version: String,
+}
+
+impl SystemVersionError {
+    fn new(tool: &str, version: &Version) -> Self {
+        SystemVersionError {
+            tool: tool.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+/// Thrown when a prerelease channel is used for a tool other than Node.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The {} channel is only supported for Node, not {}", channel, tool)]
+#[notion_fail(code = "NoVersionMatch")]
+struct ChannelNotSupportedError {
+    channel: Channel,
+    tool: String,
+}
+
+/// Thrown when a dist-tag is used for a tool that isn't published on the npm registry.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Dist-tags like '{}' are not supported for {}", tag, tool)]
+#[notion_fail(code = "NoVersionMatch")]
+struct TagNotSupportedError {
+    tag: String,
+    tool: String,
 }
 
 impl<D: Distro> Collection<D> {
@@ -235,32 +1269,93 @@ impl<D: Distro> Collection<D> {
     pub fn contains(&self, version: &Version) -> bool {
         self.versions.contains(version)
     }
+
+    /// Resolves the specified semantic versioning requirements against the versions
+    /// already in this Collection, producing the newest match, if any.
+    pub fn resolve_local(&self, matching: &VersionSpec) -> Option<Version> {
+        match *matching {
+            VersionSpec::Latest => self.versions
+                .iter()
+                .rev()
+                .find(|version| !self.channels.contains_key(version))
+                .cloned(),
+            // Semantic versioning ranges only match prereleases, like the builds of the
+            // prerelease channels, when they name one explicitly.
+            VersionSpec::Semver(ref matching) => self.versions
+                .iter()
+                .rev()
+                .find(|version| matching.matches(version))
+                .cloned(),
+            // Which versions are LTS releases, or carry a tag, is only known remotely.
+            VersionSpec::Lts(_) | VersionSpec::Tag(_) => None,
+            VersionSpec::Channel(channel) => self.versions
+                .iter()
+                .rev()
+                .find(|version| self.channels.get(version) == Some(&channel))
+                .cloned(),
+        }
+    }
 }
 
 pub trait Resolve<D: Distro> {
-    /// Resolves the specified semantic versioning requirements from a remote distributor.
+    /// Resolves the specified semantic versioning requirements from a remote distributor,
+    /// recording which distributor was consulted in `trace`.
     fn resolve_remote(
         &self,
         matching: &VersionSpec,
-        config: Option<&ToolConfig<D>>,
+        config: &Config,
+        trace: &Trace,
     ) -> Fallible<D> {
-        match config {
+        let distro = match self.tool_config(config) {
             Some(ToolConfig {
                 resolve: Some(ref plugin),
+                resolve_ttl,
                 ..
-            }) => plugin.resolve(matching),
-            _ => self.resolve_public(matching),
-        }
+            }) => {
+                trace.record(|| match plugin {
+                    &ResolvePlugin::Url(ref url) => {
+                        format!("consulted the resolve plugin at {}", url)
+                    }
+                    &ResolvePlugin::Bin(ref bin) => {
+                        format!("consulted the resolve plugin `{}`", bin)
+                    }
+                });
+                plugin.resolve(
+                    self.tool_name(),
+                    matching,
+                    self.installed(),
+                    resolve_ttl,
+                    config,
+                )?
+            }
+            _ => {
+                trace.record(|| "consulted the public version index".to_string());
+                self.resolve_public(matching, config)?
+            }
+        };
+        trace.record(|| format!("resolved {} to {}", matching, distro.version()));
+        event::record_resolved(self.tool_name(), &distro.version().to_string());
+        Ok(distro)
     }
 
+    /// The name of this collection's tool, as resolve plugins know it.
+    fn tool_name(&self) -> &'static str;
+
+    /// The versions of this collection's tool that are installed, which a resolve
+    /// plugin's candidates are picked from first.
+    fn installed(&self) -> &BTreeSet<Version>;
+
+    /// Selects the configuration settings for this collection's tool, if any.
+    fn tool_config<'a>(&self, config: &'a Config) -> Option<&'a ToolConfig<D>>;
+
     /// Resolves the specified semantic versioning requirements from the public distributor (e.g. `https://nodejs.org`).
-    fn resolve_public(&self, matching: &VersionSpec) -> Fallible<D>;
+    fn resolve_public(&self, matching: &VersionSpec, config: &Config) -> Fallible<D>;
 }
 
 /// Thrown when the public registry for Node or Yarn could not be downloaded.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "Could not fetch public registry\n{}", error)]
-#[notion_fail(code = "NetworkError")]
+#[notion_fail(code = "NetworkError", error_code = "REGISTRY_UNAVAILABLE")]
 pub(crate) struct RegistryFetchError {
     error: String,
 }
@@ -274,69 +1369,258 @@ impl RegistryFetchError {
 }
 
 impl Resolve<NodeDistro> for NodeCollection {
-    fn resolve_public(&self, matching: &VersionSpec) -> Fallible<NodeDistro> {
-        let version_opt = {
-            let index: Index = resolve_node_versions()?.into_index()?;
-            let mut entries = index.entries.into_iter();
-            let entry = match *matching {
-                VersionSpec::Latest => {
-                    // NOTE: This assumes the registry always produces a list in sorted order
-                    //       from newest to oldest. This should be specified as a requirement
-                    //       when we document the plugin API.
-                    entries.next()
-                }
-                VersionSpec::Semver(ref matching) => {
-                    // ISSUE #34: also make sure this OS is available for this version
-                    entries.find(|&(ref k, _)| matching.matches(k))
-                }
-            };
-            entry.map(|(k, _)| k)
-        };
+    fn tool_name(&self) -> &'static str {
+        "node"
+    }
+
+    fn installed(&self) -> &BTreeSet<Version> {
+        &self.versions
+    }
 
-        if let Some(version) = version_opt {
-            NodeDistro::public(version)
+    fn tool_config<'a>(&self, config: &'a Config) -> Option<&'a ToolConfig<NodeDistro>> {
+        config.node.as_ref()
+    }
+
+    fn resolve_public(&self, matching: &VersionSpec, config: &Config) -> Fallible<NodeDistro> {
+        if let VersionSpec::Channel(channel) = *matching {
+            return resolve_node_channel(channel, config);
+        }
+        if let VersionSpec::Tag(ref tag) = *matching {
+            throw!(TagNotSupportedError {
+                tag: tag.clone(),
+                tool: "Node".to_string(),
+            });
+        }
+
+        let index: Index = resolve_node_versions(config)?.into_index()?;
+        // The unofficial builds for musl-based systems don't cover every version
+        // and architecture, so only consider the versions built for this one.
+        let musl = path::is_musl();
+        let platform = path::node_platform();
+        let available: Vec<(Version, VersionData)> = index
+            .entries
+            .into_iter()
+            .filter(|&(_, ref data)| !musl || data.files.contains(&platform))
+            .collect();
+
+        if let Some(version) = select_node_version(&available, matching) {
+            NodeDistro::public(version, config)
+        } else if musl {
+            throw!(NoMuslNodeBuildError {
+                matching: matching.clone(),
+                platform,
+            })
         } else {
+            let closest = match *matching {
+                VersionSpec::Semver(ref req) => closest_version(
+                    &req.to_string(),
+                    available.iter().map(|&(ref version, _)| version),
+                ),
+                _ => None,
+            };
             throw!(NoNodeVersionFoundError {
-                matching: matching.clone()
+                matching: matching.clone(),
+                closest,
             })
         }
     }
 }
 
 impl Resolve<YarnDistro> for YarnCollection {
+    fn tool_name(&self) -> &'static str {
+        "yarn"
+    }
+
+    fn installed(&self) -> &BTreeSet<Version> {
+        &self.versions
+    }
+
+    fn tool_config<'a>(&self, config: &'a Config) -> Option<&'a ToolConfig<YarnDistro>> {
+        config.yarn.as_ref()
+    }
+
     /// Resolves the specified semantic versioning requirements from the public distributor.
-    fn resolve_public(&self, matching: &VersionSpec) -> Fallible<YarnDistro> {
+    fn resolve_public(&self, matching: &VersionSpec, config: &Config) -> Fallible<YarnDistro> {
         let version = match *matching {
             VersionSpec::Latest => {
-                let mut response: reqwest::Response = reqwest::get(PUBLIC_YARN_LATEST_VERSION)
+                http::ensure_online(PUBLIC_YARN_LATEST_VERSION)?;
+                let mut response: reqwest::Response = http::get(PUBLIC_YARN_LATEST_VERSION, config)
                     .with_context(RegistryFetchError::from_error)?;
-                response.text().unknown()?
+                http::ensure_not_throttled(PUBLIC_YARN_LATEST_VERSION, &response)?;
+                response.text().unknown()?.trim().to_string()
             }
             VersionSpec::Semver(ref matching) => {
-                let spinner = progress_spinner(&format!(
-                    "Fetching public registry: {}",
-                    PUBLIC_YARN_VERSION_INDEX
-                ));
-                let releases: Vec<String> = reqwest::get(PUBLIC_YARN_VERSION_INDEX)
-                    .with_context(RegistryFetchError::from_error)?
-                    .json()
-                    .unknown()?;
-                spinner.finish_and_clear();
-                let version = releases.into_iter().find(|v| {
-                    let v = Version::parse(v).unwrap();
-                    matching.matches(&v)
-                });
+                let releases: Vec<Version> = yarn_index::releases(config, false)?
+                    .into_iter()
+                    .map(|release| release.version)
+                    .collect();
+                let version = releases.iter().filter(|v| matching.matches(v)).max();
 
                 if let Some(version) = version {
-                    version
+                    version.to_string()
                 } else {
                     throw!(NoYarnVersionFoundError {
-                        matching: matching.clone(),
+                        matching: VersionSpec::Semver(matching.clone()),
+                        closest: closest_version(&matching.to_string(), &releases),
                     });
                 }
             }
+            VersionSpec::Lts(_) => {
+                throw!(LtsNotSupportedError {
+                    tool: "Yarn".to_string(),
+                });
+            }
+            VersionSpec::Channel(channel) => {
+                throw!(ChannelNotSupportedError {
+                    channel,
+                    tool: "Yarn".to_string(),
+                });
+            }
+            VersionSpec::Tag(ref tag) => {
+                let resolved = if tag == "berry" {
+                    resolve_registry_version(PUBLIC_YARN_BERRY_INDEX, &VersionSpec::Latest, config)?
+                } else {
+                    resolve_registry_version(PUBLIC_YARN_REGISTRY_INDEX, matching, config)?
+                };
+                match resolved {
+                    Some(version) => version.to_string(),
+                    None => throw!(NoYarnVersionFoundError {
+                        matching: matching.clone(),
+                        closest: None,
+                    }),
+                }
+            }
         };
-        YarnDistro::public(Version::parse(&version).unknown()?)
+        YarnDistro::public(Version::parse(&version).unknown()?, config)
+    }
+}
+
+impl Resolve<NpmDistro> for NpmCollection {
+    fn tool_name(&self) -> &'static str {
+        "npm"
+    }
+
+    fn installed(&self) -> &BTreeSet<Version> {
+        &self.versions
+    }
+
+    fn tool_config<'a>(&self, config: &'a Config) -> Option<&'a ToolConfig<NpmDistro>> {
+        config.npm.as_ref()
+    }
+
+    /// Resolves the specified semantic versioning requirements from the public npm registry.
+    fn resolve_public(&self, matching: &VersionSpec, config: &Config) -> Fallible<NpmDistro> {
+        if let Some(version) = resolve_registry_version(PUBLIC_NPM_VERSION_INDEX, matching, config)? {
+            NpmDistro::public(version, config)
+        } else {
+            throw!(NoNpmVersionFoundError {
+                matching: matching.clone()
+            })
+        }
+    }
+}
+
+impl Resolve<PnpmDistro> for PnpmCollection {
+    fn tool_name(&self) -> &'static str {
+        "pnpm"
+    }
+
+    fn installed(&self) -> &BTreeSet<Version> {
+        &self.versions
+    }
+
+    fn tool_config<'a>(&self, config: &'a Config) -> Option<&'a ToolConfig<PnpmDistro>> {
+        config.pnpm.as_ref()
+    }
+
+    /// Resolves the specified semantic versioning requirements from the public npm registry.
+    fn resolve_public(&self, matching: &VersionSpec, config: &Config) -> Fallible<PnpmDistro> {
+        if let Some(version) = resolve_registry_version(PUBLIC_PNPM_VERSION_INDEX, matching, config)? {
+            PnpmDistro::public(version, config)
+        } else {
+            throw!(NoPnpmVersionFoundError {
+                matching: matching.clone()
+            })
+        }
+    }
+}
+
+/// Finds the newest version of a package on the npm registry matching the specified
+/// semantic versioning requirements (or the version with the specified dist-tag),
+/// given the URL of the package's metadata.
+fn resolve_registry_version(
+    package_url: &str,
+    matching: &VersionSpec,
+    config: &Config,
+) -> Fallible<Option<Version>> {
+    http::ensure_online(package_url)?;
+    let spinner = progress_spinner(&format!("Fetching public registry: {}", package_url));
+    // The abbreviated metadata format omits the full manifest of every version.
+    let mut headers = Headers::new();
+    headers.set_raw("Accept", "application/vnd.npm.install-v1+json");
+    let client = http::client_for(package_url, config).with_context(RegistryFetchError::from_error)?;
+    let mut response = http::retry(package_url, config, http::is_transient, || {
+        http::throttled(package_url, || {
+            http::traced("GET", package_url, Some(&headers), || {
+                client.get(package_url).headers(headers.clone()).send()
+            })
+        })
+    }).with_context(RegistryFetchError::from_error)?;
+    http::ensure_not_throttled(package_url, &response)?;
+    let index: serial::NpmIndex = response.json().unknown()?;
+    spinner.finish_and_clear();
+
+    Ok(match *matching {
+        VersionSpec::Latest => match index.dist_tags.get("latest") {
+            Some(latest) => Some(Version::parse(latest).unknown()?),
+            None => None,
+        },
+        VersionSpec::Tag(ref tag) => index
+            .dist_tags
+            .get(tag)
+            .and_then(|tagged| Version::parse(tagged).ok()),
+        VersionSpec::Semver(ref matching) => index
+            .versions
+            .keys()
+            .filter_map(|v| Version::parse(v).ok())
+            .filter(|v| matching.matches(v))
+            .max(),
+        VersionSpec::Lts(_) => {
+            throw!(LtsNotSupportedError {
+                tool: package_url.rsplit('/').next().unwrap_or(package_url).to_string(),
+            });
+        }
+        VersionSpec::Channel(channel) => {
+            throw!(ChannelNotSupportedError {
+                channel,
+                tool: package_url.rsplit('/').next().unwrap_or(package_url).to_string(),
+            });
+        }
+    })
+}
+
+/// Resolves the newest build published on one of Node's prerelease channels. Channel
+/// builds come and go quickly, so the channel's index is never cached.
+fn resolve_node_channel(channel: Channel, config: &Config) -> Fallible<NodeDistro> {
+    let index_url = format!(
+        "{}{}",
+        node::channel_server_root(channel),
+        NODE_VERSION_INDEX_FILE
+    );
+    http::ensure_online(&index_url)?;
+    let spinner = progress_spinner(&format!("Fetching public registry: {}", index_url));
+    let mut response = http::get(&index_url, config).with_context(RegistryFetchError::from_error)?;
+    http::ensure_not_throttled(&index_url, &response)?;
+    let serial: serial::Index = response.json().unknown()?;
+    spinner.finish_and_clear();
+
+    let index: Index = serial.into_index()?;
+    match index.entries.into_iter().map(|(version, _)| version).max() {
+        Some(version) => NodeDistro::channel(version, channel, config),
+        None => throw!(NoNodeVersionFoundError {
+            matching: VersionSpec::Channel(channel),
+            closest: None,
+        }),
     }
 }
 
@@ -348,30 +1632,258 @@ pub struct Index {
 /// The set of available files on the public Node server for a given Node version.
 pub struct VersionData {
     pub files: HashSet<String>,
+    /// The name of the LTS release line this version belongs to, if any.
+    pub lts: Option<String>,
+    /// The release date of this version (`YYYY-MM-DD`), if the index records it.
+    pub date: Option<String>,
+    /// Whether this version is a security release.
+    pub security: bool,
+    /// The version of npm bundled with this version, if the index records it.
+    pub npm: Option<Version>,
+}
+
+impl Catalog {
+    /// Returns the version of npm bundled with an installed version of Node: the one
+    /// recorded when it was fetched or, for versions fetched before Notion recorded
+    /// it, the one in the cached Node index, if any.
+    pub fn bundled_npm(&self, node: &Version) -> Option<Version> {
+        self.node
+            .bundled_npm
+            .get(node)
+            .cloned()
+            .or_else(|| indexed_bundled_npm(node))
+    }
+
+    /// Records the version of npm bundled with a version of Node that was just fetched.
+    fn record_bundled_npm(&mut self, node: &Version) {
+        if let Some(npm) = indexed_bundled_npm(node) {
+            self.node.bundled_npm.insert(node.clone(), npm);
+        }
+    }
+}
+
+/// Looks up the version of npm bundled with a version of Node in the cached Node
+/// index. The index is never downloaded for this, so nothing is found without it.
+fn indexed_bundled_npm(node: &Version) -> Option<Version> {
+    let serial = match read_cached_opt() {
+        Ok(Some(CachedIndex::Fresh(serial))) | Ok(Some(CachedIndex::Stale(serial))) => serial,
+        _ => return None,
+    };
+    serial
+        .into_index()
+        .ok()?
+        .entries
+        .into_iter()
+        .find(|&(ref version, _)| version == node)
+        .and_then(|(_, data)| data.npm)
+}
+
+impl Catalog {
+    /// Parses a catalog file, upgrading it to the current layout first if it has an
+    /// older one, and returns whether it did.
+    fn parse(src: &str) -> Fallible<(Catalog, bool)> {
+        let mut table: toml::value::Table = toml::from_str(src).unknown()?;
+        let migrated = migrate::migrate(&mut table)?;
+        let serial: serial::Catalog = toml::Value::Table(table).try_into().unknown()?;
+        Ok((serial.into_catalog()?, migrated))
+    }
 }
 
 impl FromStr for Catalog {
     type Err = NotionError;
 
     fn from_str(src: &str) -> Result<Self, Self::Err> {
-        let serial: serial::Catalog = toml::from_str(src).unknown()?;
-        Ok(serial.into_catalog()?)
+        Catalog::parse(src).map(|(catalog, _)| catalog)
+    }
+}
+
+/// Determines the version of Node in a local archive from its file name, which must be
+/// one of the names Node publishes the archive of a version for this platform under
+/// (e.g. `node-v10.8.0-linux-x64.tar.gz`).
+fn local_archive_version(file: &Path) -> Option<Version> {
+    let name = file.file_name()?.to_str()?;
+    let prefix = "node-v";
+    let suffix = format!("-{}.", path::node_platform());
+    if !name.starts_with(prefix) {
+        return None;
+    }
+    let rest = &name[prefix.len()..];
+    let version = &rest[..rest.find(&suffix[..])?];
+    let published = Some(path::node_archive_file(version))
+        .into_iter()
+        .chain(path::node_xz_archive_file(version))
+        .any(|archive_file| archive_file == name);
+    if published {
+        Version::parse(version).ok()
+    } else {
+        None
+    }
+}
+
+/// Picks the newest release of each major line out of entries sorted newest first,
+/// keeping the newest line, which is the current one, and the LTS lines.
+fn recent_releases(entries: Vec<(Version, VersionData)>) -> Vec<(Version, VersionData)> {
+    let mut releases: Vec<(Version, VersionData)> = vec![];
+    for (version, data) in entries {
+        if !version.pre.is_empty() {
+            continue;
+        }
+        if releases.iter().any(|&(ref seen, _)| seen.major == version.major) {
+            continue;
+        }
+        if releases.is_empty() || data.lts.is_some() {
+            releases.push((version, data));
+        }
+    }
+    releases
+}
+
+/// Produces the archives of a version of a tool that are in the cache.
+fn cached_archives(tool: &str, version: &Version) -> Fallible<Vec<PathBuf>> {
+    let version_string = version.to_string();
+    let archive = match tool {
+        "node" => return cached_node_archives(version),
+        "yarn" => path::yarn_cache_dir()?.join(path::yarn_archive_file(&version_string)),
+        "npm" => path::npm_cache_dir()?.join(path::npm_archive_file(&version_string)),
+        "pnpm" => path::pnpm_cache_dir()?.join(path::pnpm_archive_file(&version_string)),
+        _ => unreachable!(),
+    };
+    Ok(if archive.is_file() { vec![archive] } else { vec![] })
+}
+
+/// Selects the version of Node that the specified requirements resolve to among the
+/// entries of an index of releases. Prerelease channels and tags aren't listed in
+/// the index, so they never resolve.
+fn select_node_version(
+    entries: &[(Version, VersionData)],
+    matching: &VersionSpec,
+) -> Option<Version> {
+    let mut entries = entries.iter();
+    let entry = match *matching {
+        VersionSpec::Latest => {
+            // Don't rely on the order of the index, which may come from a mirror.
+            entries.max_by(|&&(ref a, _), &&(ref b, _)| a.cmp(b))
+        }
+        VersionSpec::Semver(ref matching) => {
+            // ISSUE #34: also make sure this OS is available for this version
+            entries.find(|&&(ref k, _)| matching.matches(k))
+        }
+        VersionSpec::Lts(None) => entries.find(|&&(_, ref data)| data.lts.is_some()),
+        VersionSpec::Lts(Some(ref name)) => entries.find(|&&(_, ref data)| {
+            data.lts
+                .as_ref()
+                .map(|lts| lts.to_lowercase() == *name)
+                .unwrap_or(false)
+        }),
+        VersionSpec::Channel(_) | VersionSpec::Tag(_) => None,
+    };
+    entry.map(|&(ref k, _)| k.clone())
+}
+
+/// Pairs each of the specified archives with its size in bytes.
+fn archive_sizes(archives: Vec<PathBuf>) -> Vec<(PathBuf, u64)> {
+    archives
+        .into_iter()
+        .map(|archive| {
+            let size = archive.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            (archive, size)
+        })
+        .collect()
+}
+
+/// Produces the archives of a version of Node that are in the cache.
+fn cached_node_archives(version: &Version) -> Fallible<Vec<PathBuf>> {
+    let version = version.to_string();
+    let archive_files = path::node_xz_archive_file(&version)
+        .into_iter()
+        .chain(Some(path::node_archive_file(&version)))
+        .chain(Some(path::node_source_archive_file(&version)))
+        .chain(path::node_emulated_arch().map(|arch| path::node_archive_file_for(&version, arch)));
+    let mut archives = vec![];
+    for archive_file in archive_files {
+        // A custom build's archive is named like a release's, but cached apart from them.
+        for dir in &[path::node_cache_dir()?, path::node_custom_cache_dir()?] {
+            let archive = dir.join(&archive_file);
+            if archive.is_file() {
+                archives.push(archive);
+            }
+        }
+    }
+    Ok(archives)
+}
+
+/// Adds the versions installed in a versions directory of the system install to a
+/// collection, marking those the user hasn't installed as the system's.
+fn add_system_versions<D: Distro>(collection: &mut Collection<D>, dir: &Path) -> Fallible<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in read_dir(dir).unknown()? {
+        let entry = entry.unknown()?;
+        if let Ok(version) = Version::parse(&entry.file_name().to_string_lossy()) {
+            if entry.path().is_dir() && collection.versions.insert(version.clone()) {
+                collection.system.insert(version);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the specified versions to a collection if their directories exist.
+fn merge_versions<D: Distro>(
+    collection: &mut Collection<D>,
+    versions: BTreeSet<Version>,
+    version_dir: fn(&str) -> Fallible<PathBuf>,
+) -> Fallible<()> {
+    for version in versions {
+        if !collection.contains(&version) && version_dir(&version.to_string())?.is_dir() {
+            collection.versions.insert(version);
+        }
     }
+    Ok(())
 }
 
-/// Reads a public index from the Node cache, if it exists and hasn't expired.
-fn read_cached_opt() -> Fallible<Option<serial::Index>> {
+/// A public index read from the Node cache.
+enum CachedIndex {
+    /// An index that hasn't expired.
+    Fresh(serial::Index),
+    /// An index that expired less than `STALE_INDEX_GRACE` seconds ago, which is good
+    /// enough to use while a fresh one is downloaded.
+    Stale(serial::Index),
+}
+
+/// Reads a public index from the Node cache, if it exists and hasn't expired (or only
+/// expired recently). In offline mode, an expired index is still better than none, so
+/// it is used anyway.
+fn read_cached_opt() -> Fallible<Option<CachedIndex>> {
+    if env::offline() {
+        let cached: Option<String> = read_file_opt(&path::node_index_file()?).unknown()?;
+        return match cached {
+            Some(string) => Ok(Some(CachedIndex::Fresh(
+                serde_json::de::from_str(&string).unknown()?,
+            ))),
+            None => Ok(None),
+        };
+    }
+
     let expiry: Option<String> = read_file_opt(&path::node_index_expiry_file()?).unknown()?;
 
     if let Some(string) = expiry {
         let expiry_date: HttpDate = HttpDate::from_str(&string).unknown()?;
-        let current_date: HttpDate = HttpDate::from(SystemTime::now());
+        let now = SystemTime::now();
+        let current_date: HttpDate = HttpDate::from(now);
+        let stale_date: HttpDate = HttpDate::from(now - Duration::from_secs(STALE_INDEX_GRACE));
 
-        if current_date < expiry_date {
+        if stale_date < expiry_date {
             let cached: Option<String> = read_file_opt(&path::node_index_file()?).unknown()?;
 
             if let Some(string) = cached {
-                return Ok(serde_json::de::from_str(&string).unknown()?);
+                let serial = serde_json::de::from_str(&string).unknown()?;
+                return Ok(Some(if current_date < expiry_date {
+                    CachedIndex::Fresh(serial)
+                } else {
+                    CachedIndex::Stale(serial)
+                }));
             }
         }
     }
@@ -393,53 +1905,343 @@ fn max_age(response: &reqwest::Response) -> u32 {
     4 * 60 * 60
 }
 
-fn resolve_node_versions() -> Result<serial::Index, NotionError> {
-    match read_cached_opt().unknown()? {
-        Some(serial) => Ok(serial),
-        None => {
-            let spinner = progress_spinner(&format!(
-                "Fetching public registry: {}",
-                PUBLIC_NODE_VERSION_INDEX
-            ));
-            let mut response: reqwest::Response = reqwest::get(PUBLIC_NODE_VERSION_INDEX)
-                .with_context(RegistryFetchError::from_error)?;
-            let response_text: String = response.text().unknown()?;
-            let cached: NamedTempFile = NamedTempFile::new().unknown()?;
+/// Determines when a freshly downloaded or revalidated public index expires: after
+/// the `node.index-ttl` setting if configured, otherwise as the server's caching
+/// headers say.
+fn index_expiry(response: &reqwest::Response, config: &Config) -> String {
+    let ttl = config.node.as_ref().and_then(|node| node.index_ttl);
 
-            // Block to borrow cached for cached_file.
-            {
-                let mut cached_file: &File = cached.as_file();
-                cached_file.write(response_text.as_bytes()).unknown()?;
-            }
+    if let Some(ttl) = ttl {
+        return HttpDate::from(SystemTime::now() + Duration::from_secs(ttl)).to_string();
+    }
 
-            let index_cache_file = path::node_index_file()?;
-            ensure_containing_dir_exists(&index_cache_file)?;
-            cached.persist(index_cache_file).unknown()?;
+    if let Some(expires_header) = response.headers().get::<Expires>() {
+        return expires_header.to_string();
+    }
 
-            let expiry: NamedTempFile = NamedTempFile::new().unknown()?;
+    let expiry_date = SystemTime::now() + Duration::from_secs(max_age(&response).into());
+    HttpDate::from(expiry_date).to_string()
+}
 
-            // Block to borrow expiry for expiry_file.
-            {
-                let mut expiry_file: &File = expiry.as_file();
+/// Reads the public Node index from the cache if it's still fresh, and otherwise
+/// downloads it. An expired index that was served with an `ETag` is revalidated with
+/// an `If-None-Match` request, so that it only has to be downloaded again if it has
+/// actually changed.
+///
+/// An index that expired recently is still used as it is, so that the command doesn't
+/// wait on the network, while a background process refreshes it for the commands after.
+///
+/// The index of a mirror on the filesystem (a `file://` URL) is always read afresh, as is
+/// the index listed by a `node.ls-remote` plugin, which takes the place of the public one.
+fn resolve_node_versions(config: &Config) -> Result<serial::Index, NotionError> {
+    if let Some(plugin) = config.node.as_ref().and_then(|node| node.ls_remote.as_ref()) {
+        log::debug(|| "listing the Node versions with the ls-remote plugin".to_string());
+        return plugin.fetch("node", config);
+    }
 
-                if let Some(expires_header) = response.headers().get::<Expires>() {
-                    write!(expiry_file, "{}", expires_header).unknown()?;
-                } else {
-                    let expiry_date =
-                        SystemTime::now() + Duration::from_secs(max_age(&response).into());
+    let index_url = format!("{}{}", node::server_root(config), NODE_VERSION_INDEX_FILE);
 
-                    write!(expiry_file, "{}", HttpDate::from(expiry_date)).unknown()?;
+    if let Some(index_path) = http::file_url_path(&index_url) {
+        log::debug(|| format!("reading the Node index from {}", index_path.display()));
+        let src = File::open(&index_path).unknown()?.read_into_string().unknown()?;
+        return Ok(serde_json::de::from_str(&src).unknown()?);
+    }
+
+    match read_cached_opt().unknown()? {
+        Some(CachedIndex::Fresh(serial)) => {
+            log::debug(|| "using the cached Node index".to_string());
+            Ok(serial)
+        }
+        Some(CachedIndex::Stale(serial)) => {
+            log::debug(|| "using the expired cached Node index while it is refreshed".to_string());
+            start_refresh();
+            Ok(serial)
+        }
+        None => profile::time(Phase::IndexFetch, || fetch_node_index(&index_url, config)),
+    }
+}
+
+/// Downloads the public Node index, or revalidates the cached one, and caches it. Each
+/// cache file is replaced atomically, so that concurrent readers (and writers) only
+/// ever see a complete index.
+fn fetch_node_index(index_url: &str, config: &Config) -> Fallible<serial::Index> {
+    http::ensure_online(index_url)?;
+    log::info(|| format!("fetching the Node index from {}", index_url));
+    let spinner = progress_spinner(&format!("Fetching public registry: {}", index_url));
+
+    let index_cache_file = path::node_index_file()?;
+    let index_etag_file = path::node_index_etag_file()?;
+    let cached: Option<String> = read_file_opt(&index_cache_file).unknown()?;
+    let etag: Option<String> = match cached {
+        Some(_) => read_file_opt(&index_etag_file).unknown()?,
+        None => None,
+    };
+
+    let mut headers = Headers::new();
+    if let Some(ref etag) = etag {
+        headers.set_raw("If-None-Match", etag.trim().to_string());
+    }
+    let client = http::client_for(index_url, config).with_context(RegistryFetchError::from_error)?;
+    let mut response: reqwest::Response = http::retry(index_url, config, http::is_transient, || {
+        http::throttled(index_url, || {
+            http::traced("GET", index_url, Some(&headers), || {
+                client.get(index_url).headers(headers.clone()).send()
+            })
+        })
+    }).with_context(RegistryFetchError::from_error)?;
+    http::ensure_not_throttled(index_url, &response)?;
+
+    let response_text: String = match cached {
+        Some(ref cached) if response.status() == StatusCode::NotModified => {
+            log::debug(|| "the cached Node index is still current".to_string());
+            cached.clone()
+        }
+        _ => {
+            let response_text: String = response.text().unknown()?;
+            write_atomic(&index_cache_file, &response_text)?;
+            match response.headers().get::<ETag>() {
+                Some(etag) => write_atomic(&index_etag_file, &etag.to_string())?,
+                None => {
+                    let _ = remove_file(&index_etag_file);
                 }
             }
+            response_text
+        }
+    };
 
-            let index_expiry_file = path::node_index_expiry_file()?;
-            ensure_containing_dir_exists(&index_expiry_file)?;
-            expiry.persist(index_expiry_file).unknown()?;
+    write_atomic(
+        &path::node_index_expiry_file()?,
+        &index_expiry(&response, config),
+    )?;
 
-            let serial: serial::Index = serde_json::de::from_str(&response_text).unknown()?;
+    let serial: serial::Index = serde_json::de::from_str(&response_text).unknown()?;
 
-            spinner.finish_and_clear();
-            Ok(serial)
-        }
+    spinner.finish_and_clear();
+    Ok(serial)
+}
+
+/// Starts refreshing the public Node index in a background process (`notion ls-remote
+/// --refresh`), printing nothing, unless a refresh was started in the last
+/// `INDEX_REFRESH_INTERVAL` seconds (so that a failing refresh isn't repeated by every
+/// command).
+fn start_refresh() {
+    let marker = match path::node_index_refresh_file() {
+        Ok(marker) => marker,
+        Err(_) => return,
+    };
+    let started_recently = marker
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map_or(false, |age| age < Duration::from_secs(INDEX_REFRESH_INTERVAL));
+    if started_recently || write_atomic(&marker, "").is_err() {
+        return;
+    }
+    let notion = match path::notion_file() {
+        Ok(notion) => notion,
+        Err(_) => return,
+    };
+    log::debug(|| "refreshing the Node index in the background".to_string());
+    let _ = Command::new(notion)
+        .args(&["ls-remote", "--refresh"])
+        .env("NOTION_NO_UPDATE_CHECK", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{add_system_versions, closest_version, local_archive_version, recent_releases,
+                select_node_version, Catalog, CustomBuild, VersionData};
+    use path;
+    use semver::Version;
+    use std::fs::{create_dir, File};
+    use std::path::Path;
+    use tempfile::tempdir;
+    use version::{Channel, VersionSpec};
+
+    #[test]
+    fn test_add_system_versions() {
+        let mut catalog: Catalog = "[node]\nversions = [\"8.9.4\"]\n".parse().unwrap();
+        let dir = tempdir().unwrap();
+        create_dir(dir.path().join("8.9.4")).unwrap();
+        create_dir(dir.path().join("10.15.3")).unwrap();
+        File::create(dir.path().join("6.0.0")).unwrap();
+        add_system_versions(&mut catalog.node, dir.path()).unwrap();
+
+        let v8 = Version::parse("8.9.4").unwrap();
+        let v10 = Version::parse("10.15.3").unwrap();
+        assert!(catalog.node.contains(&v8) && catalog.node.contains(&v10));
+        assert!(!catalog.node.contains(&Version::parse("6.0.0").unwrap()));
+        assert_eq!(catalog.node.system.iter().collect::<Vec<_>>(), vec![&v10]);
+
+        // Only the user's versions are saved.
+        let saved = catalog.to_string();
+        assert!(saved.contains("8.9.4") && !saved.contains("10.15.3"));
+        assert!(catalog.uninstall_node(&v10).is_err());
+    }
+
+    #[test]
+    fn test_parse_unversioned() {
+        let (catalog, migrated) =
+            Catalog::parse("[node]\ndefault = \"8.9.4\"\nversions = [\"8.9.4\"]\n").unwrap();
+        assert!(migrated);
+        assert!(catalog.node.contains(&Version::parse("8.9.4").unwrap()));
+
+        let saved = catalog.to_string();
+        assert!(saved.starts_with("schema-version = 1\n"));
+        assert!(!Catalog::parse(&saved).unwrap().1);
+    }
+
+    #[test]
+    fn test_bundled_npm() {
+        let src = "schema-version = 1\n\n[node]\nversions = [\"10.15.3\"]\n\n[node.bundled-npm]\n\"10.15.3\" = \"6.4.1\"\n";
+        let (catalog, _) = Catalog::parse(src).unwrap();
+        let node = Version::parse("10.15.3").unwrap();
+        assert_eq!(
+            catalog.node.bundled_npm.get(&node),
+            Some(&Version::parse("6.4.1").unwrap())
+        );
+        assert_eq!(
+            Catalog::parse(&catalog.to_string()).unwrap().0.node.bundled_npm,
+            catalog.node.bundled_npm
+        );
+    }
+
+    #[test]
+    fn test_custom_builds() {
+        let src = "schema-version = 1\n\n[node]\nversions = [\"12.0.0-patched.1\"]\n\n[node.custom.\"12.0.0-patched.1\"]\nlabel = \"tls-fix\"\nurl = \"https://builds.corp/node-v12.0.0-patched.1-linux-x64.tar.gz\"\nchecksum = \"abc123\"\n";
+        let (catalog, _) = Catalog::parse(src).unwrap();
+        let node = Version::parse("12.0.0-patched.1").unwrap();
+        assert_eq!(
+            catalog.node.custom.get(&node),
+            Some(&CustomBuild {
+                label: "tls-fix".to_string(),
+                url: "https://builds.corp/node-v12.0.0-patched.1-linux-x64.tar.gz".to_string(),
+                checksum: Some("abc123".to_string()),
+            })
+        );
+        assert_eq!(
+            Catalog::parse(&catalog.to_string()).unwrap().0.node.custom,
+            catalog.node.custom
+        );
+    }
+
+    #[test]
+    fn test_package_integrity() {
+        let src = "schema-version = 1\n\n[packages.cowsay]\nversion = \"1.3.1\"\nnode = \"10.15.3\"\nintegrity = \"sha512-BBBB\"\n\n[packages.typescript]\nversion = \"3.2.2\"\nnode = \"10.15.3\"\n";
+        let (catalog, _) = Catalog::parse(src).unwrap();
+        assert_eq!(
+            catalog.packages["cowsay"].integrity,
+            Some("sha512-BBBB".to_string())
+        );
+        assert_eq!(catalog.packages["typescript"].integrity, None);
+        assert_eq!(
+            Catalog::parse(&catalog.to_string()).unwrap().0.packages,
+            catalog.packages
+        );
+    }
+
+    #[test]
+    fn test_closest_version() {
+        let available: Vec<Version> = vec!["8.9.4", "10.15.1", "10.15.3", "10.16.0", "11.0.0-rc.1"]
+            .into_iter()
+            .map(|v| Version::parse(v).unwrap())
+            .collect();
+        let closest = |matching| closest_version(matching, &available).map(|v| v.to_string());
+
+        assert_eq!(closest("10.15.9"), Some("10.15.3".to_string()));
+        assert_eq!(closest("^10.17.0"), Some("10.16.0".to_string()));
+        assert_eq!(closest("8"), Some("8.9.4".to_string()));
+        assert_eq!(closest("11.0.0"), None);
+        assert_eq!(closest("latest"), None);
+    }
+
+    #[test]
+    fn test_recent_releases() {
+        let entries = vec![
+            ("13.8.0", None),
+            ("13.7.0", None),
+            ("12.16.1", Some("Erbium")),
+            ("12.16.0", Some("Erbium")),
+            ("11.15.0", None),
+            ("10.19.0", Some("Dubnium")),
+        ];
+        let entries = entries
+            .into_iter()
+            .map(|(version, lts)| {
+                (
+                    Version::parse(version).unwrap(),
+                    VersionData {
+                        files: Default::default(),
+                        lts: lts.map(|lts: &str| lts.to_string()),
+                        date: None,
+                        security: false,
+                        npm: None,
+                    },
+                )
+            })
+            .collect();
+        let releases: Vec<String> = recent_releases(entries)
+            .into_iter()
+            .map(|(version, _)| version.to_string())
+            .collect();
+        assert_eq!(releases, vec!["13.8.0", "12.16.1", "10.19.0"]);
+    }
+
+    #[test]
+    fn test_select_node_version() {
+        let entries: Vec<(Version, VersionData)> = vec![
+            ("12.16.1", Some("Erbium")),
+            ("13.8.0", None),
+            ("10.19.0", Some("Dubnium")),
+        ].into_iter()
+            .map(|(version, lts)| {
+                (
+                    Version::parse(version).unwrap(),
+                    VersionData {
+                        files: Default::default(),
+                        lts: lts.map(|lts: &str| lts.to_string()),
+                        date: None,
+                        security: false,
+                        npm: None,
+                    },
+                )
+            })
+            .collect();
+        let select = |matching: VersionSpec| {
+            select_node_version(&entries, &matching).map(|version| version.to_string())
+        };
+        assert_eq!(select(VersionSpec::Latest), Some("13.8.0".to_string()));
+        assert_eq!(select(VersionSpec::parse("10").unwrap()), Some("10.19.0".to_string()));
+        assert_eq!(
+            select(VersionSpec::Lts(Some("dubnium".to_string()))),
+            Some("10.19.0".to_string())
+        );
+        assert_eq!(select(VersionSpec::parse("9").unwrap()), None);
+        assert_eq!(select(VersionSpec::Channel(Channel::Rc)), None);
+    }
+
+    #[test]
+    fn test_local_archive_version() {
+        let archive = Path::new("/tmp").join(path::node_archive_file("10.8.0"));
+        assert_eq!(
+            local_archive_version(&archive),
+            Some(Version::parse("10.8.0").unwrap())
+        );
+        let prerelease = Path::new("/tmp").join(path::node_archive_file("11.0.0-rc.1"));
+        assert_eq!(
+            local_archive_version(&prerelease),
+            Some(Version::parse("11.0.0-rc.1").unwrap())
+        );
+        assert_eq!(local_archive_version(Path::new("/tmp/node.tar.gz")), None);
+        assert_eq!(
+            local_archive_version(Path::new("/tmp/node-v10.8.0-plan9-x64.tar.gz")),
+            None
+        );
     }
 }
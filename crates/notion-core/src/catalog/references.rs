@@ -0,0 +1,196 @@
+//! Provides the `References` type, which records the tool versions pinned by the
+//! projects Notion has seen, so that versions no project uses can be collected.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use semver::Version;
+use toml;
+
+use fs::{read_file_opt, write_atomic};
+use notion_fail::{Fallible, ResultExt};
+use path::references_file;
+
+/// The tool versions pinned by a project.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pins {
+    pub node: Option<Version>,
+    pub yarn: Option<Version>,
+}
+
+/// The tool versions pinned by each registered project, keyed by the file that
+/// pins them (`package.json` or a `.notion.toml` pin file).
+#[derive(Debug, Default)]
+pub struct References {
+    projects: BTreeMap<PathBuf, Pins>,
+}
+
+impl References {
+    /// Returns the references recorded in the user's references file.
+    pub fn current() -> Fallible<References> {
+        match read_file_opt(&references_file()?).unknown()? {
+            Some(src) => References::from_str(&src),
+            None => Ok(References::default()),
+        }
+    }
+
+    fn from_str(src: &str) -> Fallible<References> {
+        let serial: serial::References = toml::from_str(src).unknown()?;
+        let mut projects = BTreeMap::new();
+        for (file, pins) in serial.projects {
+            projects.insert(PathBuf::from(file), pins.into_pins()?);
+        }
+        Ok(References { projects })
+    }
+
+    /// Saves the references to the user's references file.
+    pub fn save(&self) -> Fallible<()> {
+        write_atomic(&references_file()?, &self.to_string())
+    }
+
+    fn to_string(&self) -> String {
+        let projects = self.projects
+            .iter()
+            .map(|(file, pins)| {
+                (
+                    file.to_string_lossy().into_owned(),
+                    serial::Pins::from_pins(pins),
+                )
+            })
+            .collect();
+        toml::to_string_pretty(&serial::References { projects }).unwrap()
+    }
+
+    /// Records the versions pinned by the specified toolchain file, replacing any
+    /// versions recorded for it before. Returns `true` if anything changed.
+    pub fn register(&mut self, toolchain_file: &Path, pins: Pins) -> bool {
+        if pins == Pins::default() {
+            return self.projects.remove(toolchain_file).is_some();
+        }
+        if self.projects.get(toolchain_file) == Some(&pins) {
+            return false;
+        }
+        self.projects.insert(toolchain_file.to_path_buf(), pins);
+        true
+    }
+
+    /// Forgets the projects whose toolchain files no longer exist. Returns `true` if
+    /// any were forgotten.
+    pub fn prune(&mut self) -> bool {
+        let stale: Vec<PathBuf> = self.projects
+            .keys()
+            .filter(|file| !file.is_file())
+            .cloned()
+            .collect();
+        for file in stale.iter() {
+            self.projects.remove(file);
+        }
+        !stale.is_empty()
+    }
+
+    /// Returns the registered projects, by the file that pins their tool versions, in
+    /// order of their paths.
+    pub fn projects(&self) -> &BTreeMap<PathBuf, Pins> {
+        &self.projects
+    }
+
+    /// Returns the Node versions pinned by any registered project.
+    pub fn node_versions(&self) -> BTreeSet<Version> {
+        self.projects
+            .values()
+            .filter_map(|pins| pins.node.clone())
+            .collect()
+    }
+
+    /// Returns the Yarn versions pinned by any registered project.
+    pub fn yarn_versions(&self) -> BTreeSet<Version> {
+        self.projects
+            .values()
+            .filter_map(|pins| pins.yarn.clone())
+            .collect()
+    }
+}
+
+mod serial {
+    use std::collections::BTreeMap;
+
+    use semver::Version;
+
+    use notion_fail::{Fallible, ResultExt};
+
+    #[derive(Serialize, Deserialize, Default)]
+    pub struct References {
+        #[serde(default)]
+        pub projects: BTreeMap<String, Pins>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Pins {
+        node: Option<String>,
+        yarn: Option<String>,
+    }
+
+    impl Pins {
+        pub fn from_pins(pins: &super::Pins) -> Pins {
+            Pins {
+                node: pins.node.as_ref().map(|version| version.to_string()),
+                yarn: pins.yarn.as_ref().map(|version| version.to_string()),
+            }
+        }
+
+        pub fn into_pins(self) -> Fallible<super::Pins> {
+            Ok(super::Pins {
+                node: match self.node {
+                    Some(ref version) => Some(Version::parse(version).unknown()?),
+                    None => None,
+                },
+                yarn: match self.yarn {
+                    Some(ref version) => Some(Version::parse(version).unknown()?),
+                    None => None,
+                },
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    fn pins(node: &str) -> Pins {
+        Pins {
+            node: Some(Version::parse(node).unwrap()),
+            yarn: None,
+        }
+    }
+
+    #[test]
+    fn test_register_and_round_trip() {
+        let mut references = References::default();
+        assert!(references.register(Path::new("/a/package.json"), pins("10.15.3")));
+        assert!(!references.register(Path::new("/a/package.json"), pins("10.15.3")));
+        assert!(references.register(Path::new("/b/.notion.toml"), pins("8.9.4")));
+
+        let parsed = References::from_str(&references.to_string()).unwrap();
+        let versions: Vec<String> = parsed
+            .node_versions()
+            .iter()
+            .map(|version| version.to_string())
+            .collect();
+        assert_eq!(versions, vec!["8.9.4".to_string(), "10.15.3".to_string()]);
+        assert!(parsed.yarn_versions().is_empty());
+
+        assert!(references.register(Path::new("/b/.notion.toml"), Pins::default()));
+        assert_eq!(references.node_versions().len(), 1);
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut references = References::default();
+        references.register(Path::new("/does/not/exist/package.json"), pins("10.15.3"));
+        assert!(references.prune());
+        assert!(references.node_versions().is_empty());
+        assert!(!references.prune());
+    }
+}
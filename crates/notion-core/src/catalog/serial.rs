@@ -1,19 +1,43 @@
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::default::Default;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::string::ToString;
 
 use notion_fail::{Fallible, ResultExt};
+use package;
+
+use super::migrate;
 
 use semver::{SemVerError, Version};
+use serde::de::IgnoredAny;
+use version::Channel;
 
 #[derive(Serialize, Deserialize)]
 pub struct Catalog {
+    /// The version of the catalog's layout (see `migrate`).
+    #[serde(rename = "schema-version", default)]
+    schema_version: u32,
     #[serde(default)]
     node: NodeCollection,
     #[serde(default)]
     yarn: YarnCollection,
+    #[serde(default)]
+    npm: NpmCollection,
+    #[serde(default)]
+    pnpm: PnpmCollection,
+    #[serde(default)]
+    packages: BTreeMap<String, Package>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Package {
+    version: String,
+    node: String,
+    #[serde(default)]
+    bins: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    integrity: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,6 +45,23 @@ pub struct Catalog {
 pub struct NodeCollection {
     default: Option<String>,
     versions: Vec<String>,
+    /// The prerelease channel of each version that came from one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    channels: BTreeMap<String, String>,
+    /// The version of npm bundled with each version, where the Node index recorded it.
+    #[serde(rename = "bundled-npm", default, skip_serializing_if = "BTreeMap::is_empty")]
+    bundled_npm: BTreeMap<String, String>,
+    /// Where each version that is a custom build came from.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    custom: BTreeMap<String, CustomBuild>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CustomBuild {
+    label: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -30,11 +71,28 @@ pub struct YarnCollection {
     versions: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "npm")]
+pub struct NpmCollection {
+    default: Option<String>,
+    versions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "pnpm")]
+pub struct PnpmCollection {
+    default: Option<String>,
+    versions: Vec<String>,
+}
+
 impl Default for NodeCollection {
     fn default() -> Self {
         NodeCollection {
             default: None,
             versions: vec![],
+            channels: BTreeMap::new(),
+            bundled_npm: BTreeMap::new(),
+            custom: BTreeMap::new(),
         }
     }
 }
@@ -48,11 +106,46 @@ impl Default for YarnCollection {
     }
 }
 
+impl Default for NpmCollection {
+    fn default() -> Self {
+        NpmCollection {
+            default: None,
+            versions: vec![],
+        }
+    }
+}
+
+impl Default for PnpmCollection {
+    fn default() -> Self {
+        PnpmCollection {
+            default: None,
+            versions: vec![],
+        }
+    }
+}
+
 impl Catalog {
     pub fn into_catalog(self) -> Fallible<super::Catalog> {
         Ok(super::Catalog {
             node: self.node.into_node_collection().unknown()?,
             yarn: self.yarn.into_yarn_collection().unknown()?,
+            npm: self.npm.into_npm_collection().unknown()?,
+            pnpm: self.pnpm.into_pnpm_collection().unknown()?,
+            packages: self.packages
+                .into_iter()
+                .map(|(name, package)| Ok((name, package.into_package()?)))
+                .collect::<Fallible<_>>()?,
+        })
+    }
+}
+
+impl Package {
+    fn into_package(self) -> Fallible<package::Package> {
+        Ok(package::Package {
+            version: Version::parse(&self.version).unknown()?,
+            node: Version::parse(&self.node).unknown()?,
+            bins: self.bins,
+            integrity: self.integrity,
         })
     }
 }
@@ -69,9 +162,41 @@ impl NodeCollection {
             .map(|s| Ok(Version::parse(&s[..])?))
             .collect();
 
+        let mut channels = BTreeMap::new();
+        for (version, channel) in self.channels {
+            // Ignore channels this version of Notion doesn't know about.
+            if let Some(channel) = Channel::parse(&channel) {
+                channels.insert(Version::parse(&version[..]).unknown()?, channel);
+            }
+        }
+
+        let mut bundled_npm = BTreeMap::new();
+        for (version, npm) in self.bundled_npm {
+            bundled_npm.insert(
+                Version::parse(&version[..]).unknown()?,
+                Version::parse(&npm[..]).unknown()?,
+            );
+        }
+
+        let mut custom = BTreeMap::new();
+        for (version, build) in self.custom {
+            custom.insert(
+                Version::parse(&version[..]).unknown()?,
+                super::CustomBuild {
+                    label: build.label,
+                    url: build.url,
+                    checksum: build.checksum,
+                },
+            );
+        }
+
         Ok(super::NodeCollection {
             default,
             versions: BTreeSet::from_iter(versions.unknown()?),
+            system: BTreeSet::new(),
+            channels,
+            bundled_npm,
+            custom,
             phantom: PhantomData,
         })
     }
@@ -92,6 +217,58 @@ impl YarnCollection {
         Ok(super::YarnCollection {
             default,
             versions: BTreeSet::from_iter(versions.unknown()?),
+            system: BTreeSet::new(),
+            channels: BTreeMap::new(),
+            bundled_npm: BTreeMap::new(),
+            custom: BTreeMap::new(),
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl NpmCollection {
+    fn into_npm_collection(self) -> Fallible<super::NpmCollection> {
+        let default = match self.default {
+            Some(v) => Some(Version::parse(&v[..]).unknown()?),
+            None => None,
+        };
+
+        let versions: Result<Vec<Version>, SemVerError> = self.versions
+            .into_iter()
+            .map(|s| Ok(Version::parse(&s[..])?))
+            .collect();
+
+        Ok(super::NpmCollection {
+            default,
+            versions: BTreeSet::from_iter(versions.unknown()?),
+            system: BTreeSet::new(),
+            channels: BTreeMap::new(),
+            bundled_npm: BTreeMap::new(),
+            custom: BTreeMap::new(),
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl PnpmCollection {
+    fn into_pnpm_collection(self) -> Fallible<super::PnpmCollection> {
+        let default = match self.default {
+            Some(v) => Some(Version::parse(&v[..]).unknown()?),
+            None => None,
+        };
+
+        let versions: Result<Vec<Version>, SemVerError> = self.versions
+            .into_iter()
+            .map(|s| Ok(Version::parse(&s[..])?))
+            .collect();
+
+        Ok(super::PnpmCollection {
+            default,
+            versions: BTreeSet::from_iter(versions.unknown()?),
+            system: BTreeSet::new(),
+            channels: BTreeMap::new(),
+            bundled_npm: BTreeMap::new(),
+            custom: BTreeMap::new(),
             phantom: PhantomData,
         })
     }
@@ -100,16 +277,60 @@ impl YarnCollection {
 impl super::Catalog {
     pub fn to_serial(&self) -> Catalog {
         Catalog {
+            schema_version: migrate::CURRENT_VERSION,
             node: self.node.to_serial(),
             yarn: self.yarn.to_serial(),
+            npm: self.npm.to_serial(),
+            pnpm: self.pnpm.to_serial(),
+            packages: self.packages
+                .iter()
+                .map(|(name, package)| (name.clone(), package.to_serial()))
+                .collect(),
         }
     }
 }
+
+impl package::Package {
+    fn to_serial(&self) -> Package {
+        Package {
+            version: self.version.to_string(),
+            node: self.node.to_string(),
+            bins: self.bins.clone(),
+            integrity: self.integrity.clone(),
+        }
+    }
+}
+
 impl super::NodeCollection {
     fn to_serial(&self) -> NodeCollection {
         NodeCollection {
             default: self.default.clone().map(|v| v.to_string()),
-            versions: self.versions.iter().map(|v| v.to_string()).collect(),
+            versions: self.versions
+                .difference(&self.system)
+                .map(|v| v.to_string())
+                .collect(),
+            channels: self.channels
+                .iter()
+                .map(|(version, channel)| (version.to_string(), channel.to_string()))
+                .collect(),
+            bundled_npm: self.bundled_npm
+                .iter()
+                .filter(|&(version, _)| !self.system.contains(version))
+                .map(|(version, npm)| (version.to_string(), npm.to_string()))
+                .collect(),
+            custom: self.custom
+                .iter()
+                .map(|(version, build)| {
+                    (
+                        version.to_string(),
+                        CustomBuild {
+                            label: build.label.clone(),
+                            url: build.url.clone(),
+                            checksum: build.checksum.clone(),
+                        },
+                    )
+                })
+                .collect(),
         }
     }
 }
@@ -118,7 +339,34 @@ impl super::YarnCollection {
     fn to_serial(&self) -> YarnCollection {
         YarnCollection {
             default: self.default.clone().map(|v| v.to_string()),
-            versions: self.versions.iter().map(|v| v.to_string()).collect(),
+            versions: self.versions
+                .difference(&self.system)
+                .map(|v| v.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl super::NpmCollection {
+    fn to_serial(&self) -> NpmCollection {
+        NpmCollection {
+            default: self.default.clone().map(|v| v.to_string()),
+            versions: self.versions
+                .difference(&self.system)
+                .map(|v| v.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl super::PnpmCollection {
+    fn to_serial(&self) -> PnpmCollection {
+        PnpmCollection {
+            default: self.default.clone().map(|v| v.to_string()),
+            versions: self.versions
+                .difference(&self.system)
+                .map(|v| v.to_string())
+                .collect(),
         }
     }
 }
@@ -130,6 +378,29 @@ pub struct Index(Vec<Entry>);
 pub struct Entry {
     pub version: String,
     pub files: Vec<String>,
+    #[serde(default)]
+    pub lts: Lts,
+    pub date: Option<String>,
+    #[serde(default)]
+    pub security: bool,
+    /// The version of npm bundled with the release.
+    #[serde(default)]
+    pub npm: Option<String>,
+}
+
+/// The `lts` field of an index entry, which is `false` for releases that aren't
+/// long-term support releases, and the name of the LTS release line otherwise.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Lts {
+    Named(String),
+    Unnamed(bool),
+}
+
+impl Default for Lts {
+    fn default() -> Self {
+        Lts::Unnamed(false)
+    }
 }
 
 impl Index {
@@ -138,6 +409,13 @@ impl Index {
         for entry in self.0 {
             let data = super::VersionData {
                 files: HashSet::from_iter(entry.files.into_iter()),
+                lts: match entry.lts {
+                    Lts::Named(name) => Some(name),
+                    Lts::Unnamed(_) => None,
+                },
+                date: entry.date,
+                security: entry.security,
+                npm: entry.npm.and_then(|npm| Version::parse(npm.trim()).ok()),
             };
             let mut version = &entry.version[..];
             version = version.trim();
@@ -149,3 +427,11 @@ impl Index {
         Ok(super::Index { entries })
     }
 }
+
+/// The abbreviated metadata for a package, as served by the npm registry.
+#[derive(Deserialize)]
+pub struct NpmIndex {
+    #[serde(rename = "dist-tags")]
+    pub dist_tags: HashMap<String, String>,
+    pub versions: HashMap<String, IgnoredAny>,
+}
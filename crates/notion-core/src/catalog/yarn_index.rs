@@ -0,0 +1,366 @@
+//! The index of Yarn releases, listed from the GitHub releases of the Yarn repository.
+//!
+//! The releases API returns at most a page of releases per request, linking to the
+//! next page in the `Link` header of each response, so listing every release takes
+//! several requests. The whole index is cached in the Yarn cache directory, and read
+//! from there until it is older than the `yarn.index-ttl` setting (four hours if not
+//! configured). Offline, the cached index is used however old it is.
+//!
+//! Unauthenticated requests to the GitHub API are limited to sixty an hour for each
+//! address, which machines sharing one (like CI runners) soon use up, so the requests
+//! are authenticated with the token of the `github.token` setting (or the
+//! `GITHUB_TOKEN` environment variable) if there is one. Once GitHub rate limits the
+//! requests anyway, the cached index is used however old it is, with a warning.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest;
+use reqwest::header::Headers;
+use reqwest::StatusCode;
+use semver::Version;
+use serde_json;
+
+use config::Config;
+use env;
+use fs::{read_file_opt, write_atomic};
+use http;
+use log;
+use notion_fail::{Fallible, NotionFail, ResultExt};
+use path;
+use profile::{self, Phase};
+use style::{display_warning, progress_spinner};
+
+use super::RegistryFetchError;
+
+/// The first page of the releases of the Yarn repository, as the GitHub API lists them.
+const PUBLIC_YARN_RELEASES: &'static str =
+    "https://api.github.com/repos/yarnpkg/yarn/releases?per_page=100";
+
+/// Requests to URLs under this one are sent with the configured GitHub token, if any.
+const GITHUB_API_ROOT: &'static str = "https://api.github.com/";
+
+/// The most pages of releases followed, in case a server links pages in a cycle.
+const MAX_PAGES: usize = 50;
+
+/// How long in seconds the cached index stays fresh, unless `yarn.index-ttl` says
+/// otherwise.
+const DEFAULT_TTL: u64 = 4 * 60 * 60;
+
+/// Thrown when GitHub rate limits the requests for the Yarn index, and there is no
+/// cached index to fall back on.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "GitHub is rate limiting the requests for the Yarn index at {}", url)]
+#[notion_fail(code = "NetworkError", suggest_with = "suggest", error_code = "RATE_LIMITED")]
+pub(crate) struct GithubRateLimitedError {
+    url: String,
+    /// Whether the requests were authenticated with a token.
+    authenticated: bool,
+}
+
+impl GithubRateLimitedError {
+    fn suggest(&self) -> Option<String> {
+        Some(if self.authenticated {
+            "wait for the rate limit to reset and try again".to_string()
+        } else {
+            "set the `github.token` setting (or the GITHUB_TOKEN environment variable) to a GitHub token to raise the limit".to_string()
+        })
+    }
+}
+
+/// A release as the GitHub API describes it.
+#[derive(Deserialize)]
+struct RawRelease {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    published_at: Option<String>,
+}
+
+/// A release of Yarn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YarnRelease {
+    pub version: Version,
+    /// The date of the release (YYYY-MM-DD), if known.
+    pub date: Option<String>,
+}
+
+/// The cached index, as it is written to the cache file.
+#[derive(Serialize, Deserialize)]
+struct CachedIndex {
+    /// When the index was downloaded, in seconds since the epoch.
+    fetched: u64,
+    releases: Vec<CachedRelease>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRelease {
+    version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// Lists the Yarn releases, newest first, from the cache if it is fresh enough (or
+/// the network is off) and otherwise from the GitHub API. With `refresh`, the index is
+/// downloaded whether or not the cached one is fresh.
+pub(crate) fn releases(config: &Config, refresh: bool) -> Fallible<Vec<YarnRelease>> {
+    let cached = read_cache()?;
+    if let Some(ref cached) = cached {
+        let ttl = config
+            .yarn
+            .as_ref()
+            .and_then(|yarn| yarn.index_ttl)
+            .unwrap_or(DEFAULT_TTL);
+        let fresh = now().saturating_sub(cached.fetched) < ttl;
+        if env::offline() || (fresh && !refresh) {
+            log::debug(|| "using the cached Yarn index".to_string());
+            return Ok(from_cache(cached));
+        }
+    }
+
+    match profile::time(Phase::IndexFetch, || fetch(config))? {
+        Some(releases) => {
+            write_cache(&releases)?;
+            Ok(releases)
+        }
+        None => match cached {
+            Some(ref cached) => {
+                display_warning(&format!(
+                    "GitHub is rate limiting the requests for the Yarn index, so the index cached {} ago is used{}",
+                    describe_age(now().saturating_sub(cached.fetched)),
+                    if config.github_token().is_some() {
+                        String::new()
+                    } else {
+                        " (set the `github.token` setting to raise the limit)".to_string()
+                    }
+                ));
+                Ok(from_cache(cached))
+            }
+            None => throw!(GithubRateLimitedError {
+                url: PUBLIC_YARN_RELEASES.to_string(),
+                authenticated: config.github_token().is_some(),
+            }),
+        },
+    }
+}
+
+/// Describes an age in seconds roughly, like `3 hours`.
+fn describe_age(secs: u64) -> String {
+    if secs < 2 * 60 {
+        format!("{} seconds", secs)
+    } else if secs < 2 * 60 * 60 {
+        format!("{} minutes", secs / 60)
+    } else {
+        format!("{} hours", secs / (60 * 60))
+    }
+}
+
+fn read_cache() -> Fallible<Option<CachedIndex>> {
+    let src: Option<String> = read_file_opt(&path::yarn_index_file()?).unknown()?;
+    // A cache that can't be read (left by a crash, or a newer version of Notion) is
+    // downloaded again.
+    Ok(src.and_then(|src| serde_json::from_str(&src).ok()))
+}
+
+fn from_cache(cached: &CachedIndex) -> Vec<YarnRelease> {
+    cached
+        .releases
+        .iter()
+        .filter_map(|release| {
+            Version::parse(&release.version).ok().map(|version| YarnRelease {
+                version,
+                date: release.date.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Replaces the cached index atomically, so that concurrent readers only ever see a
+/// complete one.
+fn write_cache(releases: &[YarnRelease]) -> Fallible<()> {
+    let cached = CachedIndex {
+        fetched: now(),
+        releases: releases
+            .iter()
+            .map(|release| CachedRelease {
+                version: release.version.to_string(),
+                date: release.date.clone(),
+            })
+            .collect(),
+    };
+    write_atomic(path::yarn_index_file()?, serde_json::to_string(&cached).unknown()?)
+}
+
+/// Downloads every page of releases, following the `next` link of each. Produces
+/// `None` if GitHub rate limits the requests.
+fn fetch(config: &Config) -> Fallible<Option<Vec<YarnRelease>>> {
+    http::ensure_online(PUBLIC_YARN_RELEASES)?;
+    log::info(|| format!("fetching the Yarn index from {}", PUBLIC_YARN_RELEASES));
+    let spinner = progress_spinner(&format!("Fetching public registry: {}", PUBLIC_YARN_RELEASES));
+
+    let mut releases = vec![];
+    let mut next = Some(PUBLIC_YARN_RELEASES.to_string());
+    let mut pages = 0;
+    while let Some(url) = next.take() {
+        let mut headers = Headers::new();
+        if let Some(token) = config.github_token() {
+            if url.starts_with(GITHUB_API_ROOT) {
+                headers.set_raw("Authorization", format!("token {}", token));
+            }
+        }
+        let client = http::client_for(&url, config).with_context(RegistryFetchError::from_error)?;
+        let mut response: reqwest::Response = http::retry(&url, config, http::is_transient, || {
+            http::throttled(&url, || {
+                http::traced("GET", &url, Some(&headers), || {
+                    client.get(&url[..]).headers(headers.clone()).send()
+                })
+            })
+        }).with_context(RegistryFetchError::from_error)?;
+        if is_rate_limited(&response) {
+            spinner.finish_and_clear();
+            log::info(|| format!("GitHub rate limited the request for {}", url));
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            throw!(RegistryFetchError {
+                error: format!("HTTP failure ({}) fetching {}", response.status(), url),
+            });
+        }
+        let page: Vec<RawRelease> = response.json().with_context(RegistryFetchError::from_error)?;
+        releases.extend(parse_page(page));
+
+        pages += 1;
+        if pages < MAX_PAGES {
+            next = next_page(&response);
+        }
+    }
+    spinner.finish_and_clear();
+
+    // Don't rely on the order of the pages, which follows when releases were published.
+    releases.sort_by(|a, b| b.version.cmp(&a.version));
+    releases.dedup_by(|a, b| a.version == b.version);
+    Ok(Some(releases))
+}
+
+/// Returns whether GitHub refused a request for exceeding the rate limit, which it
+/// reports as `403 Forbidden` with no requests remaining (or as `429 Too Many
+/// Requests`, once `throttled` has stopped waiting it out).
+fn is_rate_limited(response: &reqwest::Response) -> bool {
+    match response.status() {
+        StatusCode::TooManyRequests => true,
+        StatusCode::Forbidden => response
+            .headers()
+            .get_raw("X-RateLimit-Remaining")
+            .and_then(|raw| raw.one())
+            .map_or(false, |remaining| remaining == &b"0"[..]),
+        _ => false,
+    }
+}
+
+/// The URL of the page after the one in the specified response, if there is one.
+fn next_page(response: &reqwest::Response) -> Option<String> {
+    let link = response.headers().get_raw("Link")?.one()?;
+    parse_next_link(&String::from_utf8_lossy(link))
+}
+
+/// Reads the URL of the `next` relation from a `Link` header, like
+/// `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`.
+fn parse_next_link(header: &str) -> Option<String> {
+    header.split(',').filter_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim();
+        let is_next = parts.any(|param| {
+            let param = param.trim();
+            param == "rel=\"next\"" || param == "rel=next"
+        });
+        if is_next && url.starts_with('<') && url.ends_with('>') {
+            Some(url[1..url.len() - 1].to_string())
+        } else {
+            None
+        }
+    }).next()
+}
+
+/// Reads the published releases of a page, skipping drafts, prereleases, and tags that
+/// aren't versions.
+fn parse_page(page: Vec<RawRelease>) -> Vec<YarnRelease> {
+    page.into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .filter_map(|release| {
+            let version = Version::parse(release.tag_name.trim_left_matches('v')).ok()?;
+            Some(YarnRelease {
+                version,
+                date: release
+                    .published_at
+                    .as_ref()
+                    .and_then(|published| published.get(..10))
+                    .map(|date| date.to_string()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{describe_age, from_cache, parse_next_link, parse_page, CachedIndex, RawRelease};
+    use semver::Version;
+    use serde_json;
+
+    #[test]
+    fn test_parse_next_link() {
+        let header = "<https://api.github.com/repositories/49970642/releases?per_page=100&page=2>; rel=\"next\", <https://api.github.com/repositories/49970642/releases?per_page=100&page=3>; rel=\"last\"";
+        assert_eq!(
+            parse_next_link(header),
+            Some("https://api.github.com/repositories/49970642/releases?per_page=100&page=2".to_string())
+        );
+
+        let last = "<https://api.github.com/repositories/49970642/releases?per_page=100&page=1>; rel=\"first\", <https://api.github.com/repositories/49970642/releases?per_page=100&page=2>; rel=\"prev\"";
+        assert_eq!(parse_next_link(last), None);
+        assert_eq!(parse_next_link(""), None);
+    }
+
+    #[test]
+    fn test_parse_page() {
+        let page: Vec<RawRelease> = serde_json::from_str(
+            r#"[
+                {"tag_name": "v1.22.19", "draft": false, "prerelease": false, "published_at": "2022-06-21T11:49:21Z"},
+                {"tag_name": "v1.23.0-20220130.1630", "draft": false, "prerelease": true, "published_at": "2022-01-30T16:30:00Z"},
+                {"tag_name": "v1.22.18", "draft": true, "prerelease": false, "published_at": null},
+                {"tag_name": "nightly", "draft": false, "prerelease": false, "published_at": null},
+                {"tag_name": "v0.27.5", "draft": false, "prerelease": false}
+            ]"#,
+        ).unwrap();
+        let releases = parse_page(page);
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[0].version, Version::parse("1.22.19").unwrap());
+        assert_eq!(releases[0].date, Some("2022-06-21".to_string()));
+        assert_eq!(releases[1].version, Version::parse("0.27.5").unwrap());
+        assert_eq!(releases[1].date, None);
+    }
+
+    #[test]
+    fn test_describe_age() {
+        assert_eq!(describe_age(45), "45 seconds");
+        assert_eq!(describe_age(600), "10 minutes");
+        assert_eq!(describe_age(5 * 3600 + 59), "5 hours");
+    }
+
+    #[test]
+    fn test_from_cache() {
+        let cached: CachedIndex = serde_json::from_str(
+            r#"{"fetched": 1545000000, "releases": [{"version": "1.12.3", "date": "2018-11-01"}, {"version": "not a version"}]}"#,
+        ).unwrap();
+        let releases = from_cache(&cached);
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].version, Version::parse("1.12.3").unwrap());
+    }
+}
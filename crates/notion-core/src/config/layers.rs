@@ -0,0 +1,517 @@
+//! Loads Notion's configuration in layers. From lowest precedence to highest, they
+//! are:
+//!
+//! 1. the defaults;
+//! 2. the hooks of the current project (a `hooks.toml` file at its root, or the
+//!    `notion.hooks` section of its `package.json`), which can only make a few
+//!    settings (see `ProjectHooks`);
+//! 3. the system configuration, the `config.toml` file of the system install (see
+//!    `NOTION_SYSTEM_HOME`), which an administrator provisions for every user;
+//! 4. the user's configuration, `config.toml` in the Notion home directory;
+//! 5. environment variables, which can override any setting with a single value:
+//!    the variable is named after its key, upper-cased, with its dots and dashes
+//!    turned into underscores (so `NOTION_NODE_MIRROR` overrides `node.mirror`,
+//!    and `NOTION_PROXY_NO_PROXY` overrides `proxy.no-proxy`, as a comma-separated
+//!    list);
+//! 6. command-line flags, like `--stream`, which take effect by setting the
+//!    corresponding environment variable for the rest of the process.
+//!
+//! The files merge setting by setting: a setting a higher layer makes replaces the
+//! same setting in a lower one, and leaves the rest of its section alone.
+
+use std::collections::BTreeMap;
+use std::env as std_env;
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use serde_json;
+use toml;
+use toml::value::{Table, Value};
+
+use env;
+use fs::{read_file_opt, touch};
+use notion_fail::{Fallible, ResultExt};
+use path;
+use project::{Project, HOOKS_FILE};
+use readext::ReadExt;
+
+use super::schema::{self, Kind};
+
+/// Where a setting comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The hooks of the current project, in the specified file.
+    Project(PathBuf),
+    /// The system configuration file.
+    System(PathBuf),
+    /// The user's configuration file.
+    User(PathBuf),
+    /// The specified environment variable, which a command-line flag may have set.
+    Env(String),
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Origin::Project(ref file) => write!(f, "project:{}", file.display()),
+            Origin::System(ref file) => write!(f, "system:{}", file.display()),
+            Origin::User(ref file) => write!(f, "user:{}", file.display()),
+            Origin::Env(ref var) => write!(f, "env:{}", var),
+        }
+    }
+}
+
+/// The settings one layer makes, keyed by their dotted names (like `node.mirror`).
+struct Layer {
+    origin: Origin,
+    settings: BTreeMap<String, Value>,
+}
+
+/// An effective setting, and the layer it comes from.
+pub struct Setting<'a> {
+    pub key: &'a str,
+    pub value: &'a Value,
+    pub origin: &'a Origin,
+}
+
+/// The settings whose values are credentials, which are never shown.
+const SECRET_KEYS: &[&str] = &["github.token"];
+
+impl<'a> Setting<'a> {
+    /// The value of the setting as it is shown to the user, which hides credentials.
+    pub fn display_value(&self) -> String {
+        if SECRET_KEYS.contains(&self.key) {
+            "\"[redacted]\"".to_string()
+        } else {
+            self.value.to_string()
+        }
+    }
+}
+
+/// The layers of configuration in effect, from lowest precedence to highest.
+pub struct Layers {
+    layers: Vec<Layer>,
+}
+
+impl Layers {
+    /// Loads every layer that makes settings, including the hooks of `project`.
+    pub fn load(project: Option<&Project>) -> Fallible<Layers> {
+        let mut layers = vec![];
+        if let Some(project) = project {
+            if let Some((file, table)) = hooks_table(project)? {
+                layers.push(Layer::new(Origin::Project(file), &table));
+            }
+        }
+        for (origin, table) in files()? {
+            layers.push(Layer::new(origin, &table));
+        }
+        layers.extend(env_layers()?);
+        Ok(Layers { layers })
+    }
+
+    /// The effective settings, in order of their keys, each from the highest layer
+    /// that makes it.
+    pub fn effective(&self) -> Vec<Setting> {
+        let mut effective: BTreeMap<&str, Setting> = BTreeMap::new();
+        for layer in &self.layers {
+            for (key, value) in &layer.settings {
+                effective.insert(
+                    key,
+                    Setting {
+                        key,
+                        value,
+                        origin: &layer.origin,
+                    },
+                );
+            }
+        }
+        effective.into_iter().map(|(_, setting)| setting).collect()
+    }
+}
+
+impl Layer {
+    fn new(origin: Origin, table: &Table) -> Self {
+        let mut settings = BTreeMap::new();
+        flatten("", table, &mut settings);
+        Layer { origin, settings }
+    }
+}
+
+/// Collects the settings of a table under their dotted names.
+fn flatten(prefix: &str, table: &Table, settings: &mut BTreeMap<String, Value>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match *value {
+            Value::Table(ref table) => flatten(&key, table, settings),
+            _ => {
+                settings.insert(key, value.clone());
+            }
+        }
+    }
+}
+
+/// Merges the settings of one table over another's, section by section.
+fn merge(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match value {
+            Value::Table(over_table) => {
+                if let Some(&mut Value::Table(ref mut base_table)) = base.get_mut(&key) {
+                    merge(base_table, over_table);
+                    continue;
+                }
+                base.insert(key, Value::Table(over_table));
+            }
+            value => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Parses the settings of a configuration file.
+fn parse_file(src: &str) -> Fallible<Table> {
+    toml::from_str(src).unknown()
+}
+
+/// Reads the configuration files that exist: the system's, then the user's (which is
+/// created if it doesn't exist yet).
+fn files() -> Fallible<Vec<(Origin, Table)>> {
+    let mut files = vec![];
+    if let Some(system_file) = path::system_config_file() {
+        if let Some(src) = read_file_opt(&system_file).unknown()? {
+            let table = parse_file(&src)?;
+            schema::validate(&system_file, &src, &table)?;
+            files.push((Origin::System(system_file), table));
+        }
+    }
+    let user_file = path::user_config_file()?;
+    let src = touch(&user_file)?.read_into_string().unknown()?;
+    let table = parse_file(&src)?;
+    schema::validate(&user_file, &src, &table)?;
+    files.push((Origin::User(user_file), table));
+    Ok(files)
+}
+
+/// Merges the configuration files, and the settings that environment variables
+/// override, into the table that the configuration is read from. The project's hooks
+/// are applied to the settings afterwards (see `Config::merge_project_hooks`).
+pub(crate) fn merged() -> Fallible<Table> {
+    let mut merged = Table::new();
+    for (_, table) in files()? {
+        merge(&mut merged, table);
+    }
+    for (_, key, value) in env_overrides(|var| std_env::var_os(var))? {
+        insert(&mut merged, &key, value);
+    }
+    Ok(merged)
+}
+
+/// Makes a setting in a table, by its dotted name, creating the sections it belongs
+/// to as needed.
+fn insert(table: &mut Table, key: &str, value: Value) {
+    match key.find('.') {
+        Some(index) => {
+            let section = table
+                .entry(key[..index].to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !section.is_table() {
+                *section = Value::Table(Table::new());
+            }
+            if let Some(section) = section.as_table_mut() {
+                insert(section, &key[index + 1..], value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// The environment variable that overrides a setting.
+fn env_var(key: &str) -> String {
+    format!("NOTION_{}", key.to_uppercase().replace('.', "_").replace('-', "_"))
+}
+
+/// Thrown when an environment variable that overrides a setting has a value the
+/// setting can't take.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid value for {}: '{}' is not {}", var, value, expected)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct EnvVarValueError {
+    pub(crate) var: String,
+    pub(crate) value: String,
+    pub(crate) expected: &'static str,
+}
+
+/// Parses the value of an environment variable as a setting of the specified kind.
+fn parse_env_value(var: &str, value: &str, kind: Kind) -> Fallible<Value> {
+    let invalid = || EnvVarValueError {
+        var: var.to_string(),
+        value: value.to_string(),
+        expected: kind.expected(),
+    };
+    match kind {
+        Kind::Str => Ok(Value::String(value.to_string())),
+        Kind::Url | Kind::ArchiveTemplate => {
+            let url = Value::String(value.to_string());
+            if schema::check(&url, kind).is_err() {
+                throw!(invalid());
+            }
+            Ok(url)
+        }
+        Kind::Bool => match value.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(Value::Boolean(true)),
+            "0" | "false" | "no" | "off" => Ok(Value::Boolean(false)),
+            _ => throw!(invalid()),
+        },
+        Kind::Int => match value.trim().parse::<u64>() {
+            Ok(n) if n <= i64::max_value() as u64 => Ok(Value::Integer(n as i64)),
+            _ => throw!(invalid()),
+        },
+        Kind::List => Ok(Value::Array(
+            value
+                .split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )),
+    }
+}
+
+/// The settings that environment variables override, with the variables, as `var`
+/// reads them. A variable that is set but empty overrides nothing.
+fn env_overrides<F>(var: F) -> Fallible<Vec<(String, String, Value)>>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let mut overrides = vec![];
+    for (key, kind) in schema::keys() {
+        let name = env_var(&key);
+        if let Some(value) = var(&name) {
+            let value = value.to_string_lossy().into_owned();
+            if value.is_empty() {
+                continue;
+            }
+            let value = parse_env_value(&name, &value, kind)?;
+            overrides.push((name, key, value));
+        }
+    }
+    Ok(overrides)
+}
+
+/// The settings that the tool versions selected for a directory depend on (or whether
+/// they can be cached at all), besides the ones in each tool's section.
+const PLATFORM_KEYS: &[&str] = &[
+    "behavior.corepack",
+    "events.publish.url",
+    "events.publish.bin",
+    "project.vcs-boundary",
+    "project.boundary-markers",
+    "project.toolchain-key",
+];
+
+/// The settings of each tool's section that the selected versions depend on.
+const TOOL_PLATFORM_KEYS: &[&str] = &["resolve.url", "resolve.bin", "use-engines"];
+
+/// Returns whether an environment variable, as `var` reads it, overrides a setting
+/// that the tool versions selected for a directory depend on, which the platform cache
+/// can't tell from its stamps.
+pub(crate) fn has_platform_overrides<F>(var: F) -> bool
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    schema::keys()
+        .into_iter()
+        .filter(|&(ref key, _)| {
+            PLATFORM_KEYS.contains(&&key[..]) || match key.find('.') {
+                Some(index) => TOOL_PLATFORM_KEYS.contains(&&key[index + 1..]),
+                None => false,
+            }
+        })
+        .any(|(key, _)| var(&env_var(&key)).map_or(false, |value| !value.is_empty()))
+}
+
+/// Reads the raw settings of a project's hooks, with the file they come from.
+fn hooks_table(project: &Project) -> Fallible<Option<(PathBuf, Table)>> {
+    let hooks_file = project.root().join(HOOKS_FILE);
+    if let Some(src) = read_file_opt(&hooks_file).unknown()? {
+        return Ok(Some((hooks_file, parse_file(&src)?)));
+    }
+
+    let package_file = project.root().join("package.json");
+    let src = match read_file_opt(&package_file).unknown()? {
+        Some(src) => src,
+        None => return Ok(None),
+    };
+    let manifest: serde_json::Value = serde_json::from_str(&src).unknown()?;
+    match manifest.pointer("/notion/hooks") {
+        Some(hooks) => match Value::try_from(hooks).unknown()? {
+            Value::Table(table) => Ok(Some((package_file, table))),
+            _ => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/// The settings that environment variables make, each in a layer of its own: the
+/// overrides of settings by name, and then the older variables that the getters of
+/// `Config` read (which the command-line flags set).
+fn env_layers() -> Fallible<Vec<Layer>> {
+    let mut vars = env_overrides(|var| std_env::var_os(var))?;
+    let mut legacy: Vec<(&'static str, &'static str, Value)> = vec![];
+    if env::no_implicit_fetch() {
+        legacy.push((
+            "NOTION_NO_IMPLICIT_FETCH",
+            "behavior.no-implicit-fetch",
+            Value::Boolean(true),
+        ));
+    }
+    if env::stream() {
+        legacy.push(("NOTION_STREAM", "cache.stream", Value::Boolean(true)));
+    }
+    if env::no_update_check() {
+        legacy.push(("NOTION_NO_UPDATE_CHECK", "updates.check", Value::Boolean(false)));
+    }
+    vars.extend(
+        legacy
+            .into_iter()
+            .map(|(var, key, value)| (var.to_string(), key.to_string(), value)),
+    );
+
+    Ok(vars
+        .into_iter()
+        .map(|(var, key, value)| {
+            let mut settings = BTreeMap::new();
+            settings.insert(key, value);
+            Layer {
+                origin: Origin::Env(var),
+                settings,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{env_overrides, env_var, has_platform_overrides, insert, merge, Layer, Layers,
+                Origin};
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+    use toml;
+    use toml::value::{Table, Value};
+
+    fn table(src: &str) -> Table {
+        toml::from_str(src).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = table("[node]\nmirror = \"https://system/\"\nindex-ttl = 60\n[http]\ntimeout = 30\n");
+        merge(&mut base, table("[node]\nmirror = \"https://user/\"\n"));
+        assert_eq!(
+            Value::Table(base),
+            Value::Table(table("[node]\nmirror = \"https://user/\"\nindex-ttl = 60\n[http]\ntimeout = 30\n"))
+        );
+    }
+
+    #[test]
+    fn test_env_var() {
+        assert_eq!(env_var("node.mirror"), "NOTION_NODE_MIRROR");
+        assert_eq!(env_var("proxy.no-proxy"), "NOTION_PROXY_NO_PROXY");
+        assert_eq!(env_var("yarn.resolve.url"), "NOTION_YARN_RESOLVE_URL");
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let overrides = env_overrides(|var| match var {
+            "NOTION_NODE_MIRROR" => Some(OsString::from("https://mirror/")),
+            "NOTION_HTTP_TIMEOUT" => Some(OsString::from("5")),
+            "NOTION_BEHAVIOR_DEDUPE" => Some(OsString::from("false")),
+            "NOTION_PROXY_NO_PROXY" => Some(OsString::from("localhost, .internal")),
+            "NOTION_CACHE_MAX_AGE" => Some(OsString::from("")),
+            _ => None,
+        }).unwrap();
+
+        let mut merged = table("[node]\nmirror = \"https://user/\"\nindex-ttl = 60\n");
+        for (_, key, value) in overrides {
+            insert(&mut merged, &key, value);
+        }
+        assert_eq!(
+            Value::Table(merged),
+            Value::Table(table(
+                "[node]\nmirror = \"https://mirror/\"\nindex-ttl = 60\n\
+                 [proxy]\nno-proxy = [\"localhost\", \".internal\"]\n\
+                 [http]\ntimeout = 5\n\
+                 [behavior]\ndedupe = false\n"
+            ))
+        );
+
+        let invalid = env_overrides(|var| match var {
+            "NOTION_HTTP_TIMEOUT" => Some(OsString::from("soon")),
+            _ => None,
+        });
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_has_platform_overrides() {
+        let only = |name: &'static str| {
+            move |var: &str| if var == name { Some(OsString::from("1")) } else { None }
+        };
+
+        assert!(has_platform_overrides(only("NOTION_BEHAVIOR_COREPACK")));
+        assert!(has_platform_overrides(only("NOTION_PROJECT_TOOLCHAIN_KEY")));
+        assert!(has_platform_overrides(only("NOTION_YARN_USE_ENGINES")));
+        assert!(has_platform_overrides(only("NOTION_NODE_RESOLVE_BIN")));
+        assert!(!has_platform_overrides(only("NOTION_HTTP_TIMEOUT")));
+        assert!(!has_platform_overrides(only("NOTION_NODE_MIRROR")));
+        assert!(!has_platform_overrides(|_| None));
+    }
+
+    #[test]
+    fn test_effective() {
+        let system = PathBuf::from("/etc/notion/config.toml");
+        let user = PathBuf::from("/home/user/.notion/config.toml");
+        let layers = Layers {
+            layers: vec![
+                Layer::new(
+                    Origin::System(system.clone()),
+                    &table("[node]\nmirror = \"https://system/\"\nindex-ttl = 60\n"),
+                ),
+                Layer::new(
+                    Origin::User(user.clone()),
+                    &table("[node]\nmirror = \"https://user/\"\n"),
+                ),
+            ],
+        };
+        let effective: Vec<(String, String, Origin)> = layers
+            .effective()
+            .into_iter()
+            .map(|setting| {
+                (
+                    setting.key.to_string(),
+                    setting.value.to_string(),
+                    setting.origin.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            effective,
+            vec![
+                ("node.index-ttl".to_string(), "60".to_string(), Origin::System(system)),
+                (
+                    "node.mirror".to_string(),
+                    "\"https://user/\"".to_string(),
+                    Origin::User(user)
+                ),
+            ]
+        );
+    }
+}
@@ -1,22 +1,31 @@
 //! Provides types for working with Notion configuration files.
 
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::str::FromStr;
+use std::time::Duration;
 
 use lazycell::LazyCell;
 use toml;
 
 use distro::Distro;
 use distro::node::NodeDistro;
+use distro::npm::NpmDistro;
+use distro::pnpm::PnpmDistro;
 use distro::yarn::YarnDistro;
-use fs::touch;
+use env;
+use manifest::TOOLCHAIN_KEY;
 use notion_fail::{Fallible, NotionError, ResultExt};
-use path::user_config_file;
 use plugin;
-use readext::ReadExt;
+use profile::{self, Phase};
 
+pub mod layers;
+mod schema;
 pub(crate) mod serial;
 
+/// The default number of hours between checks for a new release of Notion.
+pub(crate) const DEFAULT_UPDATE_INTERVAL: u64 = 24;
+
 /// Lazily loaded Notion configuration settings.
 pub struct LazyConfig {
     config: LazyCell<Config>,
@@ -32,7 +41,13 @@ impl LazyConfig {
 
     /// Forces the loading of the configuration settings.
     pub fn get(&self) -> Fallible<&Config> {
-        self.config.try_borrow_with(|| Config::current())
+        self.config.try_borrow_with(|| profile::time(Phase::ConfigLoad, Config::current))
+    }
+
+    /// Forces the loading of the configuration settings and returns a mutable
+    /// reference to them.
+    pub fn get_mut(&mut self) -> Fallible<&mut Config> {
+        self.config.try_borrow_mut_with(|| profile::time(Phase::ConfigLoad, Config::current))
     }
 }
 
@@ -40,25 +55,166 @@ impl LazyConfig {
 pub struct Config {
     pub node: Option<ToolConfig<NodeDistro>>,
     pub yarn: Option<ToolConfig<YarnDistro>>,
+    pub npm: Option<ToolConfig<NpmDistro>>,
+    pub pnpm: Option<ToolConfig<PnpmDistro>>,
     pub events: Option<EventsConfig>,
+    pub proxy: Option<ProxyConfig>,
+    pub retry: Option<RetryConfig>,
+    pub http: Option<HttpConfig>,
+    pub project: Option<ProjectConfig>,
+    pub cache: Option<CacheConfig>,
+    pub registry: Option<RegistryConfig>,
+    pub behavior: Option<BehaviorConfig>,
+    pub lifecycle: Option<LifecycleConfig>,
+    pub updates: Option<UpdatesConfig>,
+    pub telemetry: Option<TelemetryConfig>,
+    pub github: Option<GithubConfig>,
 }
 
 /// Notion configuration settings relating to the Node executable.
 pub struct ToolConfig<D: Distro> {
     /// The plugin for resolving Node versions, if any.
     pub resolve: Option<plugin::ResolvePlugin>,
-    /// The plugin for listing the set of Node versions available on the remote server, if any.
-    pub ls_remote: Option<plugin::LsRemote>,
+    /// The plugin for listing the set of versions available on the remote server, if any
+    /// (currently only supported for Node), which takes the place of the public index.
+    pub ls_remote: Option<plugin::Index>,
+    /// Whether to verify the GPG signature of the published checksums before installing
+    /// (currently only supported for Node).
+    pub verify_signatures: bool,
+    /// The root URL of a mirror of the public distribution server, if any (currently
+    /// only supported for Node and Yarn). For Yarn, it may instead be a template with
+    /// the placeholders of `url_template`.
+    pub mirror: Option<String>,
+    /// The root URL of the server of unofficial builds for musl-based systems like Alpine
+    /// Linux, which the public distribution server doesn't build for, if not the public
+    /// one (currently only supported for Node).
+    pub musl_mirror: Option<String>,
+    /// How long in seconds a downloaded index of available versions stays fresh, if
+    /// configured; otherwise the server's caching headers decide for Node, and it is
+    /// four hours for Yarn (currently only supported for Node and Yarn).
+    pub index_ttl: Option<u64>,
+    /// How long in seconds the resolve plugin's resolution of a version requirement
+    /// stays fresh in the cache, if configured; otherwise an hour. Zero disables
+    /// caching the plugin's resolutions.
+    pub resolve_ttl: Option<u64>,
+    /// A template for the URL to download each version from, with `{os}`, `{arch}`,
+    /// `{version}`, `{filename}`, and `{ext}` placeholders, if any. It takes precedence
+    /// over the mirror and the public distribution server. For Node, it must end in
+    /// `/{filename}`, since the published checksums are read from the same directory.
+    pub url_template: Option<String>,
+    /// Whether a project's `engines` range selects the version for projects without a
+    /// toolchain (currently only supported for Node).
+    pub use_engines: bool,
+    /// Whether pinning a version also writes a compatible range to the project's
+    /// `engines` section (currently only supported for Node).
+    pub sync_engines: bool,
 
     pub phantom: PhantomData<D>,
 }
 
 impl Config {
     /// Returns the current configuration settings, loaded from the filesystem.
+    /// Loads the system and user configuration files, merged in layers (see the
+    /// `layers` module).
     fn current() -> Fallible<Config> {
-        let path = user_config_file()?;
-        let src = touch(&path)?.read_into_string().unknown()?;
-        src.parse()
+        let serial: serial::Config = toml::Value::Table(layers::merged()?)
+            .try_into()
+            .unknown()?;
+        serial.into_config()
+    }
+
+    /// Merges the hooks of the current project under these settings: each setting the
+    /// hooks make is only used if the user's configuration doesn't make it already.
+    pub fn merge_project_hooks(&mut self, hooks: ProjectHooks) {
+        merge_tool_hooks(&mut self.node, hooks.node);
+        merge_tool_hooks(&mut self.yarn, hooks.yarn);
+        merge_tool_hooks(&mut self.npm, hooks.npm);
+        merge_tool_hooks(&mut self.pnpm, hooks.pnpm);
+        if let Some(events) = hooks.events {
+            let config = self.events.get_or_insert_with(|| EventsConfig { publish: None });
+            if config.publish.is_none() {
+                config.publish = events.publish;
+            }
+        }
+    }
+
+    /// Returns whether fetching missing tool versions on demand is forbidden, either
+    /// by the `behavior.no-implicit-fetch` setting or the `NOTION_NO_IMPLICIT_FETCH`
+    /// environment variable.
+    pub fn no_implicit_fetch(&self) -> bool {
+        env::no_implicit_fetch() || self.behavior
+            .as_ref()
+            .map(|behavior| behavior.no_implicit_fetch)
+            .unwrap_or(false)
+    }
+
+    /// Returns whether downloaded archives are unpacked as they stream in, without being
+    /// written to the cache, either by the `cache.stream` setting or the `NOTION_STREAM`
+    /// environment variable.
+    pub fn stream_downloads(&self) -> bool {
+        env::stream() || self.cache
+            .as_ref()
+            .map(|cache| cache.stream)
+            .unwrap_or(false)
+    }
+
+    /// Returns whether to deduplicate the installed Node versions after each fetch, as
+    /// the `behavior.dedupe` setting asks.
+    pub fn dedupe(&self) -> bool {
+        self.behavior
+            .as_ref()
+            .map(|behavior| behavior.dedupe)
+            .unwrap_or(false)
+    }
+
+    /// Returns whether the Yarn and pnpm shims leave selecting a package manager to
+    /// corepack, as the `behavior.corepack` setting asks.
+    pub fn defer_to_corepack(&self) -> bool {
+        self.behavior
+            .as_ref()
+            .map(|behavior| behavior.corepack)
+            .unwrap_or(false)
+    }
+
+    /// Returns the token to authenticate requests to the GitHub API with, if any: the
+    /// `github.token` setting, or else the `GITHUB_TOKEN` environment variable.
+    pub fn github_token(&self) -> Option<String> {
+        self.github
+            .as_ref()
+            .and_then(|github| github.token.clone())
+            .or_else(env::github_token)
+    }
+
+    /// Returns how often to check for a new release of Notion, or `None` if the checks
+    /// are turned off, either by the `updates.check` setting or the
+    /// `NOTION_NO_UPDATE_CHECK` environment variable.
+    pub fn update_check_interval(&self) -> Option<Duration> {
+        if env::no_update_check() {
+            return None;
+        }
+        match self.updates {
+            Some(ref updates) if !updates.check => None,
+            Some(ref updates) => Some(Duration::from_secs(updates.interval * 60 * 60)),
+            None => Some(Duration::from_secs(DEFAULT_UPDATE_INTERVAL * 60 * 60)),
+        }
+    }
+}
+
+impl<D: Distro> Default for ToolConfig<D> {
+    fn default() -> Self {
+        ToolConfig {
+            resolve: None,
+            ls_remote: None,
+            verify_signatures: false,
+            mirror: None,
+            musl_mirror: None,
+            index_ttl: None,
+            resolve_ttl: None,
+            url_template: None,
+            use_engines: true,
+            sync_engines: false,
+            phantom: PhantomData,
+        }
     }
 }
 
@@ -71,16 +227,219 @@ impl FromStr for Config {
     }
 }
 
+/// The settings a project ships for everyone who works on it, in a `hooks.toml` file
+/// at its root or the `notion.hooks` section of its `package.json`. They can only say
+/// where tools and their indexes are downloaded from and where events are published.
+///
+/// The hooks are merged under the configuration files, so the precedence of each
+/// setting is, from highest to lowest: an environment variable (e.g.
+/// `NOTION_NODE_MIRROR`), the user's configuration, the system configuration, the
+/// project's hooks, and finally the default (see the `layers` module).
+#[derive(Clone)]
+pub struct ProjectHooks {
+    pub node: Option<ToolHooks>,
+    pub yarn: Option<ToolHooks>,
+    pub npm: Option<ToolHooks>,
+    pub pnpm: Option<ToolHooks>,
+    pub events: Option<EventsConfig>,
+}
+
+/// The settings a project's hooks can make for one tool, which have the same meaning
+/// as the corresponding settings of `ToolConfig`.
+#[derive(Clone)]
+pub struct ToolHooks {
+    pub resolve: Option<plugin::ResolvePlugin>,
+    pub ls_remote: Option<plugin::Index>,
+    pub mirror: Option<String>,
+    pub musl_mirror: Option<String>,
+    pub url_template: Option<String>,
+}
+
+impl FromStr for ProjectHooks {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let serial: serial::ProjectHooks = toml::from_str(src).unknown()?;
+        Ok(serial.into_project_hooks()?)
+    }
+}
+
+/// Fills in the settings for a tool that the user's configuration doesn't make from a
+/// project's hooks.
+fn merge_tool_hooks<D: Distro>(config: &mut Option<ToolConfig<D>>, hooks: Option<ToolHooks>) {
+    let hooks = match hooks {
+        Some(hooks) => hooks,
+        None => return,
+    };
+    let config = config.get_or_insert_with(ToolConfig::default);
+    if config.resolve.is_none() {
+        config.resolve = hooks.resolve;
+    }
+    if config.ls_remote.is_none() {
+        config.ls_remote = hooks.ls_remote;
+    }
+    if config.mirror.is_none() {
+        config.mirror = hooks.mirror;
+    }
+    if config.musl_mirror.is_none() {
+        config.musl_mirror = hooks.musl_mirror;
+    }
+    if config.url_template.is_none() {
+        config.url_template = hooks.url_template;
+    }
+}
+
 /// Notion configuration settings related to events.
+#[derive(Clone)]
 pub struct EventsConfig {
     /// The plugin for publishing events, if any.
     pub publish: Option<plugin::Publish>,
 }
 
+/// Notion configuration settings for sending downloads through a proxy. Each setting
+/// takes precedence over the corresponding standard environment variable.
+pub struct ProxyConfig {
+    /// The proxy for `http` URLs, if any (overrides `HTTP_PROXY`).
+    pub http: Option<String>,
+    /// The proxy for `https` URLs, if any (overrides `HTTPS_PROXY`).
+    pub https: Option<String>,
+    /// Hosts and domains that should be accessed directly (in addition to `NO_PROXY`).
+    pub no_proxy: Vec<String>,
+}
+
+/// Notion configuration settings for retrying downloads that fail due to transient
+/// network errors.
+pub struct RetryConfig {
+    /// The maximum number of times to attempt each download, including the first try.
+    pub attempts: u32,
+    /// The delay in milliseconds before the first retry, which doubles with each
+    /// subsequent retry.
+    pub backoff: u64,
+}
+
+/// Notion configuration settings for the HTTP client that every request is sent with.
+pub struct HttpConfig {
+    /// The number of seconds a connection, read, or write may take before the request
+    /// is given up on, if configured; otherwise requests never time out.
+    pub timeout: Option<u64>,
+    /// The number of byte ranges each archive is downloaded in at once, if configured,
+    /// which speeds up downloads of large archives over high-latency links. Archives
+    /// whose server doesn't accept byte ranges are still downloaded in one request.
+    pub chunks: Option<u64>,
+}
+
+/// Notion configuration settings for finding the project that contains a directory.
+/// Discovery searches parent directories for a `package.json` file, stopping at the
+/// first directory that is a boundary.
+pub struct ProjectConfig {
+    /// Whether the root of a Git or Mercurial repository is a boundary.
+    pub vcs_boundary: bool,
+    /// The names of files or directories that mark a directory as a boundary.
+    pub boundary_markers: Vec<String>,
+    /// The path of keys to the section of package.json that pins the toolchain, e.g.
+    /// `["notion"]` for pins written as `"notion": { "node": ... }`. Defaults to
+    /// `toolchain`.
+    pub toolchain_key: Vec<String>,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        ProjectConfig {
+            vcs_boundary: true,
+            boundary_markers: vec![],
+            toolchain_key: vec![TOOLCHAIN_KEY.to_string()],
+        }
+    }
+}
+
+/// Notion configuration settings for limiting the size of the cache of downloaded
+/// archives. The limits are enforced after each fetch, by evicting the least recently
+/// used archives first.
+pub struct CacheConfig {
+    /// The maximum total size in bytes of the cached archives, if any.
+    pub max_size: Option<u64>,
+    /// The maximum age in days of a cached archive since it was last used, if any.
+    pub max_age: Option<u64>,
+    /// Whether to keep every archive regardless of the limits, so that any version can
+    /// be reinstalled offline. This takes precedence over `keep`: with it, archives
+    /// are kept after their install even if `keep` is false.
+    pub keep_archives: bool,
+    /// Whether to keep each downloaded archive once the version in it is installed (the
+    /// default). Without it (and without `keep_archives`), each archive is removed as
+    /// soon as its install completes and is verified, which saves disk space on CI
+    /// runners, say.
+    pub keep: bool,
+    /// Whether to unpack each downloaded archive as it streams in, checksumming it on
+    /// the fly, without writing it to the cache (to save disk space on CI runners, say).
+    /// Zip files, which can't be unpacked before they are complete, are still cached.
+    pub stream: bool,
+}
+
+/// Notion configuration settings for the npm registries that packages are fetched
+/// from. Each setting takes precedence over the corresponding setting in the user's
+/// `.npmrc`, which still provides the credentials for each registry.
+pub struct RegistryConfig {
+    /// The registry for packages without a scope registry, if any (overrides `registry`).
+    pub default: Option<String>,
+    /// The registry for each scope (such as `@company`), by scope (overrides
+    /// `@company:registry`).
+    pub scopes: HashMap<String, String>,
+}
+
+/// Notion configuration settings for how tools are provisioned.
+pub struct BehaviorConfig {
+    /// Whether running a tool whose selected version isn't installed is an error,
+    /// rather than fetching the version on demand, so that builds (in CI, say) only
+    /// ever use versions fetched explicitly.
+    pub no_implicit_fetch: bool,
+    /// Whether to replace the files of each Node version fetched that are identical to
+    /// files of other installed versions with hard links (see the `dedupe` module).
+    pub dedupe: bool,
+    /// Whether the Yarn and pnpm shims run the corepack of the selected Node, which
+    /// picks the package manager version itself, rather than a version Notion selects.
+    pub corepack: bool,
+}
+
+/// Notion configuration settings for the hint printed when a new release of Notion
+/// is available.
+pub struct UpdatesConfig {
+    /// Whether to check for a new release at all.
+    pub check: bool,
+    /// The number of hours between checks.
+    pub interval: u64,
+}
+
+/// Notion configuration settings for the usage telemetry the user can opt into (see
+/// the `telemetry` module).
+pub struct TelemetryConfig {
+    /// The URL the batched records are uploaded to. Without one, records are only
+    /// batched locally.
+    pub endpoint: Option<String>,
+}
+
+/// Notion configuration settings for the requests Notion makes to the GitHub API, which
+/// lists the Yarn releases.
+pub struct GithubConfig {
+    /// The token to authenticate the requests with, if any. Unauthenticated requests
+    /// are rate limited to sixty an hour for each address.
+    pub token: Option<String>,
+}
+
+/// Notion configuration settings for the lifecycle hooks: the commands run before and
+/// after a tool is fetched, pinned, or uninstalled (see the `lifecycle` module).
+pub struct LifecycleConfig {
+    pub pre_fetch: Option<String>,
+    pub post_fetch: Option<String>,
+    pub pre_pin: Option<String>,
+    pub post_pin: Option<String>,
+    pub pre_uninstall: Option<String>,
+    pub post_uninstall: Option<String>,
+}
+
 #[cfg(test)]
 pub mod tests {
 
-    use config::Config;
+    use config::{Config, ProjectHooks};
     use plugin;
     use std::fs;
     use std::path::PathBuf;
@@ -108,7 +467,7 @@ pub mod tests {
         );
         assert_eq!(
             node_config.yarn.unwrap().ls_remote,
-            Some(plugin::LsRemote::Url("https://yarnpkg.com".to_string()))
+            Some(plugin::Index::Url("https://yarnpkg.com".to_string()))
         );
         assert_eq!(
             node_config.events.unwrap().publish,
@@ -132,11 +491,288 @@ pub mod tests {
         );
         assert_eq!(
             node_config.yarn.unwrap().ls_remote,
-            Some(plugin::LsRemote::Bin("/bin/to/yarn".to_string()))
+            Some(plugin::Index::Bin("/bin/to/yarn".to_string()))
         );
         assert_eq!(
             node_config.events.unwrap().publish,
             Some(plugin::Publish::Bin("/events/bin".to_string()))
         );
     }
+
+    #[test]
+    fn test_from_str_verify_signatures() {
+        let config: Config = "[node]\nverify-signatures = true\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.node.unwrap().verify_signatures, true);
+
+        let config: Config = "[node]\n".parse().expect("Could not parse config");
+        assert_eq!(config.node.unwrap().verify_signatures, false);
+    }
+
+    #[test]
+    fn test_from_str_retry() {
+        let config: Config = "[retry]\nattempts = 5\nbackoff = 250\n"
+            .parse()
+            .expect("Could not parse config");
+        let retry = config.retry.unwrap();
+        assert_eq!(retry.attempts, 5);
+        assert_eq!(retry.backoff, 250);
+
+        let config: Config = "[retry]\n".parse().expect("Could not parse config");
+        let retry = config.retry.unwrap();
+        assert_eq!(retry.attempts, 3);
+        assert_eq!(retry.backoff, 500);
+    }
+
+    #[test]
+    fn test_from_str_http() {
+        let config: Config = "[http]\ntimeout = 30\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.http.unwrap().timeout, Some(30));
+
+        let config: Config = "[http]\n".parse().expect("Could not parse config");
+        let http = config.http.unwrap();
+        assert_eq!(http.timeout, None);
+        assert_eq!(http.chunks, None);
+
+        let config: Config = "[http]\nchunks = 4\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.http.unwrap().chunks, Some(4));
+    }
+
+    #[test]
+    fn test_from_str_project() {
+        let config: Config = "[project]\nvcs-boundary = false\nboundary-markers = [\".notion-root\"]\n"
+            .parse()
+            .expect("Could not parse config");
+        let project = config.project.unwrap();
+        assert_eq!(project.vcs_boundary, false);
+        assert_eq!(project.boundary_markers, vec![".notion-root".to_string()]);
+
+        let config: Config = "[project]\n".parse().expect("Could not parse config");
+        let project = config.project.unwrap();
+        assert_eq!(project.vcs_boundary, true);
+        assert!(project.boundary_markers.is_empty());
+        assert_eq!(project.toolchain_key, vec!["toolchain".to_string()]);
+
+        let config: Config = "[project]\ntoolchain-key = \"config.notion\"\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(
+            config.project.unwrap().toolchain_key,
+            vec!["config".to_string(), "notion".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_from_str_cache() {
+        let config: Config = "[cache]\nmax-size = 1073741824\nmax-age = 30\n"
+            .parse()
+            .expect("Could not parse config");
+        let cache = config.cache.unwrap();
+        assert_eq!(cache.max_size, Some(1073741824));
+        assert_eq!(cache.max_age, Some(30));
+        assert_eq!(cache.keep_archives, false);
+        assert_eq!(cache.keep, true);
+
+        let config: Config = "[cache]\nkeep-archives = true\n"
+            .parse()
+            .expect("Could not parse config");
+        let cache = config.cache.unwrap();
+        assert_eq!(cache.max_size, None);
+        assert_eq!(cache.keep_archives, true);
+        assert_eq!(cache.stream, false);
+
+        let config: Config = "[cache]\nstream = true\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.cache.unwrap().stream, true);
+
+        let config: Config = "[cache]\nkeep = false\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.cache.unwrap().keep, false);
+    }
+
+    #[test]
+    fn test_from_str_updates() {
+        let config: Config = "[updates]\ninterval = 168\n"
+            .parse()
+            .expect("Could not parse config");
+        let updates = config.updates.unwrap();
+        assert_eq!(updates.check, true);
+        assert_eq!(updates.interval, 168);
+
+        let config: Config = "[updates]\ncheck = false\n"
+            .parse()
+            .expect("Could not parse config");
+        assert!(config.update_check_interval().is_none());
+
+        let config: Config = "".parse().expect("Could not parse config");
+        assert!(config.updates.is_none());
+    }
+
+    #[test]
+    fn test_from_str_telemetry() {
+        let config: Config = "[telemetry]\nendpoint = \"https://telemetry.example.com/v1\"\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(
+            config.telemetry.unwrap().endpoint,
+            Some("https://telemetry.example.com/v1".to_string())
+        );
+
+        let config: Config = "".parse().expect("Could not parse config");
+        assert!(config.telemetry.is_none());
+    }
+
+    #[test]
+    fn test_from_str_github() {
+        let config: Config = "[github]\ntoken = \"ghp_example\"\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.github_token(), Some("ghp_example".to_string()));
+    }
+
+    #[test]
+    fn test_from_str_use_engines() {
+        let config: Config = "[node]\nuse-engines = false\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.node.unwrap().use_engines, false);
+
+        let config: Config = "[node]\n".parse().expect("Could not parse config");
+        assert_eq!(config.node.unwrap().use_engines, true);
+    }
+
+    #[test]
+    fn test_from_str_sync_engines() {
+        let config: Config = "[node]\nsync-engines = true\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.node.unwrap().sync_engines, true);
+
+        let config: Config = "[node]\n".parse().expect("Could not parse config");
+        assert_eq!(config.node.unwrap().sync_engines, false);
+    }
+
+    #[test]
+    fn test_from_str_index_ttl() {
+        let config: Config = "[node]\nindex-ttl = 3600\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.node.unwrap().index_ttl, Some(3600));
+    }
+
+    #[test]
+    fn test_from_str_resolve_ttl() {
+        let config: Config = "[yarn]\nresolve-ttl = 0\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(config.yarn.unwrap().resolve_ttl, Some(0));
+    }
+
+    #[test]
+    fn test_from_str_mirror() {
+        let config: Config = "[node]\nmirror = \"https://mirror.example.com/node/\"\n"
+            .parse()
+            .expect("Could not parse config");
+        assert_eq!(
+            config.node.unwrap().mirror,
+            Some("https://mirror.example.com/node/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_str_musl_mirror() {
+        let config: Config = "[node]\nmusl-mirror = \"https://mirror.example.com/node-musl/\"\n"
+            .parse()
+            .expect("Could not parse config");
+        let node = config.node.unwrap();
+        assert_eq!(node.mirror, None);
+        assert_eq!(
+            node.musl_mirror,
+            Some("https://mirror.example.com/node-musl/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_str_url_template() {
+        let config: Config =
+            "[node]\nurl-template = \"https://mirror.corp/node/{version}/{filename}\"\n[npm]\n"
+                .parse()
+                .expect("Could not parse config");
+        assert_eq!(
+            config.node.unwrap().url_template,
+            Some("https://mirror.corp/node/{version}/{filename}".to_string())
+        );
+        assert_eq!(config.npm.unwrap().url_template, None);
+        assert!(config.yarn.is_none());
+    }
+
+    #[test]
+    fn test_merge_project_hooks() {
+        let mut config: Config = "[node]\nmirror = \"https://mirror.example.com/node/\"\n"
+            .parse()
+            .expect("Could not parse config");
+        let hooks: ProjectHooks = "[node]\nmirror = \"https://mirror.corp/node/\"\nurl-template = \"https://mirror.corp/node/{filename}\"\n[yarn]\nmirror = \"https://mirror.corp/yarn/\"\n"
+            .parse()
+            .expect("Could not parse hooks");
+        config.merge_project_hooks(hooks);
+
+        // the user's own settings take precedence over the project's
+        let node = config.node.unwrap();
+        assert_eq!(node.mirror, Some("https://mirror.example.com/node/".to_string()));
+        assert_eq!(
+            node.url_template,
+            Some("https://mirror.corp/node/{filename}".to_string())
+        );
+        let yarn = config.yarn.unwrap();
+        assert_eq!(yarn.mirror, Some("https://mirror.corp/yarn/".to_string()));
+        assert_eq!(yarn.use_engines, true);
+        assert!(config.npm.is_none());
+    }
+
+    #[test]
+    fn test_from_str_registry() {
+        let config: Config = "[registry]\ndefault = \"https://npm.example.com\"\n\n[registry.scopes]\ncompany = \"https://npm.company.com/\"\n\"@internal\" = \"https://internal.example.com/npm\"\n"
+            .parse()
+            .expect("Could not parse config");
+        let registry = config.registry.unwrap();
+        assert_eq!(registry.default, Some("https://npm.example.com/".to_string()));
+        assert_eq!(
+            registry.scopes.get("@company"),
+            Some(&"https://npm.company.com/".to_string())
+        );
+        assert_eq!(
+            registry.scopes.get("@internal"),
+            Some(&"https://internal.example.com/npm/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_str_behavior() {
+        let config: Config = "[behavior]\nno-implicit-fetch = true\n"
+            .parse()
+            .expect("Could not parse config");
+        assert!(!config.dedupe());
+        assert!(config.behavior.unwrap().no_implicit_fetch);
+
+        let config: Config = "[behavior]\ndedupe = true\n"
+            .parse()
+            .expect("Could not parse config");
+        assert!(config.dedupe());
+        assert!(!config.defer_to_corepack());
+
+        let config: Config = "[behavior]\ncorepack = true\n"
+            .parse()
+            .expect("Could not parse config");
+        assert!(config.defer_to_corepack());
+
+        let config: Config = "".parse().expect("Could not parse config");
+        assert!(config.behavior.is_none());
+    }
 }
@@ -0,0 +1,298 @@
+//! The settings a configuration file can make, and the kind of value each takes, for
+//! checking a file before it is deserialized, so that a mistake in it is reported
+//! with the setting it's in and where, instead of as a failure to deserialize.
+
+use std::path::Path;
+
+use reqwest::Url;
+use toml::value::{Table, Value};
+
+use notion_fail::Fallible;
+
+/// The kinds of value a setting takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Str,
+    /// An absolute URL.
+    Url,
+    Bool,
+    /// A non-negative integer.
+    Int,
+    /// A list of strings, which an environment variable separates with commas.
+    List,
+    /// A URL template that ends in the archive's file name (`/{filename}`), so that the
+    /// checksums published in the same directory as the archive can be found.
+    ArchiveTemplate,
+}
+
+impl Kind {
+    /// Describes the values of this kind, for error messages.
+    pub(crate) fn expected(self) -> &'static str {
+        match self {
+            Kind::Str => "a string",
+            Kind::Url => "a URL",
+            Kind::Bool => "a boolean (true or false)",
+            Kind::Int => "a non-negative integer",
+            Kind::List => "a list of strings",
+            Kind::ArchiveTemplate => "a URL template ending in '/{filename}'",
+        }
+    }
+}
+
+/// The settings of each tool's section.
+const TOOL_KEYS: &[(&str, Kind)] = &[
+    ("resolve.url", Kind::Url),
+    ("resolve.bin", Kind::Str),
+    ("ls-remote.url", Kind::Url),
+    ("ls-remote.bin", Kind::Str),
+    ("verify-signatures", Kind::Bool),
+    ("mirror", Kind::Url),
+    ("musl-mirror", Kind::Url),
+    ("index-ttl", Kind::Int),
+    ("resolve-ttl", Kind::Int),
+    ("url-template", Kind::Str),
+    ("use-engines", Kind::Bool),
+    ("sync-engines", Kind::Bool),
+];
+
+/// The other settings, except the scopes of `registry.scopes` (see `SCOPES`).
+const KEYS: &[(&str, Kind)] = &[
+    ("events.publish.url", Kind::Url),
+    ("events.publish.bin", Kind::Str),
+    ("proxy.http", Kind::Url),
+    ("proxy.https", Kind::Url),
+    ("proxy.no-proxy", Kind::List),
+    ("retry.attempts", Kind::Int),
+    ("retry.backoff", Kind::Int),
+    ("http.timeout", Kind::Int),
+    ("http.chunks", Kind::Int),
+    ("project.vcs-boundary", Kind::Bool),
+    ("project.boundary-markers", Kind::List),
+    ("project.toolchain-key", Kind::Str),
+    ("cache.max-size", Kind::Int),
+    ("cache.max-age", Kind::Int),
+    ("cache.keep-archives", Kind::Bool),
+    ("cache.keep", Kind::Bool),
+    ("cache.stream", Kind::Bool),
+    ("registry.default", Kind::Url),
+    ("behavior.no-implicit-fetch", Kind::Bool),
+    ("behavior.dedupe", Kind::Bool),
+    ("behavior.corepack", Kind::Bool),
+    ("lifecycle.pre-fetch", Kind::Str),
+    ("lifecycle.post-fetch", Kind::Str),
+    ("lifecycle.pre-pin", Kind::Str),
+    ("lifecycle.post-pin", Kind::Str),
+    ("lifecycle.pre-uninstall", Kind::Str),
+    ("lifecycle.post-uninstall", Kind::Str),
+    ("updates.check", Kind::Bool),
+    ("updates.interval", Kind::Int),
+    ("telemetry.endpoint", Kind::Url),
+    ("github.token", Kind::Str),
+];
+
+/// The section whose settings are named by the user: each maps a scope to the URL
+/// of its registry.
+const SCOPES: &str = "registry.scopes";
+
+/// Every setting with a fixed name, with the kind of its value.
+pub(crate) fn keys() -> Vec<(String, Kind)> {
+    let mut keys = vec![];
+    for tool in &["node", "yarn", "npm", "pnpm"] {
+        for &(key, kind) in TOOL_KEYS {
+            // Node's checksums are looked up beside its archives.
+            let kind = match (*tool, key) {
+                ("node", "url-template") => Kind::ArchiveTemplate,
+                _ => kind,
+            };
+            keys.push((format!("{}.{}", tool, key), kind));
+        }
+    }
+    for &(key, kind) in KEYS {
+        keys.push((key.to_string(), kind));
+    }
+    keys
+}
+
+/// Thrown when a configuration file makes a setting that doesn't exist, or gives a
+/// setting a value it can't take.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid setting '{}' in {}: {}", key, location, problem)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct InvalidSettingError {
+    pub(crate) key: String,
+    /// The file, and the line of the setting in it if it can be found.
+    pub(crate) location: String,
+    pub(crate) problem: String,
+}
+
+/// Checks that a value is of the specified kind, describing the problem if not.
+pub(crate) fn check(value: &Value, kind: Kind) -> Result<(), String> {
+    let ok = match (kind, value) {
+        (Kind::Str, &Value::String(_)) => true,
+        (Kind::Url, &Value::String(ref url)) => {
+            return Url::parse(url)
+                .map(|_| ())
+                .map_err(|error| format!("expected a URL, found '{}' ({})", url, error));
+        }
+        (Kind::Bool, &Value::Boolean(_)) => true,
+        (Kind::Int, &Value::Integer(n)) => n >= 0,
+        (Kind::List, &Value::Array(ref items)) => items.iter().all(Value::is_str),
+        (Kind::ArchiveTemplate, &Value::String(ref template)) => template.ends_with("/{filename}"),
+        _ => false,
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!("expected {}, found {}", kind.expected(), describe(value)))
+    }
+}
+
+/// Describes a value, for error messages.
+fn describe(value: &Value) -> String {
+    match *value {
+        Value::Integer(n) => format!("the integer {}", n),
+        Value::Array(_) => "a list".to_string(),
+        Value::Table(_) => "a section".to_string(),
+        _ => format!("{} {}", value.type_str(), value),
+    }
+}
+
+/// Checks every setting a configuration file makes.
+pub(crate) fn validate(file: &Path, src: &str, table: &Table) -> Fallible<()> {
+    let keys = keys();
+    match validate_table("", table, &keys) {
+        Ok(()) => Ok(()),
+        Err((key, problem)) => {
+            let location = match line_of(src, &key) {
+                Some(line) => format!("{}, line {}", file.display(), line),
+                None => file.display().to_string(),
+            };
+            throw!(InvalidSettingError {
+                key,
+                location,
+                problem,
+            });
+        }
+    }
+}
+
+/// Checks the settings of a table, returning the first invalid one and its problem.
+fn validate_table(
+    prefix: &str,
+    table: &Table,
+    keys: &[(String, Kind)],
+) -> Result<(), (String, String)> {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+
+        if prefix == SCOPES {
+            check(value, Kind::Url).map_err(|problem| (key.clone(), problem))?;
+            continue;
+        }
+
+        if let Some(&(_, kind)) = keys.iter().find(|&&(ref known, _)| *known == key) {
+            check(value, kind).map_err(|problem| (key.clone(), problem))?;
+            continue;
+        }
+
+        let section = format!("{}.", key);
+        let is_section = key == SCOPES || keys.iter().any(|&(ref known, _)| known.starts_with(&section));
+        match *value {
+            Value::Table(ref inner) if is_section => validate_table(&key, inner, keys)?,
+            _ if is_section => {
+                return Err((key, format!("expected a section, found {}", describe(value))));
+            }
+            _ => return Err((key, "there is no such setting".to_string())),
+        }
+    }
+    Ok(())
+}
+
+/// Finds the line of a configuration file that makes a setting (or starts its
+/// section), counting from 1. Settings made in inline tables are found by the line
+/// of the table.
+fn line_of(src: &str, key: &str) -> Option<usize> {
+    let mut section = String::new();
+    for (index, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            section = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .split('.')
+                .map(|part| part.trim().trim_matches('"'))
+                .collect::<Vec<_>>()
+                .join(".");
+            if section == key {
+                return Some(index + 1);
+            }
+            continue;
+        }
+        if let Some(eq) = line.find('=') {
+            let name = line[..eq].trim().trim_matches('"');
+            let full = if section.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", section, name)
+            };
+            if full == key || key.starts_with(&format!("{}.", full)) {
+                return Some(index + 1);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{line_of, validate};
+    use std::path::Path;
+    use toml;
+
+    fn error(src: &str) -> String {
+        let table = toml::from_str(src).unwrap();
+        validate(Path::new("config.toml"), src, &table)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn test_validate() {
+        let src = "[node]\nmirror = \"https://mirror/\"\nindex-ttl = 60\n\n[registry.scopes]\n\"@corp\" = \"https://npm.corp/\"\n";
+        let table = toml::from_str(src).unwrap();
+        assert!(validate(Path::new("config.toml"), src, &table).is_ok());
+
+        assert_eq!(
+            error("[node]\nmirror = \"https://mirror/\"\nindex-tl = 60\n"),
+            "Invalid setting 'node.index-tl' in config.toml, line 3: there is no such setting"
+        );
+        assert_eq!(
+            error("[http]\ntimeout = \"30\"\n"),
+            "Invalid setting 'http.timeout' in config.toml, line 2: expected a non-negative integer, found string \"30\""
+        );
+        assert_eq!(
+            error("[proxy]\nhttps = \"localhost\"\n"),
+            "Invalid setting 'proxy.https' in config.toml, line 2: expected a URL, found 'localhost' (relative URL without a base)"
+        );
+        assert_eq!(
+            error("[node]\nurl-template = \"file:///mnt/node/{version}.{ext}\"\n"),
+            "Invalid setting 'node.url-template' in config.toml, line 2: expected a URL template ending in '/{filename}', found string \"file:///mnt/node/{version}.{ext}\""
+        );
+        assert_eq!(
+            error("[behaviour]\ndedupe = true\n"),
+            "Invalid setting 'behaviour' in config.toml, line 1: there is no such setting"
+        );
+    }
+
+    #[test]
+    fn test_line_of() {
+        let src = "[yarn]\nresolve = { url = \"https://resolve/\" }\n\n[yarn.ls-remote]\nurl = \"https://ls-remote/\"\n";
+        assert_eq!(line_of(src, "yarn.resolve.url"), Some(2));
+        assert_eq!(line_of(src, "yarn.ls-remote.url"), Some(5));
+        assert_eq!(line_of(src, "npm.mirror"), None);
+    }
+}
@@ -1,9 +1,16 @@
 use super::super::config;
+use super::schema::{self, InvalidSettingError, Kind};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
+use toml::value::Value;
+
 use distro::Distro;
 use distro::node::NodeDistro;
+use distro::npm::NpmDistro;
+use distro::pnpm::PnpmDistro;
 use distro::yarn::YarnDistro;
+use http;
 use plugin::serial::Plugin;
 
 use notion_fail::Fallible;
@@ -12,7 +19,20 @@ use notion_fail::Fallible;
 pub struct Config {
     pub node: Option<ToolConfig<NodeDistro>>,
     pub yarn: Option<ToolConfig<YarnDistro>>,
+    pub npm: Option<ToolConfig<NpmDistro>>,
+    pub pnpm: Option<ToolConfig<PnpmDistro>>,
     pub events: Option<EventsConfig>,
+    pub proxy: Option<ProxyConfig>,
+    pub retry: Option<RetryConfig>,
+    pub http: Option<HttpConfig>,
+    pub project: Option<ProjectConfig>,
+    pub cache: Option<CacheConfig>,
+    pub registry: Option<RegistryConfig>,
+    pub behavior: Option<BehaviorConfig>,
+    pub lifecycle: Option<LifecycleConfig>,
+    pub updates: Option<UpdatesConfig>,
+    pub telemetry: Option<TelemetryConfig>,
+    pub github: Option<GithubConfig>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,6 +41,340 @@ pub struct EventsConfig {
     pub publish: Option<Plugin>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "proxy")]
+pub struct ProxyConfig {
+    pub http: Option<String>,
+    pub https: Option<String>,
+
+    #[serde(rename = "no-proxy")]
+    pub no_proxy: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "retry")]
+pub struct RetryConfig {
+    pub attempts: Option<u32>,
+    pub backoff: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "http")]
+pub struct HttpConfig {
+    pub timeout: Option<u64>,
+    pub chunks: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "project")]
+pub struct ProjectConfig {
+    #[serde(rename = "vcs-boundary")]
+    pub vcs_boundary: Option<bool>,
+
+    #[serde(rename = "boundary-markers")]
+    pub boundary_markers: Option<Vec<String>>,
+
+    #[serde(rename = "toolchain-key")]
+    pub toolchain_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "cache")]
+pub struct CacheConfig {
+    #[serde(rename = "max-size")]
+    pub max_size: Option<u64>,
+
+    #[serde(rename = "max-age")]
+    pub max_age: Option<u64>,
+
+    #[serde(rename = "keep-archives")]
+    pub keep_archives: Option<bool>,
+
+    pub keep: Option<bool>,
+
+    pub stream: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "registry")]
+pub struct RegistryConfig {
+    pub default: Option<String>,
+    pub scopes: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "behavior")]
+pub struct BehaviorConfig {
+    #[serde(rename = "no-implicit-fetch")]
+    pub no_implicit_fetch: Option<bool>,
+
+    pub dedupe: Option<bool>,
+
+    pub corepack: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "lifecycle")]
+pub struct LifecycleConfig {
+    #[serde(rename = "pre-fetch")]
+    pub pre_fetch: Option<String>,
+
+    #[serde(rename = "post-fetch")]
+    pub post_fetch: Option<String>,
+
+    #[serde(rename = "pre-pin")]
+    pub pre_pin: Option<String>,
+
+    #[serde(rename = "post-pin")]
+    pub post_pin: Option<String>,
+
+    #[serde(rename = "pre-uninstall")]
+    pub pre_uninstall: Option<String>,
+
+    #[serde(rename = "post-uninstall")]
+    pub post_uninstall: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "updates")]
+pub struct UpdatesConfig {
+    pub check: Option<bool>,
+    pub interval: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "telemetry")]
+pub struct TelemetryConfig {
+    pub endpoint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "github")]
+pub struct GithubConfig {
+    pub token: Option<String>,
+}
+
+impl GithubConfig {
+    pub fn into_github_config(self) -> config::GithubConfig {
+        config::GithubConfig { token: self.token }
+    }
+}
+
+impl TelemetryConfig {
+    pub fn into_telemetry_config(self) -> config::TelemetryConfig {
+        config::TelemetryConfig {
+            endpoint: self.endpoint,
+        }
+    }
+}
+
+impl UpdatesConfig {
+    pub fn into_updates_config(self) -> config::UpdatesConfig {
+        config::UpdatesConfig {
+            check: self.check.unwrap_or(true),
+            interval: self.interval.unwrap_or(config::DEFAULT_UPDATE_INTERVAL),
+        }
+    }
+}
+
+impl LifecycleConfig {
+    pub fn into_lifecycle_config(self) -> config::LifecycleConfig {
+        config::LifecycleConfig {
+            pre_fetch: self.pre_fetch,
+            post_fetch: self.post_fetch,
+            pre_pin: self.pre_pin,
+            post_pin: self.post_pin,
+            pre_uninstall: self.pre_uninstall,
+            post_uninstall: self.post_uninstall,
+        }
+    }
+}
+
+impl BehaviorConfig {
+    pub fn into_behavior_config(self) -> config::BehaviorConfig {
+        config::BehaviorConfig {
+            no_implicit_fetch: self.no_implicit_fetch.unwrap_or(false),
+            dedupe: self.dedupe.unwrap_or(false),
+            corepack: self.corepack.unwrap_or(false),
+        }
+    }
+}
+
+/// Normalizes a registry URL to end with a `/`, as npm does.
+fn with_trailing_slash(url: String) -> String {
+    if url.ends_with('/') {
+        url
+    } else {
+        format!("{}/", url)
+    }
+}
+
+impl RegistryConfig {
+    pub fn into_registry_config(self) -> config::RegistryConfig {
+        config::RegistryConfig {
+            default: self.default.map(with_trailing_slash),
+            scopes: self.scopes
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(scope, registry)| {
+                    // accept scopes with or without their leading `@`
+                    let scope = if scope.starts_with('@') {
+                        scope
+                    } else {
+                        format!("@{}", scope)
+                    };
+                    (scope, with_trailing_slash(registry))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn into_cache_config(self) -> config::CacheConfig {
+        config::CacheConfig {
+            max_size: self.max_size,
+            max_age: self.max_age,
+            keep_archives: self.keep_archives.unwrap_or(false),
+            keep: self.keep.unwrap_or(true),
+            stream: self.stream.unwrap_or(false),
+        }
+    }
+}
+
+impl ProjectConfig {
+    pub fn into_project_config(self) -> config::ProjectConfig {
+        let default = config::ProjectConfig::default();
+        // a nested key is written with dots, e.g. `config.notion`
+        let toolchain_key: Vec<String> = self.toolchain_key
+            .as_ref()
+            .map(|key| {
+                key.split('.')
+                    .filter(|part| !part.is_empty())
+                    .map(|part| part.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        config::ProjectConfig {
+            vcs_boundary: self.vcs_boundary.unwrap_or(true),
+            boundary_markers: self.boundary_markers.unwrap_or_default(),
+            toolchain_key: if toolchain_key.is_empty() {
+                default.toolchain_key
+            } else {
+                toolchain_key
+            },
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn into_retry_config(self) -> config::RetryConfig {
+        config::RetryConfig {
+            attempts: self.attempts.unwrap_or(http::DEFAULT_ATTEMPTS),
+            backoff: self.backoff.unwrap_or(http::DEFAULT_BACKOFF),
+        }
+    }
+}
+
+impl HttpConfig {
+    pub fn into_http_config(self) -> config::HttpConfig {
+        config::HttpConfig {
+            timeout: self.timeout,
+            chunks: self.chunks,
+        }
+    }
+}
+
+impl ProxyConfig {
+    pub fn into_proxy_config(self) -> config::ProxyConfig {
+        config::ProxyConfig {
+            http: self.http,
+            https: self.https,
+            no_proxy: self.no_proxy.unwrap_or_default(),
+        }
+    }
+}
+
+/// The hooks a project ships, in `hooks.toml` or the `notion.hooks` section of its
+/// `package.json`.
+#[derive(Serialize, Deserialize)]
+pub struct ProjectHooks {
+    pub node: Option<ToolHooks>,
+    pub yarn: Option<ToolHooks>,
+    pub npm: Option<ToolHooks>,
+    pub pnpm: Option<ToolHooks>,
+    pub events: Option<EventsConfig>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ToolHooks {
+    pub resolve: Option<Plugin>,
+
+    #[serde(rename = "ls-remote")]
+    pub ls_remote: Option<Plugin>,
+
+    pub mirror: Option<String>,
+
+    #[serde(rename = "musl-mirror")]
+    pub musl_mirror: Option<String>,
+
+    #[serde(rename = "url-template")]
+    pub url_template: Option<String>,
+}
+
+impl ProjectHooks {
+    pub fn into_project_hooks(self) -> Fallible<config::ProjectHooks> {
+        // Project hooks aren't checked against the schema like configuration files are,
+        // so the one setting whose value has to take a particular form is checked here.
+        let node_template = self.node.as_ref().and_then(|node| node.url_template.clone());
+        if let Some(template) = node_template {
+            if let Err(problem) = schema::check(&Value::String(template), Kind::ArchiveTemplate) {
+                throw!(InvalidSettingError {
+                    key: "node.url-template".to_string(),
+                    location: "the project's hooks".to_string(),
+                    problem,
+                });
+            }
+        }
+
+        Ok(config::ProjectHooks {
+            node: ToolHooks::into_tool_hooks_opt(self.node)?,
+            yarn: ToolHooks::into_tool_hooks_opt(self.yarn)?,
+            npm: ToolHooks::into_tool_hooks_opt(self.npm)?,
+            pnpm: ToolHooks::into_tool_hooks_opt(self.pnpm)?,
+            events: if let Some(e) = self.events {
+                Some(e.into_events_config()?)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl ToolHooks {
+    fn into_tool_hooks_opt(hooks: Option<ToolHooks>) -> Fallible<Option<config::ToolHooks>> {
+        let hooks = match hooks {
+            Some(hooks) => hooks,
+            None => return Ok(None),
+        };
+        Ok(Some(config::ToolHooks {
+            resolve: if let Some(p) = hooks.resolve {
+                Some(p.into_resolve()?)
+            } else {
+                None
+            },
+            ls_remote: if let Some(p) = hooks.ls_remote {
+                Some(p.into_index()?)
+            } else {
+                None
+            },
+            mirror: hooks.mirror,
+            musl_mirror: hooks.musl_mirror,
+            url_template: hooks.url_template,
+        }))
+    }
+}
+
 impl EventsConfig {
     pub fn into_events_config(self) -> Fallible<config::EventsConfig> {
         Ok(config::EventsConfig {
@@ -41,6 +395,29 @@ pub struct ToolConfig<I> {
     #[serde(rename = "ls-remote")]
     pub ls_remote: Option<Plugin>,
 
+    #[serde(rename = "verify-signatures")]
+    pub verify_signatures: Option<bool>,
+
+    pub mirror: Option<String>,
+
+    #[serde(rename = "musl-mirror")]
+    pub musl_mirror: Option<String>,
+
+    #[serde(rename = "index-ttl")]
+    pub index_ttl: Option<u64>,
+
+    #[serde(rename = "resolve-ttl")]
+    pub resolve_ttl: Option<u64>,
+
+    #[serde(rename = "url-template")]
+    pub url_template: Option<String>,
+
+    #[serde(rename = "use-engines")]
+    pub use_engines: Option<bool>,
+
+    #[serde(rename = "sync-engines")]
+    pub sync_engines: Option<bool>,
+
     #[serde(skip)]
     phantom: PhantomData<I>,
 }
@@ -58,11 +435,32 @@ impl Config {
             } else {
                 None
             },
+            npm: if let Some(n) = self.npm {
+                Some(n.into_tool_config()?)
+            } else {
+                None
+            },
+            pnpm: if let Some(p) = self.pnpm {
+                Some(p.into_tool_config()?)
+            } else {
+                None
+            },
             events: if let Some(e) = self.events {
                 Some(e.into_events_config()?)
             } else {
                 None
             },
+            proxy: self.proxy.map(ProxyConfig::into_proxy_config),
+            retry: self.retry.map(RetryConfig::into_retry_config),
+            http: self.http.map(HttpConfig::into_http_config),
+            project: self.project.map(ProjectConfig::into_project_config),
+            cache: self.cache.map(CacheConfig::into_cache_config),
+            registry: self.registry.map(RegistryConfig::into_registry_config),
+            behavior: self.behavior.map(BehaviorConfig::into_behavior_config),
+            lifecycle: self.lifecycle.map(LifecycleConfig::into_lifecycle_config),
+            updates: self.updates.map(UpdatesConfig::into_updates_config),
+            telemetry: self.telemetry.map(TelemetryConfig::into_telemetry_config),
+            github: self.github.map(GithubConfig::into_github_config),
         })
     }
 }
@@ -76,10 +474,18 @@ impl<D: Distro> ToolConfig<D> {
                 None
             },
             ls_remote: if let Some(p) = self.ls_remote {
-                Some(p.into_ls_remote()?)
+                Some(p.into_index()?)
             } else {
                 None
             },
+            verify_signatures: self.verify_signatures.unwrap_or(false),
+            mirror: self.mirror,
+            musl_mirror: self.musl_mirror,
+            index_ttl: self.index_ttl,
+            resolve_ttl: self.resolve_ttl,
+            url_template: self.url_template,
+            use_engines: self.use_engines.unwrap_or(true),
+            sync_engines: self.sync_engines.unwrap_or(false),
             phantom: PhantomData,
         })
     }
@@ -0,0 +1,229 @@
+//! Replaces the files that installed Node versions have in common (much of npm, and the
+//! headers) with hard links to a single copy, so that adjacent versions take less space.
+//!
+//! Linked files stay safe to uninstall: removing a version directory only removes its
+//! own links, and the files every other version links to remain. Notion never writes to
+//! an installed file in place, so no version can change the files of another.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use indicatif::HumanBytes;
+use semver::Version;
+
+use config::Config;
+use distro::checksum;
+use log;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+
+/// Thrown when a duplicate file could not be replaced with a hard link.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not link {} to an identical file\n{}", file, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct LinkError {
+    file: String,
+    error: String,
+}
+
+impl LinkError {
+    fn for_file(file: &Path) -> impl FnOnce(&io::Error) -> LinkError {
+        let file = file.to_string_lossy().to_string();
+        move |error| LinkError {
+            file,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// The duplicate files replaced with hard links, and the space that freed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Saved {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl Display for Saved {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "linked {} duplicate files, saving {}", self.files, HumanBytes(self.bytes))
+    }
+}
+
+/// Deduplicates the files of every installed Node version.
+pub fn node_versions() -> Fallible<Saved> {
+    let versions_dir = path::node_versions_dir()?;
+    let mut dirs = vec![];
+    if versions_dir.is_dir() {
+        for entry in fs::read_dir(&versions_dir).unknown()? {
+            let entry = entry.unknown()?;
+            // Only whole versions: not the staging directories of unfinished fetches,
+            // nor versions being uninstalled.
+            if Version::parse(&entry.file_name().to_string_lossy()).is_ok()
+                && entry.path().is_dir()
+            {
+                dirs.push(entry.path());
+            }
+        }
+    }
+    dirs.sort();
+    dedupe(&dirs)
+}
+
+/// Deduplicates the installed Node versions after a fetch, if the `behavior.dedupe`
+/// setting asks for it.
+pub(crate) fn after_fetch(config: &Config) -> Fallible<()> {
+    if config.dedupe() {
+        let saved = node_versions()?;
+        log::info(|| format!("deduplicated the Node versions: {}", saved));
+    }
+    Ok(())
+}
+
+/// Replaces every file under the directories that is identical to one found earlier
+/// (in the order of the directories) with a hard link to that file. Files are compared
+/// by size first and by content hash after, and are only linked if their permissions
+/// match too.
+pub fn dedupe(dirs: &[PathBuf]) -> Fallible<Saved> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for dir in dirs {
+        collect_files(dir, &mut by_size).unknown()?;
+    }
+
+    let mut sizes: Vec<u64> = by_size.keys().cloned().collect();
+    sizes.sort();
+
+    let mut saved = Saved::default();
+    for size in sizes {
+        let files = &by_size[&size];
+        // Linking empty files saves nothing.
+        if size == 0 || files.len() < 2 {
+            continue;
+        }
+
+        let mut originals: HashMap<String, &PathBuf> = HashMap::new();
+        for file in files {
+            let hash = checksum::sha256_file(file)?;
+            let original = originals.get(&hash).cloned();
+            match original {
+                Some(original) => {
+                    if link(original, file).with_context(LinkError::for_file(file))? {
+                        saved.files += 1;
+                        saved.bytes += size;
+                    }
+                }
+                None => {
+                    originals.insert(hash, file);
+                }
+            }
+        }
+    }
+    Ok(saved)
+}
+
+/// Lists the regular files under a directory by size. Symbolic links are left out,
+/// since they take no space of their own.
+fn collect_files(dir: &Path, by_size: &mut HashMap<u64, Vec<PathBuf>>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), by_size)?;
+        } else if file_type.is_file() {
+            let size = entry.metadata()?.len();
+            by_size.entry(size).or_insert_with(Vec::new).push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Replaces a file with a hard link to an identical original, unless it already is one,
+/// their permissions differ, or they can't be linked (as across filesystems). The link
+/// is made beside the file and renamed over it, so the file is never missing. Returns
+/// whether the file was replaced.
+fn link(original: &Path, file: &Path) -> io::Result<bool> {
+    let original_metadata = fs::metadata(original)?;
+    let metadata = fs::metadata(file)?;
+    if same_file(&original_metadata, &metadata)
+        || original_metadata.permissions() != metadata.permissions()
+    {
+        return Ok(false);
+    }
+
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let staged = file.with_file_name(format!(".{}.dedupe", name));
+    if staged.symlink_metadata().is_ok() {
+        fs::remove_file(&staged)?;
+    }
+    if fs::hard_link(original, &staged).is_err() {
+        return Ok(false);
+    }
+    if let Err(error) = fs::rename(&staged, file) {
+        let _ = fs::remove_file(&staged);
+        return Err(error);
+    }
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{dedupe, Saved};
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, File};
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn write(file: &Path, contents: &str) {
+        create_dir_all(file.parent().unwrap()).unwrap();
+        File::create(file)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let dir = tempdir().unwrap();
+        let older = dir.path().join("10.15.2");
+        let newer = dir.path().join("10.15.3");
+        write(&older.join("include").join("node.h"), "#define NODE 1");
+        write(&newer.join("include").join("node.h"), "#define NODE 1");
+        write(&older.join("bin").join("node"), "older");
+        write(&newer.join("bin").join("node"), "newer");
+
+        let dirs = vec![older.clone(), newer.clone()];
+        assert_eq!(
+            dedupe(&dirs).unwrap(),
+            Saved {
+                files: 1,
+                bytes: 14,
+            }
+        );
+        if cfg!(unix) {
+            // The duplicate is linked already.
+            assert_eq!(dedupe(&dirs).unwrap(), Saved::default());
+        }
+
+        // Uninstalling the older version leaves the newer one whole.
+        remove_dir_all(&older).unwrap();
+        assert_eq!(
+            read_to_string(newer.join("include").join("node.h")).unwrap(),
+            "#define NODE 1"
+        );
+        assert_eq!(read_to_string(newer.join("bin").join("node")).unwrap(), "newer");
+    }
+}
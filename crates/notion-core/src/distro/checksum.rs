@@ -0,0 +1,444 @@
+//! Provides utilities for verifying the integrity of downloaded distributions.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use config::Config;
+use distro::error::{ChecksumFetchError, ChecksumNotFoundError};
+use distro::signature;
+use http;
+use notion_fail::{Fallible, ResultExt};
+
+/// The name of the checksum file published alongside each Node release.
+const SHASUMS_FILE: &'static str = "SHASUMS256.txt";
+
+/// Splits a URL into the "directory" portion and the file name at the end.
+fn split_url(url: &str) -> (&str, &str) {
+    match url.rfind('/') {
+        Some(index) => (&url[..index], &url[index + 1..]),
+        None => ("", url),
+    }
+}
+
+/// Fetches the published SHA-256 checksum for the archive at the specified URL,
+/// by reading the `SHASUMS256.txt` file from the same directory on the server (or on
+/// the filesystem, for a `file://` URL).
+/// If `verify_signature` is set, the file is first checked against its detached
+/// GPG signature, `SHASUMS256.txt.sig`.
+pub(crate) fn fetch_sha256(
+    archive_url: &str,
+    verify_signature: bool,
+    config: &Config,
+) -> Fallible<String> {
+    Shasums::default().sha256(archive_url, verify_signature, config)
+}
+
+/// The `SHASUMS256.txt` files fetched so far, by URL, so that trying several archives
+/// of a release (e.g. the xz-compressed one before the default one) fetches and
+/// verifies each file only once.
+#[derive(Default)]
+pub(crate) struct Shasums {
+    files: RefCell<HashMap<String, String>>,
+}
+
+impl Shasums {
+    /// Looks up the published SHA-256 checksum for the archive at the specified URL, in
+    /// the `SHASUMS256.txt` file from the same directory, which is fetched (and checked
+    /// against its signature if `verify_signature` is set) the first time it's needed.
+    pub(crate) fn sha256(
+        &self,
+        archive_url: &str,
+        verify_signature: bool,
+        config: &Config,
+    ) -> Fallible<String> {
+        let (dir, file) = split_url(archive_url);
+        let shasums_url = format!("{}/{}", dir, SHASUMS_FILE);
+        if !self.files.borrow().contains_key(&shasums_url) {
+            let shasums = fetch_shasums(&shasums_url, verify_signature, config)?;
+            self.files.borrow_mut().insert(shasums_url.clone(), shasums);
+        }
+
+        let checksum = find_checksum(&self.files.borrow()[&shasums_url], file);
+        match checksum {
+            Some(checksum) => Ok(checksum),
+            None => {
+                throw!(ChecksumNotFoundError {
+                    file: file.to_string(),
+                    url: shasums_url,
+                });
+            }
+        }
+    }
+}
+
+/// Reads a `SHASUMS256.txt` file, from the server or the filesystem, verifying it
+/// against its signature if `verify_signature` is set.
+fn fetch_shasums(shasums_url: &str, verify_signature: bool, config: &Config) -> Fallible<String> {
+    http::ensure_online(shasums_url)?;
+
+    let shasums = match http::file_url_path(shasums_url) {
+        Some(path) => read_to_string(&path).with_context(|error| ChecksumFetchError {
+            url: shasums_url.to_string(),
+            error: error.to_string(),
+        })?,
+        None => {
+            let mut response = http::get(shasums_url, config)
+                .with_context(ChecksumFetchError::for_url(shasums_url.to_string()))?;
+
+            if !response.status().is_success() {
+                throw!(ChecksumFetchError {
+                    url: shasums_url.to_string(),
+                    error: format!("HTTP failure ({})", response.status()),
+                });
+            }
+
+            response
+                .text()
+                .with_context(ChecksumFetchError::for_url(shasums_url.to_string()))?
+        }
+    };
+
+    if verify_signature {
+        signature::verify(shasums_url, &shasums, config)?;
+    }
+    Ok(shasums)
+}
+
+/// Looks up the checksum of the specified file in the contents of a `SHASUMS256.txt`
+/// file, which lists one `<checksum>  <file name>` pair per line.
+fn find_checksum(shasums: &str, file: &str) -> Option<String> {
+    shasums
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some(checksum), Some(name)) if name == file => Some(checksum.to_lowercase()),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// Computes the digest of the contents of a reader with the specified algorithm.
+fn digest<D: Digest + Default, R: Read>(mut reader: R) -> Fallible<Vec<u8>> {
+    let mut hasher = D::default();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let read = reader.read(&mut buffer).unknown()?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+    }
+
+    Ok(hasher.result().to_vec())
+}
+
+/// Encodes bytes in lower-case hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Computes the hex-encoded SHA-256 checksum of the contents of a reader.
+pub(crate) fn sha256<R: Read>(reader: R) -> Fallible<String> {
+    Ok(hex(&digest::<Sha256, R>(reader)?))
+}
+
+/// A SHA-256 checksum computed on the fly, from the bytes written to it, for an
+/// archive that is streamed without being cached. Clones share the same checksum, so
+/// that one can be handed to the download as its sink.
+#[derive(Clone, Default)]
+pub(crate) struct StreamingSha256 {
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+impl StreamingSha256 {
+    /// Returns a sink to stream an archive through, starting the checksum afresh (so
+    /// that a retried download isn't checksummed together with the failed one).
+    pub(crate) fn sink(&self) -> Box<Write + Send> {
+        *self.hasher.lock().unwrap() = Sha256::default();
+        Box::new(self.clone())
+    }
+
+    /// Returns the hex-encoded checksum of the bytes written so far.
+    pub(crate) fn hex(&self) -> String {
+        hex(&self.hasher.lock().unwrap().clone().result())
+    }
+}
+
+impl Write for StreamingSha256 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.lock().unwrap().input(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Computes the hex-encoded SHA-256 checksum of the contents of a file.
+pub(crate) fn sha256_file(path: &Path) -> Fallible<String> {
+    sha256(File::open(path).unknown()?)
+}
+
+/// The hash algorithms of subresource integrity strings that can be verified, from
+/// weakest to strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Algorithm {
+    Sha1,
+    Sha512,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Algorithm> {
+        match name {
+            "sha1" => Some(Algorithm::Sha1),
+            "sha512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+/// A subresource integrity (SRI) digest, like `sha512-<base64 digest>`, in the format
+/// the npm registry publishes for package tarballs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Integrity {
+    pub(crate) algorithm: Algorithm,
+    digest: String,
+}
+
+impl Integrity {
+    /// Parses an integrity string, which may list several digests separated by
+    /// whitespace, each optionally followed by `?` and options. The strongest digest
+    /// with a supported algorithm is kept; digests of other algorithms are ignored.
+    pub(crate) fn parse(src: &str) -> Option<Integrity> {
+        src.split_whitespace()
+            .filter_map(|entry| {
+                let entry = entry.split('?').next().unwrap_or(entry);
+                let dash = entry.find('-')?;
+                let algorithm = Algorithm::parse(&entry[..dash])?;
+                let digest = &entry[dash + 1..];
+                if digest.is_empty() {
+                    return None;
+                }
+                Some(Integrity {
+                    algorithm,
+                    digest: digest.to_string(),
+                })
+            })
+            .max_by_key(|integrity| integrity.algorithm)
+    }
+
+    /// The integrity of a hex-encoded SHA-1 checksum, like the `shasum` older
+    /// packages publish instead of an integrity string.
+    pub(crate) fn from_sha1_hex(shasum: &str) -> Option<Integrity> {
+        if shasum.len() != 40 {
+            return None;
+        }
+        let bytes = (0..shasum.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(shasum.get(index..index + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Integrity {
+            algorithm: Algorithm::Sha1,
+            digest: base64(&bytes),
+        })
+    }
+
+    /// Computes the integrity of the contents of a reader with the specified algorithm.
+    pub(crate) fn of<R: Read>(algorithm: Algorithm, reader: R) -> Fallible<Integrity> {
+        let bytes = match algorithm {
+            Algorithm::Sha1 => digest::<Sha1, R>(reader)?,
+            Algorithm::Sha512 => digest::<Sha512, R>(reader)?,
+        };
+        Ok(Integrity {
+            algorithm,
+            digest: base64(&bytes),
+        })
+    }
+
+    /// Computes the integrity of the contents of a file with the specified algorithm.
+    pub(crate) fn of_file(algorithm: Algorithm, path: &Path) -> Fallible<Integrity> {
+        Integrity::of(algorithm, File::open(path).unknown()?)
+    }
+}
+
+impl fmt::Display for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.algorithm.name(), self.digest)
+    }
+}
+
+/// Encodes bytes in standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &'static [u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = chunk.get(1).cloned().unwrap_or(0) as usize;
+        let b2 = chunk.get(2).cloned().unwrap_or(0) as usize;
+        encoded.push(ALPHABET[b0 >> 2] as char);
+        encoded.push(ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+        encoded.push(if chunk.len() > 1 {
+            ALPHABET[((b1 & 0x0f) << 2) | (b2 >> 6)] as char
+        } else {
+            '='
+        });
+        encoded.push(if chunk.len() > 2 {
+            ALPHABET[b2 & 0x3f] as char
+        } else {
+            '='
+        });
+    }
+    encoded
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{base64, find_checksum, sha256, split_url, Algorithm, Integrity, Shasums,
+                StreamingSha256};
+    use config::Config;
+    use reqwest::Url;
+    use std::fs::{remove_file, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_split_url() {
+        assert_eq!(
+            split_url("https://nodejs.org/dist/v8.9.4/node-v8.9.4-linux-x64.tar.gz"),
+            ("https://nodejs.org/dist/v8.9.4", "node-v8.9.4-linux-x64.tar.gz")
+        );
+    }
+
+    #[test]
+    fn test_find_checksum() {
+        let shasums = "\
+            0a1b2c  node-v8.9.4-darwin-x64.tar.gz\n\
+            3D4E5F  node-v8.9.4-linux-x64.tar.gz\n\
+            6a7b8c  node-v8.9.4-linux-x64.tar.xz\n";
+
+        assert_eq!(
+            find_checksum(shasums, "node-v8.9.4-linux-x64.tar.gz"),
+            Some("3d4e5f".to_string())
+        );
+        assert_eq!(find_checksum(shasums, "node-v8.9.4-win-x64.zip"), None);
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(&b"hello"[..]).unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_streaming_sha256() {
+        let streaming = StreamingSha256::default();
+        streaming.sink().write_all(b"discarded").unwrap();
+
+        let mut sink = streaming.sink();
+        sink.write_all(b"hel").unwrap();
+        sink.write_all(b"lo").unwrap();
+        assert_eq!(streaming.hex(), sha256(&b"hello"[..]).unwrap());
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_sha512_integrity() {
+        assert_eq!(
+            Integrity::of(Algorithm::Sha512, &b"hello"[..])
+                .unwrap()
+                .to_string(),
+            "sha512-m3HSJL1i83hdltRq0+o9czGb+8KJDKra4t/3JRlnPKcjI8PZm6XBHXx6zG4UuMXaDEZjR1wuXDre9G9zvN7AQw=="
+        );
+    }
+
+    #[test]
+    fn test_parse_integrity() {
+        let integrity = Integrity::parse("sha1-AAAA sha384-CCCC sha512-BBBB?foo").unwrap();
+        assert_eq!(integrity.algorithm, Algorithm::Sha512);
+        assert_eq!(integrity.to_string(), "sha512-BBBB");
+
+        let integrity = Integrity::parse("md5-DDDD sha1-AAAA").unwrap();
+        assert_eq!(integrity.to_string(), "sha1-AAAA");
+
+        assert_eq!(Integrity::parse("sha256-EEEE"), None);
+        assert_eq!(Integrity::parse("sha512-"), None);
+        assert_eq!(Integrity::parse(""), None);
+    }
+
+    #[test]
+    fn test_integrity_of() {
+        let hello = &b"hello"[..];
+        let sha1 = Integrity::of(Algorithm::Sha1, hello).unwrap();
+        assert_eq!(sha1.to_string(), "sha1-qvTGHdzF6KLavt4PO0gs2a6pQ00=");
+        assert_eq!(
+            Integrity::from_sha1_hex("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"),
+            Some(sha1)
+        );
+        assert_eq!(Integrity::from_sha1_hex("0123456789abcdef"), None);
+    }
+
+    #[test]
+    fn test_shasums_fetched_once() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let shasums_file = dir.path().join("SHASUMS256.txt");
+        let src = "ABC123  node-v10.8.0-linux-x64.tar.xz\ndef456  node-v10.8.0-linux-x64.tar.gz\n";
+        File::create(&shasums_file)
+            .and_then(|mut file| file.write_all(src.as_bytes()))
+            .unwrap();
+        let url = |file: &str| Url::from_file_path(dir.path().join(file)).unwrap().to_string();
+        let config: Config = "".parse().expect("Could not parse config");
+
+        let shasums = Shasums::default();
+        assert_eq!(
+            shasums
+                .sha256(&url("node-v10.8.0-linux-x64.tar.xz"), false, &config)
+                .unwrap(),
+            "abc123"
+        );
+
+        // the file is only read the first time, so later archives of the release are
+        // looked up without it
+        remove_file(&shasums_file).unwrap();
+        assert_eq!(
+            shasums
+                .sha256(&url("node-v10.8.0-linux-x64.tar.gz"), false, &config)
+                .unwrap(),
+            "def456"
+        );
+        assert!(shasums
+            .sha256(&url("node-v10.8.0-linux-x64.zip"), false, &config)
+            .is_err());
+    }
+}
@@ -1,8 +1,11 @@
 //! Provides error types for the installer tools.
 
+use std::io;
+
 use notion_fail::{ExitCode, NotionFail};
 
 use failure;
+use reqwest;
 
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "Failed to download version {}\n{}", version, error)]
@@ -20,3 +23,187 @@ impl DownloadError {
         }
     }
 }
+
+/// Thrown when the published checksums for a distribution could not be downloaded.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not fetch checksums from {}\n{}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct ChecksumFetchError {
+    pub(crate) url: String,
+    pub(crate) error: String,
+}
+
+impl ChecksumFetchError {
+    pub(crate) fn for_url(url: String) -> impl FnOnce(&reqwest::Error) -> ChecksumFetchError {
+        move |error| ChecksumFetchError {
+            url: url,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Thrown when the published checksums do not include an entry for a distribution archive.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No checksum for {} found in {}", file, url)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct ChecksumNotFoundError {
+    pub(crate) file: String,
+    pub(crate) url: String,
+}
+
+/// Thrown when a downloaded archive does not match its published checksum.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "Checksum verification failed for {}\nexpected: {}\n  actual: {}",
+    file, expected, actual
+)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct ChecksumMismatchError {
+    pub(crate) file: String,
+    pub(crate) expected: String,
+    pub(crate) actual: String,
+}
+
+/// Thrown when signature verification is enabled but the keyring is missing.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "Signature verification is enabled but no keyring was found at {}\nImport the Node release team's keys into that keyring to continue.",
+    path
+)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct MissingKeyringError {
+    pub(crate) path: String,
+}
+
+/// Thrown when signature verification is enabled but gpg could not be run.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not run gpg to verify signatures: {}", error)]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct GpgUnavailableError {
+    pub(crate) error: String,
+}
+
+impl GpgUnavailableError {
+    pub(crate) fn from_io_error(error: &io::Error) -> Self {
+        GpgUnavailableError {
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Thrown when the published checksums do not match their GPG signature.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid signature for {}\n{}", url, details)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct SignatureVerificationError {
+    pub(crate) url: String,
+    pub(crate) details: String,
+}
+
+/// Thrown when no version of a package on the npm registry matches the requested versions.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No version of package '{}' found matching {}", name, matching)]
+#[notion_fail(code = "NoVersionMatch")]
+pub(crate) struct PackageVersionNotFoundError {
+    pub(crate) name: String,
+    pub(crate) matching: String,
+}
+
+/// Thrown when the npm registry has no metadata for a package.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Package '{}' not found in the npm registry", name)]
+#[notion_fail(code = "NoVersionMatch")]
+pub(crate) struct PackageNotFoundError {
+    pub(crate) name: String,
+}
+
+/// Thrown when a registry refuses to serve a package tarball.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not download {} ({})", url, status)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct TarballFetchError {
+    pub(crate) url: String,
+    pub(crate) status: String,
+}
+
+/// Thrown when a download ended before all of the archive the server announced had
+/// arrived. The partial archive is removed from the cache, so trying again downloads
+/// it afresh.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "The download of {} ended after {} of its {} bytes\nThe partial archive has been removed from the cache; please try again.",
+    file, actual, expected
+)]
+#[notion_fail(code = "NetworkError", error_code = "DOWNLOAD_INCOMPLETE")]
+pub(crate) struct IncompleteDownloadError {
+    pub(crate) file: String,
+    pub(crate) expected: u64,
+    pub(crate) actual: u64,
+}
+
+/// Thrown when an unpacked distribution lacks a file every distribution of the tool
+/// has, like the Node executable. The archive is removed from the cache, so trying
+/// again downloads it afresh.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "The archive {} does not contain {}\nThe archive has been removed from the cache; please try again.",
+    file, missing
+)]
+#[notion_fail(code = "NetworkError", error_code = "DOWNLOAD_INCOMPLETE")]
+pub(crate) struct IncompleteArchiveError {
+    pub(crate) file: String,
+    pub(crate) missing: String,
+}
+
+/// Thrown when a freshly unpacked Node doesn't run, or reports a different version
+/// than it should. The archive is removed from the cache, so trying again downloads
+/// it afresh.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "Node v{} does not work: {}\nIts archive has been removed from the cache; please try again.",
+    version, problem
+)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct BrokenNodeError {
+    pub(crate) version: String,
+    pub(crate) problem: String,
+}
+
+/// Thrown when a distribution archive could not be unpacked. The archive is removed
+/// from the cache, since it is most likely incomplete or corrupt.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "Could not unpack {}\n{}\nThe archive has been removed from the cache; please try again.",
+    file, error
+)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct UnpackError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+/// Thrown when a step of building Node from source fails, with the last lines of its
+/// error output.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not build Node v{} from source: {} failed\n{}", version, step, output)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct SourceBuildError {
+    pub(crate) version: String,
+    pub(crate) step: String,
+    pub(crate) output: String,
+}
+
+/// Thrown when asked to build Node from source on Windows, whose build uses its own
+/// toolchain rather than `configure` and `make`.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "building Node from source is not yet supported on Windows")]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct SourceBuildUnsupportedError;
+
+/// Thrown when the path to a local Node archive has no file name, e.g. `..`.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not a Node archive file", path)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NotAnArchiveError {
+    pub(crate) path: String,
+}
@@ -1,13 +1,37 @@
 //! Provides types for fetching tool distributions into the Notion catalog.
 
-mod error;
+pub(crate) mod checksum;
+pub(crate) mod error;
+mod signature;
+pub(crate) mod stamp;
 pub mod node;
+pub mod npm;
+pub mod package;
+pub mod pnpm;
+pub(crate) mod source;
 pub mod yarn;
 
 use catalog::Collection;
-use notion_fail::Fallible;
+use config::{Config, LifecycleConfig};
+use distro::checksum::StreamingSha256;
+use distro::error::{ChecksumMismatchError, IncompleteDownloadError, UnpackError};
+use event;
+use failure;
+use fs::rename;
+use http;
+use indicatif::ProgressBar;
+use lifecycle::{self, Action};
+use log;
+use node_archive::{Archive, TruncatedError};
+use notion_fail::{Fallible, ResultExt};
+use path;
+use profile::{self, Phase};
 use semver::Version;
-use std::fs::File;
+use style::{self, ProgressLog};
+use std::fs::{remove_file, File};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tempfile::{tempdir_in, TempDir};
 
 /// The result of a requested installation.
 pub enum Fetched {
@@ -33,12 +57,48 @@ impl Fetched {
     }
 }
 
-pub trait Distro: Sized {
+/// Builds the URL to download an archive from out of a `url-template` setting, e.g.
+/// `https://mirror.corp/node/{version}/{filename}`, by filling in its `{os}`, `{arch}`,
+/// `{version}`, `{filename}`, and `{ext}` placeholders.
+pub(crate) fn template_url(template: &str, version: &Version, filename: &str, ext: &str) -> String {
+    template_url_for_arch(template, version, filename, ext, &path::node_arch())
+}
+
+/// Builds the URL to download an archive built for the specified architecture from out
+/// of a `url-template` setting.
+pub(crate) fn template_url_for_arch(
+    template: &str,
+    version: &Version,
+    filename: &str,
+    ext: &str,
+    arch: &str,
+) -> String {
+    template
+        .replace("{os}", path::OS)
+        .replace("{arch}", arch)
+        .replace("{version}", &version.to_string())
+        .replace("{filename}", filename)
+        .replace("{ext}", ext)
+}
+
+/// A provisioned distribution of a tool. Distributions are `Send`, so that several of them
+/// can be fetched at once from separate threads.
+pub trait Distro: Sized + Send {
     /// Provision a distribution from the public distributor (e.g. `https://nodejs.org`).
-    fn public(version: Version) -> Fallible<Self>;
+    fn public(version: Version, config: &Config) -> Fallible<Self>;
 
     /// Provision a distribution from a remote distributor.
-    fn remote(version: Version, url: &str) -> Fallible<Self>;
+    fn remote(version: Version, url: &str, config: &Config) -> Fallible<Self>;
+
+    /// Provision a distribution from a remote distributor, verifying the archive against
+    /// the specified SHA-256 checksum (e.g. one produced by a resolve plugin) rather
+    /// than any the distributor publishes.
+    fn remote_with_checksum(
+        version: Version,
+        url: &str,
+        checksum: &str,
+        config: &Config,
+    ) -> Fallible<Self>;
 
     /// Provision a distribution from the filesystem.
     fn cached(version: Version, file: File) -> Fallible<Self>;
@@ -46,7 +106,358 @@ pub trait Distro: Sized {
     /// Produces a reference to this distro's Tool version.
     fn version(&self) -> &Version;
 
-    /// Fetches this version of the Tool. (It is left to the responsibility of the `Collection`
-    /// to update its state after fetching succeeds.)
-    fn fetch(self, catalog: &Collection<Self>) -> Fallible<Fetched>;
+    /// Produces a progress bar for fetching this distribution, sized to its archive.
+    fn progress_bar(&self) -> ProgressBar;
+
+    /// Fetches this version of the Tool, reporting progress to the specified bar, which
+    /// is finished once the fetch succeeds. (It is left to the responsibility of the
+    /// `Collection` to update its state after fetching succeeds.)
+    fn fetch_with_progress(self, catalog: &Collection<Self>, bar: ProgressBar) -> Fallible<Fetched>;
+
+    /// Fetches this version of the specified tool like `fetch_with_progress`, between the
+    /// `pre-fetch` and `post-fetch` lifecycle hooks, which only run if the version isn't
+    /// fetched already.
+    fn fetch_with_hooks(
+        self,
+        tool: &str,
+        catalog: &Collection<Self>,
+        bar: ProgressBar,
+        hooks: Option<&LifecycleConfig>,
+    ) -> Fallible<Fetched> {
+        if catalog.contains(self.version()) {
+            return Ok(Fetched::Already(self.version().clone()));
+        }
+        let version = self.version().to_string();
+        let started = Instant::now();
+        let fetched = lifecycle::around(hooks, Action::Fetch, tool, &version, || {
+            self.fetch_with_progress(catalog, bar)
+        })?;
+        if let Fetched::Now(_) = fetched {
+            event::record_fetched(tool, &version, started.elapsed());
+        }
+        Ok(fetched)
+    }
+
+    /// Fetches this version of the specified tool. (It is left to the responsibility of the
+    /// `Collection` to update its state after fetching succeeds.)
+    fn fetch(self, tool: &str, catalog: &Collection<Self>, config: &Config) -> Fallible<Fetched> {
+        if catalog.contains(self.version()) {
+            return Ok(Fetched::Already(self.version().clone()));
+        }
+        let bar = self.progress_bar();
+        self.fetch_with_hooks(tool, catalog, bar, config.lifecycle.as_ref())
+    }
+}
+
+/// Checks whether a cached archive matches the specified SHA-256 checksum. A stamp
+/// recording that the archive was verified against the same checksum saves hashing
+/// it again.
+fn cache_matches(cache_file: &Path, expected_checksum: &str) -> bool {
+    if let Some(valid) = stamp::check(cache_file, Some(expected_checksum)) {
+        log::debug(|| format!("cached archive {} was already verified", cache_file.display()));
+        return valid;
+    }
+    if cache_file.is_file() {
+        if let Ok(actual_checksum) = checksum::sha256_file(cache_file) {
+            if actual_checksum == expected_checksum {
+                log::debug(|| {
+                    format!("cached archive {} matches its checksum", cache_file.display())
+                });
+                let _ = stamp::write(cache_file, Some(expected_checksum));
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// A download that can only be verified once its archive has been unpacked.
+pub(crate) enum PendingChecksum {
+    /// An archive downloaded into the cache file, with the SHA-256 checksum the file
+    /// is expected to have.
+    Cached(PathBuf, String),
+    /// An archive streamed from the URL without being cached, with its checksum as
+    /// computed on the fly and the one it is expected to have, if any.
+    Streamed(String, StreamingSha256, Option<String>),
+    /// An archive downloaded into the cache file in chunks, which was verified to have
+    /// the specified checksum as soon as every chunk arrived.
+    Verified(PathBuf, String),
+}
+
+/// Starts downloading an archive from a remote distributor. The archive is unpacked as
+/// it downloads, and written into the cache file at the same time, unless downloads
+/// are configured to stream (see `Config::stream_downloads`), in which case it is only
+/// checksummed. Zip files and `file://` URLs are always cached. Where the `http.chunks`
+/// setting splits downloads into chunks, the archive is instead downloaded in full
+/// before it is unpacked, and verified right away.
+pub(crate) fn download(
+    url: &str,
+    cache_file: &Path,
+    expected_checksum: Option<String>,
+    config: &Config,
+) -> Result<(Box<Archive>, Option<PendingChecksum>), failure::Error> {
+    let zip = cache_file.extension().map_or(false, |extension| extension == "zip");
+    if !config.stream_downloads() || zip || http::file_url_path(url).is_some() {
+        if let Some(archive) = http::fetch_archive_chunked(url, cache_file, config)? {
+            let pending = match expected_checksum {
+                Some(expected) => {
+                    let actual = checksum::sha256_file(cache_file)?;
+                    if actual != expected {
+                        let _ = remove_file(cache_file);
+                        Err(ChecksumMismatchError {
+                            file: cache_file.to_string_lossy().to_string(),
+                            expected,
+                            actual,
+                        })?;
+                    }
+                    Some(PendingChecksum::Verified(cache_file.to_path_buf(), actual))
+                }
+                None => None,
+            };
+            return Ok((archive, pending));
+        }
+        let archive = http::fetch_archive(url, cache_file, config)?;
+        let pending = expected_checksum
+            .map(|expected| PendingChecksum::Cached(cache_file.to_path_buf(), expected));
+        return Ok((archive, pending));
+    }
+
+    let streaming = StreamingSha256::default();
+    let archive = http::stream_archive(url, cache_file, config, || streaming.sink())?;
+    Ok((
+        archive,
+        Some(PendingChecksum::Streamed(url.to_string(), streaming, expected_checksum)),
+    ))
+}
+
+/// Verifies a downloaded archive against the checksum it is expected to have, once it
+/// has been unpacked (downloads are streamed into the cache while unpacking, so the
+/// checksum can't be verified any sooner, unless they were downloaded in chunks). An
+/// archive that doesn't match is removed from the cache, and one that does is stamped.
+/// Returns whether there was a download to verify, which is always the case for a
+/// streamed archive, since there is no cache file to stamp.
+fn verify_download(pending_checksum: Option<PendingChecksum>, bar: &ProgressBar) -> Fallible<bool> {
+    let (cache_file, expected) = match pending_checksum {
+        Some(PendingChecksum::Cached(cache_file, expected)) => (cache_file, expected),
+        Some(PendingChecksum::Streamed(url, streaming, Some(expected))) => {
+            let actual = streaming.hex();
+            if actual != expected {
+                bar.finish_and_clear();
+                throw!(ChecksumMismatchError {
+                    file: url,
+                    expected,
+                    actual,
+                });
+            }
+            return Ok(true);
+        }
+        Some(PendingChecksum::Streamed(_, _, None)) => return Ok(true),
+        Some(PendingChecksum::Verified(cache_file, checksum)) => {
+            let _ = stamp::write(&cache_file, Some(&checksum));
+            return Ok(true);
+        }
+        None => return Ok(false),
+    };
+    let actual = checksum::sha256_file(&cache_file)?;
+    if actual != expected {
+        bar.finish_and_clear();
+        let _ = remove_file(&cache_file);
+        throw!(ChecksumMismatchError {
+            file: cache_file.to_string_lossy().to_string(),
+            expected,
+            actual,
+        });
+    }
+    let _ = stamp::write(&cache_file, Some(&expected));
+    Ok(true)
+}
+
+/// Unpacks an archive into a new staging directory in `dest`, reporting progress to
+/// the specified bar, and checks that it produced the expected root directory.
+///
+/// If either step fails, the cached archive is most likely incomplete or corrupt, so
+/// it is removed and the next attempt downloads it afresh. The staging directory, and
+/// whatever was unpacked into it, is removed when it is dropped, so a failed fetch
+/// leaves nothing behind for the user to clean up.
+fn unpack_staged(
+    archive: Box<Archive>,
+    dest: &Path,
+    root_dir: &str,
+    cache_file: &Path,
+    bar: &ProgressBar,
+) -> Fallible<TempDir> {
+    let staging = tempdir_in(dest).unknown()?;
+    log::debug(|| format!("unpacking {} into {}", cache_file.display(), staging.path().display()));
+
+    // Without a terminal to draw the bar on, report the progress in lines of status
+    // instead, and snapshot it in the events file at every tenth of the way.
+    let file_name = cache_file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let size = archive.uncompressed_size().unwrap_or(archive.compressed_size());
+    let mut progress_log = ProgressLog::terse(style::Action::Fetching, &file_name, size);
+    let total = if event::recording() { size } else { 0 };
+    let mut unpacked = 0;
+    let result = profile::time(Phase::Unpack, || {
+        archive.unpack(staging.path(), &mut |_, read| {
+            bar.inc(read as u64);
+            if let Some(ref mut progress_log) = progress_log {
+                progress_log.inc(read as u64);
+            }
+            if total > 0 {
+                let before = unpacked * 10 / total;
+                unpacked += read as u64;
+                if unpacked * 10 / total > before {
+                    event::record_progress(&file_name, unpacked, total);
+                }
+            }
+        })
+    });
+    let error = match result {
+        Ok(()) if staging.path().join(root_dir).is_dir() => return Ok(staging),
+        Ok(()) => format!("The archive does not contain {}", root_dir),
+        Err(error) => match error.downcast::<TruncatedError>() {
+            Ok(truncated) => {
+                bar.finish_and_clear();
+                discard_cached(cache_file);
+                throw!(IncompleteDownloadError {
+                    file: cache_file.to_string_lossy().to_string(),
+                    expected: truncated.expected,
+                    actual: truncated.actual,
+                });
+            }
+            Err(error) => error.to_string(),
+        },
+    };
+
+    bar.finish_and_clear();
+    discard_cached(cache_file);
+    throw!(UnpackError {
+        file: cache_file.to_string_lossy().to_string(),
+        error,
+    });
+}
+
+/// Removes an archive that turned out to be incomplete or corrupt from the cache, so
+/// that the next attempt downloads it afresh.
+fn discard_cached(cache_file: &Path) {
+    let _ = remove_file(cache_file);
+    stamp::remove(cache_file);
+}
+
+/// Moves an unpacked distribution into its version directory. Distributions are
+/// unpacked into a staging directory next to the version directory, so the rename is
+/// atomic: the version directory only ever appears complete. Shims fetch a missing
+/// version the first time it runs, so several processes (such as the scripts an
+/// `npm install` runs) may fetch the same version at once; whichever finishes first
+/// wins, and the others find its version directory in place and leave it as it is.
+pub(crate) fn move_into_place(unpacked: &Path, version_dir: &Path) -> Fallible<()> {
+    if version_dir.is_dir() {
+        log::debug(|| format!("{} is already in place", version_dir.display()));
+        return Ok(());
+    }
+    log::debug(|| format!("moving {} to {}", unpacked.display(), version_dir.display()));
+    if let Err(error) = rename(unpacked, version_dir) {
+        if !version_dir.is_dir() {
+            return Err(error).unknown();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{move_into_place, template_url, unpack_staged};
+    use failure;
+    use indicatif::ProgressBar;
+    use node_archive::Archive;
+    use path;
+    use semver::Version;
+    use std::fs::{create_dir_all, read_dir, File};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    /// An archive that unpacks one file and then fails, like a truncated download.
+    struct TruncatedArchive;
+
+    impl Archive for TruncatedArchive {
+        fn compressed_size(&self) -> u64 {
+            0
+        }
+
+        fn uncompressed_size(&self) -> Option<u64> {
+            None
+        }
+
+        fn unpack(
+            self: Box<Self>,
+            dest: &Path,
+            _: &mut FnMut(&(), usize),
+        ) -> Result<(), failure::Error> {
+            create_dir_all(dest.join("package"))?;
+            File::create(dest.join("package").join("index.js"))?;
+            Err(failure::err_msg("unexpected end of file"))
+        }
+    }
+
+    #[test]
+    fn test_unpack_staged_cleans_up() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let dest = dir.path().join("versions");
+        create_dir_all(&dest).unwrap();
+        let cache_file = dir.path().join("package-1.0.0.tgz");
+        File::create(&cache_file).unwrap();
+
+        let result = unpack_staged(
+            Box::new(TruncatedArchive),
+            &dest,
+            "package",
+            &cache_file,
+            &ProgressBar::hidden(),
+        );
+        assert!(result.is_err());
+        // neither the partly unpacked files nor the corrupt archive are left behind
+        assert_eq!(read_dir(&dest).unwrap().count(), 0);
+        assert!(!cache_file.exists());
+    }
+
+    #[test]
+    fn test_move_into_place() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let version_dir = dir.path().join("14.0.0");
+
+        let unpacked = dir.path().join("staging-1");
+        create_dir_all(&unpacked).unwrap();
+        File::create(unpacked.join("first")).unwrap();
+        move_into_place(&unpacked, &version_dir).unwrap();
+        assert!(version_dir.join("first").is_file());
+
+        // a later fetch of the same version leaves the first one in place
+        let unpacked = dir.path().join("staging-2");
+        create_dir_all(&unpacked).unwrap();
+        File::create(unpacked.join("second")).unwrap();
+        move_into_place(&unpacked, &version_dir).unwrap();
+        assert!(version_dir.join("first").is_file());
+        assert!(!version_dir.join("second").exists());
+    }
+
+    #[test]
+    fn test_template_url() {
+        let version = Version::parse("10.8.0").unwrap();
+        assert_eq!(
+            template_url(
+                "https://mirror.corp/node/{version}/{filename}",
+                &version,
+                "node-v10.8.0-linux-x64.tar.gz",
+                "tar.gz"
+            ),
+            "https://mirror.corp/node/10.8.0/node-v10.8.0-linux-x64.tar.gz"
+        );
+        assert_eq!(
+            template_url("file:///mnt/tools/{os}-{arch}/{version}.{ext}", &version, "", "zip"),
+            format!("file:///mnt/tools/{}-{}/10.8.0.zip", path::OS, path::node_arch())
+        );
+    }
 }
@@ -1,73 +1,421 @@
 //! Provides the `Installer` type, which represents a provisioned Node installer.
 
-use std::fs::{rename, File};
-use std::path::PathBuf;
+use std::fs::{copy, create_dir_all, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::string::ToString;
 
-use super::{Distro, Fetched};
+use super::{cache_matches, discard_cached, download, move_into_place, template_url_for_arch,
+            unpack_staged, verify_download, Distro, Fetched, PendingChecksum};
 use catalog::NodeCollection;
-use distro::error::DownloadError;
-use fs::ensure_containing_dir_exists;
+use config::Config;
+use distro::checksum::Shasums;
+use distro::stamp;
+use distro::error::{BrokenNodeError, ChecksumNotFoundError, DownloadError, IncompleteArchiveError,
+                    NotAnArchiveError};
+use env;
+use fs::{ensure_containing_dir_exists, lock, FileLock};
+use http;
 use node_archive::{self, Archive};
 use path;
-use style::{progress_bar, Action};
+use indicatif::ProgressBar;
+use style::{display_warning, progress_bar, Action};
 
 use notion_fail::{Fallible, ResultExt};
 use semver::Version;
+use version::Channel;
 
 const PUBLIC_NODE_SERVER_ROOT: &'static str = "https://nodejs.org/dist/";
+const PUBLIC_NODE_MUSL_SERVER_ROOT: &'static str =
+    "https://unofficial-builds.nodejs.org/download/release/";
+/// Root of the public servers of Node's prerelease channels, which are named after the
+/// channel (e.g. `https://nodejs.org/download/nightly/`).
+const PUBLIC_NODE_CHANNEL_SERVER_ROOT: &'static str = "https://nodejs.org/download/";
+
+/// Returns the root URL of the Node server to download from: the `NOTION_NODE_MIRROR`
+/// environment variable if set, otherwise the `node.mirror` setting if configured,
+/// otherwise the public Node server. The result always ends with a `/`.
+///
+/// The public Node server doesn't build for musl-based systems like Alpine Linux, so
+/// on those the `node.musl-mirror` setting if configured, or otherwise the public
+/// server of unofficial builds, is used instead.
+pub(crate) fn server_root(config: &Config) -> String {
+    if !path::is_musl() {
+        return release_server_root(config);
+    }
+    let mirror = config.node.as_ref().and_then(|node| node.musl_mirror.clone());
+    with_trailing_slash(mirror, PUBLIC_NODE_MUSL_SERVER_ROOT)
+}
+
+/// Returns the root URL of the server of Node's official releases, as `server_root`
+/// does on any system but a musl-based one. The result always ends with a `/`.
+pub(crate) fn release_server_root(config: &Config) -> String {
+    let node = config.node.as_ref();
+    let mirror = env::node_mirror().or_else(|| node.and_then(|node| node.mirror.clone()));
+    with_trailing_slash(mirror, PUBLIC_NODE_SERVER_ROOT)
+}
+
+fn with_trailing_slash(mirror: Option<String>, public: &str) -> String {
+    match mirror {
+        Some(ref mirror) if mirror.ends_with('/') => mirror.clone(),
+        Some(mirror) => format!("{}/", mirror),
+        None => public.to_string(),
+    }
+}
+
+/// Returns the root URL of the public server of the specified prerelease channel. The
+/// result always ends with a `/`.
+pub(crate) fn channel_server_root(channel: Channel) -> String {
+    format!("{}{}/", PUBLIC_NODE_CHANNEL_SERVER_ROOT, channel)
+}
+
+/// Checks that a Node executable runs and reports the expected version, describing the
+/// problem if not.
+pub(crate) fn smoke_test(executable: &Path, version: &Version) -> Result<(), String> {
+    let output = Command::new(executable)
+        .arg("--version")
+        .output()
+        .map_err(|error| format!("it could not be run ({})", error))?;
+    if !output.status.success() {
+        return Err(format!("`node --version` failed ({})", output.status));
+    }
+    let reported = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let expected = format!("v{}", version);
+    if reported != expected {
+        return Err(format!(
+            "`node --version` reported '{}' instead of {}",
+            reported, expected
+        ));
+    }
+    Ok(())
+}
 
 /// A provisioned Node distribution.
 pub struct NodeDistro {
     archive: Box<Archive>,
     version: Version,
+
+    /// The architecture the archive is built for, which is the host's unless the
+    /// version predates builds for it (see `path::node_emulated_arch`).
+    arch: String,
+
+    /// The cache file the archive is read from (or downloaded into).
+    cache_file: PathBuf,
+
+    /// For an archive that is still being downloaded (or streamed without being
+    /// cached), how to verify the download once it has been unpacked.
+    pending_checksum: Option<PendingChecksum>,
+
+    /// The lock on the cache file, held while the archive is downloaded into it.
+    download_lock: Option<FileLock>,
 }
 
-/// Check if the cached file is valid. It may have been corrupted or interrupted in the middle of
-/// downloading.
-// ISSUE(#134) - verify checksum
-fn cache_is_valid(cache_file: &PathBuf) -> bool {
-    if cache_file.is_file() {
-        if let Ok(file) = File::open(cache_file) {
-            match node_archive::load(file) {
-                Ok(_) => return true,
-                Err(_) => return false,
+impl NodeDistro {
+    /// Provision a prerelease Node distribution from the public server of the specified
+    /// channel (e.g. `https://nodejs.org/download/rc/`).
+    pub(crate) fn channel(version: Version, channel: Channel, config: &Config) -> Fallible<Self> {
+        let server_root = channel_server_root(channel);
+        let version_string = version.to_string();
+        NodeDistro::from_server(
+            version,
+            &|archive_file, _, _| format!("{}v{}/{}", server_root, version_string, archive_file),
+            config,
+        )
+    }
+
+    /// Provision a Node distribution from an archive provided locally, which is named as
+    /// Node publishes it. The archive is copied into the cache, to be used like any
+    /// other cached archive from then on.
+    pub(crate) fn local(version: Version, archive: &Path) -> Fallible<Self> {
+        let archive_file = match archive.file_name() {
+            Some(file_name) => file_name.to_string_lossy().to_string(),
+            None => {
+                throw!(NotAnArchiveError {
+                    path: archive.display().to_string(),
+                });
             }
+        };
+        let cache_file = path::node_cache_dir()?.join(&archive_file);
+        {
+            let _lock = lock(&cache_file, &format!("copying {}", archive_file))?;
+            ensure_containing_dir_exists(&cache_file)?;
+            stamp::remove(&cache_file);
+            copy(archive, &cache_file).unknown()?;
         }
+
+        let distro = NodeDistro::cached(version, File::open(&cache_file).unknown()?)?;
+        Ok(NodeDistro {
+            cache_file,
+            ..distro
+        })
     }
-    false
-}
 
-impl Distro for NodeDistro {
-    /// Provision a Node distribution from the public Node distributor (`https://nodejs.org`).
-    fn public(version: Version) -> Fallible<Self> {
-        let archive_file = path::node_archive_file(&version.to_string());
-        let url = format!("{}v{}/{}", PUBLIC_NODE_SERVER_ROOT, version, &archive_file);
-        NodeDistro::remote(version, &url)
+    /// Provision a custom build of Node (e.g. one with patches under test) from the
+    /// archive at a URL, which is verified against the expected checksum if one is given.
+    /// There is no published checksum to fall back to. The archive is always cached,
+    /// under the directory of custom builds, so that its checksum can be recorded.
+    pub(crate) fn custom(
+        version: Version,
+        url: &str,
+        expected_checksum: Option<String>,
+        config: &Config,
+    ) -> Fallible<Self> {
+        let archive_file = url.rsplit('/').next().unwrap_or(url).to_string();
+        let cache_file = path::node_custom_cache_dir()?.join(&archive_file);
+        let download_lock = lock(&cache_file, &format!("downloading {}", archive_file))?;
+
+        if let Some(ref expected_checksum) = expected_checksum {
+            if cache_matches(&cache_file, expected_checksum) {
+                let distro = NodeDistro::cached(version, File::open(&cache_file).unknown()?)?;
+                return Ok(NodeDistro {
+                    cache_file,
+                    ..distro
+                });
+            }
+        }
+
+        ensure_containing_dir_exists(&cache_file)?;
+        stamp::remove(&cache_file);
+        let archive = http::fetch_archive(url, &cache_file, config)
+            .with_context(DownloadError::for_version(version.to_string()))?;
+        let pending_checksum = expected_checksum
+            .map(|expected| PendingChecksum::Cached(cache_file.clone(), expected));
+        Ok(NodeDistro {
+            archive,
+            version: version,
+            arch: path::node_arch(),
+            cache_file,
+            pending_checksum,
+            download_lock: Some(download_lock),
+        })
+    }
+
+    /// Provision a Node distribution from a server, given the URL of each archive file
+    /// (with its extension and architecture) on it. Where the host's architecture has an
+    /// emulated one (see `path::node_emulated_arch`), a release without a build for the
+    /// host falls back to the emulated build, with a warning.
+    fn from_server(
+        version: Version,
+        url_for: &Fn(&str, &str, &str) -> String,
+        config: &Config,
+    ) -> Fallible<Self> {
+        // Each archive that is tried is looked up in the release's published checksums.
+        let shasums = Shasums::default();
+        let native = path::node_arch();
+        let emulated = match path::node_emulated_arch() {
+            Some(emulated) => emulated,
+            None => {
+                return NodeDistro::from_server_for(version, &native, url_for, &shasums, config)
+            }
+        };
+
+        // Offline, only an archive that is already cached can be used.
+        let version_string = version.to_string();
+        let cached = |arch: &str| -> Fallible<bool> {
+            Ok(path::node_cache_dir()?
+                .join(path::node_archive_file_for(&version_string, arch))
+                .is_file())
+        };
+        let native_unavailable = env::offline() && !cached(&native[..])? && cached(emulated)?;
+        if !native_unavailable {
+            match NodeDistro::from_server_for(version.clone(), &native, url_for, &shasums, config)
+            {
+                Err(ref error) if error.downcast_ref::<ChecksumNotFoundError>().is_some() => {}
+                result => return result,
+            }
+        }
+
+        display_warning(&format!(
+            "Node v{} has no {} build, so its {} build is used, which runs under emulation",
+            version,
+            path::node_platform_for(&native),
+            path::node_platform_for(emulated)
+        ));
+        NodeDistro::from_server_for(version, emulated, url_for, &shasums, config)
     }
 
-    /// Provision a Node distribution from a remote distributor.
-    fn remote(version: Version, url: &str) -> Fallible<Self> {
-        let archive_file = path::node_archive_file(&version.to_string());
+    /// Provision a Node distribution built for the specified architecture from a server.
+    /// The smaller xz-compressed archive is preferred where one is published, falling
+    /// back to the default archive for releases (and servers) without one.
+    fn from_server_for(
+        version: Version,
+        arch: &str,
+        url_for: &Fn(&str, &str, &str) -> String,
+        shasums: &Shasums,
+        config: &Config,
+    ) -> Fallible<Self> {
+        let version_string = version.to_string();
+
+        if let (Some(xz_file), Some(xz_extension)) = (
+            path::node_xz_archive_file_for(&version_string, arch),
+            path::xz_archive_extension(),
+        ) {
+            // Offline, only an archive that is already cached can be used.
+            let xz_cached = path::node_cache_dir()?.join(&xz_file).is_file();
+            if xz_cached || !env::offline() {
+                let url = url_for(&xz_file, &xz_extension, arch);
+                match NodeDistro::remote_checked(version.clone(), arch, &url, None, shasums, config)
+                {
+                    Err(ref error) if error.downcast_ref::<ChecksumNotFoundError>().is_some() => {}
+                    result => return result,
+                }
+            }
+        }
+
+        let archive_file = path::node_archive_file_for(&version_string, arch);
+        let url = url_for(&archive_file, &path::archive_extension(), arch);
+        NodeDistro::remote_checked(version, arch, &url, None, shasums, config)
+    }
+
+    /// Provision a Node distribution built for the specified architecture from a remote
+    /// distributor, verifying the archive against the expected checksum if one is given,
+    /// or else the published one, which is looked up in `shasums`.
+    fn remote_checked(
+        version: Version,
+        arch: &str,
+        url: &str,
+        expected_checksum: Option<String>,
+        shasums: &Shasums,
+        config: &Config,
+    ) -> Fallible<Self> {
+        let version_string = version.to_string();
+        let archive_file = match path::node_xz_archive_file_for(&version_string, arch) {
+            Some(ref xz_file) if url.ends_with(&xz_file[..]) => xz_file.clone(),
+            _ => path::node_archive_file_for(&version_string, arch),
+        };
         let cache_file = path::node_cache_dir()?.join(&archive_file);
+        let download_lock = lock(&cache_file, &format!("downloading {}", archive_file))?;
+
+        // Offline, the published checksum can't be fetched, so fall back to trusting a
+        // complete cached archive (which was verified when it was first downloaded).
+        if env::offline() && cache_file.is_file() {
+            let file = File::open(&cache_file).unknown()?;
+            if let Ok(distro) = NodeDistro::cached(version.clone(), file) {
+                return Ok(NodeDistro {
+                    arch: arch.to_string(),
+                    cache_file,
+                    ..distro
+                });
+            }
+        }
+
+        let expected_checksum = match expected_checksum {
+            Some(expected_checksum) => expected_checksum,
+            None => {
+                let verify_signature = config
+                    .node
+                    .as_ref()
+                    .map(|node| node.verify_signatures)
+                    .unwrap_or(false);
+                shasums.sha256(url, verify_signature, config)?
+            }
+        };
 
-        if cache_is_valid(&cache_file) {
-            return NodeDistro::cached(version, File::open(cache_file).unknown()?);
+        if cache_matches(&cache_file, &expected_checksum) {
+            let distro = NodeDistro::cached(version, File::open(&cache_file).unknown()?)?;
+            return Ok(NodeDistro {
+                arch: arch.to_string(),
+                cache_file,
+                ..distro
+            });
         }
 
         ensure_containing_dir_exists(&cache_file)?;
+        stamp::remove(&cache_file);
+        let (archive, pending_checksum) =
+            download(url, &cache_file, Some(expected_checksum), config)
+                .with_context(DownloadError::for_version(version.to_string()))?;
         Ok(NodeDistro {
-            archive: node_archive::fetch(url, &cache_file)
-                .with_context(DownloadError::for_version(version.to_string()))?,
+            archive,
             version: version,
+            arch: arch.to_string(),
+            cache_file,
+            pending_checksum,
+            download_lock: Some(download_lock),
         })
     }
+}
+
+impl Distro for NodeDistro {
+    /// Provision a Node distribution from the public Node distributor (`https://nodejs.org`),
+    /// or from the configured mirror of it. A `node.url-template` setting takes precedence
+    /// over both.
+    fn public(version: Version, config: &Config) -> Fallible<Self> {
+        let version_string = version.to_string();
+        match config.node.as_ref().and_then(|node| node.url_template.clone()) {
+            Some(template) => {
+                let template_version = version.clone();
+                NodeDistro::from_server(
+                    version,
+                    &|archive_file, extension, arch| {
+                        template_url_for_arch(
+                            &template,
+                            &template_version,
+                            archive_file,
+                            extension,
+                            arch,
+                        )
+                    },
+                    config,
+                )
+            }
+            None => {
+                let server_root = server_root(config);
+                NodeDistro::from_server(
+                    version,
+                    &|archive_file, _, _| {
+                        format!("{}v{}/{}", server_root, version_string, archive_file)
+                    },
+                    config,
+                )
+            }
+        }
+    }
+
+    /// Provision a Node distribution from a remote distributor. The archive is verified
+    /// against the `SHASUMS256.txt` file published in the same directory as the archive,
+    /// and if the `node.verify-signatures` setting is enabled, that file is in turn
+    /// verified against its GPG signature. The URL may be a `file://` URL, for mirrors
+    /// on a network share, in which case the archive is copied into the cache.
+    fn remote(version: Version, url: &str, config: &Config) -> Fallible<Self> {
+        NodeDistro::remote_checked(
+            version,
+            &path::node_arch(),
+            url,
+            None,
+            &Shasums::default(),
+            config,
+        )
+    }
+
+    /// Provision a Node distribution from a remote distributor, verifying the archive
+    /// against the specified checksum instead of the published `SHASUMS256.txt`.
+    fn remote_with_checksum(
+        version: Version,
+        url: &str,
+        checksum: &str,
+        config: &Config,
+    ) -> Fallible<Self> {
+        NodeDistro::remote_checked(
+            version,
+            &path::node_arch(),
+            url,
+            Some(checksum.to_string()),
+            &Shasums::default(),
+            config,
+        )
+    }
 
     /// Provision a Node distribution from the filesystem.
     fn cached(version: Version, file: File) -> Fallible<Self> {
+        let cache_file = path::node_cache_dir()?.join(path::node_archive_file(&version.to_string()));
         Ok(NodeDistro {
             archive: node_archive::load(file).unknown()?,
             version: version,
+            arch: path::node_arch(),
+            cache_file,
+            pending_checksum: None,
+            download_lock: None,
         })
     }
 
@@ -76,33 +424,67 @@ impl Distro for NodeDistro {
         &self.version
     }
 
-    /// Fetches this version of Node. (It is left to the responsibility of the `NodeCollection`
+    /// Produces a progress bar for fetching this distribution.
+    fn progress_bar(&self) -> ProgressBar {
+        progress_bar(
+            Action::Fetching,
+            &format!("node v{}", self.version),
+            self.archive
+                .uncompressed_size()
+                .unwrap_or(self.archive.compressed_size()),
+        )
+    }
+
+    /// Fetches this version of Node, reporting progress to the specified bar. (It is left to the responsibility of the `NodeCollection`
     /// to update its state after fetching succeeds.)
-    fn fetch(self, collection: &NodeCollection) -> Fallible<Fetched> {
+    fn fetch_with_progress(self, collection: &NodeCollection, bar: ProgressBar) -> Fallible<Fetched> {
         if collection.contains(&self.version) {
             return Ok(Fetched::Already(self.version));
         }
 
         let dest = path::node_versions_dir()?;
-        let bar = progress_bar(
-            Action::Fetching,
-            &format!("v{}", self.version),
-            self.archive
-                .uncompressed_size()
-                .unwrap_or(self.archive.compressed_size()),
-        );
-
-        self.archive
-            .unpack(&dest, &mut |_, read| {
-                bar.inc(read as u64);
-            })
-            .unknown()?;
+        create_dir_all(&dest).unknown()?;
 
+        // Unpack into a private staging directory on the same filesystem, so that an
+        // interrupted unpack never leaves a partial version directory behind, and
+        // concurrent fetches of the same version don't collide.
         let version_string = self.version.to_string();
-        rename(
-            dest.join(path::node_archive_root_dir(&version_string)),
-            path::node_version_dir(&version_string)?,
-        ).unknown()?;
+        let root_dir = path::node_archive_root_dir_for(&version_string, &self.arch);
+        let staging = unpack_staged(self.archive, &dest, &root_dir, &self.cache_file, &bar)?;
+        let unpacked = staging.path().join(&root_dir);
+
+        // Verify the checksum of a download before the unpacked files are moved into place.
+        verify_download(self.pending_checksum, &bar)?;
+
+        // A mirror that serves the wrong file would otherwise only show once a shim
+        // fails to run Node.
+        let executable = if cfg!(windows) {
+            PathBuf::from("node.exe")
+        } else {
+            Path::new("bin").join("node")
+        };
+        if !unpacked.join(&executable).is_file() {
+            bar.finish_and_clear();
+            discard_cached(&self.cache_file);
+            throw!(IncompleteArchiveError {
+                file: self.cache_file.to_string_lossy().to_string(),
+                missing: format!("{}/{}", root_dir, executable.display()),
+            });
+        }
+
+        // A build for another architecture (see `NOTION_ARCH`) may not run here at all.
+        if env::arch().is_none() {
+            if let Err(problem) = smoke_test(&unpacked.join(&executable), &self.version) {
+                bar.finish_and_clear();
+                discard_cached(&self.cache_file);
+                throw!(BrokenNodeError {
+                    version: version_string,
+                    problem,
+                });
+            }
+        }
+
+        move_into_place(&unpacked, &path::user_node_version_dir(&version_string)?)?;
 
         bar.finish_and_clear();
         Ok(Fetched::Now(self.version))
@@ -0,0 +1,173 @@
+//! Provides the `NpmDistro` type, which represents a provisioned npm distribution.
+
+use std::fs::{create_dir_all, File};
+use std::path::PathBuf;
+use std::string::ToString;
+
+use super::{cache_matches, download, move_into_place, template_url, unpack_staged, verify_download,
+            Distro, Fetched, PendingChecksum};
+use catalog::NpmCollection;
+use config::Config;
+use distro::error::DownloadError;
+use distro::stamp;
+use fs::{ensure_containing_dir_exists, lock, FileLock};
+use http;
+use node_archive::{self, Archive};
+use path;
+use indicatif::ProgressBar;
+use style::{progress_bar, Action};
+
+use notion_fail::{Fallible, ResultExt};
+use semver::Version;
+
+const PUBLIC_NPM_SERVER_ROOT: &'static str = "https://registry.npmjs.org/npm/-/";
+
+/// A provisioned npm distribution.
+pub struct NpmDistro {
+    archive: Box<Archive>,
+    version: Version,
+
+    /// For an archive that is still being downloaded with a known checksum (e.g. one
+    /// produced by a resolve plugin), or that is being streamed without being cached,
+    /// how to verify the download once it has been unpacked.
+    pending_checksum: Option<PendingChecksum>,
+
+    /// The lock on the cache file, held while the archive is downloaded into it.
+    download_lock: Option<FileLock>,
+}
+
+/// Check if the cached file is valid. It may have been corrupted or interrupted in the middle of
+/// downloading. A stamp recording that the file was complete saves reading it again.
+fn cache_is_valid(cache_file: &PathBuf) -> bool {
+    if let Some(valid) = stamp::check(cache_file, None) {
+        return valid;
+    }
+    if cache_file.is_file() {
+        if let Ok(file) = File::open(cache_file) {
+            if node_archive::load(file).is_ok() {
+                let _ = stamp::write(cache_file, None);
+                return true;
+            }
+        }
+    }
+    false
+}
+
+impl NpmDistro {
+    /// Provision a distribution from a remote distributor, verifying the archive against
+    /// the expected checksum if one is given.
+    fn remote_checked(
+        version: Version,
+        url: &str,
+        expected_checksum: Option<String>,
+        config: &Config,
+    ) -> Fallible<Self> {
+        let archive_file = path::npm_archive_file(&version.to_string());
+        let cache_file = path::npm_cache_dir()?.join(&archive_file);
+        let download_lock = lock(&cache_file, &format!("downloading {}", archive_file))?;
+
+        let valid = match expected_checksum {
+            Some(ref expected_checksum) => cache_matches(&cache_file, expected_checksum),
+            None => cache_is_valid(&cache_file),
+        };
+        if valid {
+            return NpmDistro::cached(version, File::open(cache_file).unknown()?);
+        }
+
+        http::ensure_online(url)?;
+        ensure_containing_dir_exists(&cache_file)?;
+        stamp::remove(&cache_file);
+        let (archive, pending_checksum) = download(url, &cache_file, expected_checksum, config)
+            .with_context(DownloadError::for_version(version.to_string()))?;
+        Ok(NpmDistro {
+            archive,
+            version: version,
+            pending_checksum,
+            download_lock: Some(download_lock),
+        })
+    }
+}
+
+impl Distro for NpmDistro {
+    /// Provision a distribution from the public npm registry (`https://registry.npmjs.org`),
+    /// unless a `npm.url-template` setting says where to download it from instead.
+    fn public(version: Version, config: &Config) -> Fallible<Self> {
+        let archive_file = path::npm_archive_file(&version.to_string());
+        let url = match config.npm.as_ref().and_then(|npm| npm.url_template.as_ref()) {
+            Some(template) => template_url(template, &version, &archive_file, "tgz"),
+            None => format!("{}{}", PUBLIC_NPM_SERVER_ROOT, archive_file),
+        };
+        NpmDistro::remote(version, &url, config)
+    }
+
+    /// Provision a distribution from a remote distributor. npm is only published
+    /// as a gzipped tarball, regardless of platform.
+    fn remote(version: Version, url: &str, config: &Config) -> Fallible<Self> {
+        NpmDistro::remote_checked(version, url, None, config)
+    }
+
+    /// Provision a distribution from a remote distributor, verifying the archive against
+    /// the specified checksum.
+    fn remote_with_checksum(
+        version: Version,
+        url: &str,
+        checksum: &str,
+        config: &Config,
+    ) -> Fallible<Self> {
+        NpmDistro::remote_checked(version, url, Some(checksum.to_string()), config)
+    }
+
+    /// Provision a distribution from the filesystem.
+    fn cached(version: Version, file: File) -> Fallible<Self> {
+        Ok(NpmDistro {
+            archive: node_archive::load(file).unknown()?,
+            version: version,
+            pending_checksum: None,
+            download_lock: None,
+        })
+    }
+
+    /// Produces a reference to this distro's npm version.
+    fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// Produces a progress bar for fetching this distribution.
+    fn progress_bar(&self) -> ProgressBar {
+        progress_bar(
+            Action::Fetching,
+            &format!("npm v{}", self.version),
+            self.archive
+                .uncompressed_size()
+                .unwrap_or(self.archive.compressed_size()),
+        )
+    }
+
+    /// Fetches this version of npm, reporting progress to the specified bar. (It is left to the responsibility of the `NpmCollection`
+    /// to update its state after fetching succeeds.)
+    fn fetch_with_progress(self, collection: &NpmCollection, bar: ProgressBar) -> Fallible<Fetched> {
+        if collection.contains(&self.version) {
+            return Ok(Fetched::Already(self.version));
+        }
+
+        let dest = path::npm_versions_dir()?;
+        create_dir_all(&dest).unknown()?;
+
+        // Every package tarball unpacks into the same `package` directory, so unpack into
+        // a private staging directory to keep concurrent fetches from colliding.
+        let version_string = self.version.to_string();
+        let root_dir = path::npm_archive_root_dir();
+        let cache_file = path::npm_cache_dir()?.join(path::npm_archive_file(&version_string));
+        let staging = unpack_staged(self.archive, &dest, &root_dir, &cache_file, &bar)?;
+        if !verify_download(self.pending_checksum, &bar)? {
+            let _ = stamp::write(&cache_file, None);
+        }
+        move_into_place(
+            &staging.path().join(root_dir),
+            &path::user_npm_version_dir(&version_string)?,
+        )?;
+
+        bar.finish_and_clear();
+        Ok(Fetched::Now(self.version))
+    }
+}
@@ -0,0 +1,450 @@
+//! Provides the `PackageDistro` type, which represents a provisioned package
+//! tarball from the npm registry.
+
+use std::collections::HashMap;
+use std::fs::{remove_file, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use catalog::RegistryFetchError;
+use config::Config;
+use distro::checksum::{self, Integrity};
+use distro::{stamp, unpack_staged};
+use distro::error::{ChecksumMismatchError, DownloadError, PackageNotFoundError,
+                    PackageVersionNotFoundError, TarballFetchError};
+use fs::{ensure_containing_dir_exists, lock, remove_dir_all, rename, FileLock};
+use http;
+use indicatif::ProgressBar;
+use node_archive::{self, Archive};
+use npmrc::Npmrc;
+use package::PackageLtsError;
+use path;
+use profile::{self, Phase};
+use reqwest::header::Headers;
+use reqwest::StatusCode;
+use style::{progress_bar, progress_spinner, Action};
+use version::VersionSpec;
+
+use notion_fail::{Fallible, ResultExt};
+use semver::Version;
+
+/// The abbreviated metadata for a package, as served by the npm registry.
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(rename = "dist-tags")]
+    dist_tags: HashMap<String, String>,
+    versions: HashMap<String, VersionMetadata>,
+}
+
+#[derive(Deserialize)]
+struct VersionMetadata {
+    dist: Dist,
+}
+
+/// Where to download a version of a package from, and how to verify it.
+#[derive(Deserialize)]
+struct Dist {
+    tarball: String,
+    integrity: Option<String>,
+    shasum: Option<String>,
+}
+
+impl Dist {
+    /// The strongest digest of the integrity field, which may list several. Older
+    /// packages only publish a hex-encoded SHA-1 `shasum`, which is used instead.
+    fn integrity(&self) -> Option<Integrity> {
+        self.integrity
+            .as_ref()
+            .and_then(|integrity| Integrity::parse(integrity))
+            .or_else(|| {
+                self.shasum
+                    .as_ref()
+                    .and_then(|shasum| Integrity::from_sha1_hex(shasum))
+            })
+    }
+}
+
+impl Metadata {
+    /// Finds the newest version matching the specified semantic versioning requirements
+    /// (or the version tagged `latest`), along with where to download it from.
+    fn resolve(self, name: &str, matching: &VersionSpec) -> Fallible<(Version, Dist)> {
+        let Metadata {
+            dist_tags,
+            mut versions,
+        } = self;
+
+        let version = match *matching {
+            VersionSpec::Latest => dist_tags
+                .get("latest")
+                .and_then(|latest| Version::parse(latest).ok()),
+            VersionSpec::Semver(ref matching) => versions
+                .keys()
+                .filter_map(|v| Version::parse(v).ok())
+                .filter(|v| matching.matches(v))
+                .max(),
+            // Packages often publish their prereleases under tags of the same name.
+            VersionSpec::Channel(channel) => dist_tags
+                .get(&channel.to_string())
+                .and_then(|tagged| Version::parse(tagged).ok()),
+            VersionSpec::Tag(ref tag) => dist_tags
+                .get(tag)
+                .and_then(|tagged| Version::parse(tagged).ok()),
+            VersionSpec::Lts(_) => throw!(PackageLtsError {
+                name: name.to_string(),
+            }),
+        };
+
+        let found = version.and_then(|version| {
+            versions
+                .remove(&version.to_string())
+                .map(|metadata| (version, metadata.dist))
+        });
+        match found {
+            Some(found) => Ok(found),
+            None => throw!(PackageVersionNotFoundError {
+                name: name.to_string(),
+                matching: matching.to_string(),
+            }),
+        }
+    }
+}
+
+/// The URL of a package's metadata on a registry. The `/` of a scoped package name
+/// must be escaped.
+fn metadata_url(registry: &str, name: &str) -> String {
+    format!("{}{}", registry, name.replace('/', "%2f"))
+}
+
+/// The headers for a request to a registry, including the `Authorization` header the
+/// user's `.npmrc` configures for the URL, if any.
+fn registry_headers(url: &str, npmrc: &Npmrc) -> Headers {
+    let mut headers = Headers::new();
+    if let Some(credentials) = npmrc.credentials_for(url) {
+        headers.set_raw("Authorization", credentials.header_value());
+    }
+    headers
+}
+
+fn fetch_metadata(name: &str, npmrc: &Npmrc, config: &Config) -> Fallible<Metadata> {
+    let url = metadata_url(npmrc.registry_for(name), name);
+    http::ensure_online(&url)?;
+    let spinner = progress_spinner(&format!("Fetching registry: {}", url));
+    // The abbreviated metadata format omits the full manifest of every version.
+    let mut headers = registry_headers(&url, npmrc);
+    headers.set_raw("Accept", "application/vnd.npm.install-v1+json");
+    let client = http::client_for(&url, config).with_context(RegistryFetchError::from_error)?;
+    let mut response = http::retry(&url, config, http::is_transient, || {
+        http::throttled(&url, || {
+            http::traced("GET", &url, Some(&headers), || {
+                client.get(&url[..]).headers(headers.clone()).send()
+            })
+        })
+    }).with_context(RegistryFetchError::from_error)?;
+    spinner.finish_and_clear();
+    http::ensure_not_throttled(&url, &response)?;
+
+    if response.status() == StatusCode::NotFound {
+        throw!(PackageNotFoundError {
+            name: name.to_string(),
+        });
+    }
+    Ok(response.json().unknown()?)
+}
+
+/// Downloads a tarball into the cache file in full, with the credentials `.npmrc`
+/// configures for it, if any. Unlike other downloads, these aren't streamed while
+/// unpacking, so that they can be verified before anything is unpacked (and since
+/// the archive downloader doesn't send credentials).
+fn download(url: &str, cache_file: &Path, npmrc: &Npmrc, config: &Config) -> Fallible<()> {
+    let headers = registry_headers(url, npmrc);
+    let client = http::client_for(url, config).with_context(RegistryFetchError::from_error)?;
+    let mut response = http::retry(url, config, http::is_transient, || {
+        http::throttled(url, || {
+            http::traced("GET", url, Some(&headers), || {
+                client.get(url).headers(headers.clone()).send()
+            })
+        })
+    }).with_context(RegistryFetchError::from_error)?;
+    http::ensure_not_throttled(url, &response)?;
+    if !response.status().is_success() {
+        throw!(TarballFetchError {
+            url: url.to_string(),
+            status: response.status().to_string(),
+        });
+    }
+
+    let mut file = File::create(cache_file).unknown()?;
+    io::copy(&mut response, &mut file).unknown()?;
+    Ok(())
+}
+
+/// Verifies a downloaded tarball against its published integrity, removing it if it
+/// doesn't match, and stamping it as verified if it does.
+fn verify(cache_file: &Path, expected: &Integrity) -> Fallible<()> {
+    let actual = Integrity::of_file(expected.algorithm, cache_file)?;
+    if actual != *expected {
+        let _ = remove_file(cache_file);
+        throw!(ChecksumMismatchError {
+            file: cache_file.to_string_lossy().to_string(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        });
+    }
+    let _ = stamp::write(cache_file, Some(&expected.to_string()));
+    Ok(())
+}
+
+/// Check if the cached file is valid. It may have been corrupted or interrupted in the middle of
+/// downloading, or it may not match the published integrity digest. A stamp recording that the
+/// file was verified against the same digest saves reading it again.
+fn cache_is_valid(cache_file: &PathBuf, expected_integrity: &Option<Integrity>) -> bool {
+    let stamp = expected_integrity.as_ref().map(|expected| expected.to_string());
+    let expected = stamp.as_ref().map(|stamp| &stamp[..]);
+    if let Some(valid) = stamp::check(cache_file, expected) {
+        return valid;
+    }
+    if !cache_file.is_file() {
+        return false;
+    }
+    let valid = match *expected_integrity {
+        Some(ref expected) => match Integrity::of_file(expected.algorithm, cache_file) {
+            Ok(actual) => actual == *expected,
+            Err(_) => false,
+        },
+        None => match File::open(cache_file) {
+            Ok(file) => node_archive::load(file).is_ok(),
+            Err(_) => false,
+        },
+    };
+    if valid {
+        let _ = stamp::write(cache_file, expected);
+    }
+    valid
+}
+
+/// A provisioned package tarball from the npm registry.
+pub struct PackageDistro {
+    name: String,
+    archive: Box<Archive>,
+    version: Version,
+
+    /// The integrity the tarball was verified against, if the registry publishes one.
+    integrity: Option<Integrity>,
+
+    /// The lock on the cache file, held while the tarball is downloaded into it.
+    download_lock: Option<FileLock>,
+}
+
+impl PackageDistro {
+    /// Provisions the newest version of the named package that matches the specified
+    /// semantic versioning requirements (or its `latest` version), from the registry
+    /// configured for its scope or else the default registry (by default, the public
+    /// npm registry), with the credentials `.npmrc` configures for that registry.
+    /// Tarballs are cached, and verified against the registry's integrity digest
+    /// (SHA-512, or SHA-1 for older packages) before they are unpacked.
+    pub fn public(name: &str, matching: &VersionSpec, config: &Config) -> Fallible<Self> {
+        let mut npmrc = Npmrc::current()?;
+        if let Some(ref registry) = config.registry {
+            npmrc.override_with(registry);
+        }
+        let (version, dist) = fetch_metadata(name, &npmrc, config)?.resolve(name, matching)?;
+        let archive_file = path::package_archive_file(name, &version.to_string());
+        let cache_file = path::package_cache_dir()?.join(&archive_file);
+        let expected_integrity = dist.sha512();
+        let download_lock = lock(&cache_file, &format!("downloading {}", archive_file))?;
+
+        if cache_is_valid(&cache_file, &expected_integrity) {
+            return Ok(PackageDistro {
+                name: name.to_string(),
+                archive: node_archive::load(File::open(cache_file).unknown()?).unknown()?,
+                version: version,
+                integrity: expected_integrity,
+                download_lock: None,
+            });
+        }
+
+        ensure_containing_dir_exists(&cache_file)?;
+        stamp::remove(&cache_file);
+        let authenticated = npmrc.credentials_for(&dist.tarball).is_some();
+        let archive = if expected_integrity.is_some() || authenticated {
+            profile::time(Phase::Download, || {
+                download(&dist.tarball, &cache_file, &npmrc, config)
+            })?;
+            if let Some(ref expected) = expected_integrity {
+                verify(&cache_file, expected)?;
+            }
+            node_archive::load(File::open(&cache_file).unknown()?).unknown()?
+        } else {
+            http::fetch_archive(&dist.tarball, &cache_file, config)
+                .with_context(DownloadError::for_version(version.to_string()))?
+        };
+        Ok(PackageDistro {
+            name: name.to_string(),
+            archive,
+            version: version,
+            integrity: expected_integrity,
+            download_lock: Some(download_lock),
+        })
+    }
+
+    /// Produces the name of this distribution's package.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Produces a reference to this distribution's package version.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    /// The integrity string the package's tarball was verified against, like
+    /// `sha512-<base64 digest>`, if the registry publishes one.
+    pub fn integrity(&self) -> Option<String> {
+        self.integrity.as_ref().map(|integrity| integrity.to_string())
+    }
+
+    /// The hex-encoded SHA-256 checksum of the package's tarball, which identifies
+    /// its contents.
+    pub fn checksum(&self) -> Fallible<String> {
+        let archive_file = path::package_archive_file(&self.name, &self.version.to_string());
+        checksum::sha256_file(&path::package_cache_dir()?.join(archive_file))
+    }
+
+    /// Produces a progress bar for fetching this distribution.
+    pub fn progress_bar(&self) -> ProgressBar {
+        progress_bar(
+            Action::Fetching,
+            &format!("{} v{}", self.name, self.version),
+            self.archive
+                .uncompressed_size()
+                .unwrap_or(self.archive.compressed_size()),
+        )
+    }
+
+    /// Unpacks this version of the package into the specified directory, replacing
+    /// anything already there, and reporting progress to the specified bar.
+    pub fn fetch_with_progress(self, dest: &Path, bar: ProgressBar) -> Fallible<()> {
+        ensure_containing_dir_exists(&dest)?;
+        let parent = dest.parent().unwrap_or(dest);
+
+        // Every package tarball unpacks into the same `package` directory, so unpack into
+        // a private staging directory to keep concurrent fetches from colliding.
+        let root_dir = path::npm_archive_root_dir();
+        let archive_file = path::package_archive_file(&self.name, &self.version.to_string());
+        let cache_file = path::package_cache_dir()?.join(archive_file);
+        let staging = unpack_staged(self.archive, parent, &root_dir, &cache_file, &bar)?;
+
+        // A tarball published without a digest is complete once it has unpacked.
+        if self.integrity.is_none() && self.download_lock.is_some() {
+            let _ = stamp::write(&cache_file, None);
+        }
+
+        if dest.exists() {
+            remove_dir_all(dest).unknown()?;
+        }
+        rename(staging.path().join(root_dir), dest).unknown()?;
+
+        bar.finish_and_clear();
+        Ok(())
+    }
+
+    /// Unpacks this version of the package into the specified directory, replacing
+    /// anything already there.
+    pub fn fetch(self, dest: &Path) -> Fallible<()> {
+        let bar = self.progress_bar();
+        self.fetch_with_progress(dest, bar)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{metadata_url, registry_headers, Metadata};
+    use npmrc::{Npmrc, PUBLIC_NPM_REGISTRY};
+    use serde_json;
+    use version::VersionSpec;
+
+    const METADATA: &'static str = r#"{
+        "name": "cowsay",
+        "dist-tags": { "latest": "1.3.1", "next": "2.0.0-beta.1" },
+        "versions": {
+            "1.2.0": {
+                "dist": {
+                    "tarball": "https://registry.npmjs.org/cowsay/-/cowsay-1.2.0.tgz",
+                    "shasum": "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"
+                }
+            },
+            "1.3.1": {
+                "dist": {
+                    "tarball": "https://registry.npmjs.org/cowsay/-/cowsay-1.3.1.tgz",
+                    "integrity": "sha1-AAAA sha512-BBBB"
+                }
+            },
+            "2.0.0-beta.1": {
+                "dist": {
+                    "tarball": "https://registry.npmjs.org/cowsay/-/cowsay-2.0.0-beta.1.tgz"
+                }
+            }
+        }
+    }"#;
+
+    fn metadata() -> Metadata {
+        serde_json::from_str(METADATA).unwrap()
+    }
+
+    #[test]
+    fn test_metadata_url() {
+        assert_eq!(
+            metadata_url(PUBLIC_NPM_REGISTRY, "cowsay"),
+            "https://registry.npmjs.org/cowsay"
+        );
+        assert_eq!(
+            metadata_url(PUBLIC_NPM_REGISTRY, "@vue/cli"),
+            "https://registry.npmjs.org/@vue%2fcli"
+        );
+    }
+
+    #[test]
+    fn test_registry_headers() {
+        let npmrc = Npmrc::parse("//npm.example.com/:_authToken=secret\n");
+        let headers = registry_headers("https://npm.example.com/cowsay", &npmrc);
+        assert_eq!(
+            headers.get_raw("Authorization").and_then(|raw| raw.one()),
+            Some(&b"Bearer secret"[..])
+        );
+        let headers = registry_headers("https://registry.npmjs.org/cowsay", &npmrc);
+        assert!(headers.get_raw("Authorization").is_none());
+    }
+
+    #[test]
+    fn test_resolve_latest() {
+        let (version, dist) = metadata().resolve("cowsay", &VersionSpec::Latest).unwrap();
+        assert_eq!(version.to_string(), "1.3.1");
+        assert_eq!(
+            dist.tarball,
+            "https://registry.npmjs.org/cowsay/-/cowsay-1.3.1.tgz"
+        );
+        assert_eq!(
+            dist.integrity().map(|integrity| integrity.to_string()),
+            Some("sha512-BBBB".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_semver() {
+        let matching = VersionSpec::parse("~1.2").unwrap();
+        let (version, dist) = metadata().resolve("cowsay", &matching).unwrap();
+        assert_eq!(version.to_string(), "1.2.0");
+        assert_eq!(
+            dist.integrity().map(|integrity| integrity.to_string()),
+            Some("sha1-qvTGHdzF6KLavt4PO0gs2a6pQ00=".to_string())
+        );
+
+        let matching = VersionSpec::parse("2.0.0-beta.1").unwrap();
+        let (_, dist) = metadata().resolve("cowsay", &matching).unwrap();
+        assert!(dist.integrity().is_none());
+
+        let matching = VersionSpec::parse("3").unwrap();
+        assert!(metadata().resolve("cowsay", &matching).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! Provides GPG signature verification for the checksums published with a distribution.
+
+use std::fs::read;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use tempfile::NamedTempFile;
+
+use config::Config;
+use distro::error::{ChecksumFetchError, GpgUnavailableError, MissingKeyringError,
+                    SignatureVerificationError};
+use http;
+use notion_fail::{Fallible, ResultExt};
+use path;
+
+/// Verifies the contents of a checksums file, downloaded from the specified URL,
+/// against its detached signature (published at the same URL with a `.sig` suffix)
+/// and the Node release team's keys in the user's Node keyring.
+pub(crate) fn verify(shasums_url: &str, shasums: &str, config: &Config) -> Fallible<()> {
+    let keyring = path::node_keyring_file()?;
+    if !keyring.is_file() {
+        throw!(MissingKeyringError {
+            path: keyring.to_string_lossy().to_string(),
+        });
+    }
+
+    let signature_url = format!("{}.sig", shasums_url);
+    let signature = match http::file_url_path(&signature_url) {
+        Some(path) => read(&path).with_context(|error| ChecksumFetchError {
+            url: signature_url.clone(),
+            error: error.to_string(),
+        })?,
+        None => {
+            let mut response = http::get(&signature_url, config)
+                .with_context(ChecksumFetchError::for_url(signature_url.clone()))?;
+
+            if !response.status().is_success() {
+                throw!(ChecksumFetchError {
+                    url: signature_url,
+                    error: format!("HTTP failure ({})", response.status()),
+                });
+            }
+
+            let mut signature = Vec::new();
+            response.read_to_end(&mut signature).unknown()?;
+            signature
+        }
+    };
+
+    // gpg can only verify a detached signature against files on disk.
+    let mut signature_file = NamedTempFile::new().unknown()?;
+    signature_file.write_all(&signature).unknown()?;
+    let mut shasums_file = NamedTempFile::new().unknown()?;
+    shasums_file.write_all(shasums.as_bytes()).unknown()?;
+
+    let output = Command::new("gpg")
+        .arg("--batch")
+        .arg("--no-default-keyring")
+        .arg("--keyring")
+        .arg(&keyring)
+        .arg("--verify")
+        .arg(signature_file.path())
+        .arg(shasums_file.path())
+        .stdin(Stdio::null())
+        .output()
+        .with_context(GpgUnavailableError::from_io_error)?;
+
+    if !output.status.success() {
+        throw!(SignatureVerificationError {
+            url: signature_url,
+            details: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(())
+}
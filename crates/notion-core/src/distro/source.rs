@@ -0,0 +1,180 @@
+//! Builds Node from the source tarball of a release, for hosts that Node publishes no
+//! prebuilt binary for (e.g. an unusual architecture, or one that an old release
+//! predates builds for).
+
+use std::collections::VecDeque;
+use std::fs::{create_dir_all, File};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use super::{cache_matches, discard_cached, move_into_place, unpack_staged, verify_download,
+            Fetched, PendingChecksum};
+use catalog::NodeCollection;
+use config::Config;
+use distro::error::{BrokenNodeError, DownloadError, SourceBuildError,
+                    SourceBuildUnsupportedError};
+use distro::node::{release_server_root, smoke_test};
+use distro::{checksum, stamp};
+use fs::{ensure_containing_dir_exists, lock};
+use http;
+use indicatif::ProgressBar;
+#[cfg(unix)]
+use libc;
+use log;
+use node_archive;
+use path;
+use style::progress_spinner;
+
+use notion_fail::{Fallible, ResultExt};
+use semver::Version;
+
+/// How many of the last lines of a failed step's error output are reported.
+const ERROR_LINES: usize = 20;
+
+/// Fetches a version of Node by building it from the source tarball published with the
+/// release, which is verified against the release's `SHASUMS256.txt` like any archive,
+/// and cached like one. The source is configured, built, and installed into a staging
+/// directory next to the version directory, which it is then moved into. (It is left
+/// to the responsibility of the `Collection` to update its state after fetching
+/// succeeds.)
+pub(crate) fn fetch(version: Version, collection: &NodeCollection, config: &Config) -> Fallible<Fetched> {
+    if cfg!(windows) {
+        throw!(SourceBuildUnsupportedError);
+    }
+    if collection.contains(&version) {
+        return Ok(Fetched::Already(version));
+    }
+
+    let version_string = version.to_string();
+    let root_dir = format!("node-v{}", version_string);
+    let archive_file = path::node_source_archive_file(&version_string);
+    let url = format!("{}v{}/{}", release_server_root(config), version_string, archive_file);
+    let cache_file = path::node_cache_dir()?.join(&archive_file);
+    let _download_lock = lock(&cache_file, &format!("downloading {}", archive_file))?;
+
+    let verify_signature = config
+        .node
+        .as_ref()
+        .map(|node| node.verify_signatures)
+        .unwrap_or(false);
+    let expected_checksum = checksum::fetch_sha256(&url, verify_signature, config)?;
+
+    let bar = progress_spinner(&format!("Building node v{} from source", version));
+    let (archive, pending_checksum) = if cache_matches(&cache_file, &expected_checksum) {
+        let archive = node_archive::load(File::open(&cache_file).unknown()?).unknown()?;
+        (archive, None)
+    } else {
+        ensure_containing_dir_exists(&cache_file)?;
+        stamp::remove(&cache_file);
+        let archive = http::fetch_archive(&url, &cache_file, config)
+            .with_context(DownloadError::for_version(version_string.clone()))?;
+        let pending = PendingChecksum::Cached(cache_file.clone(), expected_checksum);
+        (archive, Some(pending))
+    };
+
+    let dest = path::node_versions_dir()?;
+    create_dir_all(&dest).unknown()?;
+    let staging = unpack_staged(archive, &dest, &root_dir, &cache_file, &bar)?;
+    verify_download(pending_checksum, &bar)?;
+
+    let source = staging.path().join(&root_dir);
+    let prefix = staging.path().join("install");
+
+    let mut configure = Command::new("./configure");
+    configure.arg(format!("--prefix={}", prefix.display()));
+    configure.current_dir(&source);
+    run_step(&mut configure, "./configure", &version, &bar)?;
+
+    let mut make = Command::new("make");
+    make.arg(format!("-j{}", jobs()));
+    make.current_dir(&source);
+    run_step(&mut make, "make", &version, &bar)?;
+
+    let mut install = Command::new("make");
+    install.arg("install");
+    install.current_dir(&source);
+    run_step(&mut install, "make install", &version, &bar)?;
+
+    if let Err(problem) = smoke_test(&prefix.join("bin").join("node"), &version) {
+        bar.finish_and_clear();
+        discard_cached(&cache_file);
+        throw!(BrokenNodeError {
+            version: version_string,
+            problem,
+        });
+    }
+
+    move_into_place(&prefix, &path::user_node_version_dir(&version_string)?)?;
+    bar.finish_and_clear();
+    Ok(Fetched::Now(version))
+}
+
+/// Runs a step of the build, counting the lines of output it produces on the spinner,
+/// as a sign of life: compiling Node takes a long while. If the step fails, the last
+/// lines of its error output are reported.
+fn run_step(command: &mut Command, step: &str, version: &Version, bar: &ProgressBar) -> Fallible<()> {
+    log::info(|| format!("building node v{} from source: running {}", version, step));
+    let failed = |output: String| SourceBuildError {
+        version: version.to_string(),
+        step: step.to_string(),
+        output,
+    };
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|error| failed(error.to_string()))?;
+
+    // Collect the error output on another thread, so that neither pipe fills up while
+    // the other is read.
+    let stderr = child.stderr.take().unwrap();
+    let errors = thread::spawn(move || {
+        let mut lines = VecDeque::with_capacity(ERROR_LINES + 1);
+        for line in BufReader::new(stderr).lines() {
+            match line {
+                Ok(line) => lines.push_back(line),
+                Err(_) => break,
+            }
+            if lines.len() > ERROR_LINES {
+                lines.pop_front();
+            }
+        }
+        lines.into_iter().collect::<Vec<_>>()
+    });
+
+    let stdout = child.stdout.take().unwrap();
+    let mut produced = 0;
+    for line in BufReader::new(stdout).lines() {
+        if line.is_err() {
+            break;
+        }
+        produced += 1;
+        bar.set_message(&format!(
+            "Building node v{} from source: {} ({} lines of output)",
+            version, step, produced
+        ));
+    }
+
+    let status = child.wait().with_context(|error| failed(error.to_string()))?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        bar.finish_and_clear();
+        throw!(failed(format!("{}\n{}", status, errors.join("\n"))));
+    }
+    Ok(())
+}
+
+/// The number of jobs `make` runs at once: one per processor.
+#[cfg(unix)]
+fn jobs() -> i64 {
+    let processors = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    (processors as i64).max(1)
+}
+
+#[cfg(windows)]
+fn jobs() -> i64 {
+    1
+}
@@ -0,0 +1,116 @@
+//! Provides the stamps recorded next to the archives in the cache, which let a cached
+//! archive be validated without reading the whole archive back.
+//!
+//! A stamp is written once an archive has been downloaded completely and verified (or
+//! unpacked successfully, for tools that publish no checksums). It records the size of
+//! the archive and the digest it was verified against, if any.
+
+use std::ffi::OsString;
+use std::fs::{self, remove_file};
+use std::path::{Path, PathBuf};
+
+use notion_fail::{Fallible, ResultExt};
+
+/// The contents of a stamp.
+#[derive(Debug, PartialEq)]
+struct Stamp {
+    size: u64,
+    digest: Option<String>,
+}
+
+impl Stamp {
+    fn parse(src: &str) -> Option<Stamp> {
+        let mut lines = src.lines();
+        let size = lines.next()?.parse().ok()?;
+        let digest = match lines.next() {
+            Some(digest) if !digest.is_empty() => Some(digest.to_string()),
+            _ => None,
+        };
+        Some(Stamp { size, digest })
+    }
+
+    fn to_string(&self) -> String {
+        format!("{}\n{}\n", self.size, self.digest.as_ref().map_or("", |digest| &digest[..]))
+    }
+}
+
+/// Returns the stamp file for the specified cached archive.
+fn stamp_file_for(cache_file: &Path) -> PathBuf {
+    let mut stamp_file = OsString::from(cache_file.as_os_str());
+    stamp_file.push(".stamp");
+    PathBuf::from(stamp_file)
+}
+
+/// Returns whether the specified file is a stamp rather than an archive.
+pub(crate) fn is_stamp_file(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension == "stamp")
+}
+
+/// Records that the specified cached archive is complete and, if a digest is given,
+/// that it was verified against that digest.
+pub(crate) fn write(cache_file: &Path, digest: Option<&str>) -> Fallible<()> {
+    let stamp = Stamp {
+        size: fs::metadata(cache_file).unknown()?.len(),
+        digest: digest.map(|digest| digest.to_string()),
+    };
+    fs::write(stamp_file_for(cache_file), stamp.to_string()).unknown()
+}
+
+/// Removes the stamp of the specified cached archive, if it has one.
+pub(crate) fn remove(cache_file: &Path) {
+    let _ = remove_file(stamp_file_for(cache_file));
+}
+
+/// Checks a cached archive against its stamp: the archive is valid if it is still the
+/// size it was when it was stamped and, if a digest is expected, it was verified
+/// against that digest. Produces `None` if there is no stamp to go by, in which case
+/// the archive has to be read to validate it.
+pub(crate) fn check(cache_file: &Path, expected_digest: Option<&str>) -> Option<bool> {
+    let src = fs::read_to_string(stamp_file_for(cache_file)).ok()?;
+    let stamp = Stamp::parse(&src)?;
+    if let Some(expected) = expected_digest {
+        if stamp.digest.as_ref().map(|digest| &digest[..]) != Some(expected) {
+            return None;
+        }
+    }
+    let size = match fs::metadata(cache_file) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Some(false),
+    };
+    Some(size == stamp.size)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{check, is_stamp_file, remove, stamp_file_for, write};
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let cache_file = dir.path().join("node-v10.8.0-linux-x64.tar.gz");
+        File::create(&cache_file)
+            .and_then(|mut file| file.write_all(b"archive"))
+            .unwrap();
+
+        // without a stamp, the archive has to be read
+        assert_eq!(check(&cache_file, None), None);
+
+        write(&cache_file, Some("abc123")).unwrap();
+        assert!(is_stamp_file(&stamp_file_for(&cache_file)));
+        assert_eq!(check(&cache_file, None), Some(true));
+        assert_eq!(check(&cache_file, Some("abc123")), Some(true));
+        // a stamp for another digest can't vouch for the archive
+        assert_eq!(check(&cache_file, Some("def456")), None);
+
+        // nor can a stamp for an archive that has since changed
+        File::create(&cache_file).unwrap();
+        assert_eq!(check(&cache_file, Some("abc123")), Some(false));
+
+        remove(&cache_file);
+        assert_eq!(check(&cache_file, None), None);
+    }
+}
@@ -1,15 +1,20 @@
 //! Provides the `Installer` type, which represents a provisioned Node installer.
 
-use std::fs::{rename, File};
+use std::fs::{create_dir_all, File};
 use std::path::PathBuf;
 use std::string::ToString;
 
-use super::{Distro, Fetched};
+use super::{cache_matches, download, move_into_place, template_url, unpack_staged, verify_download,
+            Distro, Fetched, PendingChecksum};
 use catalog::YarnCollection;
+use config::Config;
 use distro::error::DownloadError;
-use fs::ensure_containing_dir_exists;
+use distro::stamp;
+use fs::{ensure_containing_dir_exists, lock, FileLock};
+use http;
 use node_archive::{self, Archive};
 use path;
+use indicatif::ProgressBar;
 use style::{progress_bar, Action};
 
 use notion_fail::{Fallible, ResultExt};
@@ -18,57 +23,129 @@ use semver::Version;
 const PUBLIC_YARN_SERVER_ROOT: &'static str =
     "https://github.com/notion-cli/yarn-releases/raw/master/dist/";
 
+/// Returns the URL to download the archive of a Yarn version from: out of the
+/// `yarn.url-template` setting if configured, otherwise from the `yarn.mirror` setting
+/// if configured, otherwise from the public server. A mirror is the root of a copy of
+/// the public server's layout, which the archive's file name is appended to, unless it
+/// has placeholders like a `url-template` (e.g. `https://artifacts.corp/yarn/{version}/{filename}`).
+fn public_url(version: &Version, config: &Config) -> String {
+    let archive_file = path::yarn_archive_file(&version.to_string());
+    let yarn = config.yarn.as_ref();
+    let template = yarn
+        .and_then(|yarn| yarn.url_template.clone())
+        .or_else(|| {
+            yarn.and_then(|yarn| yarn.mirror.clone())
+                .filter(|mirror| mirror.contains('{'))
+        });
+    if let Some(template) = template {
+        return template_url(&template, version, &archive_file, &path::archive_extension());
+    }
+    match yarn.and_then(|yarn| yarn.mirror.clone()) {
+        Some(ref mirror) if mirror.ends_with('/') => format!("{}{}", mirror, archive_file),
+        Some(mirror) => format!("{}/{}", mirror, archive_file),
+        None => format!("{}{}", PUBLIC_YARN_SERVER_ROOT, archive_file),
+    }
+}
+
 /// A provisioned Yarn distribution.
 pub struct YarnDistro {
     archive: Box<Archive>,
     version: Version,
+
+    /// For an archive that is still being downloaded with a known checksum (e.g. one
+    /// produced by a resolve plugin), or that is being streamed without being cached,
+    /// how to verify the download once it has been unpacked.
+    pending_checksum: Option<PendingChecksum>,
+
+    /// The lock on the cache file, held while the archive is downloaded into it.
+    download_lock: Option<FileLock>,
 }
 
 /// Check if the cached file is valid. It may have been corrupted or interrupted in the middle of
-/// downloading.
+/// downloading. A stamp recording that the file was complete saves reading it again.
 // ISSUE(#134) - verify checksum
 fn cache_is_valid(cache_file: &PathBuf) -> bool {
+    if let Some(valid) = stamp::check(cache_file, None) {
+        return valid;
+    }
     if cache_file.is_file() {
         if let Ok(file) = File::open(cache_file) {
-            match node_archive::load(file) {
-                Ok(_) => return true,
-                Err(_) => return false,
+            if node_archive::load(file).is_ok() {
+                let _ = stamp::write(cache_file, None);
+                return true;
             }
         }
     }
     false
 }
 
-impl Distro for YarnDistro {
-    /// Provision a distribution from the public Yarn distributor (`https://yarnpkg.com`).
-    fn public(version: Version) -> Fallible<Self> {
-        let archive_file = path::yarn_archive_file(&version.to_string());
-        let url = format!("{}{}", PUBLIC_YARN_SERVER_ROOT, archive_file);
-        YarnDistro::remote(version, &url)
-    }
-
-    /// Provision a distribution from a remote distributor.
-    fn remote(version: Version, url: &str) -> Fallible<Self> {
+impl YarnDistro {
+    /// Provision a distribution from a remote distributor, verifying the archive against
+    /// the expected checksum if one is given.
+    fn remote_checked(
+        version: Version,
+        url: &str,
+        expected_checksum: Option<String>,
+        config: &Config,
+    ) -> Fallible<Self> {
         let archive_file = path::yarn_archive_file(&version.to_string());
         let cache_file = path::yarn_cache_dir()?.join(&archive_file);
+        let download_lock = lock(&cache_file, &format!("downloading {}", archive_file))?;
 
-        if cache_is_valid(&cache_file) {
+        let valid = match expected_checksum {
+            Some(ref expected_checksum) => cache_matches(&cache_file, expected_checksum),
+            None => cache_is_valid(&cache_file),
+        };
+        if valid {
             return YarnDistro::cached(version, File::open(cache_file).unknown()?);
         }
 
+        http::ensure_online(url)?;
         ensure_containing_dir_exists(&cache_file)?;
+        stamp::remove(&cache_file);
+        let (archive, pending_checksum) = download(url, &cache_file, expected_checksum, config)
+            .with_context(DownloadError::for_version(version.to_string()))?;
         Ok(YarnDistro {
-            archive: node_archive::fetch(url, &cache_file)
-                .with_context(DownloadError::for_version(version.to_string()))?,
+            archive,
             version: version,
+            pending_checksum,
+            download_lock: Some(download_lock),
         })
     }
+}
+
+impl Distro for YarnDistro {
+    /// Provision a distribution from the public Yarn distributor (`https://yarnpkg.com`),
+    /// unless a `yarn.url-template` or `yarn.mirror` setting says where to download it
+    /// from instead.
+    fn public(version: Version, config: &Config) -> Fallible<Self> {
+        let url = public_url(&version, config);
+        YarnDistro::remote(version, &url, config)
+    }
+
+    /// Provision a distribution from a remote distributor.
+    fn remote(version: Version, url: &str, config: &Config) -> Fallible<Self> {
+        YarnDistro::remote_checked(version, url, None, config)
+    }
+
+    /// Provision a distribution from a remote distributor, verifying the archive against
+    /// the specified checksum.
+    fn remote_with_checksum(
+        version: Version,
+        url: &str,
+        checksum: &str,
+        config: &Config,
+    ) -> Fallible<Self> {
+        YarnDistro::remote_checked(version, url, Some(checksum.to_string()), config)
+    }
 
     /// Provision a distribution from the filesystem.
     fn cached(version: Version, file: File) -> Fallible<Self> {
         Ok(YarnDistro {
             archive: node_archive::load(file).unknown()?,
             version: version,
+            pending_checksum: None,
+            download_lock: None,
         })
     }
 
@@ -77,35 +154,78 @@ impl Distro for YarnDistro {
         &self.version
     }
 
-    /// Fetches this version of Yarn. (It is left to the responsibility of the `YarnCollection`
+    /// Produces a progress bar for fetching this distribution.
+    fn progress_bar(&self) -> ProgressBar {
+        progress_bar(
+            Action::Fetching,
+            &format!("yarn v{}", self.version),
+            self.archive
+                .uncompressed_size()
+                .unwrap_or(self.archive.compressed_size()),
+        )
+    }
+
+    /// Fetches this version of Yarn, reporting progress to the specified bar. (It is left to the responsibility of the `YarnCollection`
     /// to update its state after fetching succeeds.)
-    fn fetch(self, collection: &YarnCollection) -> Fallible<Fetched> {
+    fn fetch_with_progress(self, collection: &YarnCollection, bar: ProgressBar) -> Fallible<Fetched> {
         if collection.contains(&self.version) {
             return Ok(Fetched::Already(self.version));
         }
 
         let dest = path::yarn_versions_dir()?;
-        let bar = progress_bar(
-            Action::Fetching,
-            &format!("v{}", self.version),
-            self.archive
-                .uncompressed_size()
-                .unwrap_or(self.archive.compressed_size()),
-        );
-
-        self.archive
-            .unpack(&dest, &mut |_, read| {
-                bar.inc(read as u64);
-            })
-            .unknown()?;
+        create_dir_all(&dest).unknown()?;
 
+        // Unpack into a private staging directory on the same filesystem, so that an
+        // interrupted unpack never leaves a partial version directory behind, and
+        // concurrent fetches of the same version don't collide.
         let version_string = self.version.to_string();
-        rename(
-            dest.join(path::yarn_archive_root_dir(&version_string)),
-            path::yarn_version_dir(&version_string)?,
-        ).unknown()?;
+        let root_dir = path::yarn_archive_root_dir(&version_string);
+        let cache_file = path::yarn_cache_dir()?.join(path::yarn_archive_file(&version_string));
+        let staging = unpack_staged(self.archive, &dest, &root_dir, &cache_file, &bar)?;
+        if !verify_download(self.pending_checksum, &bar)? {
+            let _ = stamp::write(&cache_file, None);
+        }
+        move_into_place(
+            &staging.path().join(root_dir),
+            &path::user_yarn_version_dir(&version_string)?,
+        )?;
 
         bar.finish_and_clear();
         Ok(Fetched::Now(self.version))
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::public_url;
+    use config::Config;
+    use path;
+    use semver::Version;
+
+    fn url(config: &str) -> String {
+        let config: Config = config.parse().expect("Could not parse config");
+        public_url(&Version::parse("1.12.3").unwrap(), &config)
+    }
+
+    #[test]
+    fn test_public_url() {
+        let archive_file = path::yarn_archive_file("1.12.3");
+        assert_eq!(
+            url(""),
+            format!("https://github.com/notion-cli/yarn-releases/raw/master/dist/{}", archive_file)
+        );
+        assert_eq!(
+            url("[yarn]\nmirror = \"https://artifacts.corp/yarn\"\n"),
+            format!("https://artifacts.corp/yarn/{}", archive_file)
+        );
+        assert_eq!(
+            url("[yarn]\nmirror = \"https://artifacts.corp/yarn/v{version}/{filename}\"\n"),
+            format!("https://artifacts.corp/yarn/v1.12.3/{}", archive_file)
+        );
+        assert_eq!(
+            url("[yarn]\nmirror = \"https://artifacts.corp/yarn/\"\nurl-template = \"https://cdn.corp/{filename}\"\n"),
+            format!("https://cdn.corp/{}", archive_file)
+        );
+    }
+}
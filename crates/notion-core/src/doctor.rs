@@ -0,0 +1,399 @@
+//! Diagnoses problems with the Notion installation and the current environment, for
+//! `notion doctor`.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::Version;
+use tempfile::tempfile_in;
+
+use cache;
+use distro::node;
+use env as notion_env;
+use http;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use session::Session;
+use shadow::{self, ShimPath};
+
+/// The outcome of a check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Nothing is wrong.
+    Pass,
+    /// Something is amiss, but Notion still works.
+    Warn,
+    /// Something is broken.
+    Fail,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &Status::Pass => "pass",
+            &Status::Warn => "warn",
+            &Status::Fail => "fail",
+        })
+    }
+}
+
+/// The outcome of a check, what it found, and how to fix any problem it found.
+pub struct Diagnosis {
+    pub check: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Diagnosis {
+    fn pass(check: &'static str, detail: String) -> Diagnosis {
+        Diagnosis {
+            check,
+            status: Status::Pass,
+            detail,
+            fix: None,
+        }
+    }
+
+    fn warn(check: &'static str, detail: String, fix: String) -> Diagnosis {
+        Diagnosis {
+            check,
+            status: Status::Warn,
+            detail,
+            fix: Some(fix),
+        }
+    }
+
+    fn fail(check: &'static str, detail: String, fix: String) -> Diagnosis {
+        Diagnosis {
+            check,
+            status: Status::Fail,
+            detail,
+            fix: Some(fix),
+        }
+    }
+}
+
+/// Runs every check, in order. A check that can't be carried out at all fails with
+/// the error that stopped it.
+pub fn diagnose(session: &Session) -> Vec<Diagnosis> {
+    let checks: Vec<(&'static str, Fallible<Diagnosis>)> = vec![
+        ("shims", check_shims(env::var_os("PATH"))),
+        ("corepack", check_corepack(session, env::var_os("PATH"))),
+        ("catalog", check_catalog(session)),
+        ("cache", check_cache()),
+        ("permissions", check_permissions()),
+        ("network", check_network(session)),
+        ("project", check_project(session)),
+    ];
+    checks
+        .into_iter()
+        .map(|(check, result)| {
+            result.unwrap_or_else(|error| {
+                Diagnosis::fail(
+                    check,
+                    format!("the check could not be run: {}", error),
+                    "run `notion doctor` again with NOTION_DEV=1 for details".to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Checks that the shim directory is on the `PATH`, and that no other Node installation
+/// before it shadows the shims.
+fn check_shims(search_path: Option<OsString>) -> Fallible<Diagnosis> {
+    let shim_dir = path::shim_dir()?;
+    Ok(match shadow::check(search_path)? {
+        ShimPath::Missing => Diagnosis::fail(
+            "shims",
+            format!("the shim directory {} is not on the PATH", shim_dir.display()),
+            "run `notion setup --profile` and start a new shell".to_string(),
+        ),
+        ShimPath::Shadowed(shadowing) => Diagnosis::fail(
+            "shims",
+            format!(
+                "{} in {} comes before the shim directory on the PATH",
+                shadowing.tool,
+                shadowing.dir.display()
+            ),
+            shadowing.fix()?,
+        ),
+        ShimPath::First => Diagnosis::pass(
+            "shims",
+            format!("the shim directory {} is first on the PATH", shim_dir.display()),
+        ),
+    })
+}
+
+/// Checks that corepack's shims aren't on the `PATH` alongside Notion's, unless the
+/// package managers are left to corepack.
+fn check_corepack(session: &Session, search_path: Option<OsString>) -> Fallible<Diagnosis> {
+    if session.config()?.defer_to_corepack() {
+        return Ok(Diagnosis::pass(
+            "corepack",
+            "Yarn and pnpm are left to corepack".to_string(),
+        ));
+    }
+    let shims = shadow::corepack_shims(search_path)?;
+    Ok(match shims.first() {
+        Some(shim) => Diagnosis::warn(
+            "corepack",
+            format!(
+                "corepack's {} shim in {} is on the PATH alongside Notion's",
+                shim.tool,
+                shim.dir.display()
+            ),
+            shadow::COREPACK_FIX.to_string(),
+        ),
+        None => Diagnosis::pass(
+            "corepack",
+            "no corepack shims are on the PATH".to_string(),
+        ),
+    })
+}
+
+/// Lists the versions installed in a versions directory, ignoring the staging
+/// directories of unfinished fetches.
+fn installed_versions(versions_dir: &Path) -> Fallible<BTreeSet<Version>> {
+    let mut versions = BTreeSet::new();
+    if !versions_dir.is_dir() {
+        return Ok(versions);
+    }
+    for entry in fs::read_dir(versions_dir).unknown()? {
+        let entry = entry.unknown()?;
+        if let Ok(version) = Version::parse(&entry.file_name().to_string_lossy()) {
+            if entry.path().is_dir() {
+                versions.insert(version);
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// Checks that the catalog lists exactly the versions installed on disk.
+fn check_catalog(session: &Session) -> Fallible<Diagnosis> {
+    let catalog = session.catalog()?;
+    let tools: Vec<(&str, &BTreeSet<Version>, &BTreeSet<Version>, PathBuf)> = vec![
+        ("node", &catalog.node.versions, &catalog.node.system, path::node_versions_dir()?),
+        ("yarn", &catalog.yarn.versions, &catalog.yarn.system, path::yarn_versions_dir()?),
+        ("npm", &catalog.npm.versions, &catalog.npm.system, path::npm_versions_dir()?),
+        ("pnpm", &catalog.pnpm.versions, &catalog.pnpm.system, path::pnpm_versions_dir()?),
+    ];
+
+    let mut missing = vec![];
+    let mut unlisted = vec![];
+    for (tool, listed, system, versions_dir) in tools {
+        // The versions only the system install provides aren't in the user's directory.
+        let listed: BTreeSet<Version> = listed.difference(system).cloned().collect();
+        let installed = installed_versions(&versions_dir)?;
+        for version in listed.difference(&installed) {
+            missing.push(format!("{}@{}", tool, version));
+        }
+        for version in installed.difference(&listed) {
+            unlisted.push(versions_dir.join(version.to_string()));
+        }
+    }
+
+    if !missing.is_empty() {
+        return Ok(Diagnosis::fail(
+            "catalog",
+            format!(
+                "the catalog lists versions that aren't installed: {}",
+                missing.join(", ")
+            ),
+            format!("reinstall them with `notion fetch {}`", missing.join(" ")),
+        ));
+    }
+    if !unlisted.is_empty() {
+        return Ok(Diagnosis::warn(
+            "catalog",
+            format!(
+                "versions are installed that the catalog doesn't list: {}",
+                display_paths(&unlisted)
+            ),
+            "remove their directories, or fetch them again to list them".to_string(),
+        ));
+    }
+    Ok(Diagnosis::pass(
+        "catalog",
+        "the catalog matches the installed versions".to_string(),
+    ))
+}
+
+/// Checks for files left behind by interrupted operations: stamps of archives that
+/// are gone, and the staging directories of unfinished fetches.
+fn check_cache() -> Fallible<Diagnosis> {
+    let mut orphans = cache::orphaned_stamps()?;
+    for versions_dir in vec![
+        path::node_versions_dir()?,
+        path::yarn_versions_dir()?,
+        path::npm_versions_dir()?,
+        path::pnpm_versions_dir()?,
+    ] {
+        if !versions_dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&versions_dir).unknown()? {
+            let entry = entry.unknown()?;
+            if entry.file_name().to_string_lossy().starts_with(".tmp") {
+                orphans.push(entry.path());
+            }
+        }
+    }
+
+    if orphans.is_empty() {
+        Ok(Diagnosis::pass(
+            "cache",
+            "nothing was left behind by interrupted fetches".to_string(),
+        ))
+    } else {
+        Ok(Diagnosis::warn(
+            "cache",
+            format!(
+                "interrupted fetches left behind: {}",
+                display_paths(&orphans)
+            ),
+            "remove them, once no other notion process is running".to_string(),
+        ))
+    }
+}
+
+/// Checks that Notion can write to the directories it keeps its state in.
+fn check_permissions() -> Fallible<Diagnosis> {
+    let dirs = vec![path::versions_dir()?, path::cache_dir()?, path::shim_dir()?];
+    let unwritable: Vec<PathBuf> = dirs.into_iter()
+        .filter(|dir| dir.is_dir() && tempfile_in(dir).is_err())
+        .collect();
+
+    if unwritable.is_empty() {
+        Ok(Diagnosis::pass(
+            "permissions",
+            "the Notion directories are writable".to_string(),
+        ))
+    } else {
+        Ok(Diagnosis::fail(
+            "permissions",
+            format!("cannot write to {}", display_paths(&unwritable)),
+            "make sure you own the Notion directories (they may have been created by sudo)"
+                .to_string(),
+        ))
+    }
+}
+
+/// Checks that the Node distribution server (or the configured mirror) is reachable.
+fn check_network(session: &Session) -> Fallible<Diagnosis> {
+    let config = session.config()?;
+    let server = node::server_root(config);
+    if notion_env::offline() && http::file_url_path(&server).is_none() {
+        return Ok(Diagnosis::pass(
+            "network",
+            format!("skipped reaching {} in offline mode", server),
+        ));
+    }
+    if let Some(dir) = http::file_url_path(&server) {
+        return Ok(if dir.is_dir() {
+            Diagnosis::pass("network", format!("the mirror {} is present", dir.display()))
+        } else {
+            Diagnosis::fail(
+                "network",
+                format!("the mirror {} does not exist", dir.display()),
+                "check the `node.mirror` setting and NOTION_NODE_MIRROR".to_string(),
+            )
+        });
+    }
+
+    Ok(match http::get(&server, config) {
+        Ok(ref response) if response.status().is_success() => {
+            Diagnosis::pass("network", format!("{} is reachable", server))
+        }
+        Ok(response) => Diagnosis::fail(
+            "network",
+            format!("{} responded with {}", server, response.status()),
+            "check the `node.mirror` setting and NOTION_NODE_MIRROR".to_string(),
+        ),
+        Err(error) => Diagnosis::fail(
+            "network",
+            format!("could not reach {}: {}", server, error),
+            "check your network connection and the `[proxy]` settings".to_string(),
+        ),
+    })
+}
+
+/// Checks that a Node version can be selected for the current directory.
+fn check_project(session: &Session) -> Fallible<Diagnosis> {
+    Ok(match session.active_node() {
+        Ok(Some(active)) => Diagnosis::pass(
+            "project",
+            format!("node v{} is selected ({})", active.version, active.source),
+        ),
+        Ok(None) => Diagnosis::warn(
+            "project",
+            "no Node version is selected here, so the system's is used".to_string(),
+            "set a default with `notion install node`, or pin one with `notion pin node`"
+                .to_string(),
+        ),
+        Err(error) => Diagnosis::fail(
+            "project",
+            format!("a Node version cannot be selected here: {}", error),
+            "fix the toolchain in the project's package.json".to_string(),
+        ),
+    })
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{check_shims, installed_versions, Status};
+    use path;
+    use semver::Version;
+    use std::env;
+    use std::fs::{create_dir, File};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_shims() {
+        let shim_dir = path::shim_dir().unwrap();
+        let other = tempdir().unwrap();
+
+        let missing = env::join_paths(vec![other.path().to_path_buf()]).unwrap();
+        assert_eq!(check_shims(Some(missing)).unwrap().status, Status::Fail);
+
+        let first = env::join_paths(vec![shim_dir.clone(), other.path().to_path_buf()]).unwrap();
+        assert_eq!(check_shims(Some(first)).unwrap().status, Status::Pass);
+
+        let shim_name = path::shim_file("node").unwrap();
+        File::create(other.path().join(shim_name.file_name().unwrap())).unwrap();
+        let shadowed = env::join_paths(vec![other.path().to_path_buf(), shim_dir]).unwrap();
+        let diagnosis = check_shims(Some(shadowed)).unwrap();
+        assert_eq!(diagnosis.status, Status::Fail);
+        assert!(diagnosis.detail.starts_with("node in "));
+    }
+
+    #[test]
+    fn test_installed_versions() {
+        let dir = tempdir().unwrap();
+        create_dir(dir.path().join("10.8.0")).unwrap();
+        create_dir(dir.path().join(".tmpAbC123")).unwrap();
+        File::create(dir.path().join("8.9.4")).unwrap();
+
+        let versions = installed_versions(dir.path()).unwrap();
+        assert_eq!(
+            versions.into_iter().collect::<Vec<_>>(),
+            vec![Version::parse("10.8.0").unwrap()]
+        );
+        assert!(installed_versions(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
@@ -2,21 +2,339 @@
 //! its delegated executable.
 
 use std::env;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
+use notion_fail::Fallible;
 use path;
 
 pub(crate) fn shell_name() -> Option<String> {
     env::var_os("NOTION_SHELL").map(|s| s.to_string_lossy().into_owned())
 }
 
+/// Returns whether the specified environment variable is set to anything other than an
+/// empty string or `0`.
+fn flag(name: &str) -> bool {
+    flag_in(|var| env::var_os(var), name)
+}
+
+/// Returns whether the specified environment variable, as `var` reads it, is set to
+/// anything other than an empty string or `0`.
+fn flag_in<F>(var: F, name: &str) -> bool
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    match var(name) {
+        Some(ref value) => !value.is_empty() && value != "0",
+        None => false,
+    }
+}
+
+/// Returns whether Notion is in offline mode (the `NOTION_OFFLINE` environment
+/// variable is set to anything other than an empty string or `0`), in which case it
+/// only uses the local cache and catalog.
+pub fn offline() -> bool {
+    flag("NOTION_OFFLINE")
+}
+
+/// Returns whether the `NOTION_NO_IMPLICIT_FETCH` environment variable is set to
+/// anything other than an empty string or `0`, which forbids fetching missing tool
+/// versions on demand, like the `behavior.no-implicit-fetch` setting.
+pub fn no_implicit_fetch() -> bool {
+    flag("NOTION_NO_IMPLICIT_FETCH")
+}
+
+/// Returns the directory that the `NOTION_HOME` environment variable, if set to anything
+/// other than an empty string, moves Notion's files to (see the `path` module).
+pub fn notion_home() -> Option<PathBuf> {
+    env::var_os("NOTION_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the root of the read-only system install that the `NOTION_SYSTEM_HOME`
+/// environment variable, if set to anything other than an empty string, shares with
+/// every user (see the `path` module).
+pub fn system_home() -> Option<PathBuf> {
+    env::var_os("NOTION_SYSTEM_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns whether the `NOTION_XDG` environment variable is set to anything other than
+/// an empty string or `0`, which splits Notion's files across the XDG base directories
+/// on Linux.
+pub fn xdg() -> bool {
+    flag("NOTION_XDG")
+}
+
+/// Puts the current process (and any tools it launches) in offline mode.
+pub fn enable_offline() {
+    env::set_var("NOTION_OFFLINE", "1");
+}
+
+/// Returns whether Notion is in quiet mode (the `NOTION_QUIET` environment variable is
+/// set to anything other than an empty string or `0`), in which case it displays no
+/// progress.
+pub fn quiet() -> bool {
+    flag("NOTION_QUIET")
+}
+
+/// Puts the current process (and any tools it launches) in quiet mode.
+pub fn enable_quiet() {
+    env::set_var("NOTION_QUIET", "1");
+}
+
+/// Returns whether colored output is disabled, either by the `NO_COLOR` convention
+/// (https://no-color.org), which disables it whenever `NO_COLOR` is set to anything
+/// but an empty string, or by the `NOTION_NO_COLOR` environment variable being set to
+/// anything other than an empty string or `0`.
+pub fn no_color() -> bool {
+    let no_color = match env::var_os("NO_COLOR") {
+        Some(ref value) => !value.is_empty(),
+        None => false,
+    };
+    no_color || flag("NOTION_NO_COLOR")
+}
+
+/// Disables colored output in the current process (and any tools it launches).
+pub fn disable_color() {
+    env::set_var("NOTION_NO_COLOR", "1");
+}
+
+/// Returns whether the `NOTION_NO_CACHE` environment variable is set to anything other
+/// than an empty string or `0`, in which case resolve plugins are consulted afresh
+/// rather than their cached resolutions being used.
+pub fn no_cache() -> bool {
+    flag("NOTION_NO_CACHE")
+}
+
+/// Makes the current process (and any tools it launches) consult resolve plugins afresh.
+pub fn disable_cache() {
+    env::set_var("NOTION_NO_CACHE", "1");
+}
+
+/// Returns whether the `NOTION_STREAM` environment variable is set to anything other
+/// than an empty string or `0`, in which case downloaded archives are unpacked as they
+/// stream in, without being written to the cache, like the `cache.stream` setting.
+pub fn stream() -> bool {
+    flag("NOTION_STREAM")
+}
+
+/// Makes the current process (and any tools it launches) stream downloaded archives
+/// without caching them.
+pub fn enable_stream() {
+    env::set_var("NOTION_STREAM", "1");
+}
+
+/// Returns whether the `NOTION_PROFILE` environment variable is set to anything other
+/// than an empty string or `0`, in which case the time spent in each phase of a
+/// command is printed when it exits (see the `profile` module).
+pub fn profile() -> bool {
+    profile_in(|var| env::var_os(var))
+}
+
+/// Returns whether the `NOTION_PROFILE` environment variable, as `var` reads it, turns
+/// profiling on.
+pub(crate) fn profile_in<F>(var: F) -> bool
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    flag_in(var, "NOTION_PROFILE")
+}
+
+/// Makes the current process (and any tools it launches) profile its phases.
+pub fn enable_profile() {
+    env::set_var("NOTION_PROFILE", "1");
+}
+
+/// Returns whether the `NOTION_BACKTRACE` environment variable is set to anything other
+/// than an empty string or `0`, in which case errors are reported with their cause
+/// chains and backtraces.
+pub fn backtrace() -> bool {
+    flag("NOTION_BACKTRACE")
+}
+
+/// Makes the current process capture a backtrace for every error it creates from now
+/// on, which the `failure` library only does if `RUST_BACKTRACE` is set.
+pub fn capture_backtraces() {
+    if env::var_os("RUST_BACKTRACE").is_none() {
+        env::set_var("RUST_BACKTRACE", "1");
+    }
+}
+
+/// Returns the file that events are appended to as JSON, one per line (the
+/// `NOTION_EVENTS_FILE` environment variable), if any.
+pub(crate) fn events_file() -> Option<PathBuf> {
+    events_file_in(|var| env::var_os(var))
+}
+
+/// Returns the events file that the `NOTION_EVENTS_FILE` environment variable, as `var`
+/// reads it, names, if any.
+pub(crate) fn events_file_in<F>(var: F) -> Option<PathBuf>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    match var("NOTION_EVENTS_FILE") {
+        Some(ref value) if !value.is_empty() => Some(PathBuf::from(value)),
+        _ => None,
+    }
+}
+
+/// Makes the current process (and any tools it launches) append events to the specified
+/// file. A relative path is resolved against the current directory first, so that tools
+/// running in other directories append to the same file.
+pub fn set_events_file(file: &Path) {
+    let file = match env::current_dir() {
+        Ok(dir) => dir.join(file),
+        Err(_) => file.to_path_buf(),
+    };
+    env::set_var("NOTION_EVENTS_FILE", file);
+}
+
+/// Returns whether the `NOTION_HTTP_DEBUG` environment variable is set to anything other
+/// than an empty string or `0`, in which case every network request is traced to stderr.
+pub(crate) fn http_debug() -> bool {
+    flag("NOTION_HTTP_DEBUG")
+}
+
+/// Returns the level diagnostic messages are logged at (the `NOTION_LOG` environment
+/// variable), if any.
+pub(crate) fn log_level() -> Option<String> {
+    match env::var("NOTION_LOG") {
+        Ok(ref value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Makes the current process (and any tools it launches) log diagnostic messages at the
+/// specified level.
+pub(crate) fn set_log_level(level: &str) {
+    env::set_var("NOTION_LOG", level);
+}
+
+/// Returns the architecture that overrides the host's when choosing which Node
+/// distributions to fetch (the `NOTION_ARCH` environment variable), if any.
+pub(crate) fn arch() -> Option<String> {
+    match env::var("NOTION_ARCH") {
+        Ok(ref value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Returns whether the `NOTION_NO_UPDATE_CHECK` environment variable is set, which
+/// turns off the checks for a new release of Notion, like the `updates.check` setting.
+pub fn no_update_check() -> bool {
+    flag("NOTION_NO_UPDATE_CHECK")
+}
+
+/// Returns the URL that describes the latest release of Notion in place of the public
+/// release channel (the `NOTION_UPDATE_URL` environment variable), if any.
+pub(crate) fn update_url() -> Option<String> {
+    match env::var("NOTION_UPDATE_URL") {
+        Ok(ref value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the token to authenticate requests to the GitHub API with from the
+/// `GITHUB_TOKEN` environment variable, which CI services commonly provide, if set.
+pub(crate) fn github_token() -> Option<String> {
+    match env::var("GITHUB_TOKEN") {
+        Ok(ref value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the Node mirror that overrides the `node.mirror` setting, if any.
+pub(crate) fn node_mirror() -> Option<String> {
+    env::var_os("NOTION_NODE_MIRROR").map(|s| s.to_string_lossy().into_owned())
+}
+
 pub fn postscript_path() -> Option<PathBuf> {
     env::var_os("NOTION_POSTSCRIPT")
         .as_ref()
         .map(|ref s| Path::new(s).to_path_buf())
 }
 
+/// The tools that `tool_env` builds an environment for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolKind {
+    Node,
+    Yarn,
+    Npm,
+    Pnpm,
+    /// Any other command, like those of `notion exec` and `notion run`, which finds
+    /// every selected tool first on its `PATH`, without the shims being removed.
+    Toolchain,
+}
+
+/// The environment a tool runs in under the selected toolchain: the variables to
+/// set on top of the current environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolEnv {
+    /// The `PATH`, which finds the selected versions first.
+    pub path: OsString,
+    /// The executable of the selected Node, if any, which is exported as `NODE` (as
+    /// npm does for the scripts it runs).
+    pub node: Option<PathBuf>,
+}
+
+impl ToolEnv {
+    /// The environment for running a tool with the system's Node: the current one,
+    /// with the shims removed from the `PATH`.
+    pub fn system() -> Self {
+        ToolEnv {
+            path: path_for_system_node(),
+            node: None,
+        }
+    }
+
+    /// The variables to set, in order.
+    pub fn vars(&self) -> Vec<(&'static str, &OsStr)> {
+        let mut vars = vec![("PATH", self.path.as_os_str())];
+        if let Some(ref node) = self.node {
+            vars.push(("NODE", node.as_os_str()));
+        }
+        vars
+    }
+
+    /// Sets the variables on a command.
+    pub fn apply(&self, command: &mut Command) {
+        command.envs(self.vars());
+    }
+}
+
+/// Builds the environment for running a tool with the specified versions of Node,
+/// Yarn, and npm selected. A tool that needs Node runs with the system's if none
+/// is selected.
+pub fn tool_env(
+    tool: ToolKind,
+    node_version: Option<&str>,
+    yarn_version: Option<&str>,
+    npm_version: Option<&str>,
+) -> Fallible<ToolEnv> {
+    let path = match (tool, node_version, yarn_version, npm_version) {
+        (ToolKind::Toolchain, ..) => path_for_toolchain(node_version, yarn_version, npm_version),
+        (ToolKind::Yarn, _, Some(yarn), _) => path_for_installed_yarn(yarn, node_version),
+        (ToolKind::Npm, Some(node), _, Some(npm)) => path_for_installed_npm(npm, node),
+        (_, Some(node), _, _) => path_for_installed_node(node),
+        (_, None, _, _) => return Ok(ToolEnv::system()),
+    };
+    let node = match node_version {
+        Some(node_version) => Some(node_executable(node_version)?),
+        None => None,
+    };
+    Ok(ToolEnv { path, node })
+}
+
+/// The Node executable of an installed version of Node.
+pub fn node_executable(version: &str) -> Fallible<PathBuf> {
+    let name = if cfg!(windows) { "node.exe" } else { "node" };
+    Ok(path::node_version_bin_dir(version)?.join(name))
+}
+
 /// Produces a modified version of the current `PATH` environment variable that
 /// will find Node.js executables in the installation directory for the given
 /// version of Node instead of in the Notion shim directory.
@@ -31,6 +349,62 @@ pub fn path_for_installed_node(version: &str) -> OsString {
     env::join_paths(path_vec.iter()).unwrap()
 }
 
+/// Produces a modified version of the current `PATH` environment variable that
+/// will find the Yarn executables (`yarn` and `yarnpkg`) in the installation
+/// directory for the given version of Yarn, and Node in the installation
+/// directory for the given version of Node, if one is selected.
+pub fn path_for_installed_yarn(yarn_version: &str, node_version: Option<&str>) -> OsString {
+    let current = env::var_os("PATH").unwrap_or(OsString::new());
+    let shim_dir = &path::shim_dir().unwrap();
+    let split = env::split_paths(&current).filter(|s| s != shim_dir);
+    let mut path_vec: Vec<PathBuf> = Vec::new();
+    path_vec.push(path::yarn_version_bin_dir(yarn_version).unwrap());
+    if let Some(node_version) = node_version {
+        path_vec.push(path::node_version_bin_dir(node_version).unwrap());
+    }
+    path_vec.extend(split);
+    env::join_paths(path_vec.iter()).unwrap()
+}
+
+/// Produces a modified version of the current `PATH` environment variable that
+/// will find the npm executables (`npm` and `npx`) in the installation directory
+/// for the given version of npm, ahead of the copy bundled with the given version
+/// of Node.
+pub fn path_for_installed_npm(npm_version: &str, node_version: &str) -> OsString {
+    let current = env::var_os("PATH").unwrap_or(OsString::new());
+    let shim_dir = &path::shim_dir().unwrap();
+    let split = env::split_paths(&current).filter(|s| s != shim_dir);
+    let mut path_vec: Vec<PathBuf> = Vec::new();
+    path_vec.push(path::npm_version_bin_dir(npm_version).unwrap());
+    path_vec.push(path::node_version_bin_dir(node_version).unwrap());
+    path_vec.extend(split);
+    env::join_paths(path_vec.iter()).unwrap()
+}
+
+/// Produces a modified version of the current `PATH` environment variable that
+/// finds each of the specified versions of Yarn, npm, and Node in its installation
+/// directory, ahead of everything else. The Notion shims are left in place, so that
+/// other executables still resolve the way they do in the current directory.
+pub fn path_for_toolchain(
+    node_version: Option<&str>,
+    yarn_version: Option<&str>,
+    npm_version: Option<&str>,
+) -> OsString {
+    let current = env::var_os("PATH").unwrap_or(OsString::new());
+    let mut path_vec: Vec<PathBuf> = Vec::new();
+    if let Some(yarn_version) = yarn_version {
+        path_vec.push(path::yarn_version_bin_dir(yarn_version).unwrap());
+    }
+    if let Some(npm_version) = npm_version {
+        path_vec.push(path::npm_version_bin_dir(npm_version).unwrap());
+    }
+    if let Some(node_version) = node_version {
+        path_vec.push(path::node_version_bin_dir(node_version).unwrap());
+    }
+    path_vec.extend(env::split_paths(&current));
+    env::join_paths(path_vec.iter()).unwrap()
+}
+
 /// Produces a modified version of the current `PATH` environment variable that
 /// removes the Notion shims and binaries, to use for running system node and
 /// executables.
@@ -42,6 +416,39 @@ pub fn path_for_system_node() -> OsString {
     env::join_paths(split).unwrap()
 }
 
+/// Produces a modified version of the current `PATH` environment variable that
+/// puts the Notion shims back in front, undoing `path_for_system_node`.
+pub fn path_for_shims() -> OsString {
+    let current = env::var_os("PATH").unwrap_or(OsString::new());
+    let shim_dir = path::shim_dir().unwrap();
+    let split = env::split_paths(&current).filter(|s| s != &shim_dir);
+    let mut path_vec: Vec<PathBuf> = Vec::new();
+    path_vec.push(shim_dir.clone());
+    path_vec.extend(split);
+    env::join_paths(path_vec.iter()).unwrap()
+}
+
+/// Produces a modified version of the current `PATH` environment variable that
+/// finds Node.js executables in the installation directory for the given version
+/// of Node (or through the shims, if there is none), replacing any version of Node
+/// that was put there before. Returns `None` if the `PATH` already does so, so that
+/// a shell hook can leave the environment alone.
+pub fn path_for_hook(version: Option<&str>) -> Option<OsString> {
+    let current = env::var_os("PATH").unwrap_or(OsString::new());
+    let versions_dir = path::node_versions_dir().unwrap();
+    let (managed, split): (Vec<PathBuf>, Vec<PathBuf>) =
+        env::split_paths(&current).partition(|s| s.starts_with(&versions_dir));
+    let mut path_vec: Vec<PathBuf> = Vec::new();
+    if let Some(version) = version {
+        path_vec.push(path::node_version_bin_dir(version).unwrap());
+    }
+    if managed == path_vec {
+        return None;
+    }
+    path_vec.extend(split);
+    Some(env::join_paths(path_vec.iter()).unwrap())
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -52,12 +459,79 @@ pub mod tests {
     #[cfg(windows)]
     use winfolder;
 
+    #[test]
+    fn test_tool_env() {
+        let node = tool_env(ToolKind::Node, Some("10.8.0"), Some("1.9.2"), None).unwrap();
+        assert_eq!(node.path, path_for_installed_node("10.8.0"));
+        assert_eq!(node.node, Some(node_executable("10.8.0").unwrap()));
+        assert_eq!(node.vars().len(), 2);
+
+        let yarn = tool_env(ToolKind::Yarn, Some("10.8.0"), Some("1.9.2"), None).unwrap();
+        assert_eq!(yarn.path, path_for_installed_yarn("1.9.2", Some("10.8.0")));
+
+        let npm = tool_env(ToolKind::Npm, Some("10.8.0"), None, Some("6.4.1")).unwrap();
+        assert_eq!(npm.path, path_for_installed_npm("6.4.1", "10.8.0"));
+
+        let toolchain = tool_env(ToolKind::Toolchain, None, Some("1.9.2"), None).unwrap();
+        assert_eq!(toolchain.path, path_for_toolchain(None, Some("1.9.2"), None));
+        assert_eq!(toolchain.node, None);
+
+        let system = tool_env(ToolKind::Pnpm, None, None, None).unwrap();
+        assert_eq!(system, ToolEnv::system());
+        assert_eq!(system.vars().len(), 1);
+    }
+
     #[test]
     fn test_shell_name() {
         env::set_var("NOTION_SHELL", "bash");
         assert_eq!(shell_name().unwrap(), "bash".to_string());
     }
 
+    #[test]
+    fn test_offline() {
+        env::set_var("NOTION_OFFLINE", "0");
+        assert_eq!(offline(), false);
+        enable_offline();
+        assert_eq!(offline(), true);
+        env::remove_var("NOTION_OFFLINE");
+        assert_eq!(offline(), false);
+    }
+
+    #[test]
+    fn test_quiet() {
+        env::set_var("NOTION_QUIET", "");
+        assert_eq!(quiet(), false);
+        enable_quiet();
+        assert_eq!(quiet(), true);
+        env::remove_var("NOTION_QUIET");
+        assert_eq!(quiet(), false);
+    }
+
+    #[test]
+    fn test_no_color() {
+        env::remove_var("NO_COLOR");
+        env::set_var("NOTION_NO_COLOR", "0");
+        assert_eq!(no_color(), false);
+        env::set_var("NO_COLOR", "1");
+        assert_eq!(no_color(), true);
+        env::remove_var("NO_COLOR");
+        disable_color();
+        assert_eq!(no_color(), true);
+        env::remove_var("NOTION_NO_COLOR");
+        assert_eq!(no_color(), false);
+    }
+
+    #[test]
+    fn test_node_mirror() {
+        env::set_var("NOTION_NODE_MIRROR", "https://mirror.example.com/node/");
+        assert_eq!(
+            node_mirror().unwrap(),
+            "https://mirror.example.com/node/".to_string()
+        );
+        env::remove_var("NOTION_NODE_MIRROR");
+        assert_eq!(node_mirror(), None);
+    }
+
     #[test]
     fn test_postscript_path() {
         env::set_var("NOTION_POSTSCRIPT", "/some/path");
@@ -127,6 +601,68 @@ pub mod tests {
         );
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_path_for_installed_yarn() {
+        let home = env::home_dir().expect("Could not get home directory");
+        env::set_var("PATH", "/usr/bin:/blah:/doesnt/matter/bin");
+
+        let mut expected_yarn_bin = PathBuf::from(&home);
+        expected_yarn_bin.push(".notion");
+        expected_yarn_bin.push("versions");
+        expected_yarn_bin.push("yarn");
+        expected_yarn_bin.push("1.7.0");
+        expected_yarn_bin.push("bin");
+
+        let mut expected_node_bin = PathBuf::from(&home);
+        expected_node_bin.push(".notion");
+        expected_node_bin.push("versions");
+        expected_node_bin.push("node");
+        expected_node_bin.push("8.9.4");
+        expected_node_bin.push("bin");
+
+        let mut expected_path = String::from("");
+        expected_path.push_str(expected_yarn_bin.as_path().to_str().unwrap());
+        expected_path.push_str(":");
+        expected_path.push_str(expected_node_bin.as_path().to_str().unwrap());
+        expected_path.push_str(":/usr/bin:/blah:/doesnt/matter/bin");
+
+        assert_eq!(
+            path_for_installed_yarn("1.7.0", Some("8.9.4"))
+                .into_string()
+                .unwrap(),
+            expected_path
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_path_for_toolchain() {
+        let home = env::home_dir().expect("Could not get home directory");
+        env::set_var("PATH", "/usr/bin:/blah:/doesnt/matter/bin");
+
+        let versions = home.join(".notion").join("versions");
+        let expected_yarn_bin = versions.join("yarn").join("1.7.0").join("bin");
+        let expected_node_bin = versions.join("node").join("8.9.4").join("bin");
+
+        let mut expected_path = String::from("");
+        expected_path.push_str(expected_yarn_bin.as_path().to_str().unwrap());
+        expected_path.push_str(":");
+        expected_path.push_str(expected_node_bin.as_path().to_str().unwrap());
+        expected_path.push_str(":/usr/bin:/blah:/doesnt/matter/bin");
+
+        assert_eq!(
+            path_for_toolchain(Some("8.9.4"), Some("1.7.0"), None)
+                .into_string()
+                .unwrap(),
+            expected_path
+        );
+        assert_eq!(
+            path_for_toolchain(None, None, None).into_string().unwrap(),
+            "/usr/bin:/blah:/doesnt/matter/bin"
+        );
+    }
+
     #[test]
     #[cfg(unix)]
     fn test_path_for_system_node() {
@@ -182,4 +718,72 @@ pub mod tests {
 
         assert_eq!(path_for_system_node().into_string().unwrap(), expected_path);
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_path_for_shims() {
+        let mut pathbufs: Vec<PathBuf> = Vec::new();
+
+        let home = env::home_dir().expect("Could not get home directory");
+        let mut shim_dir = PathBuf::from(&home);
+        shim_dir.push(".notion");
+        shim_dir.push("bin");
+
+        pathbufs.push(PathBuf::from("/usr/bin"));
+        pathbufs.push(shim_dir.clone());
+        pathbufs.push(PathBuf::from("/bin"));
+
+        let path_with_shim = env::join_paths(pathbufs.iter())
+            .unwrap()
+            .into_string()
+            .expect("Could not create path containing shim dir");
+
+        env::set_var("PATH", path_with_shim);
+
+        let expected_path = format!("{}:/usr/bin:/bin", shim_dir.display());
+
+        assert_eq!(path_for_shims().into_string().unwrap(), expected_path);
+
+        env::set_var("PATH", "/usr/bin:/bin");
+
+        assert_eq!(path_for_shims().into_string().unwrap(), expected_path);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_path_for_hook() {
+        let home = env::home_dir().expect("Could not get home directory");
+        let notion_home = home.join(".notion");
+        let node_bin = |version: &str| {
+            notion_home
+                .join("versions")
+                .join("node")
+                .join(version)
+                .join("bin")
+                .display()
+                .to_string()
+        };
+        let shim_dir = notion_home.join("bin").display().to_string();
+
+        env::set_var("PATH", format!("{}:/usr/bin:/bin", shim_dir));
+        assert_eq!(path_for_hook(None), None);
+        assert_eq!(
+            path_for_hook(Some("8.9.4")).unwrap().into_string().unwrap(),
+            format!("{}:{}:/usr/bin:/bin", node_bin("8.9.4"), shim_dir)
+        );
+
+        env::set_var(
+            "PATH",
+            format!("{}:{}:/usr/bin:/bin", node_bin("8.9.4"), shim_dir),
+        );
+        assert_eq!(path_for_hook(Some("8.9.4")), None);
+        assert_eq!(
+            path_for_hook(Some("10.1.0")).unwrap().into_string().unwrap(),
+            format!("{}:{}:/usr/bin:/bin", node_bin("10.1.0"), shim_dir)
+        );
+        assert_eq!(
+            path_for_hook(None).unwrap().into_string().unwrap(),
+            format!("{}:/usr/bin:/bin", shim_dir)
+        );
+    }
 }
@@ -1,14 +1,27 @@
 //! Events for the sessions in executables and shims and everything
+//!
+//! Besides being published to the events plugin, every event is appended as a line of
+//! JSON to the events file (the `--events-file` option or the `NOTION_EVENTS_FILE`
+//! environment variable), if there is one, so that CI systems can post-process what
+//! Notion did. The events file also records what happens below the level of commands:
+//! each tool version resolved, snapshots of the progress of each fetch, and each fetch
+//! completed.
 
 extern crate os_info;
 
 use std::env;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use env as notion_env;
 use monitor::LazyMonitor;
 use notion_fail::{ExitCode, Fallible, NotionError};
 use plugin::Publish;
+use serde_json;
 use session::ActivityKind;
+use telemetry;
 
 // the Event data that is serialized to JSON and sent the plugin
 #[derive(Serialize)]
@@ -42,6 +55,20 @@ enum EventKind {
     ToolEnd {
         exit_code: i32,
     },
+    Resolved {
+        tool: String,
+        version: String,
+    },
+    Progress {
+        file: String,
+        unpacked: u64,
+        total: u64,
+    },
+    Fetched {
+        tool: String,
+        version: String,
+        duration: u64,
+    },
 }
 
 impl EventKind {
@@ -54,6 +81,83 @@ impl EventKind {
     }
 }
 
+/// Appends an event to the specified events file as a line of JSON.
+fn append_to(file: &Path, event: &Event) -> io::Result<()> {
+    let mut line = serde_json::to_string(event)
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    line.push('\n');
+    // Each event is written in a single write to a file opened for appending, so that
+    // the events of concurrent processes (such as shims) never interleave.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?
+        .write_all(line.as_bytes())
+}
+
+/// Appends an event to the events file, if there is one. Events are a side channel, so
+/// failing to record one never fails the command.
+fn record(event: &Event) {
+    if let Some(file) = notion_env::events_file() {
+        let _ = append_to(&file, event);
+    }
+}
+
+/// Returns whether there is an events file to record events in.
+pub(crate) fn recording() -> bool {
+    notion_env::events_file().is_some()
+}
+
+/// Records an event that happens outside of the session's event log (which only the
+/// session can add to) in the events file.
+fn record_kind(name: &str, event: EventKind) {
+    if recording() {
+        record(&Event {
+            timestamp: unix_timestamp(),
+            name: name.to_string(),
+            event,
+        });
+    }
+}
+
+/// Records that a version requirement for a tool was resolved to a version.
+pub(crate) fn record_resolved(tool: &str, version: &str) {
+    record_kind(
+        "resolve",
+        EventKind::Resolved {
+            tool: tool.to_string(),
+            version: version.to_string(),
+        },
+    );
+}
+
+/// Records a snapshot of the progress of unpacking an archive as it is fetched, in
+/// bytes of the archive's uncompressed size (or of its compressed size, if the former
+/// is unknown).
+pub(crate) fn record_progress(file: &str, unpacked: u64, total: u64) {
+    record_kind(
+        "fetch",
+        EventKind::Progress {
+            file: file.to_string(),
+            unpacked,
+            total,
+        },
+    );
+}
+
+/// Records that a version of a tool was fetched, and how long it took in milliseconds.
+pub(crate) fn record_fetched(tool: &str, version: &str, duration: Duration) {
+    telemetry::note_fetched(tool, version);
+    record_kind(
+        "fetch",
+        EventKind::Fetched {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            duration: duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64,
+        },
+    );
+}
+
 // returns the current number of milliseconds since the epoch
 fn unix_timestamp() -> u64 {
     let start = SystemTime::now();
@@ -125,6 +229,7 @@ impl EventLog {
 
     fn add_event(&mut self, event_kind: EventKind, activity_kind: ActivityKind) {
         let event = event_kind.into_event(activity_kind);
+        record(&event);
         self.events.push(event);
     }
 
@@ -142,10 +247,35 @@ impl EventLog {
 #[cfg(test)]
 pub mod tests {
 
-    use super::EventLog;
+    use super::{append_to, EventKind, EventLog};
     use notion_fail::{ExitCode, FailExt};
+    use serde_json::{self, Value};
     use session::ActivityKind;
+    use std::fs;
     use std::io;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_append_to() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let file = dir.path().join("events.jsonl");
+        let start = EventKind::Start.into_event(ActivityKind::Fetch);
+        let resolved = EventKind::Resolved {
+            tool: "node".to_string(),
+            version: "10.8.0".to_string(),
+        }.into_event(ActivityKind::Fetch);
+        append_to(&file, &start).unwrap();
+        append_to(&file, &resolved).unwrap();
+
+        let src = fs::read_to_string(&file).unwrap();
+        let lines: Vec<Value> = src.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["name"], "fetch");
+        assert_eq!(lines[0]["event"], "start");
+        assert_eq!(lines[1]["event"]["resolved"]["version"], "10.8.0");
+    }
 
     #[test]
     fn test_adding_events() {
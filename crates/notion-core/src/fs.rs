@@ -1,12 +1,37 @@
 //! Provides utilities for operating on the filesystem.
 
-use std::fs::{self, create_dir_all, File};
-use std::io::{self, ErrorKind};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
 use std::path::{Path, PathBuf};
 
+use fs2::FileExt;
 use notion_fail::{ExitCode, FailExt, Fallible, NotionFail, ResultExt};
+use path::extended;
+use style::display_waiting;
+
+// Every operation here goes through the extended-length form of its paths (see
+// `path::extended`), so that the files of deeply nested packages can be unpacked, moved
+// and removed on Windows. The rest of Notion uses these in place of their `std::fs`
+// counterparts wherever the paths can run that deep.
+
+/// Creates a directory and any of its missing parents.
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs::create_dir_all(extended(path.as_ref()))
+}
+
+/// Removes a directory, after removing everything in it.
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    fs::remove_dir_all(extended(path.as_ref()))
+}
+
+/// Renames a file or directory, replacing the destination if it is a file.
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    fs::rename(extended(from.as_ref()), extended(to.as_ref()))
+}
 
 pub fn touch(path: &Path) -> Fallible<File> {
+    let path: &Path = &extended(path);
     if !path.is_file() {
         let basedir = path.parent().unwrap();
         create_dir_all(basedir).unknown()?;
@@ -40,7 +65,7 @@ pub(crate) struct PathInternalError;
 /// This creates the parent directory of the input path, assuming the input path is a file.
 pub fn ensure_containing_dir_exists<P: AsRef<Path>>(path: &P) -> Fallible<()> {
     if let Some(dir) = path.as_ref().parent() {
-        fs::create_dir_all(dir)
+        create_dir_all(dir)
             .with_context(CreateDirError::for_dir(dir.to_string_lossy().to_string()))
     } else {
         // this was called for a file with no parent directory
@@ -50,7 +75,7 @@ pub fn ensure_containing_dir_exists<P: AsRef<Path>>(path: &P) -> Fallible<()> {
 
 /// Reads a file, if it exists.
 pub fn read_file_opt(path: &PathBuf) -> io::Result<Option<String>> {
-    let result: io::Result<String> = fs::read_to_string(path);
+    let result: io::Result<String> = fs::read_to_string(extended(path));
 
     match result {
         Ok(string) => Ok(Some(string)),
@@ -60,3 +85,116 @@ pub fn read_file_opt(path: &PathBuf) -> io::Result<Option<String>> {
         },
     }
 }
+
+/// Writes the contents of a file atomically, so that a crash never leaves it partially
+/// written: the contents go to a temporary file next to it, which is flushed to disk
+/// and then renamed over it. A file that already exists keeps its permissions.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Fallible<()> {
+    let path = path.as_ref();
+    ensure_containing_dir_exists(&path)?;
+
+    // The temporary file goes in the same directory, so that moving it is a rename
+    // within one filesystem.
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.tmp", ::std::process::id()));
+    let temp = path.with_file_name(temp_name);
+
+    let result = write_temp(&temp, path, contents.as_ref()).and_then(|()| rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(extended(&temp));
+    }
+    result.unknown()
+}
+
+/// Writes and flushes the temporary file of `write_atomic`, with the permissions of
+/// the file it will replace.
+fn write_temp(temp: &Path, path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(extended(temp))?;
+    file.write_all(contents)?;
+    if let Ok(metadata) = fs::metadata(extended(path)) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+/// An advisory lock on a file, held by this process until it is dropped. Locks are
+/// taken on a separate `.lock` file next to the locked file, so that the locked file
+/// itself can still be replaced atomically.
+pub(crate) struct FileLock {
+    file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Returns the lock file for the specified file.
+fn lock_file_for(path: &Path) -> PathBuf {
+    let mut lock_file = OsString::from(path.as_os_str());
+    lock_file.push(".lock");
+    PathBuf::from(lock_file)
+}
+
+/// Takes an exclusive lock on the specified file, waiting for any other Notion process
+/// that holds it (with a notice about what it is waiting for) to release it first.
+pub(crate) fn lock(path: &Path, activity: &str) -> Fallible<FileLock> {
+    let lock_file = lock_file_for(path);
+    ensure_containing_dir_exists(&lock_file)?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(extended(&lock_file))
+        .unknown()?;
+    if file.try_lock_exclusive().is_err() {
+        display_waiting(activity);
+        file.lock_exclusive().unknown()?;
+    }
+    Ok(FileLock { file })
+}
+
+/// Returns whether the specified file is a lock file taken by `lock`.
+pub(crate) fn is_lock_file(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension == "lock")
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::write_atomic;
+    use std::fs::{read_dir, read_to_string};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("nested").join("catalog.toml");
+        write_atomic(&file, "[node]\n").unwrap();
+        write_atomic(&file, "[yarn]\n").unwrap();
+        assert_eq!(read_to_string(&file).unwrap(), "[yarn]\n");
+
+        // No temporary file is left behind.
+        assert_eq!(read_dir(file.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_permissions() {
+        use std::fs::{metadata, set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("package.json");
+        write_atomic(&file, "{}\n").unwrap();
+        set_permissions(&file, Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&file, "{ \"a\": 1 }\n").unwrap();
+        assert_eq!(metadata(&file).unwrap().permissions().mode() & 0o777, 0o640);
+    }
+}
@@ -0,0 +1,321 @@
+//! Provides the means of importing the Node versions installed by other version
+//! managers (nvm, n, and asdf), for `notion import`, so that they needn't be
+//! downloaded again.
+
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+use semver::Version;
+use tempfile::tempdir_in;
+
+use catalog::Catalog;
+use distro::move_into_place;
+use log;
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use path;
+
+/// Thrown when the version manager to import from isn't one Notion knows.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "cannot import from '{}' - expected nvm, n, asdf, or a file written by `notion export`",
+    name
+)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownSourceError {
+    name: String,
+}
+
+/// Thrown when the home directory, which the version managers install into, is unknown.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot find the home directory to import from")]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct NoHomeDirError;
+
+/// Thrown when an installed version could not be copied into Notion's versions directory.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not import {}\n{}", dir, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct ImportError {
+    dir: String,
+    error: String,
+}
+
+impl ImportError {
+    fn for_dir(dir: &Path) -> impl FnOnce(&io::Error) -> ImportError {
+        let dir = dir.to_string_lossy().to_string();
+        move |error| ImportError {
+            dir,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// A version manager whose installed Node versions can be imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// nvm, which installs into `$NVM_DIR/versions/node/v<version>`.
+    Nvm,
+    /// n, which installs into `$N_PREFIX/n/versions/node/<version>`.
+    N,
+    /// asdf, which installs into `$ASDF_DATA_DIR/installs/nodejs/<version>`.
+    Asdf,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &Source::Nvm => "nvm",
+            &Source::N => "n",
+            &Source::Asdf => "asdf",
+        })
+    }
+}
+
+impl FromStr for Source {
+    type Err = NotionError;
+
+    fn from_str(s: &str) -> Fallible<Source> {
+        match s {
+            "nvm" => Ok(Source::Nvm),
+            "n" => Ok(Source::N),
+            "asdf" => Ok(Source::Asdf),
+            _ => throw!(UnknownSourceError {
+                name: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl Source {
+    /// Returns the directory the version manager installs Node versions into.
+    pub fn versions_dir(&self) -> Fallible<PathBuf> {
+        let from_env = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+        let home = || env::home_dir().ok_or(NoHomeDirError);
+        Ok(match *self {
+            Source::Nvm => {
+                let nvm_dir = match from_env("NVM_DIR") {
+                    Some(dir) => PathBuf::from(dir),
+                    None => home()?.join(".nvm"),
+                };
+                nvm_dir.join("versions").join("node")
+            }
+            Source::N => {
+                let prefix = match from_env("N_PREFIX") {
+                    Some(dir) => PathBuf::from(dir),
+                    None => PathBuf::from("/usr/local"),
+                };
+                prefix.join("n").join("versions").join("node")
+            }
+            Source::Asdf => {
+                let data_dir = match from_env("ASDF_DATA_DIR") {
+                    Some(dir) => PathBuf::from(dir),
+                    None => home()?.join(".asdf"),
+                };
+                data_dir.join("installs").join("nodejs")
+            }
+        })
+    }
+}
+
+/// A Node version installed by another version manager.
+pub struct Installed {
+    pub version: Version,
+    pub dir: PathBuf,
+}
+
+/// Returns the Node executable in an installed version's directory.
+fn node_file(dir: &Path) -> PathBuf {
+    if cfg!(windows) {
+        dir.join("node.exe")
+    } else {
+        dir.join("bin").join("node")
+    }
+}
+
+/// Parses the name of a version directory, with or without a leading `v`.
+fn parse_dir_name(name: &str) -> Option<Version> {
+    let name = if name.starts_with('v') { &name[1..] } else { name };
+    Version::parse(name).ok()
+}
+
+/// Lists the Node versions installed in a version manager's versions directory, oldest
+/// first. Directories that aren't named for a version, or whose Node executable is
+/// missing, are left out.
+pub fn discover(versions_dir: &Path) -> Fallible<Vec<Installed>> {
+    let mut installed = vec![];
+    if !versions_dir.is_dir() {
+        return Ok(installed);
+    }
+    for entry in fs::read_dir(versions_dir).unknown()? {
+        let entry = entry.unknown()?;
+        let version = match parse_dir_name(&entry.file_name().to_string_lossy()) {
+            Some(version) => version,
+            None => continue,
+        };
+        let dir = entry.path();
+        if node_file(&dir).is_file() {
+            installed.push(Installed { version, dir });
+        } else {
+            log::debug(|| format!("skipping {}, which has no Node executable", dir.display()));
+        }
+    }
+    installed.sort_by(|a, b| a.version.cmp(&b.version));
+    Ok(installed)
+}
+
+/// Returns whether an installed version's Node executable runs and reports the version
+/// its directory is named for.
+pub fn validate(installed: &Installed) -> bool {
+    match Command::new(node_file(&installed.dir)).arg("--version").output() {
+        Ok(output) => {
+            output.status.success()
+                && String::from_utf8_lossy(&output.stdout).trim()
+                    == format!("v{}", installed.version)
+        }
+        Err(_) => false,
+    }
+}
+
+/// How the files of an imported version got into Notion's versions directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transfer {
+    /// Every file was hard-linked, so the import takes no extra space.
+    Linked,
+    /// Some or all of the files were copied (as across filesystems).
+    Copied,
+}
+
+/// Imports an installed Node version into Notion's versions directory and lists it in
+/// the catalog. Files are hard-linked where possible, unless `copy` is set, and copied
+/// otherwise; the import is staged next to the version directory and moved into place
+/// once complete, like a fetch.
+pub fn import(catalog: &mut Catalog, installed: &Installed, copy: bool) -> Fallible<Transfer> {
+    let versions_dir = path::node_versions_dir()?;
+    fs::create_dir_all(&versions_dir).with_context(ImportError::for_dir(&versions_dir))?;
+
+    let staging = tempdir_in(&versions_dir).with_context(ImportError::for_dir(&versions_dir))?;
+    log::debug(|| {
+        format!(
+            "importing {} into {}",
+            installed.dir.display(),
+            staging.path().display()
+        )
+    });
+    let mut linked = !copy;
+    transfer_tree(&installed.dir, staging.path(), &mut linked)
+        .with_context(ImportError::for_dir(&installed.dir))?;
+
+    let version_dir = path::user_node_version_dir(&installed.version.to_string())?;
+    move_into_place(staging.path(), &version_dir)?;
+
+    catalog.node.versions.insert(installed.version.clone());
+    catalog.save()?;
+    Ok(if linked {
+        Transfer::Linked
+    } else {
+        Transfer::Copied
+    })
+}
+
+/// Recreates a directory tree, hard-linking each file while `linked` is set and copying
+/// it (and every file after it) once a link fails. Symbolic links are recreated as they
+/// are, since the links in a Node installation (such as `bin/npm`) are relative.
+fn transfer_tree(src: &Path, dst: &Path, linked: &mut bool) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            transfer_tree(&from, &to, linked)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&from, &to)?;
+        } else {
+            if *linked && fs::hard_link(&from, &to).is_ok() {
+                continue;
+            }
+            *linked = false;
+            fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    ::std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{discover, parse_dir_name, transfer_tree, Source};
+    use semver::Version;
+    use std::fs::{create_dir_all, read_to_string, File};
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("nvm".parse::<Source>().unwrap(), Source::Nvm);
+        assert_eq!("asdf".parse::<Source>().unwrap(), Source::Asdf);
+        assert!("volta".parse::<Source>().is_err());
+        assert_eq!(Source::N.to_string(), "n");
+    }
+
+    #[test]
+    fn test_parse_dir_name() {
+        assert_eq!(parse_dir_name("v10.15.3"), Some(Version::parse("10.15.3").unwrap()));
+        assert_eq!(parse_dir_name("8.9.4"), Some(Version::parse("8.9.4").unwrap()));
+        assert_eq!(parse_dir_name("system"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover() {
+        let dir = tempdir().unwrap();
+        create_dir_all(dir.path().join("v10.15.3").join("bin")).unwrap();
+        File::create(dir.path().join("v10.15.3").join("bin").join("node")).unwrap();
+        create_dir_all(dir.path().join("v8.9.4").join("bin")).unwrap();
+        File::create(dir.path().join("v8.9.4").join("bin").join("node")).unwrap();
+        create_dir_all(dir.path().join("v6.0.0")).unwrap();
+        create_dir_all(dir.path().join("cache")).unwrap();
+
+        let versions: Vec<String> = discover(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|installed| installed.version.to_string())
+            .collect();
+        assert_eq!(versions, vec!["8.9.4", "10.15.3"]);
+        assert!(discover(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_transfer_tree() {
+        let src = tempdir().unwrap();
+        create_dir_all(src.path().join("lib")).unwrap();
+        File::create(src.path().join("lib").join("index.js"))
+            .unwrap()
+            .write_all(b"module.exports = 1;")
+            .unwrap();
+
+        let dst = tempdir().unwrap();
+        let mut linked = true;
+        transfer_tree(src.path(), &dst.path().join("10.15.3"), &mut linked).unwrap();
+        assert_eq!(
+            read_to_string(dst.path().join("10.15.3").join("lib").join("index.js")).unwrap(),
+            "module.exports = 1;"
+        );
+    }
+}
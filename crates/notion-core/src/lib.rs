@@ -4,14 +4,22 @@
 
 extern crate cmdline_words_parser;
 extern crate console;
+extern crate crossbeam;
 extern crate detect_indent;
+extern crate fs2;
+extern crate glob;
 extern crate indicatif;
 extern crate lazycell;
+#[macro_use]
+extern crate lazy_static;
 extern crate node_archive;
 extern crate readext;
 extern crate reqwest;
 extern crate semver;
+extern crate sha1;
+extern crate sha2;
 extern crate serde_json;
+extern crate serde_yaml;
 extern crate tempfile;
 extern crate term_size;
 extern crate toml;
@@ -21,24 +29,55 @@ extern crate serde;
 extern crate serde_derive;
 
 extern crate winfolder;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate winapi;
+#[cfg(windows)]
+extern crate winreg;
+#[cfg(windows)]
+extern crate verbatim;
 
+pub mod api;
+mod cache;
 pub mod catalog;
 pub mod config;
+pub mod dedupe;
 mod distro;
+pub mod doctor;
 pub mod env;
 mod event;
 pub(crate) mod fs;
+mod http;
+pub mod import;
+pub mod lifecycle;
+pub mod log;
 pub mod manifest;
 pub mod monitor;
+pub mod npmrc;
+pub mod outdated;
+pub mod package;
 pub mod path;
+pub mod pin_file;
+pub mod plan;
+pub mod platform;
 mod plugin;
+pub mod profile;
 pub mod project;
+pub mod script;
 pub mod session;
+pub mod shadow;
 pub mod shell;
 pub mod shim;
+mod signal;
+pub mod state;
 pub mod style;
+pub mod telemetry;
 pub mod tool;
+pub mod trace;
+pub mod update;
 pub mod version;
+pub mod which;
 
 extern crate failure;
 #[macro_use]
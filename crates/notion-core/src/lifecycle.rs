@@ -0,0 +1,218 @@
+//! Provides the lifecycle hooks: commands configured in the `[lifecycle]` section of the
+//! user's configuration, which Notion runs before and after it fetches, pins, or
+//! uninstalls a tool, e.g. to record installs in an audit log or send notifications.
+//!
+//! Each hook receives a JSON object describing the event on its standard input: the
+//! name of the event (e.g. `pre-fetch`), the tool, and the version, along with the
+//! duration of the operation in milliseconds and its outcome (`success` or `failure`)
+//! for the hooks that run after it. A `pre-` hook that fails aborts the operation,
+//! while a `post-` hook that fails only produces a warning.
+
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use cmdline_words_parser::StrExt;
+use config::LifecycleConfig;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+use serde_json;
+use style::display_warning;
+
+/// An operation that lifecycle hooks run around.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Fetch,
+    Pin,
+    Uninstall,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s = match *self {
+            Action::Fetch => "fetch",
+            Action::Pin => "pin",
+            Action::Uninstall => "uninstall",
+        };
+        f.write_str(s)
+    }
+}
+
+impl Action {
+    /// Selects the command to run before this operation, if any.
+    fn pre_hook<'a>(&self, hooks: &'a LifecycleConfig) -> Option<&'a String> {
+        match *self {
+            Action::Fetch => hooks.pre_fetch.as_ref(),
+            Action::Pin => hooks.pre_pin.as_ref(),
+            Action::Uninstall => hooks.pre_uninstall.as_ref(),
+        }
+    }
+
+    /// Selects the command to run after this operation, if any.
+    fn post_hook<'a>(&self, hooks: &'a LifecycleConfig) -> Option<&'a String> {
+        match *self {
+            Action::Fetch => hooks.post_fetch.as_ref(),
+            Action::Pin => hooks.post_pin.as_ref(),
+            Action::Uninstall => hooks.post_uninstall.as_ref(),
+        }
+    }
+}
+
+/// Thrown when a lifecycle hook could not be run or exited unsuccessfully.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The {} hook `{}` failed\n{}", event, command, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct LifecycleHookError {
+    event: String,
+    command: String,
+    error: String,
+}
+
+/// The description of an event that is sent to a lifecycle hook.
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: String,
+    tool: &'a str,
+    version: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Runs the specified operation on a version of a tool between the lifecycle hooks
+/// configured for it in `hooks` (the `lifecycle` settings of the configuration), if any.
+pub fn around<T, F>(
+    hooks: Option<&LifecycleConfig>,
+    action: Action,
+    tool: &str,
+    version: &str,
+    operation: F,
+) -> Fallible<T>
+where
+    F: FnOnce() -> Fallible<T>,
+{
+    if let Some(command) = hooks.and_then(|hooks| action.pre_hook(hooks)) {
+        let payload = Payload {
+            event: format!("pre-{}", action),
+            tool,
+            version,
+            duration: None,
+            outcome: None,
+            error: None,
+        };
+        run(command, &payload)?;
+    }
+
+    let start = Instant::now();
+    let result = operation();
+
+    if let Some(command) = hooks.and_then(|hooks| action.post_hook(hooks)) {
+        let elapsed = start.elapsed();
+        let payload = Payload {
+            event: format!("post-{}", action),
+            tool,
+            version,
+            duration: Some(elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64),
+            outcome: Some(if result.is_ok() { "success" } else { "failure" }),
+            error: result.as_ref().err().map(|error| error.to_string()),
+        };
+        if let Err(error) = run(command, &payload) {
+            display_warning(&error.to_string());
+        }
+    }
+
+    result
+}
+
+/// Runs a lifecycle hook, writing the description of the event to its standard input.
+fn run(command: &str, payload: &Payload) -> Fallible<()> {
+    let fail = |error: String| LifecycleHookError {
+        event: payload.event.clone(),
+        command: command.trim().to_string(),
+        error,
+    };
+
+    let mut trimmed = command.trim().to_string();
+    let (cmd, args) = {
+        let mut words = trimmed.parse_cmdline_words();
+        let cmd = match words.next() {
+            Some(word) => word.to_string(),
+            None => throw!(fail("the command is empty".to_string())),
+        };
+        let args: Vec<OsString> = words
+            .map(|s| {
+                let mut os = OsString::new();
+                os.push(s);
+                os
+            })
+            .collect();
+        (cmd, args)
+    };
+
+    let mut child = match Command::new(&cmd)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(error) => throw!(fail(error.to_string())),
+    };
+    // A hook that doesn't need the description may exit without reading it, so a
+    // failure to write it isn't an error in itself.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = serde_json::to_writer(&mut stdin, payload);
+    }
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(error) => throw!(fail(error.to_string())),
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        throw!(fail(format!("{} {}", output.status, stderr).trim().to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{around, Action, LifecycleHookError};
+    use config::Config;
+    use notion_fail::Fallible;
+
+    #[test]
+    fn test_around_without_hooks() {
+        let config: Config = "".parse().expect("Could not parse config");
+        let result: Fallible<u32> = around(config.lifecycle.as_ref(), Action::Fetch, "node", "10.8.0", || Ok(42));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_around_failing_hooks() {
+        // a failing pre- hook aborts the operation
+        let config: Config = "[lifecycle]\npre-pin = \"false\"\n"
+            .parse()
+            .expect("Could not parse config");
+        let mut ran = false;
+        let error = around(config.lifecycle.as_ref(), Action::Pin, "node", "10.8.0", || {
+            ran = true;
+            Ok(())
+        }).unwrap_err();
+        assert!(!ran);
+        assert!(error.downcast_ref::<LifecycleHookError>().is_some());
+
+        // but a failing post- hook doesn't fail it
+        let config: Config = "[lifecycle]\npre-pin = \"true\"\npost-pin = \"false\"\n"
+            .parse()
+            .expect("Could not parse config");
+        let result = around(config.lifecycle.as_ref(), Action::Pin, "node", "10.8.0", || Ok(42));
+        assert_eq!(result.unwrap(), 42);
+    }
+}
@@ -0,0 +1,105 @@
+//! Provides leveled diagnostic logging to stderr, for seeing what Notion does while
+//! debugging it: cache hits, resolution steps, and file operations.
+//!
+//! Logging is enabled by the `NOTION_LOG` environment variable (`info` or `debug`),
+//! which the `-v` and `-vv` flags set, so that the tools Notion launches log as well.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use env;
+use style::style;
+
+/// How much to log. Each level includes the messages of the levels before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// What Notion fetches and where from.
+    Info,
+    /// Every step along the way: cache hits, resolution steps, and file operations.
+    Debug,
+}
+
+impl Level {
+    /// Returns the level enabled by the specified number of `-v` flags, if any.
+    pub fn from_verbosity(count: usize) -> Option<Level> {
+        match count {
+            0 => None,
+            1 => Some(Level::Info),
+            _ => Some(Level::Debug),
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            &Level::Info => "info",
+            &Level::Debug => "debug",
+        })
+    }
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Level, ()> {
+        match &s.trim().to_lowercase()[..] {
+            "info" | "1" => Ok(Level::Info),
+            "debug" | "2" => Ok(Level::Debug),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns the level logging is enabled at, if it is.
+pub fn level() -> Option<Level> {
+    env::log_level().and_then(|level| level.parse().ok())
+}
+
+/// Enables logging at the specified level in the current process (and any tools it
+/// launches).
+pub fn set_level(level: Level) {
+    env::set_log_level(&level.to_string());
+}
+
+/// Returns whether messages at the specified level are logged.
+pub fn enabled(level: Level) -> bool {
+    self::level().map_or(false, |enabled| enabled >= level)
+}
+
+fn log<F: FnOnce() -> String>(level: Level, message: F) {
+    if enabled(level) {
+        eprintln!("{} {}", style(format!("{}:", level)).dim(), message());
+    }
+}
+
+/// Logs what Notion fetches and where from. The message is only produced if it is logged.
+pub fn info<F: FnOnce() -> String>(message: F) {
+    log(Level::Info, message);
+}
+
+/// Logs a step along the way. The message is only produced if it is logged.
+pub fn debug<F: FnOnce() -> String>(message: F) {
+    log(Level::Debug, message);
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use log::Level;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(Level::from_verbosity(0), None);
+        assert_eq!(Level::from_verbosity(1), Some(Level::Info));
+        assert_eq!(Level::from_verbosity(3), Some(Level::Debug));
+
+        assert_eq!("info".parse(), Ok(Level::Info));
+        assert_eq!(" DEBUG\n".parse(), Ok(Level::Debug));
+        assert_eq!("2".parse(), Ok(Level::Debug));
+        assert_eq!("trace".parse::<Level>(), Err(()));
+
+        assert!(Level::Debug > Level::Info);
+        assert_eq!(Level::Debug.to_string(), "debug");
+    }
+}
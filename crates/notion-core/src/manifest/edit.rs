@@ -0,0 +1,484 @@
+//! Provides surgical edits to the text of a `package.json` file, so that changing one
+//! key leaves the rest of the document byte-for-byte identical.
+
+use std::ops::Range;
+
+use serde::Serialize;
+use serde_json;
+
+/// A key-value entry of a JSON object, recorded as byte offsets into the source text.
+struct Entry {
+    key: String,
+    /// The offset of the opening quote of the key.
+    start: usize,
+    /// The span of the entry's value.
+    value: Range<usize>,
+}
+
+/// The entries of a JSON object, along with the offsets of its braces.
+struct Object {
+    open: usize,
+    close: usize,
+    entries: Vec<Entry>,
+}
+
+impl Object {
+    fn find(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.key == key)
+    }
+
+    /// The span of text to delete in order to remove the entry at `index`, including
+    /// the comma and whitespace that separate it from its neighbors.
+    fn removal_span(&self, index: usize) -> Range<usize> {
+        let entries = &self.entries;
+        if entries.len() == 1 {
+            self.open + 1..self.close
+        } else if index + 1 < entries.len() {
+            entries[index].start..entries[index + 1].start
+        } else {
+            entries[index - 1].value.end..entries[index].value.end
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' | b'\r' | b'\n' => i += 1,
+            _ => break,
+        }
+    }
+    i
+}
+
+/// Skips a string literal starting at `i`, returning the offset just past its
+/// closing quote.
+fn skip_string(bytes: &[u8], i: usize) -> Option<usize> {
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b'"' => return Some(j + 1),
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// Skips a JSON value starting at `i`, returning the offset just past its end.
+fn skip_value(bytes: &[u8], i: usize) -> Option<usize> {
+    match *bytes.get(i)? {
+        b'"' => skip_string(bytes, i),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut j = i;
+            while j < bytes.len() {
+                match bytes[j] {
+                    b'"' => {
+                        j = skip_string(bytes, j)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(j + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                j += 1;
+            }
+            None
+        }
+        _ => {
+            let mut j = i;
+            while j < bytes.len() {
+                match bytes[j] {
+                    b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n' => break,
+                    _ => j += 1,
+                }
+            }
+            Some(j)
+        }
+    }
+}
+
+/// Scans the object starting at (or after whitespace following) offset `pos`.
+fn scan_object(text: &str, pos: usize) -> Option<Object> {
+    let bytes = text.as_bytes();
+    let mut i = skip_whitespace(bytes, pos);
+    if bytes.get(i) != Some(&b'{') {
+        return None;
+    }
+    let open = i;
+    let mut entries = Vec::new();
+    i += 1;
+
+    loop {
+        i = skip_whitespace(bytes, i);
+        match *bytes.get(i)? {
+            b'}' => break,
+            b',' => {
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let start = i;
+        let key_end = skip_string(bytes, start)?;
+        let key: String = serde_json::from_str(&text[start..key_end]).ok()?;
+
+        i = skip_whitespace(bytes, key_end);
+        if bytes.get(i) != Some(&b':') {
+            return None;
+        }
+        let value_start = skip_whitespace(bytes, i + 1);
+        let value_end = skip_value(bytes, value_start)?;
+
+        entries.push(Entry {
+            key: key,
+            start: start,
+            value: value_start..value_end,
+        });
+        i = value_end;
+    }
+
+    Some(Object {
+        open: open,
+        close: i,
+        entries: entries,
+    })
+}
+
+/// Scans the top-level object of the document, skipping a leading byte order mark.
+fn scan_document(text: &str) -> Option<Object> {
+    let start = if text.starts_with('\u{feff}') { 3 } else { 0 };
+    scan_object(text, start)
+}
+
+/// The line ending used by the document.
+fn newline(text: &str) -> &'static str {
+    if text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// Pretty-prints `value` as it should appear nested in the document, where `indent` is
+/// the indentation of the entry that holds it.
+fn render(value: &serde_json::Value, indent: &str, newline: &str) -> Option<String> {
+    let mut buf = Vec::new();
+    {
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+        value.serialize(&mut ser).ok()?;
+    }
+    let rendered = String::from_utf8(buf).ok()?;
+    Some(rendered.replace('\n', &format!("{}{}", newline, indent)))
+}
+
+fn splice(text: &str, span: Range<usize>, replacement: &str) -> String {
+    format!("{}{}{}", &text[..span.start], replacement, &text[span.end..])
+}
+
+/// Sets the top-level `key` of the document to `value`, appending the key to the end of
+/// the object if it isn't present yet. Produces `None` if the document isn't an object.
+pub(crate) fn set_key(
+    text: &str,
+    key: &str,
+    value: &serde_json::Value,
+    indent: &str,
+) -> Option<String> {
+    set_nested_key(text, &[key], value, indent)
+}
+
+/// Sets the entry at `path` of the document to `value`, where `path` lists the keys
+/// leading to the entry from the top-level object. Missing keys are appended to the end
+/// of their objects, and a key on the way that doesn't hold an object is replaced with
+/// one. Produces `None` if the document isn't an object.
+pub(crate) fn set_nested_key(
+    text: &str,
+    path: &[&str],
+    value: &serde_json::Value,
+    indent: &str,
+) -> Option<String> {
+    let object = scan_document(text)?;
+    set_in(text, object, path, value, indent, 1)
+}
+
+fn set_in(
+    text: &str,
+    object: Object,
+    path: &[&str],
+    value: &serde_json::Value,
+    indent: &str,
+    depth: usize,
+) -> Option<String> {
+    let (key, rest) = path.split_first()?;
+    let newline = newline(text);
+
+    if let Some(index) = object.find(key) {
+        let span = object.entries[index].value.clone();
+        if !rest.is_empty() {
+            if let Some(nested) = scan_object(text, span.start) {
+                return set_in(text, nested, rest, value, indent, depth + 1);
+            }
+        } else if serde_json::from_str::<serde_json::Value>(&text[span.clone()]).ok().as_ref()
+            == Some(value)
+        {
+            // the entry already holds the value, so leave it as it is written, even where
+            // that differs from how it would be rendered
+            return Some(text.to_string());
+        }
+        let rendered = render(&nest(rest, value), &indent.repeat(depth), newline)?;
+        return Some(splice(text, span, &rendered));
+    }
+
+    let rendered = render(&nest(rest, value), &indent.repeat(depth), newline)?;
+    let key = serde_json::to_string(key).ok()?;
+    Some(match object.entries.last() {
+        Some(last) => splice(
+            text,
+            last.value.end..last.value.end,
+            &format!(",{}{}{}: {}", newline, indent.repeat(depth), key, rendered),
+        ),
+        None => splice(
+            text,
+            object.open + 1..object.close,
+            &format!(
+                "{}{}{}: {}{}{}",
+                newline,
+                indent.repeat(depth),
+                key,
+                rendered,
+                newline,
+                indent.repeat(depth - 1)
+            ),
+        ),
+    })
+}
+
+/// Wraps `value` in an object for each key of `path`, innermost last.
+fn nest(path: &[&str], value: &serde_json::Value) -> serde_json::Value {
+    path.iter().rev().fold(value.clone(), |value, key| {
+        let mut object = serde_json::Map::new();
+        object.insert(key.to_string(), value);
+        serde_json::Value::Object(object)
+    })
+}
+
+/// Removes the entry at `path` from the document, where `path` lists the keys leading to
+/// the entry from the top-level object. Removing the last remaining key of a nested object
+/// removes the nested object as well. Produces `None` if there is no such entry.
+pub(crate) fn remove_key(text: &str, path: &[&str]) -> Option<String> {
+    let object = scan_document(text)?;
+    remove_from(text, object, path)
+}
+
+fn remove_from(text: &str, object: Object, path: &[&str]) -> Option<String> {
+    let (key, rest) = path.split_first()?;
+    let index = object.find(key)?;
+
+    if rest.is_empty() {
+        return Some(splice(text, object.removal_span(index), ""));
+    }
+
+    let nested = scan_object(text, object.entries[index].value.start)?;
+    if nested.entries.len() == 1 && rest.len() == 1 && nested.entries[0].key == rest[0] {
+        return Some(splice(text, object.removal_span(index), ""));
+    }
+    remove_from(text, nested, rest)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    const PACKAGE: &'static str = "{
+  \"name\": \"example\",
+  \"version\": \"1.0.0\",
+  \"toolchain\": {
+    \"node\": \"8.9.4\",
+    \"yarn\": \"1.2.0\"
+  },
+  \"license\": \"MIT\"
+}
+";
+
+    fn toolchain(node: &str) -> serde_json::Value {
+        serde_json::from_str(&format!("{{\"node\": \"{}\"}}", node)).unwrap()
+    }
+
+    #[test]
+    fn test_set_existing_key() {
+        assert_eq!(
+            set_key(PACKAGE, "toolchain", &toolchain("10.0.0"), "  ").unwrap(),
+            "{
+  \"name\": \"example\",
+  \"version\": \"1.0.0\",
+  \"toolchain\": {
+    \"node\": \"10.0.0\"
+  },
+  \"license\": \"MIT\"
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_set_new_key() {
+        let package = "{\n\t\"name\": \"example\",\n\t\"z\": 1\n}";
+        assert_eq!(
+            set_key(package, "toolchain", &toolchain("10.0.0"), "\t").unwrap(),
+            "{\n\t\"name\": \"example\",\n\t\"z\": 1,\n\t\"toolchain\": {\n\t\t\"node\": \"10.0.0\"\n\t}\n}"
+        );
+        assert_eq!(
+            set_key("{}\n", "toolchain", &toolchain("10.0.0"), "  ").unwrap(),
+            "{\n  \"toolchain\": {\n    \"node\": \"10.0.0\"\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_set_key_preserves_other_bytes() {
+        let package = "{ \"name\" :\"caf\\u00e9\",\n    \"toolchain\"  :  {\"node\": \"8.9.4\"},\"x\": 1.50 }";
+        assert_eq!(
+            set_key(package, "toolchain", &toolchain("10.0.0"), "  ").unwrap(),
+            package.replace(
+                "{\"node\": \"8.9.4\"}",
+                "{\n    \"node\": \"10.0.0\"\n  }"
+            )
+        );
+        assert_eq!(
+            set_key("{\"name\": \"caf\\u00e9\" }", "toolchain", &toolchain("10.0.0"), "  ")
+                .unwrap(),
+            "{\"name\": \"caf\\u00e9\",\n  \"toolchain\": {\n    \"node\": \"10.0.0\"\n  } }"
+        );
+    }
+
+    #[test]
+    fn test_set_same_value() {
+        let package = "{\"toolchain\": {\"node\":\"10.0.0\"}}";
+        assert_eq!(
+            set_key(package, "toolchain", &toolchain("10.0.0"), "  ").unwrap(),
+            package
+        );
+    }
+
+    #[test]
+    fn test_set_key_preserves_crlf() {
+        let package = PACKAGE.replace('\n', "\r\n");
+        assert_eq!(
+            set_key(&package, "toolchain", &toolchain("10.0.0"), "  ").unwrap(),
+            "{\r\n  \"name\": \"example\",\r\n  \"version\": \"1.0.0\",\r\n  \"toolchain\": {\r\n    \"node\": \"10.0.0\"\r\n  },\r\n  \"license\": \"MIT\"\r\n}\r\n"
+        );
+    }
+
+    #[test]
+    fn test_set_nested_key() {
+        let range = serde_json::Value::String("^10.0.0".to_string());
+        assert_eq!(
+            set_nested_key(PACKAGE, &["toolchain", "node"], &range, "  ").unwrap(),
+            PACKAGE.replace("\"8.9.4\"", "\"^10.0.0\"")
+        );
+        assert_eq!(
+            set_nested_key(PACKAGE, &["engines", "node"], &range, "  ").unwrap(),
+            PACKAGE.replace(
+                "\"MIT\"\n",
+                "\"MIT\",\n  \"engines\": {\n    \"node\": \"^10.0.0\"\n  }\n"
+            )
+        );
+        assert_eq!(
+            set_nested_key("{\n  \"engines\": {}\n}", &["engines", "node"], &range, "  ")
+                .unwrap(),
+            "{\n  \"engines\": {\n    \"node\": \"^10.0.0\"\n  }\n}"
+        );
+        assert_eq!(
+            set_nested_key(
+                "{\n  \"engines\": {\n    \"npm\": \"6\"\n  }\n}",
+                &["engines", "node"],
+                &range,
+                "  "
+            ).unwrap(),
+            "{\n  \"engines\": {\n    \"npm\": \"6\",\n    \"node\": \"^10.0.0\"\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn test_remove_key() {
+        assert_eq!(
+            remove_key(PACKAGE, &["toolchain"]).unwrap(),
+            "{
+  \"name\": \"example\",
+  \"version\": \"1.0.0\",
+  \"license\": \"MIT\"
+}
+"
+        );
+        assert_eq!(
+            remove_key(PACKAGE, &["license"]).unwrap(),
+            "{
+  \"name\": \"example\",
+  \"version\": \"1.0.0\",
+  \"toolchain\": {
+    \"node\": \"8.9.4\",
+    \"yarn\": \"1.2.0\"
+  }
+}
+"
+        );
+        assert_eq!(remove_key("{ \"toolchain\": {} }", &["toolchain"]).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_remove_nested_key() {
+        assert_eq!(
+            remove_key(PACKAGE, &["toolchain", "yarn"]).unwrap(),
+            "{
+  \"name\": \"example\",
+  \"version\": \"1.0.0\",
+  \"toolchain\": {
+    \"node\": \"8.9.4\"
+  },
+  \"license\": \"MIT\"
+}
+"
+        );
+        assert_eq!(
+            remove_key(PACKAGE, &["toolchain", "node"]).unwrap(),
+            "{
+  \"name\": \"example\",
+  \"version\": \"1.0.0\",
+  \"toolchain\": {
+    \"yarn\": \"1.2.0\"
+  },
+  \"license\": \"MIT\"
+}
+"
+        );
+        let package = "{\"toolchain\": {\"node\": \"8.9.4\"}, \"name\": \"x\"}";
+        assert_eq!(
+            remove_key(package, &["toolchain", "node"]).unwrap(),
+            "{\"name\": \"x\"}"
+        );
+    }
+
+    #[test]
+    fn test_remove_missing_key() {
+        assert!(remove_key(PACKAGE, &["engines"]).is_none());
+        assert!(remove_key(PACKAGE, &["toolchain", "npm"]).is_none());
+        assert!(remove_key(PACKAGE, &["name", "npm"]).is_none());
+    }
+
+    #[test]
+    fn test_keys_containing_braces() {
+        let package = "{\"scripts\": {\"a\": \"echo \\\"}\\\"\"}, \"toolchain\": {\"node\": \"1.0.0\"}}";
+        assert_eq!(
+            remove_key(package, &["toolchain"]).unwrap(),
+            "{\"scripts\": {\"a\": \"echo \\\"}\\\"\"}}"
+        );
+    }
+}
@@ -5,14 +5,28 @@ use std::fs::File;
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
+use config::ProjectHooks;
 use detect_indent;
 use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use plan::Plan;
 use semver::Version;
-use serde::Serialize;
 use serde_json;
+use version::VersionSpec;
 
+mod edit;
 pub(crate) mod serial;
 
+/// The key of package.json that pins the toolchain, unless the `toolchain-key` setting
+/// of the `[project]` section of the configuration names another.
+pub const TOOLCHAIN_KEY: &'static str = "toolchain";
+
+/// The tools a toolchain pins.
+const TOOLS: &[&str] = &["node", "yarn", "npm", "pnpm"];
+
+fn is_default_key(toolchain_key: &[String]) -> bool {
+    toolchain_key.len() == 1 && toolchain_key[0] == TOOLCHAIN_KEY
+}
+
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "Could not read package info: {}", error)]
 #[notion_fail(code = "FileSystemError")]
@@ -28,6 +42,69 @@ impl PackageReadError {
     }
 }
 
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse {} at line {}, column {}: {}\n\n{}", file, line, column,
+       error, snippet)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct PackageParseError {
+    pub(crate) file: String,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) error: String,
+    pub(crate) snippet: String,
+}
+
+impl PackageParseError {
+    pub(crate) fn new(file: &Path, contents: &str, error: &serde_json::Error) -> Self {
+        let line = error.line();
+        let column = error.column();
+
+        // serde_json appends the position to its messages, but we report it separately
+        let message = error.to_string();
+        let suffix = format!(" at line {} column {}", line, column);
+        let message = if message.ends_with(&suffix) {
+            message[..message.len() - suffix.len()].to_string()
+        } else {
+            message
+        };
+
+        PackageParseError {
+            file: file.display().to_string(),
+            line: line,
+            column: column,
+            error: message,
+            snippet: snippet(contents, line, column),
+        }
+    }
+}
+
+/// Thrown when package.json is valid JSON, but some section of it isn't what Notion
+/// expects, and the position of the problem isn't known.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct PackageContentError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+/// Renders the offending line of a file, with a caret under the specified (1-based) column.
+fn snippet(contents: &str, line: usize, column: usize) -> String {
+    let text = contents
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or("")
+        .trim_right_matches('\r');
+    let gutter = line.to_string();
+    format!(
+        "{} | {}\n{} | {}^",
+        gutter,
+        text,
+        " ".repeat(gutter.len()),
+        " ".repeat(column.saturating_sub(1))
+    )
+}
+
 /// A toolchain manifest.
 pub struct ToolchainManifest {
     /// The pinned version of Node, under the `toolchain.node` key.
@@ -38,6 +115,153 @@ pub struct ToolchainManifest {
     pub yarn: Option<Version>,
     /// The pinned version of Yarn as a string.
     pub yarn_str: Option<String>,
+    /// The pinned version of npm, under the `toolchain.npm` key.
+    pub npm: Option<Version>,
+    /// The pinned version of npm as a string.
+    pub npm_str: Option<String>,
+    /// The pinned version of pnpm, under the `toolchain.pnpm` key.
+    pub pnpm: Option<Version>,
+    /// The pinned version of pnpm as a string.
+    pub pnpm_str: Option<String>,
+}
+
+impl ToolchainManifest {
+    /// Starts building a toolchain manifest, for pinning a project programmatically,
+    /// e.g. `ToolchainManifest::builder().node(node).yarn(yarn).build()`.
+    pub fn builder() -> ToolchainManifestBuilder {
+        ToolchainManifestBuilder::default()
+    }
+
+    /// Starts building a toolchain manifest with the same pins as this one, for
+    /// changing some of them.
+    pub fn edit(&self) -> ToolchainManifestBuilder {
+        ToolchainManifestBuilder {
+            node: Some(self.node.clone()),
+            yarn: self.yarn.clone(),
+            npm: self.npm.clone(),
+            pnpm: self.pnpm.clone(),
+        }
+    }
+
+    pub(crate) fn to_serial(&self) -> serial::ToolchainManifest {
+        serial::ToolchainManifest::new(
+            self.node.to_string(),
+            self.yarn.as_ref().map(|version| version.to_string()),
+            self.npm.as_ref().map(|version| version.to_string()),
+            self.pnpm.as_ref().map(|version| version.to_string()),
+        )
+    }
+}
+
+/// Thrown when a toolchain manifest is built without a version of Node.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "A toolchain must pin a version of Node")]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NoNodePinError;
+
+/// Builds a `ToolchainManifest`. Node must be pinned, and the other tools may be.
+#[derive(Debug, Default, Clone)]
+pub struct ToolchainManifestBuilder {
+    node: Option<Version>,
+    yarn: Option<Version>,
+    npm: Option<Version>,
+    pnpm: Option<Version>,
+}
+
+impl ToolchainManifestBuilder {
+    /// Pins a version of Node.
+    pub fn node(mut self, version: Version) -> Self {
+        self.node = Some(version);
+        self
+    }
+
+    /// Pins a version of Yarn, or unpins Yarn with `None`.
+    pub fn yarn(mut self, version: impl Into<Option<Version>>) -> Self {
+        self.yarn = version.into();
+        self
+    }
+
+    /// Pins a version of npm, or unpins npm (selecting the one bundled with Node) with
+    /// `None`.
+    pub fn npm(mut self, version: impl Into<Option<Version>>) -> Self {
+        self.npm = version.into();
+        self
+    }
+
+    /// Pins a version of pnpm, or unpins pnpm with `None`.
+    pub fn pnpm(mut self, version: impl Into<Option<Version>>) -> Self {
+        self.pnpm = version.into();
+        self
+    }
+
+    /// Builds the toolchain manifest, failing if no version of Node was pinned.
+    pub fn build(self) -> Fallible<ToolchainManifest> {
+        let ToolchainManifestBuilder {
+            node,
+            yarn,
+            npm,
+            pnpm,
+        } = self;
+        let node = match node {
+            Some(node) => node,
+            None => throw!(NoNodePinError),
+        };
+        Ok(ToolchainManifest {
+            node_str: node.to_string(),
+            node,
+            yarn_str: yarn.as_ref().map(|version| version.to_string()),
+            yarn,
+            npm_str: npm.as_ref().map(|version| version.to_string()),
+            npm,
+            pnpm_str: pnpm.as_ref().map(|version| version.to_string()),
+            pnpm,
+        })
+    }
+}
+
+/// A package manager declared by the `packageManager` key, e.g. `yarn@1.22.4`.
+#[derive(Debug, PartialEq)]
+pub struct PackageManager {
+    /// The name of the package manager, e.g. `yarn`.
+    pub name: String,
+    /// The exact version of the package manager.
+    pub version: Version,
+}
+
+/// The section of a Node manifest file that declares a dependency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DependencyKind {
+    /// The `dependencies` section.
+    Production,
+    /// The `devDependencies` section.
+    Development,
+    /// The `peerDependencies` section.
+    Peer,
+    /// The `optionalDependencies` section.
+    Optional,
+}
+
+impl DependencyKind {
+    /// The key of the section in package.json.
+    pub fn key(&self) -> &'static str {
+        match *self {
+            DependencyKind::Production => "dependencies",
+            DependencyKind::Development => "devDependencies",
+            DependencyKind::Peer => "peerDependencies",
+            DependencyKind::Optional => "optionalDependencies",
+        }
+    }
+}
+
+/// A dependency declared in a Node manifest file.
+#[derive(Debug, PartialEq)]
+pub struct Dependency<'a> {
+    /// The name of the dependency.
+    pub name: &'a str,
+    /// The version range of the dependency.
+    pub version: &'a str,
+    /// The section that declares the dependency.
+    pub kind: DependencyKind,
 }
 
 /// A Node manifest file.
@@ -48,29 +272,148 @@ pub struct Manifest {
     pub dependencies: HashMap<String, String>,
     /// The `devDependencies` section.
     pub dev_dependencies: HashMap<String, String>,
+    /// The `peerDependencies` section.
+    pub peer_dependencies: HashMap<String, String>,
+    /// The `optionalDependencies` section.
+    pub optional_dependencies: HashMap<String, String>,
+    /// The `engines` section, containing a map of tool names to the version ranges the
+    /// project is compatible with.
+    pub engines: HashMap<String, String>,
+    /// The `scripts` section, containing a map of script names to the commands they run.
+    pub scripts: HashMap<String, String>,
+    /// The package manager declared by the `packageManager` key, if any.
+    pub package_manager: Option<PackageManager>,
+    /// The package patterns of the `workspaces` section, if this is the root of a
+    /// workspace.
+    pub workspaces: Option<Vec<String>>,
     /// The `bin` section, containing a map of binary names to locations
     pub bin: HashMap<String, String>,
+    /// The hooks in the `notion.hooks` section, if any.
+    pub hooks: Option<ProjectHooks>,
 }
 
 impl Manifest {
     /// Loads and parses a Node manifest for the project rooted at the specified path.
     pub fn for_dir(project_root: &Path) -> Fallible<Manifest> {
-        let file = File::open(project_root.join("package.json"))
+        Manifest::for_dir_with_toolchain_key(project_root, &[TOOLCHAIN_KEY.to_string()])
+    }
+
+    /// Loads and parses a Node manifest whose toolchain is under the specified path of
+    /// keys. Under any other key than `toolchain`, the `toolchain` key is left alone,
+    /// for whichever other tool uses it.
+    pub fn for_dir_with_toolchain_key(
+        project_root: &Path,
+        toolchain_key: &[String],
+    ) -> Fallible<Manifest> {
+        let package_file = project_root.join("package.json");
+        let mut contents = String::new();
+        File::open(&package_file)
+            .and_then(|mut file| file.read_to_string(&mut contents))
             .with_context(PackageReadError::from_io_error)?;
-        let serial: serial::Manifest = serde_json::de::from_reader(file).unknown()?;
+        if is_default_key(toolchain_key) {
+            let serial: serial::Manifest = serde_json::de::from_str(&contents)
+                .with_context(|error| PackageParseError::new(&package_file, &contents, error))?;
+            return serial.into_manifest();
+        }
+
+        let mut document: serde_json::Value = serde_json::de::from_str(&contents)
+            .with_context(|error| PackageParseError::new(&package_file, &contents, error))?;
+        let content_error = |error: String| PackageContentError {
+            file: package_file.display().to_string(),
+            error,
+        };
+        let toolchain = match serial::toolchain_at(&document, toolchain_key) {
+            Some(toolchain) => Some(
+                serde_json::from_value::<serial::ToolchainManifest>(toolchain.clone())
+                    .map_err(|error| {
+                        content_error(format!(
+                            "invalid toolchain under `{}`: {}",
+                            toolchain_key.join("."),
+                            error
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        if let Some(object) = document.as_object_mut() {
+            object.remove(TOOLCHAIN_KEY);
+        }
+        let mut serial: serial::Manifest = serde_json::from_value(document)
+            .map_err(|error| content_error(error.to_string()))?;
+        serial.toolchain = toolchain;
         serial.into_manifest()
     }
 
+    /// Returns the dependencies declared in every section of this manifest, each tagged
+    /// with the section that declares it.
+    pub fn all_dependencies<'a>(&'a self) -> impl Iterator<Item = Dependency<'a>> + 'a {
+        let sections = vec![
+            (&self.dependencies, DependencyKind::Production),
+            (&self.dev_dependencies, DependencyKind::Development),
+            (&self.peer_dependencies, DependencyKind::Peer),
+            (&self.optional_dependencies, DependencyKind::Optional),
+        ];
+        sections.into_iter().flat_map(|(section, kind)| {
+            section.iter().map(move |(name, version)| Dependency {
+                name: name,
+                version: version,
+                kind: kind,
+            })
+        })
+    }
+
+    /// Produces a manifest with no contents, for projects that have no `package.json` file.
+    pub fn empty() -> Manifest {
+        Manifest {
+            toolchain: None,
+            dependencies: HashMap::new(),
+            dev_dependencies: HashMap::new(),
+            peer_dependencies: HashMap::new(),
+            optional_dependencies: HashMap::new(),
+            engines: HashMap::new(),
+            scripts: HashMap::new(),
+            package_manager: None,
+            workspaces: None,
+            bin: HashMap::new(),
+            hooks: None,
+        }
+    }
+
     /// Returns whether this manifest contains a toolchain section (at least Node is pinned).
     pub fn has_toolchain(&self) -> bool {
         self.toolchain.is_some()
     }
 
+    /// Returns whether this manifest is the root of a workspace.
+    pub fn is_workspace_root(&self) -> bool {
+        self.workspaces.is_some()
+    }
+
     /// Returns the pinned version of Node as a Version, if any.
     pub fn node(&self) -> Option<Version> {
         self.toolchain.as_ref().map(|t| t.node.clone())
     }
 
+    /// Returns the range of Node versions in the `engines` section, if any. Ranges that
+    /// Notion can't parse are ignored.
+    pub fn engines_node(&self) -> Option<VersionSpec> {
+        self.engines
+            .get("node")
+            .and_then(|range| VersionSpec::parse(range).ok())
+    }
+
+    /// Returns the version of the specified package manager declared by the
+    /// `packageManager` key, if that is the package manager it declares.
+    pub fn package_manager_version(&self, name: &str) -> Option<Version> {
+        self.package_manager
+            .as_ref()
+            .and_then(|package_manager| if package_manager.name == name {
+                Some(package_manager.version.clone())
+            } else {
+                None
+            })
+    }
+
     /// Returns the pinned verison of Node as a String, if any.
     pub fn node_str(&self) -> Option<String> {
         self.toolchain.as_ref().map(|t| t.node_str.clone())
@@ -92,36 +435,251 @@ impl Manifest {
             .unwrap_or(None)
     }
 
+    /// Returns the pinned verison of npm as a Version, if any.
+    pub fn npm(&self) -> Option<Version> {
+        self.toolchain
+            .as_ref()
+            .map(|t| t.npm.clone())
+            .unwrap_or(None)
+    }
+
+    /// Returns the pinned verison of npm as a String, if any.
+    pub fn npm_str(&self) -> Option<String> {
+        self.toolchain
+            .as_ref()
+            .map(|t| t.npm_str.clone())
+            .unwrap_or(None)
+    }
+
+    /// Returns the pinned verison of pnpm as a Version, if any.
+    pub fn pnpm(&self) -> Option<Version> {
+        self.toolchain
+            .as_ref()
+            .map(|t| t.pnpm.clone())
+            .unwrap_or(None)
+    }
+
+    /// Returns the pinned verison of pnpm as a String, if any.
+    pub fn pnpm_str(&self) -> Option<String> {
+        self.toolchain
+            .as_ref()
+            .map(|t| t.pnpm_str.clone())
+            .unwrap_or(None)
+    }
+
+    /// Writes this manifest's `toolchain` section to the package.json of the project
+    /// rooted at the specified path, as `notion pin` does: the rest of the file is left
+    /// exactly as it was. Without a `toolchain` section, the file's is removed. (A
+    /// project that pins its toolchain in a pin file is pinned with `pin_file` instead.)
+    pub fn write_toolchain(&self, project_root: &Path) -> Fallible<()> {
+        let package_file = project_root.join("package.json");
+        match self.toolchain {
+            Some(ref toolchain) => Manifest::update_toolchain(toolchain.to_serial(), package_file),
+            None => Manifest::remove_toolchain(None, package_file).map(|_| ()),
+        }
+    }
+
     /// Writes the input ToolchainManifest to package.json, adding the "toolchain" key if
-    /// necessary.
+    /// necessary. The rest of the file is left exactly as it was.
     pub fn update_toolchain(
         toolchain: serial::ToolchainManifest,
         package_file: PathBuf,
     ) -> Fallible<()> {
-        // parse the entire package.json file into a Value
-        let file = File::open(&package_file).unknown()?;
-        let mut v: serde_json::Value = serde_json::from_reader(file).unknown()?;
+        let mut plan = Plan::new();
+        Manifest::plan_update_toolchain(
+            toolchain,
+            &[TOOLCHAIN_KEY.to_string()],
+            package_file,
+            &mut plan,
+        )?;
+        plan.write_files()
+    }
 
-        // detect indentation in package.json
-        let mut contents = String::new();
-        let mut indent_file = File::open(&package_file).unknown()?;
-        indent_file.read_to_string(&mut contents).unknown()?;
-        let indent = detect_indent::detect_indent(&contents);
-
-        if let Some(map) = v.as_object_mut() {
-            // update the "toolchain" key
-            let toolchain_value = serde_json::to_value(toolchain).unknown()?;
-            map.insert("toolchain".to_string(), toolchain_value);
-
-            // serialize the updated contents back to package.json
-            let file = File::create(package_file).unknown()?;
-            let formatter =
-                serde_json::ser::PrettyFormatter::with_indent(indent.indent().as_bytes());
-            let mut ser = serde_json::Serializer::with_formatter(file, formatter);
-            map.serialize(&mut ser).unknown()?;
+    /// Plans the write of `update_toolchain` under the specified path of keys, on top of
+    /// the writes already planned.
+    pub(crate) fn plan_update_toolchain(
+        toolchain: serial::ToolchainManifest,
+        toolchain_key: &[String],
+        package_file: PathBuf,
+        plan: &mut Plan,
+    ) -> Fallible<()> {
+        let toolchain_value = serde_json::to_value(toolchain).unknown()?;
+        if is_default_key(toolchain_key) {
+            plan_package_edit(package_file, plan, |contents, indent| {
+                edit::set_key(contents, TOOLCHAIN_KEY, &toolchain_value, indent)
+            })?;
+            return Ok(());
         }
+
+        // A namespace like `notion` may hold other settings, so pin each tool on its
+        // own rather than replacing the whole section.
+        let key: Vec<&str> = toolchain_key.iter().map(|key| &key[..]).collect();
+        plan_package_edit(package_file, plan, |contents, indent| {
+            let mut contents = contents.to_string();
+            for tool in TOOLS {
+                let path = [&key[..], &[*tool][..]].concat();
+                match toolchain_value.get(*tool) {
+                    Some(version) => {
+                        contents = edit::set_nested_key(&contents, &path, version, indent)?;
+                    }
+                    None => {
+                        if let Some(edited) = edit::remove_key(&contents, &path) {
+                            contents = edited;
+                        }
+                    }
+                }
+            }
+            Some(contents)
+        })?;
+        Ok(())
+    }
+
+    /// Writes the input range to the `engines.node` key of package.json, adding the
+    /// "engines" key if necessary. The rest of the file is left exactly as it was.
+    pub fn update_engines_node(range: &str, package_file: PathBuf) -> Fallible<()> {
+        let mut plan = Plan::new();
+        Manifest::plan_update_engines_node(range, package_file, &mut plan)?;
+        plan.write_files()
+    }
+
+    /// Plans the write of `update_engines_node`, on top of the writes already planned.
+    pub(crate) fn plan_update_engines_node(
+        range: &str,
+        package_file: PathBuf,
+        plan: &mut Plan,
+    ) -> Fallible<()> {
+        let range_value = serde_json::Value::String(range.to_string());
+        plan_package_edit(package_file, plan, |contents, indent| {
+            edit::set_nested_key(contents, &["engines", "node"], &range_value, indent)
+        })?;
         Ok(())
     }
+
+    /// Declares a dependency on the `name` package with the input version range in the
+    /// `kind` section of package.json, adding the section if necessary. A dependency the
+    /// section already declares has its range replaced. The rest of the file is left
+    /// exactly as it was.
+    pub fn add_dependency(
+        name: &str,
+        range: &str,
+        kind: DependencyKind,
+        package_file: PathBuf,
+    ) -> Fallible<()> {
+        let range_value = serde_json::Value::String(range.to_string());
+        edit_package_file(package_file, |contents, indent| {
+            edit::set_nested_key(contents, &[kind.key(), name], &range_value, indent)
+        })?;
+        Ok(())
+    }
+
+    /// Removes the dependency on the `name` package from the `kind` section of
+    /// package.json. Removing the last dependency of the section removes the section as
+    /// well. Returns `false` if the section doesn't declare the dependency, in which case
+    /// the file is untouched.
+    pub fn remove_dependency(
+        name: &str,
+        kind: DependencyKind,
+        package_file: PathBuf,
+    ) -> Fallible<bool> {
+        edit_package_file(package_file, |contents, _| {
+            edit::remove_key(contents, &[kind.key(), name])
+        })
+    }
+
+    /// Replaces the version range of the dependency on the `name` package in the `kind`
+    /// section of package.json, leaving the rest of the file exactly as it was. Returns
+    /// `false` if the section doesn't declare the dependency, in which case the file is
+    /// untouched (use `add_dependency` to declare it).
+    pub fn set_dependency_version(
+        name: &str,
+        range: &str,
+        kind: DependencyKind,
+        package_file: PathBuf,
+    ) -> Fallible<bool> {
+        let range_value = serde_json::Value::String(range.to_string());
+        edit_package_file(package_file, |contents, indent| {
+            let document: serde_json::Value = serde_json::from_str(contents).ok()?;
+            document.get(kind.key())?.get(name)?;
+            edit::set_nested_key(contents, &[kind.key(), name], &range_value, indent)
+        })
+    }
+
+    /// Removes the "toolchain" key from package.json, or only its `tool` sub-key if one is
+    /// specified. Removing the last remaining sub-key removes the "toolchain" key as well.
+    /// Returns `false` if there was nothing to remove, in which case the file is untouched.
+    pub fn remove_toolchain(tool: Option<&str>, package_file: PathBuf) -> Fallible<bool> {
+        Manifest::remove_toolchain_at(&[TOOLCHAIN_KEY.to_string()], tool, package_file)
+    }
+
+    /// Removes the toolchain under the specified path of keys, or only its `tool` pin, as
+    /// `remove_toolchain` does. A namespace like `notion` may hold other settings, so
+    /// removing the whole toolchain from one only removes its pins.
+    pub(crate) fn remove_toolchain_at(
+        toolchain_key: &[String],
+        tool: Option<&str>,
+        package_file: PathBuf,
+    ) -> Fallible<bool> {
+        let key: Vec<&str> = toolchain_key.iter().map(|key| &key[..]).collect();
+        let tools: Vec<&str> = match tool {
+            Some(tool) => vec![tool],
+            None if is_default_key(toolchain_key) => {
+                return edit_package_file(package_file, |contents, _| {
+                    edit::remove_key(contents, &key)
+                });
+            }
+            None => TOOLS.to_vec(),
+        };
+        edit_package_file(package_file, |contents, _| {
+            let mut edited: Option<String> = None;
+            for tool in tools {
+                let path = [&key[..], &[tool][..]].concat();
+                let removed = edit::remove_key(edited.as_ref().map_or(contents, |e| &e[..]), &path);
+                if removed.is_some() {
+                    edited = removed;
+                }
+            }
+            edited
+        })
+    }
+}
+
+/// Rewrites package.json with the result of applying `edit` to its contents and detected
+/// indentation. The file is only rewritten if `edit` produces new contents, and the
+/// return value reports whether it did.
+fn edit_package_file<F>(package_file: PathBuf, edit: F) -> Fallible<bool>
+where
+    F: FnOnce(&str, &str) -> Option<String>,
+{
+    let mut plan = Plan::new();
+    let edited = plan_package_edit(package_file, &mut plan, edit)?;
+    plan.write_files()?;
+    Ok(edited)
+}
+
+/// Plans the write of `edit_package_file`, applying `edit` to the contents package.json
+/// will have once the writes already planned are made.
+fn plan_package_edit<F>(package_file: PathBuf, plan: &mut Plan, edit: F) -> Fallible<bool>
+where
+    F: FnOnce(&str, &str) -> Option<String>,
+{
+    // read the entire package.json file, making sure it parses before editing it
+    let contents = match plan.contents(&package_file)? {
+        Some(contents) => contents,
+        None => {
+            let message = format!("{} does not exist", package_file.display());
+            return Err(io::Error::new(io::ErrorKind::NotFound, message)).unknown();
+        }
+    };
+    let _: serde_json::Value = serde_json::from_str(&contents).unknown()?;
+
+    // detect indentation in package.json
+    let indent = detect_indent::detect_indent(&contents);
+
+    if let Some(updated) = edit(&contents, indent.indent()) {
+        plan.write(package_file, updated)?;
+        return Ok(true);
+    }
+    Ok(false)
 }
 
 // unit tests
@@ -1,9 +1,12 @@
 use super::super::manifest;
+use config::serial::ProjectHooks;
 use version::VersionSpec;
 
 use notion_fail::Fallible;
+use semver::Version;
 
 use serde::de::{Deserialize, Deserializer, Error, MapAccess, Visitor};
+use serde_json;
 
 use std::collections::HashMap;
 use std::fmt;
@@ -51,12 +54,75 @@ pub struct Manifest {
     #[serde(rename = "devDependencies")]
     pub dev_dependencies: HashMap<String, String>,
 
+    #[serde(default)]
+    #[serde(rename = "peerDependencies")]
+    pub peer_dependencies: HashMap<String, String>,
+
+    #[serde(default)]
+    #[serde(rename = "optionalDependencies")]
+    pub optional_dependencies: HashMap<String, String>,
+
     pub toolchain: Option<ToolchainManifest>,
 
+    #[serde(default)]
+    pub engines: HashMap<String, String>,
+
+    #[serde(default)]
+    pub scripts: HashMap<String, String>,
+
+    #[serde(rename = "packageManager")]
+    pub package_manager: Option<String>,
+
+    pub workspaces: Option<Workspaces>,
+
     // the "bin" field can be a map or a string
     // (see https://docs.npmjs.com/files/package.json#bin)
     #[serde(default)] // handles Option
     pub bin: Option<BinMap<String, String>>,
+
+    pub notion: Option<NotionManifest>,
+}
+
+/// Finds the toolchain under the specified path of keys of a manifest, for a toolchain
+/// kept elsewhere than the `toolchain` key. A section that doesn't pin Node isn't a
+/// toolchain, since a namespace like `notion` may hold other settings (like the hooks)
+/// even when the project pins nothing.
+pub fn toolchain_at<'a>(
+    document: &'a serde_json::Value,
+    toolchain_key: &[String],
+) -> Option<&'a serde_json::Value> {
+    let mut value = document;
+    for key in toolchain_key {
+        value = value.get(key)?;
+    }
+    value.get("node").map(|_| value)
+}
+
+/// The `notion` section.
+#[derive(Deserialize)]
+pub struct NotionManifest {
+    pub hooks: Option<ProjectHooks>,
+}
+
+/// The `workspaces` field, which is either a list of package patterns or an object
+/// with a `packages` list (see https://yarnpkg.com/lang/en/docs/workspaces/).
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Workspaces {
+    Packages(Vec<String>),
+    Config {
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+}
+
+impl Workspaces {
+    fn into_packages(self) -> Vec<String> {
+        match self {
+            Workspaces::Packages(packages) => packages,
+            Workspaces::Config { packages } => packages,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -64,6 +130,10 @@ pub struct ToolchainManifest {
     pub node: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub yarn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pnpm: Option<String>,
 }
 
 impl Manifest {
@@ -73,8 +143,11 @@ impl Manifest {
             for (name, path) in bin.iter() {
                 // handle case where only the path was given and binary name was unknown
                 if name == "" {
-                    // npm uses the package name for the binary in this case
-                    map.insert(self.name.clone().unwrap(), path.clone());
+                    // npm uses the package name for the binary in this case, and without
+                    // a package name there is no way to know what the binary is called
+                    if let Some(ref package_name) = self.name {
+                        map.insert(default_bin_name(package_name), path.clone());
+                    }
                 } else {
                     map.insert(name.clone(), path.clone());
                 }
@@ -84,32 +157,96 @@ impl Manifest {
             toolchain: self.into_toolchain_manifest()?,
             dependencies: self.dependencies,
             dev_dependencies: self.dev_dependencies,
+            peer_dependencies: self.peer_dependencies,
+            optional_dependencies: self.optional_dependencies,
+            engines: self.engines,
+            scripts: self.scripts,
+            package_manager: self.package_manager
+                .as_ref()
+                .and_then(|declaration| parse_package_manager(declaration)),
+            workspaces: self.workspaces.map(Workspaces::into_packages),
             bin: map,
+            hooks: match self.notion.and_then(|notion| notion.hooks) {
+                Some(hooks) => Some(hooks.into_project_hooks()?),
+                None => None,
+            },
         })
     }
 
     pub fn into_toolchain_manifest(&self) -> Fallible<Option<manifest::ToolchainManifest>> {
         if let Some(toolchain) = &self.toolchain {
-            return Ok(Some(manifest::ToolchainManifest {
-                node: VersionSpec::parse_version(&toolchain.node)?,
-                node_str: toolchain.node.clone(),
-                yarn: if let Some(yarn) = &toolchain.yarn {
-                    Some(VersionSpec::parse_version(&yarn)?)
-                } else {
-                    None
-                },
-                yarn_str: toolchain.yarn.clone(),
-            }));
+            return Ok(Some(toolchain.into_toolchain_manifest()?));
         }
         Ok(None)
     }
 }
 
+/// The name npm gives the binary of a package whose `bin` field is just a path: the
+/// package name, without the scope for scoped packages (e.g. `@scope/cli` becomes `cli`).
+fn default_bin_name(package_name: &str) -> String {
+    match package_name.rfind('/') {
+        Some(index) if package_name.starts_with('@') => package_name[index + 1..].to_string(),
+        _ => package_name.to_string(),
+    }
+}
+
+/// Parses a Corepack-style `packageManager` declaration, e.g. `yarn@1.22.4`, which may
+/// end with a `+<algorithm>.<hash>` integrity suffix. Declarations without an exact
+/// version are ignored.
+fn parse_package_manager(declaration: &str) -> Option<manifest::PackageManager> {
+    let declaration = declaration.trim();
+    let index = match declaration.rfind('@') {
+        Some(index) if index > 0 => index,
+        _ => {
+            return None;
+        }
+    };
+    let version = declaration[index + 1..].split('+').next().unwrap_or("");
+    Version::parse(version)
+        .ok()
+        .map(|version| manifest::PackageManager {
+            name: declaration[..index].to_string(),
+            version,
+        })
+}
+
 impl ToolchainManifest {
-    pub fn new(node_version: String, yarn_version: Option<String>) -> Self {
+    pub fn into_toolchain_manifest(&self) -> Fallible<manifest::ToolchainManifest> {
+        Ok(manifest::ToolchainManifest {
+            node: VersionSpec::parse_version(&self.node)?,
+            node_str: self.node.clone(),
+            yarn: if let Some(yarn) = &self.yarn {
+                Some(VersionSpec::parse_version(&yarn)?)
+            } else {
+                None
+            },
+            yarn_str: self.yarn.clone(),
+            npm: if let Some(npm) = &self.npm {
+                Some(VersionSpec::parse_version(&npm)?)
+            } else {
+                None
+            },
+            npm_str: self.npm.clone(),
+            pnpm: if let Some(pnpm) = &self.pnpm {
+                Some(VersionSpec::parse_version(&pnpm)?)
+            } else {
+                None
+            },
+            pnpm_str: self.pnpm.clone(),
+        })
+    }
+
+    pub fn new(
+        node_version: String,
+        yarn_version: Option<String>,
+        npm_version: Option<String>,
+        pnpm_version: Option<String>,
+    ) -> Self {
         ToolchainManifest {
             node: node_version,
             yarn: yarn_version,
+            npm: npm_version,
+            pnpm: pnpm_version,
         }
     }
 }
@@ -316,6 +453,80 @@ pub mod tests {
             .expect("Did not parse toolchain correctly");
         assert_eq!(toolchain.node, "0.10.5");
         assert_eq!(toolchain.yarn.unwrap(), "1.2.1");
+
+        let package_node_and_npm = r#"{
+            "toolchain": {
+                "node": "0.10.5",
+                "npm": "5.6.0"
+            }
+        }"#;
+        let manifest_node_and_npm: Manifest =
+            serde_json::de::from_str(package_node_and_npm).expect("Could not deserialize string");
+        let toolchain = manifest_node_and_npm
+            .toolchain
+            .expect("Did not parse toolchain correctly");
+        assert_eq!(toolchain.node, "0.10.5");
+        assert_eq!(toolchain.yarn, None);
+        assert_eq!(toolchain.npm.unwrap(), "5.6.0");
+
+        let package_node_and_pnpm = r#"{
+            "toolchain": {
+                "node": "8.11.3",
+                "pnpm": "2.9.0"
+            }
+        }"#;
+        let manifest_node_and_pnpm: Manifest =
+            serde_json::de::from_str(package_node_and_pnpm).expect("Could not deserialize string");
+        let toolchain = manifest_node_and_pnpm
+            .toolchain
+            .expect("Did not parse toolchain correctly");
+        assert_eq!(toolchain.node, "8.11.3");
+        assert_eq!(toolchain.pnpm.unwrap(), "2.9.0");
+    }
+
+    #[test]
+    fn test_package_workspaces() {
+        let package_workspaces_list = r#"{
+            "workspaces": ["packages/*"]
+        }"#;
+        let manifest: Manifest = serde_json::de::from_str(package_workspaces_list)
+            .expect("Could not deserialize string");
+        assert_eq!(
+            manifest.into_manifest().unwrap().workspaces,
+            Some(vec!["packages/*".to_string()])
+        );
+
+        let package_workspaces_object = r#"{
+            "workspaces": {
+                "packages": ["packages/*", "tools/cli"],
+                "nohoist": ["**/react-native"]
+            }
+        }"#;
+        let manifest: Manifest = serde_json::de::from_str(package_workspaces_object)
+            .expect("Could not deserialize string");
+        assert_eq!(
+            manifest.into_manifest().unwrap().workspaces,
+            Some(vec!["packages/*".to_string(), "tools/cli".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_package_hooks() {
+        let package_hooks = r#"{
+            "notion": {
+                "hooks": {
+                    "node": { "mirror": "https://mirror.corp/node/" }
+                }
+            }
+        }"#;
+        let manifest: Manifest =
+            serde_json::de::from_str(package_hooks).expect("Could not deserialize string");
+        let hooks = manifest.into_manifest().unwrap().hooks.unwrap();
+        assert_eq!(
+            hooks.node.unwrap().mirror,
+            Some("https://mirror.corp/node/".to_string())
+        );
+        assert!(hooks.events.is_none());
     }
 
     #[test]
@@ -350,4 +561,26 @@ pub mod tests {
         expected_bin_string.insert("".to_string(), "cli.js".to_string());
         assert_eq!(manifest_bin_string.bin.unwrap(), expected_bin_string);
     }
+
+    #[test]
+    fn test_package_bin_string_names() {
+        let scoped: Manifest = serde_json::de::from_str(
+            r#"{
+            "name": "@namespace/some-cli",
+            "bin": "cli.js"
+        }"#,
+        ).expect("Could not deserialize string");
+        let bin = scoped.into_manifest().expect("Could not read manifest").bin;
+        let mut expected_bin = HashMap::new();
+        expected_bin.insert("some-cli".to_string(), "cli.js".to_string());
+        assert_eq!(bin, expected_bin);
+
+        let unnamed: Manifest = serde_json::de::from_str(
+            r#"{
+            "bin": "cli.js"
+        }"#,
+        ).expect("Could not deserialize string");
+        let bin = unnamed.into_manifest().expect("Could not read manifest").bin;
+        assert_eq!(bin, HashMap::new());
+    }
 }
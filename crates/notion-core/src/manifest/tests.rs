@@ -1,7 +1,12 @@
-use manifest::Manifest;
+use manifest::serial;
+use manifest::{Dependency, DependencyKind, Manifest, ToolchainManifest};
+use plan::Plan;
 use semver::Version;
 use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
+use tempfile::{tempdir, TempDir};
+use version::VersionSpec;
 
 fn fixture_path(fixture_dir: &str) -> PathBuf {
     let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -10,6 +15,16 @@ fn fixture_path(fixture_dir: &str) -> PathBuf {
     cargo_manifest_dir
 }
 
+/// Copies a fixture's package.json into a fresh temporary directory, so tests can modify it.
+fn scratch_fixture(fixture_dir: &str) -> TempDir {
+    let dir = tempdir().expect("Could not create temporary directory");
+    fs::copy(
+        fixture_path(fixture_dir).join("package.json"),
+        dir.path().join("package.json"),
+    ).expect("Could not copy fixture");
+    dir
+}
+
 #[test]
 fn gets_node_version() {
     let project_path = fixture_path("basic");
@@ -56,6 +71,61 @@ fn gets_dev_dependencies() {
     assert_eq!(dev_dependencies, expected_deps);
 }
 
+#[test]
+fn gets_peer_and_optional_dependencies() {
+    let project_path = fixture_path("basic");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    let mut expected_peer_deps = HashMap::new();
+    expected_peer_deps.insert("react".to_string(), "^16.0.0".to_string());
+    assert_eq!(manifest.peer_dependencies, expected_peer_deps);
+    let mut expected_optional_deps = HashMap::new();
+    expected_optional_deps.insert("fsevents".to_string(), "^1.2.4".to_string());
+    assert_eq!(manifest.optional_dependencies, expected_optional_deps);
+}
+
+#[test]
+fn gets_all_dependencies() {
+    let project_path = fixture_path("basic");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    let mut dependencies: Vec<Dependency> = manifest.all_dependencies().collect();
+    dependencies.sort_by_key(|dependency| dependency.name);
+    assert_eq!(
+        dependencies,
+        vec![
+            Dependency {
+                name: "@namespace/some-dep",
+                version: "0.2.4",
+                kind: DependencyKind::Production,
+            },
+            Dependency {
+                name: "@namespaced/something-else",
+                version: "^6.3.7",
+                kind: DependencyKind::Development,
+            },
+            Dependency {
+                name: "eslint",
+                version: "~4.8.0",
+                kind: DependencyKind::Development,
+            },
+            Dependency {
+                name: "fsevents",
+                version: "^1.2.4",
+                kind: DependencyKind::Optional,
+            },
+            Dependency {
+                name: "react",
+                version: "^16.0.0",
+                kind: DependencyKind::Peer,
+            },
+            Dependency {
+                name: "rsvp",
+                version: "^3.5.0",
+                kind: DependencyKind::Production,
+            },
+        ]
+    );
+}
+
 #[test]
 fn node_for_no_toolchain() {
     let project_path = fixture_path("no_toolchain");
@@ -70,6 +140,67 @@ fn yarn_for_no_toolchain() {
     assert_eq!(manifest.yarn(), None);
 }
 
+#[test]
+fn gets_engines_node() {
+    let project_path = fixture_path("engines");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    match manifest.engines_node() {
+        Some(VersionSpec::Semver(ref range)) => {
+            assert!(range.matches(&Version::parse("10.15.3").unwrap()));
+            assert!(!range.matches(&Version::parse("8.9.4").unwrap()));
+        }
+        _ => panic!("expected a range for engines.node"),
+    }
+}
+
+#[test]
+fn engines_node_for_no_engines() {
+    let project_path = fixture_path("basic");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert!(manifest.engines_node().is_none());
+}
+
+#[test]
+fn gets_package_manager() {
+    let project_path = fixture_path("package_manager");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert_eq!(
+        manifest.package_manager_version("yarn"),
+        Some(Version::parse("1.22.4").unwrap())
+    );
+    assert_eq!(manifest.package_manager_version("pnpm"), None);
+}
+
+#[test]
+fn package_manager_for_no_package_manager() {
+    let project_path = fixture_path("basic");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert_eq!(manifest.package_manager, None);
+}
+
+#[test]
+fn npm_for_unpinned_npm() {
+    let project_path = fixture_path("basic");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert_eq!(manifest.npm(), None);
+}
+
+#[test]
+fn gets_scripts() {
+    let project_path = fixture_path("basic");
+    let scripts = Manifest::for_dir(&project_path)
+        .expect("Could not get manifest")
+        .scripts;
+    let mut expected_scripts = HashMap::new();
+    expected_scripts.insert("test".to_string(), "eslint . && mocha".to_string());
+    expected_scripts.insert("pretest".to_string(), "echo linting".to_string());
+    assert_eq!(scripts, expected_scripts);
+
+    let project_path = fixture_path("no_toolchain");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert!(manifest.scripts.is_empty());
+}
+
 #[test]
 fn gets_bin_map_format() {
     let project_path = fixture_path("basic/node_modules/eslint");
@@ -113,3 +244,228 @@ fn handles_dep_with_no_bin() {
     let expected_bin = HashMap::new();
     assert_eq!(bin, expected_bin);
 }
+
+#[test]
+fn removes_single_toolchain_key() {
+    let dir = scratch_fixture("basic");
+    let package_file = dir.path().join("package.json");
+    assert!(Manifest::remove_toolchain(Some("yarn"), package_file.clone()).unwrap());
+
+    let manifest = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    assert_eq!(manifest.node().unwrap(), Version::parse("6.11.1").unwrap());
+    assert_eq!(manifest.yarn(), None);
+
+    // the rest of the file keeps its original formatting
+    let contents = fs::read_to_string(&package_file).unwrap();
+    assert!(contents.contains("\n  \"toolchain\": {\n    \"node\": \"6.11.1\"\n  }\n"));
+    assert!(contents.ends_with("}\n"));
+}
+
+#[test]
+fn removes_whole_toolchain() {
+    let dir = scratch_fixture("basic");
+    let package_file = dir.path().join("package.json");
+    assert!(Manifest::remove_toolchain(None, package_file.clone()).unwrap());
+
+    let manifest = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    assert!(!manifest.has_toolchain());
+    assert_eq!(manifest.dependencies.len(), 2);
+}
+
+#[test]
+fn removing_missing_toolchain_leaves_file_untouched() {
+    let dir = scratch_fixture("no_toolchain");
+    let package_file = dir.path().join("package.json");
+    let before = fs::read_to_string(&package_file).unwrap();
+    assert!(!Manifest::remove_toolchain(None, package_file.clone()).unwrap());
+    assert!(!Manifest::remove_toolchain(Some("yarn"), package_file.clone()).unwrap());
+    assert_eq!(fs::read_to_string(&package_file).unwrap(), before);
+}
+
+#[test]
+fn adds_dependency() {
+    let dir = scratch_fixture("no_toolchain");
+    let package_file = dir.path().join("package.json");
+    Manifest::add_dependency("lodash", "^4.17.11", DependencyKind::Production, package_file.clone())
+        .unwrap();
+    Manifest::add_dependency("eslint", "^5.0.0", DependencyKind::Development, package_file.clone())
+        .unwrap();
+    Manifest::add_dependency("react", "^16.0.0", DependencyKind::Peer, package_file.clone())
+        .unwrap();
+
+    let manifest = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    assert_eq!(manifest.dependencies.len(), 3);
+    assert_eq!(manifest.dependencies["lodash"], "^4.17.11");
+    assert_eq!(manifest.dev_dependencies.len(), 2);
+    assert_eq!(manifest.dev_dependencies["eslint"], "^5.0.0");
+    assert_eq!(manifest.peer_dependencies["react"], "^16.0.0");
+
+    // the rest of the file keeps its original formatting
+    let contents = fs::read_to_string(&package_file).unwrap();
+    assert!(contents.contains("\"rsvp\": \"^3.5.0\",\n    \"lodash\": \"^4.17.11\"\n  },\n"));
+    assert!(contents.contains("\"@namespaced/something-else\": \"^6.3.7\",\n    \"eslint\""));
+    assert!(contents.ends_with("\"peerDependencies\": {\n    \"react\": \"^16.0.0\"\n  }\n}\n"));
+}
+
+#[test]
+fn removes_dependency() {
+    let dir = scratch_fixture("no_toolchain");
+    let package_file = dir.path().join("package.json");
+    let remove = |name: &str, kind: DependencyKind| {
+        Manifest::remove_dependency(name, kind, package_file.clone()).unwrap()
+    };
+    assert!(remove("rsvp", DependencyKind::Production));
+    assert!(!remove("rsvp", DependencyKind::Production));
+    assert!(!remove("eslint", DependencyKind::Peer));
+
+    let manifest = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    assert_eq!(manifest.dependencies.len(), 1);
+    assert!(manifest.dependencies.contains_key("@namespace/some-dep"));
+    assert_eq!(manifest.dev_dependencies.len(), 2);
+
+    assert!(remove("@namespace/some-dep", DependencyKind::Production));
+    let contents = fs::read_to_string(&package_file).unwrap();
+    assert!(!contents.contains("\"dependencies\""));
+    assert!(contents.ends_with("}\n"));
+}
+
+#[test]
+fn sets_dependency_version() {
+    let dir = scratch_fixture("no_toolchain");
+    let package_file = dir.path().join("package.json");
+    assert!(Manifest::set_dependency_version(
+        "eslint",
+        "^5.16.0",
+        DependencyKind::Development,
+        package_file.clone()
+    ).unwrap());
+
+    let before = fs::read_to_string(&package_file).unwrap();
+    assert!(!Manifest::set_dependency_version(
+        "eslint",
+        "^5.16.0",
+        DependencyKind::Production,
+        package_file.clone()
+    ).unwrap());
+    assert_eq!(fs::read_to_string(&package_file).unwrap(), before);
+
+    let manifest = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    assert_eq!(manifest.dev_dependencies["eslint"], "^5.16.0");
+    assert_eq!(manifest.dependencies.len(), 2);
+    assert!(before.contains("\n    \"eslint\": \"^5.16.0\"\n  }\n"));
+}
+
+#[test]
+fn builds_toolchain() {
+    let toolchain = ToolchainManifest::builder()
+        .node(Version::parse("10.15.3").unwrap())
+        .yarn(Version::parse("1.13.0").unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(toolchain.node_str, "10.15.3");
+    assert_eq!(toolchain.yarn_str, Some("1.13.0".to_string()));
+    assert_eq!(toolchain.npm, None);
+
+    let edited = toolchain.edit().yarn(None).build().unwrap();
+    assert_eq!(edited.node, toolchain.node);
+    assert_eq!(edited.yarn, None);
+
+    assert!(ToolchainManifest::builder().build().is_err());
+}
+
+#[test]
+fn writes_toolchain() {
+    let dir = scratch_fixture("no_toolchain");
+    let mut manifest = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    manifest.toolchain = Some(
+        ToolchainManifest::builder()
+            .node(Version::parse("10.15.3").unwrap())
+            .npm(Version::parse("6.9.0").unwrap())
+            .build()
+            .unwrap(),
+    );
+    manifest.write_toolchain(dir.path()).unwrap();
+
+    let written = Manifest::for_dir(dir.path()).expect("Could not get manifest");
+    assert_eq!(written.node(), Some(Version::parse("10.15.3").unwrap()));
+    assert_eq!(written.npm(), Some(Version::parse("6.9.0").unwrap()));
+    assert_eq!(written.yarn(), None);
+    let contents = fs::read_to_string(dir.path().join("package.json")).unwrap();
+    assert!(contents.ends_with("}\n"));
+
+    manifest.toolchain = None;
+    manifest.write_toolchain(dir.path()).unwrap();
+    assert!(!Manifest::for_dir(dir.path()).unwrap().has_toolchain());
+}
+
+#[test]
+fn uses_namespaced_toolchain_key() {
+    let dir = tempdir().expect("Could not create temporary directory");
+    let package_file = dir.path().join("package.json");
+    let other = "\"toolchain\": {\"rust\": \"1.30.0\"}";
+    fs::write(
+        &package_file,
+        format!(
+            "{{\n  {},\n  \"notion\": {{\n    \"other\": true,\n    \"node\": \"8.9.4\"\n  }}\n}}\n",
+            other
+        ),
+    ).unwrap();
+    let key = vec!["notion".to_string()];
+
+    let manifest = Manifest::for_dir_with_toolchain_key(dir.path(), &key).unwrap();
+    assert_eq!(manifest.node(), Some(Version::parse("8.9.4").unwrap()));
+    assert!(Manifest::for_dir(dir.path()).is_err());
+
+    let toolchain = serial::ToolchainManifest::new(
+        "10.15.3".to_string(),
+        Some("1.13.0".to_string()),
+        None,
+        None,
+    );
+    let mut plan = Plan::new();
+    Manifest::plan_update_toolchain(toolchain, &key, package_file.clone(), &mut plan).unwrap();
+    plan.write_files().unwrap();
+    let manifest = Manifest::for_dir_with_toolchain_key(dir.path(), &key).unwrap();
+    assert_eq!(manifest.node(), Some(Version::parse("10.15.3").unwrap()));
+    assert_eq!(manifest.yarn(), Some(Version::parse("1.13.0").unwrap()));
+    let contents = fs::read_to_string(&package_file).unwrap();
+    assert!(contents.contains(other));
+    assert!(contents.contains("\"other\": true"));
+
+    // removing the toolchain leaves the rest of the namespace
+    assert!(Manifest::remove_toolchain_at(&key, None, package_file.clone()).unwrap());
+    let contents = fs::read_to_string(&package_file).unwrap();
+    assert!(contents.contains(other));
+    assert!(contents.contains("\"notion\": {\n    \"other\": true\n  }"));
+    assert!(!Manifest::for_dir_with_toolchain_key(dir.path(), &key)
+        .unwrap()
+        .has_toolchain());
+}
+
+#[test]
+fn reports_parse_error_position() {
+    let dir = tempdir().expect("Could not create temporary directory");
+    fs::write(
+        dir.path().join("package.json"),
+        "{\n  \"name\": \"broken\"\n  \"version\": \"1.0.0\"\n}\n",
+    ).unwrap();
+
+    let message = match Manifest::for_dir(dir.path()) {
+        Ok(_) => panic!("Parsed an invalid package.json"),
+        Err(error) => error.to_string(),
+    };
+    assert!(message.contains("at line 3, column 3"));
+    assert!(message.contains("3 |   \"version\": \"1.0.0\"\n  |   ^"));
+}
+
+#[test]
+fn gets_workspaces() {
+    let project_path = fixture_path("workspace");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert!(manifest.is_workspace_root());
+    assert_eq!(manifest.workspaces, Some(vec!["packages/*".to_string()]));
+
+    let project_path = fixture_path("workspace/packages/member");
+    let manifest = Manifest::for_dir(&project_path).expect("Could not get manifest");
+    assert!(!manifest.is_workspace_root());
+}
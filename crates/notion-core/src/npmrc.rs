@@ -0,0 +1,321 @@
+//! Provides the registry settings from the user's `.npmrc` file, so that packages
+//! can be fetched from authenticated and private registries the way npm would.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use config::RegistryConfig;
+use notion_fail::{Fallible, ResultExt};
+
+/// The public npm registry, used unless `.npmrc` configures another one.
+pub const PUBLIC_NPM_REGISTRY: &'static str = "https://registry.npmjs.org/";
+
+/// The credentials npm sends to a registry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credentials {
+    /// An `_authToken`, sent as a bearer token.
+    Token(String),
+    /// An `_auth` setting, which is the base64 encoding of `username:password`.
+    Basic(String),
+}
+
+impl Credentials {
+    /// The value of the `Authorization` header for these credentials.
+    pub fn header_value(&self) -> String {
+        match self {
+            &Credentials::Token(ref token) => format!("Bearer {}", token),
+            &Credentials::Basic(ref auth) => format!("Basic {}", auth),
+        }
+    }
+}
+
+/// The registry settings of an `.npmrc` file.
+#[derive(Debug, Default, PartialEq)]
+pub struct Npmrc {
+    /// The `registry` setting: the default registry.
+    registry: Option<String>,
+    /// The `@scope:registry` settings: the registry for each scope, by scope.
+    scopes: HashMap<String, String>,
+    /// The `//host/path/:_authToken` and `//host/path/:_auth` settings, by the
+    /// registry URL (without its scheme) they apply to.
+    credentials: HashMap<String, Credentials>,
+    /// The top-level `_authToken` or `_auth` setting, which applies to the default
+    /// registry.
+    default_credentials: Option<Credentials>,
+    /// The `always-auth` setting, which sends the top-level credentials with every
+    /// request, including tarball downloads from other hosts.
+    always_auth: bool,
+}
+
+/// Expands `${NAME}` references to environment variables, which npm allows in the
+/// values of `.npmrc` settings so that tokens can be kept out of the file.
+fn expand_env(value: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        match rest[start..].find('}') {
+            Some(end) => {
+                expanded.push_str(&rest[..start]);
+                let name = &rest[start + 2..start + end];
+                expanded.push_str(&env::var(name).unwrap_or_default());
+                rest = &rest[start + end + 1..];
+            }
+            None => break,
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Normalizes a registry URL to end with a `/`, as npm does.
+fn with_trailing_slash(url: &str) -> String {
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    }
+}
+
+/// Strips the scheme from a URL, leaving the `//host/path` form that `.npmrc`
+/// keys credentials by.
+fn nerf_dart(url: &str) -> &str {
+    match url.find("//") {
+        Some(index) => &url[index..],
+        None => url,
+    }
+}
+
+impl Npmrc {
+    /// Returns the user's `.npmrc` settings, from the file named by the
+    /// `NPM_CONFIG_USERCONFIG` environment variable or else `~/.npmrc`. Without
+    /// such a file, every setting has its default.
+    pub fn current() -> Fallible<Npmrc> {
+        let file = match user_npmrc_file() {
+            Some(file) => file,
+            None => {
+                return Ok(Npmrc::default());
+            }
+        };
+        if !file.is_file() {
+            return Ok(Npmrc::default());
+        }
+        let mut src = String::new();
+        File::open(&file)
+            .and_then(|mut file| file.read_to_string(&mut src))
+            .unknown()?;
+        Ok(Npmrc::parse(&src))
+    }
+
+    /// Parses the contents of an `.npmrc` file, ignoring the settings that don't
+    /// concern registries.
+    pub fn parse(src: &str) -> Npmrc {
+        let mut npmrc = Npmrc::default();
+        for line in src.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => continue,
+            };
+            let value = expand_env(value.trim_matches('"'));
+
+            if key == "registry" {
+                npmrc.registry = Some(with_trailing_slash(&value));
+            } else if key == "always-auth" {
+                npmrc.always_auth = value == "true";
+            } else if key == "_authToken" {
+                npmrc.default_credentials = Some(Credentials::Token(value));
+            } else if key == "_auth" {
+                npmrc.default_credentials = Some(Credentials::Basic(value));
+            } else if key.starts_with('@') && key.ends_with(":registry") {
+                let scope = &key[..key.len() - ":registry".len()];
+                npmrc
+                    .scopes
+                    .insert(scope.to_string(), with_trailing_slash(&value));
+            } else if key.starts_with("//") && key.ends_with(":_authToken") {
+                let registry = &key[..key.len() - ":_authToken".len()];
+                npmrc
+                    .credentials
+                    .insert(with_trailing_slash(registry), Credentials::Token(value));
+            } else if key.starts_with("//") && key.ends_with(":_auth") {
+                let registry = &key[..key.len() - ":_auth".len()];
+                npmrc
+                    .credentials
+                    .insert(with_trailing_slash(registry), Credentials::Basic(value));
+            }
+        }
+        npmrc
+    }
+
+    /// Overrides the registries with those of the `[registry]` section of the Notion
+    /// configuration. The credentials for each registry still come from `.npmrc`.
+    pub fn override_with(&mut self, config: &RegistryConfig) {
+        if let Some(ref registry) = config.default {
+            self.registry = Some(registry.clone());
+        }
+        for (scope, registry) in config.scopes.iter() {
+            self.scopes.insert(scope.clone(), registry.clone());
+        }
+    }
+
+    /// The default registry, ending with a `/`.
+    pub fn default_registry(&self) -> &str {
+        match self.registry {
+            Some(ref registry) => registry,
+            None => PUBLIC_NPM_REGISTRY,
+        }
+    }
+
+    /// The registry the named package is fetched from: its scope's registry, if it
+    /// is a scoped package and one is configured, otherwise the default registry.
+    pub fn registry_for(&self, package: &str) -> &str {
+        if package.starts_with('@') {
+            if let Some(index) = package.find('/') {
+                if let Some(registry) = self.scopes.get(&package[..index]) {
+                    return registry;
+                }
+            }
+        }
+        self.default_registry()
+    }
+
+    /// The credentials to send with a request to the specified URL: those configured
+    /// for the most specific registry the URL belongs to, otherwise the top-level
+    /// credentials if the URL belongs to the default registry or `always-auth` is set.
+    pub fn credentials_for(&self, url: &str) -> Option<&Credentials> {
+        let target = nerf_dart(url);
+        let scoped = self.credentials
+            .iter()
+            .filter(|&(registry, _)| target.starts_with(&registry[..]))
+            .max_by_key(|&(registry, _)| registry.len())
+            .map(|(_, credentials)| credentials);
+        if scoped.is_some() {
+            return scoped;
+        }
+
+        if self.always_auth || target.starts_with(nerf_dart(self.default_registry())) {
+            self.default_credentials.as_ref()
+        } else {
+            None
+        }
+    }
+}
+
+fn user_npmrc_file() -> Option<PathBuf> {
+    match env::var_os("NPM_CONFIG_USERCONFIG") {
+        Some(ref file) if !file.is_empty() => Some(PathBuf::from(file)),
+        _ => env::home_dir().map(|home| home.join(".npmrc")),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    const NPMRC: &'static str = "
+; the company registry
+registry=https://npm.example.com/repository/npm
+@internal:registry = https://internal.example.com/npm/
+//npm.example.com/repository/npm/:_authToken=${NOTION_TEST_NPM_TOKEN}
+//internal.example.com/npm/:_auth=\"dXNlcjpwYXNz\"
+//internal.example.com/:_authToken=outer
+color=false
+";
+
+    #[test]
+    fn test_registry_for() {
+        let npmrc = Npmrc::parse(NPMRC);
+        assert_eq!(
+            npmrc.registry_for("typescript"),
+            "https://npm.example.com/repository/npm/"
+        );
+        assert_eq!(
+            npmrc.registry_for("@internal/cli"),
+            "https://internal.example.com/npm/"
+        );
+        assert_eq!(
+            npmrc.registry_for("@other/cli"),
+            "https://npm.example.com/repository/npm/"
+        );
+        assert_eq!(Npmrc::parse("").registry_for("typescript"), PUBLIC_NPM_REGISTRY);
+    }
+
+    #[test]
+    fn test_override_with() {
+        env::set_var("NOTION_TEST_NPM_TOKEN", "secret");
+        let mut npmrc = Npmrc::parse(NPMRC);
+        let mut scopes = HashMap::new();
+        scopes.insert(
+            "@company".to_string(),
+            "https://npm.example.com/repository/company/".to_string(),
+        );
+        npmrc.override_with(&RegistryConfig {
+            default: None,
+            scopes,
+        });
+
+        assert_eq!(
+            npmrc.registry_for("@company/cli"),
+            "https://npm.example.com/repository/company/"
+        );
+        assert_eq!(
+            npmrc.registry_for("@internal/cli"),
+            "https://internal.example.com/npm/"
+        );
+        assert_eq!(
+            npmrc.registry_for("typescript"),
+            "https://npm.example.com/repository/npm/"
+        );
+        // a scope registry without credentials of its own gets none
+        assert_eq!(
+            npmrc.credentials_for("https://npm.example.com/repository/company/@company%2fcli"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_credentials_for() {
+        env::set_var("NOTION_TEST_NPM_TOKEN", "secret");
+        let npmrc = Npmrc::parse(NPMRC);
+        assert_eq!(
+            npmrc.credentials_for("https://npm.example.com/repository/npm/typescript"),
+            Some(&Credentials::Token("secret".to_string()))
+        );
+        // the most specific registry wins
+        assert_eq!(
+            npmrc.credentials_for("https://internal.example.com/npm/@internal%2fcli"),
+            Some(&Credentials::Basic("dXNlcjpwYXNz".to_string()))
+        );
+        assert_eq!(
+            npmrc.credentials_for("https://internal.example.com/other"),
+            Some(&Credentials::Token("outer".to_string()))
+        );
+        assert_eq!(npmrc.credentials_for("https://registry.npmjs.org/typescript"), None);
+    }
+
+    #[test]
+    fn test_default_credentials() {
+        let npmrc = Npmrc::parse("_authToken=top\n");
+        assert_eq!(
+            npmrc.credentials_for("https://registry.npmjs.org/typescript"),
+            Some(&Credentials::Token("top".to_string()))
+        );
+        assert_eq!(npmrc.credentials_for("https://cdn.example.com/typescript.tgz"), None);
+
+        let npmrc = Npmrc::parse("_authToken=top\nalways-auth=true\n");
+        assert_eq!(
+            npmrc.credentials_for("https://cdn.example.com/typescript.tgz"),
+            Some(&Credentials::Token("top".to_string()))
+        );
+        assert_eq!(
+            Credentials::Token("top".to_string()).header_value(),
+            "Bearer top"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Compares the versions pinned in a project's toolchain with the versions available
+//! for download, for `notion outdated`.
+
+use semver::Version;
+
+/// The newest versions available to move a pinned version to.
+#[derive(Debug, PartialEq)]
+pub struct Updates {
+    /// The newest version with the pinned version's major and minor versions.
+    pub patch: Option<Version>,
+    /// The newest long-term support version, for tools that have them.
+    pub lts: Option<Version>,
+    /// The newest version of all.
+    pub latest: Option<Version>,
+}
+
+impl Updates {
+    /// Finds the updates for a pinned version among the available versions, each paired
+    /// with whether it is a long-term support version. Prereleases are never updates.
+    pub fn find<'a, I>(pinned: &Version, available: I) -> Updates
+    where
+        I: IntoIterator<Item = (&'a Version, bool)>,
+    {
+        let mut updates = Updates {
+            patch: None,
+            lts: None,
+            latest: None,
+        };
+        for (version, lts) in available {
+            if version.is_prerelease() {
+                continue;
+            }
+            if version.major == pinned.major && version.minor == pinned.minor {
+                keep_newest(&mut updates.patch, version);
+            }
+            if lts {
+                keep_newest(&mut updates.lts, version);
+            }
+            keep_newest(&mut updates.latest, version);
+        }
+        updates
+    }
+
+    /// Returns whether any version newer than the pinned version is available.
+    pub fn is_outdated(&self, pinned: &Version) -> bool {
+        self.latest.as_ref().map_or(false, |latest| latest > pinned)
+    }
+}
+
+fn keep_newest(newest: &mut Option<Version>, version: &Version) {
+    if newest.as_ref().map_or(true, |newest| version > newest) {
+        *newest = Some(version.clone());
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::Updates;
+    use semver::Version;
+
+    fn v(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    #[test]
+    fn test_find() {
+        let available = vec![
+            (v("11.1.0"), false),
+            (v("12.0.0-rc.1"), false),
+            (v("10.13.0"), true),
+            (v("10.12.0"), true),
+            (v("8.12.0"), true),
+            (v("8.11.4"), true),
+            (v("8.11.1"), true),
+        ];
+        let pinned = v("8.11.1");
+        let updates = Updates::find(&pinned, available.iter().map(|&(ref v, lts)| (v, lts)));
+        assert_eq!(
+            updates,
+            Updates {
+                patch: Some(v("8.11.4")),
+                lts: Some(v("10.13.0")),
+                latest: Some(v("11.1.0")),
+            }
+        );
+        assert!(updates.is_outdated(&pinned));
+        assert!(!updates.is_outdated(&v("11.1.0")));
+    }
+
+    #[test]
+    fn test_find_without_lts() {
+        let available = vec![v("1.12.3"), v("1.12.1"), v("1.9.4")];
+        let updates = Updates::find(&v("1.9.4"), available.iter().map(|v| (v, false)));
+        assert_eq!(updates.patch, Some(v("1.9.4")));
+        assert_eq!(updates.lts, None);
+        assert_eq!(updates.latest, Some(v("1.12.3")));
+    }
+}
@@ -0,0 +1,259 @@
+//! Provides support for installing packages from the npm registry as global tools,
+//! each run with the version of Node it was installed with.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use semver::Version;
+use serde_json;
+
+use config::Config;
+use distro::package::PackageDistro;
+use env;
+use fs::{create_dir_all, remove_dir_all, rename};
+use manifest::Manifest;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+use version::VersionSpec;
+
+#[cfg(unix)]
+const NPM: &'static str = "npm";
+
+#[cfg(windows)]
+const NPM: &'static str = "npm.cmd";
+
+/// A package installed as a global tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    /// The installed version of the package.
+    pub version: Version,
+    /// The version of Node the package was installed with, which always runs it.
+    pub node: Version,
+    /// The names of the executables the package provides.
+    pub bins: Vec<String>,
+    /// The integrity string of the tarball npm installed the package from, like
+    /// `sha512-<base64 digest>`, for later audits.
+    pub integrity: Option<String>,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not install package '{}' (npm exited with an error)", name)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct PackageInstallError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "LTS versions only apply to Node, not to the package '{}'", name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct PackageLtsError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not install the dependencies of package '{}' (npm exited with an error)",
+       name)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct PackageDependenciesError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "package '{}' does not provide a default executable to run", name)]
+#[notion_fail(code = "ExecutableNotFound")]
+pub(crate) struct PackageBinNotFoundError {
+    pub(crate) name: String,
+}
+
+/// A package fetched for a one-off run with `notion x`.
+pub struct OneOff {
+    /// The version of the package that was fetched.
+    pub version: Version,
+    /// The version of Node that installed the package's dependencies, which runs it.
+    pub node: Version,
+    /// The directory the package was unpacked into, along with its dependencies.
+    pub dir: PathBuf,
+    /// The script of the package's default executable.
+    pub bin: PathBuf,
+    /// The integrity string the package's tarball was verified against, if the
+    /// registry publishes one.
+    pub integrity: Option<String>,
+}
+
+/// The part of an installed package's manifest that `Manifest` doesn't keep.
+#[derive(Deserialize)]
+struct PackageVersion {
+    version: String,
+    /// The integrity of the tarball, which npm records when it installs a package.
+    #[serde(rename = "_integrity")]
+    integrity: Option<String>,
+}
+
+/// Installs a version of the named package matching `matching` from the npm registry,
+/// using the npm bundled with the specified (installed) version of Node. The package
+/// is installed into a fresh directory first, so that a failed install leaves any
+/// previously installed version in place.
+pub fn install(name: &str, matching: &VersionSpec, node: &Version) -> Fallible<Package> {
+    let spec = install_spec(name, matching)?;
+
+    let node_version = node.to_string();
+    let package_dir = path::package_dir(name)?;
+    let staging = package_dir.with_extension("installing");
+    if staging.exists() {
+        remove_dir_all(&staging).unknown()?;
+    }
+    create_dir_all(&staging).unknown()?;
+
+    let npm = path::node_version_bin_dir(&node_version)?.join(NPM);
+    let status = Command::new(&npm)
+        .args(&["install", "--global", "--prefix"])
+        .arg(&staging)
+        .arg(&spec)
+        .env("PATH", env::path_for_installed_node(&node_version))
+        .status()
+        .unknown()?;
+    if !status.success() {
+        remove_dir_all(&staging).unknown()?;
+        throw!(PackageInstallError {
+            name: name.to_string(),
+        });
+    }
+
+    if package_dir.exists() {
+        remove_dir_all(&package_dir).unknown()?;
+    }
+    rename(&staging, &package_dir).unknown()?;
+
+    let root = path::package_root_dir(name)?;
+    let mut contents = String::new();
+    File::open(root.join("package.json"))
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .unknown()?;
+    let installed: PackageVersion = serde_json::from_str(&contents).unknown()?;
+    let mut bins: Vec<String> = Manifest::for_dir(&root)?.bin.keys().cloned().collect();
+    bins.sort();
+
+    Ok(Package {
+        version: Version::parse(&installed.version).unknown()?,
+        node: node.clone(),
+        bins,
+        integrity: installed.integrity,
+    })
+}
+
+/// Produces the package spec that npm installs for a version of the named package
+/// matching `matching`, e.g. `typescript@>=3.0.0 <4.0.0`.
+pub(crate) fn install_spec(name: &str, matching: &VersionSpec) -> Fallible<String> {
+    Ok(match *matching {
+        VersionSpec::Latest => format!("{}@latest", name),
+        // npm separates the comparators of a range with spaces rather than commas.
+        VersionSpec::Semver(ref req) => format!("{}@{}", name, req.to_string().replace(", ", " ")),
+        VersionSpec::Channel(channel) => format!("{}@{}", name, channel),
+        VersionSpec::Tag(ref tag) => format!("{}@{}", name, tag),
+        VersionSpec::Lts(_) => throw!(PackageLtsError {
+            name: name.to_string(),
+        }),
+    })
+}
+
+/// Runs the specified version of Node's npm in a directory.
+fn npm(node: &Version, dir: &Path) -> Fallible<Command> {
+    let node_version = node.to_string();
+    let mut command = Command::new(path::node_version_bin_dir(&node_version)?.join(NPM));
+    command
+        .current_dir(dir)
+        .env("PATH", env::path_for_installed_node(&node_version));
+    Ok(command)
+}
+
+/// Finds the default executable of a package the way npx does: its only executable,
+/// or else the one named after the package (without its scope).
+fn default_bin(name: &str, manifest: &Manifest) -> Option<String> {
+    if manifest.bin.len() == 1 {
+        return manifest.bin.values().next().cloned();
+    }
+    let unscoped = match name.rfind('/') {
+        Some(index) => &name[index + 1..],
+        None => name,
+    };
+    manifest.bin.get(unscoped).cloned()
+}
+
+/// Fetches a version of the named package matching `matching` from the npm registry
+/// for a one-off run, installing its dependencies with the npm bundled with the
+/// specified (installed) version of Node. Packages are kept in a cache directory
+/// named after the checksum of their tarball, so running the same package again
+/// reuses the earlier fetch.
+pub fn fetch_one_off(
+    name: &str,
+    matching: &VersionSpec,
+    node: &Version,
+    config: &Config,
+) -> Fallible<OneOff> {
+    let distro = PackageDistro::public(name, matching, config)?;
+    let version = distro.version().clone();
+    let integrity = distro.integrity();
+    let dir = path::exec_package_dir(&distro.checksum()?)?;
+
+    if !dir.exists() {
+        let staging = dir.with_extension("installing");
+        distro.fetch(&staging)?;
+
+        let has_dependencies = !Manifest::for_dir(&staging)?.dependencies.is_empty();
+        if has_dependencies {
+            let status = npm(node, &staging)?
+                .args(&["install", "--production", "--no-package-lock"])
+                .status()
+                .unknown()?;
+            if !status.success() {
+                remove_dir_all(&staging).unknown()?;
+                throw!(PackageDependenciesError {
+                    name: name.to_string(),
+                });
+            }
+        }
+        rename(&staging, &dir).unknown()?;
+    }
+
+    let bin = match default_bin(name, &Manifest::for_dir(&dir)?) {
+        Some(bin) => dir.join(bin),
+        None => throw!(PackageBinNotFoundError {
+            name: name.to_string(),
+        }),
+    };
+    Ok(OneOff {
+        version,
+        node: node.clone(),
+        dir,
+        bin,
+        integrity,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::default_bin;
+    use manifest::Manifest;
+
+    #[test]
+    fn test_default_bin() {
+        let mut manifest = Manifest::empty();
+        assert_eq!(default_bin("cowsay", &manifest), None);
+
+        manifest
+            .bin
+            .insert("cowsay".to_string(), "./cli.js".to_string());
+        assert_eq!(default_bin("cowsay", &manifest), Some("./cli.js".to_string()));
+
+        // with several executables, the one named after the package (without its
+        // scope) is the default
+        manifest
+            .bin
+            .insert("cowthink".to_string(), "./think.js".to_string());
+        assert_eq!(default_bin("@cows/cowsay", &manifest), Some("./cli.js".to_string()));
+        assert_eq!(default_bin("cows", &manifest), None);
+    }
+}
@@ -19,12 +19,185 @@ cfg_if! {
     }
 }
 
+use std::path::{Path, PathBuf};
+
+#[cfg(windows)]
+use verbatim::PathExt;
+
+use env;
+use notion_fail::Fallible;
+
+// A read-only system install, provisioned by an administrator (e.g. by running Notion
+// with `NOTION_HOME` set to it), can be shared by every user through the
+// `NOTION_SYSTEM_HOME` environment variable. Its versions directory is laid out like
+// the user's:
+//
+// $NOTION_SYSTEM_HOME/
+//     versions/                                           system_versions_dir
+//         node/
+//             10.15.3/                                    system_version_dir("node", "10.15.3")
+//             ...
+//         yarn/
+//         npm/
+//         pnpm/
+//
+// The versions a user installs overlay the system's: each `*_version_dir` function
+// returns the user's directory for a version if it is installed there, the system's
+// if only the system install has it, and otherwise the user's, which is where every
+// install is written (the `user_*_version_dir` functions).
+
+/// The versions directory of the system install, if there is one.
+pub fn system_versions_dir() -> Option<PathBuf> {
+    env::system_home().map(|home| home.join("versions"))
+}
+
+/// The configuration file of the system install, if there is one, whose settings
+/// apply to every user under their own (see the `config::layers` module).
+pub fn system_config_file() -> Option<PathBuf> {
+    env::system_home().map(|home| home.join("config.toml"))
+}
+
+fn system_version_dir(tool: &str, version: &str) -> Option<PathBuf> {
+    system_versions_dir().map(|dir| dir.join(tool).join(version))
+}
+
+/// Selects the system's directory for a version over the user's only if the user
+/// hasn't installed the version and the system install has it.
+fn overlay(user: PathBuf, system: Option<PathBuf>) -> PathBuf {
+    match system {
+        Some(system) if !user.is_dir() && system.is_dir() => system,
+        _ => user,
+    }
+}
+
+pub fn user_node_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(node_versions_dir()?.join(version))
+}
+
+pub fn user_yarn_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(yarn_versions_dir()?.join(version))
+}
+
+pub fn user_npm_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(npm_versions_dir()?.join(version))
+}
+
+pub fn user_pnpm_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(pnpm_versions_dir()?.join(version))
+}
+
+pub fn node_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(overlay(user_node_version_dir(version)?, system_version_dir("node", version)))
+}
+
+pub fn yarn_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(overlay(user_yarn_version_dir(version)?, system_version_dir("yarn", version)))
+}
+
+pub fn npm_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(overlay(user_npm_version_dir(version)?, system_version_dir("npm", version)))
+}
+
+pub fn pnpm_version_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(overlay(user_pnpm_version_dir(version)?, system_version_dir("pnpm", version)))
+}
+
+/// The cache of the archives of custom builds of Node (see
+/// `Catalog::fetch_node_url`), which are kept apart from those of Node's own releases
+/// because they are named the same way.
+pub fn node_custom_cache_dir() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("custom"))
+}
+
 pub fn node_archive_file(version: &str) -> String {
-    format!("{}.{}", node_archive_root_dir(version), archive_extension())
+    node_archive_file_for(version, &node_arch())
+}
+
+/// The tarball of a Node version's source, which is the same on every platform.
+pub fn node_source_archive_file(version: &str) -> String {
+    format!("node-v{}.tar.gz", version)
+}
+
+/// The archive of a Node version built for the specified architecture.
+pub fn node_archive_file_for(version: &str, arch: &str) -> String {
+    format!("{}.{}", node_archive_root_dir_for(version, arch), archive_extension())
+}
+
+/// The xz-compressed archive of a Node version, which is smaller than the default
+/// archive, if Node publishes one for the current platform.
+pub fn node_xz_archive_file(version: &str) -> Option<String> {
+    node_xz_archive_file_for(version, &node_arch())
+}
+
+/// The xz-compressed archive of a Node version built for the specified architecture.
+pub fn node_xz_archive_file_for(version: &str, arch: &str) -> Option<String> {
+    xz_archive_extension()
+        .map(|extension| format!("{}.{}", node_archive_root_dir_for(version, arch), extension))
 }
 
 pub fn node_archive_root_dir(version: &str) -> String {
-    format!("node-v{}-{}-{}", version, OS, ARCH)
+    node_archive_root_dir_for(version, &node_arch())
+}
+
+/// The root directory of the archive of a Node version built for the specified
+/// architecture.
+pub fn node_archive_root_dir_for(version: &str, arch: &str) -> String {
+    format!("node-v{}-{}", version, node_platform_for(arch))
+}
+
+/// The platform component of a Node distribution's name, e.g. `linux-x64`, or
+/// `linux-x64-musl` for the unofficial builds for musl-based systems.
+pub fn node_platform() -> String {
+    node_platform_for(&node_arch())
+}
+
+/// The platform component of the name of a Node distribution built for the specified
+/// architecture.
+pub fn node_platform_for(arch: &str) -> String {
+    let platform = format!("{}-{}", OS, arch);
+    if is_musl() {
+        format!("{}-musl", platform)
+    } else {
+        platform
+    }
+}
+
+/// The system architecture to fetch Node distributions for, in the form Node's release
+/// names use: the host's architecture, unless the `NOTION_ARCH` environment variable
+/// overrides it (e.g. to provision a different architecture's Node). An x64 or x86
+/// build of Notion running under emulation on ARM64 Windows fetches ARM64 builds.
+pub fn node_arch() -> String {
+    match env::arch() {
+        Some(arch) => normalize_arch(&arch),
+        None => match host_arch().map(|arch| normalize_arch(&arch)) {
+            Some(ref arch) if arch == "arm64" => arch.clone(),
+            _ => ARCH.to_string(),
+        },
+    }
+}
+
+/// The architecture whose Node builds run under emulation on this platform, for the
+/// versions of Node that predate builds of its own: x64 on ARM64 Windows.
+pub fn node_emulated_arch() -> Option<&'static str> {
+    if OS == "win" && node_arch() == "arm64" {
+        Some("x64")
+    } else {
+        None
+    }
+}
+
+/// Converts the common alternative names of an architecture to the names Node's
+/// releases use, e.g. `x86_64` to `x64` and `aarch64` to `arm64`.
+fn normalize_arch(arch: &str) -> String {
+    let arch = arch.to_lowercase();
+    match &arch[..] {
+        "x86_64" | "amd64" => "x64".to_string(),
+        "i386" | "i686" | "ia32" => "x86".to_string(),
+        "aarch64" => "arm64".to_string(),
+        "arm" | "armv7" | "armhf" => "armv7l".to_string(),
+        "ppc64el" => "ppc64le".to_string(),
+        _ => arch,
+    }
 }
 
 pub fn yarn_archive_file(version: &str) -> String {
@@ -35,16 +208,59 @@ pub fn yarn_archive_root_dir(version: &str) -> String {
     format!("yarn-v{}", version)
 }
 
+pub fn npm_archive_file(version: &str) -> String {
+    format!("npm-{}.tgz", version)
+}
+
+pub fn pnpm_archive_file(version: &str) -> String {
+    format!("pnpm-{}.tgz", version)
+}
+
+/// The name of the cached tarball for a version of a package from the npm registry.
+/// The scope of a scoped package (`@scope/name`) becomes part of the file name.
+pub fn package_archive_file(name: &str, version: &str) -> String {
+    format!("{}-{}.tgz", name.trim_left_matches('@').replace('/', "-"), version)
+}
+
+/// The extended-length (`\\?\`) form of an absolute path on Windows, which isn't held to
+/// the legacy 260 byte path limit that the deeply nested files of installed packages
+/// can exceed. Relative paths, and every path on other platforms, are left unchanged.
+pub fn extended(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        if path.is_absolute() {
+            return PathBuf::from(path.to_verbatim());
+        }
+    }
+    path.to_path_buf()
+}
+
+/// The root directory of the contents of a package tarball from the npm registry.
+pub fn npm_archive_root_dir() -> String {
+    String::from("package")
+}
+
 #[cfg(test)]
 pub mod tests {
 
     use super::*;
+    use std::fs::create_dir;
+    use tempfile::tempdir;
 
     #[test]
     fn test_node_archive_file() {
         assert_eq!(
             node_archive_file("1.2.3"),
-            format!("node-v1.2.3-{}-{}.{}", OS, ARCH, archive_extension())
+            format!("node-v1.2.3-{}.{}", node_platform(), archive_extension())
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_node_xz_archive_file() {
+        assert_eq!(
+            node_xz_archive_file("1.2.3"),
+            Some(format!("node-v1.2.3-{}.tar.xz", node_platform()))
         );
     }
 
@@ -52,8 +268,70 @@ pub mod tests {
     fn test_node_archive_root_dir() {
         assert_eq!(
             node_archive_root_dir("1.2.3"),
-            format!("node-v1.2.3-{}-{}", OS, ARCH)
+            format!("node-v1.2.3-{}", node_platform())
+        );
+    }
+
+    #[test]
+    fn test_overlay() {
+        let user = tempdir().unwrap();
+        let system = tempdir().unwrap();
+        create_dir(system.path().join("10.15.3")).unwrap();
+        create_dir(user.path().join("8.9.4")).unwrap();
+        create_dir(system.path().join("8.9.4")).unwrap();
+
+        // Only the system has it.
+        assert_eq!(
+            overlay(user.path().join("10.15.3"), Some(system.path().join("10.15.3"))),
+            system.path().join("10.15.3")
+        );
+        // Both have it.
+        assert_eq!(
+            overlay(user.path().join("8.9.4"), Some(system.path().join("8.9.4"))),
+            user.path().join("8.9.4")
+        );
+        // Neither has it, so an install goes to the user's.
+        assert_eq!(
+            overlay(user.path().join("6.0.0"), Some(system.path().join("6.0.0"))),
+            user.path().join("6.0.0")
+        );
+        assert_eq!(overlay(user.path().join("6.0.0"), None), user.path().join("6.0.0"));
+    }
+
+    #[test]
+    fn test_extended() {
+        assert_eq!(extended(Path::new("node_modules")), PathBuf::from("node_modules"));
+
+        #[cfg(unix)]
+        assert_eq!(extended(Path::new("/tmp/notion")), PathBuf::from("/tmp/notion"));
+
+        #[cfg(windows)]
+        assert_eq!(
+            extended(Path::new(r"C:\ProgramData\Notion")),
+            PathBuf::from(r"\\?\C:\ProgramData\Notion")
+        );
+    }
+
+    #[test]
+    fn test_normalize_arch() {
+        assert_eq!(normalize_arch("x64"), "x64");
+        assert_eq!(normalize_arch("x86_64"), "x64");
+        assert_eq!(normalize_arch("AArch64"), "arm64");
+        assert_eq!(normalize_arch("armv7"), "armv7l");
+        assert_eq!(normalize_arch("ppc64le"), "ppc64le");
+    }
+
+    #[test]
+    fn test_node_archive_file_for() {
+        assert_eq!(
+            node_archive_file_for("10.8.0", "x64"),
+            format!("node-v10.8.0-{}.{}", node_platform_for("x64"), archive_extension())
+        );
+        assert_eq!(
+            node_archive_root_dir_for("10.8.0", "arm64"),
+            format!("node-v10.8.0-{}", node_platform_for("arm64"))
         );
+        assert_eq!(node_archive_file("10.8.0"), node_archive_file_for("10.8.0", &node_arch()));
     }
 
     #[test]
@@ -68,4 +346,26 @@ pub mod tests {
     fn yarn_node_archive_root_dir() {
         assert_eq!(yarn_archive_root_dir("1.2.3"), "yarn-v1.2.3".to_string());
     }
+
+    #[test]
+    fn test_npm_archive_file() {
+        assert_eq!(npm_archive_file("5.6.0"), "npm-5.6.0.tgz".to_string());
+    }
+
+    #[test]
+    fn test_pnpm_archive_file() {
+        assert_eq!(pnpm_archive_file("2.9.0"), "pnpm-2.9.0.tgz".to_string());
+    }
+
+    #[test]
+    fn test_package_archive_file() {
+        assert_eq!(
+            package_archive_file("typescript", "3.1.6"),
+            "typescript-3.1.6.tgz".to_string()
+        );
+        assert_eq!(
+            package_archive_file("@vue/cli", "3.2.1"),
+            "vue-cli-3.2.1.tgz".to_string()
+        );
+    }
 }
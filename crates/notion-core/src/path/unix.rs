@@ -2,11 +2,16 @@
 //! in a standard Notion layout in Unix-based operating systems.
 
 use std::{env, io};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::os::unix;
 
+use env as notion_env;
+use glob::glob;
 use notion_fail::{ExitCode, Fallible, NotionFail};
 
+use super::{node_version_dir, npm_version_dir, yarn_version_dir};
+
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "environment variable 'HOME' is not set")]
 #[notion_fail(code = "EnvironmentError")]
@@ -35,11 +40,29 @@ cfg_if! {
     } else if #[cfg(target_arch = "x86_64")] {
         /// The system architecture component of a Node distribution tarball's name.
         pub const ARCH: &'static str = "x64";
+    } else if #[cfg(target_arch = "aarch64")] {
+        /// The system architecture component of a Node distribution tarball's name.
+        pub const ARCH: &'static str = "arm64";
+    } else if #[cfg(target_arch = "arm")] {
+        /// The system architecture component of a Node distribution tarball's name.
+        pub const ARCH: &'static str = "armv7l";
+    } else if #[cfg(all(target_arch = "powerpc64", target_endian = "little"))] {
+        /// The system architecture component of a Node distribution tarball's name.
+        pub const ARCH: &'static str = "ppc64le";
+    } else if #[cfg(target_arch = "s390x")] {
+        /// The system architecture component of a Node distribution tarball's name.
+        pub const ARCH: &'static str = "s390x";
     } else {
-        compile_error!("Unsupported target_arch variant of unix (expected 'x86' or 'x64').");
+        compile_error!("Unsupported target_arch variant of unix (expected 'x86', 'x64', 'arm64', 'armv7l', 'ppc64le' or 's390x').");
     }
 }
 
+/// The architecture of the host, where it can differ from `ARCH`. Notion only runs
+/// under emulation on Windows, so it is always `ARCH` here.
+pub fn host_arch() -> Option<String> {
+    None
+}
+
 // ~/
 //     .notion/
 //         cache/                                          cache_dir
@@ -48,13 +71,44 @@ cfg_if! {
 //                 node-dist-v6.11.3-linux-x64.tar.gz
 //                 node-dist-v8.6.0-linux-x64.tar.gz
 //                 ...
+//             npm/                                        npm_cache_dir
+//                 npm-5.6.0.tgz                           npm_archive_file("5.6.0")
+//                 ...
+//             pnpm/                                       pnpm_cache_dir
+//                 pnpm-2.9.0.tgz                          pnpm_archive_file("2.9.0")
+//                 ...
+//             packages/                                   package_cache_dir
+//                 typescript-3.1.6.tgz                    package_archive_file("typescript", "3.1.6")
+//                 ...
+//             exec/                                       exec_cache_dir
+//                 3b8f...e01c/                            exec_package_dir("3b8f...e01c")
+//                 ...
+//             platform/                                   platform_cache_dir
+//                 5e1a0c3f9b2d7e84
+//                 ...
+//             resolve/                                    resolve_cache_dir
+//                 9c04d2e1b7a3f658.json
+//                 ...
+//             latest-release                              latest_release_file
 //         versions/                                       versions_dir
 //             node/                                       node_versions_dir
-//                 4.8.4/                                  node_version_dir("4.8.4")
+//                 4.8.4/                                  user_node_version_dir("4.8.4")
 //                   bin/                                  node_version_bin_dir("4.8.4")
 //                 6.11.3/
 //                 8.6.0/
 //                 ...
+//             npm/                                        npm_versions_dir
+//                 5.6.0/                                  user_npm_version_dir("5.6.0")
+//                   bin/                                  npm_version_bin_dir("5.6.0")
+//                 ...
+//             pnpm/                                       pnpm_versions_dir
+//                 2.9.0/                                  user_pnpm_version_dir("2.9.0")
+//                 ...
+//         packages/                                       packages_dir
+//             typescript/                                 package_dir("typescript")
+//                 bin/                                    package_bin_dir("typescript")
+//                 lib/node_modules/typescript/            package_root_dir("typescript")
+//             ...
 //         bin/                                            shim_dir
 //             node                                        shim_file("node")
 //             npm
@@ -65,13 +119,64 @@ cfg_if! {
 //         launchscript                                    launchscript_file
 //         config.toml                                     user_config_file
 //         catalog.toml                                    user_catalog_file
+//         references.toml                                 references_file
+//         telemetry/                                      telemetry_dir
+//             enabled                                     telemetry_consent_file
+//             queue.jsonl                                 telemetry_queue_file
+//         keys/
+//             node.gpg                                    node_keyring_file
+//
+// The `NOTION_HOME` environment variable moves `~/.notion` elsewhere. On Linux, setting
+// `NOTION_XDG` splits it across the XDG base directories instead: the cache moves to
+// `$XDG_CACHE_HOME/notion` (`~/.cache/notion`), `config.toml` to `$XDG_CONFIG_HOME/notion`
+// (`~/.config/notion`), and everything else to `$XDG_DATA_HOME/notion`
+// (`~/.local/share/notion`), or to `NOTION_HOME` if that is set too.
 
 fn notion_home() -> Fallible<PathBuf> {
-    let home = env::home_dir().ok_or(NoHomeEnvVar)?;
-    Ok(home.join(".notion"))
+    if let Some(home) = notion_env::notion_home() {
+        return Ok(home);
+    }
+    if use_xdg() {
+        return xdg_root("XDG_DATA_HOME", ".local/share");
+    }
+    Ok(home_dir()?.join(".notion"))
+}
+
+fn home_dir() -> Fallible<PathBuf> {
+    Ok(env::home_dir().ok_or(NoHomeEnvVar)?)
+}
+
+fn use_xdg() -> bool {
+    cfg!(target_os = "linux") && notion_env::xdg()
+}
+
+/// Returns Notion's directory within one of the XDG base directories.
+fn xdg_root(var: &str, default: &str) -> Fallible<PathBuf> {
+    Ok(xdg_dir(env::var_os(var), &home_dir()?, default).join("notion"))
+}
+
+/// Returns the base directory an XDG environment variable selects, or its default within
+/// the home directory if the variable is unset, empty, or (which the specification says
+/// to ignore) relative.
+fn xdg_dir(value: Option<OsString>, home: &Path, default: &str) -> PathBuf {
+    match value.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home.join(default),
+    }
+}
+
+/// Returns the directory containing the user's `config.toml`.
+fn config_root() -> Fallible<PathBuf> {
+    if use_xdg() {
+        return xdg_root("XDG_CONFIG_HOME", ".config");
+    }
+    notion_home()
 }
 
 pub fn cache_dir() -> Fallible<PathBuf> {
+    if use_xdg() {
+        return xdg_root("XDG_CACHE_HOME", ".cache");
+    }
     Ok(notion_home()?.join("cache"))
 }
 
@@ -82,6 +187,42 @@ pub fn yarn_cache_dir() -> Fallible<PathBuf> {
     Ok(cache_dir()?.join("yarn"))
 }
 
+pub fn yarn_index_file() -> Fallible<PathBuf> {
+    Ok(yarn_cache_dir()?.join("index.json"))
+}
+
+pub fn npm_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("npm"))
+}
+
+pub fn pnpm_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("pnpm"))
+}
+
+pub fn package_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("packages"))
+}
+
+pub fn exec_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("exec"))
+}
+
+pub fn exec_package_dir(checksum: &str) -> Fallible<PathBuf> {
+    Ok(exec_cache_dir()?.join(checksum))
+}
+
+pub fn platform_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("platform"))
+}
+
+pub fn resolve_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("resolve"))
+}
+
+pub fn latest_release_file() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("latest-release"))
+}
+
 pub fn node_index_file() -> Fallible<PathBuf> {
     Ok(node_cache_dir()?.join("index.json"))
 }
@@ -90,10 +231,36 @@ pub fn node_index_expiry_file() -> Fallible<PathBuf> {
     Ok(node_cache_dir()?.join("index.json.expires"))
 }
 
+pub fn node_index_etag_file() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("index.json.etag"))
+}
+
+pub fn node_index_refresh_file() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("index.json.refresh"))
+}
+
+/// Returns whether the host's C library is musl, as on Alpine Linux, which the official
+/// Node distributions don't run against. A musl-based system is recognized by musl's
+/// dynamic loader being installed without glibc's.
+pub fn is_musl() -> bool {
+    cfg!(target_os = "linux") && has_file("/lib/ld-musl-*.so.1") && !has_file("/lib*/ld-linux*.so.*")
+}
+
+fn has_file(pattern: &str) -> bool {
+    glob(pattern)
+        .map(|mut paths| paths.next().is_some())
+        .unwrap_or(false)
+}
+
 pub fn archive_extension() -> String {
     String::from("tar.gz")
 }
 
+/// The extension of the smaller, xz-compressed Node archives, where Node publishes them.
+pub fn xz_archive_extension() -> Option<String> {
+    Some(String::from("tar.xz"))
+}
+
 pub fn versions_dir() -> Fallible<PathBuf> {
     Ok(notion_home()?.join("versions"))
 }
@@ -106,12 +273,12 @@ pub fn yarn_versions_dir() -> Fallible<PathBuf> {
     Ok(versions_dir()?.join("yarn"))
 }
 
-pub fn node_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(node_versions_dir()?.join(version))
+pub fn npm_versions_dir() -> Fallible<PathBuf> {
+    Ok(versions_dir()?.join("npm"))
 }
 
-pub fn yarn_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(yarn_versions_dir()?.join(version))
+pub fn pnpm_versions_dir() -> Fallible<PathBuf> {
+    Ok(versions_dir()?.join("pnpm"))
 }
 
 pub fn node_version_bin_dir(version: &str) -> Fallible<PathBuf> {
@@ -122,11 +289,31 @@ pub fn yarn_version_bin_dir(version: &str) -> Fallible<PathBuf> {
     Ok(yarn_version_dir(version)?.join("bin"))
 }
 
+pub fn npm_version_bin_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(npm_version_dir(version)?.join("bin"))
+}
+
 // 3rd-party binaries installed globally for this node version
 pub fn node_version_3p_bin_dir(version: &str) -> Fallible<PathBuf> {
     Ok(node_version_dir(version)?.join("lib/node_modules/.bin"))
 }
 
+pub fn packages_dir() -> Fallible<PathBuf> {
+    Ok(notion_home()?.join("packages"))
+}
+
+pub fn package_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(packages_dir()?.join(name))
+}
+
+pub fn package_bin_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(package_dir(name)?.join("bin"))
+}
+
+pub fn package_root_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(package_dir(name)?.join("lib").join("node_modules").join(name))
+}
+
 pub fn notion_file() -> Fallible<PathBuf> {
     Ok(notion_home()?.join("notion"))
 }
@@ -148,13 +335,68 @@ pub fn launchscript_file() -> Fallible<PathBuf> {
 }
 
 pub fn user_config_file() -> Fallible<PathBuf> {
-    Ok(notion_home()?.join("config.toml"))
+    Ok(config_root()?.join("config.toml"))
 }
 
 pub fn user_catalog_file() -> Fallible<PathBuf> {
     Ok(notion_home()?.join("catalog.toml"))
 }
 
-pub fn create_file_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
+pub fn references_file() -> Fallible<PathBuf> {
+    Ok(notion_home()?.join("references.toml"))
+}
+
+pub fn telemetry_dir() -> Fallible<PathBuf> {
+    Ok(notion_home()?.join("telemetry"))
+}
+
+pub fn telemetry_consent_file() -> Fallible<PathBuf> {
+    Ok(telemetry_dir()?.join("enabled"))
+}
+
+pub fn telemetry_queue_file() -> Fallible<PathBuf> {
+    Ok(telemetry_dir()?.join("queue.jsonl"))
+}
+
+pub fn node_keyring_file() -> Fallible<PathBuf> {
+    Ok(notion_home()?.join("keys").join("node.gpg"))
+}
+
+pub fn node_version_file_hints_file() -> Fallible<PathBuf> {
+    Ok(notion_home()?.join("hints").join("node-version-files"))
+}
+
+pub fn path_shadowing_hints_file() -> Fallible<PathBuf> {
+    Ok(notion_home()?.join("hints").join("path-shadowing"))
+}
+
+/// Creates a shim as a symbolic link to the executable it runs.
+pub fn create_shim_file(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
     unix::fs::symlink(src, dst)
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::xdg_dir;
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_xdg_dir() {
+        let home = Path::new("/home/user");
+        assert_eq!(xdg_dir(None, home, ".cache"), PathBuf::from("/home/user/.cache"));
+        assert_eq!(
+            xdg_dir(Some(OsString::from("/mnt/cache")), home, ".cache"),
+            PathBuf::from("/mnt/cache")
+        );
+        assert_eq!(
+            xdg_dir(Some(OsString::from("cache")), home, ".cache"),
+            PathBuf::from("/home/user/.cache")
+        );
+        assert_eq!(
+            xdg_dir(Some(OsString::new()), home, ".cache"),
+            PathBuf::from("/home/user/.cache")
+        );
+    }
+}
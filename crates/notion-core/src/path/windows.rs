@@ -1,15 +1,17 @@
 //! Provides functions for determining the paths of files and directories
 //! in a standard Notion layout in Windows operating systems.
 
+use std::fs::{File, OpenOptions};
 use std::path::PathBuf;
-#[cfg(windows)]
-use std::os::windows;
 use std::io;
 
 use winfolder;
 
+use env as notion_env;
 use notion_fail::Fallible;
 
+use super::{node_version_dir, npm_version_dir, yarn_version_dir};
+
 // These are taken from: https://nodejs.org/dist/index.json and are used
 // by `path::archive_root_dir` to determine the root directory of the
 // contents of a Node installer archive.
@@ -21,11 +23,27 @@ cfg_if! {
         pub const ARCH: &'static str = "x86";
     } else if #[cfg(target_arch = "x86_64")] {
         pub const ARCH: &'static str = "x64";
+    } else if #[cfg(target_arch = "aarch64")] {
+        pub const ARCH: &'static str = "arm64";
     } else {
-        compile_error!("Unsupported target_arch variant of Windows (expected 'x86' or 'x64').");
+        compile_error!("Unsupported target_arch variant of Windows (expected 'x86', 'x64' or 'arm64').");
     }
 }
 
+/// The architecture of the host as Windows names it (e.g. `ARM64`), which differs
+/// from `ARCH` when an x64 or x86 build of Notion runs under emulation. It is read
+/// from the system environment in the registry, which emulation doesn't change, unlike
+/// the environment of the process.
+pub fn host_arch() -> Option<String> {
+    use winreg::enums::HKEY_LOCAL_MACHINE;
+    use winreg::RegKey;
+
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey("SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment")
+        .and_then(|environment| environment.get_value("PROCESSOR_ARCHITECTURE"))
+        .ok()
+}
+
 // C:\
 //     ProgramData\
 //         Notion\
@@ -35,13 +53,44 @@ cfg_if! {
 //                     node-v6.11.3-win-x64.zip
 //                     node-v8.6.0-win-x64.zip
 //                     ...
+//                 npm\                                npm_cache_dir
+//                     npm-5.6.0.tgz                   npm_archive_file("5.6.0")
+//                     ...
+//                 pnpm\                               pnpm_cache_dir
+//                     pnpm-2.9.0.tgz                  pnpm_archive_file("2.9.0")
+//                     ...
+//                 packages\                           package_cache_dir
+//                     typescript-3.1.6.tgz            package_archive_file("typescript", "3.1.6")
+//                     ...
+//                 exec\                               exec_cache_dir
+//                     3b8f...e01c\                    exec_package_dir("3b8f...e01c")
+//                     ...
+//                 platform\                           platform_cache_dir
+//                     5e1a0c3f9b2d7e84
+//                     ...
+//                 resolve\                            resolve_cache_dir
+//                     9c04d2e1b7a3f658.json
+//                     ...
+//                 latest-release                      latest_release_file
 //             versions\                               versions_dir
 //                 node\                               node_versions_dir
-//                     4.8.4\                          node_version_dir("4.8.4")
+//                     4.8.4\                          user_node_version_dir("4.8.4")
 //                                                     node_version_bin_dir("4.8.4")
 //                     6.11.3\
 //                     8.6.0\
 //                     ...
+//                 npm\                                npm_versions_dir
+//                     5.6.0\                          user_npm_version_dir("5.6.0")
+//                         bin\                        npm_version_bin_dir("5.6.0")
+//                     ...
+//                 pnpm\                               pnpm_versions_dir
+//                     2.9.0\                          user_pnpm_version_dir("2.9.0")
+//                     ...
+//             packages\                               packages_dir
+//                 typescript\                         package_dir("typescript")
+//                                                     package_bin_dir("typescript")
+//                     node_modules\typescript\        package_root_dir("typescript")
+//                 ...
 //             launchbin.exe                           launchbin_file
 //             launchscript.exe                        launchscript_file
 
@@ -66,6 +115,42 @@ pub fn yarn_cache_dir() -> Fallible<PathBuf> {
     Ok(cache_dir()?.join("yarn"))
 }
 
+pub fn yarn_index_file() -> Fallible<PathBuf> {
+    Ok(yarn_cache_dir()?.join("index.json"))
+}
+
+pub fn npm_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("npm"))
+}
+
+pub fn pnpm_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("pnpm"))
+}
+
+pub fn package_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("packages"))
+}
+
+pub fn exec_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("exec"))
+}
+
+pub fn exec_package_dir(checksum: &str) -> Fallible<PathBuf> {
+    Ok(exec_cache_dir()?.join(checksum))
+}
+
+pub fn platform_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("platform"))
+}
+
+pub fn resolve_cache_dir() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("resolve"))
+}
+
+pub fn latest_release_file() -> Fallible<PathBuf> {
+    Ok(cache_dir()?.join("latest-release"))
+}
+
 pub fn node_index_file() -> Fallible<PathBuf> {
     Ok(node_cache_dir()?.join("index.json"))
 }
@@ -74,10 +159,28 @@ pub fn node_index_expiry_file() -> Fallible<PathBuf> {
     Ok(node_cache_dir()?.join("index.json.expires"))
 }
 
+pub fn node_index_etag_file() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("index.json.etag"))
+}
+
+pub fn node_index_refresh_file() -> Fallible<PathBuf> {
+    Ok(node_cache_dir()?.join("index.json.refresh"))
+}
+
+/// Returns whether the host's C library is musl, which is never the case on Windows.
+pub fn is_musl() -> bool {
+    false
+}
+
 pub fn archive_extension() -> String {
     String::from("zip")
 }
 
+/// The extension of the smaller, xz-compressed Node archives, where Node publishes them.
+pub fn xz_archive_extension() -> Option<String> {
+    None
+}
+
 pub fn versions_dir() -> Fallible<PathBuf> {
     Ok(program_data_root()?.join("versions"))
 }
@@ -90,12 +193,12 @@ pub fn yarn_versions_dir() -> Fallible<PathBuf> {
     Ok(versions_dir()?.join("yarn"))
 }
 
-pub fn node_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(node_versions_dir()?.join(version))
+pub fn npm_versions_dir() -> Fallible<PathBuf> {
+    Ok(versions_dir()?.join("npm"))
 }
 
-pub fn yarn_version_dir(version: &str) -> Fallible<PathBuf> {
-    Ok(yarn_versions_dir()?.join(version))
+pub fn pnpm_versions_dir() -> Fallible<PathBuf> {
+    Ok(versions_dir()?.join("pnpm"))
 }
 
 pub fn node_version_bin_dir(version: &str) -> Fallible<PathBuf> {
@@ -106,12 +209,32 @@ pub fn yarn_version_bin_dir(version: &str) -> Fallible<PathBuf> {
     Ok(yarn_version_dir(version)?.join("bin"))
 }
 
+pub fn npm_version_bin_dir(version: &str) -> Fallible<PathBuf> {
+    Ok(npm_version_dir(version)?.join("bin"))
+}
+
 // 3rd-party binaries installed globally for this node version
 pub fn node_version_3p_bin_dir(version: &str) -> Fallible<PathBuf> {
     // ISSUE (#90) Figure out where binaries are globally installed on Windows
     unimplemented!("global 3rd party executables not yet implemented for Windows")
 }
 
+pub fn packages_dir() -> Fallible<PathBuf> {
+    Ok(program_data_root()?.join("packages"))
+}
+
+pub fn package_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(packages_dir()?.join(name))
+}
+
+pub fn package_bin_dir(name: &str) -> Fallible<PathBuf> {
+    package_dir(name)
+}
+
+pub fn package_root_dir(name: &str) -> Fallible<PathBuf> {
+    Ok(package_dir(name)?.join("node_modules").join(name))
+}
+
 pub fn launchbin_file() -> Fallible<PathBuf> {
     Ok(program_data_root()?.join("launchbin.exe"))
 }
@@ -130,7 +253,15 @@ pub fn launchscript_file() -> Fallible<PathBuf> {
 //                 npx.exe
 //                 ...
 
+// The `NOTION_HOME` environment variable moves these files elsewhere. It doesn't move
+// the files in AppData and ProgramData, since the shell profile sets it to this
+// directory, which only administrators can write to.
+
 fn program_files_root() -> Fallible<PathBuf> {
+    if let Some(home) = notion_env::notion_home() {
+        return Ok(home);
+    }
+
     #[cfg(all(windows, target_arch = "x86"))]
     return Ok(winfolder::Folder::ProgramFiles.path().join("Notion"));
 
@@ -162,6 +293,12 @@ pub fn shim_file(toolname: &str) -> Fallible<PathBuf> {
 //                     Notion\
 //                         config.toml                 user_config_file
 //                         catalog.toml                user_catalog_file
+//                         references.toml             references_file
+//                         telemetry\                  telemetry_dir
+//                             enabled                 telemetry_consent_file
+//                             queue.jsonl             telemetry_queue_file
+//                         keys\
+//                             node.gpg                node_keyring_file
 
 fn local_data_root() -> Fallible<PathBuf> {
     #[cfg(windows)]
@@ -180,11 +317,40 @@ pub fn user_catalog_file() -> Fallible<PathBuf> {
     Ok(local_data_root()?.join("catalog.toml"))
 }
 
-pub fn create_file_symlink(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
-    #[cfg(windows)]
-    return windows::fs::symlink_file(src, dst);
+pub fn references_file() -> Fallible<PathBuf> {
+    Ok(local_data_root()?.join("references.toml"))
+}
 
-    // "universal-docs" is built on a Unix machine, so we can't include Windows-specific libs
-    #[cfg(feature = "universal-docs")]
-    unimplemented!()
+pub fn telemetry_dir() -> Fallible<PathBuf> {
+    Ok(local_data_root()?.join("telemetry"))
+}
+
+pub fn telemetry_consent_file() -> Fallible<PathBuf> {
+    Ok(telemetry_dir()?.join("enabled"))
+}
+
+pub fn telemetry_queue_file() -> Fallible<PathBuf> {
+    Ok(telemetry_dir()?.join("queue.jsonl"))
+}
+
+pub fn node_keyring_file() -> Fallible<PathBuf> {
+    Ok(local_data_root()?.join("keys").join("node.gpg"))
+}
+
+pub fn node_version_file_hints_file() -> Fallible<PathBuf> {
+    Ok(local_data_root()?.join("hints").join("node-version-files"))
+}
+
+pub fn path_shadowing_hints_file() -> Fallible<PathBuf> {
+    Ok(local_data_root()?.join("hints").join("path-shadowing"))
+}
+
+/// Creates a shim as a copy of the executable it runs, since creating a symbolic link
+/// on Windows takes elevated privileges (or developer mode). The launchers dispatch on
+/// the name they are run by, so a copy works as well as a link would. Like creating a
+/// link, this fails if the shim already exists.
+pub fn create_shim_file(src: PathBuf, dst: PathBuf) -> Result<(), io::Error> {
+    let mut shim = OpenOptions::new().write(true).create_new(true).open(&dst)?;
+    io::copy(&mut File::open(&src)?, &mut shim)?;
+    Ok(())
 }
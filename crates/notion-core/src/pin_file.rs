@@ -0,0 +1,193 @@
+//! Provides functions for reading and writing `.notion.toml` pin files, which pin a
+//! toolchain for projects that don't have (or don't want to use) a `package.json` file.
+//!
+//! A pin file uses the same keys as the `toolchain` section of `package.json`:
+//!
+//! ```toml
+//! [toolchain]
+//! node = "10.11.0"
+//! yarn = "1.10.1"
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use toml;
+use toml::value::Table;
+
+use fs::{read_file_opt, write_atomic};
+use manifest::ToolchainManifest;
+use manifest::serial;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use plan::Plan;
+
+/// The name of the pin file.
+pub const PIN_FILE: &'static str = ".notion.toml";
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct PinFileParseError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+impl PinFileParseError {
+    pub(crate) fn new(file: &Path, error: &toml::de::Error) -> Self {
+        PinFileParseError {
+            file: file.display().to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PinFile {
+    toolchain: Option<serial::ToolchainManifest>,
+}
+
+/// Reads the toolchain pinned by the specified pin file, if the file exists and pins one.
+pub fn read_toolchain(file: &Path) -> Fallible<Option<ToolchainManifest>> {
+    let contents = match read_file_opt(&PathBuf::from(file)).unknown()? {
+        Some(contents) => contents,
+        None => {
+            return Ok(None);
+        }
+    };
+    let pin_file: PinFile =
+        toml::from_str(&contents).with_context(|error| PinFileParseError::new(file, error))?;
+    match pin_file.toolchain {
+        Some(toolchain) => Ok(Some(toolchain.into_toolchain_manifest()?)),
+        None => Ok(None),
+    }
+}
+
+/// Writes the input ToolchainManifest to the `toolchain` table of the pin file, creating
+/// the file if necessary. Any other tables in the file are kept.
+pub fn update_toolchain(toolchain: serial::ToolchainManifest, file: &Path) -> Fallible<()> {
+    let mut plan = Plan::new();
+    plan_update_toolchain(toolchain, file, &mut plan)?;
+    plan.write_files()
+}
+
+/// Plans the write of `update_toolchain`, on top of the writes already planned.
+pub(crate) fn plan_update_toolchain(
+    toolchain: serial::ToolchainManifest,
+    file: &Path,
+    plan: &mut Plan,
+) -> Fallible<()> {
+    let mut document = parse_document(file, plan.contents(file)?)?;
+    document.insert(
+        "toolchain".to_string(),
+        toml::Value::try_from(toolchain).unknown()?,
+    );
+    let contents = toml::to_string(&toml::Value::Table(document)).unknown()?;
+    plan.write(file.to_path_buf(), contents)
+}
+
+/// Removes the `toolchain` table from the pin file, or only its `tool` key if one is
+/// specified. Removing the last remaining key removes the table as well. Returns `false`
+/// if there was nothing to remove, in which case the file is untouched.
+pub fn remove_toolchain(tool: Option<&str>, file: &Path) -> Fallible<bool> {
+    let mut document = read_document(file)?;
+    let removed = match tool {
+        None => document.remove("toolchain").is_some(),
+        Some(tool) => {
+            let (removed, now_empty) = match document
+                .get_mut("toolchain")
+                .and_then(|toolchain| toolchain.as_table_mut())
+            {
+                Some(toolchain) => (toolchain.remove(tool).is_some(), toolchain.is_empty()),
+                None => (false, false),
+            };
+            if now_empty {
+                document.remove("toolchain");
+            }
+            removed
+        }
+    };
+
+    if removed {
+        write_document(file, document)?;
+    }
+    Ok(removed)
+}
+
+fn read_document(file: &Path) -> Fallible<Table> {
+    parse_document(file, read_file_opt(&PathBuf::from(file)).unknown()?)
+}
+
+fn parse_document(file: &Path, contents: Option<String>) -> Fallible<Table> {
+    match contents {
+        Some(contents) => {
+            toml::from_str(&contents).with_context(|error| PinFileParseError::new(file, error))
+        }
+        None => Ok(Table::new()),
+    }
+}
+
+fn write_document(file: &Path, document: Table) -> Fallible<()> {
+    let contents = toml::to_string(&toml::Value::Table(document)).unknown()?;
+    write_atomic(file, contents)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+    use semver::Version;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_update_and_read_toolchain() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let file = dir.path().join(PIN_FILE);
+        assert!(read_toolchain(&file).unwrap().is_none());
+
+        let toolchain =
+            serial::ToolchainManifest::new("10.11.0".to_string(), None, None, None);
+        update_toolchain(toolchain, &file).unwrap();
+        let toolchain = read_toolchain(&file).unwrap().unwrap();
+        assert_eq!(toolchain.node, Version::parse("10.11.0").unwrap());
+        assert_eq!(toolchain.yarn, None);
+    }
+
+    #[test]
+    fn test_update_keeps_other_tables() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let file = dir.path().join(PIN_FILE);
+        fs::write(
+            &file,
+            "[toolchain]\nnode = \"8.9.4\"\n\n[scripts]\nbuild = \"cargo build\"\n",
+        ).unwrap();
+
+        let toolchain = serial::ToolchainManifest::new(
+            "10.11.0".to_string(),
+            Some("1.10.1".to_string()),
+            None,
+            None,
+        );
+        update_toolchain(toolchain, &file).unwrap();
+        let contents = fs::read_to_string(&file).unwrap();
+        assert!(contents.contains("build = \"cargo build\""));
+        let toolchain = read_toolchain(&file).unwrap().unwrap();
+        assert_eq!(toolchain.node, Version::parse("10.11.0").unwrap());
+        assert_eq!(toolchain.yarn, Some(Version::parse("1.10.1").unwrap()));
+    }
+
+    #[test]
+    fn test_remove_toolchain() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let file = dir.path().join(PIN_FILE);
+        fs::write(&file, "[toolchain]\nnode = \"8.9.4\"\nyarn = \"1.2.0\"\n").unwrap();
+
+        assert!(remove_toolchain(Some("yarn"), &file).unwrap());
+        let toolchain = read_toolchain(&file).unwrap().unwrap();
+        assert_eq!(toolchain.yarn, None);
+
+        assert!(!remove_toolchain(Some("yarn"), &file).unwrap());
+        assert!(remove_toolchain(None, &file).unwrap());
+        assert!(read_toolchain(&file).unwrap().is_none());
+        assert!(!remove_toolchain(None, &file).unwrap());
+    }
+}
@@ -0,0 +1,231 @@
+//! Provides the `Plan` type, which lists the changes a command is about to make, so
+//! that with `--dry-run` they can be reported instead of made.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use fs::{read_file_opt, write_atomic};
+use notion_fail::{Fallible, ResultExt};
+
+/// A change to the filesystem.
+pub enum Change {
+    /// Installing something (a tool version or a package) into a directory.
+    Install { what: String, dir: PathBuf },
+    /// Deleting a file or a directory and everything in it.
+    Delete(PathBuf),
+    /// Writing new contents to a file.
+    Write(FileEdit),
+    /// Updating one of Notion's own records, which only Notion reads.
+    Update { file: PathBuf, what: String },
+}
+
+/// The contents of a file before and after it is written.
+pub struct FileEdit {
+    pub file: PathBuf,
+    /// The contents before, or `None` if the file doesn't exist yet.
+    pub before: Option<String>,
+    pub after: String,
+}
+
+impl FileEdit {
+    /// Describes the lines the edit changes: the lines it removes, prefixed with `-`,
+    /// followed by the lines it adds, prefixed with `+`.
+    pub fn diff(&self) -> Vec<String> {
+        let before: Vec<&str> = match self.before {
+            Some(ref before) => before.lines().collect(),
+            None => vec![],
+        };
+        let after: Vec<&str> = self.after.lines().collect();
+
+        let prefix = before
+            .iter()
+            .zip(after.iter())
+            .take_while(|&(old, new)| old == new)
+            .count();
+        let suffix = before[prefix..]
+            .iter()
+            .rev()
+            .zip(after[prefix..].iter().rev())
+            .take_while(|&(old, new)| old == new)
+            .count();
+
+        let removed = before[prefix..before.len() - suffix]
+            .iter()
+            .map(|line| format!("-{}", line));
+        let added = after[prefix..after.len() - suffix]
+            .iter()
+            .map(|line| format!("+{}", line));
+        removed.chain(added).collect()
+    }
+}
+
+/// The changes a command is about to make, in order.
+#[derive(Default)]
+pub struct Plan {
+    changes: Vec<Change>,
+}
+
+impl Plan {
+    pub fn new() -> Plan {
+        Plan::default()
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Reads a file as it will be once the planned writes are made, or `None` if it
+    /// doesn't exist and none of them creates it.
+    pub(crate) fn contents(&self, file: &Path) -> Fallible<Option<String>> {
+        match self.find_write(file) {
+            Some(index) => match self.changes[index] {
+                Change::Write(ref edit) => Ok(Some(edit.after.clone())),
+                _ => unreachable!(),
+            },
+            None => read_file_opt(&file.to_path_buf()).unknown(),
+        }
+    }
+
+    /// Plans to write new contents to a file. Writing the same file more than once only
+    /// plans one write, of the last contents; writing a file's current contents plans
+    /// nothing.
+    pub(crate) fn write(&mut self, file: PathBuf, contents: String) -> Fallible<()> {
+        if let Some(index) = self.find_write(&file) {
+            if let Change::Write(ref mut edit) = self.changes[index] {
+                edit.after = contents;
+            }
+            return Ok(());
+        }
+
+        let before = read_file_opt(&file).unknown()?;
+        if before.as_ref() != Some(&contents) {
+            self.changes.push(Change::Write(FileEdit {
+                file,
+                before,
+                after: contents,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Makes the planned writes, each atomically (see `write_atomic`). The plan's other
+    /// changes are only descriptions, which the command that planned them carries out.
+    pub(crate) fn write_files(&self) -> Fallible<()> {
+        for change in &self.changes {
+            if let Change::Write(ref edit) = *change {
+                write_atomic(&edit.file, &edit.after)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_write(&self, file: &Path) -> Option<usize> {
+        self.changes.iter().position(|change| match *change {
+            Change::Write(ref edit) => edit.file == file,
+            _ => false,
+        })
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Change::Install { ref what, ref dir } => {
+                write!(f, "Would install {} into {}", what, dir.display())
+            }
+            Change::Delete(ref path) => write!(f, "Would delete {}", path.display()),
+            Change::Write(ref edit) => {
+                let verb = if edit.before.is_some() {
+                    "write"
+                } else {
+                    "create"
+                };
+                write!(f, "Would {} {}:", verb, edit.file.display())?;
+                for line in edit.diff() {
+                    write!(f, "\n    {}", line)?;
+                }
+                Ok(())
+            }
+            Change::Update { ref file, ref what } => {
+                write!(f, "Would update {} to {}", file.display(), what)
+            }
+        }
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.changes.is_empty() {
+            return f.write_str("Nothing would change");
+        }
+        let lines: Vec<String> = self.changes.iter().map(|change| change.to_string()).collect();
+        f.write_str(&lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{FileEdit, Plan};
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_diff() {
+        let edit = FileEdit {
+            file: PathBuf::from("package.json"),
+            before: Some("{\n  \"node\": \"8.9.4\",\n  \"yarn\": \"1.2.0\"\n}\n".to_string()),
+            after: "{\n  \"node\": \"10.0.0\",\n  \"yarn\": \"1.2.0\"\n}\n".to_string(),
+        };
+        assert_eq!(
+            edit.diff(),
+            vec!["-  \"node\": \"8.9.4\",", "+  \"node\": \"10.0.0\","]
+        );
+
+        let created = FileEdit {
+            file: PathBuf::from(".notion.toml"),
+            before: None,
+            after: "[toolchain]\nnode = \"10.0.0\"\n".to_string(),
+        };
+        assert_eq!(created.diff(), vec!["+[toolchain]", "+node = \"10.0.0\""]);
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("package.json");
+        File::create(&file).unwrap().write_all(b"{}\n").unwrap();
+
+        let mut plan = Plan::new();
+        plan.write(file.clone(), "{}\n".to_string()).unwrap();
+        assert!(plan.is_empty());
+
+        plan.write(file.clone(), "{ \"a\": 1 }\n".to_string()).unwrap();
+        plan.write(file.clone(), "{ \"a\": 2 }\n".to_string()).unwrap();
+        assert_eq!(plan.changes().len(), 1);
+        assert_eq!(
+            plan.contents(&file).unwrap(),
+            Some("{ \"a\": 2 }\n".to_string())
+        );
+
+        // Nothing is written until the plan is carried out.
+        let mut contents = String::new();
+        File::open(&file).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "{}\n");
+
+        plan.write_files().unwrap();
+        contents.clear();
+        File::open(&file).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "{ \"a\": 2 }\n");
+    }
+}
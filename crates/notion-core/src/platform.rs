@@ -0,0 +1,421 @@
+//! Provides a cache of the tool versions selected for a directory, which lets shims
+//! skip loading the session (the project's manifests, the configuration, and the
+//! catalog) on their hot path.
+//!
+//! Each directory a shim has run in gets a small binary cache file, which records
+//! the selected Node, Yarn, and npm versions (and the Yarn release the project
+//! vendors, if any) along with the state (size and modification time) of every file
+//! the selection depends on. A shim only trusts
+//! the file while none of those files have changed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use config::layers;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use pin_file::PIN_FILE;
+use project::HOOKS_FILE;
+
+const MAGIC: &'static [u8] = b"NTPC\x04";
+
+/// The tool versions selected for a directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    /// The selected version of Node.
+    pub node: String,
+    /// The version of Yarn pinned by the project, if any. Without one, the user's
+    /// default Yarn (which only the catalog knows) is selected.
+    pub yarn: Option<String>,
+    /// The version of npm pinned by the project, if any. Without one, the npm bundled
+    /// with Node is selected.
+    pub npm: Option<String>,
+    /// The release of Yarn the project vendors (with Yarn 2's `yarnPath`), if any,
+    /// which runs with the selected Node in place of any other Yarn.
+    pub yarn_path: Option<String>,
+}
+
+/// The state of a file that the selection depends on: its size and modification
+/// time, or `None` if it doesn't exist.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Stamp {
+    path: String,
+    state: Option<(u64, u64, u32)>,
+}
+
+impl Stamp {
+    pub(crate) fn of(path: &Path) -> Option<Stamp> {
+        let state = match fs::metadata(path) {
+            Ok(metadata) => {
+                let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+                Some((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+            }
+            Err(_) => None,
+        };
+        Some(Stamp {
+            path: path.to_str()?.to_string(),
+            state,
+        })
+    }
+
+    pub(crate) fn is_current(&self) -> bool {
+        Stamp::of(Path::new(&self.path)).as_ref() == Some(self)
+    }
+}
+
+/// Returns the cache file for the specified directory, which is named after a hash
+/// of the directory's path.
+fn cache_file(dir: &Path) -> Fallible<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    dir.hash(&mut hasher);
+    Ok(path::platform_cache_dir()?.join(format!("{:016x}", hasher.finish())))
+}
+
+/// Returns whether any `NOTION_<TOOL>_VERSION` override set by `notion use`, or any
+/// environment variable that overrides a setting the selection depends on, applies (as
+/// `var` reads the environment), in which case the cache is neither used nor saved.
+fn has_override<F>(var: F) -> bool
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    ["NODE", "YARN", "NPM"].iter().any(|tool| {
+        var(&format!("NOTION_{}_VERSION", tool)).map_or(false, |value| !value.is_empty())
+    }) || layers::has_platform_overrides(&var)
+}
+
+/// The files that selecting tool versions in `dir` depends on: the `package.json`
+/// and pin file of `dir` and of each directory up to the project root (whose
+/// appearance would change the project), the project's toolchain file and hooks file,
+/// the `.yarnrc.yml` files that could vendor a release of Yarn, and the system's and
+/// the user's configuration.
+fn dependencies(
+    dir: &Path,
+    root: &Path,
+    toolchain_file: &Path,
+    yarnrc_files: &[PathBuf],
+) -> Fallible<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut ancestor = Some(dir);
+    while let Some(current) = ancestor {
+        files.push(current.join("package.json"));
+        files.push(current.join(PIN_FILE));
+        if current == root {
+            break;
+        }
+        ancestor = current.parent();
+    }
+    files.push(toolchain_file.to_path_buf());
+    files.push(root.join(HOOKS_FILE));
+    files.extend(yarnrc_files.iter().cloned());
+    files.extend(path::system_config_file());
+    files.push(path::user_config_file()?);
+    Ok(files)
+}
+
+/// Records the tool versions selected for `dir`, which belongs to the project rooted
+/// at `root` whose toolchain is pinned in `toolchain_file`, and whose `yarnPath` can
+/// be set in `yarnrc_files`.
+pub(crate) fn save(
+    dir: &Path,
+    root: &Path,
+    toolchain_file: &Path,
+    yarnrc_files: &[PathBuf],
+    platform: &Platform,
+) -> Fallible<()> {
+    if has_override(|var| env::var_os(var)) {
+        return Ok(());
+    }
+    let mut stamps = vec![];
+    for file in dependencies(dir, root, toolchain_file, yarnrc_files)? {
+        match Stamp::of(&file) {
+            Some(stamp) => stamps.push(stamp),
+            // paths that aren't valid Unicode can't be recorded
+            None => return Ok(()),
+        }
+    }
+
+    let dir_name = match dir.to_str() {
+        Some(dir_name) => dir_name,
+        None => return Ok(()),
+    };
+    let bytes = match encode(dir_name, &stamps, platform) {
+        Some(bytes) => bytes,
+        // too many files, or names too long, to record
+        None => return Ok(()),
+    };
+
+    let file = cache_file(dir)?;
+    fs::create_dir_all(file.parent().unwrap()).unknown()?;
+    // write to a temporary file first, so that a concurrent shim never reads half a file
+    let staging = file.with_extension(format!("{}", ::std::process::id()));
+    File::create(&staging)
+        .and_then(|mut out| out.write_all(&bytes))
+        .unknown()?;
+    fs::rename(&staging, &file).unknown()?;
+    Ok(())
+}
+
+/// Returns the tool versions cached for the current directory, if the cache is still
+/// valid and the versions are installed. Any problem reading the cache is a miss.
+pub fn current() -> Option<Platform> {
+    if has_override(|var| env::var_os(var)) {
+        return None;
+    }
+    let dir = env::current_dir().ok()?;
+    let mut bytes = vec![];
+    File::open(cache_file(&dir).ok()?)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .ok()?;
+    let platform = parse(&bytes, &dir)?;
+
+    if !path::node_version_dir(&platform.node).ok()?.is_dir() {
+        return None;
+    }
+    if let Some(ref npm) = platform.npm {
+        if !path::npm_version_dir(npm).ok()?.is_dir() {
+            return None;
+        }
+    }
+    if let Some(ref yarn) = platform.yarn {
+        if !path::yarn_version_dir(yarn).ok()?.is_dir() {
+            return None;
+        }
+    }
+    if let Some(ref yarn_path) = platform.yarn_path {
+        if !Path::new(yarn_path).is_file() {
+            return None;
+        }
+    }
+    Some(platform)
+}
+
+/// Encodes the cache, unless a count or length doesn't fit in the two bytes it is
+/// recorded in.
+fn encode(dir: &str, stamps: &[Stamp], platform: &Platform) -> Option<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    write_str(&mut bytes, dir)?;
+    write_len(&mut bytes, stamps.len())?;
+    for stamp in stamps {
+        write_str(&mut bytes, &stamp.path)?;
+        match stamp.state {
+            Some((len, secs, nanos)) => {
+                bytes.push(1);
+                write_int(&mut bytes, len, 8);
+                write_int(&mut bytes, secs, 8);
+                write_int(&mut bytes, nanos as u64, 4);
+            }
+            None => bytes.push(0),
+        }
+    }
+    write_str(&mut bytes, &platform.node)?;
+    write_opt_str(&mut bytes, &platform.yarn)?;
+    write_opt_str(&mut bytes, &platform.npm)?;
+    write_opt_str(&mut bytes, &platform.yarn_path)?;
+    Some(bytes)
+}
+
+fn parse(bytes: &[u8], dir: &Path) -> Option<Platform> {
+    let mut cursor = Cursor { bytes, pos: 0 };
+    if cursor.take(MAGIC.len())? != MAGIC {
+        return None;
+    }
+    // the hash of the path could collide
+    if Path::new(&cursor.read_str()?) != dir {
+        return None;
+    }
+    let count = cursor.read_int(2)?;
+    for _ in 0..count {
+        let path = cursor.read_str()?;
+        let state = match cursor.take(1)?[0] {
+            0 => None,
+            _ => Some((cursor.read_int(8)?, cursor.read_int(8)?, cursor.read_int(4)? as u32)),
+        };
+        if !(Stamp { path, state }).is_current() {
+            return None;
+        }
+    }
+    Some(Platform {
+        node: cursor.read_str()?,
+        yarn: cursor.read_opt_str()?,
+        npm: cursor.read_opt_str()?,
+        yarn_path: cursor.read_opt_str()?,
+    })
+}
+
+/// Writes the low `width` bytes of an integer, least significant first.
+fn write_int(bytes: &mut Vec<u8>, n: u64, width: usize) {
+    for i in 0..width {
+        bytes.push((n >> (8 * i)) as u8);
+    }
+}
+
+/// Writes a count or length in two bytes, failing if it doesn't fit.
+fn write_len(bytes: &mut Vec<u8>, len: usize) -> Option<()> {
+    if len > u16::MAX as usize {
+        return None;
+    }
+    write_int(bytes, len as u64, 2);
+    Some(())
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) -> Option<()> {
+    write_len(bytes, s.len())?;
+    bytes.extend_from_slice(s.as_bytes());
+    Some(())
+}
+
+fn write_opt_str(bytes: &mut Vec<u8>, s: &Option<String>) -> Option<()> {
+    match *s {
+        Some(ref s) => {
+            bytes.push(1);
+            write_str(bytes, s)
+        }
+        None => {
+            bytes.push(0);
+            Some(())
+        }
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.pos + len > self.bytes.len() {
+            return None;
+        }
+        let taken = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Some(taken)
+    }
+
+    /// Reads an integer of `width` bytes, least significant first.
+    fn read_int(&mut self, width: usize) -> Option<u64> {
+        let bytes = self.take(width)?;
+        Some(bytes
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, &byte)| n | ((byte as u64) << (8 * i))))
+    }
+
+    fn read_str(&mut self) -> Option<String> {
+        let len = self.read_int(2)? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn read_opt_str(&mut self) -> Option<Option<String>> {
+        match self.take(1)?[0] {
+            0 => Some(None),
+            _ => self.read_str().map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{dependencies, encode, has_override, parse, Platform, Stamp};
+    use path;
+    use project::HOOKS_FILE;
+    use std::ffi::OsString;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let package_file = dir.path().join("package.json");
+        File::create(&package_file).unwrap();
+        let stamps = vec![
+            Stamp::of(&package_file).unwrap(),
+            Stamp::of(&dir.path().join(".notion.toml")).unwrap(),
+        ];
+        let platform = Platform {
+            node: "10.8.0".to_string(),
+            yarn: Some("1.12.3".to_string()),
+            npm: None,
+            yarn_path: None,
+        };
+        let bytes = encode(dir.path().to_str().unwrap(), &stamps, &platform).unwrap();
+
+        assert_eq!(parse(&bytes, dir.path()), Some(platform));
+        // the cache is only valid for the directory it was saved for
+        assert_eq!(parse(&bytes, &dir.path().join("src")), None);
+        // and while the files it depends on are unchanged
+        File::create(&package_file)
+            .and_then(|mut file| file.write_all(b"{}"))
+            .unwrap();
+        assert_eq!(parse(&bytes, dir.path()), None);
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let platform = Platform {
+            node: "10.8.0".to_string(),
+            yarn: None,
+            npm: Some("6.4.1".to_string()),
+            yarn_path: Some("/project/.yarn/releases/yarn-2.4.1.cjs".to_string()),
+        };
+        let bytes = encode(dir.path().to_str().unwrap(), &[], &platform).unwrap();
+        assert_eq!(parse(&bytes, dir.path()), Some(platform));
+        assert_eq!(parse(&bytes[..bytes.len() - 3], dir.path()), None);
+    }
+
+    #[test]
+    fn test_encode_overflow() {
+        let platform = Platform {
+            node: "10.8.0".to_string(),
+            yarn: None,
+            npm: None,
+            yarn_path: None,
+        };
+        let stamps: Vec<Stamp> = (0..u16::MAX as usize + 1)
+            .map(|_| Stamp {
+                path: String::new(),
+                state: None,
+            })
+            .collect();
+        assert!(encode("/project", &stamps, &platform).is_none());
+        assert!(encode("/project", &stamps[1..], &platform).is_some());
+        assert!(encode(&"a".repeat(u16::MAX as usize + 1), &[], &platform).is_none());
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let dir = tempdir().expect("Could not create temporary directory");
+        let toolchain_file = dir.path().join("package.json");
+        let files = dependencies(dir.path(), dir.path(), &toolchain_file, &[]).unwrap();
+
+        assert!(files.contains(&dir.path().join(HOOKS_FILE)));
+        // the system's configuration (wherever the environment puts it) and the user's
+        if let Some(config_file) = path::system_config_file() {
+            assert!(files.contains(&config_file));
+        }
+        assert!(files.contains(&path::user_config_file().unwrap()));
+    }
+
+    #[test]
+    fn test_has_override() {
+        let only = |name: &'static str, value: &'static str| {
+            move |var: &str| if var == name { Some(OsString::from(value)) } else { None }
+        };
+
+        assert!(!has_override(|_| None));
+        assert!(has_override(only("NOTION_NODE_VERSION", "10.8.0")));
+        assert!(!has_override(only("NOTION_NODE_VERSION", "")));
+        assert!(has_override(only("NOTION_BEHAVIOR_COREPACK", "true")));
+        // settings that don't select versions leave the cache in use
+        assert!(!has_override(only("NOTION_HTTP_TIMEOUT", "5")));
+    }
+}
@@ -0,0 +1,127 @@
+//! Provides a cache of the resolutions of resolve plugins, which saves consulting a
+//! plugin (spawning its process or sending it a request) every time a version
+//! requirement has to be resolved.
+//!
+//! Each resolution is cached in a small JSON file, named after a hash of the plugin,
+//! the tool, and the requirement it resolved, which records when it expires.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{serial, ResolvePlugin, ResolveResponse};
+use env;
+use notion_fail::{Fallible, ResultExt};
+use path;
+use serde_json;
+use version::VersionSpec;
+
+/// Identifies a plugin by its URL or its command.
+fn plugin_name(plugin: &ResolvePlugin) -> String {
+    match plugin {
+        &ResolvePlugin::Url(ref url) => format!("url:{}", url),
+        &ResolvePlugin::Bin(ref bin) => format!("bin:{}", bin.trim()),
+    }
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Returns the cache file for a resolution.
+fn cache_file(plugin: &str, tool: &str, req: &str) -> Fallible<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    (plugin, tool, req).hash(&mut hasher);
+    Ok(path::resolve_cache_dir()?.join(format!("{:016x}.json", hasher.finish())))
+}
+
+/// Parses a cached resolution, if it is the resolution of the specified requirement
+/// (the hash of the file name could collide) and it hasn't expired by `now`.
+fn parse(src: &str, plugin: &str, tool: &str, req: &str, now: u64) -> Option<ResolveResponse> {
+    let cached: serial::CachedResolution = serde_json::from_str(src).ok()?;
+    if cached.plugin != plugin || cached.tool != tool || cached.req != req || cached.expires <= now {
+        return None;
+    }
+    cached.response.into_resolve_response().ok()
+}
+
+/// Returns the cached resolution of the specified requirement by a plugin, if it is
+/// still fresh. Offline, an expired resolution is still better than none, so it is used
+/// anyway. Any problem reading the cache is a miss.
+pub(crate) fn read(plugin: &ResolvePlugin, tool: &str, matching: &VersionSpec) -> Option<ResolveResponse> {
+    let plugin = plugin_name(plugin);
+    let req = matching.to_string();
+    let src = fs::read_to_string(cache_file(&plugin, tool, &req).ok()?).ok()?;
+    let now = if env::offline() { 0 } else { now() };
+    parse(&src, &plugin, tool, &req, now)
+}
+
+/// Caches a plugin's resolution of the specified requirement for `ttl` seconds.
+pub(crate) fn write(
+    plugin: &ResolvePlugin,
+    tool: &str,
+    matching: &VersionSpec,
+    response: &ResolveResponse,
+    ttl: u64,
+) -> Fallible<()> {
+    let plugin = plugin_name(plugin);
+    let req = matching.to_string();
+    let file = cache_file(&plugin, tool, &req)?;
+    let cached = serial::CachedResolution {
+        plugin,
+        tool: tool.to_string(),
+        req,
+        expires: now() + ttl,
+        response: serial::ResolveResponse::from_resolve_response(response),
+    };
+    let src = serde_json::to_string(&cached).unknown()?;
+
+    fs::create_dir_all(file.parent().unwrap()).unknown()?;
+    // write to a temporary file first, so that a concurrent read never sees half a file
+    let staging = file.with_extension(format!("{}", ::std::process::id()));
+    File::create(&staging)
+        .and_then(|mut out| out.write_all(src.as_bytes()))
+        .unknown()?;
+    fs::rename(&staging, &file).unknown()?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{parse, serial};
+    use plugin::ResolveResponse;
+    use semver::Version;
+    use serde_json;
+
+    #[test]
+    fn test_parse() {
+        let response = ResolveResponse::Url {
+            version: Version::parse("10.8.0").unwrap(),
+            url: "https://example.com/node-v10.8.0.tar.gz".to_string(),
+            checksum: None,
+        };
+        let src = serde_json::to_string(&serial::CachedResolution {
+            plugin: "bin:resolve-node".to_string(),
+            tool: "node".to_string(),
+            req: "^10".to_string(),
+            expires: 1000,
+            response: serial::ResolveResponse::from_resolve_response(&response),
+        }).unwrap();
+
+        assert_eq!(parse(&src, "bin:resolve-node", "node", "^10", 999), Some(response));
+        // the resolution expires
+        assert_eq!(parse(&src, "bin:resolve-node", "node", "^10", 1000), None);
+        // and is only the resolution of what it resolved
+        assert_eq!(parse(&src, "bin:resolve-node", "node", "^8", 999), None);
+        assert_eq!(parse(&src, "bin:resolve-node", "yarn", "^10", 999), None);
+        assert_eq!(parse(&src, "url:https://example.com", "node", "^10", 999), None);
+    }
+}
@@ -1,21 +1,31 @@
 //! Types representing Notion plugins.
 
+use std::collections::BTreeSet;
 use std::ffi::OsString;
 use std::io::Read;
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
+use config::Config;
 use distro::Distro;
+use env;
+use http;
+use log;
+use path;
 
 use cmdline_words_parser::StrExt;
-use notion_fail::{FailExt, Fallible, ResultExt};
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
 use semver::Version;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use serde_json;
 use version::VersionSpec;
 
+mod cache;
 pub(crate) mod serial;
 
 /// A Node version resolution plugin.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum ResolvePlugin {
     /// Resolves a Tool version by sending it to a URL and receiving the
     /// resolution in the response.
@@ -26,67 +36,411 @@ pub enum ResolvePlugin {
     Bin(String),
 }
 
-#[derive(Fail, Debug)]
+/// Thrown when the command of a bin plugin is empty.
+#[derive(Debug, Fail, NotionFail)]
 #[fail(display = "Invalid plugin command: '{}'", command)]
+#[notion_fail(code = "ConfigurationError")]
 pub struct InvalidCommandError {
     command: String,
 }
 
+/// The version of the protocol that Notion speaks with resolve and index plugins. It is
+/// sent with every request, and a plugin that says which version it responded in has to
+/// have responded in this one.
+pub const PLUGIN_PROTOCOL: u32 = 1;
+
+/// How long a resolve plugin's resolution stays fresh in the cache by default.
+const DEFAULT_RESOLVE_TTL_SECS: u64 = 60 * 60;
+
+/// How long a URL plugin has to respond before the request is abandoned.
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Thrown when a URL plugin could not be reached or responded with an error.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not get a response from the plugin at {}\n{}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct PluginRequestError {
+    url: String,
+    error: String,
+}
+
+/// Thrown when a resolve plugin responded with something other than a valid resolution.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid response from the resolve plugin {}\n{}", plugin, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct ResolveResponseError {
+    /// The plugin's URL, or its command in backticks.
+    plugin: String,
+    error: String,
+}
+
+/// Thrown when an index plugin responded with something other than a valid index.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Invalid version index from the plugin {}\n{}", plugin, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct IndexResponseError {
+    /// The plugin's URL, or its command in backticks.
+    plugin: String,
+    error: String,
+}
+
+/// Thrown when the command of a bin plugin could not be started.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not run the plugin `{}`\n{}", command, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct PluginSpawnError {
+    command: String,
+    error: String,
+}
+
+/// Thrown when the command of a bin plugin exited unsuccessfully.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "The plugin `{}` failed ({})\n{}", command, status, stderr)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct PluginExitError {
+    command: String,
+    status: String,
+    stderr: String,
+}
+
+/// Thrown when a plugin responded in a version of the plugin protocol that Notion
+/// doesn't speak.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(
+    display = "The plugin {} responded in version {} of the plugin protocol, but Notion speaks version {}\n{}",
+    plugin,
+    protocol,
+    supported,
+    hint
+)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct IncompatibleProtocolError {
+    plugin: String,
+    protocol: u32,
+    supported: u32,
+    hint: &'static str,
+}
+
+impl IncompatibleProtocolError {
+    fn new(plugin: String, protocol: u32) -> Self {
+        IncompatibleProtocolError {
+            plugin,
+            protocol,
+            supported: PLUGIN_PROTOCOL,
+            hint: if protocol > PLUGIN_PROTOCOL {
+                "Upgrade Notion to use this plugin."
+            } else {
+                "Upgrade the plugin, or ask its author to support the current protocol."
+            },
+        }
+    }
+}
+
+/// A request to a resolve plugin, which is sent as a JSON object: in the body of a POST
+/// request to a URL plugin, or on the standard input of a bin plugin.
+#[derive(Serialize)]
+struct ResolveRequest {
+    /// The version of the plugin protocol the request is written in.
+    protocol: u32,
+    /// The tool to resolve, e.g. `node` or `yarn`.
+    tool: &'static str,
+    /// The requested version, e.g. `^10.8`, `latest`, or `lts/carbon`.
+    req: String,
+    /// The OS component of the names of Node's archives for this platform.
+    os: &'static str,
+    /// The architecture component of the names of Node's archives for this platform.
+    arch: String,
+}
+
+impl ResolveRequest {
+    fn new(tool: &'static str, matching: &VersionSpec) -> Self {
+        ResolveRequest {
+            protocol: PLUGIN_PROTOCOL,
+            tool,
+            req: matching.to_string(),
+            os: path::OS,
+            arch: path::node_arch(),
+        }
+    }
+}
+
+/// Provisions the distribution at a URL that a resolve plugin resolved to, verifying it
+/// against the checksum the plugin produced, if any.
+fn remote<D: Distro>(
+    version: Version,
+    url: &str,
+    checksum: Option<String>,
+    config: &Config,
+) -> Fallible<D> {
+    match checksum {
+        Some(checksum) => D::remote_with_checksum(version, url, &checksum, config),
+        None => D::remote(version, url, config),
+    }
+}
+
+/// Picks the best of the candidates a resolve plugin offers for a requirement. For a
+/// semantic versioning range, that is the newest matching version already installed,
+/// so that nothing has to be fetched, or else the newest matching version. Other
+/// requirements (like `latest` or `lts`) are left to the plugin, so the newest
+/// candidate is picked.
+fn pick_candidate(
+    candidates: Vec<Candidate>,
+    matching: &VersionSpec,
+    installed: &BTreeSet<Version>,
+) -> Option<Candidate> {
+    let mut candidates = candidates;
+    candidates.sort_by(|a, b| a.version.cmp(&b.version));
+    let req = match *matching {
+        VersionSpec::Semver(ref req) => req,
+        _ => return candidates.pop(),
+    };
+
+    let mut matches: Vec<Candidate> = candidates
+        .into_iter()
+        .filter(|candidate| req.matches(&candidate.version))
+        .collect();
+    match matches
+        .iter()
+        .rposition(|candidate| installed.contains(&candidate.version))
+    {
+        Some(index) => Some(matches.swap_remove(index)),
+        None => matches.pop(),
+    }
+}
+
 impl ResolvePlugin {
-    /// Performs resolution of a Tool version based on the given semantic
-    /// versioning requirements.
-    pub fn resolve<D: Distro>(&self, _matching: &VersionSpec) -> Fallible<D> {
-        match self {
-            &ResolvePlugin::Url(_) => unimplemented!(),
-
-            &ResolvePlugin::Bin(ref bin) => {
-                let mut trimmed = bin.trim().to_string();
-                let mut words = trimmed.parse_cmdline_words();
-                let cmd = if let Some(word) = words.next() {
-                    word
-                } else {
-                    throw!(
-                        InvalidCommandError {
-                            command: String::from(bin.trim()),
-                        }.unknown()
-                    );
+    /// Performs resolution of a version of the specified tool based on the given
+    /// semantic versioning requirements, picking among the candidates the plugin
+    /// offers, if it offers several, with the help of the `installed` versions. A
+    /// successful resolution is cached for `ttl` seconds (an hour if unspecified),
+    /// unless caching is disabled.
+    pub fn resolve<D: Distro>(
+        &self,
+        tool: &'static str,
+        matching: &VersionSpec,
+        installed: &BTreeSet<Version>,
+        ttl: Option<u64>,
+        config: &Config,
+    ) -> Fallible<D> {
+        let ttl = ttl.unwrap_or(DEFAULT_RESOLVE_TTL_SECS);
+        let cached = if ttl > 0 && !env::no_cache() {
+            cache::read(self, tool, matching)
+        } else {
+            None
+        };
+
+        let response = match cached {
+            Some(response) => {
+                log::debug(|| format!("using the cached resolution of {} {}", tool, matching));
+                response
+            }
+            None => {
+                let request = ResolveRequest::new(tool, matching);
+                let response = match self {
+                    &ResolvePlugin::Url(ref url) => match resolve_url(url, &request, config)? {
+                        ResolveResponse::Stream { .. } => throw!(ResolveResponseError {
+                            plugin: url.clone(),
+                            error: "a URL plugin can't stream the installer".to_string(),
+                        }),
+                        response => response,
+                    },
+                    &ResolvePlugin::Bin(ref bin) => resolve_bin(bin, &request)?,
                 };
-                let args: Vec<OsString> = words
-                    .map(|s| {
-                        let mut os = OsString::new();
-                        os.push(s);
-                        os
-                    })
-                    .collect();
-                let child = Command::new(cmd)
-                    .args(&args)
-                    .stdin(Stdio::null())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .unknown()?;
-                let response = ResolveResponse::from_reader(child.stdout.unwrap())?;
-                match response {
-                    ResolveResponse::Url { version, url } => D::remote(version, &url),
-                    ResolveResponse::Stream { version: _version } => {
-                        unimplemented!("bin plugin produced a stream")
-                    }
+                if ttl > 0 {
+                    let _ = cache::write(self, tool, matching, &response, ttl);
+                }
+                response
+            }
+        };
+
+        match response {
+            ResolveResponse::Url {
+                version,
+                url,
+                checksum,
+            } => remote(version, &url, checksum, config),
+            // Installers streamed by the plugin aren't supported yet, so fetch the
+            // version it resolved to from the public distributor instead.
+            ResolveResponse::Stream { version } => D::public(version, config),
+            ResolveResponse::Candidates(candidates) => {
+                match pick_candidate(candidates, matching, installed) {
+                    Some(Candidate {
+                        version,
+                        url,
+                        checksum,
+                    }) => remote(version, &url, checksum, config),
+                    None => throw!(ResolveResponseError {
+                        plugin: match self {
+                            &ResolvePlugin::Url(ref url) => url.clone(),
+                            &ResolvePlugin::Bin(ref bin) => format!("`{}`", bin.trim()),
+                        },
+                        error: format!("none of the candidates matches {}", matching),
+                    }),
                 }
             }
         }
     }
 }
 
+/// Checks that a plugin, which is identified by its URL or its command in backticks,
+/// responded in the version of the plugin protocol that Notion speaks, if it says.
+fn check_protocol(plugin: &str, protocol: Option<u32>) -> Fallible<()> {
+    match protocol {
+        Some(protocol) if protocol != PLUGIN_PROTOCOL => {
+            throw!(IncompatibleProtocolError::new(plugin.to_string(), protocol))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parses the response of a resolve plugin, which is identified by its URL or its
+/// command in backticks.
+fn parse_response(plugin: String, response: &[u8]) -> Fallible<ResolveResponse> {
+    let serial: serial::ResolveResponse =
+        serde_json::from_slice(response).with_context(|error| ResolveResponseError {
+            plugin: plugin.clone(),
+            error: error.to_string(),
+        })?;
+    check_protocol(&plugin, serial.protocol())?;
+    serial
+        .into_resolve_response()
+        .with_context(|error| ResolveResponseError {
+            plugin,
+            error: error.to_string(),
+        })
+}
+
+/// Runs the command of a bin resolve plugin, writing the request to its standard input,
+/// and parses the response it prints.
+fn resolve_bin(bin: &str, request: &ResolveRequest) -> Fallible<ResolveResponse> {
+    let output = run_bin(bin, request)?;
+    parse_response(format!("`{}`", bin.trim()), &output)
+}
+
+/// Sends a request to a URL resolve plugin, in the body of a POST request, and parses
+/// its response.
+fn resolve_url(url: &str, request: &ResolveRequest, config: &Config) -> Fallible<ResolveResponse> {
+    let body = post_url(url, request, config)?;
+    parse_response(url.to_string(), body.as_bytes())
+}
+
+/// Runs the command of a bin plugin, writing the request to its standard input as a
+/// JSON object, and produces what it prints to its standard output.
+fn run_bin<T: Serialize>(bin: &str, request: &T) -> Fallible<Vec<u8>> {
+    let command = bin.trim();
+    let mut trimmed = command.to_string();
+    let (cmd, args) = {
+        let mut words = trimmed.parse_cmdline_words();
+        let cmd = match words.next() {
+            Some(word) => word.to_string(),
+            None => throw!(InvalidCommandError {
+                command: command.to_string(),
+            }),
+        };
+        let args: Vec<OsString> = words
+            .map(|s| {
+                let mut os = OsString::new();
+                os.push(s);
+                os
+            })
+            .collect();
+        (cmd, args)
+    };
+
+    let mut child = Command::new(&cmd)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|error| PluginSpawnError {
+            command: command.to_string(),
+            error: error.to_string(),
+        })?;
+    // Closing the plugin's standard input once the request is written tells it the
+    // request is complete. A plugin that doesn't need the request may exit without
+    // reading it, so a failure to write it isn't an error in itself.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = serde_json::to_writer(&mut stdin, request);
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|error| PluginSpawnError {
+            command: command.to_string(),
+            error: error.to_string(),
+        })?;
+
+    if !output.status.success() {
+        throw!(PluginExitError {
+            command: command.to_string(),
+            status: output.status.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(output.stdout)
+}
+
+/// Sends a request to a URL plugin, as a JSON object in the body of a POST request, and
+/// produces the body of its response.
+fn post_url<T: Serialize>(url: &str, request: &T, config: &Config) -> Fallible<String> {
+    http::ensure_online(url)?;
+    let client = http::client_with_timeout(url, config, Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .with_context(|error| PluginRequestError {
+            url: url.to_string(),
+            error: error.to_string(),
+        })?;
+    let mut response = http::retry(url, config, http::is_transient, || {
+        http::throttled(url, || {
+            http::traced("POST", url, None, || client.post(url).json(request).send())
+        })
+    }).with_context(|error| PluginRequestError {
+        url: url.to_string(),
+        error: error.to_string(),
+    })?;
+    http::ensure_not_throttled(url, &response)?;
+
+    if !response.status().is_success() {
+        throw!(PluginRequestError {
+            url: url.to_string(),
+            error: format!("HTTP failure ({})", response.status()),
+        });
+    }
+
+    response.text().with_context(|error| PluginRequestError {
+        url: url.to_string(),
+        error: error.to_string(),
+    })
+}
+
 /// A response from the Node version resolution plugin.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ResolveResponse {
     /// A plugin response indicating that the Node installer for the resolved version
-    /// can be downloaded from the specified URL.
-    Url { version: Version, url: String },
+    /// can be downloaded from the specified URL, optionally with the SHA-256 checksum
+    /// to verify it against.
+    Url {
+        version: Version,
+        url: String,
+        checksum: Option<String>,
+    },
 
     /// A plugin response indicating that the Node installer for the resolved version
     /// is being delivered via the stderr stream of the plugin process.
     Stream { version: Version },
+
+    /// A plugin response offering several versions to choose from, for Notion to pick
+    /// the best of (see `pick_candidate`).
+    Candidates(Vec<Candidate>),
+}
+
+/// A version offered by a resolve plugin, which can be downloaded from the specified
+/// URL, optionally with the SHA-256 checksum to verify it against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub version: Version,
+    pub url: String,
+    pub checksum: Option<String>,
 }
 
 impl ResolveResponse {
@@ -97,15 +451,66 @@ impl ResolveResponse {
     }
 }
 
-/// A plugin listing the available versions of Node.
-#[derive(PartialEq, Debug)]
-pub enum LsRemote {
+/// A plugin listing the available versions of a tool, for `notion ls-remote` and for
+/// resolving version requirements (like `lts`) that need the whole list.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Index {
+    /// Lists the available versions in the response to a request sent to a URL.
     Url(String),
+
+    /// Lists the available versions in the stdout stream of an executable, which
+    /// receives the request in its stdin stream.
     Bin(String),
 }
 
+/// A request to an index plugin, which is sent as a JSON object like a request to a
+/// resolve plugin.
+#[derive(Serialize)]
+struct IndexRequest {
+    /// The version of the plugin protocol the request is written in.
+    protocol: u32,
+    /// The tool to list the available versions of, e.g. `node`.
+    tool: &'static str,
+    /// The OS component of the names of Node's archives for this platform.
+    os: &'static str,
+    /// The architecture component of the names of Node's archives for this platform.
+    arch: String,
+}
+
+impl Index {
+    /// Fetches the list of available versions of the specified tool. The plugin
+    /// responds with the list in the format of the tool's public index (for Node, that
+    /// of `https://nodejs.org/dist/index.json`), either as is or as the `versions` of
+    /// an object that also says which `protocol` it is written in.
+    pub(crate) fn fetch<T: DeserializeOwned>(&self, tool: &'static str, config: &Config) -> Fallible<T> {
+        let request = IndexRequest {
+            protocol: PLUGIN_PROTOCOL,
+            tool,
+            os: path::OS,
+            arch: path::node_arch(),
+        };
+        let (plugin, response) = match self {
+            &Index::Url(ref url) => (url.clone(), post_url(url, &request, config)?.into_bytes()),
+            &Index::Bin(ref bin) => (format!("`{}`", bin.trim()), run_bin(bin, &request)?),
+        };
+        parse_index(plugin, &response)
+    }
+}
+
+/// Parses the response of an index plugin, which is identified by its URL or its
+/// command in backticks.
+fn parse_index<T: DeserializeOwned>(plugin: String, response: &[u8]) -> Fallible<T> {
+    let serial: serial::IndexResponse<T> =
+        serde_json::from_slice(response).with_context(|error| IndexResponseError {
+            plugin: plugin.clone(),
+            error: error.to_string(),
+        })?;
+    check_protocol(&plugin, serial.protocol())?;
+    Ok(serial.into_versions())
+}
+
 /// A plugin for publishing Notion events.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Publish {
     /// Reports an event by sending a POST request to a URL.
     Url(String),
@@ -113,3 +518,132 @@ pub enum Publish {
     /// Reports an event by forking a process and sending the event by IPC.
     Bin(String),
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{parse_index, parse_response, pick_candidate, resolve_bin, Candidate,
+                IncompatibleProtocolError, InvalidCommandError, PluginExitError,
+                ResolveRequest, ResolveResponse};
+    use semver::{Version, VersionReq};
+    use std::collections::BTreeSet;
+    use version::VersionSpec;
+
+    fn request() -> ResolveRequest {
+        ResolveRequest::new("node", &VersionSpec::Latest)
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = br#"{"protocol": 1, "version": "10.8.0", "url": "https://example.com/node.tar.gz", "checksum": "abc123"}"#;
+        match parse_response("test".to_string(), response).unwrap() {
+            ResolveResponse::Url {
+                version,
+                url,
+                checksum,
+            } => {
+                assert_eq!(version.to_string(), "10.8.0");
+                assert_eq!(url, "https://example.com/node.tar.gz");
+                assert_eq!(checksum, Some("abc123".to_string()));
+            }
+            _ => panic!("expected a URL response"),
+        }
+
+        // plugins that predate the protocol's versioning don't say which they speak
+        let response = br#"{"version": "10.8.0", "stream": true}"#;
+        assert!(parse_response("test".to_string(), response).is_ok());
+
+        let response = br#"{"protocol": 2, "version": "10.8.0", "stream": true}"#;
+        let error = parse_response("test".to_string(), response).unwrap_err();
+        assert!(error.downcast_ref::<IncompatibleProtocolError>().is_some());
+        assert!(error.to_string().contains("Upgrade Notion"));
+    }
+
+    #[test]
+    fn test_parse_candidates() {
+        let response = br#"{"protocol": 1, "candidates": [
+            {"version": "10.8.0", "url": "https://example.com/node-10.8.0.tar.gz", "checksum": "abc123"},
+            {"version": "10.7.0", "url": "https://example.com/node-10.7.0.tar.gz", "integrity": "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="}
+        ]}"#;
+        match parse_response("test".to_string(), response).unwrap() {
+            ResolveResponse::Candidates(candidates) => {
+                assert_eq!(candidates.len(), 2);
+                assert_eq!(candidates[0].checksum, Some("abc123".to_string()));
+                assert_eq!(
+                    candidates[1].checksum,
+                    Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string())
+                );
+            }
+            _ => panic!("expected candidates"),
+        }
+
+        let response = br#"{"candidates": []}"#;
+        assert!(parse_response("test".to_string(), response).is_err());
+
+        let response = br#"{"version": "10.8.0", "candidates": [{"version": "10.8.0", "url": "x"}]}"#;
+        assert!(parse_response("test".to_string(), response).is_err());
+
+        let response = br#"{"candidates": [{"version": "10.8.0", "url": "x", "integrity": "sha512-AAAA"}]}"#;
+        assert!(parse_response("test".to_string(), response).is_err());
+    }
+
+    #[test]
+    fn test_pick_candidate() {
+        let candidate = |version: &str| Candidate {
+            version: Version::parse(version).unwrap(),
+            url: format!("https://example.com/node-{}.tar.gz", version),
+            checksum: None,
+        };
+        let candidates = vec![candidate("10.7.0"), candidate("10.8.0"), candidate("11.0.0")];
+        let caret_10 = VersionSpec::Semver(VersionReq::parse("^10").unwrap());
+        let mut installed = BTreeSet::new();
+
+        let picked = pick_candidate(candidates.clone(), &caret_10, &installed).unwrap();
+        assert_eq!(picked.version.to_string(), "10.8.0");
+
+        installed.insert(Version::parse("10.7.0").unwrap());
+        let picked = pick_candidate(candidates.clone(), &caret_10, &installed).unwrap();
+        assert_eq!(picked.version.to_string(), "10.7.0");
+
+        let picked = pick_candidate(candidates.clone(), &VersionSpec::Latest, &installed).unwrap();
+        assert_eq!(picked.version.to_string(), "11.0.0");
+
+        let caret_12 = VersionSpec::Semver(VersionReq::parse("^12").unwrap());
+        assert!(pick_candidate(candidates, &caret_12, &installed).is_none());
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let index: Vec<String> = parse_index("test".to_string(), br#"["10.8.0", "10.7.0"]"#).unwrap();
+        assert_eq!(index, vec!["10.8.0", "10.7.0"]);
+
+        let response = br#"{"protocol": 1, "versions": ["10.8.0"]}"#;
+        let index: Vec<String> = parse_index("test".to_string(), response).unwrap();
+        assert_eq!(index, vec!["10.8.0"]);
+
+        let response = br#"{"protocol": 2, "versions": ["10.8.0"]}"#;
+        let error = parse_index::<Vec<String>>("test".to_string(), response).unwrap_err();
+        assert!(error.downcast_ref::<IncompatibleProtocolError>().is_some());
+    }
+
+    #[test]
+    fn test_resolve_bin_empty_command() {
+        let error = resolve_bin("  ", &request()).unwrap_err();
+        assert!(error.downcast_ref::<InvalidCommandError>().is_some());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_resolve_bin_failures() {
+        let error = resolve_bin("false", &request()).unwrap_err();
+        assert!(error.downcast_ref::<PluginExitError>().is_some());
+
+        // `true` succeeds without printing a response
+        let error = resolve_bin("true", &request()).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Invalid response from the resolve plugin `true`")
+        );
+    }
+}
@@ -47,8 +47,8 @@ impl Plugin {
         self.into_plugin(plugin::ResolvePlugin::Url, plugin::ResolvePlugin::Bin)
     }
 
-    pub fn into_ls_remote(self) -> Fallible<plugin::LsRemote> {
-        self.into_plugin(plugin::LsRemote::Url, plugin::LsRemote::Bin)
+    pub fn into_index(self) -> Fallible<plugin::Index> {
+        self.into_plugin(plugin::Index::Url, plugin::Index::Bin)
     }
 
     pub fn into_publish(self) -> Fallible<plugin::Publish> {
@@ -58,9 +58,110 @@ impl Plugin {
 
 #[derive(Serialize, Deserialize)]
 pub struct ResolveResponse {
-    version: String,
+    protocol: Option<u32>,
+    version: Option<String>,
     url: Option<String>,
+    checksum: Option<String>,
     stream: Option<bool>,
+    candidates: Option<Vec<Candidate>>,
+}
+
+/// One of the versions a plugin offers, with where to download it and, optionally,
+/// either its SHA-256 checksum in hex or a Subresource Integrity hash of it (like
+/// `sha256-...`, as npm records).
+#[derive(Serialize, Deserialize)]
+pub struct Candidate {
+    version: String,
+    url: String,
+    checksum: Option<String>,
+    integrity: Option<String>,
+}
+
+#[derive(Fail, Debug)]
+#[fail(display = "Plugin must contain a 'version' field")]
+struct NoVersion;
+
+#[derive(Fail, Debug)]
+#[fail(display = "Plugin 'candidates' field can't be combined with 'version', 'url', or 'stream'")]
+struct CandidatesAndVersion;
+
+#[derive(Fail, Debug)]
+#[fail(display = "Plugin 'candidates' field must not be empty")]
+struct NoCandidates;
+
+#[derive(Fail, Debug)]
+#[fail(display = "Candidate contains both 'checksum' and 'integrity' fields")]
+struct BothChecksumAndIntegrity;
+
+#[derive(Fail, Debug)]
+#[fail(display = "Unsupported integrity hash '{}' (only 'sha256-' hashes are supported)", _0)]
+struct UnsupportedIntegrity(String);
+
+impl Candidate {
+    fn from_candidate(candidate: &plugin::Candidate) -> Self {
+        Candidate {
+            version: candidate.version.to_string(),
+            url: candidate.url.clone(),
+            checksum: candidate.checksum.clone(),
+            integrity: None,
+        }
+    }
+
+    fn into_candidate(self) -> Fallible<plugin::Candidate> {
+        let checksum = match (self.checksum, self.integrity) {
+            (Some(_), Some(_)) => return Err(BothChecksumAndIntegrity.unknown()),
+            (Some(checksum), None) => Some(checksum),
+            (None, Some(integrity)) => Some(integrity_to_hex(&integrity)?),
+            (None, None) => None,
+        };
+        Ok(plugin::Candidate {
+            version: Version::parse(&self.version).unknown()?,
+            url: self.url,
+            checksum,
+        })
+    }
+}
+
+/// Converts a SHA-256 Subresource Integrity hash, whose digest is in base64, to the
+/// hex checksum the distributions are verified against.
+fn integrity_to_hex(integrity: &str) -> Fallible<String> {
+    let digest = if integrity.starts_with("sha256-") {
+        decode_base64(&integrity["sha256-".len()..])
+    } else {
+        None
+    };
+    match digest {
+        Some(ref digest) if digest.len() == 32 => Ok(digest
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()),
+        _ => Err(UnsupportedIntegrity(integrity.to_string()).unknown()),
+    }
+}
+
+/// Decodes standard (padded) base64, or returns `None` if it isn't valid.
+fn decode_base64(src: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in src.trim_right_matches('=').chars() {
+        let value = match c {
+            'A'...'Z' => c as u32 - 'A' as u32,
+            'a'...'z' => c as u32 - 'a' as u32 + 26,
+            '0'...'9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
 }
 
 #[derive(Fail, Debug)]
@@ -75,39 +176,120 @@ struct NeitherUrlNorStream;
 #[fail(display = "Plugin 'stream' field must be 'true' if present")]
 struct FalseStream;
 
+/// A resolution cached from a resolve plugin, along with what it resolved and when it
+/// expires (in seconds since the Unix epoch).
+#[derive(Serialize, Deserialize)]
+pub struct CachedResolution {
+    pub plugin: String,
+    pub tool: String,
+    pub req: String,
+    pub expires: u64,
+    pub response: ResolveResponse,
+}
+
 impl ResolveResponse {
-    pub fn into_resolve_response(self) -> Fallible<plugin::ResolveResponse> {
-        match self {
-            ResolveResponse {
-                url: Some(_),
-                stream: Some(_),
-                ..
-            } => Err(BothUrlAndStream.unknown()),
-            ResolveResponse {
-                url: None,
+    pub fn from_resolve_response(response: &plugin::ResolveResponse) -> Self {
+        match *response {
+            plugin::ResolveResponse::Url {
+                ref version,
+                ref url,
+                ref checksum,
+            } => ResolveResponse {
+                protocol: Some(plugin::PLUGIN_PROTOCOL),
+                version: Some(version.to_string()),
+                url: Some(url.clone()),
+                checksum: checksum.clone(),
                 stream: None,
-                ..
-            } => Err(NeitherUrlNorStream.unknown()),
-            ResolveResponse {
+                candidates: None,
+            },
+            plugin::ResolveResponse::Stream { ref version } => ResolveResponse {
+                protocol: Some(plugin::PLUGIN_PROTOCOL),
+                version: Some(version.to_string()),
                 url: None,
-                stream: Some(false),
-                ..
-            } => Err(FalseStream.unknown()),
-            ResolveResponse {
-                url: Some(url),
+                checksum: None,
+                stream: Some(true),
+                candidates: None,
+            },
+            plugin::ResolveResponse::Candidates(ref candidates) => ResolveResponse {
+                protocol: Some(plugin::PLUGIN_PROTOCOL),
+                version: None,
+                url: None,
+                checksum: None,
                 stream: None,
-                version,
-            } => Ok(plugin::ResolveResponse::Url {
+                candidates: Some(candidates.iter().map(Candidate::from_candidate).collect()),
+            },
+        }
+    }
+
+    /// The version of the plugin protocol the response was written in, if the plugin
+    /// says (plugins that predate the protocol's versioning don't).
+    pub fn protocol(&self) -> Option<u32> {
+        self.protocol
+    }
+
+    pub fn into_resolve_response(self) -> Fallible<plugin::ResolveResponse> {
+        let ResolveResponse {
+            version,
+            url,
+            checksum,
+            stream,
+            candidates,
+            ..
+        } = self;
+
+        if let Some(candidates) = candidates {
+            if version.is_some() || url.is_some() || stream.is_some() {
+                return Err(CandidatesAndVersion.unknown());
+            }
+            if candidates.is_empty() {
+                return Err(NoCandidates.unknown());
+            }
+            let candidates = candidates
+                .into_iter()
+                .map(Candidate::into_candidate)
+                .collect::<Fallible<Vec<_>>>()?;
+            return Ok(plugin::ResolveResponse::Candidates(candidates));
+        }
+
+        let version = match version {
+            Some(version) => Version::parse(&version).unknown()?,
+            None => return Err(NoVersion.unknown()),
+        };
+        match (url, stream) {
+            (Some(_), Some(_)) => Err(BothUrlAndStream.unknown()),
+            (None, None) => Err(NeitherUrlNorStream.unknown()),
+            (None, Some(false)) => Err(FalseStream.unknown()),
+            (Some(url), None) => Ok(plugin::ResolveResponse::Url {
                 url,
-                version: Version::parse(&version).unknown()?,
-            }),
-            ResolveResponse {
-                url: None,
-                stream: Some(true),
                 version,
-            } => Ok(plugin::ResolveResponse::Stream {
-                version: Version::parse(&version).unknown()?,
+                checksum,
             }),
+            (None, Some(true)) => Ok(plugin::ResolveResponse::Stream { version }),
+        }
+    }
+}
+
+/// The response of an index plugin: the list of versions, either on its own or along
+/// with the version of the plugin protocol it is written in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum IndexResponse<T> {
+    Versioned { protocol: u32, versions: T },
+    Bare(T),
+}
+
+impl<T> IndexResponse<T> {
+    pub fn protocol(&self) -> Option<u32> {
+        match *self {
+            IndexResponse::Versioned { protocol, .. } => Some(protocol),
+            IndexResponse::Bare(_) => None,
+        }
+    }
+
+    pub fn into_versions(self) -> T {
+        match self {
+            IndexResponse::Versioned { versions, .. } => versions,
+            IndexResponse::Bare(versions) => versions,
         }
     }
 }
@@ -0,0 +1,144 @@
+//! Opt-in timing of the major phases of a command, for measuring where the time of a
+//! command (or shim) goes in the field.
+//!
+//! Profiling is enabled by the `NOTION_PROFILE` environment variable, which the
+//! `--profile` flag sets, so that shims can be profiled as well. Each phase is timed
+//! wherever it happens, and the totals are printed to stderr when the session exits.
+//! Archives that are unpacked while they download count as unpacking.
+
+use std::fmt::{self, Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use env;
+use style::style;
+
+lazy_static! {
+    /// When the process started profiling.
+    static ref STARTED: Instant = Instant::now();
+
+    /// The time spent in each phase so far, and how many times it ran.
+    static ref PHASES: Mutex<Vec<(Phase, Duration, u32)>> = Mutex::new(vec![]);
+}
+
+/// The phases of a command that are timed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    ConfigLoad,
+    ProjectDetection,
+    CatalogLoad,
+    IndexFetch,
+    Download,
+    Unpack,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match *self {
+            Phase::ConfigLoad => "config load",
+            Phase::ProjectDetection => "project detection",
+            Phase::CatalogLoad => "catalog load",
+            Phase::IndexFetch => "index fetch",
+            Phase::Download => "download",
+            Phase::Unpack => "unpack",
+        })
+    }
+}
+
+/// Returns whether profiling is enabled.
+pub fn enabled() -> bool {
+    env::profile()
+}
+
+/// Starts the clock that the total time of the process is measured by.
+pub(crate) fn start() {
+    if enabled() {
+        let _ = *STARTED;
+    }
+}
+
+/// Runs a phase, adding the time it takes to the phase's total if profiling is enabled.
+pub(crate) fn time<T, F: FnOnce() -> T>(phase: Phase, f: F) -> T {
+    if !enabled() {
+        return f();
+    }
+    let started = Instant::now();
+    let result = f();
+    record(phase, started.elapsed());
+    result
+}
+
+fn record(phase: Phase, elapsed: Duration) {
+    if let Ok(mut phases) = PHASES.lock() {
+        if let Some(entry) = phases.iter_mut().find(|entry| entry.0 == phase) {
+            entry.1 += elapsed;
+            entry.2 += 1;
+            return;
+        }
+        phases.push((phase, elapsed, 1));
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+/// Formats the breakdown of the phases that ran, in the order of `Phase`, followed by
+/// the total time of the process.
+fn breakdown(phases: &[(Phase, Duration, u32)], total: Duration) -> String {
+    let mut phases = phases.to_vec();
+    phases.sort_by_key(|entry| entry.0);
+
+    let mut report = String::from("Profile:\n");
+    for (phase, elapsed, count) in phases {
+        let times = if count > 1 {
+            format!(" ({} times)", count)
+        } else {
+            String::new()
+        };
+        report.push_str(&format!(
+            "    {:<20}{:>8}ms{}\n",
+            phase.to_string(),
+            millis(elapsed),
+            times
+        ));
+    }
+    report.push_str(&format!("    {:<20}{:>8}ms\n", "total", millis(total)));
+    report
+}
+
+/// Prints the breakdown of the phases that ran to stderr, if profiling is enabled.
+pub(crate) fn report() {
+    if !enabled() {
+        return;
+    }
+    let phases = PHASES
+        .lock()
+        .map(|phases| phases.clone())
+        .unwrap_or_default();
+    eprint!("{}", style(breakdown(&phases, STARTED.elapsed())).dim());
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{breakdown, Phase};
+    use std::time::Duration;
+
+    #[test]
+    fn test_breakdown() {
+        let phases = vec![
+            (Phase::Unpack, Duration::from_millis(1500), 1),
+            (Phase::ConfigLoad, Duration::from_millis(3), 1),
+            (Phase::IndexFetch, Duration::from_millis(250), 2),
+        ];
+        assert_eq!(
+            breakdown(&phases, Duration::from_millis(1800)),
+            "Profile:\n\
+             \x20   config load                3ms\n\
+             \x20   index fetch              250ms (2 times)\n\
+             \x20   unpack                  1500ms\n\
+             \x20   total                   1800ms\n"
+        );
+    }
+}
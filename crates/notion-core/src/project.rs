@@ -1,20 +1,49 @@
 //! Provides the `Project` type, which represents a Node project tree in
 //! the filesystem.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::env;
 use std::ffi::OsStr;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use glob::{MatchOptions, Pattern};
 use lazycell::LazyCell;
+use serde_yaml;
 
+use config::{ProjectConfig, ProjectHooks};
+use fs::{ensure_containing_dir_exists, read_file_opt};
 use manifest::Manifest;
 use manifest::serial::ToolchainManifest;
 use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use path;
+use pin_file::{self, PIN_FILE};
+use plan::Plan;
 use semver::Version;
+use style::display_hint;
+use version::VersionSpec;
+
+/// The files that other Node version managers use to select a Node version for a
+/// project, in order of precedence. They are honored for projects without a toolchain.
+const NODE_VERSION_FILES: [&'static str; 2] = [".nvmrc", ".node-version"];
+
+/// The file at the root of a project that holds the hooks it ships, if any.
+pub const HOOKS_FILE: &'static str = "hooks.toml";
 
 fn is_node_root(dir: &Path) -> bool {
-    dir.join("package.json").is_file()
+    dir.join("package.json").is_file() || dir.join(PIN_FILE).is_file()
+}
+
+/// Loads the manifest of the project rooted at the specified directory, which is empty
+/// for projects that only have a pin file. The toolchain is read from the section
+/// configured by `config`.
+fn load_manifest(dir: &Path, config: &ProjectConfig) -> Fallible<Manifest> {
+    if dir.join("package.json").is_file() {
+        Manifest::for_dir_with_toolchain_key(dir, &config.toolchain_key)
+    } else {
+        Ok(Manifest::empty())
+    }
 }
 
 fn is_node_modules(dir: &Path) -> bool {
@@ -29,6 +58,154 @@ fn is_project_root(dir: &Path) -> bool {
     is_node_root(dir) && !is_dependency(dir)
 }
 
+/// The package patterns of the workspace rooted at the specified project root, if it is
+/// one: either the `workspaces` section of its manifest, or its `pnpm-workspace.yaml`.
+fn workspace_patterns(root: &Path, manifest: &Manifest) -> Fallible<Option<Vec<String>>> {
+    if let Some(ref patterns) = manifest.workspaces {
+        return Ok(Some(patterns.clone()));
+    }
+
+    let pnpm_file = root.join("pnpm-workspace.yaml");
+    match read_file_opt(&pnpm_file).unknown()? {
+        Some(contents) => {
+            let workspace: PnpmWorkspace = serde_yaml::from_str(&contents)
+                .with_context(|error| PnpmWorkspaceParseError::new(&pnpm_file, error))?;
+            Ok(Some(workspace.packages))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns whether the directory `dir` is a member of the workspace rooted at `root`,
+/// given the workspace's package patterns. Later patterns override earlier ones, and
+/// patterns starting with `!` exclude the packages they match.
+fn is_workspace_member(root: &Path, dir: &Path, patterns: &[String]) -> bool {
+    let relative = match dir.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => {
+            return false;
+        }
+    };
+
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    let mut member = false;
+    for pattern in patterns {
+        let (excluded, pattern) = if pattern.starts_with('!') {
+            (true, &pattern[1..])
+        } else {
+            (false, &pattern[..])
+        };
+        let pattern = pattern.trim_left_matches("./").trim_right_matches('/');
+        if let Ok(pattern) = Pattern::new(pattern) {
+            if pattern.matches_path_with(relative, &options) {
+                member = !excluded;
+            }
+        }
+    }
+    member
+}
+
+/// Finds the root of the workspace that the project rooted at `dir` belongs to, if any.
+/// A workspace root is considered to belong to its own workspace.
+fn find_workspace_root(
+    dir: &Path,
+    manifest: &Manifest,
+    config: &ProjectConfig,
+) -> Fallible<Option<PathBuf>> {
+    if workspace_patterns(dir, manifest)?.is_some() {
+        return Ok(Some(PathBuf::from(dir)));
+    }
+
+    let mut ancestor = find_parent_project_root(dir, config);
+    while let Some(root) = ancestor {
+        let root_manifest = load_manifest(root, config)?;
+        if let Some(patterns) = workspace_patterns(root, &root_manifest)? {
+            if is_workspace_member(root, dir, &patterns) {
+                return Ok(Some(PathBuf::from(root)));
+            }
+        }
+        ancestor = find_parent_project_root(root, config);
+    }
+    Ok(None)
+}
+
+/// The directories whose presence marks the root of a version control repository.
+const VCS_DIRS: [&'static str; 2] = [".git", ".hg"];
+
+/// The directory at which project discovery stopped searching parent directories.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Boundary {
+    /// The root of a Git or Mercurial repository.
+    Vcs(PathBuf),
+    /// A directory containing one of the configured boundary markers.
+    Marker(PathBuf),
+}
+
+impl Boundary {
+    /// Returns the directory of this boundary.
+    pub fn dir(&self) -> &Path {
+        match *self {
+            Boundary::Vcs(ref dir) | Boundary::Marker(ref dir) => dir,
+        }
+    }
+}
+
+/// Returns the boundary at the specified directory, if it is one.
+fn boundary_at(dir: &Path, config: &ProjectConfig) -> Option<Boundary> {
+    if config.vcs_boundary && VCS_DIRS.iter().any(|name| dir.join(name).exists()) {
+        return Some(Boundary::Vcs(PathBuf::from(dir)));
+    }
+    if config
+        .boundary_markers
+        .iter()
+        .any(|marker| dir.join(marker).exists())
+    {
+        return Some(Boundary::Marker(PathBuf::from(dir)));
+    }
+    None
+}
+
+/// Finds the innermost boundary at or above the specified directory, if any.
+fn find_boundary(dir: &Path, config: &ProjectConfig) -> Option<Boundary> {
+    let mut dir = dir;
+    loop {
+        if let Some(boundary) = boundary_at(dir, config) {
+            return Some(boundary);
+        }
+        dir = dir.parent()?;
+    }
+}
+
+/// Finds the nearest project root containing the specified directory, without searching
+/// past a boundary.
+fn find_project_root<'a>(dir: &'a Path, config: &ProjectConfig) -> Option<&'a Path> {
+    let mut dir = dir;
+    loop {
+        if is_project_root(dir) {
+            return Some(dir);
+        }
+        if boundary_at(dir, config).is_some() {
+            return None;
+        }
+        dir = dir.parent()?;
+    }
+}
+
+/// Finds the nearest project root above the project rooted at `dir`, without searching
+/// past a boundary.
+fn find_parent_project_root<'a>(dir: &'a Path, config: &ProjectConfig) -> Option<&'a Path> {
+    if boundary_at(dir, config).is_some() {
+        return None;
+    }
+    dir.parent()
+        .and_then(|parent| find_project_root(parent, config))
+}
+
 pub struct LazyDependentBins {
     bins: LazyCell<HashMap<String, String>>,
 }
@@ -63,6 +240,57 @@ impl DepPackageReadError {
     }
 }
 
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct PnpmWorkspaceParseError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+impl PnpmWorkspaceParseError {
+    pub(crate) fn new(file: &Path, error: &serde_yaml::Error) -> Self {
+        PnpmWorkspaceParseError {
+            file: file.display().to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// The contents of a `pnpm-workspace.yaml` file.
+#[derive(Deserialize)]
+struct PnpmWorkspace {
+    #[serde(default)]
+    packages: Vec<String>,
+}
+
+/// The file in which Yarn 2 and later keep a project's settings.
+pub const YARNRC_FILE: &'static str = ".yarnrc.yml";
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not parse {}: {}", file, error)]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct YarnrcParseError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
+
+impl YarnrcParseError {
+    pub(crate) fn new(file: &Path, error: &serde_yaml::Error) -> Self {
+        YarnrcParseError {
+            file: file.display().to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// The settings of a `.yarnrc.yml` file that Notion reads.
+#[derive(Deserialize)]
+struct Yarnrc {
+    #[serde(rename = "yarnPath")]
+    yarn_path: Option<String>,
+}
+
 /// Thrown when a user tries to pin a Yarn version before pinning a Node version.
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "There is no pinned node version for this project")]
@@ -75,46 +303,170 @@ impl NoPinnedNodeVersion {
     }
 }
 
+/// A Node version selected by another version manager's file (e.g. `.nvmrc`).
+pub struct NodeVersionFile {
+    /// The name of the file, e.g. `.nvmrc`.
+    pub name: &'static str,
+    /// The Node version the file selects.
+    pub version: VersionSpec,
+}
+
+impl NodeVersionFile {
+    /// Finds the first node version file in the specified project root that selects a
+    /// version Notion understands, if any.
+    fn for_dir(project_root: &Path) -> Fallible<Option<NodeVersionFile>> {
+        for name in NODE_VERSION_FILES.iter() {
+            let contents = read_file_opt(&project_root.join(name)).unknown()?;
+            let line = contents.as_ref().and_then(|contents| {
+                contents
+                    .lines()
+                    .map(|line| line.trim())
+                    .find(|line| !line.is_empty())
+            });
+            if let Some(Ok(version)) = line.map(VersionSpec::parse) {
+                return Ok(Some(NodeVersionFile {
+                    name: *name,
+                    version,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
 /// A Node project tree in the filesystem.
 pub struct Project {
     manifest: Manifest,
     project_root: PathBuf,
+    toolchain_file: PathBuf,
+    /// The path of keys to the toolchain in package.json (see `ProjectConfig`).
+    toolchain_key: Vec<String>,
+    workspace_root: Option<PathBuf>,
+    boundary: Option<Boundary>,
     dependent_bins: LazyDependentBins,
+    node_version_file: Option<NodeVersionFile>,
 }
 
 impl Project {
     /// Returns the Node project containing the current working directory,
     /// if any.
-    pub fn for_current_dir() -> Fallible<Option<Project>> {
+    pub fn for_current_dir(config: &ProjectConfig) -> Fallible<Option<Project>> {
         let current_dir: &Path = &env::current_dir().unknown()?;
-        Self::for_dir(&current_dir)
+        Self::for_dir_within(&current_dir, config)
     }
 
-    /// Returns the Node project for the input directory, if any.
+    /// Returns the Node project for the input directory, if any, using the default
+    /// discovery boundaries.
     pub fn for_dir(base_dir: &Path) -> Fallible<Option<Project>> {
-        let mut dir = base_dir.clone();
-        while !is_project_root(dir) {
-            dir = match dir.parent() {
-                Some(parent) => parent,
-                None => {
-                    return Ok(None);
+        Self::for_dir_within(base_dir, &ProjectConfig::default())
+    }
+
+    /// Returns the Node project for the input directory, if any, without searching
+    /// past the boundaries configured by `config`.
+    pub fn for_dir_within(base_dir: &Path, config: &ProjectConfig) -> Fallible<Option<Project>> {
+        let dir = match find_project_root(base_dir, config) {
+            Some(dir) => dir,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let mut manifest = load_manifest(dir, config)?;
+        let workspace_root = find_workspace_root(dir, &manifest, config)?;
+
+        // Pins go in package.json, unless the project has no package.json or keeps
+        // its pins in a pin file instead.
+        let pin_file = dir.join(PIN_FILE);
+        let mut toolchain_file = dir.join("package.json");
+        if !manifest.has_toolchain() && pin_file.is_file() {
+            manifest.toolchain = pin_file::read_toolchain(&pin_file)?;
+            toolchain_file = pin_file;
+        } else if !toolchain_file.is_file() {
+            toolchain_file = pin_file;
+        }
+
+        // Members of a workspace usually don't pin a toolchain of their own, and
+        // instead inherit the toolchain pinned at the workspace root.
+        if !manifest.has_toolchain() {
+            if let Some(ref root) = workspace_root {
+                let root_manifest = load_manifest(root, config)?;
+                if root_manifest.has_toolchain() {
+                    manifest.toolchain = root_manifest.toolchain;
+                    toolchain_file = root.join("package.json");
                 }
             }
         }
 
+        let node_version_file = if manifest.has_toolchain() {
+            None
+        } else {
+            NodeVersionFile::for_dir(&dir)?
+        };
+
         Ok(Some(Project {
-            manifest: Manifest::for_dir(&dir)?,
+            manifest,
             project_root: PathBuf::from(dir),
+            toolchain_file,
+            toolchain_key: config.toolchain_key.clone(),
+            workspace_root,
+            boundary: find_boundary(dir, config),
             dependent_bins: LazyDependentBins::new(),
+            node_version_file,
         }))
     }
 
-    /// Returns true if the project manifest contains a toolchain.
+    /// Returns the root directory of this project.
+    pub fn root(&self) -> &Path {
+        &self.project_root
+    }
+
+    /// Returns the `.nvmrc` or `.node-version` file selecting this project's Node
+    /// version, if the project has no toolchain but has such a file.
+    pub fn node_version_file(&self) -> Option<&NodeVersionFile> {
+        self.node_version_file.as_ref()
+    }
+
+    /// Suggests pinning the project's toolchain instead of relying on its node version
+    /// file. The hint is only shown the first time the file is used for this project.
+    pub fn hint_node_version_file(&self) -> Fallible<()> {
+        let file = match self.node_version_file {
+            Some(ref file) => file,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let hints_file = path::node_version_file_hints_file()?;
+        let root = self.project_root.to_string_lossy().to_string();
+        let hinted = read_file_opt(&hints_file).unknown()?;
+        if hinted.map_or(false, |hinted| hinted.lines().any(|line| line == root)) {
+            return Ok(());
+        }
+
+        display_hint(&format!(
+            "using Node {} from {}; run `notion pin node {}` to pin it in package.json",
+            file.version, file.name, file.version
+        ));
+
+        ensure_containing_dir_exists(&hints_file)?;
+        let mut hints = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&hints_file)
+            .unknown()?;
+        writeln!(hints, "{}", root).unknown()?;
+        Ok(())
+    }
+
+    /// Returns true if the project manifest (or the project's pin file) contains a
+    /// toolchain.
     pub fn is_pinned(&self) -> bool {
         self.manifest.has_toolchain()
     }
 
-    /// Returns the project manifest (`package.json`) for this project.
+    /// Returns the project manifest (`package.json`) for this project. For a project
+    /// that pins its toolchain in a pin file, the manifest's toolchain is the one the
+    /// pin file specifies.
     pub fn manifest(&self) -> &Manifest {
         &self.manifest
     }
@@ -124,12 +476,124 @@ impl Project {
         self.project_root.join("package.json")
     }
 
+    /// Returns the root directory of the workspace this project belongs to, if any. For
+    /// the root of a workspace, this is the project's own root directory.
+    pub fn workspace_root(&self) -> Option<&Path> {
+        self.workspace_root.as_ref().map(|root| root.as_path())
+    }
+
+    /// Returns the `.yarnrc.yml` files that can set this project's `yarnPath`: its own,
+    /// and then its workspace root's, whether they exist or not.
+    pub fn yarnrc_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![self.project_root.join(YARNRC_FILE)];
+        files.extend(self.workspace_root().map(|root| root.join(YARNRC_FILE)));
+        files
+    }
+
+    /// Returns the release of Yarn this project vendors, if it uses Yarn 2 or later: the
+    /// file (usually a `.yarn/releases/yarn-*.cjs` the project commits) that the
+    /// `yarnPath` setting of its `.yarnrc.yml` points to, relative to that file. A
+    /// `yarnPath` pointing to a file that doesn't exist is ignored.
+    pub fn vendored_yarn(&self) -> Fallible<Option<PathBuf>> {
+        for file in self.yarnrc_files() {
+            let contents = match read_file_opt(&file).unknown()? {
+                Some(contents) => contents,
+                None => continue,
+            };
+            let yarnrc: Yarnrc = serde_yaml::from_str(&contents)
+                .with_context(|error| YarnrcParseError::new(&file, error))?;
+            if let Some(yarn_path) = yarnrc.yarn_path {
+                let release = file.with_file_name(yarn_path);
+                if release.is_file() {
+                    return Ok(Some(release));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns true if this project is a member of a workspace rooted in another directory.
+    pub fn is_workspace_member(&self) -> bool {
+        self.workspace_root
+            .as_ref()
+            .map_or(false, |root| root != &self.project_root)
+    }
+
+    /// Returns the boundary that limited the search for this project and its workspace,
+    /// if discovery didn't search all the way to the root of the filesystem.
+    pub fn boundary(&self) -> Option<&Boundary> {
+        self.boundary.as_ref()
+    }
+
+    /// Returns the path to the file that pins this project's toolchain: usually its
+    /// `package.json` file, or its `.notion.toml` pin file if it uses one or has no
+    /// `package.json`. For a workspace member that inherits its toolchain, this is the
+    /// `package.json` file of the workspace root.
+    pub fn toolchain_file(&self) -> PathBuf {
+        self.toolchain_file.clone()
+    }
+
+    /// Returns true if this project's toolchain is pinned in a pin file.
+    fn uses_pin_file(&self) -> bool {
+        self.toolchain_file.file_name() == Some(OsStr::new(PIN_FILE))
+    }
+
+    /// Returns the file name of the file that pins this project's toolchain, for messages.
+    fn toolchain_file_name(&self) -> String {
+        self.toolchain_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Plans to write the input ToolchainManifest to the file that pins this project's
+    /// toolchain.
+    fn plan_toolchain(&self, toolchain: ToolchainManifest, plan: &mut Plan) -> Fallible<()> {
+        if self.uses_pin_file() {
+            pin_file::plan_update_toolchain(toolchain, &self.toolchain_file, plan)
+        } else {
+            Manifest::plan_update_toolchain(
+                toolchain,
+                &self.toolchain_key,
+                self.toolchain_file(),
+                plan,
+            )
+        }
+    }
+
+    /// Removes the toolchain, or only the `tool` pin, from the file that pins this
+    /// project's toolchain. Returns `false` if there was nothing to remove.
+    fn remove_toolchain(&self, tool: Option<&str>) -> Fallible<bool> {
+        if self.uses_pin_file() {
+            pin_file::remove_toolchain(tool, &self.toolchain_file)
+        } else {
+            Manifest::remove_toolchain_at(&self.toolchain_key, tool, self.toolchain_file())
+        }
+    }
+
+    /// Returns the hooks this project ships for everyone who works on it: those of the
+    /// `hooks.toml` file at its root if it has one, and otherwise those of the
+    /// `notion.hooks` section of its manifest.
+    pub fn hooks(&self) -> Fallible<Option<ProjectHooks>> {
+        let hooks_file = self.project_root.join(HOOKS_FILE);
+        if let Some(src) = read_file_opt(&hooks_file).unknown()? {
+            return Ok(Some(src.parse()?));
+        }
+        Ok(self.manifest.hooks.clone())
+    }
+
     /// Returns the path to the local binary directory for this project.
     pub fn local_bin_dir(&self) -> PathBuf {
         let sub_dir: PathBuf = ["node_modules", ".bin"].iter().collect();
         self.project_root.join(sub_dir)
     }
 
+    /// Returns true if the project's local binary directory has an executable with the
+    /// input name, whether a direct dependency provides it or one of their dependencies.
+    pub fn has_local_bin(&self, bin_name: &OsStr) -> bool {
+        self.local_bin_dir().join(bin_name).is_file()
+    }
+
     /// Returns true if the input binary name is a direct dependency of the input project
     pub fn has_direct_bin(&self, bin_name: &OsStr) -> Fallible<bool> {
         let dep_bins = self.dependent_bins.get(&self)?;
@@ -143,7 +607,7 @@ impl Project {
 
     /// Gets the names of all the direct dependencies of the current project
     fn all_dependencies(&self) -> Fallible<HashSet<String>> {
-        let manifest = Manifest::for_dir(&self.project_root)?;
+        let manifest = &self.manifest;
         let mut dependencies = HashSet::new();
         for (name, _version) in manifest.dependencies.iter() {
             dependencies.insert(name.clone());
@@ -182,27 +646,153 @@ impl Project {
         Ok(dependent_bins)
     }
 
-    /// Writes the specified version of Node to the `toolchain.node` key in package.json.
+    /// Returns the binaries that more than one direct dependency of the current project
+    /// declares, mapped to the names of those dependencies. Only one of them can be
+    /// installed in `node_modules/.bin`, so which one a shim runs is up to the package
+    /// manager.
+    pub fn bin_collisions(&self) -> Fallible<BTreeMap<String, Vec<String>>> {
+        let mut providers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let all_deps: BTreeSet<String> = self.all_dependencies()?.into_iter().collect();
+        for dep_name in all_deps {
+            let pkg_path = self.project_root.join("node_modules").join(&dep_name);
+            let pkg_info =
+                Manifest::for_dir(&pkg_path).with_context(DepPackageReadError::from_error)?;
+            for name in pkg_info.bin.keys() {
+                providers
+                    .entry(name.clone())
+                    .or_insert_with(Vec::new)
+                    .push(dep_name.clone());
+            }
+        }
+        Ok(providers
+            .into_iter()
+            .filter(|&(_, ref deps)| deps.len() > 1)
+            .collect())
+    }
+
+    /// Writes the specified version of Node to the `toolchain.node` key of the file that
+    /// pins this project's toolchain.
     pub fn pin_node_in_toolchain(&self, node_version: Version) -> Fallible<()> {
+        let mut plan = Plan::new();
+        self.plan_node_pin(&node_version, &mut plan)?;
+        plan.write_files()?;
+        println!(
+            "Pinned node to version {} in {}",
+            node_version,
+            self.toolchain_file_name()
+        );
+        Ok(())
+    }
+
+    /// Plans the write of `pin_node_in_toolchain`.
+    pub(crate) fn plan_node_pin(&self, node_version: &Version, plan: &mut Plan) -> Fallible<()> {
         // update the toolchain node version
-        let toolchain =
-            ToolchainManifest::new(node_version.to_string(), self.manifest().yarn_str().clone());
-        Manifest::update_toolchain(toolchain, self.package_file())?;
-        println!("Pinned node to version {} in package.json", node_version);
+        let toolchain = ToolchainManifest::new(
+            node_version.to_string(),
+            self.manifest().yarn_str(),
+            self.manifest().npm_str(),
+            self.manifest().pnpm_str(),
+        );
+        self.plan_toolchain(toolchain, plan)
+    }
+
+    /// Writes a range compatible with the specified version of Node to the `engines.node`
+    /// key of the `package.json` file next to the file that pins this project's
+    /// toolchain, so that npm and Yarn, which only read `engines`, expect the pinned
+    /// version too. Returns `false` if there is no such `package.json` file.
+    pub fn pin_node_in_engines(&self, node_version: &Version) -> Fallible<bool> {
+        let mut plan = Plan::new();
+        if !self.plan_engines_pin(node_version, &mut plan)? {
+            return Ok(false);
+        }
+        plan.write_files()?;
+        println!("Set engines.node to ^{} in package.json", node_version);
+        Ok(true)
+    }
+
+    /// Plans the write of `pin_node_in_engines`, on top of the writes already planned.
+    pub(crate) fn plan_engines_pin(
+        &self,
+        node_version: &Version,
+        plan: &mut Plan,
+    ) -> Fallible<bool> {
+        let package_file = self.toolchain_file.with_file_name("package.json");
+        if !package_file.is_file() {
+            return Ok(false);
+        }
+        let range = format!("^{}", node_version);
+        Manifest::plan_update_engines_node(&range, package_file, plan)?;
+        Ok(true)
+    }
+
+    /// Starts a `.notion.toml` pin file in the specified directory, pinning the specified
+    /// version of Node. This is how projects without a `package.json` file get pinned.
+    pub fn pin_node_in_new_pin_file(dir: &Path, node_version: Version) -> Fallible<()> {
+        let mut plan = Plan::new();
+        Project::plan_new_pin_file(dir, &node_version, &mut plan)?;
+        plan.write_files()?;
+        println!("Pinned node to version {} in {}", node_version, PIN_FILE);
         Ok(())
     }
 
-    /// Writes the specified version of Yarn to the `toolchain.yarn` key in package.json.
+    /// Plans the write of `pin_node_in_new_pin_file`.
+    pub(crate) fn plan_new_pin_file(
+        dir: &Path,
+        node_version: &Version,
+        plan: &mut Plan,
+    ) -> Fallible<()> {
+        let toolchain = ToolchainManifest::new(node_version.to_string(), None, None, None);
+        pin_file::plan_update_toolchain(toolchain, &dir.join(PIN_FILE), plan)
+    }
+
+    /// Writes the specified version of Yarn to the `toolchain.yarn` key of the file that
+    /// pins this project's toolchain.
     pub fn pin_yarn_in_toolchain(&self, yarn_version: Version) -> Fallible<()> {
+        let mut plan = Plan::new();
+        self.plan_yarn_pin(&yarn_version, &mut plan)?;
+        plan.write_files()?;
+        println!(
+            "Pinned yarn to version {} in {}",
+            yarn_version,
+            self.toolchain_file_name()
+        );
+        Ok(())
+    }
+
+    /// Plans the write of `pin_yarn_in_toolchain`.
+    pub(crate) fn plan_yarn_pin(&self, yarn_version: &Version, plan: &mut Plan) -> Fallible<()> {
         // update the toolchain yarn version
         if let Some(node_str) = self.manifest().node_str() {
-            let toolchain =
-                ToolchainManifest::new(node_str.clone(), Some(yarn_version.to_string()));
-            Manifest::update_toolchain(toolchain, self.package_file())?;
-            println!("Pinned yarn to version {} in package.json", yarn_version);
+            let toolchain = ToolchainManifest::new(
+                node_str.clone(),
+                Some(yarn_version.to_string()),
+                self.manifest().npm_str(),
+                self.manifest().pnpm_str(),
+            );
+            self.plan_toolchain(toolchain, plan)
         } else {
             throw!(NoPinnedNodeVersion::new());
         }
+    }
+
+    /// Removes the `toolchain` key from package.json. Every other pin depends on the
+    /// pinned Node version, so this is also how Node is unpinned.
+    pub fn unpin_toolchain(&self) -> Fallible<()> {
+        if self.remove_toolchain(None)? {
+            println!("Unpinned the toolchain in {}", self.toolchain_file_name());
+        } else {
+            println!("No toolchain is pinned in {}", self.toolchain_file_name());
+        }
+        Ok(())
+    }
+
+    /// Removes the `toolchain.yarn` key from package.json.
+    pub fn unpin_yarn_from_toolchain(&self) -> Fallible<()> {
+        if self.remove_toolchain(Some("yarn"))? {
+            println!("Unpinned yarn in {}", self.toolchain_file_name());
+        } else {
+            println!("No yarn version is pinned in {}", self.toolchain_file_name());
+        }
         Ok(())
     }
 }
@@ -214,9 +804,15 @@ pub mod tests {
     use std::collections::HashMap;
     use std::collections::HashSet;
     use std::ffi::OsStr;
+    use std::fs;
     use std::path::PathBuf;
 
-    use project::Project;
+    use tempfile::tempdir;
+
+    use config::ProjectConfig;
+    use project::{Boundary, Project};
+    use semver::Version;
+    use version::VersionSpec;
 
     fn fixture_path(fixture_dir: &str) -> PathBuf {
         let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -268,6 +864,190 @@ pub mod tests {
         assert!(test_project.has_direct_bin(&OsStr::new("bin-2")).unwrap());
     }
 
+    #[test]
+    fn detects_bin_collisions() {
+        let basic = Project::for_dir(&fixture_path("basic")).unwrap().unwrap();
+        assert!(basic.bin_collisions().unwrap().is_empty());
+
+        let project_path = fixture_path("bin_collision");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        let collisions = test_project.bin_collisions().unwrap();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(
+            collisions.get("lint"),
+            Some(&vec!["lint-a".to_string(), "lint-b".to_string()])
+        );
+    }
+
+    #[test]
+    fn node_version_file_without_toolchain() {
+        let project_path = fixture_path("nvmrc");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        let file = test_project
+            .node_version_file()
+            .expect("Could not find .nvmrc");
+        assert_eq!(file.name, ".nvmrc");
+        match file.version {
+            VersionSpec::Semver(ref req) => assert!(req.matches(&Version::parse("8.9.4").unwrap())),
+            ref spec => panic!("expected a semver requirement, got {}", spec),
+        }
+    }
+
+    #[test]
+    fn node_version_file_ignored_with_toolchain() {
+        let project_path = fixture_path("basic");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert!(test_project.node_version_file().is_none());
+    }
+
+    #[test]
+    fn workspace_member_inherits_toolchain() {
+        let project_path = fixture_path("workspace/packages/member");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert!(test_project.is_pinned());
+        assert_eq!(
+            test_project.manifest().node().unwrap(),
+            Version::parse("10.11.0").unwrap()
+        );
+        assert_eq!(
+            test_project.toolchain_file(),
+            fixture_path("workspace").join("package.json")
+        );
+        assert_eq!(
+            test_project.package_file(),
+            project_path.join("package.json")
+        );
+    }
+
+    #[test]
+    fn workspace_root_is_reported() {
+        let root_path = fixture_path("workspace");
+        let member = Project::for_dir(&root_path.join("packages").join("member"))
+            .unwrap()
+            .unwrap();
+        assert!(member.is_workspace_member());
+        assert_eq!(member.workspace_root(), Some(root_path.as_path()));
+
+        let root = Project::for_dir(&root_path).unwrap().unwrap();
+        assert!(!root.is_workspace_member());
+        assert_eq!(root.workspace_root(), Some(root_path.as_path()));
+
+        let standalone = Project::for_dir(&fixture_path("basic")).unwrap().unwrap();
+        assert!(!standalone.is_workspace_member());
+        assert_eq!(standalone.workspace_root(), None);
+    }
+
+    #[test]
+    fn workspace_patterns_select_members() {
+        let project_path = fixture_path("workspace/tools/excluded");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert!(!test_project.is_workspace_member());
+        assert!(!test_project.is_pinned());
+    }
+
+    #[test]
+    fn pnpm_workspace_member_inherits_toolchain() {
+        let project_path = fixture_path("pnpm_workspace/packages/app");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert!(test_project.is_workspace_member());
+        assert_eq!(
+            test_project.workspace_root(),
+            Some(fixture_path("pnpm_workspace").as_path())
+        );
+        assert_eq!(
+            test_project.manifest().node().unwrap(),
+            Version::parse("10.11.0").unwrap()
+        );
+    }
+
+    #[test]
+    fn finds_vendored_yarn() {
+        let project_path = fixture_path("yarn_berry");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert_eq!(
+            test_project.vendored_yarn().unwrap(),
+            Some(project_path.join(".yarn/releases/yarn-2.4.1.cjs"))
+        );
+
+        let basic = Project::for_dir(&fixture_path("basic")).unwrap().unwrap();
+        assert_eq!(basic.vendored_yarn().unwrap(), None);
+    }
+
+    #[test]
+    fn discovery_stops_at_boundaries() {
+        // repository/package.json
+        // repository/.git/
+        // repository/.notion-root
+        // repository/nested/
+        let outer = tempdir().expect("Could not create temporary directory");
+        fs::write(outer.path().join("package.json"), "{}").unwrap();
+        let repository = outer.path().join("repository");
+        let nested = repository.join("nested");
+        fs::create_dir_all(repository.join(".git")).unwrap();
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(repository.join(".notion-root"), "").unwrap();
+
+        let vcs = ProjectConfig::default();
+        assert!(Project::for_dir_within(&nested, &vcs).unwrap().is_none());
+
+        let markers = ProjectConfig {
+            vcs_boundary: false,
+            boundary_markers: vec![".notion-root".to_string()],
+            ..ProjectConfig::default()
+        };
+        assert!(Project::for_dir_within(&nested, &markers).unwrap().is_none());
+
+        let unbounded = ProjectConfig {
+            vcs_boundary: false,
+            boundary_markers: vec![],
+            ..ProjectConfig::default()
+        };
+        let project = Project::for_dir_within(&nested, &unbounded)
+            .unwrap()
+            .unwrap();
+        assert_eq!(project.package_file(), outer.path().join("package.json"));
+
+        // a package.json at the boundary itself still belongs to the project
+        fs::write(repository.join("package.json"), "{}").unwrap();
+        let project = Project::for_dir_within(&nested, &vcs).unwrap().unwrap();
+        assert_eq!(project.package_file(), repository.join("package.json"));
+        assert_eq!(project.boundary(), Some(&Boundary::Vcs(repository.clone())));
+        let project = Project::for_dir_within(&nested, &markers).unwrap().unwrap();
+        assert_eq!(project.boundary(), Some(&Boundary::Marker(repository.clone())));
+    }
+
+    #[test]
+    fn pin_file_without_package_json() {
+        let project_path = fixture_path("pin_file");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert!(test_project.is_pinned());
+        assert_eq!(
+            test_project.manifest().node().unwrap(),
+            Version::parse("10.11.0").unwrap()
+        );
+        assert_eq!(
+            test_project.manifest().yarn().unwrap(),
+            Version::parse("1.10.1").unwrap()
+        );
+        assert_eq!(
+            test_project.toolchain_file(),
+            project_path.join(".notion.toml")
+        );
+        assert!(test_project.all_dependencies().unwrap().is_empty());
+    }
+
+    #[test]
+    fn toolchain_not_inherited_outside_workspace() {
+        // the repository root pins a toolchain, but isn't a workspace root
+        let project_path = fixture_path("no_toolchain");
+        let test_project = Project::for_dir(&project_path).unwrap().unwrap();
+        assert!(!test_project.is_pinned());
+        assert_eq!(
+            test_project.toolchain_file(),
+            project_path.join("package.json")
+        );
+    }
+
     #[test]
     fn local_bin_false() {
         let project_path = fixture_path("basic");
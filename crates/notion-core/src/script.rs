@@ -0,0 +1,214 @@
+//! Provides support for running the scripts in the `scripts` section of a
+//! project's manifest, with the environment npm gives them.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use serde_json::{self, Value};
+
+use env::ToolKind;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use session::{NotInPackageError, Session};
+
+/// Thrown when the user tries to run a script that the project's manifest doesn't define.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no script named `{}` in {}", name, file)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NoSuchScriptError {
+    pub(crate) name: String,
+    pub(crate) file: String,
+}
+
+impl NoSuchScriptError {
+    pub(crate) fn new(name: &str, package_file: &Path) -> Self {
+        NoSuchScriptError {
+            name: name.to_string(),
+            file: package_file.display().to_string(),
+        }
+    }
+}
+
+/// Converts a key of the manifest into the form npm uses in environment variable
+/// names, in which anything other than letters, digits, and `_` becomes `_`.
+fn env_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn flatten(prefix: &str, value: &Value, vars: &mut Vec<(String, String)>) {
+    match *value {
+        Value::Object(ref map) => for (key, value) in map.iter() {
+            flatten(&format!("{}_{}", prefix, env_key(key)), value, vars);
+        },
+        Value::Array(ref array) => for (index, value) in array.iter().enumerate() {
+            flatten(&format!("{}_{}", prefix, index), value, vars);
+        },
+        Value::String(ref string) => vars.push((prefix.to_string(), string.clone())),
+        Value::Number(ref number) => vars.push((prefix.to_string(), number.to_string())),
+        Value::Bool(boolean) => vars.push((prefix.to_string(), boolean.to_string())),
+        Value::Null => {}
+    }
+}
+
+/// Produces the `npm_package_*` environment variables for a manifest, which npm
+/// derives by flattening its contents: `npm_package_name`, `npm_package_version`,
+/// `npm_package_scripts_test`, and so on.
+pub fn package_env(package_file: &Path) -> Fallible<Vec<(String, String)>> {
+    let mut contents = String::new();
+    File::open(package_file)
+        .and_then(|mut file| file.read_to_string(&mut contents))
+        .unknown()?;
+    let manifest: Value = serde_json::from_str(&contents).unknown()?;
+    let mut vars = Vec::new();
+    flatten("npm_package", &manifest, &mut vars);
+    Ok(vars)
+}
+
+/// Quotes an argument to a script for the shell that runs it.
+#[cfg(unix)]
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// Quotes an argument to a script for the shell that runs it.
+#[cfg(windows)]
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('"', "\"\""))
+}
+
+/// Builds the command that runs a script and appends the specified arguments to it,
+/// through `sh` (or `cmd.exe` on Windows), as npm does.
+fn command(script: &str, args: &[String]) -> Command {
+    let mut line = script.to_string();
+    for arg in args {
+        line.push(' ');
+        line.push_str(&quote(arg));
+    }
+
+    #[cfg(unix)]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(line);
+        command
+    };
+
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd.exe");
+        command.args(&["/d", "/s", "/c"]).arg(line);
+        command
+    };
+
+    command.env("npm_lifecycle_script", script);
+    command
+}
+
+/// Produces the `PATH` for running a script: the project's `node_modules/.bin`
+/// directory ahead of the specified `PATH`, as npm does.
+fn path_for_script(local_bin_dir: &Path, path_var: &OsString) -> OsString {
+    let mut paths = vec![local_bin_dir.to_path_buf()];
+    paths.extend(env::split_paths(path_var));
+    env::join_paths(paths).unwrap()
+}
+
+/// Builds the commands that run the named script of the current project, preceded
+/// by its `pre` script and followed by its `post` script if the manifest defines
+/// them. Each command is paired with the name of the script it runs, and only the
+/// named script itself receives the specified arguments.
+///
+/// The scripts run with the project's toolchain and `node_modules/.bin` directory
+/// first on their `PATH`, and with the `npm_lifecycle_event`, `npm_lifecycle_script`,
+/// and `npm_package_*` environment variables npm sets.
+pub fn lifecycle(
+    session: &mut Session,
+    name: &str,
+    args: &[String],
+) -> Fallible<Vec<(String, Command)>> {
+    let tool_env = session.env_for_tool(ToolKind::Toolchain)?;
+
+    let project = match session.project() {
+        Some(project) => project,
+        None => throw!(NotInPackageError::new()),
+    };
+    let package_file = project.package_file();
+    let scripts = &project.manifest().scripts;
+    if !scripts.contains_key(name) {
+        throw!(NoSuchScriptError::new(name, &package_file));
+    }
+
+    let package_env = package_env(&package_file)?;
+    let path_var = path_for_script(&project.local_bin_dir(), &tool_env.path);
+    let stages = vec![
+        (format!("pre{}", name), &[][..]),
+        (name.to_string(), args),
+        (format!("post{}", name), &[][..]),
+    ];
+
+    let mut commands = Vec::new();
+    for (event, stage_args) in stages {
+        if let Some(script) = scripts.get(&event) {
+            let mut script_command = command(script, stage_args);
+            tool_env.apply(&mut script_command);
+            script_command
+                .current_dir(project.root())
+                .envs(package_env.iter().map(|&(ref key, ref value)| (key, value)))
+                .env("npm_lifecycle_event", &event)
+                .env("PATH", &path_var);
+            commands.push((event, script_command));
+        }
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{env_key, package_env, quote};
+    use std::path::PathBuf;
+
+    fn fixture_path(fixture_dir: &str) -> PathBuf {
+        let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        cargo_manifest_dir.push("fixtures");
+        cargo_manifest_dir.push(fixture_dir);
+        cargo_manifest_dir
+    }
+
+    #[test]
+    fn test_env_key() {
+        assert_eq!(env_key("name"), "name");
+        assert_eq!(env_key("@namespace/some-dep"), "_namespace_some_dep");
+    }
+
+    #[test]
+    fn test_package_env() {
+        let vars = package_env(&fixture_path("basic").join("package.json")).unwrap();
+        let get = |key: &str| {
+            vars.iter()
+                .filter(|&&(ref name, _)| name == key)
+                .map(|&(_, ref value)| value.clone())
+                .next()
+        };
+        assert_eq!(get("npm_package_name"), Some("basic-project".to_string()));
+        assert_eq!(get("npm_package_version"), Some("0.0.7".to_string()));
+        assert_eq!(
+            get("npm_package_scripts_test"),
+            Some("eslint . && mocha".to_string())
+        );
+        assert_eq!(
+            get("npm_package_dependencies__namespace_some_dep"),
+            Some("0.2.4".to_string())
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_quote() {
+        assert_eq!(quote("--watch"), "'--watch'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+}
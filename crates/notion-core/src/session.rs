@@ -4,17 +4,35 @@
 
 use std::env::{self, VarError};
 
-use catalog::{Catalog, LazyCatalog};
-use config::{Config, LazyConfig};
+use catalog::references::{Pins, References};
+use catalog::{Catalog, LazyCatalog, ReleaseInfo, ToolSpec, VersionData, YarnRelease};
+use config::{Config, LazyConfig, ProjectConfig};
 use distro::Fetched;
+use lifecycle::{self, Action};
+use package::{self, OneOff, Package};
+use path;
 use plugin::Publish;
+use pin_file::PIN_FILE;
+use plan::{Change, Plan};
+use profile::{self, Phase};
+use platform::{self, Platform};
 use project::Project;
+use env::{self as notion_env, ToolEnv, ToolKind};
+use shadow;
+use shim;
+use signal;
+use state::State;
+use style::display_warning;
+use trace::Trace;
 use version::VersionSpec;
 
+use std::collections::BTreeSet;
 use std::fmt::{self, Display, Formatter};
-use std::process::exit;
+use std::path::{Path, PathBuf};
+use std::process::{exit, ExitStatus};
 
 use event::EventLog;
+use telemetry::Recorder;
 use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
 use semver::Version;
 
@@ -22,19 +40,46 @@ use semver::Version;
 pub enum ActivityKind {
     Fetch,
     Install,
+    Import,
+    Export,
     Uninstall,
+    Gc,
     Current,
+    Prompt,
+    Query,
+    Activate,
     Deactivate,
+    Setup,
+    Hook,
+    Env,
+    Completions,
+    Exec,
+    Run,
+    X,
     Default,
     Use,
+    Pin,
+    Unpin,
+    Update,
+    List,
+    LsRemote,
+    Info,
+    Outdated,
     Node,
     Yarn,
+    Npm,
+    Pnpm,
     Notion,
     Tool,
     Help,
     Version,
     Binary,
     Shim,
+    Which,
+    Why,
+    Doctor,
+    SelfUpdate,
+    Telemetry,
 }
 
 impl Display for ActivityKind {
@@ -42,19 +87,46 @@ impl Display for ActivityKind {
         let s = match self {
             &ActivityKind::Fetch => "fetch",
             &ActivityKind::Install => "install",
+            &ActivityKind::Import => "import",
+            &ActivityKind::Export => "export",
             &ActivityKind::Uninstall => "uninstall",
+            &ActivityKind::Gc => "gc",
             &ActivityKind::Current => "current",
+            &ActivityKind::Prompt => "prompt",
+            &ActivityKind::Query => "query",
+            &ActivityKind::Activate => "activate",
             &ActivityKind::Deactivate => "deactivate",
+            &ActivityKind::Setup => "setup",
+            &ActivityKind::Hook => "hook",
+            &ActivityKind::Env => "env",
+            &ActivityKind::Completions => "completions",
+            &ActivityKind::Exec => "exec",
+            &ActivityKind::Run => "run",
+            &ActivityKind::X => "x",
             &ActivityKind::Default => "default",
             &ActivityKind::Use => "use",
+            &ActivityKind::Pin => "pin",
+            &ActivityKind::Unpin => "unpin",
+            &ActivityKind::Update => "update",
+            &ActivityKind::List => "list",
+            &ActivityKind::LsRemote => "ls-remote",
+            &ActivityKind::Info => "info",
+            &ActivityKind::Outdated => "outdated",
             &ActivityKind::Node => "node",
             &ActivityKind::Yarn => "yarn",
+            &ActivityKind::Npm => "npm",
+            &ActivityKind::Pnpm => "pnpm",
             &ActivityKind::Notion => "notion",
             &ActivityKind::Tool => "tool",
             &ActivityKind::Help => "help",
             &ActivityKind::Version => "version",
             &ActivityKind::Binary => "binary",
             &ActivityKind::Shim => "shim",
+            &ActivityKind::Which => "which",
+            &ActivityKind::Why => "why",
+            &ActivityKind::Doctor => "doctor",
+            &ActivityKind::SelfUpdate => "self-update",
+            &ActivityKind::Telemetry => "telemetry",
         };
         f.write_str(s)
     }
@@ -72,6 +144,190 @@ impl NotInPackageError {
     }
 }
 
+/// Thrown when the user tries to uninstall a tool version that isn't installed.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} version {} is not installed", tool, version)]
+#[notion_fail(
+    code = "NoVersionMatch",
+    suggestion = "run `notion list` to see the installed versions",
+    error_code = "NOT_INSTALLED"
+)]
+pub(crate) struct NotInstalledError {
+    pub(crate) tool: String,
+    pub(crate) version: String,
+}
+
+/// Thrown when the user tries to uninstall a tool version that is still in use,
+/// without forcing it.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} version {} is {}\nUse `--force` to uninstall it anyway.", tool, version,
+       usage)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct VersionInUseError {
+    pub(crate) tool: String,
+    pub(crate) version: String,
+    pub(crate) usage: String,
+}
+
+/// Thrown when the user tries to install a package without a version of Node to
+/// install it with.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "No Node version selected to install package '{}' with", name)]
+#[notion_fail(
+    code = "NoVersionMatch",
+    suggestion = "select a default with `notion default node <version>`",
+    error_code = "NO_VERSION_SELECTED"
+)]
+pub(crate) struct NoPackageNodeError {
+    pub(crate) name: String,
+}
+
+/// Thrown when the selected version of a tool isn't installed, and fetching it on
+/// demand is forbidden.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} v{} is not installed and implicit fetches are disabled; fetch it with `notion fetch {}@{}`",
+       tool, version, command, version)]
+#[notion_fail(code = "NoVersionMatch", error_code = "IMPLICIT_FETCH_DISABLED")]
+pub(crate) struct ImplicitFetchError {
+    pub(crate) tool: String,
+    pub(crate) command: String,
+    pub(crate) version: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} is not a directory", dir)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NoSuchDirError {
+    pub(crate) dir: String,
+}
+
+/// Fails if fetching missing tool versions on demand is forbidden, naming the
+/// missing version of the specified tool.
+pub(crate) fn check_implicit_fetch(
+    config: &Config,
+    tool: &str,
+    command: &str,
+    version: &Version,
+) -> Fallible<()> {
+    if config.no_implicit_fetch() {
+        throw!(ImplicitFetchError {
+            tool: tool.to_string(),
+            command: command.to_string(),
+            version: version.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Where the version of a tool selected for the current session comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    /// Pinned in the toolchain of the specified `package.json` or pin file.
+    Project(PathBuf),
+    /// Declared as the project's `packageManager`.
+    PackageManager,
+    /// Selected by the project's `.nvmrc` or `.node-version` file.
+    NodeVersionFile(&'static str),
+    /// Constrained by the `engines` section of the project's manifest.
+    Engines,
+    /// Overridden for the current shell by `notion use`, which sets the `NOTION_NODE_VERSION`
+    /// or `NOTION_YARN_VERSION` environment variable.
+    Override,
+    /// The user's default version.
+    User,
+}
+
+impl Display for VersionSource {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            &VersionSource::Project(ref file) => match file.file_name() {
+                Some(name) => write!(f, "pinned in {}", name.to_string_lossy()),
+                None => write!(f, "pinned in {}", file.display()),
+            },
+            &VersionSource::PackageManager => f.write_str("packageManager in package.json"),
+            &VersionSource::NodeVersionFile(name) => write!(f, "selected by {}", name),
+            &VersionSource::Engines => f.write_str("engines in package.json"),
+            &VersionSource::Override => f.write_str("overridden by `notion use`"),
+            &VersionSource::User => f.write_str("user default"),
+        }
+    }
+}
+
+/// A version of a tool selected for the current session, along with where the
+/// selection came from.
+#[derive(Debug, Clone)]
+pub struct ActiveVersion {
+    pub version: Version,
+    pub source: VersionSource,
+    /// Whether the version is already installed. Running the tool installs it if not.
+    pub installed: bool,
+}
+
+/// Records the versions pinned by a toolchain file in the references used by `notion gc`.
+fn register_pins(toolchain_file: &Path, pins: Pins) -> Fallible<()> {
+    let mut references = References::current()?;
+    if references.register(toolchain_file, pins) {
+        references.save()?;
+    }
+    Ok(())
+}
+
+/// Plans setting the user's default version of a tool, fetching it into `dir` first if it
+/// isn't installed yet.
+fn plan_default(
+    tool: &str,
+    version: &Version,
+    installed: bool,
+    default: Option<&Version>,
+    dir: PathBuf,
+) -> Fallible<Plan> {
+    let mut plan = Plan::new();
+    if !installed {
+        plan.push(Change::Install {
+            what: format!("{} v{}", tool, version),
+            dir,
+        });
+    }
+    if default != Some(version) {
+        plan.push(Change::Update {
+            file: path::user_catalog_file()?,
+            what: format!("make {} v{} the default", tool, version),
+        });
+    }
+    Ok(plan)
+}
+
+/// Describes the change `register_pins` makes, for plans.
+fn register_pins_change(toolchain_file: &Path) -> Fallible<Change> {
+    Ok(Change::Update {
+        file: path::references_file()?,
+        what: format!("register the pins of {}", toolchain_file.display()),
+    })
+}
+
+/// Warns that pinning Node can't keep `engines.node` in step with the pin.
+fn display_no_engines() {
+    display_warning("not syncing engines.node, since the project has no package.json");
+}
+
+/// Produces the version of a tool that `notion use` selected for the current shell, if any.
+fn version_override(tool: &str) -> Fallible<Option<Version>> {
+    let var = format!("NOTION_{}_VERSION", tool.to_ascii_uppercase());
+    match env::var(&var) {
+        Ok(ref s) if !s.is_empty() => Ok(Some(Version::parse(s).unknown()?)),
+        Ok(_) | Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => unimplemented!(),
+    }
+}
+
+/// Reports the exact version a version request resolved to, unless the request
+/// already named that version.
+fn display_resolved(tool: &str, matching: &VersionSpec, version: &Version) {
+    if matching.to_string() != format!("={}", version) {
+        println!("Resolved {}@{} to version {}", tool, matching, version);
+    }
+}
+
 /// Represents the user's state during an execution of a Notion tool. The session
 /// encapsulates a number of aspects of the environment in which the tool was
 /// invoked, including:
@@ -80,23 +336,88 @@ impl NotInPackageError {
 ///     - the Notion configuration settings
 ///     - the catalog of locally-installed Notion tools
 pub struct Session {
+    dir: PathBuf,
     config: LazyConfig,
     catalog: LazyCatalog,
     project: Option<Project>,
     event_log: EventLog,
+    telemetry: Recorder,
+    trace: Trace,
 }
 
 impl Session {
-    /// Constructs a new `Session`.
+    /// Constructs a new `Session` for the current directory, for the `notion` CLI and
+    /// the shims, warning if the shims are shadowed on the `PATH`.
     pub fn new() -> Fallible<Session> {
+        let session = Session::for_dir(&env::current_dir().unknown()?)?;
+        session.warn_if_shadowed();
+        Ok(session)
+    }
+
+    /// Constructs a `Session` for the specified directory, as `notion --cwd` and
+    /// programs that embed Notion (see the `api` module) do: the project, the pins, and
+    /// the cached versions are those of that directory. A relative directory is
+    /// relative to the current one. Unlike `new`, this never prints anything.
+    pub fn for_dir(dir: &Path) -> Fallible<Session> {
+        let dir = &session_dir(&env::current_dir().unknown()?, dir)?;
+        if notion_env::backtrace() {
+            notion_env::capture_backtraces();
+        }
+        profile::start();
+
+        let config = LazyConfig::new();
+        let project = {
+            let default = ProjectConfig::default();
+            let project_config = config.get()?.project.as_ref().unwrap_or(&default);
+            profile::time(Phase::ProjectDetection, || {
+                Project::for_dir_within(dir, project_config)
+            })?
+        };
+        Session::with_project(dir, config, project)
+    }
+
+    /// Warns once if another executable shadows one of the shims on the `PATH`, as
+    /// `new` does. The warning is only advice, so failing to check is harmless.
+    pub fn warn_if_shadowed(&self) {
+        let defer_to_corepack = self.config()
+            .map(|config| config.defer_to_corepack())
+            .unwrap_or(false);
+        let _ = shadow::warn_once(defer_to_corepack);
+    }
+
+    fn with_project(
+        dir: &Path,
+        mut config: LazyConfig,
+        project: Option<Project>,
+    ) -> Fallible<Session> {
+        if let Some(ref project) = project {
+            if let Some(hooks) = project.hooks()? {
+                config.get_mut()?.merge_project_hooks(hooks);
+            }
+        }
+
         Ok(Session {
-            config: LazyConfig::new(),
+            dir: dir.to_path_buf(),
+            config: config,
             catalog: LazyCatalog::new(),
-            project: Project::for_current_dir()?,
+            project: project,
             event_log: EventLog::new()?,
+            telemetry: Recorder::new(),
+            trace: Trace::new(),
         })
     }
 
+    /// Produces the decisions made so far while selecting tool versions for this session.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Produces the directory this session selects tool versions for: the current
+    /// directory, unless the session was constructed for another.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
     /// Produces a reference to the current Node project, if any.
     pub fn project(&self) -> Option<&Project> {
         self.project.as_ref()
@@ -125,36 +446,336 @@ impl Session {
         self.config.get()
     }
 
-    /// Produces the version of Node for the current session. If there is an
-    /// active pinned project, this will ensure that project's Node version is
-    /// installed before returning. If there is no active pinned project, this
-    /// produces the user version, which may be `None`.
+    /// Produces the version of Node for the current session, installing it first if
+    /// it isn't installed yet. See `active_node` for how the version is selected.
     pub fn current_node(&mut self) -> Fallible<Option<Version>> {
+        let mut active = match self.active_node()? {
+            Some(active) => active,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        // An install whose executable has gone missing is fetched again, rather than
+        // leaving the shim to fail.
+        let repaired = active.installed && self.catalog()?.node_is_broken(&active.version)?;
+        if repaired {
+            display_warning(&format!(
+                "node v{} is missing its executable, so it is fetched again",
+                active.version
+            ));
+            self.catalog.get_mut()?.forget_broken_node(&active.version)?;
+            active.installed = false;
+        }
+
+        if let VersionSource::NodeVersionFile(_) = active.source {
+            self.project.as_ref().unwrap().hint_node_version_file()?;
+        }
+        if active.installed || (active.source == VersionSource::User && !repaired) {
+            return Ok(Some(active.version));
+        }
+
+        // Fetching a project's pinned Node registers the project, so that `notion gc`
+        // keeps its versions and `notion list --projects` lists it. Projects are also
+        // registered when they are pinned, so selecting an installed version never writes.
+        if let VersionSource::Project(ref toolchain_file) = active.source {
+            let manifest = self.project.as_ref().unwrap().manifest();
+            let pins = Pins {
+                node: manifest.node(),
+                yarn: manifest.yarn(),
+            };
+            register_pins(toolchain_file, pins)?;
+        }
+
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        check_implicit_fetch(config, "Node", "node", &active.version)?;
+        let fetched = catalog.fetch_node(&VersionSpec::exact(&active.version), config)?;
+        Ok(Some(fetched.into_version()))
+    }
+
+    /// Selects the version of Node for the current session, without installing it. The
+    /// first of these that applies decides:
+    ///
+    /// 1. the override for the current shell set by `notion use`;
+    /// 2. the Node version pinned by the project's toolchain (in `package.json`, a
+    ///    `.notion.toml` pin file, or the workspace root);
+    /// 3. the version requested by the project's `.nvmrc` or `.node-version` file;
+    /// 4. the `engines` range of the project's manifest, unless the user's default
+    ///    satisfies it;
+    /// 5. the user's default, which may be `None`.
+    ///
+    /// The decisions leading to the selection are recorded in the session's trace.
+    pub fn active_node(&self) -> Fallible<Option<ActiveVersion>> {
+        let catalog = self.catalog()?;
+        let trace = &self.trace;
+
+        if let Some(version) = version_override("node")? {
+            trace.record(|| format!("`notion use` overrides node to {} in this shell", version));
+            return Ok(Some(ActiveVersion {
+                installed: catalog.node.contains(&version),
+                version,
+                source: VersionSource::Override,
+            }));
+        }
+
+        match self.project {
+            Some(ref project) => trace.record(|| {
+                format!("found a project at {}", project.root().display())
+            }),
+            None => trace.record(|| "not in a project".to_string()),
+        }
+
         if self.in_pinned_project() {
             let project = self.project.as_ref().unwrap();
-            let version = &project.manifest().node().unwrap();
-            let catalog = self.catalog.get_mut()?;
-            let spec = VersionSpec::exact(&version);
+            let version = project.manifest().node().unwrap();
+            let toolchain_file = project.toolchain_file();
+            trace.record(|| {
+                format!("the toolchain in {} pins node {}", toolchain_file.display(), version)
+            });
+            return Ok(Some(ActiveVersion {
+                installed: catalog.node.contains(&version),
+                version,
+                source: VersionSource::Project(toolchain_file),
+            }));
+        }
 
-            if catalog.node.contains(version) {
-                return Ok(Some(version.clone()));
+        let node_version_file = self.project
+            .as_ref()
+            .and_then(|project| project.node_version_file());
+        if let Some(file) = node_version_file {
+            trace.record(|| {
+                format!(
+                    "the project has no toolchain, but its {} requests node {}",
+                    file.name, file.version
+                )
+            });
+            let source = VersionSource::NodeVersionFile(file.name);
+            return self.select_node(&file.version, source).map(Some);
+        }
+
+        let user = self.user_node()?;
+        if let Some(spec) = self.engines_node()? {
+            trace.record(|| format!("the project's engines request node {}", spec));
+            // The `engines` range is a soft pin: the user version wins if it satisfies it.
+            let satisfied = match (&spec, &user) {
+                (&VersionSpec::Semver(ref range), &Some(ref version)) => range.matches(version),
+                _ => false,
+            };
+            if !satisfied {
+                return self.select_node(&spec, VersionSource::Engines).map(Some);
             }
+            trace.record(|| "the user version satisfies the engines range".to_string());
+        }
 
-            let config = self.config.get()?;
-            let fetched = catalog.fetch_node(&spec, config)?;
+        match user {
+            Some(ref version) => trace.record(|| format!("using the user default node {}", version)),
+            None => trace.record(|| "no default version of node is set".to_string()),
+        }
+        Ok(user.map(|version| ActiveVersion {
+            installed: catalog.node.contains(&version),
+            version,
+            source: VersionSource::User,
+        }))
+    }
 
-            return Ok(Some(fetched.into_version()));
+    /// Selects the Node version matching a project's version request, preferring an
+    /// installed version over resolving one from the public index.
+    fn select_node(&self, spec: &VersionSpec, source: VersionSource) -> Fallible<ActiveVersion> {
+        let catalog = self.catalog()?;
+        if let Some(version) = catalog.node.resolve_local(spec) {
+            self.trace
+                .record(|| format!("resolved {} locally to {}", spec, version));
+            return Ok(ActiveVersion {
+                version,
+                source,
+                installed: true,
+            });
         }
+        self.trace
+            .record(|| format!("no installed version of node matches {}", spec));
+        let version = catalog.resolve_node(spec, self.config()?, &self.trace)?;
+        Ok(ActiveVersion {
+            installed: catalog.node.contains(&version),
+            version,
+            source,
+        })
+    }
+
+    /// Uninstalls the specified version of Node. Unless `force` is set, this refuses to
+    /// uninstall the user's default version, the version pinned by the current project,
+    /// or a version that a globally installed package runs with.
+    pub fn uninstall_node(&mut self, version: &Version, force: bool) -> Fallible<()> {
+        self.check_uninstall_node(version, force)?;
 
-        self.user_node()
+        let hooks = self.config.get()?.lifecycle.as_ref();
+        let catalog = self.catalog.get_mut()?;
+        lifecycle::around(hooks, Action::Uninstall, "node", &version.to_string(), || {
+            catalog.uninstall_node(version)
+        })
     }
 
-    pub fn user_node(&self) -> Fallible<Option<Version>> {
-        match env::var("NOTION_NODE_VERSION") {
-            Ok(s) => Ok(Some(Version::parse(&s[..]).unknown()?)),
-            Err(VarError::NotPresent) => Ok(self.catalog()?.node.default.clone()),
-            Err(VarError::NotUnicode(_)) => unimplemented!(),
+    /// Plans the changes of `uninstall_node`, without making them.
+    pub fn plan_uninstall_node(&self, version: &Version, force: bool) -> Fallible<Plan> {
+        self.check_uninstall_node(version, force)?;
+        self.catalog()?.plan_uninstall_node(version)
+    }
+
+    /// Checks that the specified version of Node is installed and, unless `force` is
+    /// set, that nothing uses it.
+    fn check_uninstall_node(&self, version: &Version, force: bool) -> Fallible<()> {
+        let in_use = {
+            let catalog = self.catalog()?;
+            if !catalog.node.contains(version) {
+                throw!(NotInstalledError {
+                    tool: "Node".to_string(),
+                    version: version.to_string(),
+                });
+            }
+
+            if catalog.node.default.as_ref() == Some(version) {
+                Some("the user default".to_string())
+            } else {
+                self.project
+                    .as_ref()
+                    .and_then(|project| {
+                        if project.manifest().node().as_ref() == Some(version) {
+                            let file = project.toolchain_file();
+                            Some(format!("pinned by the current project in {}", file.display()))
+                        } else {
+                            None
+                        }
+                    })
+                    .or_else(|| {
+                        catalog
+                            .packages
+                            .iter()
+                            .filter(|&(_, package)| &package.node == version)
+                            .map(|(name, _)| format!("used by the package {}", name))
+                            .next()
+                    })
+            }
+        };
+
+        if let Some(usage) = in_use {
+            if !force {
+                throw!(VersionInUseError {
+                    tool: "Node".to_string(),
+                    version: version.to_string(),
+                    usage: usage,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Produces the projects registered by pinning, installing, or running their
+    /// toolchains, forgetting those whose toolchain files no longer exist.
+    pub fn registered_projects(&self) -> Fallible<References> {
+        let mut references = References::current()?;
+        if references.prune() {
+            references.save()?;
         }
+        Ok(references)
+    }
+
+    /// Finds the installed versions of Node and Yarn that nothing references, and
+    /// uninstalls them unless `dry_run` is set. A version is referenced if it is the
+    /// user version, if the current project selects it, if a globally installed package
+    /// runs with it, or, when `include_projects` is set, if any project registered by
+    /// pinning, installing, or running its toolchain pins it.
+    /// Produces the unreferenced versions, by tool.
+    pub fn collect_garbage(
+        &mut self,
+        dry_run: bool,
+        include_projects: bool,
+    ) -> Fallible<Vec<(&'static str, Version)>> {
+        let mut references = References::current()?;
+        if references.prune() && !dry_run {
+            references.save()?;
+        }
+
+        let (mut keep_node, mut keep_yarn) = if include_projects {
+            (references.node_versions(), references.yarn_versions())
+        } else {
+            (BTreeSet::new(), BTreeSet::new())
+        };
+        if let Some(active) = self.active_node()? {
+            keep_node.insert(active.version);
+        }
+        if let Some(active) = self.active_yarn()? {
+            keep_yarn.insert(active.version);
+        }
+        let unused: Vec<(&'static str, Version)> = {
+            let catalog = self.catalog()?;
+            keep_node.extend(catalog.node.default.clone());
+            keep_yarn.extend(catalog.yarn.default.clone());
+            // The system install is read-only.
+            keep_node.extend(catalog.node.system.iter().cloned());
+            keep_yarn.extend(catalog.yarn.system.iter().cloned());
+            // Globally installed packages always run with the Node they were installed with.
+            keep_node.extend(catalog.packages.values().map(|package| package.node.clone()));
+
+            let node = catalog
+                .node
+                .versions
+                .difference(&keep_node)
+                .map(|version| ("node", version.clone()));
+            let yarn = catalog
+                .yarn
+                .versions
+                .difference(&keep_yarn)
+                .map(|version| ("yarn", version.clone()));
+            node.chain(yarn).collect()
+        };
+
+        if !dry_run {
+            let catalog = self.catalog.get_mut()?;
+            for &(tool, ref version) in unused.iter() {
+                match tool {
+                    "node" => catalog.uninstall_node(version)?,
+                    _ => catalog.uninstall_yarn(version)?,
+                }
+            }
+        }
+        Ok(unused)
+    }
+
+    /// Plans the changes of uninstalling one of the versions `collect_garbage` produces,
+    /// without making them.
+    pub fn plan_collect(&self, tool: &str, version: &Version) -> Fallible<Plan> {
+        let catalog = self.catalog()?;
+        match tool {
+            "node" => catalog.plan_uninstall_node(version),
+            _ => catalog.plan_uninstall_yarn(version),
+        }
+    }
+
+    /// Produces the range of Node versions in the `engines` section of the current
+    /// project's manifest, unless the project has a toolchain or the `node.use-engines`
+    /// setting is disabled.
+    fn engines_node(&self) -> Fallible<Option<VersionSpec>> {
+        let project = match self.project {
+            Some(ref project) if !project.is_pinned() => project,
+            _ => {
+                return Ok(None);
+            }
+        };
+        let use_engines = self.config()?
+            .node
+            .as_ref()
+            .map(|node| node.use_engines)
+            .unwrap_or(true);
+        Ok(if use_engines {
+            project.manifest().engines_node()
+        } else {
+            None
+        })
+    }
+
+    /// Produces the user's default version of Node, if any.
+    pub fn user_node(&self) -> Fallible<Option<Version>> {
+        Ok(self.catalog()?.node.default.clone())
     }
 
     /// Fetches a version of Node matching the specified semantic verisoning
@@ -165,57 +786,363 @@ impl Session {
         catalog.fetch_node(matching, config)
     }
 
-    /// Sets the user toolchain's Node version to one matching the specified semantic versioning
-    /// requirements.
-    pub fn set_user_node(&mut self, matching: &VersionSpec) -> Fallible<()> {
+    /// Fetches a version of Node matching the specified semantic versioning requirements
+    /// from an archive provided locally, rather than downloading it.
+    pub fn fetch_node_file(&mut self, matching: &VersionSpec, file: &Path) -> Fallible<Fetched> {
         let catalog = self.catalog.get_mut()?;
         let config = self.config.get()?;
-        catalog.set_user_node(matching, config)
+        catalog.fetch_node_file(matching, file, config)
+    }
+
+    /// Fetches a version of each of the specified tools, concurrently.
+    pub fn fetch_many(&mut self, specs: &[ToolSpec]) -> Fallible<Vec<Fetched>> {
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        catalog.fetch_many(specs, config)
+    }
+
+    /// Returns the directory where the specified version of Node is installed.
+    pub fn node_install_path(&self, version: &Version) -> Fallible<PathBuf> {
+        self.catalog()?.node_install_path(version)
+    }
+
+    /// Returns the directory containing the executables of the specified version of Node,
+    /// suitable for adding to the `PATH` of a subprocess.
+    pub fn node_bin_path(&self, version: &Version) -> Fallible<PathBuf> {
+        self.catalog()?.node_bin_path(version)
+    }
+
+    /// Lists the Node versions available for download, newest first.
+    pub fn remote_node_versions(&self) -> Fallible<Vec<(Version, VersionData)>> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.remote_node_versions(config)
+    }
+
+    /// Lists the newest release of the current line of Node and of each LTS line, newest
+    /// first.
+    pub fn recent_node_releases(&self) -> Fallible<Vec<(Version, VersionData)>> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.recent_node_releases(config)
+    }
+
+    /// Describes the newest release of Node matching the specified requirements.
+    pub fn node_release_info(&self, matching: &VersionSpec) -> Fallible<ReleaseInfo> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.node_release_info(matching, config)
+    }
+
+    /// Describes the newest release of Yarn matching the specified requirements.
+    pub fn yarn_release_info(&self, matching: &VersionSpec) -> Fallible<ReleaseInfo> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.yarn_release_info(matching, config)
+    }
+
+    /// Downloads the index of the Node versions available for download afresh.
+    pub fn refresh_node_index(&self) -> Fallible<()> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.refresh_node_index(config)
+    }
+
+    /// Lists the Yarn versions available for download, newest first.
+    pub fn remote_yarn_versions(&self) -> Fallible<Vec<Version>> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.remote_yarn_versions(config)
+    }
+
+    /// Lists the Yarn releases available for download, newest first.
+    pub fn remote_yarn_releases(&self) -> Fallible<Vec<YarnRelease>> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.remote_yarn_releases(config)
+    }
+
+    /// Downloads the index of the Yarn releases available for download afresh.
+    pub fn refresh_yarn_index(&self) -> Fallible<()> {
+        let catalog = self.catalog.get()?;
+        let config = self.config.get()?;
+        catalog.refresh_yarn_index(config)
+    }
+
+    /// Sets the user's default Node version to one matching the specified semantic versioning
+    /// requirements, fetching it if necessary. Unlike pinning, this never touches the
+    /// current project.
+    pub fn set_default_node(&mut self, matching: &VersionSpec) -> Fallible<()> {
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        catalog.set_default_node(matching, config)
+    }
+
+    /// Sets the user's default Node version like `set_default_node`, building the
+    /// matching release from source if Node publishes no prebuilt binary of it for this
+    /// platform.
+    pub fn set_default_node_from_source(&mut self, matching: &VersionSpec) -> Fallible<()> {
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        catalog.set_default_node_from_source(matching, config)
+    }
+
+    /// Sets the user's default Node version to a custom build fetched from the archive
+    /// at a URL, recording where it came from under the specified label. Produces the
+    /// version of the build.
+    pub fn set_default_node_url(
+        &mut self,
+        url: &str,
+        label: &str,
+        checksum: Option<&str>,
+    ) -> Fallible<Version> {
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        catalog.set_default_node_url(url, label, checksum, config)
+    }
+
+    /// Plans the changes of `set_default_node`, without making them. Resolving the
+    /// version may still download the index of available versions.
+    pub fn plan_default_node(&self, matching: &VersionSpec) -> Fallible<Plan> {
+        let version = self.get_matching_node(matching)?;
+        let catalog = self.catalog()?;
+        plan_default(
+            "node",
+            &version,
+            catalog.node.contains(&version),
+            catalog.node.default.as_ref(),
+            path::node_version_dir(&version.to_string())?,
+        )
     }
 
     /// Returns the version of Node matching the specified semantic versioning requirements.
     pub fn get_matching_node(&self, matching: &VersionSpec) -> Fallible<Version> {
         let catalog = self.catalog.get()?;
         let config = self.config.get()?;
-        catalog.resolve_node(matching, config)
+        catalog.resolve_node(matching, config, &Trace::disabled())
     }
 
     /// Updates toolchain in package.json with the Node version matching the specified semantic
-    /// versioning requirements.
-    pub fn pin_node_version(&self, matching: &VersionSpec) -> Fallible<()> {
+    /// versioning requirements. Outside of any project, this starts a `.notion.toml` pin
+    /// file in the current directory instead.
+    pub fn pin_node_version(
+        &self,
+        matching: &VersionSpec,
+        sync_engines: Option<bool>,
+    ) -> Fallible<()> {
+        let node_version = self.get_matching_node(matching)?;
+        display_resolved("node", matching, &node_version);
+        let sync_engines = self.sync_engines(sync_engines)?;
+        let hooks = self.config.get()?.lifecycle.as_ref();
+        lifecycle::around(hooks, Action::Pin, "node", &node_version.to_string(), || {
+            self.write_node_pin(node_version.clone())?;
+            if sync_engines {
+                self.write_engines_node(&node_version)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Plans the changes of `pin_node_version`, without making them.
+    pub fn plan_node_pin(
+        &self,
+        matching: &VersionSpec,
+        sync_engines: Option<bool>,
+    ) -> Fallible<Plan> {
+        let node_version = self.get_matching_node(matching)?;
+        display_resolved("node", matching, &node_version);
+        let sync_engines = self.sync_engines(sync_engines)?;
+
+        let mut plan = Plan::new();
+        let toolchain_file = match self.project() {
+            Some(project) => {
+                project.plan_node_pin(&node_version, &mut plan)?;
+                if sync_engines && !project.plan_engines_pin(&node_version, &mut plan)? {
+                    display_no_engines();
+                }
+                project.toolchain_file()
+            }
+            None => {
+                Project::plan_new_pin_file(&self.dir, &node_version, &mut plan)?;
+                if sync_engines {
+                    display_no_engines();
+                }
+                self.dir.join(PIN_FILE)
+            }
+        };
+        plan.push(register_pins_change(&toolchain_file)?);
+        Ok(plan)
+    }
+
+    /// Decides whether pinning Node also writes `engines.node`: as `sync_engines` says,
+    /// if it says, and otherwise as the `node.sync-engines` setting says.
+    fn sync_engines(&self, sync_engines: Option<bool>) -> Fallible<bool> {
+        let config = self.config.get()?;
+        Ok(sync_engines.unwrap_or_else(|| {
+            config
+                .node
+                .as_ref()
+                .map(|node| node.sync_engines)
+                .unwrap_or(false)
+        }))
+    }
+
+    /// Keeps the `engines.node` range of the project's manifest in step with a newly
+    /// pinned version of Node. A project without a `package.json` file has no
+    /// `engines` section to keep, which is only worth a warning.
+    fn write_engines_node(&self, node_version: &Version) -> Fallible<()> {
+        let synced = match self.project() {
+            Some(project) => project.pin_node_in_engines(node_version)?,
+            None => false,
+        };
+        if !synced {
+            display_no_engines();
+        }
+        Ok(())
+    }
+
+    /// Moves the Node version pinned in the current project's toolchain to the newest
+    /// version matching the specified semantic versioning requirements, fetching it first
+    /// so that the project is ready to use it. Returns the previously pinned version and
+    /// the newly pinned one, which are the same if the pin is already up to date.
+    pub fn update_node_pin(&mut self, matching: &VersionSpec) -> Fallible<(Version, Version)> {
+        let pinned = match self.project() {
+            Some(project) => project.manifest().node(),
+            None => None,
+        };
+        let pinned = match pinned {
+            Some(pinned) => pinned,
+            None => throw!(NotInPackageError::new()),
+        };
+
+        let node_version = self.get_matching_node(matching)?;
+        if node_version == pinned {
+            return Ok((pinned, node_version));
+        }
+        display_resolved("node", matching, &node_version);
+        self.fetch_node(&VersionSpec::exact(&node_version))?;
+
+        let hooks = self.config.get()?.lifecycle.as_ref();
+        lifecycle::around(hooks, Action::Pin, "node", &node_version.to_string(), || {
+            self.write_node_pin(node_version.clone())
+        })?;
+        Ok((pinned, node_version))
+    }
+
+    /// Pins the specified Node version in the current project's toolchain, or in a new
+    /// pin file in the current directory outside of any project.
+    fn write_node_pin(&self, node_version: Version) -> Fallible<()> {
         if let Some(ref project) = self.project() {
-            let node_version = self.get_matching_node(matching)?;
+            let pins = Pins {
+                node: Some(node_version.clone()),
+                yarn: project.manifest().yarn(),
+            };
             project.pin_node_in_toolchain(node_version)?;
+            register_pins(&project.toolchain_file(), pins)?;
         } else {
-            throw!(NotInPackageError::new());
+            let pins = Pins {
+                node: Some(node_version.clone()),
+                yarn: None,
+            };
+            Project::pin_node_in_new_pin_file(&self.dir, node_version)?;
+            register_pins(&self.dir.join(PIN_FILE), pins)?;
         }
         Ok(())
     }
 
-    /// Produces the version of Yarn for the current session. If there is an
-    /// active pinned project, this will ensure that project's Yarn version is
-    /// installed before returning. If there is no active pinned project, this
-    /// produces the user version, which may be `None`.
+    /// Builds the environment for running a tool under the toolchain selected for this
+    /// session, fetching the selected versions first if need be. The shims, `notion
+    /// exec`, and `notion run` all run their tools in the environment this builds.
+    pub fn env_for_tool(&mut self, tool: ToolKind) -> Fallible<ToolEnv> {
+        let node = self.current_node()?.map(|version| version.to_string());
+        let yarn = match tool {
+            ToolKind::Yarn | ToolKind::Toolchain => {
+                self.current_yarn()?.map(|version| version.to_string())
+            }
+            _ => None,
+        };
+        let npm = match tool {
+            ToolKind::Npm | ToolKind::Toolchain => {
+                self.current_npm()?.map(|version| version.to_string())
+            }
+            _ => None,
+        };
+        notion_env::tool_env(
+            tool,
+            node.as_ref().map(|version| &version[..]),
+            yarn.as_ref().map(|version| &version[..]),
+            npm.as_ref().map(|version| &version[..]),
+        )
+    }
+
+    /// Produces the release of Yarn the current project vendors, if it uses Yarn 2 or
+    /// later and sets `yarnPath`. A vendored release runs in place of any other Yarn.
+    pub fn vendored_yarn(&self) -> Fallible<Option<PathBuf>> {
+        match self.project {
+            Some(ref project) => project.vendored_yarn(),
+            None => Ok(None),
+        }
+    }
+
+    /// Produces the version of Yarn for the current session, installing it first if
+    /// it isn't installed yet. See `active_yarn` for how the version is selected.
     pub fn current_yarn(&mut self) -> Fallible<Option<Version>> {
-        if self.in_pinned_project() {
-            let project = self.project.as_ref().unwrap();
-            // pinning yarn is optional
-            if let Some(version) = &project.manifest().yarn().clone() {
-                let catalog = self.catalog.get_mut()?;
-                let spec = VersionSpec::exact(&version);
+        let active = match self.active_yarn()? {
+            Some(active) => active,
+            None => {
+                return Ok(None);
+            }
+        };
+        if active.installed || active.source == VersionSource::User {
+            return Ok(Some(active.version));
+        }
 
-                if catalog.yarn.contains(&version) {
-                    return Ok(Some(version.clone()));
-                }
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        check_implicit_fetch(config, "Yarn", "yarn", &active.version)?;
+        let fetched = catalog.fetch_yarn(&VersionSpec::exact(&active.version), config)?;
+        Ok(Some(fetched.into_version()))
+    }
 
-                let config = self.config.get()?;
-                let fetched = catalog.fetch_yarn(&spec, config)?;
+    /// Selects the version of Yarn for the current session, without installing it.
+    /// An override for the current shell set by `notion use` comes first. Then, if the
+    /// active project pins Yarn, or declares it as its `packageManager`, this
+    /// is that project's Yarn version. Otherwise, this is the user version, which may
+    /// be `None`.
+    pub fn active_yarn(&self) -> Fallible<Option<ActiveVersion>> {
+        let catalog = self.catalog()?;
 
-                return Ok(Some(fetched.into_version()));
+        if let Some(version) = version_override("yarn")? {
+            return Ok(Some(ActiveVersion {
+                installed: catalog.yarn.contains(&version),
+                version,
+                source: VersionSource::Override,
+            }));
+        }
+
+        if let Some(ref project) = self.project {
+            let manifest = project.manifest();
+            // pinning yarn is optional; without a pin, a `packageManager` declaration selects it
+            let project_yarn = match manifest.yarn() {
+                Some(version) => Some((version, VersionSource::Project(project.toolchain_file()))),
+                None => manifest
+                    .package_manager_version("yarn")
+                    .map(|version| (version, VersionSource::PackageManager)),
+            };
+            if let Some((version, source)) = project_yarn {
+                return Ok(Some(ActiveVersion {
+                    installed: catalog.yarn.contains(&version),
+                    version,
+                    source,
+                }));
             }
         }
 
-        Ok(self.catalog()?.yarn.default.clone())
+        Ok(catalog.yarn.default.clone().map(|version| ActiveVersion {
+            installed: catalog.yarn.contains(&version),
+            version,
+            source: VersionSource::User,
+        }))
     }
 
     /// Fetches a version of Node matching the specified semantic verisoning
@@ -226,12 +1153,32 @@ impl Session {
         catalog.fetch_yarn(matching, config)
     }
 
-    /// Sets the Yarn version in the user toolchain to one matching the specified semantic versioning
-    /// requirements.
-    pub fn set_user_yarn(&mut self, matching: &VersionSpec) -> Fallible<()> {
+    /// Sets the user's default Yarn version to one matching the specified semantic versioning
+    /// requirements, fetching it if necessary. Unlike pinning, this never touches the
+    /// current project.
+    pub fn set_default_yarn(&mut self, matching: &VersionSpec) -> Fallible<()> {
         let catalog = self.catalog.get_mut()?;
         let config = self.config.get()?;
-        catalog.set_user_yarn(matching, config)
+        catalog.set_default_yarn(matching, config)
+    }
+
+    /// Plans the changes of `set_default_yarn`, without making them. Resolving the
+    /// version may still download the index of available versions.
+    pub fn plan_default_yarn(&self, matching: &VersionSpec) -> Fallible<Plan> {
+        let version = self.get_matching_yarn(matching)?;
+        let catalog = self.catalog()?;
+        plan_default(
+            "yarn",
+            &version,
+            catalog.yarn.contains(&version),
+            catalog.yarn.default.as_ref(),
+            path::yarn_version_dir(&version.to_string())?,
+        )
+    }
+
+    /// Produces the user's default version of Yarn, if any.
+    pub fn user_yarn(&self) -> Fallible<Option<Version>> {
+        Ok(self.catalog()?.yarn.default.clone())
     }
 
     /// Returns the version of Yarn matching the specified semantic versioning requirements
@@ -246,14 +1193,323 @@ impl Session {
     pub fn pin_yarn_version(&self, matching: &VersionSpec) -> Fallible<()> {
         if let Some(ref project) = self.project() {
             let yarn_version = self.get_matching_yarn(matching)?;
-            project.pin_yarn_in_toolchain(yarn_version)?;
+            display_resolved("yarn", matching, &yarn_version);
+            let hooks = self.config.get()?.lifecycle.as_ref();
+            lifecycle::around(hooks, Action::Pin, "yarn", &yarn_version.to_string(), || {
+                let pins = Pins {
+                    node: project.manifest().node(),
+                    yarn: Some(yarn_version.clone()),
+                };
+                project.pin_yarn_in_toolchain(yarn_version.clone())?;
+                register_pins(&project.toolchain_file(), pins)
+            })?;
+        } else {
+            throw!(NotInPackageError::new());
+        }
+        Ok(())
+    }
+
+    /// Plans the changes of `pin_yarn_version`, without making them.
+    pub fn plan_yarn_pin(&self, matching: &VersionSpec) -> Fallible<Plan> {
+        let project = match self.project() {
+            Some(project) => project,
+            None => throw!(NotInPackageError::new()),
+        };
+        let yarn_version = self.get_matching_yarn(matching)?;
+        display_resolved("yarn", matching, &yarn_version);
+        let mut plan = Plan::new();
+        project.plan_yarn_pin(&yarn_version, &mut plan)?;
+        plan.push(register_pins_change(&project.toolchain_file())?);
+        Ok(plan)
+    }
+
+    /// Removes the project's toolchain from package.json, unpinning Node along with
+    /// every other tool.
+    pub fn unpin_node_version(&self) -> Fallible<()> {
+        if let Some(ref project) = self.project() {
+            project.unpin_toolchain()?;
+            register_pins(&project.toolchain_file(), Pins::default())?;
         } else {
             throw!(NotInPackageError::new());
         }
         Ok(())
     }
 
+    /// Removes the project's Yarn pin from package.json.
+    pub fn unpin_yarn_version(&self) -> Fallible<()> {
+        if let Some(ref project) = self.project() {
+            project.unpin_yarn_from_toolchain()?;
+            let pins = Pins {
+                node: project.manifest().node(),
+                yarn: None,
+            };
+            register_pins(&project.toolchain_file(), pins)?;
+        } else {
+            throw!(NotInPackageError::new());
+        }
+        Ok(())
+    }
+
+    /// Produces the version of npm pinned by the current project, if any, ensuring that it
+    /// is installed before returning. When this is `None`, the npm bundled with the current
+    /// version of Node should be used.
+    pub fn current_npm(&mut self) -> Fallible<Option<Version>> {
+        if self.in_pinned_project() {
+            let project = self.project.as_ref().unwrap();
+            // pinning npm is optional
+            if let Some(version) = &project.manifest().npm().clone() {
+                let catalog = self.catalog.get_mut()?;
+                let spec = VersionSpec::exact(&version);
+
+                if catalog.npm.contains(&version) {
+                    return Ok(Some(version.clone()));
+                }
+
+                let config = self.config.get()?;
+                check_implicit_fetch(config, "npm", "npm", version)?;
+                let fetched = catalog.fetch_npm(&spec, config)?;
+
+                return Ok(Some(fetched.into_version()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fetches a version of npm matching the specified semantic verisoning
+    /// requirements.
+    pub fn fetch_npm(&mut self, matching: &VersionSpec) -> Fallible<Fetched> {
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        catalog.fetch_npm(matching, config)
+    }
+
+    /// Produces the version of pnpm pinned by the current project (or declared as its
+    /// `packageManager`), if any, ensuring that it is installed before returning.
+    pub fn current_pnpm(&mut self) -> Fallible<Option<Version>> {
+        // without a pin, a `packageManager` declaration selects pnpm
+        let project_pnpm = self.project.as_ref().and_then(|project| {
+            let manifest = project.manifest();
+            manifest
+                .pnpm()
+                .or_else(|| manifest.package_manager_version("pnpm"))
+        });
+        if let Some(version) = &project_pnpm {
+            let catalog = self.catalog.get_mut()?;
+            let spec = VersionSpec::exact(&version);
+
+            if catalog.pnpm.contains(&version) {
+                return Ok(Some(version.clone()));
+            }
+
+            let config = self.config.get()?;
+            check_implicit_fetch(config, "pnpm", "pnpm", version)?;
+            let fetched = catalog.fetch_pnpm(&spec, config)?;
+
+            return Ok(Some(fetched.into_version()));
+        }
+
+        Ok(None)
+    }
+
+    /// Fetches a version of pnpm matching the specified semantic verisoning
+    /// requirements.
+    pub fn fetch_pnpm(&mut self, matching: &VersionSpec) -> Fallible<Fetched> {
+        let catalog = self.catalog.get_mut()?;
+        let config = self.config.get()?;
+        catalog.fetch_pnpm(matching, config)
+    }
+
+    /// Installs a version of the named package matching the specified semantic versioning
+    /// requirements as a global tool, with the Node version selected for the current
+    /// session, and creates shims for the executables it provides. The package always
+    /// runs with that Node version, whatever version is selected when it runs.
+    pub fn install_package(&mut self, name: &str, matching: &VersionSpec) -> Fallible<Package> {
+        let node = if let Some(version) = self.current_node()? {
+            version
+        } else {
+            throw!(NoPackageNodeError {
+                name: name.to_string(),
+            });
+        };
+        self.install_package_with(name, matching, &node)
+    }
+
+    /// Plans the changes of `install_package`, without making them. Which version of
+    /// the package gets installed is only known once npm installs it.
+    pub fn plan_package_install(&self, name: &str, matching: &VersionSpec) -> Fallible<Plan> {
+        let node = match self.active_node()? {
+            Some(node) => node,
+            None => throw!(NoPackageNodeError {
+                name: name.to_string(),
+            }),
+        };
+        let mut plan = Plan::new();
+        if !node.installed {
+            plan.push(Change::Install {
+                what: format!("node v{}", node.version),
+                dir: path::node_version_dir(&node.version.to_string())?,
+            });
+        }
+        plan.push(Change::Install {
+            what: format!(
+                "{} from the npm registry, to run with node v{}",
+                package::install_spec(name, matching)?,
+                node.version
+            ),
+            dir: path::package_dir(name)?,
+        });
+        plan.push(Change::Install {
+            what: format!("shims for the executables of {}", name),
+            dir: path::shim_dir()?,
+        });
+        plan.push(Change::Update {
+            file: path::user_catalog_file()?,
+            what: format!("record the package {}", name),
+        });
+        Ok(plan)
+    }
+
+    /// Installs a package as a global tool that runs with the specified version of Node.
+    fn install_package_with(
+        &mut self,
+        name: &str,
+        matching: &VersionSpec,
+        node: &Version,
+    ) -> Fallible<Package> {
+        let package = package::install(name, matching, node)?;
+        for bin in package.bins.iter() {
+            // Another package, or an earlier install of this one, may already have
+            // created the shim; it resolves to whichever package provides the binary.
+            if path::shim_file(bin)?.symlink_metadata().is_err() {
+                shim::create(bin)?;
+            }
+        }
+
+        let catalog = self.catalog.get_mut()?;
+        catalog.packages.insert(name.to_string(), package.clone());
+        catalog.save()?;
+        Ok(package)
+    }
+
+    /// Restores a toolchain state exported from another machine: fetches the tool versions
+    /// missing here, sets the user's defaults to those of the state, and installs the
+    /// global packages missing here with the Node versions they ran with. Returns the
+    /// names of the packages installed.
+    pub fn restore_state(&mut self, state: &State) -> Fallible<Vec<String>> {
+        let missing = state.missing(self.catalog()?)?;
+        if !missing.is_empty() {
+            self.fetch_many(&missing)?;
+        }
+        state.apply_defaults(self.catalog.get_mut()?)?;
+
+        let mut installed = vec![];
+        for (name, package) in state.packages.iter() {
+            if self.catalog()?.packages.contains_key(name) {
+                continue;
+            }
+            let version = VersionSpec::parse_version(&package.version)?;
+            let node = VersionSpec::parse_version(&package.node)?;
+            self.install_package_with(name, &VersionSpec::exact(&version), &node)?;
+            installed.push(name.clone());
+        }
+        Ok(installed)
+    }
+
+    /// Records the tool versions selected for the current directory in the platform
+    /// cache, so that later shims run here can skip loading the session. Only
+    /// selections that come entirely from the project's toolchain, with every version
+    /// installed, are cached, and nothing is cached while an events plugin is
+    /// configured, since shims that skip the session don't publish events.
+    pub fn save_platform(&self) -> Fallible<()> {
+        let project = match self.project {
+            Some(ref project) => project,
+            None => return Ok(()),
+        };
+        let publishes_events = self.config()?
+            .events
+            .as_ref()
+            .map_or(false, |events| events.publish.is_some());
+        if publishes_events {
+            return Ok(());
+        }
+
+        let node = match self.active_node()? {
+            Some(ActiveVersion {
+                version,
+                source: VersionSource::Project(_),
+                installed: true,
+            }) => version.to_string(),
+            _ => return Ok(()),
+        };
+        let yarn = match self.active_yarn()? {
+            Some(ActiveVersion {
+                version,
+                source: VersionSource::Project(_),
+                installed: true,
+            })
+            | Some(ActiveVersion {
+                version,
+                source: VersionSource::PackageManager,
+                installed: true,
+            }) => Some(version.to_string()),
+            // the user's default Yarn is left to the catalog
+            Some(ActiveVersion {
+                source: VersionSource::User,
+                ..
+            })
+            | None => None,
+            _ => return Ok(()),
+        };
+        let npm = match project.manifest().npm() {
+            Some(ref version) if self.catalog()?.npm.contains(version) => Some(version.to_string()),
+            Some(_) => return Ok(()),
+            None => None,
+        };
+
+        // the package manager is left to corepack, so only Node can be cached
+        let defer_to_corepack = self.config.get()?.defer_to_corepack();
+        let yarn = if defer_to_corepack { None } else { yarn };
+
+        let yarn_path = match project.vendored_yarn()? {
+            Some(_) if defer_to_corepack => None,
+            Some(release) => match release.to_str() {
+                Some(release) => Some(release.to_string()),
+                // paths that aren't valid Unicode can't be recorded
+                None => return Ok(()),
+            },
+            None => None,
+        };
+
+        platform::save(
+            &self.dir,
+            project.root(),
+            &project.toolchain_file(),
+            &project.yarnrc_files(),
+            &Platform {
+                node,
+                yarn,
+                npm,
+                yarn_path,
+            },
+        )
+    }
+
+    /// Fetches a version of the named package for a one-off run with `notion x`,
+    /// with the current version of Node, which runs it.
+    pub fn fetch_one_off(&mut self, name: &str, matching: &VersionSpec) -> Fallible<OneOff> {
+        let node = if let Some(version) = self.current_node()? {
+            version
+        } else {
+            throw!(NoPackageNodeError {
+                name: name.to_string(),
+            });
+        };
+        package::fetch_one_off(name, matching, &node, self.config()?)
+    }
+
     pub fn add_event_start(&mut self, activity_kind: ActivityKind) {
+        self.telemetry.start(activity_kind);
         self.event_log.add_event_start(activity_kind)
     }
     pub fn add_event_end(&mut self, activity_kind: ActivityKind, exit_code: ExitCode) {
@@ -263,10 +1519,11 @@ impl Session {
         self.event_log.add_event_tool_end(activity_kind, exit_code)
     }
     pub fn add_event_error(&mut self, activity_kind: ActivityKind, error: &NotionError) {
+        self.telemetry.error(error);
         self.event_log.add_event_error(activity_kind, error)
     }
 
-    fn publish_to_event_log(mut self) {
+    fn publish_to_event_log(&mut self) {
         match publish_plugin(&self.config) {
             Ok(plugin) => {
                 self.event_log.publish(plugin);
@@ -277,15 +1534,46 @@ impl Session {
         }
     }
 
-    pub fn exit(self, code: ExitCode) -> ! {
+    /// Queues the telemetry record of the session, if telemetry is on.
+    fn record_telemetry(&self, exit_code: i32) {
+        self.telemetry.finish(exit_code, self.config.get().ok());
+    }
+
+    pub fn exit(mut self, code: ExitCode) -> ! {
         self.publish_to_event_log();
+        self.record_telemetry(code as i32);
+        profile::report();
         code.exit();
     }
 
-    pub fn exit_tool(self, code: i32) -> ! {
+    /// Exits with the exit code of a tool that Notion ran. This only borrows the
+    /// session, so that commands that run a tool can forward its exit code.
+    pub fn exit_tool(&mut self, code: i32) -> ! {
         self.publish_to_event_log();
+        self.record_telemetry(code);
+        profile::report();
         exit(code);
     }
+
+    /// Exits the way a shimmed tool exited, including by the signal that killed it.
+    pub(crate) fn exit_like_tool(&mut self, status: &ExitStatus) -> ! {
+        self.publish_to_event_log();
+        self.record_telemetry(status.code().unwrap_or(ExitCode::UnknownError as i32));
+        profile::report();
+        signal::exit_like(status);
+    }
+}
+
+/// Resolves the directory a `Session` is for against `base` (the current directory),
+/// failing if it isn't a directory.
+fn session_dir(base: &Path, dir: &Path) -> Fallible<PathBuf> {
+    let dir = base.join(dir);
+    if !dir.is_dir() {
+        throw!(NoSuchDirError {
+            dir: dir.display().to_string(),
+        });
+    }
+    Ok(dir)
 }
 
 fn publish_plugin(config: &LazyConfig) -> Fallible<Option<&Publish>> {
@@ -299,9 +1587,9 @@ fn publish_plugin(config: &LazyConfig) -> Fallible<Option<&Publish>> {
 #[cfg(test)]
 pub mod tests {
 
-    use session::Session;
+    use session::{session_dir, Session, VersionSource};
     use std::env;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     fn fixture_path(fixture_dir: &str) -> PathBuf {
         let mut cargo_manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -322,4 +1610,36 @@ pub mod tests {
         let unpinned_session = Session::new().expect("Couldn't create new Session");
         assert_eq!(unpinned_session.in_pinned_project(), false);
     }
+
+    #[test]
+    fn test_session_dir() {
+        let base = fixture_path("");
+        assert_eq!(
+            session_dir(&base, Path::new("basic")).unwrap(),
+            fixture_path("basic")
+        );
+        // an absolute directory is used as is
+        assert_eq!(
+            session_dir(Path::new("/no/such/base"), &fixture_path("basic")).unwrap(),
+            fixture_path("basic")
+        );
+        assert!(session_dir(&base, Path::new("no_such_dir")).is_err());
+    }
+
+    #[test]
+    fn test_for_non_dir() {
+        assert!(Session::for_dir(&fixture_path("basic").join("package.json")).is_err());
+        assert!(Session::for_dir(&fixture_path("no_such_dir")).is_err());
+    }
+
+    #[test]
+    fn test_version_source_display() {
+        let source = VersionSource::Project(fixture_path("basic").join("package.json"));
+        assert_eq!(source.to_string(), "pinned in package.json");
+        assert_eq!(
+            VersionSource::NodeVersionFile(".nvmrc").to_string(),
+            "selected by .nvmrc"
+        );
+        assert_eq!(VersionSource::User.to_string(), "user default");
+    }
 }
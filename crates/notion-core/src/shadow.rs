@@ -0,0 +1,265 @@
+//! Detects other Node installations whose executables come before Notion's shims on
+//! the `PATH`, and so run in place of the versions Notion selects, and the shims of
+//! corepack, which manage Yarn and pnpm in competition with Notion's.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use fs::{ensure_containing_dir_exists, read_file_opt};
+use notion_fail::{Fallible, ResultExt};
+use path;
+use style::display_warning;
+
+/// The tools whose shims have to come first on the `PATH`.
+pub const SHIMMED_TOOLS: &'static [&'static str] = &["node", "yarn", "npm"];
+
+/// The package managers that corepack provides shims for.
+pub const COREPACK_TOOLS: &'static [&'static str] = &["yarn", "pnpm"];
+
+/// How to stop corepack and Notion from both managing Yarn and pnpm.
+pub const COREPACK_FIX: &'static str = "run `corepack disable`, or set `corepack = true` in the [behavior] section of Notion's config to let corepack manage Yarn and pnpm";
+
+/// An executable that runs in place of one of Notion's shims.
+pub struct Shadowing {
+    /// The tool whose shim is shadowed.
+    pub tool: &'static str,
+    /// The directory on the `PATH`, before the shim directory, that provides the tool.
+    pub dir: PathBuf,
+}
+
+impl Shadowing {
+    /// Suggests how to let the shim run again.
+    pub fn fix(&self) -> Fallible<String> {
+        Ok(format!(
+            "move {} to the front of the PATH in your shell's profile, or uninstall the {} in {}",
+            path::shim_dir()?.display(),
+            self.tool,
+            self.dir.display()
+        ))
+    }
+}
+
+/// Where the shim directory is on a search path.
+pub enum ShimPath {
+    /// The shim directory isn't on the search path at all.
+    Missing,
+    /// Another executable comes before one of the shims.
+    Shadowed(Shadowing),
+    /// Every shim comes before any other executable of its tool.
+    First,
+}
+
+/// Checks that the shim directory is on a search path, and that no directory before
+/// it provides a tool that the shims are meant to provide. Notion's own directories
+/// don't count, since the shims put them first on the `PATH` of the tools they run.
+pub fn check(search_path: Option<OsString>) -> Fallible<ShimPath> {
+    let shim_dir = path::shim_dir()?;
+    let dirs: Vec<PathBuf> = search_path
+        .map(|search_path| env::split_paths(&search_path).collect())
+        .unwrap_or_default();
+
+    let position = match dirs.iter().position(|dir| dir == &shim_dir) {
+        Some(position) => position,
+        None => {
+            return Ok(ShimPath::Missing);
+        }
+    };
+
+    let mut own_dirs = vec![path::versions_dir()?, path::packages_dir()?];
+    own_dirs.extend(path::system_versions_dir());
+
+    for &tool in SHIMMED_TOOLS {
+        let shim_name = match path::shim_file(tool)?.file_name() {
+            Some(name) => name.to_os_string(),
+            None => continue,
+        };
+        let shadowing = dirs[..position].iter().find(|dir| {
+            !own_dirs.iter().any(|own| dir.starts_with(own)) && dir.join(&shim_name).is_file()
+        });
+        if let Some(dir) = shadowing {
+            return Ok(ShimPath::Shadowed(Shadowing {
+                tool,
+                dir: dir.clone(),
+            }));
+        }
+    }
+
+    Ok(ShimPath::First)
+}
+
+/// Finds the shims of corepack on a search path: the Yarn and pnpm executables,
+/// outside of Notion's shim directory, that belong to corepack.
+pub fn corepack_shims(search_path: Option<OsString>) -> Fallible<Vec<Shadowing>> {
+    let shim_dir = path::shim_dir()?;
+    let dirs: Vec<PathBuf> = search_path
+        .map(|search_path| env::split_paths(&search_path).collect())
+        .unwrap_or_default();
+
+    let mut shims = vec![];
+    for dir in dirs.iter().filter(|dir| *dir != &shim_dir) {
+        for &tool in COREPACK_TOOLS {
+            let found = executable_names(tool)
+                .iter()
+                .any(|name| is_corepack_shim(&dir.join(name)));
+            if found {
+                shims.push(Shadowing {
+                    tool,
+                    dir: dir.clone(),
+                });
+            }
+        }
+    }
+    Ok(shims)
+}
+
+/// The names an executable of a tool can have in a directory on the `PATH`.
+fn executable_names(tool: &str) -> Vec<String> {
+    if cfg!(windows) {
+        vec![format!("{}.cmd", tool), format!("{}.exe", tool)]
+    } else {
+        vec![tool.to_string()]
+    }
+}
+
+/// Whether an executable belongs to corepack: either it links into corepack's
+/// package (as `corepack enable` sets up on Unix), or it is a small script that
+/// refers to it (as on Windows).
+fn is_corepack_shim(file: &Path) -> bool {
+    if !file.is_file() {
+        return false;
+    }
+    let links_to_corepack = fs::canonicalize(file)
+        .map(|target| {
+            target
+                .components()
+                .any(|component| component == Component::Normal("corepack".as_ref()))
+        })
+        .unwrap_or(false);
+    if links_to_corepack {
+        return true;
+    }
+    let small = fs::metadata(file)
+        .map(|metadata| metadata.len() < 64 * 1024)
+        .unwrap_or(false);
+    small
+        && fs::read(file)
+            .map(|contents| String::from_utf8_lossy(&contents).contains("corepack"))
+            .unwrap_or(false)
+}
+
+/// Warns if another executable shadows one of the shims on the current `PATH`, or
+/// (unless Notion defers to corepack) if corepack's shims are on it too. Each
+/// directory is only warned about once, so that the warning doesn't repeat on every run.
+pub(crate) fn warn_once(defer_to_corepack: bool) -> Fallible<()> {
+    let search_path = env::var_os("PATH");
+    if let ShimPath::Shadowed(shadowing) = check(search_path.clone())? {
+        let message = format!(
+            "the {} in {} comes before Notion's shims on the PATH, so it runs instead of the version Notion selects; {}",
+            shadowing.tool,
+            shadowing.dir.display(),
+            shadowing.fix()?
+        );
+        warn_once_for(&shadowing.dir, &message)?;
+    }
+
+    if !defer_to_corepack {
+        if let Some(shim) = corepack_shims(search_path)?.into_iter().next() {
+            let message = format!(
+                "corepack's {} shim in {} is on the PATH, so corepack and Notion may select different versions of the package manager; {}",
+                shim.tool,
+                shim.dir.display(),
+                COREPACK_FIX
+            );
+            warn_once_for(&shim.dir, &message)?;
+        }
+    }
+    Ok(())
+}
+
+/// Displays a warning about a directory on the `PATH`, unless one has been displayed
+/// about it before.
+fn warn_once_for(dir: &Path, message: &str) -> Fallible<()> {
+    let hints_file = path::path_shadowing_hints_file()?;
+    let dir = dir.to_string_lossy().to_string();
+    let warned = read_file_opt(&hints_file).unknown()?;
+    if warned.map_or(false, |warned| warned.lines().any(|line| line == dir)) {
+        return Ok(());
+    }
+
+    display_warning(message);
+
+    ensure_containing_dir_exists(&hints_file)?;
+    let mut hints = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&hints_file)
+        .unknown()?;
+    writeln!(hints, "{}", dir).unknown()?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{check, corepack_shims, executable_names, ShimPath};
+    use path;
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check() {
+        let shim_dir = path::shim_dir().unwrap();
+        let other = tempdir().unwrap();
+        let shim_name = path::shim_file("npm").unwrap();
+        let shim_name = shim_name.file_name().unwrap();
+        File::create(other.path().join(shim_name)).unwrap();
+
+        let missing = env::join_paths(vec![other.path().to_path_buf()]).unwrap();
+        assert!(match check(Some(missing)).unwrap() {
+            ShimPath::Missing => true,
+            _ => false,
+        });
+
+        let first = env::join_paths(vec![shim_dir.clone(), other.path().to_path_buf()]).unwrap();
+        assert!(match check(Some(first)).unwrap() {
+            ShimPath::First => true,
+            _ => false,
+        });
+
+        let shadowed = env::join_paths(vec![other.path().to_path_buf(), shim_dir]).unwrap();
+        match check(Some(shadowed)).unwrap() {
+            ShimPath::Shadowed(shadowing) => {
+                assert_eq!(shadowing.tool, "npm");
+                assert_eq!(shadowing.dir, other.path());
+            }
+            _ => panic!("expected npm to shadow its shim"),
+        }
+    }
+
+    #[test]
+    fn test_corepack_shims() {
+        let shim_dir = path::shim_dir().unwrap();
+        let other = tempdir().unwrap();
+        let name = &executable_names("pnpm")[0];
+        File::create(other.path().join(name))
+            .unwrap()
+            .write_all(b"#!/bin/sh\nexec node \"$basedir/node_modules/corepack/dist/pnpm.js\" \"$@\"\n")
+            .unwrap();
+        let name = &executable_names("yarn")[0];
+        File::create(other.path().join(name))
+            .unwrap()
+            .write_all(b"#!/bin/sh\nexec node yarn.js \"$@\"\n")
+            .unwrap();
+
+        let search_path = env::join_paths(vec![shim_dir, other.path().to_path_buf()]).unwrap();
+        let shims = corepack_shims(Some(search_path)).unwrap();
+        assert_eq!(shims.len(), 1);
+        assert_eq!(shims[0].tool, "pnpm");
+        assert_eq!(shims[0].dir, other.path());
+    }
+}
@@ -25,6 +25,10 @@ impl Shell for Bash {
                 tool.to_ascii_uppercase(),
                 version
             ),
+            &Postscript::ClearToolVersions { ref tools } => tools
+                .iter()
+                .map(|tool| format!("unset NOTION_{}_VERSION\n", tool.to_ascii_uppercase()))
+                .collect(),
         }
     }
 }
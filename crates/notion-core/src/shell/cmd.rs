@@ -0,0 +1,34 @@
+use std::path::{Path, PathBuf};
+
+use super::{Postscript, Shell};
+
+pub(crate) struct Cmd {
+    pub(crate) postscript_path: PathBuf,
+}
+
+impl Shell for Cmd {
+    fn postscript_path(&self) -> &Path {
+        &self.postscript_path
+    }
+
+    fn compile_postscript(&self, postscript: &Postscript) -> String {
+        match postscript {
+            &Postscript::Path(ref s) => {
+                // ISSUE(#99): proper escaping
+                format!("set \"PATH={}\"\r\n", s)
+            }
+            &Postscript::ToolVersion {
+                ref tool,
+                ref version,
+            } => format!(
+                "set \"NOTION_{}_VERSION={}\"\r\n",
+                tool.to_ascii_uppercase(),
+                version
+            ),
+            &Postscript::ClearToolVersions { ref tools } => tools
+                .iter()
+                .map(|tool| format!("set NOTION_{}_VERSION=\r\n", tool.to_ascii_uppercase()))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::{Path, PathBuf};
+
+use super::{Postscript, Shell};
+
+pub(crate) struct Fish {
+    pub(crate) postscript_path: PathBuf,
+}
+
+impl Shell for Fish {
+    fn postscript_path(&self) -> &Path {
+        &self.postscript_path
+    }
+
+    fn compile_postscript(&self, postscript: &Postscript) -> String {
+        match postscript {
+            &Postscript::Path(ref s) => {
+                // ISSUE(#99): proper escaping
+                format!("set -gx PATH (string split : -- '{}')\n", s)
+            }
+            &Postscript::ToolVersion {
+                ref tool,
+                ref version,
+            } => format!(
+                "set -gx NOTION_{}_VERSION {}\n",
+                tool.to_ascii_uppercase(),
+                version
+            ),
+            &Postscript::ClearToolVersions { ref tools } => tools
+                .iter()
+                .map(|tool| format!("set -e NOTION_{}_VERSION\n", tool.to_ascii_uppercase()))
+                .collect(),
+        }
+    }
+}
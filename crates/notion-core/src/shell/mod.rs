@@ -10,12 +10,20 @@ use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
 use env;
 
 mod bash;
+mod cmd;
+mod fish;
+mod powershell;
+pub mod setup;
 
 pub(crate) use self::bash::Bash;
+pub(crate) use self::cmd::Cmd;
+pub(crate) use self::fish::Fish;
+pub(crate) use self::powershell::PowerShell;
 
 pub enum Postscript {
     Path(String),
     ToolVersion { tool: String, version: Version },
+    ClearToolVersions { tools: Vec<String> },
 }
 
 /// Thrown when the postscript file was not specified in the Notion environment.
@@ -84,6 +92,9 @@ impl FromStr for CurrentShell {
 
         Ok(CurrentShell(match src {
             "bash" => Box::new(Bash { postscript_path }),
+            "fish" => Box::new(Fish { postscript_path }),
+            "powershell" => Box::new(PowerShell { postscript_path }),
+            "cmd" => Box::new(Cmd { postscript_path }),
             _ => {
                 throw!(UnrecognizedShellError {
                     name: src.to_string()
@@ -122,5 +133,66 @@ pub mod tests {
             }),
             "export NOTION_TEST_VERSION=2.4.5\n"
         );
+
+        assert_eq!(
+            bash.compile_postscript(&Postscript::ClearToolVersions {
+                tools: vec!["test".to_string(), "other".to_string()],
+            }),
+            "unset NOTION_TEST_VERSION\nunset NOTION_OTHER_VERSION\n"
+        );
+
+        let fish = CurrentShell::from_str("fish").expect("Could not create fish shell");
+
+        assert_eq!(
+            fish.compile_postscript(&Postscript::Path("some:path".to_string())),
+            "set -gx PATH (string split : -- 'some:path')\n"
+        );
+
+        assert_eq!(
+            fish.compile_postscript(&Postscript::ToolVersion {
+                tool: "test".to_string(),
+                version: Version::parse("2.4.5").unwrap()
+            }),
+            "set -gx NOTION_TEST_VERSION 2.4.5\n"
+        );
+
+        assert_eq!(
+            fish.compile_postscript(&Postscript::ClearToolVersions {
+                tools: vec!["test".to_string()],
+            }),
+            "set -e NOTION_TEST_VERSION\n"
+        );
+
+        let powershell =
+            CurrentShell::from_str("powershell").expect("Could not create PowerShell shell");
+
+        assert_eq!(
+            powershell.compile_postscript(&Postscript::Path("C:\\some;D:\\path".to_string())),
+            "$env:PATH = 'C:\\some;D:\\path'\n"
+        );
+
+        assert_eq!(
+            powershell.compile_postscript(&Postscript::ClearToolVersions {
+                tools: vec!["test".to_string()],
+            }),
+            "Remove-Item Env:NOTION_TEST_VERSION -ErrorAction SilentlyContinue\n"
+        );
+
+        let cmd = CurrentShell::from_str("cmd").expect("Could not create cmd shell");
+
+        assert_eq!(
+            cmd.compile_postscript(&Postscript::ToolVersion {
+                tool: "test".to_string(),
+                version: Version::parse("2.4.5").unwrap()
+            }),
+            "set \"NOTION_TEST_VERSION=2.4.5\"\r\n"
+        );
+
+        assert_eq!(
+            cmd.compile_postscript(&Postscript::ClearToolVersions {
+                tools: vec!["test".to_string()],
+            }),
+            "set NOTION_TEST_VERSION=\r\n"
+        );
     }
 }
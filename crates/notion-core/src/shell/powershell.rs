@@ -0,0 +1,39 @@
+use std::path::{Path, PathBuf};
+
+use super::{Postscript, Shell};
+
+pub(crate) struct PowerShell {
+    pub(crate) postscript_path: PathBuf,
+}
+
+impl Shell for PowerShell {
+    fn postscript_path(&self) -> &Path {
+        &self.postscript_path
+    }
+
+    fn compile_postscript(&self, postscript: &Postscript) -> String {
+        match postscript {
+            &Postscript::Path(ref s) => {
+                // ISSUE(#99): proper escaping
+                format!("$env:PATH = '{}'\n", s)
+            }
+            &Postscript::ToolVersion {
+                ref tool,
+                ref version,
+            } => format!(
+                "$env:NOTION_{}_VERSION = '{}'\n",
+                tool.to_ascii_uppercase(),
+                version
+            ),
+            &Postscript::ClearToolVersions { ref tools } => tools
+                .iter()
+                .map(|tool| {
+                    format!(
+                        "Remove-Item Env:NOTION_{}_VERSION -ErrorAction SilentlyContinue\n",
+                        tool.to_ascii_uppercase()
+                    )
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Provides the shell code that puts Notion on the `PATH` and wraps the
+//! `notion` executable, so that commands can modify the calling shell.
+
+use std::env;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+
+use path;
+
+use super::{Bash, Cmd, Fish, Postscript, PowerShell, Shell};
+
+/// Thrown when the user asks to set up a shell that Notion doesn't support.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot set up unknown shell '{}' - expected bash, zsh, fish, powershell, or cmd",
+       name)]
+#[notion_fail(code = "InvalidArguments")]
+pub struct UnknownShellError {
+    pub name: String,
+}
+
+/// Thrown when the user's shell can't be determined from the `SHELL` environment variable.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not detect your shell from $SHELL - use `--shell` to choose one")]
+#[notion_fail(code = "EnvironmentError")]
+pub struct UndetectedShellError;
+
+/// Thrown when the user's home directory, where the profile files live, can't be determined.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not determine your home directory")]
+#[notion_fail(code = "EnvironmentError")]
+pub struct NoHomeDirError;
+
+/// Thrown when asked to edit the profile of a shell that doesn't have one.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} has no profile file - run the output of `notion setup` from AutoRun instead",
+       name)]
+#[notion_fail(code = "InvalidArguments")]
+pub struct NoProfileError {
+    pub name: String,
+}
+
+/// Thrown when asked for a directory-change hook for a shell that Notion can't hook into.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "automatic version switching is not yet supported in {}", name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub struct NoHookError {
+    pub name: String,
+}
+
+/// The shells that Notion can set up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShellKind {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Cmd,
+}
+
+/// Thrown when the user's `PATH` could not be read from or written to the registry.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not add the shims to your PATH in the registry: {}", error)]
+#[notion_fail(code = "EnvironmentError")]
+pub struct RegistryPathError {
+    pub error: String,
+}
+
+/// The outcome of adding the shim directory to the user's `PATH` in the registry.
+pub enum PathUpdate {
+    /// The shim directory was put at the front of the user's `PATH`.
+    Updated,
+    /// The user's `PATH` already has the shim directory, so it was left alone.
+    Unchanged,
+}
+
+/// The outcome of adding Notion's setup to a profile file.
+pub enum ProfileUpdate {
+    /// The setup was appended to the profile file.
+    Updated(PathBuf),
+    /// The profile file already sets up Notion, so it was left alone.
+    Unchanged(PathBuf),
+}
+
+impl FromStr for ShellKind {
+    type Err = NotionError;
+
+    fn from_str(src: &str) -> Result<Self, NotionError> {
+        Ok(match src {
+            "bash" => ShellKind::Bash,
+            "zsh" => ShellKind::Zsh,
+            "fish" => ShellKind::Fish,
+            "powershell" => ShellKind::PowerShell,
+            "cmd" => ShellKind::Cmd,
+            _ => {
+                throw!(UnknownShellError {
+                    name: src.to_string(),
+                });
+            }
+        })
+    }
+}
+
+impl ShellKind {
+    /// Determines the user's shell from the `SHELL` environment variable.
+    pub fn detect() -> Fallible<Self> {
+        let shell = env::var_os("SHELL").ok_or(UndetectedShellError)?;
+        let name = Path::new(&shell)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or(UndetectedShellError)?;
+        match name.parse() {
+            Ok(kind) => Ok(kind),
+            Err(_) => throw!(UndetectedShellError),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ShellKind::Bash => "bash",
+            ShellKind::Zsh => "zsh",
+            ShellKind::Fish => "fish",
+            ShellKind::PowerShell => "powershell",
+            ShellKind::Cmd => "cmd",
+        }
+    }
+
+    /// Produces the shell code that sets up Notion in this shell.
+    pub fn env_script(&self) -> Fallible<String> {
+        let notion_file = path::notion_file()?;
+        let notion_home = notion_file.parent().unwrap_or(Path::new(""));
+        Ok(self.script(notion_home, &path::shim_dir()?))
+    }
+
+    fn script(&self, notion_home: &Path, shim_dir: &Path) -> String {
+        // ISSUE(#99): proper escaping
+        match *self {
+            ShellKind::Bash | ShellKind::Zsh => format!(
+                "export NOTION_HOME=\"{}\"\n\
+                 [ -s \"$NOTION_HOME/load.sh\" ] && \\. \"$NOTION_HOME/load.sh\"\n\
+                 \n\
+                 export PATH=\"{}:$PATH\"\n",
+                notion_home.display(),
+                shim_dir.display()
+            ),
+            ShellKind::Fish => format!(
+                "set -gx NOTION_HOME \"{}\"\n\
+                 \n\
+                 function notion\n    \
+                     set -gx NOTION_POSTSCRIPT \"$NOTION_HOME/tmp/notion_tmp_\"(random)\".fish\"\n    \
+                     env NOTION_SHELL=fish \"$NOTION_HOME/notion\" $argv\n    \
+                     set -l exit_code $status\n    \
+                     if test -f \"$NOTION_POSTSCRIPT\"\n        \
+                         source \"$NOTION_POSTSCRIPT\"\n        \
+                         rm \"$NOTION_POSTSCRIPT\"\n    \
+                     end\n    \
+                     set -e NOTION_POSTSCRIPT\n    \
+                     return $exit_code\n\
+                 end\n\
+                 \n\
+                 set -gx PATH \"{}\" $PATH\n",
+                notion_home.display(),
+                shim_dir.display()
+            ),
+            // The shims are executables, so make sure the shell will find them even
+            // if `.EXE` was left out of PATHEXT.
+            ShellKind::PowerShell => format!(
+                "$env:NOTION_HOME = \"{}\"\n\
+                 if (Test-Path \"$env:NOTION_HOME\\notion.ps1\") {{ . \"$env:NOTION_HOME\\notion.ps1\" }}\n\
+                 \n\
+                 $env:PATH = \"{};$env:PATH\"\n\
+                 if (-not (($env:PATHEXT -split ';') -contains '.EXE')) {{ $env:PATHEXT = \"$env:PATHEXT;.EXE\" }}\n",
+                notion_home.display(),
+                shim_dir.display()
+            ),
+            // cmd.exe has no functions, so a macro sends `notion` to the wrapper script,
+            // which would otherwise lose to `notion.exe` in the PATHEXT order.
+            ShellKind::Cmd => format!(
+                "@echo off\r\n\
+                 set \"NOTION_HOME={}\"\r\n\
+                 doskey notion=\"%NOTION_HOME%\\notion.cmd\" $*\r\n\
+                 \r\n\
+                 set \"PATH={};%PATH%\"\r\n\
+                 echo ;%PATHEXT%; | find /i \";.EXE;\" > nul || set \"PATHEXT=%PATHEXT%;.EXE\"\r\n",
+                notion_home.display(),
+                shim_dir.display()
+            ),
+        }
+    }
+
+    /// Produces the shell code that runs `notion env --check` whenever the shell
+    /// shows a prompt (or, in zsh and fish, changes directory), applying whatever it
+    /// prints to switch the shell to the active toolchain.
+    pub fn hook_script(&self) -> Fallible<String> {
+        self.hook(&path::notion_file()?)
+    }
+
+    fn hook(&self, notion_file: &Path) -> Fallible<String> {
+        Ok(match *self {
+            ShellKind::Bash => format!(
+                "_notion_hook() {{\n    \
+                     local script\n    \
+                     script=\"$(command \"{}\" env --check --shell bash 2> /dev/null)\"\n    \
+                     if [ -n \"$script\" ]; then eval \"$script\"; fi\n\
+                 }}\n\
+                 \n\
+                 case \";${{PROMPT_COMMAND:-}};\" in\n    \
+                     *\";_notion_hook;\"*) ;;\n    \
+                     *) PROMPT_COMMAND=\"_notion_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}\" ;;\n\
+                 esac\n",
+                notion_file.display()
+            ),
+            ShellKind::Zsh => format!(
+                "_notion_hook() {{\n    \
+                     local script\n    \
+                     script=\"$(command \"{}\" env --check --shell zsh 2> /dev/null)\"\n    \
+                     if [ -n \"$script\" ]; then eval \"$script\"; fi\n\
+                 }}\n\
+                 \n\
+                 autoload -U add-zsh-hook\n\
+                 add-zsh-hook chpwd _notion_hook\n\
+                 _notion_hook\n",
+                notion_file.display()
+            ),
+            ShellKind::Fish => format!(
+                "function __notion_hook --on-variable PWD\n    \
+                     \"{}\" env --check --shell fish 2> /dev/null | source\n\
+                 end\n\
+                 \n\
+                 __notion_hook\n",
+                notion_file.display()
+            ),
+            ShellKind::PowerShell | ShellKind::Cmd => {
+                throw!(NoHookError {
+                    name: self.name().to_string(),
+                });
+            }
+        })
+    }
+
+    /// Compiles a postscript for this shell, for commands whose output the shell
+    /// evaluates directly rather than through the postscript file.
+    pub fn compile(&self, postscript: &Postscript) -> String {
+        let postscript_path = PathBuf::new();
+        let shell: Box<dyn Shell> = match *self {
+            ShellKind::Bash | ShellKind::Zsh => Box::new(Bash { postscript_path }),
+            ShellKind::Fish => Box::new(Fish { postscript_path }),
+            ShellKind::PowerShell => Box::new(PowerShell { postscript_path }),
+            ShellKind::Cmd => Box::new(Cmd { postscript_path }),
+        };
+        shell.compile_postscript(postscript)
+    }
+
+    /// Produces the path of the profile file this shell reads at startup.
+    pub fn profile_file(&self) -> Fallible<PathBuf> {
+        let home = env::home_dir().ok_or(NoHomeDirError)?;
+        Ok(match *self {
+            ShellKind::Bash => {
+                let bashrc = home.join(".bashrc");
+                let bash_profile = home.join(".bash_profile");
+                if !bashrc.exists() && bash_profile.exists() {
+                    bash_profile
+                } else {
+                    bashrc
+                }
+            }
+            ShellKind::Zsh => home.join(".zshrc"),
+            ShellKind::Fish => home.join(".config").join("fish").join("config.fish"),
+            ShellKind::PowerShell => home
+                .join("Documents")
+                .join("WindowsPowerShell")
+                .join("Microsoft.PowerShell_profile.ps1"),
+            ShellKind::Cmd => {
+                throw!(NoProfileError {
+                    name: self.name().to_string(),
+                });
+            }
+        })
+    }
+
+    /// Appends the setup to this shell's profile file, unless the file already
+    /// mentions `NOTION_HOME`.
+    pub fn install_profile(&self) -> Fallible<ProfileUpdate> {
+        let profile = self.profile_file()?;
+
+        if profile.exists() {
+            let mut contents = String::new();
+            File::open(&profile)
+                .and_then(|mut file| file.read_to_string(&mut contents))
+                .unknown()?;
+            if contents.contains("NOTION_HOME") {
+                return Ok(ProfileUpdate::Unchanged(profile));
+            }
+        } else if let Some(dir) = profile.parent() {
+            create_dir_all(dir).unknown()?;
+        }
+
+        let script = self.env_script()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&profile)
+            .unknown()?;
+        file.write_all(format!("\n{}", script).as_bytes())
+            .unknown()?;
+        Ok(ProfileUpdate::Updated(profile))
+    }
+}
+
+/// Adds the shim directory to the front of the user's `PATH` in the registry (the `Path`
+/// value of `HKEY_CURRENT_USER\Environment`), unless it is already there, so that every
+/// program started afterwards finds the shims, whatever shell (if any) it runs from.
+/// Only Windows has a registry; elsewhere this does nothing.
+pub fn register_user_path() -> Fallible<PathUpdate> {
+    register(&path::shim_dir()?)
+}
+
+#[cfg(windows)]
+fn register(shim_dir: &Path) -> Fallible<PathUpdate> {
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+
+    use winapi::shared::minwindef::LPARAM;
+    use winapi::um::winuser::{SendMessageTimeoutW, HWND_BROADCAST, SMTO_ABORTIFHUNG,
+                              WM_SETTINGCHANGE};
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE, REG_EXPAND_SZ};
+    use winreg::{RegKey, RegValue};
+
+    let error = |error: ::std::io::Error| RegistryPathError {
+        error: error.to_string(),
+    };
+    let environment = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey_with_flags("Environment", KEY_READ | KEY_WRITE)
+        .map_err(error)?;
+    // The variable is missing until the user first sets a PATH of their own.
+    let current: String = environment.get_value("Path").unwrap_or_default();
+    let updated = match prepend_path(&current, shim_dir) {
+        Some(updated) => updated,
+        None => return Ok(PathUpdate::Unchanged),
+    };
+
+    // The entries of the PATH may refer to other variables, like %USERPROFILE%, so it
+    // is stored as an expandable string, encoded as null-terminated UTF-16.
+    let bytes = OsStr::new(&updated)
+        .encode_wide()
+        .chain(Some(0))
+        .flat_map(|unit| vec![unit as u8, (unit >> 8) as u8])
+        .collect();
+    environment
+        .set_raw_value(
+            "Path",
+            &RegValue {
+                vtype: REG_EXPAND_SZ,
+                bytes,
+            },
+        )
+        .map_err(error)?;
+
+    // Tell the running programs (Explorer, in particular) to reload the environment, so
+    // that the terminals opened from now on get the new PATH without signing out.
+    let area: Vec<u16> = OsStr::new("Environment").encode_wide().chain(Some(0)).collect();
+    unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            0,
+            area.as_ptr() as LPARAM,
+            SMTO_ABORTIFHUNG,
+            5000,
+            ptr::null_mut(),
+        );
+    }
+    Ok(PathUpdate::Updated)
+}
+
+#[cfg(not(windows))]
+fn register(_: &Path) -> Fallible<PathUpdate> {
+    Ok(PathUpdate::Unchanged)
+}
+
+/// Puts a directory at the front of a `;`-separated `PATH`, unless one of its entries
+/// already is the directory. Windows paths are compared without regard to case or a
+/// trailing `\`.
+fn prepend_path(current: &str, dir: &Path) -> Option<String> {
+    let dir = dir.to_string_lossy();
+    let normalize = |entry: &str| entry.trim().trim_right_matches('\\').to_lowercase();
+    if current
+        .split(';')
+        .any(|entry| normalize(entry) == normalize(&dir))
+    {
+        return None;
+    }
+    Some(if current.is_empty() {
+        dir.to_string()
+    } else {
+        format!("{};{}", dir, current)
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{prepend_path, ShellKind};
+    use std::path::Path;
+
+    #[test]
+    fn test_prepend_path() {
+        let shims = Path::new("C:\\Program Files\\Notion\\bin");
+        assert_eq!(
+            prepend_path("%USERPROFILE%\\bin", shims),
+            Some("C:\\Program Files\\Notion\\bin;%USERPROFILE%\\bin".to_string())
+        );
+        assert_eq!(
+            prepend_path("", shims),
+            Some("C:\\Program Files\\Notion\\bin".to_string())
+        );
+        assert_eq!(
+            prepend_path("c:\\program files\\notion\\bin\\;C:\\Windows", shims),
+            None
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("bash".parse::<ShellKind>().unwrap(), ShellKind::Bash);
+        assert_eq!("zsh".parse::<ShellKind>().unwrap(), ShellKind::Zsh);
+        assert_eq!("fish".parse::<ShellKind>().unwrap(), ShellKind::Fish);
+        assert_eq!(
+            "powershell".parse::<ShellKind>().unwrap(),
+            ShellKind::PowerShell
+        );
+        assert_eq!("cmd".parse::<ShellKind>().unwrap(), ShellKind::Cmd);
+        assert!("tcsh".parse::<ShellKind>().is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let home = Path::new("/home/user/.notion");
+        let shims = Path::new("/home/user/.notion/bin");
+
+        let bash = ShellKind::Bash.script(home, shims);
+        assert!(bash.starts_with("export NOTION_HOME=\"/home/user/.notion\"\n"));
+        assert!(bash.ends_with("export PATH=\"/home/user/.notion/bin:$PATH\"\n"));
+        assert_eq!(bash, ShellKind::Zsh.script(home, shims));
+
+        let fish = ShellKind::Fish.script(home, shims);
+        assert!(fish.starts_with("set -gx NOTION_HOME \"/home/user/.notion\"\n"));
+        assert!(fish.contains("\nfunction notion\n    set -gx NOTION_POSTSCRIPT"));
+        assert!(fish.contains("    env NOTION_SHELL=fish \"$NOTION_HOME/notion\" $argv\n"));
+        assert!(fish.ends_with("set -gx PATH \"/home/user/.notion/bin\" $PATH\n"));
+
+        let home = Path::new("C:\\Program Files\\Notion");
+        let shims = Path::new("C:\\Program Files\\Notion\\bin");
+
+        let powershell = ShellKind::PowerShell.script(home, shims);
+        assert!(powershell.starts_with("$env:NOTION_HOME = \"C:\\Program Files\\Notion\"\n"));
+        assert!(powershell.contains("\n$env:PATH = \"C:\\Program Files\\Notion\\bin;$env:PATH\"\n"));
+        assert!(powershell.contains("$env:PATHEXT"));
+
+        let cmd = ShellKind::Cmd.script(home, shims);
+        assert!(cmd.contains("\r\ndoskey notion=\"%NOTION_HOME%\\notion.cmd\" $*\r\n"));
+        assert!(cmd.contains("\r\nset \"PATH=C:\\Program Files\\Notion\\bin;%PATH%\"\r\n"));
+    }
+
+    #[test]
+    fn test_hook() {
+        let notion = Path::new("/home/user/.notion/notion");
+
+        let bash = ShellKind::Bash.hook(notion).unwrap();
+        assert!(bash.contains("\"/home/user/.notion/notion\" env --check --shell bash"));
+        assert!(bash.contains("PROMPT_COMMAND=\"_notion_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}\""));
+
+        let zsh = ShellKind::Zsh.hook(notion).unwrap();
+        assert!(zsh.contains("env --check --shell zsh"));
+        assert!(zsh.contains("\nadd-zsh-hook chpwd _notion_hook\n"));
+
+        let fish = ShellKind::Fish.hook(notion).unwrap();
+        assert!(fish.starts_with("function __notion_hook --on-variable PWD\n"));
+        assert!(fish.contains("env --check --shell fish 2> /dev/null | source\n"));
+
+        assert!(ShellKind::PowerShell.hook(notion).is_err());
+        assert!(ShellKind::Cmd.hook(notion).is_err());
+    }
+}
@@ -1,9 +1,14 @@
 //! Provides utilities for modifying shims for 3rd-party executables
 
 use std::{fs, io};
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::PathBuf;
 
-use notion_fail::{ExitCode, FailExt, Fallible, NotionFail};
+use notion_fail::{ExitCode, FailExt, Fallible, NotionFail, ResultExt};
 use path;
+use session::Session;
+use which;
 
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "{}", error)]
@@ -26,17 +31,37 @@ impl SymlinkError {
     }
 }
 
-fn is_3p_shim(name: &str) -> bool {
+/// Returns whether the shim with the specified name is for a 3rd-party executable,
+/// rather than one of the tools Notion manages.
+pub fn is_3p_shim(name: &str) -> bool {
     match name {
-        "node" | "yarn" | "npm" | "npx" => false,
+        "node" | "yarn" | "yarnpkg" | "npm" | "npx" | "pnpm" | "pnpx" => false,
         _ => true,
     }
 }
 
+/// The shims for the tools Notion manages. The first four are executables installed
+/// with Notion; the rest link to one of them.
+pub const TOOL_SHIMS: &'static [&'static str] =
+    &["node", "yarn", "npm", "pnpm", "yarnpkg", "npx", "pnpx"];
+
+/// Returns the shim that the shim with the specified name links to, if it is a link:
+/// the launcher for a 3rd-party executable, or the tool an alias like `npx` runs.
+/// The tools themselves are executables, so there is nothing to link to.
+fn link_target(shim_name: &str) -> Fallible<Option<PathBuf>> {
+    Ok(match shim_name {
+        "node" | "yarn" | "npm" | "pnpm" => None,
+        "yarnpkg" => Some(path::shim_file("yarn")?),
+        "npx" => Some(path::shim_file("npm")?),
+        "pnpx" => Some(path::shim_file("pnpm")?),
+        _ => Some(path::launchbin_file()?),
+    })
+}
+
 pub fn create(shim_name: &str) -> Fallible<()> {
     let launchbin = path::launchbin_file()?;
     let shim = path::shim_file(shim_name)?;
-    match path::create_file_symlink(launchbin, shim) {
+    match path::create_shim_file(launchbin, shim) {
         Ok(_) => Ok(()),
         Err(err) => {
             if err.kind() == io::ErrorKind::AlreadyExists {
@@ -70,3 +95,176 @@ pub fn delete(shim_name: &str) -> Fallible<()> {
         }
     }
 }
+
+/// What repairing a shim did.
+#[derive(Debug, PartialEq)]
+pub enum Repair {
+    /// The shim was already in working order.
+    Unchanged,
+    /// The shim was missing, and has been created.
+    Created,
+    /// The shim was broken or out of date, and has been replaced.
+    Replaced,
+    /// The shim is one of the tool executables installed with Notion, and is missing,
+    /// so only reinstalling Notion can restore it.
+    Missing,
+}
+
+/// Makes sure the shim with the specified name exists and links to what it should.
+/// A link that dangles or points elsewhere is replaced, as is a copy of the launcher
+/// that no longer matches it (as after upgrading Notion on a system without symlinks).
+pub fn repair(shim_name: &str) -> Fallible<Repair> {
+    let shim = path::shim_file(shim_name)?;
+    let target = match link_target(shim_name)? {
+        Some(target) => target,
+        None => {
+            return Ok(if shim.is_file() {
+                Repair::Unchanged
+            } else {
+                Repair::Missing
+            });
+        }
+    };
+
+    let metadata = match fs::symlink_metadata(&shim) {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            ::fs::ensure_containing_dir_exists(&shim)?;
+            path::create_shim_file(target, shim).with_context(SymlinkError::from_io_error)?;
+            return Ok(Repair::Created);
+        }
+    };
+
+    let intact = if metadata.file_type().is_symlink() {
+        fs::read_link(&shim).ok().as_ref() == Some(&target) && shim.is_file()
+    } else {
+        let copied = fs::metadata(&target).unknown()?;
+        metadata.is_file() && metadata.len() == copied.len()
+    };
+    if intact {
+        return Ok(Repair::Unchanged);
+    }
+
+    fs::remove_file(&shim).with_context(SymlinkError::from_io_error)?;
+    path::create_shim_file(target, shim).with_context(SymlinkError::from_io_error)?;
+    Ok(Repair::Replaced)
+}
+
+/// Repairs every shim Notion expects: those of the tools it manages, those of the
+/// executables of installed packages, and any other shim already in the shim directory.
+/// Returns what was done to each, in alphabetical order.
+pub fn repair_all(session: &Session) -> Fallible<Vec<(String, Repair)>> {
+    let mut shim_names: BTreeSet<String> = TOOL_SHIMS.iter().map(|name| name.to_string()).collect();
+    for package in session.catalog()?.packages.values() {
+        shim_names.extend(package.bins.iter().cloned());
+    }
+    if path::shim_dir()?.is_dir() {
+        shim_names.extend(names()?.into_iter().map(|name| shim_name_of(&name)));
+    }
+
+    let mut repairs = vec![];
+    for shim_name in shim_names {
+        let repair = repair(&shim_name)?;
+        repairs.push((shim_name, repair));
+    }
+    Ok(repairs)
+}
+
+/// Returns the name of the shim stored in the specified file in the shim directory.
+fn shim_name_of(file_name: &str) -> String {
+    if cfg!(windows) && file_name.ends_with(".exe") {
+        file_name[..file_name.len() - 4].to_string()
+    } else {
+        file_name.to_string()
+    }
+}
+
+/// The health of a shim in the shim directory.
+#[derive(Debug, PartialEq)]
+pub enum ShimStatus {
+    /// The shim delegates to an executable that exists.
+    Ok,
+    /// The shim links to a launcher that no longer exists, so it can't run at all.
+    Broken,
+    /// Nothing provides the shim's executable anymore: not the current project, a
+    /// package installed for any Node version, or the system.
+    Stale,
+}
+
+/// Lists the names of the shims in the shim directory, in alphabetical order.
+pub fn names() -> Fallible<Vec<String>> {
+    let mut names = vec![];
+    for entry in fs::read_dir(path::shim_dir()?).unknown()? {
+        let entry = entry.unknown()?;
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Determines whether the shim with the specified name can still run something.
+pub fn status(session: &Session, shim_name: &str) -> Fallible<ShimStatus> {
+    let shim = path::shim_file(shim_name)?;
+    // `metadata` follows the link to the launcher, while `symlink_metadata` doesn't.
+    if fs::metadata(&shim).is_err() && fs::symlink_metadata(&shim).is_ok() {
+        return Ok(ShimStatus::Broken);
+    }
+    if !is_3p_shim(shim_name) {
+        return Ok(ShimStatus::Ok);
+    }
+
+    let name = OsStr::new(shim_name);
+    if let Some(path) = which::resolve(session, name)?.path {
+        if path.is_file() {
+            return Ok(ShimStatus::Ok);
+        }
+    }
+
+    // The binary might still be installed for a Node version that isn't active here.
+    for version in session.catalog()?.node.versions.iter() {
+        let bin = path::node_version_3p_bin_dir(&version.to_string())?.join(shim_name);
+        if bin.is_file() {
+            return Ok(ShimStatus::Ok);
+        }
+    }
+    Ok(ShimStatus::Stale)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{link_target, shim_name_of, TOOL_SHIMS};
+    use path;
+
+    #[test]
+    fn test_link_target() {
+        assert_eq!(link_target("node").unwrap(), None);
+        assert_eq!(link_target("pnpm").unwrap(), None);
+        assert_eq!(
+            link_target("npx").unwrap(),
+            Some(path::shim_file("npm").unwrap())
+        );
+        assert_eq!(
+            link_target("yarnpkg").unwrap(),
+            Some(path::shim_file("yarn").unwrap())
+        );
+        assert_eq!(
+            link_target("tsc").unwrap(),
+            Some(path::launchbin_file().unwrap())
+        );
+        for name in TOOL_SHIMS {
+            assert_ne!(
+                link_target(name).unwrap(),
+                Some(path::launchbin_file().unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_shim_name_of() {
+        assert_eq!(shim_name_of("tsc"), "tsc");
+        if cfg!(windows) {
+            assert_eq!(shim_name_of("tsc.exe"), "tsc");
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Runs the tool a shim delegates to so that signals and exit statuses pass through
+//! the shim, and Ctrl-C and process managers behave as they would with the tool itself.
+
+use std::io;
+use std::process::{Command, ExitStatus};
+
+/// Runs a shim's command to completion. While it runs, the signals sent to the shim
+/// are forwarded to it (on Unix), and the shim outlives any Ctrl-C (on Windows), so
+/// that the tool decides how to handle them.
+pub(crate) fn run(command: &mut Command) -> io::Result<ExitStatus> {
+    let mut child = command.spawn()?;
+    imp::forward_to(child.id());
+    let status = child.wait();
+    imp::forward_to(0);
+    status
+}
+
+/// The exit code of a tool, as shells report it: a Unix process that was killed by a
+/// signal gets 128 plus the number of the signal.
+pub(crate) fn exit_code(status: &ExitStatus) -> i32 {
+    imp::exit_code(status)
+}
+
+/// Exits the way a tool did: with its exit code, or on Unix by the signal that killed
+/// it, so that the parent process sees the signal death it would have seen otherwise.
+pub(crate) fn exit_like(status: &ExitStatus) -> ! {
+    imp::exit_like(status)
+}
+
+#[cfg(unix)]
+mod imp {
+    use libc;
+    use std::mem;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{exit, ExitStatus};
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+    /// The signals that a shim forwards to its tool.
+    const FORWARDED: [libc::c_int; 4] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+    /// The process ID of the running tool, or 0 while none is running.
+    static CHILD: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    extern "C" fn forward(signal: libc::c_int) {
+        let child = CHILD.load(Ordering::SeqCst);
+        if child == 0 {
+            return;
+        }
+        unsafe {
+            // A terminal's Ctrl-C (or Ctrl-\) reaches its whole foreground process
+            // group, which the tool shares with the shim. Forwarding it then would
+            // deliver it to the tool twice.
+            let from_terminal = (signal == libc::SIGINT || signal == libc::SIGQUIT)
+                && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp();
+            if !from_terminal {
+                libc::kill(child as libc::pid_t, signal);
+            }
+        }
+    }
+
+    pub(super) fn forward_to(child: u32) {
+        let previous = CHILD.swap(child as usize, Ordering::SeqCst);
+        if child == 0 || previous != 0 {
+            return;
+        }
+        // The handlers stay in place once the tool exits, doing nothing, so that the
+        // shim can't be killed between the tool's exit and its own.
+        for &signal in FORWARDED.iter() {
+            unsafe {
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = forward as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, ptr::null_mut());
+            }
+        }
+    }
+
+    pub(super) fn exit_code(status: &ExitStatus) -> i32 {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 1,
+        }
+    }
+
+    pub(super) fn exit_like(status: &ExitStatus) -> ! {
+        if let Some(signal) = status.signal() {
+            unsafe {
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = libc::SIG_DFL;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(signal, &action, ptr::null_mut());
+                libc::raise(signal);
+            }
+        }
+        // Only reached if the signal's default action doesn't terminate the process.
+        exit(exit_code(status))
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::process::{exit, ExitStatus};
+    use winapi::shared::minwindef::TRUE;
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+
+    pub(super) fn forward_to(child: u32) {
+        // Ctrl-C and Ctrl-Break reach every process attached to the console, the tool
+        // included, so the shim only has to ignore them and wait for the tool to exit.
+        if child != 0 {
+            unsafe {
+                SetConsoleCtrlHandler(None, TRUE);
+            }
+        }
+    }
+
+    pub(super) fn exit_code(status: &ExitStatus) -> i32 {
+        status.code().unwrap_or(1)
+    }
+
+    pub(super) fn exit_like(status: &ExitStatus) -> ! {
+        exit(exit_code(status))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exit_code() {
+        use super::exit_code;
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        // Exited with 3.
+        assert_eq!(exit_code(&ExitStatus::from_raw(3 << 8)), 3);
+        // Killed by SIGINT (2) and SIGTERM (15).
+        assert_eq!(exit_code(&ExitStatus::from_raw(2)), 130);
+        assert_eq!(exit_code(&ExitStatus::from_raw(15)), 143);
+    }
+}
@@ -0,0 +1,231 @@
+//! Provides a snapshot of the user's toolchain state (the installed tool versions, the
+//! user's defaults, and the packages installed as global tools), for `notion export`
+//! and for restoring it on another machine with `notion import`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::path::Path;
+
+use semver::Version;
+use serde_json;
+
+use catalog::{Catalog, Collection, ToolSpec};
+use distro::Distro;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use version::VersionSpec;
+
+/// Thrown when a state file could not be read, or isn't the output of `notion export`.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not read the state in {}: {}", file, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct StateFileError {
+    file: String,
+    error: String,
+}
+
+/// The toolchain state of a machine. The JSON serialization of this type is the format of
+/// `notion export`, so its fields must only change compatibly.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub node: ToolState,
+    pub yarn: ToolState,
+    pub npm: ToolState,
+    pub pnpm: ToolState,
+    /// The packages installed as global tools, by name.
+    pub packages: BTreeMap<String, PackageState>,
+}
+
+/// The installed versions of a tool and the user's default version of it.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ToolState {
+    pub default: Option<String>,
+    pub versions: Vec<String>,
+}
+
+/// A package installed as a global tool, and the Node version it runs with.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackageState {
+    pub version: String,
+    pub node: String,
+}
+
+impl ToolState {
+    fn capture<D: Distro>(collection: &Collection<D>) -> ToolState {
+        ToolState {
+            default: collection.default.as_ref().map(|version| version.to_string()),
+            // The system install's versions are the system's to provide.
+            versions: collection
+                .versions
+                .difference(&collection.system)
+                .map(|version| version.to_string())
+                .collect(),
+        }
+    }
+
+    /// Parses the versions listed, and the default if any.
+    fn parse(&self) -> Fallible<(BTreeSet<Version>, Option<Version>)> {
+        let mut versions = BTreeSet::new();
+        for version in &self.versions {
+            versions.insert(VersionSpec::parse_version(version)?);
+        }
+        let default = match self.default {
+            Some(ref default) => {
+                let default = VersionSpec::parse_version(default)?;
+                versions.insert(default.clone());
+                Some(default)
+            }
+            None => None,
+        };
+        Ok((versions, default))
+    }
+
+    /// Lists the versions of this state missing from a collection.
+    fn missing<D: Distro>(&self, collection: &Collection<D>) -> Fallible<Vec<Version>> {
+        let (versions, _) = self.parse()?;
+        Ok(versions
+            .into_iter()
+            .filter(|version| !collection.contains(version))
+            .collect())
+    }
+}
+
+impl State {
+    /// Captures the toolchain state recorded in the catalog.
+    pub fn capture(catalog: &Catalog) -> State {
+        State {
+            node: ToolState::capture(&catalog.node),
+            yarn: ToolState::capture(&catalog.yarn),
+            npm: ToolState::capture(&catalog.npm),
+            pnpm: ToolState::capture(&catalog.pnpm),
+            packages: catalog
+                .packages
+                .iter()
+                .map(|(name, package)| {
+                    (
+                        name.clone(),
+                        PackageState {
+                            version: package.version.to_string(),
+                            node: package.node.to_string(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads a state written by `notion export`.
+    pub fn from_file(file: &Path) -> Fallible<State> {
+        let error = |error: String| StateFileError {
+            file: file.to_string_lossy().to_string(),
+            error,
+        };
+        let reader = File::open(file).with_context(|e| error(e.to_string()))?;
+        Ok(serde_json::from_reader(reader).with_context(|e| error(e.to_string()))?)
+    }
+
+    /// Lists the tool versions this state has that the catalog doesn't, including the
+    /// Node versions the packages of this state run with, as specs to fetch.
+    pub fn missing(&self, catalog: &Catalog) -> Fallible<Vec<ToolSpec>> {
+        let exact = |version: Version| VersionSpec::exact(&version);
+        let mut node = self.node.missing(&catalog.node)?;
+        for package in self.packages.values() {
+            let version = VersionSpec::parse_version(&package.node)?;
+            if !catalog.node.contains(&version) && !node.contains(&version) {
+                node.push(version);
+            }
+        }
+
+        let yarn = self.yarn.missing(&catalog.yarn)?;
+        let npm = self.npm.missing(&catalog.npm)?;
+        let pnpm = self.pnpm.missing(&catalog.pnpm)?;
+
+        let mut specs: Vec<ToolSpec> = node.into_iter().map(exact).map(ToolSpec::Node).collect();
+        specs.extend(yarn.into_iter().map(exact).map(ToolSpec::Yarn));
+        specs.extend(npm.into_iter().map(exact).map(ToolSpec::Npm));
+        specs.extend(pnpm.into_iter().map(exact).map(ToolSpec::Pnpm));
+        Ok(specs)
+    }
+
+    /// Sets the user's default version of each tool to the default of this state, where
+    /// it has one. The versions must already be installed. Returns whether anything
+    /// changed.
+    pub fn apply_defaults(&self, catalog: &mut Catalog) -> Fallible<bool> {
+        let mut changed = false;
+        changed |= apply_default(&self.node, &mut catalog.node)?;
+        changed |= apply_default(&self.yarn, &mut catalog.yarn)?;
+        changed |= apply_default(&self.npm, &mut catalog.npm)?;
+        changed |= apply_default(&self.pnpm, &mut catalog.pnpm)?;
+        if changed {
+            catalog.save()?;
+        }
+        Ok(changed)
+    }
+}
+
+fn apply_default<D: Distro>(state: &ToolState, collection: &mut Collection<D>) -> Fallible<bool> {
+    let (_, default) = state.parse()?;
+    if default.is_some() && collection.default != default {
+        collection.default = default;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{PackageState, State, ToolState};
+    use catalog::{Catalog, ToolSpec};
+    use semver::Version;
+    use serde_json;
+
+    fn state() -> State {
+        let mut packages = ::std::collections::BTreeMap::new();
+        packages.insert(
+            "typescript".to_string(),
+            PackageState {
+                version: "3.1.6".to_string(),
+                node: "8.9.4".to_string(),
+            },
+        );
+        State {
+            node: ToolState {
+                default: Some("10.15.3".to_string()),
+                versions: vec!["10.15.3".to_string()],
+            },
+            yarn: ToolState {
+                default: None,
+                versions: vec!["1.12.3".to_string()],
+            },
+            npm: ToolState {
+                default: None,
+                versions: vec![],
+            },
+            pnpm: ToolState {
+                default: None,
+                versions: vec![],
+            },
+            packages,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = state();
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<State>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn test_missing() {
+        let mut catalog: Catalog = "".parse().unwrap();
+        catalog.yarn.versions.insert(Version::parse("1.12.3").unwrap());
+
+        let missing = state().missing(&catalog).unwrap();
+        assert_eq!(missing.len(), 2);
+        assert!(missing.iter().all(|spec| match *spec {
+            ToolSpec::Node(_) => true,
+            _ => false,
+        }));
+    }
+}
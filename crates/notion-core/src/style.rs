@@ -1,13 +1,60 @@
 //! The view layer of Notion, with utilities for styling command-line output.
 
+use std::cmp::min;
 use std::env;
 use std::fmt::{self, Display, Formatter};
+use std::io;
 
-use console::style;
+use std::time::{Duration, Instant};
+
+use console::{self, Key, StyledObject, Term};
 use failure::Fail;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use notion_fail::NotionError;
 use term_size;
 
+use env as notion_env;
+use log::{self, Level};
+
+/// Returns whether output may be colored: not if colors are disabled (by the `NO_COLOR`
+/// convention or the `--no-color` flag) or on a dumb terminal.
+pub fn supports_color() -> bool {
+    !notion_env::no_color() && !dumb_terminal()
+}
+
+/// Returns whether output may use symbols beyond ASCII: not on a dumb terminal.
+fn supports_unicode() -> bool {
+    !dumb_terminal()
+}
+
+fn dumb_terminal() -> bool {
+    env::var("TERM").map(|term| term == "dumb").unwrap_or(false)
+}
+
+/// Turns off colors for the whole process if output may not be colored. Every styled
+/// piece of output, including progress bars, checks first.
+fn check_colors() {
+    if !supports_color() {
+        console::set_colors_enabled(false);
+    }
+}
+
+/// Styles a value for display, with colors only where they are supported. All colored
+/// output goes through here (rather than `console::style`).
+pub fn style<D>(val: D) -> StyledObject<D> {
+    check_colors();
+    console::style(val)
+}
+
+/// The ellipsis that ends a notice of something still going on.
+fn ellipsis() -> &'static str {
+    if supports_unicode() {
+        "…"
+    } else {
+        "..."
+    }
+}
+
 /// Represents the context from which an error is being reported.
 pub enum ErrorContext {
     /// An error reported from the `notion` executable.
@@ -23,6 +70,38 @@ pub fn display_error<E: Display>(cx: ErrorContext, err: &E) {
     eprintln!("{}", err);
 }
 
+/// Returns whether errors are reported verbosely: with `--verbose` (which enables
+/// logging) or `NOTION_BACKTRACE=1`.
+pub fn verbose_errors() -> bool {
+    log::enabled(Level::Info) || notion_env::backtrace()
+}
+
+/// Displays the cause chain of an error to stderr, followed by its backtrace, if errors
+/// are reported verbosely. Otherwise the error's own message is all there is to see.
+pub fn display_error_details(err: &NotionError) {
+    if !verbose_errors() {
+        return;
+    }
+
+    let causes = err.cause_messages();
+    if !causes.is_empty() {
+        eprintln!();
+        eprintln!("{}", style("caused by:").yellow().bold());
+        for cause in causes {
+            eprintln!("    {}", cause);
+        }
+    }
+
+    let backtrace = format!("{:?}", err.backtrace());
+    eprintln!();
+    if backtrace.trim().is_empty() {
+        eprintln!("No backtrace was captured; run with NOTION_BACKTRACE=1 to capture one.");
+    } else {
+        eprintln!("{}", style("backtrace:").yellow().bold());
+        eprintln!("{}", backtrace);
+    }
+}
+
 /// Displays an error to stderr with a styled prefix.
 pub fn display_error_prefix(cx: ErrorContext) {
     match cx {
@@ -41,6 +120,18 @@ pub fn display_error_prefix(cx: ErrorContext) {
     }
 }
 
+/// Displays a hint to stderr, suggesting how the user could improve their setup.
+pub fn display_hint(message: &str) {
+    eprintln!("{} {}", style("hint:").cyan().bold(), message);
+}
+
+/// Displays what the user could do about an error to stderr, if it suggests anything.
+pub fn display_suggestion(err: &NotionError) {
+    if let Some(suggestion) = err.suggestion() {
+        display_hint(suggestion);
+    }
+}
+
 /// Displays a generic message for internal errors to stderr.
 pub fn display_unknown_error<E: Fail>(cx: ErrorContext, err: &E) {
     display_error_prefix(cx);
@@ -78,6 +169,90 @@ pub fn display_unknown_error<E: Fail>(cx: ErrorContext, err: &E) {
     }
 }
 
+/// Displays a notice to stderr that a failed download is about to be retried. The
+/// current line is cleared first, so that the notice isn't garbled by a progress
+/// spinner that is drawing on the same line (the spinner redraws itself below).
+pub fn display_retrying(url: &str, attempt: u32, attempts: u32, delay: Duration) {
+    let term = Term::stderr();
+    let _ = term.clear_line();
+    let _ = term.write_line(&format!(
+        "{} fetching {} failed, retrying in {}ms ({}/{}){}",
+        style("warning:").yellow().bold(),
+        url,
+        delay.as_secs() * 1000 + (delay.subsec_nanos() / 1_000_000) as u64,
+        attempt,
+        attempts - 1,
+        ellipsis()
+    ));
+}
+
+/// Displays a notice to stderr that a server rate limited a request, which is sent again
+/// once the wait is over. As with `display_retrying`, the current line is cleared first.
+pub fn display_rate_limited(url: &str, attempt: u32, attempts: u32, wait: Duration) {
+    let term = Term::stderr();
+    let _ = term.clear_line();
+    let _ = term.write_line(&format!(
+        "{} {} is rate limiting requests, waiting {}s to try again ({}/{}){}",
+        style("warning:").yellow().bold(),
+        url,
+        wait.as_secs(),
+        attempt,
+        attempts,
+        ellipsis()
+    ));
+}
+
+/// Displays a notice to stderr that Notion is waiting for another Notion process to
+/// finish with a file before going on. As with `display_retrying`, the current line
+/// is cleared first.
+pub fn display_waiting(activity: &str) {
+    let term = Term::stderr();
+    let _ = term.clear_line();
+    let _ = term.write_line(&format!(
+        "{} waiting for another notion process to finish {}{}",
+        style("note:").cyan().bold(),
+        activity,
+        ellipsis()
+    ));
+}
+
+/// Displays a warning to stderr. As with `display_retrying`, the current line is
+/// cleared first.
+pub fn display_warning(message: &str) {
+    let term = Term::stderr();
+    let _ = term.clear_line();
+    let _ = term.write_line(&format!("{} {}", style("warning:").yellow().bold(), message));
+}
+
+/// Formats rows of text as left-aligned columns, each as wide as its widest cell and
+/// separated by two spaces. Trailing whitespace is trimmed from every line.
+pub fn format_columns(rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = vec![];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            if i < widths.len() {
+                widths[i] = ::std::cmp::max(widths[i], width);
+            } else {
+                widths.push(width);
+            }
+        }
+    }
+
+    let mut lines = vec![];
+    for row in rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            line.push_str(&format!("{:width$}", cell, width = widths[i]));
+        }
+        lines.push(line.trim_right().to_string());
+    }
+    lines.join("\n")
+}
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
 pub enum Action {
     Fetching,
@@ -97,11 +272,105 @@ impl Display for Action {
     }
 }
 
+/// How progress is displayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Animated progress bars and spinners, for terminals.
+    Animated,
+    /// A single line of status as each operation starts, for logs.
+    Terse,
+    /// Nothing at all.
+    Quiet,
+}
+
+/// Determines how progress is displayed: not at all in quiet mode (the `--quiet` flag
+/// or the `NOTION_QUIET` environment variable), as terse lines of status when stderr
+/// isn't a terminal that can redraw them (animated bars garble CI logs), and
+/// animated otherwise.
+pub fn progress_mode() -> ProgressMode {
+    if notion_env::quiet() {
+        ProgressMode::Quiet
+    } else if !Term::stderr().is_term() || dumb_terminal() {
+        ProgressMode::Terse
+    } else {
+        ProgressMode::Animated
+    }
+}
+
+/// How often a `ProgressLog` reports progress that isn't another tenth of the way along.
+const PROGRESS_LOG_INTERVAL_SECS: u64 = 10;
+
+/// Reports the progress of an operation as occasional lines of status rather than as an
+/// animated bar, for when stderr isn't a terminal, so that a long download in CI doesn't
+/// look hung: a line every tenth of the way, or every ten seconds, whichever is sooner.
+pub struct ProgressLog {
+    action: Action,
+    details: String,
+    total: u64,
+    done: u64,
+    reported_at: Instant,
+}
+
+impl ProgressLog {
+    /// Constructs a log of the progress of an operation with the specified logical
+    /// length, if progress is displayed in terse mode.
+    pub fn terse(action: Action, details: &str, total: u64) -> Option<ProgressLog> {
+        if progress_mode() != ProgressMode::Terse || total == 0 {
+            return None;
+        }
+        Some(ProgressLog {
+            action,
+            details: details.to_string(),
+            total,
+            done: 0,
+            reported_at: Instant::now(),
+        })
+    }
+
+    /// Advances the progress, reporting it if it is due.
+    pub fn inc(&mut self, delta: u64) {
+        let tenth = self.done * 10 / self.total;
+        self.done = ::std::cmp::min(self.done + delta, self.total);
+        let elapsed = self.reported_at.elapsed().as_secs() >= PROGRESS_LOG_INTERVAL_SECS;
+        if elapsed || self.done * 10 / self.total > tenth {
+            eprintln!(
+                "{}",
+                format_progress(self.action, &self.details, self.done, self.total)
+            );
+            self.reported_at = Instant::now();
+        }
+    }
+}
+
+/// Formats a line of status reporting progress in bytes, e.g.
+/// `Fetching v10.15.3: 45% (13.2/29.1 MB)`.
+fn format_progress(action: Action, details: &str, done: u64, total: u64) -> String {
+    let megabytes = |bytes: u64| bytes as f64 / 1_000_000.0;
+    format!(
+        "{} {}: {}% ({:.1}/{:.1} MB)",
+        action,
+        details,
+        done * 100 / total,
+        megabytes(done),
+        megabytes(total)
+    )
+}
+
 /// Constructs a command-line progress bar with the specified Action enum
 /// (e.g., `Action::Installing`), details string (e.g., `"v1.23.4"`), and logical
 /// length (i.e., the number of logical progress steps in the process being
-/// visualized by the progress bar).
+/// visualized by the progress bar). In terse mode, only the action and details are
+/// displayed, as a line of status, and in quiet mode nothing is.
 pub fn progress_bar(action: Action, details: &str, len: u64) -> ProgressBar {
+    match progress_mode() {
+        ProgressMode::Quiet => return ProgressBar::hidden(),
+        ProgressMode::Terse => {
+            eprintln!("{} {}", action, details);
+            return ProgressBar::hidden();
+        }
+        ProgressMode::Animated => {}
+    }
+
     let display_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(80);
     let msg_width = Action::MAX_WIDTH + 1 + details.len();
 
@@ -131,10 +400,134 @@ pub fn progress_bar(action: Action, details: &str, len: u64) -> ProgressBar {
     bar
 }
 
+/// A display of the progress bars of operations that run concurrently, stacked one per
+/// line, each labeled with its operation, rather than drawn over one another. The bars
+/// it hands out are handles that it manages, and it has to be drawn from the thread
+/// that owns it (with `join_and_clear`) while the operations run on other threads.
+pub struct ProgressDisplay {
+    multi: MultiProgress,
+    bars: usize,
+}
+
+impl ProgressDisplay {
+    /// Constructs an empty display. When progress isn't animated, the display draws
+    /// nothing itself (though its bars still report their operations in terse mode).
+    pub fn new() -> ProgressDisplay {
+        let multi = match progress_mode() {
+            ProgressMode::Animated => MultiProgress::new(),
+            _ => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        };
+        ProgressDisplay { multi, bars: 0 }
+    }
+
+    /// Constructs a progress bar (as `progress_bar` does) in the next line of the display.
+    pub fn progress_bar(&mut self, action: Action, details: &str, len: u64) -> ProgressBar {
+        self.add(progress_bar(action, details, len))
+    }
+
+    /// Moves a progress bar into the next line of the display.
+    pub fn add(&mut self, bar: ProgressBar) -> ProgressBar {
+        self.bars += 1;
+        self.multi.add(bar)
+    }
+
+    /// Draws the display until every one of its bars has finished, and then clears it.
+    /// Every bar has to be finished, including those of operations that failed, or
+    /// this never returns.
+    pub fn join_and_clear(self) {
+        if self.bars > 0 {
+            let _ = self.multi.join_and_clear();
+        }
+    }
+}
+
+/// Returns whether the user can answer questions: both stdin and stderr have to be
+/// terminals, and not dumb ones.
+pub fn interactive() -> bool {
+    stdin_is_term() && Term::stderr().is_term() && !dumb_terminal()
+}
+
+#[cfg(unix)]
+fn stdin_is_term() -> bool {
+    use libc;
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+#[cfg(windows)]
+fn stdin_is_term() -> bool {
+    use winapi::um::consoleapi::GetConsoleMode;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_INPUT_HANDLE;
+
+    let mut mode = 0;
+    unsafe { GetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), &mut mode) != 0 }
+}
+
+/// The number of choices a picker shows at once.
+const PICKER_ROWS: usize = 8;
+
+/// Asks the user to pick one of `choices`, which they move through with the arrow keys
+/// (or `j` and `k`) and pick with Enter. The list scrolls, showing `PICKER_ROWS`
+/// choices at once. Produces `None` if the user cancels with Escape (or `q`), or if
+/// there is nothing to pick. The picker is drawn on stderr and erased afterwards.
+pub fn pick(prompt: &str, choices: &[String]) -> io::Result<Option<usize>> {
+    if choices.is_empty() {
+        return Ok(None);
+    }
+
+    let term = Term::stderr();
+    let rows = min(PICKER_ROWS, choices.len());
+    let mut selected = 0;
+    let mut top = 0;
+    term.write_line(prompt)?;
+    loop {
+        for (index, choice) in choices.iter().enumerate().skip(top).take(rows) {
+            if index == selected {
+                term.write_line(&format!("{} {}", style(">").cyan(), style(choice).bold()))?;
+            } else {
+                term.write_line(&format!("  {}", choice))?;
+            }
+        }
+
+        let key = term.read_key()?;
+        term.clear_last_lines(rows)?;
+        match key {
+            Key::ArrowUp | Key::Char('k') if selected > 0 => selected -= 1,
+            Key::ArrowDown | Key::Char('j') if selected + 1 < choices.len() => selected += 1,
+            Key::Enter => {
+                term.clear_last_lines(1)?;
+                return Ok(Some(selected));
+            }
+            Key::Escape | Key::Char('q') => {
+                term.clear_last_lines(1)?;
+                return Ok(None);
+            }
+            _ => {}
+        }
+
+        if selected < top {
+            top = selected;
+        } else if selected >= top + rows {
+            top = selected + 1 - rows;
+        }
+    }
+}
+
 /// Constructs a command-line progress spinner with the specified "message"
-/// string. The spinner is ticked by default every 20ms.
+/// string. The spinner is ticked by default every 20ms. (As with `progress_bar`, the
+/// message is only displayed once, as a line of status, in terse mode.)
 pub fn progress_spinner(message: &str) -> ProgressBar {
+    match progress_mode() {
+        ProgressMode::Quiet => return ProgressBar::hidden(),
+        ProgressMode::Terse => {
+            eprintln!("{}", message);
+            return ProgressBar::hidden();
+        }
+        ProgressMode::Animated => {}
+    }
+
     // ⠋ Fetching public registry: https://nodejs.org/dist/index.json
+    check_colors();
     let spinner = ProgressBar::new_spinner();
 
     spinner.set_message(message);
@@ -143,3 +536,38 @@ pub fn progress_spinner(message: &str) -> ProgressBar {
 
     spinner
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_format_progress() {
+        assert_eq!(
+            format_progress(Action::Fetching, "v10.15.3", 13_200_000, 29_100_000),
+            "Fetching v10.15.3: 45% (13.2/29.1 MB)"
+        );
+        assert_eq!(
+            format_progress(Action::Fetching, "v10.15.3", 29_100_000, 29_100_000),
+            "Fetching v10.15.3: 100% (29.1/29.1 MB)"
+        );
+    }
+
+    #[test]
+    fn test_format_columns() {
+        let rows = vec![
+            vec!["v10.12.0".to_string(), "2018-10-10".to_string(), "".to_string()],
+            vec![
+                "v8.12.0".to_string(),
+                "2018-09-10".to_string(),
+                "carbon".to_string(),
+            ],
+        ];
+        assert_eq!(
+            format_columns(&rows),
+            "v10.12.0  2018-10-10\nv8.12.0   2018-09-10  carbon"
+        );
+        assert_eq!(format_columns(&[]), "");
+    }
+}
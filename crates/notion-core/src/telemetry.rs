@@ -0,0 +1,348 @@
+//! Anonymous usage telemetry, which is off unless the user turns it on with
+//! `notion telemetry on`.
+//!
+//! Each command (or shim) that runs while it is on adds one record to a local queue:
+//! the name of the command, the tool versions it fetched, how long it took, and its
+//! exit and error codes. Records never include paths, package names, arguments, or
+//! anything else from the user's projects. Once enough records are queued, they are
+//! uploaded in a background process to the endpoint of the `telemetry.endpoint`
+//! setting; without one, they are only kept locally (up to `MAX_QUEUED` of them), where
+//! `notion telemetry status` shows them.
+
+use std::env as std_env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use config::Config;
+use env;
+use fs::{ensure_containing_dir_exists, lock, read_file_opt, write_atomic};
+use http;
+use log;
+use notion_fail::{ExitCode, Fallible, NotionError, NotionFail, ResultExt};
+use path;
+use session::ActivityKind;
+
+/// The number of queued records that starts an upload.
+const BATCH_SIZE: usize = 20;
+
+/// The most records kept in the queue: once it is full, the oldest are dropped.
+const MAX_QUEUED: usize = 1000;
+
+lazy_static! {
+    /// The tool versions fetched by this process, for its record.
+    static ref FETCHED: Mutex<Vec<Fetch>> = Mutex::new(vec![]);
+}
+
+/// A tool version a command fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fetch {
+    pub tool: String,
+    pub version: String,
+}
+
+/// What one command reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When the command finished, in seconds since the epoch.
+    pub timestamp: u64,
+    /// The command, like `fetch`, or the tool a shim ran, like `node`.
+    pub command: String,
+    pub fetched: Vec<Fetch>,
+    /// How long the command took, in milliseconds.
+    pub duration: u64,
+    pub exit_code: i32,
+    /// The code of the error the command failed with, if any (see `ExitCode::error_code`).
+    pub error: Option<String>,
+    /// The operating system, like `linux`.
+    pub os: String,
+}
+
+/// Thrown when the telemetry endpoint refuses an upload.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not upload telemetry to {}: {}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct UploadError {
+    pub(crate) url: String,
+    pub(crate) error: String,
+}
+
+/// Returns whether the user has turned telemetry on.
+pub fn enabled() -> bool {
+    path::telemetry_consent_file().map_or(false, |file| file.is_file())
+}
+
+/// Turns telemetry on.
+pub fn enable() -> Fallible<()> {
+    write_atomic(path::telemetry_consent_file()?, "")
+}
+
+/// Turns telemetry off, discarding the records not uploaded yet.
+pub fn disable() -> Fallible<()> {
+    for file in &[path::telemetry_consent_file()?, path::telemetry_queue_file()?] {
+        if let Err(error) = fs::remove_file(file) {
+            if error.kind() != io::ErrorKind::NotFound {
+                return Err(error).unknown();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The state of telemetry, for `notion telemetry status`.
+pub struct Status {
+    pub enabled: bool,
+    /// The records not uploaded yet.
+    pub queued: Vec<Record>,
+    pub endpoint: Option<String>,
+}
+
+/// Reports the state of telemetry.
+pub fn status(config: &Config) -> Fallible<Status> {
+    Ok(Status {
+        enabled: enabled(),
+        queued: queued()?,
+        endpoint: endpoint(config),
+    })
+}
+
+/// The configured endpoint, if any.
+fn endpoint(config: &Config) -> Option<String> {
+    config
+        .telemetry
+        .as_ref()
+        .and_then(|telemetry| telemetry.endpoint.clone())
+}
+
+/// Reads the queued records. Lines that can't be read as records (left by a crash, or
+/// a newer version of Notion) are skipped.
+fn queued() -> Fallible<Vec<Record>> {
+    let src = read_file_opt(&path::telemetry_queue_file()?)
+        .unknown()?
+        .unwrap_or_default();
+    Ok(parse_queue(&src))
+}
+
+fn parse_queue(src: &str) -> Vec<Record> {
+    src.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn write_queue(records: &[Record]) -> Fallible<()> {
+    let mut src = String::new();
+    for record in records {
+        src.push_str(&serde_json::to_string(record).unknown()?);
+        src.push('\n');
+    }
+    write_atomic(path::telemetry_queue_file()?, src)
+}
+
+/// Notes that this process fetched a tool version, for its record.
+pub(crate) fn note_fetched(tool: &str, version: &str) {
+    if let Ok(mut fetched) = FETCHED.lock() {
+        fetched.push(Fetch {
+            tool: tool.to_string(),
+            version: version.to_string(),
+        });
+    }
+}
+
+/// Keeps track of a command for its record, from the start of its session.
+pub(crate) struct Recorder {
+    started: Instant,
+    command: Option<ActivityKind>,
+    error: Option<&'static str>,
+}
+
+impl Recorder {
+    pub(crate) fn new() -> Self {
+        Recorder {
+            started: Instant::now(),
+            command: None,
+            error: None,
+        }
+    }
+
+    /// Notes an activity the session started. The command is the first activity
+    /// more specific than running `notion` at all.
+    pub(crate) fn start(&mut self, activity_kind: ActivityKind) {
+        match (self.command, activity_kind) {
+            (None, _) | (Some(ActivityKind::Notion), _) => self.command = Some(activity_kind),
+            _ => {}
+        }
+    }
+
+    /// Notes the error the command failed with.
+    pub(crate) fn error(&mut self, error: &NotionError) {
+        self.error = Some(error.error_code());
+    }
+
+    /// Queues the command's record, if telemetry is on, and starts an upload if enough
+    /// records are queued. Telemetry is a side channel, so failing to record never
+    /// fails the command. The `notion telemetry` commands aren't recorded, so that
+    /// uploads don't queue records of their own.
+    pub(crate) fn finish(&self, exit_code: i32, config: Option<&Config>) {
+        if !enabled() || self.command == Some(ActivityKind::Telemetry) {
+            return;
+        }
+        let record = self.record(exit_code);
+        let _ = queue(record).map(|queued| {
+            let endpoint = config.and_then(endpoint);
+            if queued >= BATCH_SIZE && endpoint.is_some() && !env::offline() {
+                start_upload();
+            }
+        });
+    }
+
+    fn record(&self, exit_code: i32) -> Record {
+        let elapsed = self.started.elapsed();
+        let fetched = FETCHED
+            .lock()
+            .map(|fetched| fetched.clone())
+            .unwrap_or_default();
+        Record {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or(0),
+            command: self
+                .command
+                .map_or("notion".to_string(), |command| command.to_string()),
+            fetched,
+            duration: elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64,
+            exit_code,
+            error: if exit_code == ExitCode::Success as i32 {
+                None
+            } else {
+                self.error.map(|error| error.to_string())
+            },
+            os: std_env::consts::OS.to_string(),
+        }
+    }
+}
+
+/// Adds a record to the queue, returning the number of records queued. The queue is
+/// locked while the record is appended, so that a concurrent trim or upload (which
+/// rewrites the queue) never drops it.
+fn queue(record: Record) -> Fallible<usize> {
+    let file = path::telemetry_queue_file()?;
+    ensure_containing_dir_exists(&file)?;
+    let mut line = serde_json::to_string(&record).unknown()?;
+    line.push('\n');
+    let _lock = lock(&file, "queueing telemetry")?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .and_then(|mut queue| queue.write_all(line.as_bytes()))
+        .unknown()?;
+
+    let records = queued()?;
+    if records.len() > MAX_QUEUED {
+        write_queue(&records[records.len() - MAX_QUEUED..])?;
+        return Ok(MAX_QUEUED);
+    }
+    Ok(records.len())
+}
+
+/// Starts an upload of the queued records in a background process, so that no
+/// command waits for the network.
+fn start_upload() {
+    let notion = match path::notion_file() {
+        Ok(notion) => notion,
+        Err(_) => return,
+    };
+    log::debug(|| "uploading telemetry in the background".to_string());
+    let _ = Command::new(notion)
+        .args(&["telemetry", "flush"])
+        .env("NOTION_NO_UPDATE_CHECK", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// Uploads the queued records to the configured endpoint, as a JSON array, and removes
+/// them from the queue. Returns the number of records uploaded, which is zero when
+/// telemetry is off or no endpoint is configured.
+pub fn flush(config: &Config) -> Fallible<usize> {
+    let url = match endpoint(config) {
+        Some(ref url) if enabled() => url.clone(),
+        _ => return Ok(0),
+    };
+    let file = path::telemetry_queue_file()?;
+    // Only one upload runs at a time. The queue itself is only locked while it is read
+    // and rewritten, so that commands queueing records never wait for the network.
+    let _upload = lock(&file.with_extension("upload"), "uploading telemetry")?;
+    let records = {
+        let _lock = lock(&file, "reading the telemetry queue")?;
+        queued()?
+    };
+    if records.is_empty() {
+        return Ok(0);
+    }
+
+    http::ensure_online(&url)?;
+    let body = serde_json::to_string(&records).unknown()?;
+    let response = http::post_json(&url, &body, config).unknown()?;
+    http::ensure_not_throttled(&url, &response)?;
+    if !response.status().is_success() {
+        throw!(UploadError {
+            url,
+            error: response.status().to_string(),
+        });
+    }
+
+    // Records queued while the upload was under way stay for the next one.
+    let _lock = lock(&file, "trimming the telemetry queue")?;
+    let remaining = queued()?;
+    write_queue(&remaining[::std::cmp::min(records.len(), remaining.len())..])?;
+    Ok(records.len())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{parse_queue, Fetch, Record, Recorder};
+    use serde_json;
+    use session::ActivityKind;
+
+    #[test]
+    fn test_recorder() {
+        let mut recorder = Recorder::new();
+        recorder.start(ActivityKind::Notion);
+        recorder.start(ActivityKind::Fetch);
+        recorder.start(ActivityKind::Node);
+        let record = recorder.record(0);
+        assert_eq!(record.command, "fetch");
+        assert_eq!(record.exit_code, 0);
+        assert_eq!(record.error, None);
+    }
+
+    #[test]
+    fn test_parse_queue() {
+        let record = Record {
+            timestamp: 1545000000,
+            command: "install".to_string(),
+            fetched: vec![Fetch {
+                tool: "node".to_string(),
+                version: "10.15.3".to_string(),
+            }],
+            duration: 2500,
+            exit_code: 5,
+            error: Some("NETWORK_ERROR".to_string()),
+            os: "linux".to_string(),
+        };
+        let src = format!(
+            "{}\n{{\"truncated\n{}\n",
+            serde_json::to_string(&record).unwrap(),
+            serde_json::to_string(&record).unwrap()
+        );
+        assert_eq!(parse_queue(&src), vec![record.clone(), record]);
+    }
+}
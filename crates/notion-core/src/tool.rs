@@ -7,18 +7,26 @@ use std::marker::Sized;
 use std::path::Path;
 use std::process::Command;
 
-use env;
+use env::{self, ToolEnv, ToolKind};
+use manifest::Manifest;
 use notion_fail::{ExitCode, FailExt, Fallible, NotionError, NotionFail};
 use path;
-use session::{ActivityKind, Session};
+use platform::{self, Platform};
+use session::{check_implicit_fetch, ActivityKind, Session};
+use signal;
 use style;
+use telemetry;
+use version::VersionSpec;
+use which::{self, Provider};
 
 fn display_error(err: &NotionError) {
     if err.is_user_friendly() {
         style::display_error(style::ErrorContext::Shim, err);
+        style::display_suggestion(err);
     } else {
         style::display_unknown_error(style::ErrorContext::Shim, err);
     }
+    style::display_error_details(err);
 }
 
 #[derive(Debug, Fail, NotionFail)]
@@ -53,9 +61,29 @@ impl ToolUnimplementedError {
     }
 }
 
+/// Returns whether a shim can delegate from the platform cache without loading the
+/// session, which only the session can observe a run with: its events are the only
+/// ones written to the events file, it queues the run's telemetry record, and it
+/// prints the profile of the run. The environment is read with `var`.
+fn can_skip_session<F>(var: F, telemetry_enabled: bool) -> bool
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    env::events_file_in(&var).is_none() && !telemetry_enabled && !env::profile_in(&var)
+}
+
 /// Represents a command-line tool that Notion shims delegate to.
 pub trait Tool: Sized {
     fn launch() -> ! {
+        // The hot path: with the versions selected for the current directory cached,
+        // delegate right away, without loading the session.
+        let cached = platform::current();
+        if can_skip_session(|name| var_os(name), telemetry::enabled()) {
+            if let Some(tool) = cached.as_ref().and_then(Self::from_platform) {
+                tool.exec_without_session();
+            }
+        }
+
         let mut session = match Session::new() {
             Ok(session) => session,
             Err(err) => {
@@ -68,6 +96,10 @@ pub trait Tool: Sized {
 
         match Self::new(&mut session) {
             Ok(tool) => {
+                if cached.is_none() {
+                    // the cache is only an optimization, so failing to save it is harmless
+                    let _ = session.save_platform();
+                }
                 tool.exec(session);
             }
             Err(err) => {
@@ -81,8 +113,14 @@ pub trait Tool: Sized {
     /// Constructs a new instance.
     fn new(&mut Session) -> Fallible<Self>;
 
-    /// Constructs a new instance, using the specified command-line and `PATH` variable.
-    fn from_components(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Self;
+    /// Constructs a new instance from the cached versions selected for the current
+    /// directory, if they are enough to run this tool.
+    fn from_platform(_platform: &Platform) -> Option<Self> {
+        None
+    }
+
+    /// Constructs a new instance, using the specified command-line and environment.
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self;
 
     /// Extracts the `Command` from this tool.
     fn command(self) -> Command;
@@ -90,17 +128,15 @@ pub trait Tool: Sized {
     /// Delegates the current process to this tool.
     fn exec(self, mut session: Session) -> ! {
         let mut command = self.command();
-        let status = command.status();
+        let status = signal::run(&mut command);
         match status {
             Ok(status) if status.success() => {
                 session.add_event_end(ActivityKind::Tool, ExitCode::Success);
                 session.exit(ExitCode::Success);
             }
             Ok(status) => {
-                // ISSUE (#36): if None, in unix, find out the signal
-                let code = status.code().unwrap_or(1);
-                session.add_event_tool_end(ActivityKind::Tool, code);
-                session.exit_tool(code);
+                session.add_event_tool_end(ActivityKind::Tool, signal::exit_code(&status));
+                session.exit_like_tool(&status);
             }
             Err(err) => {
                 let notion_err = err.with_context(BinaryExecError::from_io_error);
@@ -110,6 +146,18 @@ pub trait Tool: Sized {
             }
         }
     }
+
+    /// Delegates the current process to this tool, without a session to record events.
+    fn exec_without_session(self) -> ! {
+        let mut command = self.command();
+        match signal::run(&mut command) {
+            Ok(status) => signal::exit_like(&status),
+            Err(err) => {
+                display_error(&err.with_context(BinaryExecError::from_io_error));
+                ExitCode::ExecutionFailure.exit();
+            }
+        }
+    }
 }
 
 /// Represents a delegated script.
@@ -124,13 +172,19 @@ pub struct Node(Command);
 /// Represents a Yarn executable.
 pub struct Yarn(Command);
 
+/// Represents an npm executable (`npm` or `npx`).
+pub struct Npm(Command);
+
+/// Represents a pnpm executable (`pnpm` or `pnpx`).
+pub struct Pnpm(Command);
+
 #[cfg(windows)]
 impl Tool for Script {
     fn new(session: &mut Session) -> Fallible<Self> {
         throw!(ToolUnimplementedError::new())
     }
 
-    fn from_components(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Self {
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
         // The best way to launch a script in Windows is to use `cmd.exe`
         // as the executable and pass `"/C"` followed by the name of the
         // script and then its arguments. Unfortunately, the docs aren't
@@ -141,7 +195,7 @@ impl Tool for Script {
         command.arg("/C");
         command.arg(exe);
         command.args(args);
-        command.env("PATH", path_var);
+        env.apply(&mut command);
         Script(command)
     }
 
@@ -150,10 +204,10 @@ impl Tool for Script {
     }
 }
 
-fn command_for(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Command {
+fn command_for(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Command {
     let mut command = Command::new(exe);
     command.args(args);
-    command.env("PATH", path_var);
+    env.apply(&mut command);
     command
 }
 
@@ -163,8 +217,8 @@ impl Tool for Script {
         throw!(ToolUnimplementedError::new())
     }
 
-    fn from_components(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Self {
-        Script(command_for(exe, args, path_var))
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
+        Script(command_for(exe, args, env))
     }
 
     fn command(self) -> Command {
@@ -178,45 +232,42 @@ impl Tool for Binary {
 
         let mut args = args_os();
         let exe = arg0(&mut args)?;
-        let current_path = var_os("PATH").unwrap_or(OsString::new());
-
-        if session.in_pinned_project() {
-            // we are in a pinned Node project
-            let project = session.project().unwrap();
-
-            // if this project has this as a local executable, use that
-            if project.has_direct_bin(&exe)? {
-                // use the full path to the file
-                let mut path_to_bin = project.local_bin_dir();
-                path_to_bin.push(&exe);
-                return Ok(Self::from_components(
-                    &path_to_bin.as_os_str(),
-                    args,
-                    &current_path,
-                ));
-            }
-        }
 
-        // if node is configured with Notion (`notion use` or notion config), use the global executable
-        if let Some(version) = session.current_node()? {
-            // use the full path to the binary
-            let mut third_p_bin_dir = path::node_version_3p_bin_dir(&version.to_string())?;
-            third_p_bin_dir.push(&exe);
-            return Ok(Self::from_components(
-                &third_p_bin_dir.as_os_str(),
-                args,
-                &current_path,
-            ));
+        // Find the project dependency or global package that provides the binary,
+        // following the same rules as `notion which`.
+        let resolution = which::resolve(session, &exe)?;
+
+        // Package binaries are usually Node scripts, so run them with the selected
+        // Node (fetching it first, if need be) ahead of everything else on the PATH.
+        // Packages installed by `notion install` always run with the Node they were
+        // installed with instead. Without either, remove the shims so that the system's
+        // Node runs them.
+        let node = match resolution.provider {
+            Provider::Package(_, ref node) => {
+                let installed = session.catalog()?.node.contains(node);
+                if !installed {
+                    check_implicit_fetch(session.config()?, "Node", "node", node)?;
+                    session.fetch_node(&VersionSpec::exact(node))?;
+                }
+                Some(node.clone())
+            }
+            _ => session.current_node()?,
         };
-
-        // otherwise use system node
-        // (remove notion shims and bins)
-        let path_for_system_node = env::path_for_system_node();
-        Ok(Self::from_components(&exe, args, &path_for_system_node))
+        let node = node.map(|version| version.to_string());
+        let tool_env = env::tool_env(
+            ToolKind::Node,
+            node.as_ref().map(|version| &version[..]),
+            None,
+            None,
+        )?;
+        match resolution.path {
+            Some(path) => Ok(Self::from_components(path.as_os_str(), args, &tool_env)),
+            None => Ok(Self::from_components(&exe, args, &tool_env)),
+        }
     }
 
-    fn from_components(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Self {
-        Binary(command_for(exe, args, path_var))
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
+        Binary(command_for(exe, args, env))
     }
 
     fn command(self) -> Command {
@@ -243,11 +294,24 @@ fn arg0(args: &mut ArgsOs) -> Fallible<OsString> {
 
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "No {} version selected", tool)]
-#[notion_fail(code = "NoVersionMatch")]
+#[notion_fail(
+    code = "NoVersionMatch",
+    suggest_with = "suggest",
+    error_code = "NO_VERSION_SELECTED"
+)]
 struct NoGlobalError {
     tool: String,
 }
 
+impl NoGlobalError {
+    fn suggest(&self) -> Option<String> {
+        Some(format!(
+            "select a default with `notion default {} <version>`",
+            self.tool.to_lowercase()
+        ))
+    }
+}
+
 impl Tool for Node {
     fn new(session: &mut Session) -> Fallible<Self> {
         session.add_event_start(ActivityKind::Node);
@@ -261,12 +325,19 @@ impl Tool for Node {
                 tool: "Node".to_string()
             });
         };
-        let path_var = env::path_for_installed_node(&version.to_string());
-        Ok(Self::from_components(&exe, args, &path_var))
+        let tool_env = env::tool_env(ToolKind::Node, Some(&version.to_string()), None, None)?;
+        Ok(Self::from_components(&exe, args, &tool_env))
+    }
+
+    fn from_platform(platform: &Platform) -> Option<Self> {
+        let mut args = args_os();
+        let exe = arg0(&mut args).ok()?;
+        let tool_env = env::tool_env(ToolKind::Node, Some(&platform.node), None, None).ok()?;
+        Some(Self::from_components(&exe, args, &tool_env))
     }
 
-    fn from_components(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Self {
-        Node(command_for(exe, args, path_var))
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
+        Node(command_for(exe, args, env))
     }
 
     fn command(self) -> Command {
@@ -280,6 +351,24 @@ impl Tool for Yarn {
 
         let mut args = args_os();
         let exe = arg0(&mut args)?;
+
+        if session.config()?.defer_to_corepack() {
+            return Ok(Yarn(corepack(session, &exe, args)?));
+        }
+
+        // A project on Yarn 2 or later runs the release it vendors, so no other Yarn
+        // has to be fetched for it.
+        if let Some(release) = session.vendored_yarn()? {
+            let node_version = if let Some(version) = session.current_node()? {
+                version.to_string()
+            } else {
+                throw!(NoGlobalError {
+                    tool: "Node".to_string()
+                });
+            };
+            return vendored_yarn(&node_version, &release, args);
+        }
+
         let version = if let Some(version) = session.current_yarn()? {
             version
         } else {
@@ -287,15 +376,221 @@ impl Tool for Yarn {
                 tool: "Yarn".to_string()
             });
         };
-        let path_var = env::path_for_installed_node(&version.to_string());
-        Ok(Self::from_components(&exe, args, &path_var))
+        let node_version = session.current_node()?.map(|version| version.to_string());
+        let tool_env = env::tool_env(
+            ToolKind::Yarn,
+            node_version.as_ref().map(|version| &version[..]),
+            Some(&version.to_string()),
+            None,
+        )?;
+        Ok(Self::from_components(&exe, args, &tool_env))
+    }
+
+    fn from_platform(platform: &Platform) -> Option<Self> {
+        let mut args = args_os();
+        let exe = arg0(&mut args).ok()?;
+        if let Some(ref release) = platform.yarn_path {
+            return vendored_yarn(&platform.node, Path::new(release), args).ok();
+        }
+        let yarn = platform.yarn.as_ref()?;
+        let tool_env =
+            env::tool_env(ToolKind::Yarn, Some(&platform.node), Some(yarn), None).ok()?;
+        Some(Self::from_components(&exe, args, &tool_env))
     }
 
-    fn from_components(exe: &OsStr, args: ArgsOs, path_var: &OsStr) -> Self {
-        Yarn(command_for(exe, args, path_var))
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
+        Yarn(command_for(exe, args, env))
     }
 
     fn command(self) -> Command {
         self.0
     }
 }
+
+/// Thrown when the package managers are left to corepack, but the selected Node
+/// doesn't include it.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Node v{} does not include corepack", version)]
+#[notion_fail(
+    code = "ExecutableNotFound",
+    suggestion = "Select Node 16.9.0 or later, or set `corepack = false` in the [behavior] section of Notion's config."
+)]
+struct CorepackNotFoundError {
+    version: String,
+}
+
+/// Builds the command that runs a package manager executable through the corepack of
+/// the selected Node, which picks the version of the package manager itself.
+fn corepack(session: &mut Session, exe: &OsStr, args: ArgsOs) -> Fallible<Command> {
+    let node_version = if let Some(version) = session.current_node()? {
+        version.to_string()
+    } else {
+        throw!(NoGlobalError {
+            tool: "Node".to_string()
+        });
+    };
+    let corepack = path::node_version_bin_dir(&node_version)?.join(if cfg!(windows) {
+        "corepack.cmd"
+    } else {
+        "corepack"
+    });
+    if !corepack.is_file() {
+        throw!(CorepackNotFoundError {
+            version: node_version,
+        });
+    }
+
+    let mut command = Command::new(corepack);
+    command.arg(exe);
+    command.args(args);
+    env::tool_env(ToolKind::Node, Some(&node_version), None, None)?.apply(&mut command);
+    Ok(command)
+}
+
+/// Runs a release of Yarn that a project vendors, which is a single script, with the
+/// specified version of Node.
+fn vendored_yarn(node_version: &str, release: &Path, args: ArgsOs) -> Fallible<Yarn> {
+    let tool_env = env::tool_env(ToolKind::Node, Some(node_version), None, None)?;
+    let mut command = Command::new(env::node_executable(node_version)?);
+    command.arg(release);
+    command.args(args);
+    tool_env.apply(&mut command);
+    Ok(Yarn(command))
+}
+
+impl Tool for Npm {
+    fn new(session: &mut Session) -> Fallible<Self> {
+        session.add_event_start(ActivityKind::Npm);
+
+        let mut args = args_os();
+        let exe = arg0(&mut args)?;
+        let node_version = if let Some(version) = session.current_node()? {
+            version
+        } else {
+            throw!(NoGlobalError {
+                tool: "Node".to_string()
+            });
+        };
+
+        // prefer the npm pinned by the project over the one bundled with Node
+        let npm_version = session.current_npm()?.map(|version| version.to_string());
+        let tool_env = env::tool_env(
+            ToolKind::Npm,
+            Some(&node_version.to_string()),
+            None,
+            npm_version.as_ref().map(|version| &version[..]),
+        )?;
+        Ok(Self::from_components(&exe, args, &tool_env))
+    }
+
+    fn from_platform(platform: &Platform) -> Option<Self> {
+        let mut args = args_os();
+        let exe = arg0(&mut args).ok()?;
+        let tool_env = env::tool_env(
+            ToolKind::Npm,
+            Some(&platform.node),
+            None,
+            platform.npm.as_ref().map(|npm| &npm[..]),
+        ).ok()?;
+        Some(Self::from_components(&exe, args, &tool_env))
+    }
+
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
+        Npm(command_for(exe, args, env))
+    }
+
+    fn command(self) -> Command {
+        self.0
+    }
+}
+
+/// Thrown when a pnpm executable is not provided by the pinned pnpm package.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "pnpm {} does not provide the `{}` executable", version, exe)]
+#[notion_fail(code = "ExecutableNotFound")]
+struct PnpmBinNotFoundError {
+    version: String,
+    exe: String,
+}
+
+impl Tool for Pnpm {
+    fn new(session: &mut Session) -> Fallible<Self> {
+        session.add_event_start(ActivityKind::Pnpm);
+
+        let mut args = args_os();
+        let exe = arg0(&mut args)?;
+        if session.config()?.defer_to_corepack() {
+            return Ok(Pnpm(corepack(session, &exe, args)?));
+        }
+
+        let node_version = if let Some(version) = session.current_node()? {
+            version
+        } else {
+            throw!(NoGlobalError {
+                tool: "Node".to_string()
+            });
+        };
+        let version = if let Some(version) = session.current_pnpm()? {
+            version
+        } else {
+            throw!(NoGlobalError {
+                tool: "pnpm".to_string()
+            });
+        };
+
+        // pnpm's executables are Node scripts, declared in the `bin` section of its manifest
+        let package_dir = path::pnpm_version_dir(&version.to_string())?;
+        let exe_name = exe.to_string_lossy().into_owned();
+        let script = match Manifest::for_dir(&package_dir)?.bin.get(&exe_name) {
+            Some(script) => package_dir.join(script),
+            None => {
+                throw!(PnpmBinNotFoundError {
+                    version: version.to_string(),
+                    exe: exe_name,
+                });
+            }
+        };
+
+        let tool_env = env::tool_env(ToolKind::Pnpm, Some(&node_version.to_string()), None, None)?;
+        let mut command = Command::new("node");
+        command.arg(script);
+        command.args(args);
+        tool_env.apply(&mut command);
+        Ok(Pnpm(command))
+    }
+
+    fn from_components(exe: &OsStr, args: ArgsOs, env: &ToolEnv) -> Self {
+        Pnpm(command_for(exe, args, env))
+    }
+
+    fn command(self) -> Command {
+        self.0
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::can_skip_session;
+    use std::ffi::OsString;
+
+    fn only(name: &'static str, value: &'static str) -> impl Fn(&str) -> Option<OsString> {
+        move |var: &str| if var == name { Some(OsString::from(value)) } else { None }
+    }
+
+    #[test]
+    fn test_can_skip_session() {
+        assert!(can_skip_session(|_| None, false));
+
+        // even with the platform cached, the shim loads the session, whose events are
+        // appended to the events file, which queues the telemetry record of the run,
+        // and which prints the profile of the run
+        assert!(!can_skip_session(only("NOTION_EVENTS_FILE", "/tmp/events.jsonl"), false));
+        assert!(!can_skip_session(|_| None, true));
+        assert!(!can_skip_session(only("NOTION_PROFILE", "1"), false));
+
+        // variables set but empty (or `0`, for flags) turn nothing on
+        assert!(can_skip_session(only("NOTION_EVENTS_FILE", ""), false));
+        assert!(can_skip_session(only("NOTION_PROFILE", "0"), false));
+    }
+}
@@ -0,0 +1,65 @@
+//! Provides the `Trace` type, which records the chain of decisions behind the
+//! selection of a tool version, so that users can find out why a version was picked.
+
+use std::cell::RefCell;
+
+use log;
+
+/// A record of the decisions made while selecting a tool version, in order.
+pub struct Trace {
+    steps: Option<RefCell<Vec<String>>>,
+}
+
+impl Trace {
+    /// Constructs a new, empty `Trace` that records decisions.
+    pub fn new() -> Trace {
+        Trace {
+            steps: Some(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Constructs a `Trace` that discards decisions, for resolutions nobody asked
+    /// to explain.
+    pub fn disabled() -> Trace {
+        Trace { steps: None }
+    }
+
+    /// Records a decision, which is also logged at the debug level. The description is
+    /// only produced if the trace is enabled or the decision is logged.
+    pub fn record<F: FnOnce() -> String>(&self, step: F) {
+        match self.steps {
+            Some(ref steps) => {
+                let step = step();
+                log::debug(|| step.clone());
+                steps.borrow_mut().push(step);
+            }
+            None => log::debug(step),
+        }
+    }
+
+    /// Returns the decisions recorded so far, in order.
+    pub fn steps(&self) -> Vec<String> {
+        self.steps
+            .as_ref()
+            .map(|steps| steps.borrow().clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use trace::Trace;
+
+    #[test]
+    fn test_record() {
+        let trace = Trace::new();
+        trace.record(|| "first".to_string());
+        trace.record(|| "second".to_string());
+        assert_eq!(trace.steps(), vec!["first".to_string(), "second".to_string()]);
+
+        let disabled = Trace::disabled();
+        disabled.record(|| panic!("a disabled trace shouldn't describe its steps"));
+        assert!(disabled.steps().is_empty());
+    }
+}
@@ -0,0 +1,386 @@
+//! Provides the means of updating Notion itself to its latest release, for
+//! `notion self-update`.
+//!
+//! Releases are published on the release channel (the GitHub releases of the Notion
+//! repository, unless the `NOTION_UPDATE_URL` environment variable names another
+//! source), each with one executable per platform and a `SHASUMS256.txt` file listing
+//! their checksums.
+//!
+//! After a successful command, `notify` tells whether the last check found a newer
+//! release, and starts a new check in the background once the last one is older than
+//! the `updates.interval` setting, so that no command waits for the network.
+
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use reqwest;
+use semver::Version;
+use tempfile::NamedTempFile;
+
+use config::Config;
+use distro::checksum;
+use distro::error::ChecksumMismatchError;
+use env as notion_env;
+use fs::ensure_containing_dir_exists;
+use http;
+use log;
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+use path;
+
+/// The latest release of Notion, as the GitHub API describes it.
+const PUBLIC_LATEST_RELEASE: &'static str =
+    "https://api.github.com/repos/notion-cli/notion/releases/latest";
+
+/// Thrown when the release channel could not be read.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not check for a new release of Notion at {}\n{}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct ReleaseCheckError {
+    url: String,
+    error: String,
+}
+
+impl ReleaseCheckError {
+    fn for_url(url: String) -> impl FnOnce(&reqwest::Error) -> ReleaseCheckError {
+        move |error| ReleaseCheckError {
+            url,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Thrown when the latest release has no executable for the current platform.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Notion {} has no release for this platform ({})", version, artifact)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoArtifactError {
+    version: String,
+    artifact: String,
+}
+
+/// Thrown when the new executable could not be downloaded.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not download {}\n{}", url, error)]
+#[notion_fail(code = "NetworkError")]
+pub(crate) struct ArtifactFetchError {
+    url: String,
+    error: String,
+}
+
+/// Thrown when the running executable could not be replaced.
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "Could not replace {}\n{}", file, error)]
+#[notion_fail(code = "FileSystemError")]
+pub(crate) struct ReplaceError {
+    file: String,
+    error: String,
+}
+
+impl ReplaceError {
+    fn for_file(file: &Path) -> impl FnOnce(&io::Error) -> ReplaceError {
+        let file = file.to_string_lossy().to_string();
+        move |error| ReplaceError {
+            file,
+            error: error.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawRelease {
+    tag_name: String,
+    assets: Vec<RawAsset>,
+}
+
+#[derive(Deserialize)]
+struct RawAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// A release of Notion, with the URL of its executable for the current platform.
+pub struct Release {
+    pub version: Version,
+    pub url: String,
+}
+
+impl Release {
+    /// Returns whether this release is newer than the specified version of Notion.
+    pub fn is_newer_than(&self, current: &Version) -> bool {
+        self.version > *current
+    }
+}
+
+/// Returns the URL the latest release is described at.
+fn release_url() -> String {
+    notion_env::update_url().unwrap_or_else(|| PUBLIC_LATEST_RELEASE.to_string())
+}
+
+/// Returns the name of the executable published for the current platform, such as
+/// `notion-0.1.3-linux-x64` or `notion-0.1.3-win-x64.exe`.
+fn artifact_name(version: &Version) -> String {
+    format!(
+        "notion-{}-{}-{}{}",
+        version,
+        path::OS,
+        path::ARCH,
+        env::consts::EXE_SUFFIX
+    )
+}
+
+/// Finds the latest release on the release channel.
+pub fn latest(config: &Config) -> Fallible<Release> {
+    let url = release_url();
+    http::ensure_online(&url)?;
+    log::info(|| format!("checking for a new release at {}", url));
+
+    let mut response = http::get(&url, config).with_context(ReleaseCheckError::for_url(url.clone()))?;
+    if !response.status().is_success() {
+        throw!(ReleaseCheckError {
+            url,
+            error: format!("HTTP failure ({})", response.status()),
+        });
+    }
+    let raw: RawRelease = response
+        .json()
+        .with_context(ReleaseCheckError::for_url(url.clone()))?;
+
+    let version = Version::parse(raw.tag_name.trim_left_matches('v')).unknown()?;
+    let artifact = artifact_name(&version);
+    match raw.assets.into_iter().find(|asset| asset.name == artifact) {
+        Some(asset) => {
+            record_latest(&version)?;
+            Ok(Release {
+                version,
+                url: asset.browser_download_url,
+            })
+        }
+        None => throw!(NoArtifactError {
+            version: version.to_string(),
+            artifact,
+        }),
+    }
+}
+
+/// Records the latest release found on the release channel, for `notify`. The time of
+/// the check is the modification time of the file.
+fn record_latest(version: &Version) -> Fallible<()> {
+    let file = path::latest_release_file()?;
+    ensure_containing_dir_exists(&file)?;
+    fs::write(&file, version.to_string()).unknown()
+}
+
+/// Reads the latest release recorded by the last check, and how long ago that was.
+fn recorded_latest() -> Option<(Version, Duration)> {
+    let file = path::latest_release_file().ok()?;
+    let version = Version::parse(fs::read_to_string(&file).ok()?.trim()).ok()?;
+    let modified = fs::metadata(&file).and_then(|metadata| metadata.modified()).ok()?;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::from_secs(0));
+    Some((version, age))
+}
+
+/// Returns whether a check made the specified time ago (if ever) is due to be repeated.
+fn check_due(age: Option<Duration>, interval: Duration) -> bool {
+    age.map_or(true, |age| age >= interval)
+}
+
+/// Returns the release newer than the specified version of Notion that the last check
+/// found, if any, so that a hint can be shown after a command. If the last check is
+/// due to be repeated, `notion self-update --check` is started in the background to
+/// repeat it, printing nothing; a later command shows what it finds.
+pub fn notify(current: &Version, config: &Config) -> Option<Version> {
+    let interval = config.update_check_interval()?;
+    let recorded = recorded_latest();
+    if !notion_env::offline() && check_due(recorded.as_ref().map(|&(_, age)| age), interval) {
+        let latest = recorded.as_ref().map_or(current, |&(ref latest, _)| latest);
+        start_check(latest);
+    }
+    recorded
+        .map(|(latest, _)| latest)
+        .filter(|latest| latest > current)
+}
+
+/// Starts a check for a new release in a background process.
+fn start_check(latest: &Version) {
+    // Restart the clock first, so that the commands run while the check is under way
+    // don't start checks of their own, and a check that fails isn't retried until the
+    // interval is up again.
+    if record_latest(latest).is_err() {
+        return;
+    }
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(_) => return,
+    };
+    log::debug(|| "checking for a new release in the background".to_string());
+    let _ = Command::new(exe)
+        .args(&["self-update", "--check"])
+        .env("NOTION_NO_UPDATE_CHECK", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// Returns the file a replaced executable is moved aside to, on Windows, where an
+/// executable can be renamed but not removed while it runs.
+fn old_file(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    exe.with_file_name(name)
+}
+
+/// Removes the executable left behind by an earlier update, if any.
+pub fn clean_up() -> Fallible<()> {
+    let old = old_file(&env::current_exe().unknown()?);
+    if old.exists() {
+        log::debug(|| format!("removing {}", old.display()));
+        fs::remove_file(&old).with_context(ReplaceError::for_file(&old))?;
+    }
+    Ok(())
+}
+
+/// Downloads the executable of the specified release, verifies it against the
+/// published checksums, and puts it in place of the running executable. The new
+/// executable is downloaded next to the old one, so that the replacement is a single
+/// rename, and a failed download leaves the old executable as it was.
+pub fn install(release: &Release, config: &Config) -> Fallible<PathBuf> {
+    let exe = env::current_exe().unknown()?;
+    let dir = exe.parent().unwrap_or(Path::new("."));
+
+    let expected = checksum::fetch_sha256(&release.url, false, config)?;
+
+    log::info(|| format!("downloading {} to {}", release.url, dir.display()));
+    let download = NamedTempFile::new_in(dir).with_context(ReplaceError::for_file(&exe))?;
+    let mut response = http::get(&release.url, config).with_context(|error| ArtifactFetchError {
+        url: release.url.clone(),
+        error: error.to_string(),
+    })?;
+    if !response.status().is_success() {
+        throw!(ArtifactFetchError {
+            url: release.url.clone(),
+            error: format!("HTTP failure ({})", response.status()),
+        });
+    }
+    // Block to borrow download for file.
+    {
+        let mut file: &File = download.as_file();
+        response
+            .copy_to(&mut file)
+            .with_context(|error| ArtifactFetchError {
+                url: release.url.clone(),
+                error: error.to_string(),
+            })?;
+    }
+
+    let actual = checksum::sha256(File::open(download.path()).unknown()?)?;
+    if actual != expected {
+        throw!(ChecksumMismatchError {
+            file: release.url.clone(),
+            expected,
+            actual,
+        });
+    }
+
+    make_executable(download.as_file())?;
+    replace(download, &exe)?;
+    Ok(exe)
+}
+
+#[cfg(unix)]
+fn make_executable(file: &File) -> Fallible<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    file.set_permissions(fs::Permissions::from_mode(0o755))
+        .unknown()
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &File) -> Fallible<()> {
+    Ok(())
+}
+
+/// Renames the downloaded executable over the running one, which Unix allows.
+#[cfg(unix)]
+fn replace(download: NamedTempFile, exe: &Path) -> Fallible<()> {
+    log::debug(|| format!("moving the new executable to {}", exe.display()));
+    download
+        .persist(exe)
+        .map_err(|error| error.error)
+        .with_context(ReplaceError::for_file(exe))?;
+    Ok(())
+}
+
+/// Moves the running executable aside, since Windows won't overwrite it, and then
+/// renames the download into its place, moving the old executable back if that fails.
+/// The old executable is removed by `clean_up` the next time Notion updates itself.
+#[cfg(not(unix))]
+fn replace(download: NamedTempFile, exe: &Path) -> Fallible<()> {
+    let old = old_file(exe);
+    if old.exists() {
+        fs::remove_file(&old).with_context(ReplaceError::for_file(&old))?;
+    }
+    log::debug(|| format!("moving {} to {}", exe.display(), old.display()));
+    fs::rename(exe, &old).with_context(ReplaceError::for_file(exe))?;
+
+    log::debug(|| format!("moving the new executable to {}", exe.display()));
+    if let Err(error) = download.persist(exe) {
+        let _ = fs::rename(&old, exe);
+        throw!(ReplaceError {
+            file: exe.to_string_lossy().to_string(),
+            error: error.error.to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{artifact_name, check_due, old_file, Release};
+    use path;
+    use semver::Version;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn test_artifact_name() {
+        let name = artifact_name(&Version::parse("0.1.3").unwrap());
+        assert!(name.starts_with(&format!("notion-0.1.3-{}-", path::OS)));
+        if cfg!(windows) {
+            assert!(name.ends_with(".exe"));
+        }
+    }
+
+    #[test]
+    fn test_check_due() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert!(check_due(None, day));
+        assert!(!check_due(Some(Duration::from_secs(60)), day));
+        assert!(check_due(Some(day + Duration::from_secs(1)), day));
+    }
+
+    #[test]
+    fn test_old_file() {
+        assert_eq!(
+            old_file(Path::new("/opt/notion/notion.exe")),
+            Path::new("/opt/notion/notion.exe.old")
+        );
+    }
+
+    #[test]
+    fn test_is_newer_than() {
+        let release = Release {
+            version: Version::parse("0.2.0").unwrap(),
+            url: "https://example.com/notion".to_string(),
+        };
+        assert!(release.is_newer_than(&Version::parse("0.1.2").unwrap()));
+        assert!(!release.is_newer_than(&Version::parse("0.2.0").unwrap()));
+    }
+}
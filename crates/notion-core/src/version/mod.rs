@@ -13,6 +13,42 @@ use self::serial::parse_requirements;
 pub enum VersionSpec {
     Latest,
     Semver(VersionReq),
+    /// The newest long-term support release of Node, optionally restricted to a named
+    /// LTS release line (e.g. `lts/carbon`).
+    Lts(Option<String>),
+    /// The newest build published on one of Node's prerelease channels.
+    Channel(Channel),
+    /// The version a tool's package is tagged with on the npm registry (e.g. `berry`
+    /// for Yarn), for the tools published there.
+    Tag(String),
+}
+
+/// A channel of prerelease Node builds, published separately from the releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Channel {
+    /// Release candidates.
+    Rc,
+    /// Nightly builds.
+    Nightly,
+}
+
+impl Channel {
+    pub fn parse(s: &str) -> Option<Channel> {
+        match s {
+            "rc" => Some(Channel::Rc),
+            "nightly" => Some(Channel::Nightly),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Channel::Rc => write!(f, "rc"),
+            Channel::Nightly => write!(f, "nightly"),
+        }
+    }
 }
 
 impl fmt::Display for VersionSpec {
@@ -20,6 +56,10 @@ impl fmt::Display for VersionSpec {
         match *self {
             VersionSpec::Latest => write!(f, "latest"),
             VersionSpec::Semver(ref req) => req.fmt(f),
+            VersionSpec::Lts(None) => write!(f, "lts/*"),
+            VersionSpec::Lts(Some(ref name)) => write!(f, "lts/{}", name),
+            VersionSpec::Channel(ref channel) => channel.fmt(f),
+            VersionSpec::Tag(ref tag) => write!(f, "{}", tag),
         }
     }
 }
@@ -54,14 +94,40 @@ impl FromStr for VersionSpec {
     type Err = ReqParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s == "latest" {
+        // Like nvm, accept `node` as an alias for the newest release.
+        if s == "latest" || s == "node" {
             return Ok(VersionSpec::Latest);
         }
 
-        Ok(VersionSpec::Semver(parse_requirements(s)?))
+        if s == "lts" || s == "lts/*" {
+            return Ok(VersionSpec::Lts(None));
+        }
+
+        if s.starts_with("lts/") {
+            return Ok(VersionSpec::Lts(Some(s["lts/".len()..].to_lowercase())));
+        }
+
+        if let Some(channel) = Channel::parse(s) {
+            return Ok(VersionSpec::Channel(channel));
+        }
+
+        match parse_requirements(s) {
+            Ok(req) => Ok(VersionSpec::Semver(req)),
+            Err(_) if is_tag(s) => Ok(VersionSpec::Tag(s.to_string())),
+            Err(error) => Err(error),
+        }
     }
 }
 
+/// Tests whether a string can be a dist-tag: like npm, anything that doesn't parse as
+/// a version range, as long as it starts with a letter and contains no spaces or
+/// characters with a meaning in a version range.
+fn is_tag(s: &str) -> bool {
+    s.chars().next().map_or(false, |c| c.is_ascii_alphabetic())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
 #[derive(Debug, Fail, NotionFail)]
 #[fail(display = "{}", error)]
 #[notion_fail(code = "NoVersionMatch")]
@@ -82,3 +148,65 @@ impl VersionParseError {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use version::{Channel, VersionSpec};
+
+    #[test]
+    fn test_parse_latest() {
+        match VersionSpec::parse("latest").unwrap() {
+            VersionSpec::Latest => {}
+            spec => panic!("expected latest, got {}", spec),
+        }
+        match VersionSpec::parse("node").unwrap() {
+            VersionSpec::Latest => {}
+            spec => panic!("expected latest, got {}", spec),
+        }
+    }
+
+    #[test]
+    fn test_parse_lts() {
+        match VersionSpec::parse("lts").unwrap() {
+            VersionSpec::Lts(None) => {}
+            spec => panic!("expected lts, got {}", spec),
+        }
+        match VersionSpec::parse("lts/*").unwrap() {
+            VersionSpec::Lts(None) => {}
+            spec => panic!("expected lts/*, got {}", spec),
+        }
+        match VersionSpec::parse("lts/Carbon").unwrap() {
+            VersionSpec::Lts(Some(ref name)) if name == "carbon" => {}
+            spec => panic!("expected lts/carbon, got {}", spec),
+        }
+    }
+
+    #[test]
+    fn test_parse_channel() {
+        match VersionSpec::parse("rc").unwrap() {
+            VersionSpec::Channel(Channel::Rc) => {}
+            spec => panic!("expected rc, got {}", spec),
+        }
+        match VersionSpec::parse("nightly").unwrap() {
+            VersionSpec::Channel(Channel::Nightly) => {}
+            spec => panic!("expected nightly, got {}", spec),
+        }
+        assert_eq!(VersionSpec::parse("nightly").unwrap().to_string(), "nightly");
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        match VersionSpec::parse("berry").unwrap() {
+            VersionSpec::Tag(ref tag) if tag == "berry" => {}
+            spec => panic!("expected berry, got {}", spec),
+        }
+        assert_eq!(VersionSpec::parse("next-8").unwrap().to_string(), "next-8");
+        match VersionSpec::parse("^1.22").unwrap() {
+            VersionSpec::Semver(_) => {}
+            spec => panic!("expected a range, got {}", spec),
+        }
+        assert!(VersionSpec::parse("not a tag").is_err());
+        assert!(VersionSpec::parse(">= foo").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Explains which executable a Notion shim delegates to, and why.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+use env::path_for_system_node;
+use manifest::Manifest;
+use notion_fail::Fallible;
+use path;
+use semver::Version;
+use session::{ActiveVersion, Session, VersionSource};
+
+/// What provides the executable a shim delegates to.
+pub enum Provider {
+    /// A direct dependency of the current project, installed in its `node_modules`.
+    Dependency,
+    /// An indirect dependency of the current project, whose executable is installed in
+    /// its `node_modules/.bin` all the same (as npm scripts would find it).
+    Local,
+    /// A version of a tool managed by Notion.
+    Toolchain(&'static str, ActiveVersion),
+    /// A package installed globally by `notion install`, which runs with the Node
+    /// version it was installed with.
+    Package(String, Version),
+    /// The system, since Notion doesn't manage the tool in the current context.
+    System,
+}
+
+impl Display for Provider {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match self {
+            &Provider::Dependency => f.write_str("a dependency of the current project"),
+            &Provider::Local => f.write_str("the current project's node_modules/.bin"),
+            &Provider::Toolchain(tool, ref active) => write!(
+                f,
+                "{} v{} ({}{})",
+                tool,
+                active.version,
+                active.source,
+                if active.installed {
+                    ""
+                } else {
+                    ", will be installed on first use"
+                }
+            ),
+            &Provider::Package(ref name, ref node) => {
+                write!(f, "the global package {} (node v{})", name, node)
+            }
+            &Provider::System => f.write_str("the system"),
+        }
+    }
+}
+
+/// The executable a shim delegates to.
+pub struct Resolution {
+    /// The full path to the executable, if it can be found.
+    pub path: Option<PathBuf>,
+    /// What provides the executable.
+    pub provider: Provider,
+}
+
+impl Resolution {
+    fn toolchain(tool: &'static str, active: ActiveVersion, path: PathBuf) -> Self {
+        Resolution {
+            path: Some(path),
+            provider: Provider::Toolchain(tool, active),
+        }
+    }
+
+    fn system(name: &OsStr) -> Self {
+        Resolution {
+            path: find_on_system_path(name),
+            provider: Provider::System,
+        }
+    }
+}
+
+/// Searches the `PATH`, minus the Notion shim directory, for an executable.
+fn find_on_system_path(name: &OsStr) -> Option<PathBuf> {
+    env::split_paths(&path_for_system_node())
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Determines which executable the shim with the specified name delegates to in
+/// the current session, following the same rules as the shims themselves.
+pub fn resolve(session: &Session, name: &OsStr) -> Fallible<Resolution> {
+    match name.to_str() {
+        Some("node") => resolve_node(session, name),
+        Some("npm") | Some("npx") => resolve_npm(session, name),
+        Some("yarn") | Some("yarnpkg") => resolve_yarn(session, name),
+        Some("pnpm") | Some("pnpx") => resolve_pnpm(session, name),
+        _ => resolve_binary(session, name),
+    }
+}
+
+fn resolve_node(session: &Session, name: &OsStr) -> Fallible<Resolution> {
+    Ok(match session.active_node()? {
+        Some(active) => {
+            let path = path::node_version_bin_dir(&active.version.to_string())?.join(name);
+            Resolution::toolchain("node", active, path)
+        }
+        None => Resolution::system(name),
+    })
+}
+
+fn resolve_npm(session: &Session, name: &OsStr) -> Fallible<Resolution> {
+    // prefer the npm pinned by the project over the one bundled with Node
+    if session.in_pinned_project() {
+        let project = session.project().unwrap();
+        if let Some(version) = project.manifest().npm() {
+            let path = path::npm_version_bin_dir(&version.to_string())?.join(name);
+            let active = ActiveVersion {
+                installed: session.catalog()?.npm.contains(&version),
+                version,
+                source: VersionSource::Project(project.toolchain_file()),
+            };
+            return Ok(Resolution::toolchain("npm", active, path));
+        }
+    }
+    resolve_node(session, name)
+}
+
+fn resolve_yarn(session: &Session, name: &OsStr) -> Fallible<Resolution> {
+    Ok(match session.active_yarn()? {
+        Some(active) => {
+            let path = path::yarn_version_bin_dir(&active.version.to_string())?.join(name);
+            Resolution::toolchain("yarn", active, path)
+        }
+        None => Resolution::system(name),
+    })
+}
+
+fn resolve_pnpm(session: &Session, name: &OsStr) -> Fallible<Resolution> {
+    let project = session.project();
+    let pinned = project.and_then(|project| {
+        let manifest = project.manifest();
+        match manifest.pnpm() {
+            Some(version) => Some((version, VersionSource::Project(project.toolchain_file()))),
+            None => manifest
+                .package_manager_version("pnpm")
+                .map(|version| (version, VersionSource::PackageManager)),
+        }
+    });
+    let (version, source) = match pinned {
+        Some(pinned) => pinned,
+        None => {
+            return Ok(Resolution::system(name));
+        }
+    };
+
+    // pnpm's executables are Node scripts, declared in the `bin` section of its manifest
+    let package_dir = path::pnpm_version_dir(&version.to_string())?;
+    let installed = session.catalog()?.pnpm.contains(&version);
+    let path = if installed {
+        let exe_name = name.to_string_lossy().into_owned();
+        let manifest = Manifest::for_dir(&package_dir)?;
+        manifest.bin.get(&exe_name).map(|script| package_dir.join(script))
+    } else {
+        Some(package_dir)
+    };
+    Ok(Resolution {
+        path,
+        provider: Provider::Toolchain(
+            "pnpm",
+            ActiveVersion {
+                version,
+                source,
+                installed,
+            },
+        ),
+    })
+}
+
+/// Lists every executable the shim with the specified name could delegate to in the
+/// current session, in the order the shim considers them: the first is the one it
+/// runs, and the rest are shadowed by it.
+pub fn resolve_all(session: &Session, name: &OsStr) -> Fallible<Vec<Resolution>> {
+    let mut resolutions = match name.to_str() {
+        Some("node") | Some("npm") | Some("npx") | Some("yarn") | Some("yarnpkg")
+        | Some("pnpm") | Some("pnpx") => vec![resolve(session, name)?],
+        _ => binary_candidates(session, name, true)?,
+    };
+    let has_system = resolutions.iter().any(|resolution| match resolution.provider {
+        Provider::System => true,
+        _ => false,
+    });
+    if !has_system {
+        resolutions.push(Resolution::system(name));
+    }
+    resolutions.retain(|resolution| resolution.path.is_some());
+    Ok(resolutions)
+}
+
+/// Resolves a binary that isn't one of Notion's tools: first the current project's
+/// `node_modules/.bin` (whether or not the project is pinned), as npm scripts would,
+/// then a package installed by `notion install`, then a package installed globally for
+/// the active Node, then the system.
+fn resolve_binary(session: &Session, name: &OsStr) -> Fallible<Resolution> {
+    let mut candidates = binary_candidates(session, name, false)?;
+    Ok(if candidates.is_empty() {
+        Resolution::system(name)
+    } else {
+        candidates.remove(0)
+    })
+}
+
+/// Finds the executables that provide a binary, in the order of `resolve_binary`, up
+/// to the first unless `all` is set. The system's is left for the caller to add.
+fn binary_candidates(session: &Session, name: &OsStr, all: bool) -> Fallible<Vec<Resolution>> {
+    let mut candidates = vec![];
+
+    if let Some(project) = session.project() {
+        let provider = if project.has_direct_bin(name)? {
+            Some(Provider::Dependency)
+        } else if project.has_local_bin(name) {
+            Some(Provider::Local)
+        } else {
+            None
+        };
+        if let Some(provider) = provider {
+            candidates.push(Resolution {
+                path: Some(project.local_bin_dir().join(name)),
+                provider,
+            });
+            if !all {
+                return Ok(candidates);
+            }
+        }
+    }
+
+    let exe_name = name.to_string_lossy();
+    for (package_name, package) in session.catalog()?.packages.iter() {
+        if package.bins.iter().any(|bin| *bin == exe_name) {
+            candidates.push(Resolution {
+                path: Some(path::package_bin_dir(package_name)?.join(name)),
+                provider: Provider::Package(package_name.clone(), package.node.clone()),
+            });
+            if !all {
+                return Ok(candidates);
+            }
+        }
+    }
+
+    if let Some(active) = session.active_node()? {
+        let path = path::node_version_3p_bin_dir(&active.version.to_string())?.join(name);
+        if path.is_file() {
+            candidates.push(Resolution::toolchain("node", active, path));
+        }
+    }
+
+    Ok(candidates)
+}
@@ -18,6 +18,8 @@ pub fn notion_fail(token_stream: TokenStream) -> TokenStream {
     let mut code = Ident::new("UnknownError", Span::call_site());
     let mut code_set = false;
     let mut is_friendly = Ident::new("true", Span::call_site());
+    let mut suggestion = None;
+    let mut error_code = None;
 
     for meta in input.attrs.iter().filter_map(get_notion_fail_meta_items) {
         for item in meta {
@@ -49,6 +51,35 @@ pub fn notion_fail(token_stream: TokenStream) -> TokenStream {
                     }
                 }
 
+                Meta(NameValue(ref m)) if m.ident == "suggestion" => {
+                    if let Lit::Str(s) = &m.lit {
+                        let text = s.value();
+                        suggestion = Some(quote! { Some(#text.to_string()) });
+                    } else {
+                        // Defined, but not a string.
+                        panic!("#[notion_fail()]: 'suggestion' must be a string.");
+                    }
+                }
+
+                Meta(NameValue(ref m)) if m.ident == "suggest_with" => {
+                    if let Lit::Str(s) = &m.lit {
+                        let method = Ident::new(&s.value(), Span::call_site());
+                        suggestion = Some(quote! { self.#method() });
+                    } else {
+                        // Defined, but not a string.
+                        panic!("#[notion_fail()]: 'suggest_with' must be a string.");
+                    }
+                }
+
+                Meta(NameValue(ref m)) if m.ident == "error_code" => {
+                    if let Lit::Str(s) = &m.lit {
+                        error_code = Some(Ident::new(&s.value(), Span::call_site()));
+                    } else {
+                        // Defined, but not a string.
+                        panic!("#[notion_fail()]: 'error_code' must be a string.");
+                    }
+                }
+
                 Meta(NameValue(m)) => {
                     panic!("#[notion_fail()]: not a recognized name: '{}'", m.ident);
                 }
@@ -64,6 +95,25 @@ pub fn notion_fail(token_stream: TokenStream) -> TokenStream {
         panic!("#[notion_fail()] must set an exit code");
     }
 
+    // Without a suggestion, the trait's default (no suggestion) applies.
+    let suggestion_fn = suggestion.map(|suggestion| {
+        quote! {
+            fn suggestion(&self) -> Option<String> {
+                #suggestion
+            }
+        }
+    });
+
+    // Without a specific error code, the general code of the exit code applies. Naming
+    // the code's constant makes the compiler check that it is in the registry.
+    let error_code_fn = error_code.map(|error_code| {
+        quote! {
+            fn error_code(&self) -> &'static str {
+                ::notion_fail::codes::#error_code
+            }
+        }
+    });
+
     let tokens = quote! {
         impl NotionFail for #name {
             fn exit_code(&self) -> ExitCode {
@@ -73,6 +123,10 @@ pub fn notion_fail(token_stream: TokenStream) -> TokenStream {
             fn is_user_friendly(&self) -> bool {
                 #is_friendly
             }
+
+            #suggestion_fn
+
+            #error_code_fn
         }
     };
 
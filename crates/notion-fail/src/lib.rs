@@ -23,6 +23,21 @@
 //! The `NotionFail::exit_code()` method allows each error type to indicate what the
 //! process exit code should be if the error is the reason for exiting Notion.
 //!
+//! ## Error codes
+//!
+//! The `NotionFail::error_code()` method returns the error's code from the registry
+//! in `codes`, which machine-readable output reports so that wrappers and editor
+//! integrations can branch on it. By default this is the general code of the error's
+//! exit code; the derive selects a more specific one from the registry as
+//! `#[notion_fail(error_code = "VERSION_NOT_FOUND")]`.
+//!
+//! ## Suggestions
+//!
+//! The optional `NotionFail::suggestion()` method lets an error suggest what the user
+//! could do about it, such as a command to run or a version they may have meant. The
+//! derive takes a fixed suggestion as `#[notion_fail(suggestion = "...")]`, or computes
+//! one with a method of the error as `#[notion_fail(suggest_with = "method")]`.
+//!
 //! # The `NotionError` type and `Fallible` functions
 //!
 //! The main error type provided by this crate is `NotionError`. This acts more
@@ -232,6 +247,10 @@
 //! }
 //! ```
 //!
+//! The `NotionError::cause_messages()` method lists the messages of the cause chain,
+//! which Notion's top-level shows (together with the backtrace) when errors are
+//! reported verbosely.
+//!
 //! Notice that you can use `with_context` to wrap any kind of error, including
 //! errors that may already be user-friendly. So you can always use this to add
 //! even more clarity to any errors. For instance, in our running example of an
@@ -299,6 +318,70 @@ impl ExitCode {
     pub fn exit(self) -> ! {
         exit(self as i32);
     }
+
+    /// The general error code for errors that exit with this exit code.
+    pub fn error_code(self) -> &'static str {
+        match self {
+            ExitCode::Success => codes::SUCCESS,
+            ExitCode::UnknownError => codes::UNKNOWN,
+            ExitCode::InvalidArguments => codes::INVALID_ARGUMENTS,
+            ExitCode::NoVersionMatch => codes::NO_VERSION_MATCH,
+            ExitCode::NetworkError => codes::NETWORK,
+            ExitCode::EnvironmentError => codes::ENVIRONMENT,
+            ExitCode::FileSystemError => codes::FILE_SYSTEM,
+            ExitCode::ConfigurationError => codes::CONFIGURATION,
+            ExitCode::NotYetImplemented => codes::NOT_YET_IMPLEMENTED,
+            ExitCode::ExecutionFailure => codes::EXECUTION_FAILURE,
+            ExitCode::ExecutableNotFound => codes::EXECUTABLE_NOT_FOUND,
+        }
+    }
+}
+
+/// The registry of error codes reported in machine-readable output. These are part of
+/// Notion's interface: a code is never renamed, removed, or given a new meaning once
+/// released, and new codes are only ever added.
+pub mod codes {
+    // The general codes, one for each exit code.
+
+    /// No error occurred.
+    pub const SUCCESS: &'static str = "E_SUCCESS";
+    /// An internal error, which is most likely a bug in Notion.
+    pub const UNKNOWN: &'static str = "E_UNKNOWN";
+    /// An invalid combination of command-line arguments was supplied.
+    pub const INVALID_ARGUMENTS: &'static str = "E_INVALID_ARGUMENTS";
+    /// A requested version could not be resolved.
+    pub const NO_VERSION_MATCH: &'static str = "E_NO_VERSION_MATCH";
+    /// A network request failed.
+    pub const NETWORK: &'static str = "E_NETWORK";
+    /// A required environment variable was unset or invalid.
+    pub const ENVIRONMENT: &'static str = "E_ENVIRONMENT";
+    /// A file could not be read or written.
+    pub const FILE_SYSTEM: &'static str = "E_FILE_SYSTEM";
+    /// Configuration is missing or incorrect.
+    pub const CONFIGURATION: &'static str = "E_CONFIGURATION";
+    /// The command or feature is not yet implemented.
+    pub const NOT_YET_IMPLEMENTED: &'static str = "E_NOT_YET_IMPLEMENTED";
+    /// The requested executable could not be run.
+    pub const EXECUTION_FAILURE: &'static str = "E_EXECUTION_FAILURE";
+    /// The requested executable is not available.
+    pub const EXECUTABLE_NOT_FOUND: &'static str = "E_EXECUTABLE_NOT_FOUND";
+
+    // The specific codes.
+
+    /// No published version of a tool matches the requested version.
+    pub const VERSION_NOT_FOUND: &'static str = "E_VERSION_NOT_FOUND";
+    /// The tool version to act on isn't installed.
+    pub const NOT_INSTALLED: &'static str = "E_NOT_INSTALLED";
+    /// No version of a tool is selected, by the project or as the user's default.
+    pub const NO_VERSION_SELECTED: &'static str = "E_NO_VERSION_SELECTED";
+    /// A tool version is missing and fetching it on demand is disabled.
+    pub const IMPLICIT_FETCH_DISABLED: &'static str = "E_IMPLICIT_FETCH_DISABLED";
+    /// The index of a tool's versions could not be fetched.
+    pub const REGISTRY_UNAVAILABLE: &'static str = "E_REGISTRY_UNAVAILABLE";
+    /// A server kept refusing requests because too many were sent.
+    pub const RATE_LIMITED: &'static str = "E_RATE_LIMITED";
+    /// A download ended before all of it arrived, or didn't contain what it should.
+    pub const DOWNLOAD_INCOMPLETE: &'static str = "E_DOWNLOAD_INCOMPLETE";
 }
 
 /// The failure trait for all Notion errors.
@@ -308,6 +391,17 @@ pub trait NotionFail: Fail {
 
     /// Returns the process exit code that should be returned if the process exits with this error.
     fn exit_code(&self) -> ExitCode;
+
+    /// Suggests what the user could do about this error, if there is an obvious next step.
+    fn suggestion(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns this error's code from the `codes` registry, which is the general code
+    /// of its exit code unless it has a more specific one.
+    fn error_code(&self) -> &'static str {
+        self.exit_code().error_code()
+    }
 }
 
 /// The `NotionError` type, which can contain any Notion failure.
@@ -321,6 +415,12 @@ pub struct NotionError {
 
     /// The result of `error.exit_code()`.
     exit_code: ExitCode,
+
+    /// The result of `error.suggestion()`.
+    suggestion: Option<String>,
+
+    /// The result of `error.error_code()`.
+    error_code: &'static str,
 }
 
 impl Fail for NotionError {
@@ -350,6 +450,18 @@ impl NotionError {
         self.error.backtrace()
     }
 
+    /// Lists the messages of the errors that caused this one, from the error wrapped by
+    /// this one down to the root cause. The wrappers that `unknown()` adds are left out,
+    /// since they have no message of their own.
+    pub fn cause_messages(&self) -> Vec<String> {
+        self.as_fail()
+            .causes()
+            .skip(1)
+            .filter(|cause| cause.downcast_ref::<UnknownNotionError>().is_none())
+            .map(|cause| cause.to_string())
+            .collect()
+    }
+
     /// Attempts to downcast this error to a particular `NotionFail` type by reference.
     ///
     /// If the underlying error is not of type `T`, this will return `None`.
@@ -373,16 +485,30 @@ impl NotionError {
     pub fn exit_code(&self) -> ExitCode {
         self.exit_code
     }
+
+    /// Returns this error's stable code, from the `codes` registry.
+    pub fn error_code(&self) -> &'static str {
+        self.error_code
+    }
+
+    /// Suggests what the user could do about this error, if there is an obvious next step.
+    pub fn suggestion(&self) -> Option<&str> {
+        self.suggestion.as_ref().map(|suggestion| &suggestion[..])
+    }
 }
 
 impl<T: NotionFail> From<T> for NotionError {
     fn from(failure: T) -> Self {
         let user_friendly = failure.is_user_friendly();
         let exit_code = failure.exit_code();
+        let suggestion = failure.suggestion();
+        let error_code = failure.error_code();
         NotionError {
             error: failure.into(),
             user_friendly,
             exit_code,
+            suggestion,
+            error_code,
         }
     }
 }
@@ -486,6 +612,14 @@ impl<D: NotionFail> NotionFail for failure::Context<D> {
     fn exit_code(&self) -> ExitCode {
         self.get_context().exit_code()
     }
+
+    fn suggestion(&self) -> Option<String> {
+        self.get_context().suggestion()
+    }
+
+    fn error_code(&self) -> &'static str {
+        self.get_context().error_code()
+    }
 }
 
 /// A convenient shorthand for `Result` types that produce `NotionError`s.
@@ -1 +1,20 @@
-@echo Not yet implemented.
+@echo off
+
+rem Generate a random file name, to avoid clashing with concurrent executions.
+set "NOTION_POSTSCRIPT=%TEMP%\notion_tmp_%RANDOM%%RANDOM%.cmd"
+
+rem Forward the arguments to the Notion executable.
+set "NOTION_SHELL=cmd"
+"%~dp0notion.exe" %*
+set NOTION_EXIT_CODE=%ERRORLEVEL%
+set NOTION_SHELL=
+
+rem Call the post-invocation script if it is present, then delete it.
+rem This allows the invocation to potentially modify the caller's environment (e.g., PATH).
+if exist "%NOTION_POSTSCRIPT%" (
+    call "%NOTION_POSTSCRIPT%"
+    del "%NOTION_POSTSCRIPT%"
+)
+
+set NOTION_POSTSCRIPT=
+exit /b %NOTION_EXIT_CODE%
@@ -1 +1,20 @@
-Write-Host "Not yet implemented"
+function notion {
+    # Generate a random file name, to avoid clashing with concurrent executions.
+    $env:NOTION_POSTSCRIPT = Join-Path ([System.IO.Path]::GetTempPath()) ("notion_tmp_" + (Get-Random) + ".ps1")
+
+    # Forward the arguments to the Notion executable.
+    $env:NOTION_SHELL = "powershell"
+    & (Join-Path $PSScriptRoot "notion.exe") @args
+    $exitCode = $LASTEXITCODE
+    Remove-Item Env:NOTION_SHELL
+
+    # Call the post-invocation script if it is present, then delete it.
+    # This allows the invocation to potentially modify the caller's environment (e.g., PATH).
+    if (Test-Path $env:NOTION_POSTSCRIPT) {
+        . $env:NOTION_POSTSCRIPT
+        Remove-Item $env:NOTION_POSTSCRIPT
+    }
+
+    Remove-Item Env:NOTION_POSTSCRIPT
+    $global:LASTEXITCODE = $exitCode
+}
@@ -0,0 +1,335 @@
+use std::collections::BTreeSet;
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::setup::ShellKind;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+use semver::Version;
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_shell: Option<String>,
+    arg_tool: Option<String>,
+    flag_versions: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "completions for {} are not yet implemented", name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoCompletionsError {
+    pub(crate) name: String,
+}
+
+/// The tools that commands taking a `<tool>` argument are completed with.
+const TOOLS: &'static str = "node yarn npm pnpm";
+
+/// What the completion scripts need to know about a command, gathered from its
+/// usage documentation so that the completions never drift from the commands.
+struct CommandSpec {
+    name: String,
+    summary: &'static str,
+    flags: Vec<&'static str>,
+    takes_tool: bool,
+    takes_version: bool,
+}
+
+impl CommandSpec {
+    fn new(command: CommandName) -> Self {
+        let usage = Help::Command(command).usage();
+        CommandSpec {
+            name: command.to_string(),
+            summary: summary(usage),
+            flags: flags(usage),
+            takes_tool: usage.contains("<tool>"),
+            // Only these commands' versions are completed with the installed versions.
+            takes_version: match command {
+                CommandName::Install | CommandName::Uninstall => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// The first line of a usage string, which summarizes the command.
+fn summary(usage: &'static str) -> &'static str {
+    usage
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .next()
+        .unwrap_or("")
+}
+
+/// The flags a usage string lists under `Options:`, such as `-f` and `--force`.
+fn flags(usage: &'static str) -> Vec<&'static str> {
+    let mut flags = vec![];
+    let options = usage
+        .lines()
+        .skip_while(|line| line.trim() != "Options:")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty());
+    for line in options {
+        let line = line.trim();
+        let names = match line.find("  ") {
+            Some(index) => &line[..index],
+            None => line,
+        };
+        for name in names.split(", ") {
+            if let Some(flag) = name.split(' ').next() {
+                if flag.starts_with('-') {
+                    flags.push(flag);
+                }
+            }
+        }
+    }
+    flags
+}
+
+fn bash_script(commands: &[CommandSpec], global: &[&str]) -> String {
+    let names: Vec<&str> = commands.iter().map(|c| &c.name[..]).collect();
+    let versioned: Vec<&str> = commands
+        .iter()
+        .filter(|c| c.takes_version)
+        .map(|c| &c.name[..])
+        .collect();
+    let with_tool: Vec<&str> = commands
+        .iter()
+        .filter(|c| c.takes_tool)
+        .map(|c| &c.name[..])
+        .collect();
+
+    let mut script = String::new();
+    script.push_str("_notion() {\n");
+    script.push_str("    local cur command words\n");
+    script.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    command=\"${COMP_WORDS[1]}\"\n\n");
+    script.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+    script.push_str(&format!(
+        "        COMPREPLY=($(compgen -W \"{} {}\" -- \"$cur\"))\n",
+        names.join(" "),
+        global.join(" ")
+    ));
+    script.push_str("        return\n    fi\n\n");
+    script.push_str("    case \"$command\" in\n");
+    script.push_str(&format!("        {})\n", versioned.join("|")));
+    script.push_str("            if [ \"$COMP_CWORD\" -eq 3 ] && [[ \"$cur\" != -* ]]; then\n");
+    script.push_str(
+        "                COMPREPLY=($(compgen -W \"$(notion completions --versions \"${COMP_WORDS[2]}\" 2> /dev/null)\" -- \"$cur\"))\n",
+    );
+    script.push_str("                return\n            fi\n            ;;\n    esac\n\n");
+    script.push_str("    case \"$command\" in\n");
+    for command in commands {
+        script.push_str(&format!(
+            "        {}) words=\"{}\" ;;\n",
+            command.name,
+            command.flags.join(" ")
+        ));
+    }
+    script.push_str("    esac\n");
+    script.push_str("    case \"$command\" in\n");
+    script.push_str(&format!(
+        "        {}) [ \"$COMP_CWORD\" -eq 2 ] && words=\"{} $words\" ;;\n",
+        with_tool.join("|"),
+        TOOLS
+    ));
+    script.push_str("    esac\n\n");
+    script.push_str("    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n");
+    script.push_str("}\n\n");
+    script.push_str("complete -F _notion notion\n");
+    script
+}
+
+fn fish_flag(condition: &str, flag: &str) -> String {
+    if flag.starts_with("--") {
+        format!("complete -c notion -n \"{}\" -l {}\n", condition, &flag[2..])
+    } else {
+        format!("complete -c notion -n \"{}\" -s {}\n", condition, &flag[1..])
+    }
+}
+
+fn fish_script(commands: &[CommandSpec], global: &[&str]) -> String {
+    let mut script = String::new();
+    script.push_str("function __notion_versions\n");
+    script.push_str("    set -l tokens (commandline -opc)\n");
+    script.push_str("    if test (count $tokens) -eq 3\n");
+    script.push_str("        notion completions --versions $tokens[3] 2> /dev/null\n");
+    script.push_str("    end\n");
+    script.push_str("end\n\n");
+    script.push_str("function __notion_needs_tool\n");
+    script.push_str("    test (count (commandline -opc)) -eq 2\n");
+    script.push_str("end\n\n");
+    script.push_str("complete -c notion -f\n");
+
+    for flag in global {
+        script.push_str(&fish_flag("__fish_use_subcommand", flag));
+    }
+    for command in commands {
+        script.push_str(&format!(
+            "complete -c notion -n \"__fish_use_subcommand\" -a {} -d \"{}\"\n",
+            command.name, command.summary
+        ));
+    }
+    for command in commands {
+        let condition = format!("__fish_seen_subcommand_from {}", command.name);
+        for flag in &command.flags {
+            script.push_str(&fish_flag(&condition, flag));
+        }
+        if command.takes_tool {
+            script.push_str(&format!(
+                "complete -c notion -n \"{}; and __notion_needs_tool\" -a \"{}\"\n",
+                condition, TOOLS
+            ));
+        }
+        if command.takes_version {
+            script.push_str(&format!(
+                "complete -c notion -n \"{}\" -a \"(__notion_versions)\"\n",
+                condition
+            ));
+        }
+    }
+    script
+}
+
+fn powershell_list(words: &[&str]) -> String {
+    let quoted: Vec<String> = words.iter().map(|word| format!("'{}'", word)).collect();
+    format!("@({})", quoted.join(", "))
+}
+
+fn powershell_script(commands: &[CommandSpec], global: &[&str]) -> String {
+    let mut first: Vec<&str> = commands.iter().map(|c| &c.name[..]).collect();
+    first.extend(global);
+    let tools: Vec<&str> = TOOLS.split(' ').collect();
+
+    let mut script = String::new();
+    script.push_str("Register-ArgumentCompleter -Native -CommandName notion -ScriptBlock {\n");
+    script.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n\n");
+    script.push_str("    $words = @($commandAst.CommandElements | ForEach-Object { $_.ToString() })\n");
+    script.push_str("    $position = $words.Count\n");
+    script.push_str("    if ($wordToComplete -ne '') { $position -= 1 }\n\n");
+    script.push_str("    $candidates = @()\n");
+    script.push_str(&format!(
+        "    if ($position -eq 1) {{\n        $candidates = {}\n    }} else {{\n",
+        powershell_list(&first)
+    ));
+    script.push_str("        switch ($words[1]) {\n");
+    for command in commands {
+        script.push_str(&format!(
+            "            '{}' {{\n                $candidates = {}\n",
+            command.name,
+            powershell_list(&command.flags)
+        ));
+        if command.takes_tool {
+            script.push_str(&format!(
+                "                if ($position -eq 2) {{ $candidates += {} }}\n",
+                powershell_list(&tools)
+            ));
+        }
+        if command.takes_version {
+            script.push_str(
+                "                if ($position -eq 3) { $candidates += @(notion completions --versions $words[2] 2> $null) }\n",
+            );
+        }
+        script.push_str("            }\n");
+    }
+    script.push_str("        }\n    }\n\n");
+    script.push_str("    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n");
+    script.push_str(
+        "        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n",
+    );
+    script.push_str("    }\n");
+    script.push_str("}\n");
+    script
+}
+
+pub(crate) enum Completions {
+    Help,
+    Script(ShellKind),
+    Versions(String),
+}
+
+impl Command for Completions {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Generate shell completions for Notion
+
+Usage:
+    notion completions <shell>
+    notion completions --versions <tool>
+    notion completions -h | --help
+
+Options:
+    -h, --help     Display this message
+    --versions     List the installed versions of a tool, one per line
+
+The shell may be bash, zsh, fish, or powershell. The completions cover the commands
+and their flags, and complete the versions for `notion install` and `notion uninstall`
+from the installed versions. For example, add this to ~/.bashrc:
+
+    eval \"$(notion completions bash)\"
+";
+
+    fn help() -> Self {
+        Completions::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_shell,
+            arg_tool,
+            flag_versions,
+        }: Args,
+    ) -> Fallible<Self> {
+        if flag_versions {
+            // docopt guarantees the tool is present with `--versions`
+            return Ok(Completions::Versions(arg_tool.unwrap()));
+        }
+        Ok(Completions::Script(arg_shell.unwrap().parse()?))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Completions);
+        match self {
+            Completions::Help => Help::Command(CommandName::Completions).run(session)?,
+            Completions::Script(shell) => {
+                let commands: Vec<CommandSpec> = CommandName::ALL
+                    .iter()
+                    .map(|&command| CommandSpec::new(command))
+                    .collect();
+                let global = flags(Notion::USAGE);
+                let script = match shell {
+                    ShellKind::Bash => bash_script(&commands, &global),
+                    ShellKind::Zsh => format!(
+                        "autoload -U +X bashcompinit && bashcompinit\n\n{}",
+                        bash_script(&commands, &global)
+                    ),
+                    ShellKind::Fish => fish_script(&commands, &global),
+                    ShellKind::PowerShell => powershell_script(&commands, &global),
+                    ShellKind::Cmd => throw!(NoCompletionsError {
+                        name: shell.name().to_string(),
+                    }),
+                };
+                print!("{}", script);
+            }
+            Completions::Versions(tool) => {
+                let catalog = session.catalog()?;
+                let versions: Option<&BTreeSet<Version>> = match &tool[..] {
+                    "node" => Some(&catalog.node.versions),
+                    "yarn" => Some(&catalog.yarn.versions),
+                    "npm" => Some(&catalog.npm.versions),
+                    "pnpm" => Some(&catalog.pnpm.versions),
+                    _ => None,
+                };
+                // Unknown tools have no versions to complete, rather than being an error.
+                for version in versions.into_iter().flat_map(|versions| versions.iter()) {
+                    println!("{}", version);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Completions, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use std::str::FromStr;
 use docopt::Docopt;
 use serde::Deserialize;
 
+use notion_core::config::layers::Layers;
 use notion_core::session::Session;
 use notion_fail::{FailExt, Fallible};
 
@@ -42,6 +43,11 @@ pub(crate) struct KeyValue {
 #[derive(Debug, Deserialize)]
 pub(crate) struct Nullary;
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct ListArgs {
+    flag_origin: bool,
+}
+
 impl Display for SubcommandName {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(
@@ -100,7 +106,9 @@ pub(crate) enum Subcommand {
         #[allow(dead_code)]
         key: String,
     },
-    List,
+    List {
+        origin: bool,
+    },
     Edit,
 }
 
@@ -138,8 +146,19 @@ Config commands:
     get <key>
     set <key> <value>
     delete <key>
-    list
+    list [--origin]
     edit
+
+`notion config list` prints each setting in effect. The settings come in layers,
+from lowest precedence to highest: the current project's hooks, the system
+configuration (in NOTION_SYSTEM_HOME), the user's configuration, environment
+variables like NOTION_NODE_MIRROR, and command-line flags like --stream. With
+--origin, each setting is preceded by the layer it comes from.
+
+Any setting with a single value can be overridden by the environment variable
+named after its key: upper-cased, with `NOTION_` in front and its dots and dashes
+turned into underscores (NOTION_HTTP_TIMEOUT for http.timeout, say). Lists, like
+proxy.no-proxy, are separated by commas.
 ";
 
     fn help() -> Self {
@@ -167,8 +186,10 @@ Config commands:
                 Config::Subcommand(Subcommand::Delete { key: arg_key })
             }
             SubcommandName::List => {
-                let Nullary = parse_subcommand("list", "", argv)?;
-                Config::Subcommand(Subcommand::List)
+                let ListArgs { flag_origin } = parse_subcommand("list", "[--origin]", argv)?;
+                Config::Subcommand(Subcommand::List {
+                    origin: flag_origin,
+                })
             }
             SubcommandName::Edit => {
                 let Nullary = parse_subcommand("edit", "", argv)?;
@@ -188,7 +209,22 @@ Config commands:
             Config::Subcommand(Subcommand::Delete { key: _ }) => {
                 throw!(CommandUnimplementedError::new("delete"))
             }
-            Config::Subcommand(Subcommand::List) => throw!(CommandUnimplementedError::new("list")),
+            Config::Subcommand(Subcommand::List { origin }) => {
+                let layers = Layers::load(session.project())?;
+                for setting in layers.effective() {
+                    if origin {
+                        println!(
+                            "{}\t{} = {}",
+                            setting.origin,
+                            setting.key,
+                            setting.display_value()
+                        );
+                    } else {
+                        println!("{} = {}", setting.key, setting.display_value());
+                    }
+                }
+                Ok(())
+            }
             Config::Subcommand(Subcommand::Edit) => throw!(CommandUnimplementedError::new("edit")),
         };
         //session.add_event_end(ActivityKind::Version, 0);
@@ -1,15 +1,17 @@
 use std::string::ToString;
 
-use notion_core::session::{ActivityKind, Session};
+use notion_core::session::{ActiveVersion, ActivityKind, Session};
 use notion_fail::{ExitCode, Fallible, NotionFail};
 
 use Notion;
 use command::{Command, CommandName, Help};
+use output::{emit, source_kind, Format, Output};
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
     flag_project: bool,
     flag_user: bool,
+    flag_json: bool,
 }
 
 #[derive(Debug, Fail, NotionFail)]
@@ -19,16 +21,79 @@ struct NoVersionsFoundError;
 
 pub(crate) enum Current {
     Help,
-    Project,
-    User,
-    All,
+    Project(Format),
+    User(Format),
+    All(Format),
+}
+
+/// The Node version pinned by the current project or the user's default Node version.
+#[derive(Serialize)]
+struct NodeVersion {
+    version: String,
+}
+
+impl Output for NodeVersion {
+    fn to_human(&self) -> String {
+        format!("v{}", self.version)
+    }
+}
+
+/// The versions of the tools in effect for the current directory.
+#[derive(Serialize)]
+struct Toolchain {
+    node: Option<Active>,
+    yarn: Option<Active>,
+}
+
+#[derive(Serialize)]
+struct Active {
+    #[serde(skip)]
+    tool: &'static str,
+    version: String,
+    source: &'static str,
+    description: String,
+    installed: bool,
+}
+
+impl Active {
+    fn new(tool: &'static str, active: Option<ActiveVersion>) -> Option<Self> {
+        active.map(|active| Active {
+            tool,
+            version: active.version.to_string(),
+            source: source_kind(&active.source),
+            description: active.source.to_string(),
+            installed: active.installed,
+        })
+    }
+}
+
+impl Output for Toolchain {
+    fn to_human(&self) -> String {
+        self.node
+            .iter()
+            .chain(self.yarn.iter())
+            .map(|active| {
+                format!(
+                    "{}: v{} ({}{})",
+                    active.tool,
+                    active.version,
+                    active.description,
+                    if active.installed { "" } else { ", not installed" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 impl Command for Current {
     type Args = Args;
 
     const USAGE: &'static str = "
-Display the currently activated Node version
+Display the currently activated toolchain
+
+By default, this shows the Node and Yarn versions in effect for the current
+directory, where each version comes from, and whether it is installed yet.
 
 Usage:
     notion current [options]
@@ -37,6 +102,17 @@ Options:
     -h, --help     Display this message
     -p, --project  Display the current project's Node version
     -u, --user     Display the user's Node version
+    --json         Display the versions as JSON
+
+With --json, --project and --user display an object like {\"version\": \"10.8.0\"}.
+Otherwise the output is an object with `node` and `yarn` fields, each either null
+or an object with the fields:
+
+    version      The version, without a leading v
+    source       Where the version comes from: project, package-manager,
+                 node-version-file, engines, override, or user
+    description  A description of where the version comes from
+    installed    Whether the version is installed yet
 ";
 
     fn help() -> Self {
@@ -48,14 +124,16 @@ Options:
         Args {
             flag_project,
             flag_user,
+            flag_json,
         }: Args,
     ) -> Fallible<Current> {
+        let format = Format::from_json_flag(flag_json);
         Ok(if !flag_project && flag_user {
-            Current::User
+            Current::User(format)
         } else if flag_project && !flag_user {
-            Current::Project
+            Current::Project(format)
         } else {
-            Current::All
+            Current::All(format)
         })
     }
 
@@ -67,37 +145,29 @@ Options:
                 Help::Command(CommandName::Current).run(session)?;
                 true
             }
-            Current::Project => project_node_version(&session)?
-                .map(|version| {
-                    println!("v{}", version);
-                })
-                .is_some(),
-            Current::User => user_node_version(session)?
-                .map(|version| {
-                    println!("v{}", version);
-                })
-                .is_some(),
-            Current::All => {
-                let (project, user) = (
-                    project_node_version(&session)?,
-                    user_node_version(&session)?,
-                );
-
-                let user_active = project.is_none() && user.is_some();
-                let any = project.is_some() || user.is_some();
-
-                for version in project {
-                    println!("project: v{} (active)", version);
+            Current::Project(format) => match project_node_version(&session)? {
+                Some(version) => {
+                    emit(&NodeVersion { version }, format)?;
+                    true
                 }
-
-                for version in user {
-                    println!(
-                        "user: v{}{}",
-                        version,
-                        if user_active { " (active)" } else { "" }
-                    );
+                None => false,
+            },
+            Current::User(format) => match user_node_version(session)? {
+                Some(version) => {
+                    emit(&NodeVersion { version }, format)?;
+                    true
+                }
+                None => false,
+            },
+            Current::All(format) => {
+                let toolchain = Toolchain {
+                    node: Active::new("node", session.active_node()?),
+                    yarn: Active::new("yarn", session.active_yarn()?),
+                };
+                let any = toolchain.node.is_some() || toolchain.yarn.is_some();
+                if any {
+                    emit(&toolchain, format)?;
                 }
-
                 any
             }
         };
@@ -26,6 +26,9 @@ Usage:
 
 Options:
     -h, --help     Display this message
+
+Takes the Notion shims off the PATH, so that the shell finds the system's own
+tools (if any) instead. Run `notion activate` to put them back.
 ";
 
     fn help() -> Self {
@@ -0,0 +1,111 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use result::ResultOptionExt;
+
+use Notion;
+use command::{split_tool_version, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    arg_version: Option<String>,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "setting a default version of '{}' is not yet implemented", name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoCustomDefaultError {
+    pub(crate) name: String,
+}
+
+pub(crate) enum Default {
+    Help,
+    Show(String),
+    Node(VersionSpec),
+    Yarn(VersionSpec),
+    Other { name: String },
+}
+
+impl Command for Default {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Get or set the user's default version of a tool
+
+Usage:
+    notion default <tool> [<version>]
+    notion default -h | --help
+
+Options:
+    -h, --help     Display this message
+
+The default version is the one used outside of any project that selects its own.
+Setting it never changes a project's toolchain; use `notion pin` for that.
+The version may also be given as part of the tool, e.g. `notion default node@10`.
+Without a version, this displays the current default.
+";
+
+    fn help() -> Self {
+        Default::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            arg_version,
+        }: Args,
+    ) -> Fallible<Self> {
+        let (tool, arg_version) = split_tool_version(&arg_tool, arg_version);
+
+        let version = match arg_version.map(VersionSpec::parse).invert()? {
+            Some(version) => version,
+            None => {
+                return Ok(Default::Show(tool));
+            }
+        };
+
+        match &tool[..] {
+            "node" => Ok(Default::Node(version)),
+            "yarn" => Ok(Default::Yarn(version)),
+            ref tool => Ok(Default::Other {
+                name: tool.to_string(),
+            }),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Default);
+        match self {
+            Default::Help => Help::Command(CommandName::Default).run(session)?,
+            Default::Show(tool) => {
+                let version = match &tool[..] {
+                    "node" => session.user_node()?,
+                    "yarn" => session.user_yarn()?,
+                    _ => throw!(NoCustomDefaultError { name: tool }),
+                };
+                match version {
+                    Some(version) => println!("v{}", version),
+                    None => println!("no default version of {} is set", tool),
+                }
+            }
+            Default::Node(spec) => {
+                session.set_default_node(&spec)?;
+                if let Some(version) = session.user_node()? {
+                    println!("Default node set to version {}", version);
+                }
+            }
+            Default::Yarn(spec) => {
+                session.set_default_yarn(&spec)?;
+                if let Some(version) = session.user_yarn()? {
+                    println!("Default yarn set to version {}", version);
+                }
+            }
+            Default::Other { name } => throw!(NoCustomDefaultError { name }),
+        };
+        session.add_event_end(ActivityKind::Default, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use notion_core::doctor::{self, Status};
+use notion_core::session::{ActivityKind, Session};
+use notion_core::style::style;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args;
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} of the checks failed", failed)]
+#[notion_fail(code = "EnvironmentError")]
+pub(crate) struct ChecksFailedError {
+    pub(crate) failed: usize,
+}
+
+pub(crate) enum Doctor {
+    Help,
+    Diagnose,
+}
+
+impl Command for Doctor {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Diagnose problems with Notion and the current environment
+
+Usage:
+    notion doctor
+    notion doctor -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Checks that the shim directory comes first on the PATH, that no corepack shims
+compete with it, that the catalog matches the versions installed on disk, that
+interrupted fetches left nothing behind, that the Notion directories are writable,
+that the Node server (or mirror) is reachable, and that a Node version can be
+selected for the current directory. Each problem is listed with a suggested fix.
+";
+
+    fn help() -> Self {
+        Doctor::Help
+    }
+
+    fn parse(_: Notion, _: Args) -> Fallible<Self> {
+        Ok(Doctor::Diagnose)
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Doctor);
+        match self {
+            Doctor::Help => Help::Command(CommandName::Doctor).run(session)?,
+            Doctor::Diagnose => {
+                let diagnoses = doctor::diagnose(session);
+                for diagnosis in &diagnoses {
+                    let status = match diagnosis.status {
+                        Status::Pass => style(diagnosis.status.to_string()).green(),
+                        Status::Warn => style(diagnosis.status.to_string()).yellow(),
+                        Status::Fail => style(diagnosis.status.to_string()).red(),
+                    };
+                    println!("{} {}: {}", status.bold(), diagnosis.check, diagnosis.detail);
+                    if let Some(ref fix) = diagnosis.fix {
+                        println!("     fix: {}", fix);
+                    }
+                }
+
+                let failed = diagnoses
+                    .iter()
+                    .filter(|diagnosis| diagnosis.status == Status::Fail)
+                    .count();
+                if failed > 0 {
+                    throw!(ChecksFailedError { failed });
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Doctor, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use notion_core::env;
+use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::Postscript;
+use notion_core::shell::setup::ShellKind;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_shell: Option<String>,
+}
+
+pub(crate) enum Env {
+    Help,
+    Check(ShellKind),
+}
+
+impl Command for Env {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Bring the shell environment up to date with the active toolchain
+
+Usage:
+    notion env --check [--shell <shell>]
+    notion env -h | --help
+
+Options:
+    -h, --help         Display this message
+    --check            Print the shell code to switch to the active Node, if any
+    --shell <shell>    The shell to print code for (detected from $SHELL by default)
+
+Prints nothing when the environment already matches, so that the hook installed
+by `notion hook` stays quiet. Versions that aren't installed yet are left to the
+shims, which fetch them when they're first run.
+";
+
+    fn help() -> Self {
+        Env::Help
+    }
+
+    fn parse(_: Notion, Args { flag_shell }: Args) -> Fallible<Self> {
+        let shell = match flag_shell {
+            Some(name) => name.parse()?,
+            None => ShellKind::detect()?,
+        };
+        Ok(Env::Check(shell))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Env);
+        match self {
+            Env::Help => Help::Command(CommandName::Env).run(session)?,
+            Env::Check(shell) => {
+                let version = match session.active_node()? {
+                    Some(ref active) if active.installed => Some(active.version.to_string()),
+                    _ => None,
+                };
+                if let Some(path) = env::path_for_hook(version.as_ref().map(|v| &v[..])) {
+                    let postscript = match path.into_string() {
+                        Ok(path) => Postscript::Path(path),
+                        Err(_) => unimplemented!(),
+                    };
+                    print!("{}", shell.compile(&postscript));
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Env, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::process;
+
+use notion_core::env::ToolKind;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_command: String,
+    arg_args: Vec<String>,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not run `{}`: {}", command, error)]
+#[notion_fail(code = "ExecutionFailure")]
+pub(crate) struct ExecError {
+    pub(crate) command: String,
+    pub(crate) error: String,
+}
+
+pub(crate) enum Exec {
+    Help,
+    Command { command: String, args: Vec<String> },
+}
+
+impl Command for Exec {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Run a command with the current toolchain
+
+Usage:
+    notion exec [--] <command> [<args> ...]
+    notion exec -h | --help
+
+Options:
+    -h, --help     Display this message
+
+The command runs with the versions of Node, Yarn, and npm selected for the current
+directory first on its PATH, fetching them first if need be, and Notion exits with
+its exit status. This lets scripts use the project's toolchain without setting up a
+shell, e.g. `notion exec -- node --version`. Use `--` to separate the command's own
+options from Notion's.
+";
+
+    fn help() -> Self {
+        Exec::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_command,
+            arg_args,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(Exec::Command {
+            command: arg_command,
+            args: arg_args,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Exec);
+        match self {
+            Exec::Help => Help::Command(CommandName::Exec).run(session)?,
+            Exec::Command { command, args } => {
+                let tool_env = session.env_for_tool(ToolKind::Toolchain)?;
+
+                let status = process::Command::new(&command)
+                    .args(&args)
+                    .envs(tool_env.vars())
+                    .status()
+                    .with_context(|error| ExecError {
+                        command: command.clone(),
+                        error: error.to_string(),
+                    })?;
+                if !status.success() {
+                    // ISSUE (#36): if None, in unix, find out the signal
+                    let code = status.code().unwrap_or(1);
+                    session.add_event_tool_end(ActivityKind::Exec, code);
+                    session.exit_tool(code);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Exec, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use serde_json;
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::state::State;
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args;
+
+pub(crate) enum Export {
+    Help,
+    Export,
+}
+
+impl Command for Export {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Export the installed tool versions, the user's defaults, and the global tools
+
+Usage:
+    notion export
+    notion export -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Prints the toolchain state of this machine as JSON: the versions of Node, Yarn, npm,
+and pnpm that are installed, the user's default version of each, and the packages
+installed as global tools, with the Node version each runs with. Save it to a file,
+as with `notion export > state.json`, and restore it on another machine with
+`notion import state.json`.
+";
+
+    fn help() -> Self {
+        Export::Help
+    }
+
+    fn parse(_: Notion, _: Args) -> Fallible<Self> {
+        Ok(Export::Export)
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Export);
+        match self {
+            Export::Help => Help::Command(CommandName::Export).run(session)?,
+            Export::Export => {
+                let state = State::capture(session.catalog()?);
+                println!("{}", serde_json::to_string_pretty(&state).unknown()?);
+            }
+        };
+        session.add_event_end(ActivityKind::Export, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -1,34 +1,77 @@
+use std::path::PathBuf;
+
+use notion_core::catalog::ToolSpec;
 use notion_core::session::{ActivityKind, Session};
 use notion_core::version::VersionSpec;
 use notion_fail::{ExitCode, Fallible};
 
-use command::{Command, CommandName, Help};
+use command::{split_tool_version, Command, CommandName, Help};
 use {CliParseError, Notion};
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
-    arg_tool: String,
-    arg_version: String,
+    arg_tool: Vec<String>,
+    flag_file: Option<String>,
 }
 
 pub(crate) enum Fetch {
     Help,
-    Node(VersionSpec),
-    Yarn(VersionSpec),
+    Tools(Vec<ToolSpec>),
+    File { version: VersionSpec, path: PathBuf },
+}
+
+/// Tests whether the specified argument names a tool, rather than a version.
+fn is_tool(arg: &str) -> bool {
+    match arg {
+        "node" | "yarn" | "npm" | "pnpm" => true,
+        _ => false,
+    }
+}
+
+/// Parses a tool spec of the form `<tool>[@<version>]`, where the version defaults
+/// to the latest.
+fn parse_spec(tool: &str, version: Option<&str>) -> Fallible<ToolSpec> {
+    let version = match version {
+        Some(version) => VersionSpec::parse(version)?,
+        None => VersionSpec::Latest,
+    };
+    match tool {
+        "node" => Ok(ToolSpec::Node(version)),
+        "yarn" => Ok(ToolSpec::Yarn(version)),
+        "npm" => Ok(ToolSpec::Npm(version)),
+        "pnpm" => Ok(ToolSpec::Pnpm(version)),
+        tool => {
+            throw!(CliParseError {
+                usage: None,
+                error: format!("no such tool: `{}`", tool),
+            });
+        }
+    }
 }
 
 impl Command for Fetch {
     type Args = Args;
 
     const USAGE: &'static str = "
-Fetch a tool to the local machine
+Fetch one or more tools to the local machine
 
 Usage:
-    notion fetch <tool> <version>
+    notion fetch [options] <tool>...
     notion fetch -h | --help
 
 Options:
-    -h, --help     Display this message
+    -h, --help         Display this message
+    --file <path>      Fetch Node from a local archive instead of downloading it
+
+Several tools can be fetched at once, each given as <tool>@<version>, e.g.
+`notion fetch node@8 node@10 yarn@1.5`. They are downloaded concurrently.
+A tool given without a version is fetched at its latest version, and a single
+tool may also be given as `notion fetch <tool> <version>`.
+
+With `--file`, a single Node version is fetched from an archive on the local machine,
+e.g. `notion fetch node@10.8.0 --file node-v10.8.0-linux-x64.tar.gz`. The archive must
+keep the name Node publishes it under, which identifies the version it contains, and
+that version must match the one requested. The archive is added to Notion's cache.
 ";
 
     fn help() -> Self {
@@ -39,18 +82,39 @@ Options:
         _: Notion,
         Args {
             arg_tool,
-            arg_version,
+            flag_file,
         }: Args,
     ) -> Fallible<Self> {
-        match &arg_tool[..] {
-            "node" => Ok(Fetch::Node(VersionSpec::parse(&arg_version)?)),
-            "yarn" => Ok(Fetch::Yarn(VersionSpec::parse(&arg_version)?)),
-            ref tool => {
-                throw!(CliParseError {
-                    usage: None,
-                    error: format!("no such tool: `{}`", tool),
-                });
+        // `notion fetch <tool> <version>` is shorthand for `notion fetch <tool>@<version>`.
+        let specs = if arg_tool.len() == 2 && is_tool(&arg_tool[0]) && !is_tool(&arg_tool[1]) {
+            vec![parse_spec(&arg_tool[0], Some(&arg_tool[1]))?]
+        } else {
+            let mut specs = Vec::new();
+            for arg in arg_tool {
+                let (tool, version) = split_tool_version(&arg, None);
+                specs.push(parse_spec(&tool, version.as_ref().map(String::as_str))?);
             }
+            specs
+        };
+
+        match flag_file {
+            None => Ok(Fetch::Tools(specs)),
+            Some(path) => match specs.len() {
+                1 => match specs.into_iter().next().unwrap() {
+                    ToolSpec::Node(version) => Ok(Fetch::File {
+                        version,
+                        path: PathBuf::from(path),
+                    }),
+                    _ => throw!(CliParseError {
+                        usage: None,
+                        error: "only Node can be fetched from a local archive".to_string(),
+                    }),
+                },
+                _ => throw!(CliParseError {
+                    usage: None,
+                    error: "--file fetches a single version of Node".to_string(),
+                }),
+            },
         }
     }
 
@@ -58,11 +122,11 @@ Options:
         session.add_event_start(ActivityKind::Fetch);
         match self {
             Fetch::Help => Help::Command(CommandName::Fetch).run(session)?,
-            Fetch::Node(version) => {
-                session.fetch_node(&version)?;
+            Fetch::Tools(specs) => {
+                session.fetch_many(&specs)?;
             }
-            Fetch::Yarn(version) => {
-                session.fetch_yarn(&version)?;
+            Fetch::File { version, path } => {
+                session.fetch_node_file(&version, &path)?;
             }
         };
         session.add_event_end(ActivityKind::Fetch, ExitCode::Success);
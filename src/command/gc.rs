@@ -0,0 +1,103 @@
+use notion_core::dedupe;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_dry_run: bool,
+    flag_ignore_projects: bool,
+    flag_dedupe: bool,
+}
+
+pub(crate) enum Gc {
+    Help,
+    Collect {
+        dry_run: bool,
+        include_projects: bool,
+    },
+    Dedupe,
+}
+
+impl Command for Gc {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Uninstall the tool versions that nothing uses
+
+Usage:
+    notion gc [options]
+    notion gc -h | --help
+
+Options:
+    -n, --dry-run          List the versions that would be uninstalled, and the files that
+                           would be deleted, without uninstalling them
+    --ignore-projects      Don't keep versions pinned by other projects Notion has seen
+    --dedupe               Hard-link identical files across the Node versions instead
+    -h, --help             Display this message
+
+A version is kept if it is the user version, if the current project selects it,
+or if it is pinned by a project whose toolchain Notion has pinned, installed, or
+run (see `notion list --projects`).
+
+With --dedupe, nothing is uninstalled: instead, each file of an installed Node
+version that is identical to a file of another version is replaced with a hard link
+to it, and the space saved is reported. Set `dedupe = true` in the `[behavior]`
+section of the config to do this after each fetch of Node.
+";
+
+    fn help() -> Self {
+        Gc::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            flag_dry_run,
+            flag_ignore_projects,
+            flag_dedupe,
+        }: Args,
+    ) -> Fallible<Self> {
+        if flag_dedupe {
+            return Ok(Gc::Dedupe);
+        }
+        Ok(Gc::Collect {
+            dry_run: flag_dry_run,
+            include_projects: !flag_ignore_projects,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Gc);
+        match self {
+            Gc::Help => Help::Command(CommandName::Gc).run(session)?,
+            Gc::Collect {
+                dry_run,
+                include_projects,
+            } => {
+                let unused = session.collect_garbage(dry_run, include_projects)?;
+                if unused.is_empty() {
+                    println!("No unused versions to uninstall");
+                }
+                for (tool, version) in unused {
+                    if dry_run {
+                        println!("Would uninstall {} v{}:", tool, version);
+                        for change in session.plan_collect(tool, &version)?.changes() {
+                            println!("    {}", change);
+                        }
+                    } else {
+                        println!("Uninstalled {} v{}", tool, version);
+                    }
+                }
+            }
+            Gc::Dedupe => {
+                let saved = dedupe::node_versions()?;
+                println!("Deduplicated the Node versions: {}", saved);
+            }
+        };
+        session.add_event_end(ActivityKind::Gc, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use notion_core::session::{ActivityKind, Session};
 use notion_fail::{ExitCode, Fallible};
 
-use command::{Command, CommandName, Config, Current, Deactivate, Fetch, Install, Shim, Use,
-              Version};
+use command::{Activate, Command, CommandName, Completions, Config, Current, Deactivate, Default,
+              Doctor, Env, Exec, Export, Fetch, Gc, Hook, Import, Info, Install, List, LsRemote,
+              Outdated, Pin, Prompt, Query, Run, SelfUpdate, Setup, Shim, Telemetry, Uninstall,
+              Unpin, Update, Use, Version, Which, Why, X};
 use {CliParseError, Notion};
 
 #[derive(Debug, Deserialize)]
@@ -51,22 +53,53 @@ Options:
 
     fn run(self, session: &mut Session) -> Fallible<()> {
         session.add_event_start(ActivityKind::Help);
-        eprintln!(
-            "{}",
-            match self {
-                Help::Notion => Notion::USAGE,
-                Help::Command(CommandName::Use) => Use::USAGE,
-                Help::Command(CommandName::Config) => Config::USAGE,
-                Help::Command(CommandName::Current) => Current::USAGE,
-                Help::Command(CommandName::Deactivate) => Deactivate::USAGE,
-                Help::Command(CommandName::Help) => Help::USAGE,
-                Help::Command(CommandName::Version) => Version::USAGE,
-                Help::Command(CommandName::Fetch) => Fetch::USAGE,
-                Help::Command(CommandName::Install) => Install::USAGE,
-                Help::Command(CommandName::Shim) => Shim::USAGE,
-            }
-        );
+        eprintln!("{}", self.usage());
         session.add_event_end(ActivityKind::Help, ExitCode::Success);
         Ok(())
     }
 }
+
+impl Help {
+    /// The usage documentation this help topic displays.
+    pub(crate) fn usage(&self) -> &'static str {
+        match *self {
+            Help::Notion => Notion::USAGE,
+            Help::Command(CommandName::Use) => Use::USAGE,
+            Help::Command(CommandName::Config) => Config::USAGE,
+            Help::Command(CommandName::Current) => Current::USAGE,
+            Help::Command(CommandName::Activate) => Activate::USAGE,
+            Help::Command(CommandName::Deactivate) => Deactivate::USAGE,
+            Help::Command(CommandName::Setup) => Setup::USAGE,
+            Help::Command(CommandName::Hook) => Hook::USAGE,
+            Help::Command(CommandName::Env) => Env::USAGE,
+            Help::Command(CommandName::Completions) => Completions::USAGE,
+            Help::Command(CommandName::Exec) => Exec::USAGE,
+            Help::Command(CommandName::Run) => Run::USAGE,
+            Help::Command(CommandName::X) => X::USAGE,
+            Help::Command(CommandName::Help) => Help::USAGE,
+            Help::Command(CommandName::Version) => Version::USAGE,
+            Help::Command(CommandName::Fetch) => Fetch::USAGE,
+            Help::Command(CommandName::Install) => Install::USAGE,
+            Help::Command(CommandName::Import) => Import::USAGE,
+            Help::Command(CommandName::Export) => Export::USAGE,
+            Help::Command(CommandName::Default) => Default::USAGE,
+            Help::Command(CommandName::Uninstall) => Uninstall::USAGE,
+            Help::Command(CommandName::Gc) => Gc::USAGE,
+            Help::Command(CommandName::List) => List::USAGE,
+            Help::Command(CommandName::LsRemote) => LsRemote::USAGE,
+            Help::Command(CommandName::Info) => Info::USAGE,
+            Help::Command(CommandName::Outdated) => Outdated::USAGE,
+            Help::Command(CommandName::Pin) => Pin::USAGE,
+            Help::Command(CommandName::Unpin) => Unpin::USAGE,
+            Help::Command(CommandName::Update) => Update::USAGE,
+            Help::Command(CommandName::Shim) => Shim::USAGE,
+            Help::Command(CommandName::Prompt) => Prompt::USAGE,
+            Help::Command(CommandName::Query) => Query::USAGE,
+            Help::Command(CommandName::Which) => Which::USAGE,
+            Help::Command(CommandName::Why) => Why::USAGE,
+            Help::Command(CommandName::Doctor) => Doctor::USAGE,
+            Help::Command(CommandName::SelfUpdate) => SelfUpdate::USAGE,
+            Help::Command(CommandName::Telemetry) => Telemetry::USAGE,
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::setup::ShellKind;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_shell: String,
+}
+
+pub(crate) enum Hook {
+    Help,
+    Print(ShellKind),
+}
+
+impl Command for Hook {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Switch Node versions automatically when changing directories
+
+Usage:
+    notion hook <shell>
+    notion hook -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Prints a hook for bash, zsh, or fish that runs `notion env --check` as you move
+around, putting the active project's Node directly on the PATH. To opt in, add
+this to your profile (or `notion hook fish | source` to config.fish):
+
+    eval \"$(notion hook bash)\"
+";
+
+    fn help() -> Self {
+        Hook::Help
+    }
+
+    fn parse(_: Notion, Args { arg_shell }: Args) -> Fallible<Self> {
+        Ok(Hook::Print(arg_shell.parse()?))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Hook);
+        match self {
+            Hook::Help => Help::Command(CommandName::Hook).run(session)?,
+            Hook::Print(shell) => {
+                print!("{}", shell.hook_script()?);
+            }
+        };
+        session.add_event_end(ActivityKind::Hook, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use notion_core::import::{self, Source, Transfer};
+use notion_core::session::{ActivityKind, Session};
+use notion_core::state::State;
+use notion_core::style::display_warning;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_source: String,
+    flag_copy: bool,
+    flag_dry_run: bool,
+}
+
+pub(crate) enum Import {
+    Help,
+    Import {
+        source: Source,
+        copy: bool,
+        dry_run: bool,
+    },
+    Restore(PathBuf),
+}
+
+impl Command for Import {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Import the Node versions installed by another version manager, or a `notion export`
+
+Usage:
+    notion import <source> [options]
+    notion import -h | --help
+
+Options:
+    -h, --help     Display this message
+    --copy         Copy the files, rather than hard-linking them where possible
+    --dry-run      Only list the versions that would be imported
+
+Finds the Node versions installed by nvm (in $NVM_DIR/versions/node), n (in
+$N_PREFIX/n/versions/node), or asdf (in $ASDF_DATA_DIR/installs/nodejs), checks that
+each one runs, and adds it to Notion's versions, so that it needn't be downloaded
+again. Files are hard-linked where possible, so that the import takes no extra space,
+and the other version manager can still be uninstalled afterwards. Versions Notion
+already has are left alone.
+
+Given the file written by `notion export` instead, fetches the tool versions it lists
+that aren't installed, sets the user's defaults to those it records, and installs the
+global tools it lists that aren't installed, each with the Node version it ran with.
+";
+
+    fn help() -> Self {
+        Import::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_source,
+            flag_copy,
+            flag_dry_run,
+        }: Args,
+    ) -> Fallible<Self> {
+        if arg_source.parse::<Source>().is_err() && Path::new(&arg_source).is_file() {
+            return Ok(Import::Restore(PathBuf::from(arg_source)));
+        }
+        Ok(Import::Import {
+            source: arg_source.parse()?,
+            copy: flag_copy,
+            dry_run: flag_dry_run,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Import);
+        match self {
+            Import::Help => Help::Command(CommandName::Import).run(session)?,
+            Import::Import {
+                source,
+                copy,
+                dry_run,
+            } => {
+                let versions_dir = source.versions_dir()?;
+                let installed = import::discover(&versions_dir)?;
+                if installed.is_empty() {
+                    println!(
+                        "No Node versions installed by {} were found in {}",
+                        source,
+                        versions_dir.display()
+                    );
+                }
+
+                let mut imported = 0;
+                for installed in installed {
+                    if session.catalog()?.node.contains(&installed.version) {
+                        println!("node v{} is already installed", installed.version);
+                        continue;
+                    }
+                    if !import::validate(&installed) {
+                        display_warning(&format!(
+                            "skipping {}, whose Node executable doesn't run as v{}",
+                            installed.dir.display(),
+                            installed.version
+                        ));
+                        continue;
+                    }
+                    if dry_run {
+                        println!(
+                            "Would import node v{} from {}",
+                            installed.version,
+                            installed.dir.display()
+                        );
+                        continue;
+                    }
+
+                    let transfer = import::import(session.catalog_mut()?, &installed, copy)?;
+                    println!(
+                        "Imported node v{} from {} ({})",
+                        installed.version,
+                        installed.dir.display(),
+                        match transfer {
+                            Transfer::Linked => "linked",
+                            Transfer::Copied => "copied",
+                        }
+                    );
+                    imported += 1;
+                }
+                if imported > 0 {
+                    println!("Imported {} Node versions from {}", imported, source);
+                }
+            }
+            Import::Restore(file) => {
+                let state = State::from_file(&file)?;
+                for name in session.restore_state(&state)? {
+                    println!("Installed {}", name);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Import, ExitCode::Success);
+        Ok(())
+    }
+}
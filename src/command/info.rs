@@ -0,0 +1,203 @@
+use notion_core::catalog::ReleaseInfo;
+use notion_core::session::{ActivityKind, Session};
+use notion_core::style::format_columns;
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use result::ResultOptionExt;
+
+use Notion;
+use command::{split_tool_version, Command, CommandName, Help};
+use output::{emit, Format, Output};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    arg_version: Option<String>,
+    flag_json: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot describe the releases of unknown tool '{}' - expected node or yarn",
+       name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownToolError {
+    pub(crate) name: String,
+}
+
+pub(crate) enum Info {
+    Help,
+    Node(VersionSpec, Format),
+    Yarn(VersionSpec, Format),
+}
+
+/// A release of a tool, and what is known about it.
+#[derive(Serialize)]
+struct Release {
+    tool: &'static str,
+    version: String,
+    date: Option<String>,
+    lts: Option<String>,
+    security: bool,
+    npm: Option<String>,
+    files: Vec<String>,
+    cached: Vec<CachedArchive>,
+    installed: bool,
+    default: bool,
+}
+
+#[derive(Serialize)]
+struct CachedArchive {
+    path: String,
+    size: u64,
+}
+
+impl Release {
+    fn new(tool: &'static str, info: ReleaseInfo) -> Self {
+        Release {
+            tool,
+            version: info.version.to_string(),
+            date: info.date,
+            lts: info.lts,
+            security: info.security,
+            npm: info.npm.map(|npm| npm.to_string()),
+            files: info.files,
+            cached: info.cached
+                .into_iter()
+                .map(|(path, size)| CachedArchive {
+                    path: path.to_string_lossy().to_string(),
+                    size,
+                })
+                .collect(),
+            installed: info.installed,
+            default: info.default,
+        }
+    }
+}
+
+impl Output for Release {
+    fn to_human(&self) -> String {
+        let mut rows = vec![];
+        {
+            let mut row = |name: &str, value: String| {
+                rows.push(vec![format!("    {}", name), value])
+            };
+
+            if let Some(ref date) = self.date {
+                row("released", date.clone());
+            }
+            if let Some(ref lts) = self.lts {
+                row("LTS", lts.clone());
+            }
+            if self.security {
+                row("security", "this is a security release".to_string());
+            }
+            if let Some(ref npm) = self.npm {
+                row("bundled npm", format!("v{}", npm));
+            }
+            if !self.files.is_empty() {
+                row("files", self.files.join(", "));
+            }
+            for archive in &self.cached {
+                row(
+                    "cached",
+                    format!("{} ({:.1} MB)", archive.path, archive.size as f64 / 1_000_000.0),
+                );
+            }
+            if self.cached.is_empty() {
+                row("cached", "no".to_string());
+            }
+            row(
+                "installed",
+                match (self.installed, self.default) {
+                    (true, true) => "yes (the default)".to_string(),
+                    (true, false) => "yes".to_string(),
+                    (false, _) => "no".to_string(),
+                },
+            );
+        }
+
+        format!("{} v{}\n{}", self.tool, self.version, format_columns(&rows))
+    }
+}
+
+impl Command for Info {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Show what is known about a release of a tool
+
+Usage:
+    notion info [options] <tool> [<version>]
+    notion info -h | --help
+
+Options:
+    --json         Display the release as JSON
+    -h, --help     Display this message
+
+Describes the newest release of Node or Yarn matching the version (by default, the
+newest release), e.g. `notion info node@10` or `notion info node lts/dubnium`: its
+release date, LTS line, bundled npm, the files it is published as (which name the
+platforms it is built for), whether its archive is cached and how big it is, and
+whether it is installed. Everything comes from the index of releases (which is only
+downloaded if the cached one has expired) and the catalog. Only the Node index
+records more than the release date.
+
+With --json, the output is an object with the fields:
+
+    tool       The tool, node or yarn
+    version    The version, without a leading v
+    date       The date of the release (YYYY-MM-DD), or null if unknown
+    lts        The name of the release's LTS line, or null
+    security   Whether the index marks the release as a security release
+    npm        The version of npm bundled with the release, or null
+    files      The files the release is published as, as the Node index names them
+    cached     The release's archives in the cache, as objects with their `path`
+               and their `size` in bytes
+    installed  Whether the release is installed
+    default    Whether the release is the user's default version
+";
+
+    fn help() -> Self {
+        Info::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            arg_version,
+            flag_json,
+        }: Args,
+    ) -> Fallible<Self> {
+        let (tool, arg_version) = split_tool_version(&arg_tool, arg_version);
+        let version = arg_version
+            .map(VersionSpec::parse)
+            .invert()?
+            .unwrap_or_default();
+        let format = Format::from_json_flag(flag_json);
+
+        match &tool[..] {
+            "node" => Ok(Info::Node(version, format)),
+            "yarn" => Ok(Info::Yarn(version, format)),
+            _ => throw!(UnknownToolError { name: tool }),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Info);
+        match self {
+            Info::Help => Help::Command(CommandName::Info).run(session)?,
+            Info::Node(version, format) => {
+                let info = session.node_release_info(&version)?;
+                emit(&Release::new("node", info), format)?;
+            }
+            Info::Yarn(version, format) => {
+                let info = session.yarn_release_info(&version)?;
+                emit(&Release::new("yarn", info), format)?;
+            }
+        };
+        session.add_event_end(ActivityKind::Info, ExitCode::Success);
+        Ok(())
+    }
+}
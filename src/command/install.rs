@@ -1,27 +1,45 @@
+use notion_core::plan::Plan;
 use notion_core::session::{ActivityKind, Session};
+use notion_core::style::{interactive, pick};
 use notion_core::version::VersionSpec;
-use notion_fail::{ExitCode, Fallible};
+use notion_fail::{ExitCode, Fallible, ResultExt};
 
 use result::ResultOptionExt;
 
-use CommandUnimplementedError;
-use Notion;
-use command::{Command, CommandName, Help};
+use command::{split_tool_version, Command, CommandName, Help};
+use {CliParseError, Notion};
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
     arg_tool: String,
     arg_version: Option<String>,
+    flag_dry_run: bool,
+    flag_url: Option<String>,
+    flag_label: Option<String>,
+    flag_checksum: Option<String>,
+    flag_build_from_source: bool,
 }
 
 pub(crate) enum Install {
     Help,
     Node(VersionSpec),
+    /// Installs a version of Node, building it from source if there is no prebuilt
+    /// binary of it for this platform.
+    NodeFromSource(VersionSpec),
+    /// Installs a custom build of Node from the archive at a URL.
+    NodeUrl {
+        url: String,
+        label: String,
+        checksum: Option<String>,
+    },
+    /// Installs the version of Node the user picks from the recent releases.
+    PickNode,
     Yarn(VersionSpec),
     Other {
         package: String,
         version: VersionSpec,
     },
+    DryRun(Box<Install>),
 }
 
 impl Command for Install {
@@ -31,14 +49,46 @@ impl Command for Install {
 Install a tool in the user toolchain
 
 Usage:
-    notion install <tool> [<version>]
+    notion install [options] <tool> [<version>]
     notion install -h | --help
 
 Options:
-    -h, --help     Display this message
+    -n, --dry-run        Show the changes installing would make, without making them
+    --url <url>          Install a custom build of Node from the archive at <url>
+    --label <label>      The label to record a custom build under
+    --checksum <sha256>  The checksum a custom build's archive must have
+    --build-from-source  Build Node from source if it has no binary for this platform
+    -h, --help           Display this message
+
+The version may also be given as part of the tool, e.g. `notion install yarn@1.7.0`.
+Without a version (or with `latest`), the newest release is installed, and the
+version it resolves to is recorded so that later runs keep using it. Node's
+prerelease builds are installed with `rc` or `nightly`, e.g. `notion install node@rc`;
+they are only ever selected by asking for their channel (or their exact version).
+When `notion install node` is run in a terminal without a version, it asks which
+release to install instead, listing the newest release of the current line and of
+each LTS line.
+
+Any other tool is installed from the npm registry as a global package, with shims for
+its executables. The package always runs with the Node version that was selected when
+it was installed, e.g. `notion install typescript@3`.
 
-Supported Tools:
-    Currently Notion supports installing `node` and `yarn` - support for more tools is coming soon!
+With --url, a custom build of Node (e.g. one with patches under test) is installed
+from its archive, e.g. `notion install node --url <url> --label tls-fix`. The
+archive must keep the name Node publishes archives under, which identifies the
+version it contains. The URL and the archive's checksum are recorded in the catalog
+under the label, which is required. A project pins a custom build by its version,
+like any other; a `node.resolve` plugin can serve the build to machines that
+haven't installed it.
+
+With --build-from-source, a release of Node that has no prebuilt binary for this
+platform (e.g. on an unusual architecture) is built from its source instead, with
+`configure` and `make`, which takes a while. The build needs the tools Node's own
+build does, like Python and a C++ compiler, and isn't supported on Windows.
+
+With --dry-run, the version is still resolved, but instead of being installed, the
+changes installing would make are listed. The version of a package that npm would
+install is only known once it installs it.
 ";
 
     fn help() -> Self {
@@ -50,21 +100,64 @@ Supported Tools:
         Args {
             arg_tool,
             arg_version,
+            flag_dry_run,
+            flag_url,
+            flag_label,
+            flag_checksum,
+            flag_build_from_source,
         }: Args,
     ) -> Fallible<Self> {
+        if let Some(url) = flag_url {
+            if arg_tool != "node" || arg_version.is_some() || flag_dry_run {
+                throw!(CliParseError {
+                    usage: None,
+                    error: "--url installs a custom build of Node, e.g. `notion install node --url <url> --label <label>`".to_string(),
+                });
+            }
+            let label = match flag_label {
+                Some(label) => label,
+                None => throw!(CliParseError {
+                    usage: None,
+                    error: "a custom build needs a --label to record it under".to_string(),
+                }),
+            };
+            return Ok(Install::NodeUrl {
+                url,
+                label,
+                checksum: flag_checksum,
+            });
+        }
+
+        let (tool, arg_version) = split_tool_version(&arg_tool, arg_version);
+
+        let picking = arg_version.is_none() && interactive();
         let version = arg_version
             .map(VersionSpec::parse)
             .invert()?
             .unwrap_or_default();
 
-        match &arg_tool[..] {
-            "node" => Ok(Install::Node(version)),
-            "yarn" => Ok(Install::Yarn(version)),
-            ref package => Ok(Install::Other {
+        if flag_build_from_source && (tool != "node" || flag_dry_run) {
+            throw!(CliParseError {
+                usage: None,
+                error: "--build-from-source only installs Node, and can't be combined with --dry-run".to_string(),
+            });
+        }
+
+        let install = match &tool[..] {
+            "node" if flag_build_from_source => Install::NodeFromSource(version),
+            "node" if picking => Install::PickNode,
+            "node" => Install::Node(version),
+            "yarn" => Install::Yarn(version),
+            ref package => Install::Other {
                 package: package.to_string(),
                 version: version,
-            }),
-        }
+            },
+        };
+        Ok(if flag_dry_run {
+            Install::DryRun(Box::new(install))
+        } else {
+            install
+        })
     }
 
     fn run(self, session: &mut Session) -> Fallible<()> {
@@ -74,20 +167,83 @@ Supported Tools:
                 Help::Command(CommandName::Install).run(session)?;
             }
             Install::Node(requirements) => {
-                session.set_user_node(&requirements)?;
+                session.set_default_node(&requirements)?;
+            }
+            Install::NodeFromSource(requirements) => {
+                session.set_default_node_from_source(&requirements)?;
+            }
+            Install::NodeUrl {
+                url,
+                label,
+                checksum,
+            } => {
+                let version = session.set_default_node_url(
+                    &url,
+                    &label,
+                    checksum.as_ref().map(String::as_str),
+                )?;
+                println!("Installed node v{} ({})", version, label);
+            }
+            Install::PickNode => {
+                if let Some(requirements) = pick_node(session)? {
+                    session.set_default_node(&requirements)?;
+                }
             }
             Install::Yarn(requirements) => {
-                session.set_user_yarn(&requirements)?;
+                session.set_default_yarn(&requirements)?;
+            }
+            Install::Other { package, version } => {
+                let installed = session.install_package(&package, &version)?;
+                println!(
+                    "Installed {} v{} with node v{} (executables: {})",
+                    package,
+                    installed.version,
+                    installed.node,
+                    installed.bins.join(", ")
+                );
+            }
+            Install::DryRun(install) => {
+                let plan = match *install {
+                    Install::Node(requirements) => session.plan_default_node(&requirements)?,
+                    Install::PickNode => match pick_node(session)? {
+                        Some(requirements) => session.plan_default_node(&requirements)?,
+                        None => Plan::new(),
+                    },
+                    Install::Yarn(requirements) => session.plan_default_yarn(&requirements)?,
+                    Install::Other { package, version } => {
+                        session.plan_package_install(&package, &version)?
+                    }
+                    Install::Help
+                    | Install::NodeFromSource(_)
+                    | Install::NodeUrl { .. }
+                    | Install::DryRun(_) => {
+                        unreachable!()
+                    }
+                };
+                println!("{}", plan);
             }
-            Install::Other {
-                package,
-                version: _,
-            } => throw!(CommandUnimplementedError::new(&format!(
-                "notion install {}",
-                package
-            ))),
         };
         session.add_event_end(ActivityKind::Install, ExitCode::Success);
         Ok(())
     }
 }
+
+/// Asks the user which recent release of Node to install. Produces `None` if they
+/// don't pick one.
+fn pick_node(session: &Session) -> Fallible<Option<VersionSpec>> {
+    let releases = session.recent_node_releases()?;
+    let choices: Vec<String> = releases
+        .iter()
+        .map(|&(ref version, ref data)| match data.lts {
+            Some(ref lts) => format!("v{} (LTS: {})", version, lts),
+            None => format!("v{} (current)", version),
+        })
+        .collect();
+    match pick("Which version of Node do you want to install?", &choices).unknown()? {
+        Some(index) => Ok(Some(VersionSpec::exact(&releases[index].0))),
+        None => {
+            println!("No version of Node picked");
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use std::path::PathBuf;
+
+use notion_core::path;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+use semver::Version;
+
+use Notion;
+use command::{Command, CommandName, Help};
+use output::{emit, Format, Output};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: Option<String>,
+    flag_paths: bool,
+    flag_projects: bool,
+    flag_json: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot list unknown tool '{}' - expected node, yarn, npm, pnpm, or all",
+       name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownToolError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot list the projects that pin {} - only node and yarn pins are recorded",
+       name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnrecordedPinsError {
+    pub(crate) name: &'static str,
+}
+
+/// The tools whose installed versions can be listed.
+#[derive(Clone, Copy)]
+pub(crate) enum Tool {
+    Node,
+    Yarn,
+    Npm,
+    Pnpm,
+}
+
+impl Tool {
+    fn name(&self) -> &'static str {
+        match *self {
+            Tool::Node => "node",
+            Tool::Yarn => "yarn",
+            Tool::Npm => "npm",
+            Tool::Pnpm => "pnpm",
+        }
+    }
+
+    fn install_dir(&self, version: &Version) -> Fallible<PathBuf> {
+        let version = version.to_string();
+        match *self {
+            Tool::Node => path::node_version_dir(&version),
+            Tool::Yarn => path::yarn_version_dir(&version),
+            Tool::Npm => path::npm_version_dir(&version),
+            Tool::Pnpm => path::pnpm_version_dir(&version),
+        }
+    }
+}
+
+pub(crate) enum List {
+    Help,
+    Tools {
+        tools: Vec<Tool>,
+        paths: bool,
+        format: Format,
+    },
+    Projects {
+        tools: Vec<Tool>,
+        format: Format,
+    },
+}
+
+/// The listed versions of each tool.
+#[derive(Serialize)]
+struct Listing {
+    tools: Vec<ToolListing>,
+    #[serde(skip)]
+    paths: bool,
+}
+
+#[derive(Serialize)]
+struct ToolListing {
+    tool: &'static str,
+    versions: Vec<ListedVersion>,
+}
+
+#[derive(Serialize)]
+struct ListedVersion {
+    version: String,
+    default: bool,
+    project: bool,
+    installed: bool,
+    system: bool,
+    path: Option<String>,
+    /// The version of npm bundled with a version of Node, if known.
+    npm: Option<String>,
+}
+
+/// The registered projects, and the versions they pin.
+#[derive(Serialize)]
+struct ProjectListing {
+    projects: Vec<ListedProject>,
+}
+
+#[derive(Serialize)]
+struct ListedProject {
+    path: String,
+    file: String,
+    node: Option<String>,
+    yarn: Option<String>,
+}
+
+impl Output for ProjectListing {
+    fn to_human(&self) -> String {
+        if self.projects.is_empty() {
+            return "(no projects registered)".to_string();
+        }
+        let mut lines = vec![];
+        for project in &self.projects {
+            lines.push(format!("{}:", project.path));
+            if let Some(ref version) = project.node {
+                lines.push(format!("    node v{}", version));
+            }
+            if let Some(ref version) = project.yarn {
+                lines.push(format!("    yarn v{}", version));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl Output for Listing {
+    fn to_human(&self) -> String {
+        let mut lines = vec![];
+        for tool in &self.tools {
+            lines.push(format!("{}:", tool.tool));
+            if tool.versions.is_empty() {
+                lines.push("    (none installed)".to_string());
+            }
+            for version in &tool.versions {
+                let mut marks = vec![];
+                if version.default {
+                    marks.push("default");
+                }
+                if version.project {
+                    marks.push("project");
+                }
+                if version.system {
+                    marks.push("system");
+                }
+                if !version.installed {
+                    marks.push("not installed");
+                }
+
+                let mut line = format!("    v{}", version.version);
+                if !marks.is_empty() {
+                    line.push_str(&format!(" ({})", marks.join(", ")));
+                }
+                if let Some(ref npm) = version.npm {
+                    line.push_str(&format!(" with npm v{}", npm));
+                }
+                match version.path {
+                    Some(ref path) if self.paths => line.push_str(&format!("  {}", path)),
+                    _ => {}
+                }
+                lines.push(line);
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl Command for List {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+List the installed versions of tools
+
+Usage:
+    notion list [options] [<tool>]
+    notion list --projects [options] [<tool>]
+    notion list -h | --help
+
+Options:
+    -h, --help     Display this message
+    --paths        Show the install directory of each version
+    --projects     List the projects that pin versions, instead of the versions
+    --json         List the versions (or projects) as JSON
+
+The tool may be node, yarn, npm, pnpm, or all (the default). Versions are marked
+with (default) if they are the user's default, which is used outside of projects,
+with (project) if the current project pins them, and with (system) if only the
+read-only system install (NOTION_SYSTEM_HOME) provides them. A version pinned by the
+current project that isn't installed yet is listed as well. Each version of Node is
+listed with the version of npm it bundles, where the Node index records it.
+
+With --json, the output is an object with a `tools` field, listing an object for
+each tool with the fields `tool` (its name) and `versions`, which lists an object
+for each version with the fields:
+
+    version    The version, without a leading v
+    default    Whether the version is the user's default
+    project    Whether the current project pins the version
+    installed  Whether the version is installed
+    system     Whether only the system install provides the version
+    path       The install directory of the version, or null if not installed
+    npm        The version of npm bundled with a version of Node, or null if
+               unknown (and for other tools)
+
+With --projects, the projects Notion has registered by pinning, installing, or
+running their toolchains are listed, each with the versions of Node and Yarn it
+pins. Only node and yarn pins are recorded, so the tool may be node, yarn, or all,
+and only projects that pin that tool are listed. Projects whose toolchain files no
+longer exist are forgotten. As JSON, the output is an object with a `projects`
+field, listing an object for each project with the fields `path` (its directory),
+`file` (the file that pins its versions), `node`, and `yarn` (the pinned versions,
+or null).
+";
+
+    fn help() -> Self {
+        List::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            flag_paths,
+            flag_projects,
+            flag_json,
+        }: Args,
+    ) -> Fallible<Self> {
+        let tools = match arg_tool.as_ref().map(|tool| &tool[..]) {
+            None | Some("all") => vec![Tool::Node, Tool::Yarn, Tool::Npm, Tool::Pnpm],
+            Some("node") => vec![Tool::Node],
+            Some("yarn") => vec![Tool::Yarn],
+            Some("npm") => vec![Tool::Npm],
+            Some("pnpm") => vec![Tool::Pnpm],
+            Some(name) => {
+                throw!(UnknownToolError {
+                    name: name.to_string(),
+                });
+            }
+        };
+        let format = Format::from_json_flag(flag_json);
+        if flag_projects {
+            // Asking for all tools lists the projects that pin node or yarn, but asking
+            // for npm or pnpm alone could only ever list nothing.
+            match tools[..] {
+                [tool @ Tool::Npm] | [tool @ Tool::Pnpm] => {
+                    throw!(UnrecordedPinsError { name: tool.name() });
+                }
+                _ => {}
+            }
+            let tools = tools
+                .into_iter()
+                .filter(|tool| match *tool {
+                    Tool::Node | Tool::Yarn => true,
+                    _ => false,
+                })
+                .collect();
+            return Ok(List::Projects { tools, format });
+        }
+        Ok(List::Tools {
+            tools,
+            paths: flag_paths,
+            format,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::List);
+        match self {
+            List::Help => Help::Command(CommandName::List).run(session)?,
+            List::Tools {
+                tools,
+                paths,
+                format,
+            } => {
+                let mut listing = Listing {
+                    tools: vec![],
+                    paths,
+                };
+                for tool in tools {
+                    listing.tools.push(list_tool(session, tool)?);
+                }
+                emit(&listing, format)?;
+            }
+            List::Projects { tools, format } => {
+                emit(&list_projects(session, &tools)?, format)?;
+            }
+        };
+        session.add_event_end(ActivityKind::List, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn list_tool(session: &Session, tool: Tool) -> Fallible<ToolListing> {
+    let catalog = session.catalog()?;
+    let (versions, system, default) = match tool {
+        Tool::Node => (&catalog.node.versions, &catalog.node.system, &catalog.node.default),
+        Tool::Yarn => (&catalog.yarn.versions, &catalog.yarn.system, &catalog.yarn.default),
+        Tool::Npm => (&catalog.npm.versions, &catalog.npm.system, &catalog.npm.default),
+        Tool::Pnpm => (&catalog.pnpm.versions, &catalog.pnpm.system, &catalog.pnpm.default),
+    };
+
+    // Only report the project's pins, rather than resolving the versions the project
+    // would actually use, so that listing never has to fetch anything.
+    let pinned = session.project().and_then(|project| {
+        let manifest = project.manifest();
+        match tool {
+            Tool::Node => manifest.node(),
+            Tool::Yarn => manifest.yarn(),
+            Tool::Npm => manifest.npm(),
+            Tool::Pnpm => manifest.pnpm(),
+        }
+    });
+
+    let bundled_npm = |version: &Version| match tool {
+        Tool::Node => catalog.bundled_npm(version).map(|npm| npm.to_string()),
+        _ => None,
+    };
+
+    let mut listed = vec![];
+    for version in versions {
+        listed.push(ListedVersion {
+            version: version.to_string(),
+            default: default.as_ref() == Some(version),
+            project: pinned.as_ref() == Some(version),
+            installed: true,
+            system: system.contains(version),
+            path: Some(tool.install_dir(version)?.to_string_lossy().to_string()),
+            npm: bundled_npm(version),
+        });
+    }
+    if let Some(ref version) = pinned {
+        if !versions.contains(version) {
+            listed.push(ListedVersion {
+                version: version.to_string(),
+                default: false,
+                project: true,
+                installed: false,
+                system: false,
+                path: None,
+                npm: bundled_npm(version),
+            });
+        }
+    }
+    Ok(ToolListing {
+        tool: tool.name(),
+        versions: listed,
+    })
+}
+
+fn list_projects(session: &Session, tools: &[Tool]) -> Fallible<ProjectListing> {
+    let references = session.registered_projects()?;
+    let mut projects = vec![];
+    for (file, pins) in references.projects() {
+        let pins_tool = tools.iter().any(|tool| match *tool {
+            Tool::Node => pins.node.is_some(),
+            Tool::Yarn => pins.yarn.is_some(),
+            _ => false,
+        });
+        if !pins_tool {
+            continue;
+        }
+        let dir = file.parent().unwrap_or(file);
+        projects.push(ListedProject {
+            path: dir.to_string_lossy().to_string(),
+            file: file.to_string_lossy().to_string(),
+            node: pins.node.as_ref().map(|version| version.to_string()),
+            yarn: pins.yarn.as_ref().map(|version| version.to_string()),
+        });
+    }
+    Ok(ProjectListing { projects })
+}
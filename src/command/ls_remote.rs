@@ -0,0 +1,298 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::style::format_columns;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+use output::{emit, Format, Output};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: Option<String>,
+    flag_lts: bool,
+    flag_lts_name: Option<String>,
+    flag_since: Option<String>,
+    flag_security: bool,
+    flag_refresh: bool,
+    flag_json: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "invalid date '{}' - expected a date like 2018-01-31", date)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct InvalidDateError {
+    pub(crate) date: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no {} versions match the specified filters", tool)]
+#[notion_fail(code = "NoVersionMatch")]
+pub(crate) struct NoMatchingVersionsError {
+    pub(crate) tool: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "cannot list the versions of unknown tool '{}' - expected node or yarn",
+       name)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct UnknownToolError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "{} only applies to Node versions", flag)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NodeOnlyFilterError {
+    pub(crate) flag: String,
+}
+
+/// Checks that a date has the `YYYY-MM-DD` form used by the Node index, so that dates
+/// can be compared as strings.
+fn is_date(date: &str) -> bool {
+    date.len() == 10 && date.chars().enumerate().all(|(i, c)| match i {
+        4 | 7 => c == '-',
+        _ => c.is_ascii_digit(),
+    })
+}
+
+/// Filters on the versions listed by `notion ls-remote`.
+pub(crate) struct Filters {
+    /// Only list LTS versions, optionally only those of the named release line.
+    lts: Option<Option<String>>,
+    /// Only list versions released on or after this date.
+    since: Option<String>,
+    /// Only list security releases.
+    security: bool,
+}
+
+pub(crate) enum LsRemote {
+    Help,
+    List(Filters, bool, Format),
+    Yarn(Option<String>, bool, Format),
+}
+
+/// The versions available for download.
+#[derive(Serialize)]
+struct Available {
+    versions: Vec<AvailableVersion>,
+}
+
+#[derive(Serialize)]
+struct AvailableVersion {
+    version: String,
+    date: Option<String>,
+    lts: Option<String>,
+    security: bool,
+    npm: Option<String>,
+}
+
+impl Output for Available {
+    fn to_human(&self) -> String {
+        let rows: Vec<Vec<String>> = self.versions
+            .iter()
+            .map(|available| {
+                vec![
+                    format!("v{}", available.version),
+                    available.date.clone().unwrap_or_default(),
+                    available.lts.clone().unwrap_or_default(),
+                    available
+                        .npm
+                        .as_ref()
+                        .map(|npm| format!("npm v{}", npm))
+                        .unwrap_or_default(),
+                    if available.security {
+                        "security".to_string()
+                    } else {
+                        String::new()
+                    },
+                ]
+            })
+            .collect();
+        format_columns(&rows)
+    }
+}
+
+impl Command for LsRemote {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+List the Node or Yarn versions available for download
+
+Usage:
+    notion ls-remote [options] [<tool>]
+    notion ls-remote -h | --help
+
+Options:
+    -h, --help            Display this message
+    --lts                 Only list long-term support versions
+    --lts-name <name>     Only list versions of the named LTS line (e.g. carbon)
+    --since <date>        Only list versions released on or after a date (YYYY-MM-DD)
+    --security            Only list security releases
+    --refresh             Download the index afresh instead of using the cached copy
+    --json                List the versions as JSON
+
+The tool is node (the default) or yarn.
+
+Node versions are listed from the public Node index, or from the index listed by
+the `node.ls-remote` plugin if one is configured. The public index is cached
+until the server says it expires. For a day after that, the expired copy is
+still listed while a fresh one is downloaded in the background for the next
+command.
+
+Yarn versions are listed from the releases of the Yarn repository on GitHub.
+The index is cached for the time of the `yarn.index-ttl` setting (four hours
+if not configured). --lts, --lts-name, and --security only apply to Node.
+The requests to GitHub are authenticated with the `github.token` setting (or
+GITHUB_TOKEN) if set; only sixty unauthenticated requests are allowed an hour.
+When GitHub rate limits them, the cached index is listed however old it is.
+
+With --json, the output is an object with a `versions` field, listing an object
+for each version with the fields:
+
+    version   The version, without a leading v
+    date      The release date (YYYY-MM-DD), or null if unknown
+    lts       The lowercase name of the version's LTS line, or null
+    security  Whether the version is a security release
+    npm       The version of npm bundled with the version, or null if unknown
+";
+
+    fn help() -> Self {
+        LsRemote::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            flag_lts,
+            flag_lts_name,
+            flag_since,
+            flag_security,
+            flag_refresh,
+            flag_json,
+        }: Args,
+    ) -> Fallible<Self> {
+        if let Some(ref date) = flag_since {
+            if !is_date(date) {
+                throw!(InvalidDateError { date: date.clone() });
+            }
+        }
+
+        match arg_tool.as_ref().map(|tool| &tool[..]) {
+            None | Some("node") => {}
+            Some("yarn") => {
+                if flag_lts || flag_lts_name.is_some() || flag_security {
+                    let flag = if flag_security {
+                        "--security"
+                    } else if flag_lts_name.is_some() {
+                        "--lts-name"
+                    } else {
+                        "--lts"
+                    };
+                    throw!(NodeOnlyFilterError {
+                        flag: flag.to_string(),
+                    });
+                }
+                return Ok(LsRemote::Yarn(
+                    flag_since,
+                    flag_refresh,
+                    Format::from_json_flag(flag_json),
+                ));
+            }
+            Some(name) => throw!(UnknownToolError {
+                name: name.to_string(),
+            }),
+        }
+
+        let lts = match flag_lts_name {
+            Some(name) => Some(Some(name.to_lowercase())),
+            None if flag_lts => Some(None),
+            None => None,
+        };
+
+        Ok(LsRemote::List(
+            Filters {
+                lts,
+                since: flag_since,
+                security: flag_security,
+            },
+            flag_refresh,
+            Format::from_json_flag(flag_json),
+        ))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::LsRemote);
+        match self {
+            LsRemote::Help => Help::Command(CommandName::LsRemote).run(session)?,
+            LsRemote::List(filters, refresh, format) => {
+                if refresh {
+                    session.refresh_node_index()?;
+                }
+                let mut versions = vec![];
+                for (version, data) in session.remote_node_versions()? {
+                    let matches_lts = match filters.lts {
+                        None => true,
+                        Some(None) => data.lts.is_some(),
+                        Some(Some(ref name)) => data.lts
+                            .as_ref()
+                            .map_or(false, |lts| lts.to_lowercase() == *name),
+                    };
+                    let matches_since = match (&filters.since, &data.date) {
+                        (&None, _) => true,
+                        (&Some(ref since), &Some(ref date)) => date >= since,
+                        (&Some(_), &None) => false,
+                    };
+                    if !matches_lts || !matches_since || (filters.security && !data.security) {
+                        continue;
+                    }
+
+                    versions.push(AvailableVersion {
+                        version: version.to_string(),
+                        date: data.date.clone(),
+                        lts: data.lts.clone().map(|lts| lts.to_lowercase()),
+                        security: data.security,
+                        npm: data.npm.as_ref().map(|npm| npm.to_string()),
+                    });
+                }
+
+                if versions.is_empty() {
+                    throw!(NoMatchingVersionsError {
+                        tool: "Node".to_string(),
+                    });
+                }
+                emit(&Available { versions }, format)?;
+            }
+            LsRemote::Yarn(since, refresh, format) => {
+                if refresh {
+                    session.refresh_yarn_index()?;
+                }
+                let versions: Vec<AvailableVersion> = session
+                    .remote_yarn_releases()?
+                    .into_iter()
+                    .filter(|release| match (&since, &release.date) {
+                        (&None, _) => true,
+                        (&Some(ref since), &Some(ref date)) => date >= since,
+                        (&Some(_), &None) => false,
+                    })
+                    .map(|release| AvailableVersion {
+                        version: release.version.to_string(),
+                        date: release.date,
+                        lts: None,
+                        security: false,
+                        npm: None,
+                    })
+                    .collect();
+
+                if versions.is_empty() {
+                    throw!(NoMatchingVersionsError {
+                        tool: "Yarn".to_string(),
+                    });
+                }
+                emit(&Available { versions }, format)?;
+            }
+        };
+        session.add_event_end(ActivityKind::LsRemote, ExitCode::Success);
+        Ok(())
+    }
+}
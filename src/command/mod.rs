@@ -1,22 +1,76 @@
+mod activate;
+mod completions;
 mod config;
 mod current;
 mod deactivate;
+mod default;
+mod doctor;
+mod env;
+mod exec;
+mod export;
 mod fetch;
+mod gc;
 mod help;
+mod hook;
+mod import;
+mod info;
 mod install;
+mod list;
+mod ls_remote;
+mod outdated;
+mod pin;
+mod prompt;
+mod query;
+mod run;
+mod self_update;
+mod setup;
 mod shim;
+mod telemetry;
+mod uninstall;
+mod unpin;
+mod update;
 mod use_;
 mod version;
+mod which;
+mod why;
+mod x;
 
+pub(crate) use self::activate::Activate;
+pub(crate) use self::completions::Completions;
 pub(crate) use self::config::Config;
 pub(crate) use self::current::Current;
 pub(crate) use self::deactivate::Deactivate;
+pub(crate) use self::default::Default;
+pub(crate) use self::doctor::Doctor;
+pub(crate) use self::env::Env;
+pub(crate) use self::exec::Exec;
+pub(crate) use self::export::Export;
 pub(crate) use self::fetch::Fetch;
+pub(crate) use self::gc::Gc;
 pub(crate) use self::help::Help;
+pub(crate) use self::hook::Hook;
+pub(crate) use self::import::Import;
+pub(crate) use self::info::Info;
 pub(crate) use self::install::Install;
+pub(crate) use self::list::List;
+pub(crate) use self::ls_remote::LsRemote;
+pub(crate) use self::outdated::Outdated;
+pub(crate) use self::pin::Pin;
+pub(crate) use self::prompt::Prompt;
+pub(crate) use self::query::Query;
+pub(crate) use self::run::Run;
+pub(crate) use self::self_update::SelfUpdate;
+pub(crate) use self::setup::Setup;
 pub(crate) use self::shim::Shim;
+pub(crate) use self::telemetry::Telemetry;
+pub(crate) use self::uninstall::Uninstall;
+pub(crate) use self::unpin::Unpin;
+pub(crate) use self::update::Update;
 pub(crate) use self::use_::Use;
 pub(crate) use self::version::Version;
+pub(crate) use self::which::Which;
+pub(crate) use self::why::Why;
+pub(crate) use self::x::X;
 
 use docopt::Docopt;
 use serde::de::DeserializeOwned;
@@ -34,15 +88,85 @@ use std::str::FromStr;
 pub(crate) enum CommandName {
     Fetch,
     Install,
+    Import,
+    Export,
+    Default,
+    Uninstall,
+    Gc,
+    List,
+    #[serde(rename = "ls-remote")]
+    LsRemote,
+    Info,
+    Outdated,
+    Pin,
+    Unpin,
+    Update,
     Use,
     Config,
     Current,
+    Prompt,
+    Query,
+    Activate,
     Deactivate,
+    Setup,
+    Hook,
+    Env,
+    Completions,
+    Exec,
+    Run,
+    X,
     Shim,
+    Which,
+    Why,
+    Doctor,
+    SelfUpdate,
+    Telemetry,
     Help,
     Version,
 }
 
+impl CommandName {
+    /// Every Notion command, in the order `notion help` lists them.
+    pub(crate) const ALL: &'static [CommandName] = &[
+        CommandName::Fetch,
+        CommandName::Install,
+        CommandName::Import,
+        CommandName::Export,
+        CommandName::Default,
+        CommandName::Uninstall,
+        CommandName::Gc,
+        CommandName::List,
+        CommandName::LsRemote,
+        CommandName::Info,
+        CommandName::Outdated,
+        CommandName::Pin,
+        CommandName::Unpin,
+        CommandName::Update,
+        CommandName::Use,
+        CommandName::Config,
+        CommandName::Current,
+        CommandName::Prompt,
+        CommandName::Query,
+        CommandName::Activate,
+        CommandName::Deactivate,
+        CommandName::Setup,
+        CommandName::Hook,
+        CommandName::Env,
+        CommandName::Completions,
+        CommandName::Exec,
+        CommandName::Run,
+        CommandName::X,
+        CommandName::Shim,
+        CommandName::Which,
+        CommandName::Why,
+        CommandName::Doctor,
+        CommandName::SelfUpdate,
+        CommandName::Telemetry,
+        CommandName::Help,
+        CommandName::Version,
+    ];
+}
+
 impl Display for CommandName {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(
@@ -51,11 +175,38 @@ impl Display for CommandName {
             match *self {
                 CommandName::Fetch => "fetch",
                 CommandName::Install => "install",
+                CommandName::Import => "import",
+                CommandName::Export => "export",
+                CommandName::Default => "default",
+                CommandName::Uninstall => "uninstall",
+                CommandName::Gc => "gc",
+                CommandName::List => "list",
+                CommandName::LsRemote => "ls-remote",
+                CommandName::Info => "info",
+                CommandName::Outdated => "outdated",
+                CommandName::Pin => "pin",
+                CommandName::Unpin => "unpin",
+                CommandName::Update => "update",
                 CommandName::Use => "use",
                 CommandName::Config => "config",
+                CommandName::Activate => "activate",
                 CommandName::Deactivate => "deactivate",
                 CommandName::Current => "current",
+                CommandName::Prompt => "prompt",
+                CommandName::Query => "query",
+                CommandName::Setup => "setup",
+                CommandName::Hook => "hook",
+                CommandName::Env => "env",
+                CommandName::Completions => "completions",
+                CommandName::Exec => "exec",
+                CommandName::Run => "run",
+                CommandName::X => "x",
                 CommandName::Shim => "shim",
+                CommandName::Which => "which",
+                CommandName::Why => "why",
+                CommandName::Doctor => "doctor",
+                CommandName::SelfUpdate => "self-update",
+                CommandName::Telemetry => "telemetry",
                 CommandName::Help => "help",
                 CommandName::Version => "version",
             }
@@ -70,11 +221,38 @@ impl FromStr for CommandName {
         Ok(match s {
             "fetch" => CommandName::Fetch,
             "install" => CommandName::Install,
+            "import" => CommandName::Import,
+            "export" => CommandName::Export,
+            "default" => CommandName::Default,
+            "uninstall" => CommandName::Uninstall,
+            "gc" => CommandName::Gc,
+            "list" => CommandName::List,
+            "ls-remote" => CommandName::LsRemote,
+            "info" => CommandName::Info,
+            "outdated" => CommandName::Outdated,
+            "pin" => CommandName::Pin,
+            "unpin" => CommandName::Unpin,
+            "update" => CommandName::Update,
             "use" => CommandName::Use,
             "config" => CommandName::Config,
             "current" => CommandName::Current,
+            "prompt" => CommandName::Prompt,
+            "query" => CommandName::Query,
+            "activate" => CommandName::Activate,
             "deactivate" => CommandName::Deactivate,
+            "setup" => CommandName::Setup,
+            "hook" => CommandName::Hook,
+            "env" => CommandName::Env,
+            "completions" => CommandName::Completions,
+            "exec" => CommandName::Exec,
+            "run" => CommandName::Run,
+            "x" => CommandName::X,
             "shim" => CommandName::Shim,
+            "which" => CommandName::Which,
+            "why" => CommandName::Why,
+            "doctor" => CommandName::Doctor,
+            "self-update" => CommandName::SelfUpdate,
+            "telemetry" => CommandName::Telemetry,
             "help" => CommandName::Help,
             "version" => CommandName::Version,
             _ => {
@@ -127,3 +305,41 @@ pub(crate) trait Command: Sized {
         }
     }
 }
+
+/// Splits the `<tool>@<version>` shorthand into the tool and its version, unless the
+/// version was given as an argument of its own. The split is at the last `@`, so the
+/// leading `@` of a scoped package like `@scope/name` is left alone.
+pub(crate) fn split_tool_version(tool: &str, version: Option<String>) -> (String, Option<String>) {
+    match (tool.rfind('@'), version) {
+        (Some(index), None) if index > 0 => (
+            tool[..index].to_string(),
+            Some(tool[index + 1..].to_string()),
+        ),
+        (_, version) => (tool.to_string(), version),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use command::split_tool_version;
+
+    #[test]
+    fn test_split_tool_version() {
+        let split = |tool: &str, version: Option<&str>| {
+            split_tool_version(tool, version.map(str::to_string))
+        };
+        let expected = |tool: &str, version: Option<&str>| {
+            (tool.to_string(), version.map(str::to_string))
+        };
+
+        assert_eq!(split("node", None), expected("node", None));
+        assert_eq!(split("node@10", None), expected("node", Some("10")));
+        assert_eq!(split("node", Some("10")), expected("node", Some("10")));
+        assert_eq!(split("@scope/pkg", None), expected("@scope/pkg", None));
+        assert_eq!(
+            split("@scope/pkg@1.0", None),
+            expected("@scope/pkg", Some("1.0"))
+        );
+    }
+}
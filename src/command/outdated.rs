@@ -0,0 +1,157 @@
+use semver::Version;
+
+use notion_core::outdated::Updates;
+use notion_core::session::{ActivityKind, Session};
+use notion_core::style::format_columns;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+use output::{emit, Format, Output};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_json: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no toolchain is pinned in the current project")]
+#[notion_fail(code = "ConfigurationError")]
+pub(crate) struct NoToolchainError;
+
+pub(crate) enum Outdated {
+    Help,
+    Compare(Format),
+}
+
+/// The updates available for the tools pinned in the current project.
+#[derive(Serialize)]
+struct Comparison {
+    tools: Vec<ToolComparison>,
+}
+
+#[derive(Serialize)]
+struct ToolComparison {
+    tool: &'static str,
+    pinned: String,
+    patch: Option<String>,
+    lts: Option<String>,
+    latest: Option<String>,
+    outdated: bool,
+}
+
+impl ToolComparison {
+    fn new(tool: &'static str, pinned: &Version, updates: Updates) -> Self {
+        ToolComparison {
+            tool,
+            pinned: pinned.to_string(),
+            outdated: updates.is_outdated(pinned),
+            patch: updates.patch.map(|version| version.to_string()),
+            lts: updates.lts.map(|version| version.to_string()),
+            latest: updates.latest.map(|version| version.to_string()),
+        }
+    }
+}
+
+impl Output for Comparison {
+    fn to_human(&self) -> String {
+        let column = |version: &Option<String>| {
+            version
+                .as_ref()
+                .map_or("-".to_string(), |version| format!("v{}", version))
+        };
+        let mut rows = vec![vec![
+            "tool".to_string(),
+            "pinned".to_string(),
+            "patch".to_string(),
+            "lts".to_string(),
+            "latest".to_string(),
+        ]];
+        for tool in &self.tools {
+            rows.push(vec![
+                tool.tool.to_string(),
+                format!("v{}", tool.pinned),
+                column(&tool.patch),
+                column(&tool.lts),
+                column(&tool.latest),
+            ]);
+        }
+        format_columns(&rows)
+    }
+}
+
+impl Command for Outdated {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Compare the project's pinned tools with the newest versions
+
+Usage:
+    notion outdated [options]
+    notion outdated -h | --help
+
+Options:
+    -h, --help     Display this message
+    --json         Print the comparison as JSON
+
+For the Node and Yarn versions pinned in the current project's toolchain, lists the
+newest version of the same minor version (patch), the newest long-term support
+version (lts, for Node only), and the newest version of all (latest), from the
+versions available for download. Prereleases are never listed.
+
+With --json, the output is an object with a `tools` field, listing an object for each
+pinned tool with the fields:
+
+    tool      The name of the tool: node or yarn
+    pinned    The pinned version, without a leading v
+    patch     The newest version of the pinned minor version, or null
+    lts       The newest long-term support version, or null
+    latest    The newest version, or null
+    outdated  Whether any version newer than the pinned version is available
+";
+
+    fn help() -> Self {
+        Outdated::Help
+    }
+
+    fn parse(_: Notion, Args { flag_json }: Args) -> Fallible<Self> {
+        Ok(Outdated::Compare(Format::from_json_flag(flag_json)))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Outdated);
+        match self {
+            Outdated::Help => Help::Command(CommandName::Outdated).run(session)?,
+            Outdated::Compare(format) => {
+                let toolchain = match session.project() {
+                    Some(project) if project.is_pinned() => {
+                        let manifest = project.manifest();
+                        (manifest.node(), manifest.yarn())
+                    }
+                    _ => throw!(NoToolchainError),
+                };
+
+                let mut tools = vec![];
+                if let Some(ref node) = toolchain.0 {
+                    let available = session.remote_node_versions()?;
+                    let updates = Updates::find(
+                        node,
+                        available
+                            .iter()
+                            .map(|&(ref version, ref data)| (version, data.lts.is_some())),
+                    );
+                    tools.push(ToolComparison::new("node", node, updates));
+                }
+                if let Some(ref yarn) = toolchain.1 {
+                    let available = session.remote_yarn_versions()?;
+                    let updates =
+                        Updates::find(yarn, available.iter().map(|version| (version, false)));
+                    tools.push(ToolComparison::new("yarn", yarn, updates));
+                }
+                emit(&Comparison { tools }, format)?;
+            }
+        };
+        session.add_event_end(ActivityKind::Outdated, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use result::ResultOptionExt;
+
+use Notion;
+use command::{split_tool_version, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    arg_version: Option<String>,
+    flag_sync_engines: bool,
+    flag_no_sync_engines: bool,
+    flag_dry_run: bool,
+}
+
+// error message for pinning tools that are not node|yarn
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "pinning tool '{}' not yet implemented - for now you can manually edit package.json",
+       name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoCustomPinError {
+    pub(crate) name: String,
+}
+
+impl NoCustomPinError {
+    pub(crate) fn new(name: String) -> Self {
+        NoCustomPinError { name: name }
+    }
+}
+
+pub(crate) enum Pin {
+    Help,
+    Node {
+        version: VersionSpec,
+        sync_engines: Option<bool>,
+        dry_run: bool,
+    },
+    Yarn { version: VersionSpec, dry_run: bool },
+    Other { name: String, version: VersionSpec },
+}
+
+impl Command for Pin {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Pin a tool in the current project's toolchain
+
+Usage:
+    notion pin [options] <tool> [<version>]
+    notion pin -h | --help
+
+Options:
+    -h, --help          Display this message
+    --sync-engines      Also write a compatible range to `engines.node`
+    --no-sync-engines   Leave `engines.node` as it is
+    -n, --dry-run       Show the changes pinning would make, without making them
+
+The version may also be given as part of the tool, e.g. `notion pin node@^10`.
+Version ranges are resolved to the newest matching release, and that exact
+version is written to the `toolchain` section of package.json, or of the
+project's `.notion.toml` pin file. Outside of any project, pinning Node starts a
+`.notion.toml` pin file in the current directory.
+
+Yarn may also be pinned by a dist-tag of its package on the npm registry, e.g.
+`notion pin yarn@latest` or `notion pin yarn@berry` (the newest Yarn 2 or later),
+which is likewise resolved to the version it tags.
+
+With --sync-engines, pinning Node also writes a range compatible with the pinned
+version (`^` the version) to the `engines.node` key of package.json, which is what
+npm and Yarn read, so that it stays in step with the pin. Only that key is changed.
+The `sync-engines` setting of the `[node]` section of the configuration makes this
+the default, which --no-sync-engines overrides.
+
+With --dry-run, the version is still resolved, but instead of being pinned, the
+changes pinning would make are listed, along with the lines of each file it would
+write.
+";
+
+    fn help() -> Self {
+        Pin::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            arg_version,
+            flag_sync_engines,
+            flag_no_sync_engines,
+            flag_dry_run,
+        }: Args,
+    ) -> Fallible<Self> {
+        let (tool, arg_version) = split_tool_version(&arg_tool, arg_version);
+
+        let version = arg_version
+            .map(VersionSpec::parse)
+            .invert()?
+            .unwrap_or_default();
+
+        match &tool[..] {
+            "node" => Ok(Pin::Node {
+                version,
+                sync_engines: match (flag_sync_engines, flag_no_sync_engines) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                dry_run: flag_dry_run,
+            }),
+            "yarn" => Ok(Pin::Yarn {
+                version,
+                dry_run: flag_dry_run,
+            }),
+            ref tool => Ok(Pin::Other {
+                name: tool.to_string(),
+                version: version,
+            }),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Pin);
+        match self {
+            Pin::Help => Help::Command(CommandName::Pin).run(session)?,
+            Pin::Node {
+                version,
+                sync_engines,
+                dry_run: true,
+            } => println!("{}", session.plan_node_pin(&version, sync_engines)?),
+            Pin::Node {
+                version,
+                sync_engines,
+                dry_run: false,
+            } => session.pin_node_version(&version, sync_engines)?,
+            Pin::Yarn {
+                version,
+                dry_run: true,
+            } => println!("{}", session.plan_yarn_pin(&version)?),
+            Pin::Yarn {
+                version,
+                dry_run: false,
+            } => session.pin_yarn_version(&version)?,
+            Pin::Other { name, version: _ } => throw!(NoCustomPinError::new(name)),
+        };
+        session.add_event_end(ActivityKind::Pin, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::env;
+
+use docopt::Docopt;
+
+use notion_core::platform;
+use notion_core::session::{ActiveVersion, ActivityKind, Session, VersionSource};
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_format: Option<String>,
+}
+
+pub(crate) enum Prompt {
+    Help,
+    Show(Option<String>),
+}
+
+/// Renders the prompt for the active versions, with the specified format if any.
+fn render(format: Option<&str>, node: &str, yarn: Option<&str>) -> String {
+    match format {
+        Some(format) => format
+            .replace("{node}", node)
+            .replace("{yarn}", yarn.unwrap_or("")),
+        None => match yarn {
+            Some(yarn) => format!("node v{} yarn v{}", node, yarn),
+            None => format!("node v{}", node),
+        },
+    }
+}
+
+impl Prompt {
+    /// Prints the prompt from the versions cached for the current directory (see
+    /// `notion_core::platform`), without loading the session, if the command is
+    /// `notion prompt` and the cache is valid. Returns whether it did. Prompts run
+    /// before every command the user types, so this is their hot path.
+    pub(crate) fn print_cached() -> bool {
+        let argv: Vec<String> = env::args().collect();
+        if argv.get(1).map(String::as_str) != Some("prompt") {
+            return false;
+        }
+        let args: Args = match Docopt::new(Prompt::USAGE).and_then(|d| d.argv(argv).deserialize()) {
+            Ok(args) => args,
+            Err(_) => return false,
+        };
+        let cached = match platform::current() {
+            Some(cached) => cached,
+            None => return false,
+        };
+        println!(
+            "{}",
+            render(
+                args.flag_format.as_ref().map(String::as_str),
+                &cached.node,
+                cached.yarn.as_ref().map(String::as_str)
+            )
+        );
+        true
+    }
+}
+
+/// Renders the prompt for the current directory, if it is in a project that selects a
+/// version of Node, and saves the selection in the cache for the next prompt.
+fn prompt_line(session: &Session, format: Option<&str>) -> Fallible<Option<String>> {
+    if session.project().is_none() {
+        return Ok(None);
+    }
+    let node = match session.active_node()? {
+        Some(active) => active.version.to_string(),
+        None => return Ok(None),
+    };
+    // Only a project's Yarn is shown, as the cache only records that.
+    let yarn = match session.active_yarn()? {
+        Some(ActiveVersion {
+            version,
+            source: VersionSource::Project(_),
+            ..
+        })
+        | Some(ActiveVersion {
+            version,
+            source: VersionSource::PackageManager,
+            ..
+        }) => Some(version.to_string()),
+        _ => None,
+    };
+
+    // the cache is only an optimization, so failing to save it is harmless
+    let _ = session.save_platform();
+    Ok(Some(render(format, &node, yarn.as_ref().map(String::as_str))))
+}
+
+impl Command for Prompt {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Print the active Node (and Yarn) version, for a shell prompt
+
+Usage:
+    notion prompt [options]
+    notion prompt -h | --help
+
+Options:
+    --format <format>  Print the versions in the specified format
+    -h, --help         Display this message
+
+Prints the Node version selected for the current directory, and the Yarn version
+if the project selects one, e.g. `node v10.15.3 yarn v1.12.3`. Outside of a project,
+it prints nothing. With --format, the versions replace the `{node}` and `{yarn}`
+placeholders of the format, e.g. `notion prompt --format 'node:{node}'`; `{yarn}` is
+replaced with nothing if the project selects no Yarn.
+
+This is meant to run in a prompt, such as bash's PS1 or a starship custom command,
+so it is fast: once a shim or a prompt has run in a directory, the versions are read
+from a cache, without loading the project, the configuration, or the catalog. A
+prompt never reports errors; if the versions can't be determined, it prints nothing.
+";
+
+    fn help() -> Self {
+        Prompt::Help
+    }
+
+    fn parse(_: Notion, Args { flag_format }: Args) -> Fallible<Self> {
+        Ok(Prompt::Show(flag_format))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Prompt);
+        match self {
+            Prompt::Help => Help::Command(CommandName::Prompt).run(session)?,
+            Prompt::Show(format) => {
+                if let Ok(Some(line)) = prompt_line(session, format.as_ref().map(String::as_str)) {
+                    println!("{}", line);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Prompt, ExitCode::Success);
+        Ok(())
+    }
+}
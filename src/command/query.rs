@@ -0,0 +1,341 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Value};
+
+use notion_core::api::{Installed, ProjectInfo, Resolution, Resolver, Selection, Source};
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionError, ResultExt};
+
+use command::{Command, CommandName, Help};
+use {CliParseError, Notion};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_stdio: bool,
+}
+
+pub(crate) enum Query {
+    Help,
+    Stdio,
+}
+
+// The error codes that JSON-RPC reserves.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The code of a failure to answer a valid request, whose data has the details.
+const NOTION_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+}
+
+#[derive(Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<ErrorData>,
+}
+
+/// The details of a Notion failure, as `--json` reports them.
+#[derive(Serialize)]
+struct ErrorData {
+    code: &'static str,
+    exit: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<String>,
+}
+
+impl ResponseError {
+    fn new(code: i64, message: String) -> Self {
+        ResponseError {
+            code,
+            message,
+            data: None,
+        }
+    }
+
+    fn from_notion_error(err: &NotionError) -> Self {
+        ResponseError {
+            code: NOTION_ERROR,
+            message: err.to_string(),
+            data: Some(ErrorData {
+                code: err.error_code(),
+                exit: err.exit_code() as i32,
+                suggestion: err.suggestion().map(str::to_string),
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ResolveParams {
+    path: String,
+}
+
+#[derive(Serialize)]
+struct ResolveResult {
+    project: Option<ProjectResult>,
+    node: Option<SelectionResult>,
+    yarn: Option<SelectionResult>,
+}
+
+#[derive(Serialize)]
+struct ProjectResult {
+    root: PathBuf,
+    toolchain_file: PathBuf,
+    workspace_root: Option<PathBuf>,
+    node: Option<String>,
+    yarn: Option<String>,
+    npm: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SelectionResult {
+    version: String,
+    source: &'static str,
+    bin_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct InstalledResult {
+    node: Vec<String>,
+    yarn: Vec<String>,
+    default_node: Option<String>,
+    default_yarn: Option<String>,
+}
+
+/// The stable name of where a version comes from, as `--json` names it.
+fn source_kind(source: &Source) -> &'static str {
+    match source {
+        &Source::Project(_) => "project",
+        &Source::PackageManager => "package-manager",
+        &Source::NodeVersionFile(_) => "node-version-file",
+        &Source::Engines => "engines",
+        &Source::Override => "override",
+        &Source::Default => "user",
+    }
+}
+
+impl SelectionResult {
+    fn new(selection: Selection) -> Self {
+        SelectionResult {
+            version: selection.version,
+            source: source_kind(&selection.source),
+            bin_dir: selection.bin_dir,
+        }
+    }
+}
+
+impl ResolveResult {
+    fn new(resolution: Resolution) -> Self {
+        ResolveResult {
+            project: resolution.project.map(|project: ProjectInfo| ProjectResult {
+                root: project.root,
+                toolchain_file: project.toolchain_file,
+                workspace_root: project.workspace_root,
+                node: project.node,
+                yarn: project.yarn,
+                npm: project.npm,
+            }),
+            node: resolution.node.map(SelectionResult::new),
+            yarn: resolution.yarn.map(SelectionResult::new),
+        }
+    }
+}
+
+impl InstalledResult {
+    fn new(installed: Installed) -> Self {
+        InstalledResult {
+            node: installed.node,
+            yarn: installed.yarn,
+            default_node: installed.default_node,
+            default_yarn: installed.default_yarn,
+        }
+    }
+}
+
+/// The directory to resolve for a path: the path itself if it is a directory, or else
+/// the directory it is in (editors ask about the files they edit). A relative path is
+/// relative to the current directory.
+fn resolve_dir(path: &str) -> Fallible<PathBuf> {
+    let path = env::current_dir().unknown()?.join(Path::new(path));
+    if path.is_dir() {
+        return Ok(path);
+    }
+    Ok(path.parent().map_or(path.clone(), Path::to_path_buf))
+}
+
+/// Answers the request of a method.
+fn answer(resolver: &mut Resolver, method: &str, params: Value) -> Result<Value, ResponseError> {
+    let to_value =
+        |result: Fallible<Value>| result.map_err(|err| ResponseError::from_notion_error(&err));
+    match method {
+        "resolve" => {
+            let params: ResolveParams = serde_json::from_value(params)
+                .map_err(|error| ResponseError::new(INVALID_PARAMS, error.to_string()))?;
+            to_value(resolve_dir(&params.path).and_then(|dir| {
+                let result = ResolveResult::new(resolver.resolve(&dir)?);
+                serde_json::to_value(result).unknown()
+            }))
+        }
+        "installed" => to_value(resolver.installed().and_then(|installed| {
+            serde_json::to_value(InstalledResult::new(installed)).unknown()
+        })),
+        "exit" => Ok(Value::Null),
+        _ => Err(ResponseError::new(
+            METHOD_NOT_FOUND,
+            format!("no such method: `{}`", method),
+        )),
+    }
+}
+
+/// Answers a line of input, unless it is a notification (a request without an `id`).
+/// Returns the response, and whether the request was to exit.
+fn respond(resolver: &mut Resolver, line: &str) -> (Option<Response>, bool) {
+    let failure = |id: Value, error: ResponseError| Response {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(error),
+    };
+
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(error) => {
+            let error = ResponseError::new(PARSE_ERROR, error.to_string());
+            return (Some(failure(Value::Null, error)), false);
+        }
+    };
+    let request: Request = match serde_json::from_value(value) {
+        Ok(ref request) if request.jsonrpc.as_ref().map(String::as_str) != Some("2.0") => {
+            let error = ResponseError::new(INVALID_REQUEST, "expected JSON-RPC 2.0".to_string());
+            return (Some(failure(request.id.clone().unwrap_or(Value::Null), error)), false);
+        }
+        Ok(request) => request,
+        Err(error) => {
+            let error = ResponseError::new(INVALID_REQUEST, error.to_string());
+            return (Some(failure(Value::Null, error)), false);
+        }
+    };
+
+    let exit = request.method == "exit";
+    let outcome = answer(resolver, &request.method, request.params);
+    let response = request.id.map(|id| match outcome {
+        Ok(result) => Response {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(error) => failure(id, error),
+    });
+    (response, exit)
+}
+
+/// Answers requests from stdin, one per line, until the `exit` method or the end of
+/// the input.
+fn serve() -> Fallible<()> {
+    let mut resolver = Resolver::new();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line.unknown()?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (response, exit) = respond(&mut resolver, &line);
+        if let Some(response) = response {
+            let mut out = stdout.lock();
+            writeln!(out, "{}", serde_json::to_string(&response).unknown()?).unknown()?;
+            out.flush().unknown()?;
+        }
+        if exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl Command for Query {
+    type Args = Args;
+
+    const USAGE: &'static str = r#"
+Answer queries from editors and other long-running programs
+
+Usage:
+    notion query --stdio
+    notion query -h | --help
+
+Options:
+    --stdio        Answer JSON-RPC requests from stdin, until it closes
+    -h, --help     Display this message
+
+Reads JSON-RPC 2.0 requests from stdin, one per line, and writes each response to
+stdout on a line of its own. This saves an editor plugin from starting `notion` (and
+loading the configuration, the project, and the catalog) for every check it makes:
+answers are kept in memory, and reused for as long as the files they depend on are
+unchanged. The methods are:
+
+    resolve    With the params {"path": <path>}, selects the versions of Node and
+               Yarn for the directory at the path (or the directory of the file at
+               the path), as the shims would. The result is an object with the fields
+               `project` (an object with the `root`, `toolchain_file`, and
+               `workspace_root` of the project, and the versions it pins as `node`,
+               `yarn`, and `npm`, or null outside of a project), `node`, and `yarn`
+               (each an object with the `version`, its `source` as `notion current
+               --json` names it, and the `bin_dir` of its executables if it is
+               installed, or null if no version is selected).
+    installed  Lists the installed versions. The result is an object with the fields
+               `node` and `yarn` (the installed versions, oldest first), and
+               `default_node` and `default_yarn` (the user's defaults, or null).
+    exit       Stops answering, with a null result.
+
+A request that fails has an error with the code -32000, whose data has the `code`,
+`exit`, and `suggestion` that `--json` reports failures with. Like every query, none
+of the methods installs anything.
+"#;
+
+    fn help() -> Self {
+        Query::Help
+    }
+
+    fn parse(_: Notion, Args { flag_stdio }: Args) -> Fallible<Self> {
+        if !flag_stdio {
+            throw!(CliParseError {
+                usage: Some(Query::USAGE.to_string()),
+                error: "`notion query` only answers queries over stdio, with --stdio".to_string(),
+            });
+        }
+        Ok(Query::Stdio)
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Query);
+        match self {
+            Query::Help => Help::Command(CommandName::Query).run(session)?,
+            Query::Stdio => serve()?,
+        };
+        session.add_event_end(ActivityKind::Query, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use notion_core::script::lifecycle;
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::exec::ExecError;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_script: String,
+    arg_args: Vec<String>,
+}
+
+pub(crate) enum Run {
+    Help,
+    Script { name: String, args: Vec<String> },
+}
+
+impl Command for Run {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Run a script from the project's package.json
+
+Usage:
+    notion run <script> [--] [<args> ...]
+    notion run -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Runs the named entry of the `scripts` section of the current project's package.json,
+preceded by its `pre` script and followed by its `post` script if they exist, the way
+`npm run` does. The scripts run in the project's root directory with the project's
+toolchain and `node_modules/.bin` first on their PATH, and with the
+`npm_lifecycle_event` and `npm_package_*` environment variables npm sets. Any
+arguments are passed on to the named script; use `--` to separate its own options
+from Notion's.
+";
+
+    fn help() -> Self {
+        Run::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_script,
+            arg_args,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(Run::Script {
+            name: arg_script,
+            args: arg_args,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Run);
+        match self {
+            Run::Help => Help::Command(CommandName::Run).run(session)?,
+            Run::Script { name, args } => {
+                for (event, mut command) in lifecycle(session, &name, &args)? {
+                    let status = command.status().with_context(|error| ExecError {
+                        command: event.clone(),
+                        error: error.to_string(),
+                    })?;
+                    if !status.success() {
+                        // ISSUE (#36): if None, in unix, find out the signal
+                        let code = status.code().unwrap_or(1);
+                        session.add_event_tool_end(ActivityKind::Run, code);
+                        session.exit_tool(code);
+                    }
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Run, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use semver::Version;
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::update;
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_check: bool,
+}
+
+pub(crate) enum SelfUpdate {
+    Help,
+    Check,
+    Update,
+}
+
+impl Command for SelfUpdate {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Update Notion to its latest release
+
+Usage:
+    notion self-update [options]
+    notion self-update -h | --help
+
+Options:
+    -h, --help     Display this message
+    --check        Only report whether a newer release is available
+
+Checks the release channel for a release newer than this one, downloads its executable
+for this platform, verifies it against the published checksums, and puts it in place
+of the running `notion`. A failed or interrupted update leaves the installed Notion as
+it was. The NOTION_UPDATE_URL environment variable names a release channel in place of
+the public one.
+
+Notion also checks for a new release in the background once a day, and mentions it
+after a command. Set `check = false` in the `[updates]` section of the configuration
+(or NOTION_NO_UPDATE_CHECK=1) to turn the checks off, or `interval` to the number of
+hours between them.
+";
+
+    fn help() -> Self {
+        SelfUpdate::Help
+    }
+
+    fn parse(_: Notion, Args { flag_check }: Args) -> Fallible<Self> {
+        Ok(if flag_check {
+            SelfUpdate::Check
+        } else {
+            SelfUpdate::Update
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::SelfUpdate);
+        match self {
+            SelfUpdate::Help => Help::Command(CommandName::SelfUpdate).run(session)?,
+            SelfUpdate::Check => {
+                let current = Version::parse(::VERSION).unknown()?;
+                let release = update::latest(session.config()?)?;
+                if release.is_newer_than(&current) {
+                    println!(
+                        "Notion {} is available (this is {}) - run `notion self-update` to update",
+                        release.version, current
+                    );
+                } else {
+                    println!("Notion {} is the latest release", current);
+                }
+            }
+            SelfUpdate::Update => {
+                update::clean_up()?;
+                let current = Version::parse(::VERSION).unknown()?;
+                let config = session.config()?;
+                let release = update::latest(config)?;
+                if release.is_newer_than(&current) {
+                    let exe = update::install(&release, config)?;
+                    println!(
+                        "Updated Notion from {} to {} in {}",
+                        current,
+                        release.version,
+                        exe.display()
+                    );
+                } else {
+                    println!("Notion {} is the latest release", current);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::SelfUpdate, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::setup::{self, PathUpdate, ProfileUpdate, ShellKind};
+use notion_core::shim::{self, Repair};
+use notion_core::style::display_warning;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    flag_shell: Option<String>,
+    flag_profile: bool,
+}
+
+pub(crate) enum Setup {
+    Help,
+    Print(ShellKind),
+    Profile(ShellKind),
+}
+
+impl Command for Setup {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Set up a shell to use Notion
+
+Usage:
+    notion setup [options]
+    notion setup -h | --help
+
+Options:
+    -h, --help         Display this message
+    --shell <shell>    The shell to set up: bash, zsh, fish, powershell, or cmd
+                       (detected from $SHELL by default)
+    --profile          Repair the shims and add the setup to the shell's profile file
+                       instead of printing it
+
+Prints the shell code that puts the Notion shims on the PATH and lets Notion commands
+like `notion use` change the current shell. With `--profile`, the code is appended to
+~/.bashrc (or ~/.bash_profile), ~/.zshrc, ~/.config/fish/config.fish, or the PowerShell
+profile, unless the profile already sets up Notion. cmd.exe has no profile, so save its
+code as a batch file and run it from the AutoRun command instead.
+
+On Windows, `--profile` also puts the shims at the front of your PATH in the registry,
+so that they work from any terminal or program without a profile (including cmd.exe,
+whose setup is then left out). The shims there are copies of the Notion launcher
+rather than symbolic links, which would take administrator rights to create.
+
+`--profile` also recreates any missing shims and replaces broken ones, for the tools
+Notion manages and for the executables of installed packages. It is safe to run again,
+for instance after upgrading Notion or losing part of its directory.
+";
+
+    fn help() -> Self {
+        Setup::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            flag_shell,
+            flag_profile,
+        }: Args,
+    ) -> Fallible<Self> {
+        let shell = match flag_shell {
+            Some(name) => name.parse()?,
+            None => ShellKind::detect()?,
+        };
+        Ok(if flag_profile {
+            Setup::Profile(shell)
+        } else {
+            Setup::Print(shell)
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Setup);
+        match self {
+            Setup::Help => Help::Command(CommandName::Setup).run(session)?,
+            Setup::Print(shell) => {
+                print!("{}", shell.env_script()?);
+            }
+            Setup::Profile(shell) => {
+                repair_shims(session)?;
+                if cfg!(windows) {
+                    register_user_path()?;
+                }
+                // On Windows, the PATH in the registry is all cmd.exe needs.
+                if !(cfg!(windows) && shell == ShellKind::Cmd) {
+                    install_profile(&shell)?;
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Setup, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn repair_shims(session: &Session) -> Fallible<()> {
+    let mut created = 0;
+    let mut replaced = 0;
+    for (name, repair) in shim::repair_all(session)? {
+        match repair {
+            Repair::Unchanged => {}
+            Repair::Created => created += 1,
+            Repair::Replaced => replaced += 1,
+            Repair::Missing => {
+                display_warning(&format!(
+                    "the `{}` shim is missing, and only reinstalling Notion can restore it",
+                    name
+                ));
+            }
+        }
+    }
+    if created + replaced > 0 {
+        println!("Created {} and repaired {} shims", created, replaced);
+    }
+    Ok(())
+}
+
+fn register_user_path() -> Fallible<()> {
+    if let PathUpdate::Updated = setup::register_user_path()? {
+        println!("Added the Notion shims to your PATH - open a new terminal to start using Notion");
+    }
+    Ok(())
+}
+
+fn install_profile(shell: &ShellKind) -> Fallible<()> {
+    match shell.install_profile()? {
+        ProfileUpdate::Updated(profile) => {
+            println!(
+                "Set up {} in {} - open a new terminal to start using Notion",
+                shell.name(),
+                profile.display()
+            );
+        }
+        ProfileUpdate::Unchanged(profile) => {
+            println!(
+                "{} already sets up Notion and has not been changed",
+                profile.display()
+            );
+        }
+    }
+    Ok(())
+}
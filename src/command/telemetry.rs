@@ -0,0 +1,128 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::telemetry;
+use notion_fail::{ExitCode, Fallible};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    cmd_on: bool,
+    cmd_off: bool,
+    cmd_status: bool,
+    cmd_flush: bool,
+}
+
+pub(crate) enum Telemetry {
+    Help,
+    On,
+    Off,
+    Status,
+    Flush,
+}
+
+impl Command for Telemetry {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Turn anonymous usage telemetry on or off
+
+Usage:
+    notion telemetry on
+    notion telemetry off
+    notion telemetry status
+    notion telemetry flush
+    notion telemetry -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Telemetry is off unless you turn it on. While it is on, each Notion command and shim
+records its name, the tool versions it fetched, how long it took, and its exit and
+error codes, but never paths, package names, or arguments. The records are batched
+locally and uploaded in the background to the URL of the `endpoint` setting in the
+`[telemetry]` section of the configuration (or NOTION_TELEMETRY_ENDPOINT).
+
+`notion telemetry status` shows the records not uploaded yet, `notion telemetry flush`
+uploads them now, and `notion telemetry off` discards them.
+";
+
+    fn help() -> Self {
+        Telemetry::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            cmd_on,
+            cmd_off,
+            cmd_status,
+            cmd_flush,
+        }: Args,
+    ) -> Fallible<Self> {
+        Ok(if cmd_on {
+            Telemetry::On
+        } else if cmd_off {
+            Telemetry::Off
+        } else if cmd_status {
+            Telemetry::Status
+        } else if cmd_flush {
+            Telemetry::Flush
+        } else {
+            Telemetry::Help
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Telemetry);
+
+        match self {
+            Telemetry::Help => Help::Command(CommandName::Telemetry).run(session)?,
+            Telemetry::On => {
+                telemetry::enable()?;
+                println!("Telemetry is on - thank you!");
+            }
+            Telemetry::Off => {
+                telemetry::disable()?;
+                println!("Telemetry is off");
+            }
+            Telemetry::Status => status(session)?,
+            Telemetry::Flush => {
+                let uploaded = telemetry::flush(session.config()?)?;
+                println!("Uploaded {} records", uploaded);
+            }
+        };
+        session.add_event_end(ActivityKind::Telemetry, ExitCode::Success);
+        Ok(())
+    }
+}
+
+fn status(session: &Session) -> Fallible<()> {
+    let status = telemetry::status(session.config()?)?;
+    println!("Telemetry is {}", if status.enabled { "on" } else { "off" });
+    match status.endpoint {
+        Some(ref endpoint) => println!("Endpoint: {}", endpoint),
+        None => println!("Endpoint: none configured (records are only kept locally)"),
+    }
+    println!("Records not uploaded yet: {}", status.queued.len());
+    for record in &status.queued {
+        let fetched: Vec<String> = record
+            .fetched
+            .iter()
+            .map(|fetch| format!("{}@{}", fetch.tool, fetch.version))
+            .collect();
+        let mut line = format!(
+            "    {} ({} ms, exit code {}",
+            record.command, record.duration, record.exit_code
+        );
+        if let Some(ref error) = record.error {
+            line.push_str(&format!(", {}", error));
+        }
+        line.push(')');
+        if !fetched.is_empty() {
+            line.push_str(&format!(" fetched {}", fetched.join(", ")));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+use semver::Version;
+
+use Notion;
+use command::{split_tool_version, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    arg_version: Option<String>,
+    flag_force: bool,
+    flag_dry_run: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "uninstalling tool '{}' not yet implemented", name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoCustomUninstallError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "expected an exact version to uninstall, e.g. `notion uninstall node@10.15.3`, but got '{}'",
+       version)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct InexactVersionError {
+    pub(crate) version: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "no version of {} specified to uninstall", tool)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NoVersionError {
+    pub(crate) tool: String,
+}
+
+pub(crate) enum Uninstall {
+    Help,
+    Node {
+        version: Version,
+        force: bool,
+        dry_run: bool,
+    },
+    Other { name: String },
+}
+
+impl Command for Uninstall {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Uninstall a tool version from the local machine
+
+Usage:
+    notion uninstall <tool> [<version>] [options]
+    notion uninstall -h | --help
+
+Options:
+    -f, --force    Uninstall even if the version is in use
+    -n, --dry-run  Show the files uninstalling would delete, without deleting them
+    -h, --help     Display this message
+
+The version may also be given as part of the tool, e.g. `notion uninstall node@10.15.3`.
+Uninstalling deletes the version's files and its downloaded archive. Without
+`--force`, Notion refuses to uninstall the user's default version or the version
+pinned by the current project.
+";
+
+    fn help() -> Self {
+        Uninstall::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            arg_version,
+            flag_force,
+            flag_dry_run,
+        }: Args,
+    ) -> Fallible<Self> {
+        let (tool, arg_version) = split_tool_version(&arg_tool, arg_version);
+
+        if tool != "node" {
+            return Ok(Uninstall::Other { name: tool });
+        }
+
+        let version = match arg_version {
+            Some(version) => version,
+            None => {
+                throw!(NoVersionError { tool });
+            }
+        };
+        let parsed = Version::parse(version.trim_left_matches('v'));
+        match parsed {
+            Ok(exact) => Ok(Uninstall::Node {
+                version: exact,
+                force: flag_force,
+                dry_run: flag_dry_run,
+            }),
+            Err(_) => throw!(InexactVersionError { version }),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Uninstall);
+        match self {
+            Uninstall::Help => Help::Command(CommandName::Uninstall).run(session)?,
+            Uninstall::Node {
+                version,
+                force,
+                dry_run: true,
+            } => println!("{}", session.plan_uninstall_node(&version, force)?),
+            Uninstall::Node {
+                version,
+                force,
+                dry_run: false,
+            } => {
+                session.uninstall_node(&version, force)?;
+                println!("Uninstalled Node v{}", version);
+            }
+            Uninstall::Other { name } => throw!(NoCustomUninstallError { name }),
+        };
+        session.add_event_end(ActivityKind::Uninstall, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: Option<String>,
+}
+
+// error message for unpinning tools that are not node|yarn
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "unpinning tool '{}' not yet implemented - for now you can manually edit package.json",
+       name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoCustomUnpinError {
+    pub(crate) name: String,
+}
+
+impl NoCustomUnpinError {
+    pub(crate) fn new(name: String) -> Self {
+        NoCustomUnpinError { name: name }
+    }
+}
+
+pub(crate) enum Unpin {
+    Help,
+    Node,
+    Yarn,
+    Other(String),
+}
+
+impl Command for Unpin {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Remove a tool from the current project's toolchain
+
+Usage:
+    notion unpin [<tool>]
+    notion unpin -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Every other tool in the toolchain depends on the pinned Node version, so
+unpinning Node (the default) removes the whole `toolchain` section of
+package.json.
+";
+
+    fn help() -> Self {
+        Unpin::Help
+    }
+
+    fn parse(_: Notion, Args { arg_tool }: Args) -> Fallible<Self> {
+        match arg_tool {
+            None => Ok(Unpin::Node),
+            Some(ref tool) if tool == "node" => Ok(Unpin::Node),
+            Some(ref tool) if tool == "yarn" => Ok(Unpin::Yarn),
+            Some(tool) => Ok(Unpin::Other(tool)),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Unpin);
+        match self {
+            Unpin::Help => Help::Command(CommandName::Unpin).run(session)?,
+            Unpin::Node => session.unpin_node_version()?,
+            Unpin::Yarn => session.unpin_yarn_version()?,
+            Unpin::Other(name) => throw!(NoCustomUnpinError::new(name)),
+        };
+        session.add_event_end(ActivityKind::Unpin, ExitCode::Success);
+        Ok(())
+    }
+}
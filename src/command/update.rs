@@ -0,0 +1,118 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+    flag_lts: bool,
+    flag_latest: bool,
+    flag_patch: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "updating the pin of '{}' is not yet implemented - use `notion pin` instead",
+       name)]
+#[notion_fail(code = "NotYetImplemented")]
+pub(crate) struct NoCustomUpdateError {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "only one of --lts, --latest, and --patch can be given")]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct ConflictingTargetsError;
+
+/// The version to move a pin to.
+pub(crate) enum Target {
+    /// The newest version of the pinned minor version.
+    Patch,
+    /// The newest long-term support version.
+    Lts,
+    /// The newest version of all.
+    Latest,
+}
+
+pub(crate) enum Update {
+    Help,
+    Node(Target),
+}
+
+impl Command for Update {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Update a tool pinned in the current project's toolchain
+
+Usage:
+    notion update <tool> [options]
+    notion update -h | --help
+
+Options:
+    -h, --help     Display this message
+    --patch        Update to the newest version of the pinned minor version (the default)
+    --lts          Update to the newest long-term support version
+    --latest       Update to the newest version
+
+Resolves the version to update to (see `notion outdated` for the candidates), fetches
+it, and rewrites the pin in the `toolchain` section of package.json, or of the project's
+`.notion.toml` pin file, in one step. Only Node can be updated so far.
+";
+
+    fn help() -> Self {
+        Update::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_tool,
+            flag_lts,
+            flag_latest,
+            flag_patch,
+        }: Args,
+    ) -> Fallible<Self> {
+        let target = match (flag_patch, flag_lts, flag_latest) {
+            (_, false, false) => Target::Patch,
+            (false, true, false) => Target::Lts,
+            (false, false, true) => Target::Latest,
+            _ => throw!(ConflictingTargetsError),
+        };
+
+        match &arg_tool[..] {
+            "node" => Ok(Update::Node(target)),
+            _ => throw!(NoCustomUpdateError { name: arg_tool }),
+        }
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Update);
+        match self {
+            Update::Help => Help::Command(CommandName::Update).run(session)?,
+            Update::Node(target) => {
+                let matching = match target {
+                    Target::Patch => {
+                        let pinned = session
+                            .project()
+                            .and_then(|project| project.manifest().node());
+                        match pinned {
+                            Some(pinned) => VersionSpec::parse(format!("~{}", pinned))?,
+                            None => VersionSpec::Latest,
+                        }
+                    }
+                    Target::Lts => VersionSpec::Lts(None),
+                    Target::Latest => VersionSpec::Latest,
+                };
+                let (pinned, updated) = session.update_node_pin(&matching)?;
+                if pinned == updated {
+                    println!("node is already pinned to {}, the newest matching version", pinned);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::Update, ExitCode::Success);
+        Ok(())
+    }
+}
@@ -3,22 +3,25 @@
 // could consider something like `r#use` instead.
 
 use notion_core::session::{ActivityKind, Session};
+use notion_core::shell::{CurrentShell, Postscript, Shell};
 use notion_core::version::VersionSpec;
 use notion_fail::{ExitCode, Fallible, NotionFail};
 
+use result::ResultOptionExt;
+
 use Notion;
-use command::{Command, CommandName, Help};
+use command::{split_tool_version, Command, CommandName, Help};
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Args {
-    arg_tool: String,
-    arg_version: String,
+    arg_tool: Option<String>,
+    arg_version: Option<String>,
+    flag_clear: bool,
 }
 
 // error message for using tools that are not node|yarn
 #[derive(Debug, Fail, NotionFail)]
-#[fail(display = "pinning tool '{}' not yet implemented - for now you can manually edit package.json",
-       name)]
+#[fail(display = "using tool '{}' in the current shell is not yet implemented", name)]
 #[notion_fail(code = "NotYetImplemented")]
 pub(crate) struct NoCustomUseError {
     pub(crate) name: String,
@@ -34,21 +37,29 @@ pub(crate) enum Use {
     Help,
     Node(VersionSpec),
     Yarn(VersionSpec),
-    Other { name: String, version: VersionSpec },
+    Clear(Vec<String>),
+    Other { name: String },
 }
 
 impl Command for Use {
     type Args = Args;
 
     const USAGE: &'static str = "
-Select a tool for the current project's toolchain
+Use a tool version in the current shell
 
 Usage:
-    notion use <tool> <version>
+    notion use <tool> [<version>]
+    notion use --clear [<tool>]
     notion use -h | --help
 
 Options:
+    --clear        Drop the override, so the shell goes back to the usual version
     -h, --help     Display this message
+
+The version may also be given as part of the tool, e.g. `notion use node@10`.
+The version overrides the project's toolchain and the user's default, but only
+in the current shell and only until it is cleared; neither package.json nor the
+default version are changed. Without a tool, `--clear` drops every override.
 ";
 
     fn help() -> Self {
@@ -60,14 +71,31 @@ Options:
         Args {
             arg_tool,
             arg_version,
+            flag_clear,
         }: Args,
     ) -> Fallible<Self> {
-        match &arg_tool[..] {
-            "node" => Ok(Use::Node(VersionSpec::parse(&arg_version)?)),
-            "yarn" => Ok(Use::Yarn(VersionSpec::parse(&arg_version)?)),
+        if flag_clear {
+            return Ok(Use::Clear(match arg_tool {
+                Some(tool) => vec![tool],
+                None => vec!["node".to_string(), "yarn".to_string()],
+            }));
+        }
+
+        // docopt guarantees the tool is present without `--clear`
+        let arg_tool = arg_tool.unwrap();
+
+        let (tool, arg_version) = split_tool_version(&arg_tool, arg_version);
+
+        let version = arg_version
+            .map(VersionSpec::parse)
+            .invert()?
+            .unwrap_or_default();
+
+        match &tool[..] {
+            "node" => Ok(Use::Node(version)),
+            "yarn" => Ok(Use::Yarn(version)),
             ref tool => Ok(Use::Other {
                 name: tool.to_string(),
-                version: VersionSpec::parse(&arg_version)?,
             }),
         }
     }
@@ -76,12 +104,32 @@ Options:
         session.add_event_start(ActivityKind::Use);
         match self {
             Use::Help => Help::Command(CommandName::Use).run(session)?,
-            Use::Node(spec) => session.pin_node_version(&spec)?,
-            Use::Yarn(spec) => session.pin_yarn_version(&spec)?,
-            Use::Other {
-                name: _name,
-                version: _,
-            } => throw!(NoCustomUseError::new(_name)),
+            Use::Node(spec) => {
+                let version = session.fetch_node(&spec)?.into_version();
+                CurrentShell::detect()?.save_postscript(&Postscript::ToolVersion {
+                    tool: "node".to_string(),
+                    version: version.clone(),
+                })?;
+                println!("Using node {} in this shell", version);
+            }
+            Use::Yarn(spec) => {
+                let version = session.fetch_yarn(&spec)?.into_version();
+                CurrentShell::detect()?.save_postscript(&Postscript::ToolVersion {
+                    tool: "yarn".to_string(),
+                    version: version.clone(),
+                })?;
+                println!("Using yarn {} in this shell", version);
+            }
+            Use::Clear(tools) => {
+                for tool in &tools {
+                    match &tool[..] {
+                        "node" | "yarn" => {}
+                        _ => throw!(NoCustomUseError::new(tool.to_string())),
+                    }
+                }
+                CurrentShell::detect()?.save_postscript(&Postscript::ClearToolVersions { tools })?;
+            }
+            Use::Other { name } => throw!(NoCustomUseError::new(name)),
         };
         session.add_event_end(ActivityKind::Use, ExitCode::Success);
         Ok(())
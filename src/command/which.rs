@@ -0,0 +1,169 @@
+use std::ffi::OsString;
+
+use notion_core::session::{ActivityKind, Session};
+use notion_core::which::{self, Provider, Resolution};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+use output::{emit, Format, Output};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_binary: String,
+    flag_all: bool,
+    flag_json: bool,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "could not find an executable named `{}`", name)]
+#[notion_fail(code = "ExecutableNotFound")]
+pub(crate) struct ExecutableNotFoundError {
+    pub(crate) name: String,
+}
+
+pub(crate) enum Which {
+    Help,
+    Binary(String, Format),
+    All(String, Format),
+}
+
+/// The executable a shim runs, and what provides it.
+#[derive(Serialize)]
+struct Resolved {
+    binary: String,
+    path: String,
+    provider: &'static str,
+    description: String,
+}
+
+impl Resolved {
+    fn new(binary: &str, resolution: Resolution) -> Option<Self> {
+        let path = resolution.path?.to_string_lossy().to_string();
+        Some(Resolved {
+            binary: binary.to_string(),
+            path,
+            provider: provider_kind(&resolution.provider),
+            description: resolution.provider.to_string(),
+        })
+    }
+}
+
+impl Output for Resolved {
+    fn to_human(&self) -> String {
+        format!("{}\n    provided by {}", self.path, self.description)
+    }
+}
+
+/// Every executable a shim could run, in the order it considers them.
+#[derive(Serialize)]
+struct Candidates(Vec<Resolved>);
+
+impl Output for Candidates {
+    fn to_human(&self) -> String {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, resolved)| {
+                format!(
+                    "{}\n    provided by {}{}",
+                    resolved.path,
+                    resolved.description,
+                    if i == 0 { "" } else { " (shadowed)" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The stable name of what provides an executable, for JSON output.
+fn provider_kind(provider: &Provider) -> &'static str {
+    match provider {
+        &Provider::Dependency => "dependency",
+        &Provider::Local => "local",
+        &Provider::Toolchain(..) => "toolchain",
+        &Provider::Package(..) => "package",
+        &Provider::System => "system",
+    }
+}
+
+impl Command for Which {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Show which executable a Notion shim runs in the current directory
+
+Usage:
+    notion which [options] <binary>
+    notion which -h | --help
+
+Options:
+    -h, --help     Display this message
+    --all          List every executable the shim could run, in resolution order
+    --json         Display the executable as JSON
+
+A binary that isn't one of Notion's tools resolves first to the current project's
+node_modules/.bin (as in npm scripts, and run with the project's Node), then to a
+package installed by `notion install`, then to a package installed globally for the
+active Node, and last to the system. With --all, the executables after the first are
+the ones it shadows.
+
+With --json, the output is an object (or with --all, a list of objects) with the
+fields:
+
+    binary       The name of the executable
+    path         The full path to the executable
+    provider     What provides the executable: dependency, local, toolchain,
+                 package, or system
+    description  A description of what provides the executable
+";
+
+    fn help() -> Self {
+        Which::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_binary,
+            flag_all,
+            flag_json,
+        }: Args,
+    ) -> Fallible<Self> {
+        let format = Format::from_json_flag(flag_json);
+        if flag_all {
+            return Ok(Which::All(arg_binary, format));
+        }
+        Ok(Which::Binary(arg_binary, format))
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Which);
+        match self {
+            Which::Help => Help::Command(CommandName::Which).run(session)?,
+            Which::Binary(name, format) => {
+                let resolution = which::resolve(session, &OsString::from(&name))?;
+                let resolved = match Resolved::new(&name, resolution) {
+                    Some(resolved) => resolved,
+                    None => {
+                        throw!(ExecutableNotFoundError { name });
+                    }
+                };
+                emit(&resolved, format)?;
+            }
+            Which::All(name, format) => {
+                let candidates: Vec<Resolved> = which::resolve_all(session, &OsString::from(&name))?
+                    .into_iter()
+                    .filter_map(|resolution| Resolved::new(&name, resolution))
+                    .collect();
+                if candidates.is_empty() {
+                    throw!(ExecutableNotFoundError { name });
+                }
+                emit(&Candidates(candidates), format)?;
+            }
+        };
+        session.add_event_end(ActivityKind::Which, ExitCode::Success);
+        Ok(())
+    }
+}
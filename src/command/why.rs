@@ -0,0 +1,79 @@
+use notion_core::session::{ActivityKind, Session};
+use notion_fail::{ExitCode, Fallible, NotionFail};
+
+use Notion;
+use command::{Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_tool: String,
+}
+
+#[derive(Debug, Fail, NotionFail)]
+#[fail(display = "explaining the selected version of {} is not supported", tool)]
+#[notion_fail(code = "InvalidArguments")]
+pub(crate) struct NoCustomWhyError {
+    pub(crate) tool: String,
+}
+
+pub(crate) enum Why {
+    Help,
+    Node,
+    Other(String),
+}
+
+impl Command for Why {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Explain how the version of a tool was selected for the current directory
+
+Usage:
+    notion why <tool>
+    notion why -h | --help
+
+Supported Tools:
+    Currently Notion only supports explaining the selection of 'node'.
+
+Options:
+    -h, --help     Display this message
+";
+
+    fn help() -> Self {
+        Why::Help
+    }
+
+    fn parse(_: Notion, Args { arg_tool }: Args) -> Fallible<Self> {
+        Ok(match &arg_tool[..] {
+            "node" => Why::Node,
+            _ => Why::Other(arg_tool),
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::Why);
+        match self {
+            Why::Help => Help::Command(CommandName::Why).run(session)?,
+            Why::Node => {
+                let active = session.active_node()?;
+                for (i, step) in session.trace().steps().iter().enumerate() {
+                    println!("{}. {}", i + 1, step);
+                }
+                match active {
+                    Some(active) => println!(
+                        "=> node v{} ({}{})",
+                        active.version,
+                        active.source,
+                        if active.installed { "" } else { ", not installed" }
+                    ),
+                    None => println!("=> node from the system"),
+                }
+            }
+            Why::Other(tool) => {
+                throw!(NoCustomWhyError { tool });
+            }
+        };
+        session.add_event_end(ActivityKind::Why, ExitCode::Success);
+        Ok(())
+    }
+}
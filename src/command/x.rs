@@ -0,0 +1,102 @@
+use std::process;
+
+use notion_core::env::{tool_env, ToolKind};
+use notion_core::session::{ActivityKind, Session};
+use notion_core::version::VersionSpec;
+use notion_fail::{ExitCode, Fallible, ResultExt};
+
+use Notion;
+use command::exec::ExecError;
+use command::{split_tool_version, Command, CommandName, Help};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Args {
+    arg_package: String,
+    arg_args: Vec<String>,
+}
+
+pub(crate) enum X {
+    Help,
+    Package {
+        name: String,
+        version: VersionSpec,
+        args: Vec<String>,
+    },
+}
+
+impl Command for X {
+    type Args = Args;
+
+    const USAGE: &'static str = "
+Run a package from the npm registry without installing it
+
+Usage:
+    notion x <package> [--] [<args> ...]
+    notion x -h | --help
+
+Options:
+    -h, --help     Display this message
+
+Fetches the package (its latest version, unless `<package>@<version>` specifies one)
+into Notion's cache along with its dependencies, and runs its default executable with
+the Node selected for the current directory, like `npx`. The default executable is
+the package's only one, or else the one named after the package, e.g.
+`notion x create-react-app my-app`. Use `--` to separate the executable's own options
+from Notion's.
+";
+
+    fn help() -> Self {
+        X::Help
+    }
+
+    fn parse(
+        _: Notion,
+        Args {
+            arg_package,
+            arg_args,
+        }: Args,
+    ) -> Fallible<Self> {
+        let (name, version) = split_tool_version(&arg_package, None);
+        let version = match version {
+            Some(version) => VersionSpec::parse(&version)?,
+            None => VersionSpec::default(),
+        };
+        Ok(X::Package {
+            name,
+            version,
+            args: arg_args,
+        })
+    }
+
+    fn run(self, session: &mut Session) -> Fallible<()> {
+        session.add_event_start(ActivityKind::X);
+        match self {
+            X::Help => Help::Command(CommandName::X).run(session)?,
+            X::Package {
+                name,
+                version,
+                args,
+            } => {
+                let one_off = session.fetch_one_off(&name, &version)?;
+                let node = one_off.node.to_string();
+                let status = process::Command::new("node")
+                    .arg(&one_off.bin)
+                    .args(&args)
+                    .envs(tool_env(ToolKind::Node, Some(&node), None, None)?.vars())
+                    .status()
+                    .with_context(|error| ExecError {
+                        command: name.clone(),
+                        error: error.to_string(),
+                    })?;
+                if !status.success() {
+                    // ISSUE (#36): if None, in unix, find out the signal
+                    let code = status.code().unwrap_or(1);
+                    session.add_event_tool_end(ActivityKind::X, code);
+                    session.exit_tool(code);
+                }
+            }
+        };
+        session.add_event_end(ActivityKind::X, ExitCode::Success);
+        Ok(())
+    }
+}
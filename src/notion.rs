@@ -10,23 +10,33 @@ extern crate notion_fail;
 extern crate notion_fail_derive;
 extern crate semver;
 extern crate serde;
+extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 extern crate result;
 
 mod command;
 mod error;
+mod output;
 
+use std::path::Path;
 use std::string::ToString;
 
 use docopt::Docopt;
 
+use notion_core::env;
+use notion_core::log::{self, Level};
 use notion_core::session::{ActivityKind, Session};
-use notion_core::style::{display_error, display_unknown_error, ErrorContext};
+use notion_core::style::{self, display_error, display_error_details, display_hint,
+                         display_suggestion, display_unknown_error, ErrorContext,
+                         ProgressMode};
+use notion_core::update;
 use notion_fail::{ExitCode, FailExt, Fallible, NotionError};
 
-use command::{Command, CommandName, Config, Current, Deactivate, Fetch, Help, Install, Shim, Use,
-              Version};
+use command::{Activate, Command, CommandName, Completions, Config, Current, Deactivate, Default,
+              Doctor, Env, Exec, Export, Fetch, Gc, Help, Hook, Import, Info, Install, List,
+              LsRemote, Outdated, Pin, Prompt, Query, Run, SelfUpdate, Setup, Shim, Telemetry,
+              Uninstall, Unpin, Update, Use, Version, Which, Why, X};
 use error::{CliParseError, CommandUnimplementedError, DocoptExt, NotionErrorExt};
 
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -36,13 +46,29 @@ struct Args {
     arg_command: Option<CommandName>,
     arg_args: Vec<String>,
     flag_version: bool,
-    flag_verbose: bool,
+    flag_verbose: usize,
+    flag_quiet: bool,
+    flag_offline: bool,
+    flag_no_cache: bool,
+    flag_stream: bool,
+    flag_no_color: bool,
+    flag_events_file: Option<String>,
+    flag_cwd: Option<String>,
+    flag_profile: bool,
 }
 
 pub(crate) struct Notion {
     command: CommandName,
     args: Vec<String>,
-    verbose: bool,
+    verbose: usize,
+    quiet: bool,
+    offline: bool,
+    no_cache: bool,
+    stream: bool,
+    no_color: bool,
+    events_file: Option<String>,
+    cwd: Option<String>,
+    profile: bool,
 }
 
 impl Notion {
@@ -50,35 +76,70 @@ impl Notion {
 Notion: the hassle-free Node.js manager
 
 Usage:
-    notion [-v | --verbose] [<command> <args> ...]
+    notion [-v...] [-q | --quiet] [--offline] [--no-cache] [--stream] [--no-color] [--events-file <path>] [--cwd <dir>] [--profile] [<command> <args> ...]
     notion -h | --help
     notion -V | --version
 
 Options:
     -h, --help     Display this message
     -V, --version  Print version info and exit
-    -v, --verbose  Use verbose output
+    -v, --verbose  Log what Notion does (-vv to log every step, or set NOTION_LOG
+                   to info or debug), and report errors with their causes and
+                   backtrace (or set NOTION_BACKTRACE)
+    -q, --quiet    Display no progress (or set NOTION_QUIET)
+    --offline      Only use the local cache and catalog (or set NOTION_OFFLINE)
+    --no-cache     Consult resolve plugins afresh (or set NOTION_NO_CACHE)
+    --stream       Unpack downloads as they stream in, without caching their
+                   archives (or set NOTION_STREAM)
+    --no-color     Display no colors (or set NO_COLOR)
+    --events-file <path>
+                   Append what Notion does to a file as JSON events, one per line
+                   (or set NOTION_EVENTS_FILE)
+    --cwd <dir>    Select tool versions for a directory other than the current one,
+                   as if Notion were run there
+    --profile      Print how long each phase of the command took when it exits
+                   (or set NOTION_PROFILE, to profile shims as well)
 
 Some common notion commands are:
     fetch          Fetch a tool to the local machine
     install        Install a tool in the user toolchain
-    use            Select a tool for the current project's toolchain
+    import         Import Node versions from nvm, n, or asdf, or a `notion export`
+    export         Export the installed tools and defaults, to import elsewhere
+    default        Get or set the user's default version of a tool
+    uninstall      Uninstall a tool version from the local machine
+    gc             Uninstall the tool versions that nothing uses
+    list           List the installed versions of tools
+    ls-remote      List the Node or Yarn versions available for download
+    info           Show what is known about a release of Node or Yarn
+    outdated       Compare the project's pinned tools with the newest versions
+    pin            Pin a tool in the current project's toolchain
+    unpin          Remove a tool from the current project's toolchain
+    update         Update a tool pinned in the current project's toolchain
+    use            Use a tool version in the current shell
     config         Get or set configuration values
-    current        Display the currently activated Node version
+    current        Display the currently activated toolchain
+    prompt         Print the active Node version, for a shell prompt
+    query          Answer queries from editors over stdio
+    activate       Re-enable Notion in the current shell
     deactivate     Remove Notion from the current shell
+    setup          Set up a shell to use Notion
+    hook           Switch Node versions automatically when changing directories
+    completions    Generate shell completions for Notion
+    exec           Run a command with the current toolchain
+    run            Run a script from the project's package.json
+    x              Run a package from the npm registry without installing it
     shim           View and manage shims
+    which          Show which executable a shim runs
+    why            Explain how a tool version was selected
+    doctor         Diagnose problems with Notion and the current environment
+    self-update    Update Notion to its latest release
+    telemetry      Turn anonymous usage telemetry on or off
     help           Display this message
     version        Print version info and exit
 
 See 'notion help <command>' for more information on a specific command.
 ";
 
-    // This isn't used yet but we can use it for verbose mode in the future.
-    #[allow(dead_code)]
-    pub(crate) fn verbose(&self) -> bool {
-        self.verbose
-    }
-
     pub(crate) fn full_argv(&self) -> Vec<String> {
         let mut argv = vec![String::from("notion"), self.command.to_string()];
         let mut sub_argv = self.args.clone();
@@ -86,15 +147,20 @@ See 'notion help <command>' for more information on a specific command.
         argv
     }
 
-    fn go(session: &mut Session) -> Fallible<()> {
-        Self::parse()?.run(session)
+    fn parse() -> Fallible<Notion> {
+        Notion::parse_from(std::env::args())
     }
 
-    fn parse() -> Fallible<Notion> {
+    fn parse_from<I, S>(argv: I) -> Fallible<Notion>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
         let mut command_string: Option<String> = None;
 
         let args: Result<Args, docopt::Error> = Docopt::new(Notion::USAGE).and_then(|d| {
             d.options_first(true)
+                .argv(argv)
                 .version(Some(String::from(VERSION)))
                 .parse()
                 .and_then(|vals| {
@@ -116,18 +182,42 @@ See 'notion help <command>' for more information on a specific command.
             }) => Notion {
                 command: CommandName::Help,
                 args: vec![],
-                verbose: false,
+                verbose: 0,
+                quiet: false,
+                offline: false,
+                no_cache: false,
+                stream: false,
+                no_color: false,
+                events_file: None,
+                cwd: None,
+                profile: false,
             },
 
             Ok(Args {
                 arg_command: Some(cmd),
                 arg_args,
                 flag_verbose,
+                flag_quiet,
+                flag_offline,
+                flag_no_cache,
+                flag_stream,
+                flag_no_color,
+                flag_events_file,
+                flag_cwd,
+                flag_profile,
                 ..
             }) => Notion {
                 command: cmd,
                 args: arg_args,
                 verbose: flag_verbose,
+                quiet: flag_quiet,
+                offline: flag_offline,
+                no_cache: flag_no_cache,
+                stream: flag_stream,
+                no_color: flag_no_color,
+                events_file: flag_events_file,
+                cwd: flag_cwd,
+                profile: flag_profile,
             },
 
             Err(err) => {
@@ -137,7 +227,15 @@ See 'notion help <command>' for more information on a specific command.
                     Notion {
                         command: CommandName::Help,
                         args: vec![],
-                        verbose: false,
+                        verbose: 0,
+                        quiet: false,
+                        offline: false,
+                        no_cache: false,
+                        stream: false,
+                        no_color: false,
+                        events_file: None,
+                        cwd: None,
+                        profile: false,
                     }
                 }
                 // Docopt models `-V` and `--version` as errors, so this
@@ -146,7 +244,15 @@ See 'notion help <command>' for more information on a specific command.
                     Notion {
                         command: CommandName::Version,
                         args: vec![],
-                        verbose: false,
+                        verbose: 0,
+                        quiet: false,
+                        offline: false,
+                        no_cache: false,
+                        stream: false,
+                        no_color: false,
+                        events_file: None,
+                        cwd: None,
+                        profile: false,
                     }
                 }
                 // The only type that gets deserialized is CommandName. If
@@ -171,27 +277,121 @@ See 'notion help <command>' for more information on a specific command.
         })
     }
 
+    /// Applies the global options, which the session is constructed under, so that it
+    /// loads the configuration, finds the project, and records its first events with them.
+    fn apply_options(&self) {
+        if let Some(level) = Level::from_verbosity(self.verbose) {
+            log::set_level(level);
+            env::capture_backtraces();
+        }
+        if self.quiet {
+            env::enable_quiet();
+        }
+        if self.offline {
+            env::enable_offline();
+        }
+        if self.no_cache {
+            env::disable_cache();
+        }
+        if self.stream {
+            env::enable_stream();
+        }
+        if self.no_color {
+            env::disable_color();
+        }
+        if let Some(ref events_file) = self.events_file {
+            env::set_events_file(Path::new(events_file));
+        }
+        if self.profile {
+            env::enable_profile();
+        }
+    }
+
     fn run(self, session: &mut Session) -> Fallible<()> {
-        match self.command {
+        let hint_update = match self.command {
+            CommandName::SelfUpdate => false,
+            _ => true,
+        };
+
+        let result = match self.command {
             CommandName::Fetch => Fetch::go(self, session),
             CommandName::Install => Install::go(self, session),
+            CommandName::Import => Import::go(self, session),
+            CommandName::Export => Export::go(self, session),
+            CommandName::Default => Default::go(self, session),
+            CommandName::Uninstall => Uninstall::go(self, session),
+            CommandName::Gc => Gc::go(self, session),
+            CommandName::List => List::go(self, session),
+            CommandName::LsRemote => LsRemote::go(self, session),
+            CommandName::Info => Info::go(self, session),
+            CommandName::Outdated => Outdated::go(self, session),
+            CommandName::Pin => Pin::go(self, session),
+            CommandName::Unpin => Unpin::go(self, session),
+            CommandName::Update => Update::go(self, session),
             CommandName::Use => Use::go(self, session),
             CommandName::Config => Config::go(self, session),
             CommandName::Current => Current::go(self, session),
+            CommandName::Prompt => Prompt::go(self, session),
+            CommandName::Query => Query::go(self, session),
+            CommandName::Activate => Activate::go(self, session),
             CommandName::Deactivate => Deactivate::go(self, session),
+            CommandName::Setup => Setup::go(self, session),
+            CommandName::Hook => Hook::go(self, session),
+            CommandName::Env => Env::go(self, session),
+            CommandName::Completions => Completions::go(self, session),
+            CommandName::Exec => Exec::go(self, session),
+            CommandName::Run => Run::go(self, session),
+            CommandName::X => X::go(self, session),
             CommandName::Shim => Shim::go(self, session),
+            CommandName::Which => Which::go(self, session),
+            CommandName::Why => Why::go(self, session),
+            CommandName::Doctor => Doctor::go(self, session),
+            CommandName::SelfUpdate => SelfUpdate::go(self, session),
+            CommandName::Telemetry => Telemetry::go(self, session),
             CommandName::Help => Help::go(self, session),
             CommandName::Version => Version::go(self, session),
+        };
+
+        if result.is_ok() && hint_update {
+            display_update_hint(session);
+        }
+        result
+    }
+}
+
+/// Displays a hint after a successful command if a newer release of Notion is available.
+/// It goes to stderr, and only to a terminal, so that it never ends up in the output of
+/// a script.
+fn display_update_hint(session: &Session) {
+    if style::progress_mode() != ProgressMode::Animated {
+        return;
+    }
+    let current = match semver::Version::parse(VERSION) {
+        Ok(current) => current,
+        Err(_) => return,
+    };
+    if let Ok(config) = session.config() {
+        if let Some(latest) = update::notify(&current, config) {
+            display_hint(&format!(
+                "Notion {} is available (this is {}) - run `notion self-update` to update",
+                latest, current
+            ));
         }
     }
 }
 
 fn display_error_and_usage(err: &NotionError) {
+    if output::emit_error(err) {
+        return;
+    }
+
     if err.is_user_friendly() {
         display_error(ErrorContext::Notion, err);
+        display_suggestion(err);
     } else {
         display_unknown_error(ErrorContext::Notion, err);
     }
+    display_error_details(err);
 
     if let Some(ref usage) = err.usage() {
         eprintln!();
@@ -199,9 +399,40 @@ fn display_error_and_usage(err: &NotionError) {
     }
 }
 
+/// Constructs the session for a command line, under its global options, and records
+/// that the command started.
+fn start_session(notion: &Fallible<Notion>) -> Fallible<Session> {
+    let cwd = match *notion {
+        Ok(ref notion) => {
+            notion.apply_options();
+            notion.cwd.clone()
+        }
+        Err(_) => None,
+    };
+
+    let mut session = match cwd {
+        Some(dir) => {
+            let session = Session::for_dir(Path::new(&dir))?;
+            session.warn_if_shadowed();
+            session
+        }
+        None => Session::new()?,
+    };
+    session.add_event_start(ActivityKind::Notion);
+    Ok(session)
+}
+
 /// The entry point for the `notion` CLI.
 pub fn main() {
-    let mut session = match Session::new() {
+    if Prompt::print_cached() {
+        return;
+    }
+
+    // The command line is parsed before the session is constructed, since the session
+    // is for the directory of `--cwd`, and the global options apply to constructing it.
+    // A command line that can't be parsed fails once the session can record it.
+    let notion = Notion::parse();
+    let mut session = match start_session(&notion) {
         Ok(session) => session,
         Err(err) => {
             display_error_and_usage(&err);
@@ -209,9 +440,7 @@ pub fn main() {
         }
     };
 
-    session.add_event_start(ActivityKind::Notion);
-
-    let exit_code = match Notion::go(&mut session) {
+    let exit_code = match notion.and_then(|notion| notion.run(&mut session)) {
         Ok(_) => ExitCode::Success,
         Err(err) => {
             display_error_and_usage(&err);
@@ -222,3 +451,33 @@ pub fn main() {
     session.add_event_end(ActivityKind::Notion, exit_code);
     session.exit(exit_code);
 }
+
+#[cfg(test)]
+pub mod tests {
+
+    use serde_json::{self, Value};
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::{start_session, Notion};
+
+    #[test]
+    fn test_events_file_records_start() {
+        let file = env::temp_dir().join(format!("notion-events-{}.jsonl", process::id()));
+        let path = file.to_string_lossy().to_string();
+        let notion = Notion::parse_from(vec!["notion", "--events-file", &path[..], "current"]);
+
+        // the events file applies to the session from the start, so the start of the
+        // command is recorded in it
+        start_session(&notion).expect("Could not start session");
+        env::remove_var("NOTION_EVENTS_FILE");
+
+        let src = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert!(src.lines().any(|line| {
+            let event: Value = serde_json::from_str(line).unwrap();
+            event["name"] == "notion" && event["event"] == "start"
+        }));
+    }
+}
@@ -0,0 +1,8 @@
+extern crate notion_core;
+
+use notion_core::tool::{Npm, Tool};
+
+/// The entry point for the `npm` and `npx` shims.
+pub fn main() {
+    Npm::launch()
+}
@@ -0,0 +1,111 @@
+//! Renders the results of Notion's read commands, either for people or as JSON for
+//! editors and scripts.
+
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+use serde::Serialize;
+use serde_json;
+
+use notion_core::session::VersionSource;
+use notion_fail::{Fallible, NotionError, ResultExt};
+
+/// Whether the command was asked for JSON, so that a failure is reported as JSON too.
+static JSON_REQUESTED: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// How a read command renders its results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// Tables and lines of text, for people.
+    Human,
+    /// A single JSON document, whose structure the command's usage documents.
+    Json,
+}
+
+impl Format {
+    /// Selects the format requested by a command's `--json` flag.
+    pub(crate) fn from_json_flag(json: bool) -> Self {
+        if json {
+            JSON_REQUESTED.store(true, Ordering::SeqCst);
+            Format::Json
+        } else {
+            Format::Human
+        }
+    }
+}
+
+/// The results of a read command. The JSON rendering is the serialization of the
+/// results, so the fields of implementing types are part of Notion's interface and
+/// must only change compatibly.
+pub(crate) trait Output: Serialize {
+    /// Renders the results for people.
+    fn to_human(&self) -> String;
+}
+
+/// Prints the results of a read command in the specified format.
+pub(crate) fn emit<T: Output>(output: &T, format: Format) -> Fallible<()> {
+    match format {
+        Format::Human => {
+            let text = output.to_human();
+            if !text.is_empty() {
+                println!("{}", text);
+            }
+        }
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(output).unknown()?);
+        }
+    }
+    Ok(())
+}
+
+/// A failure, as reported to a command that was asked for JSON, like
+/// `{"error": {"code": "E_VERSION_NOT_FOUND", "message": "...", "exit": 4}}`. The code comes from the registry in `notion_fail::codes`, which never changes the
+/// meaning of a released code, so wrappers and editor integrations can branch on it.
+#[derive(Serialize)]
+struct ErrorOutput<'a> {
+    error: ErrorDetails<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetails<'a> {
+    code: &'static str,
+    message: String,
+    exit: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<&'a str>,
+}
+
+/// Prints a failure as JSON, if the command was asked for JSON output. Returns whether
+/// it was printed.
+pub(crate) fn emit_error(err: &NotionError) -> bool {
+    if !JSON_REQUESTED.load(Ordering::SeqCst) {
+        return false;
+    }
+    let output = ErrorOutput {
+        error: ErrorDetails {
+            code: err.error_code(),
+            message: err.to_string(),
+            exit: err.exit_code() as i32,
+            suggestion: err.suggestion(),
+        },
+    };
+    match serde_json::to_string_pretty(&output) {
+        Ok(json) => {
+            println!("{}", json);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// The stable name of where a version comes from, for JSON output. (The `Display`
+/// implementation of `VersionSource` is meant for people and may change.)
+pub(crate) fn source_kind(source: &VersionSource) -> &'static str {
+    match source {
+        &VersionSource::Project(_) => "project",
+        &VersionSource::PackageManager => "package-manager",
+        &VersionSource::NodeVersionFile(_) => "node-version-file",
+        &VersionSource::Engines => "engines",
+        &VersionSource::Override => "override",
+        &VersionSource::User => "user",
+    }
+}
@@ -0,0 +1,8 @@
+extern crate notion_core;
+
+use notion_core::tool::{Pnpm, Tool};
+
+/// The entry point for the `pnpm` and `pnpx` shims.
+pub fn main() {
+    Pnpm::launch()
+}